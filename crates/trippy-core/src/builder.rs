@@ -1,13 +1,14 @@
 use crate::config::{ChannelConfig, StateConfig, StrategyConfig};
-use crate::constants::MAX_INITIAL_SEQUENCE;
+use crate::constants::{MAX_INITIAL_SEQUENCE, MAX_SEQUENCE_PER_ROUND};
 use crate::error::Result;
 use crate::{
-    Error, IcmpExtensionParseMode, MaxInflight, MaxRounds, MultipathStrategy, PacketSize,
-    PayloadPattern, PortDirection, PrivilegeMode, Protocol, Sequence, TimeToLive, TraceId, Tracer,
-    TypeOfService, MAX_TTL,
+    Error, FlowLabelStrategy, IcmpExtensionParseMode, Ipv6ExtensionHeader, MaxInflight, MaxRounds,
+    MultipathStrategy, Nat64Prefix, PacketSize, PayloadPattern, PortDirection, PrivilegeMode,
+    Protocol, Sequence, TimeToLive, TraceId, Tracer, TypeOfService, MAX_FLOW_LABEL, MAX_TTL,
 };
 use std::net::IpAddr;
 use std::num::NonZeroUsize;
+use std::path::PathBuf;
 use std::time::Duration;
 
 /// Build a tracer.
@@ -36,6 +37,7 @@ use std::time::Duration;
 ///
 /// - [`Tracer`] - A traceroute implementation.
 #[derive(Debug)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct Builder {
     interface: Option<String>,
     source_addr: Option<IpAddr>,
@@ -43,11 +45,15 @@ pub struct Builder {
     privilege_mode: PrivilegeMode,
     protocol: Protocol,
     packet_size: PacketSize,
+    max_packet_size: PacketSize,
     payload_pattern: PayloadPattern,
     tos: TypeOfService,
+    dont_fragment: bool,
     icmp_extension_parse_mode: IcmpExtensionParseMode,
+    ipv6_extension_header: Ipv6ExtensionHeader,
     read_timeout: Duration,
     tcp_connect_timeout: Duration,
+    tcp_mss: Option<u16>,
     trace_identifier: TraceId,
     max_rounds: Option<MaxRounds>,
     first_ttl: TimeToLive,
@@ -55,13 +61,21 @@ pub struct Builder {
     grace_duration: Duration,
     max_inflight: MaxInflight,
     initial_sequence: Sequence,
+    max_sequence: Sequence,
     multipath_strategy: MultipathStrategy,
     port_direction: PortDirection,
+    flow_label_strategy: FlowLabelStrategy,
+    flows_per_round: u8,
+    nat64_prefixes: Vec<Nat64Prefix>,
     min_round_duration: Duration,
     max_round_duration: Duration,
     max_samples: usize,
     max_flows: usize,
     drop_privileges: bool,
+    unprivileged_fallback: bool,
+    udp_recv_err: bool,
+    validate_icmp_checksum: bool,
+    capture_file: Option<PathBuf>,
 }
 
 impl Default for Builder {
@@ -73,11 +87,15 @@ impl Default for Builder {
             privilege_mode: ChannelConfig::default().privilege_mode,
             protocol: ChannelConfig::default().protocol,
             packet_size: ChannelConfig::default().packet_size,
+            max_packet_size: ChannelConfig::default().max_packet_size,
             payload_pattern: ChannelConfig::default().payload_pattern,
             tos: ChannelConfig::default().tos,
+            dont_fragment: ChannelConfig::default().dont_fragment,
             icmp_extension_parse_mode: ChannelConfig::default().icmp_extension_parse_mode,
+            ipv6_extension_header: ChannelConfig::default().ipv6_extension_header,
             read_timeout: ChannelConfig::default().read_timeout,
             tcp_connect_timeout: ChannelConfig::default().tcp_connect_timeout,
+            tcp_mss: ChannelConfig::default().tcp_mss,
             trace_identifier: StrategyConfig::default().trace_identifier,
             max_rounds: StrategyConfig::default().max_rounds,
             first_ttl: StrategyConfig::default().first_ttl,
@@ -85,13 +103,21 @@ impl Default for Builder {
             grace_duration: StrategyConfig::default().grace_duration,
             max_inflight: StrategyConfig::default().max_inflight,
             initial_sequence: StrategyConfig::default().initial_sequence,
+            max_sequence: StrategyConfig::default().max_sequence,
             multipath_strategy: StrategyConfig::default().multipath_strategy,
             port_direction: StrategyConfig::default().port_direction,
+            flow_label_strategy: StrategyConfig::default().flow_label_strategy,
+            flows_per_round: StrategyConfig::default().flows_per_round,
+            nat64_prefixes: StrategyConfig::default().nat64_prefixes,
             min_round_duration: StrategyConfig::default().min_round_duration,
             max_round_duration: StrategyConfig::default().max_round_duration,
             max_samples: StateConfig::default().max_samples,
             max_flows: StateConfig::default().max_flows,
             drop_privileges: false,
+            unprivileged_fallback: ChannelConfig::default().unprivileged_fallback,
+            udp_recv_err: ChannelConfig::default().udp_recv_err,
+            validate_icmp_checksum: ChannelConfig::default().validate_icmp_checksum,
+            capture_file: None,
         }
     }
 }
@@ -149,7 +175,9 @@ impl Builder {
     /// Set the source interface.
     ///
     /// If the source interface is provided it will be used to look up the IPv4
-    /// or IPv6 source address.
+    /// or IPv6 source address.  The interface may be given by name or by index
+    /// and probes are sent only via this interface.  This is supported on Linux
+    /// and macOS only, on other platforms the tracer fails to start.
     ///
     /// If not provided the source address will be determined by OS based on
     /// the target IPv4 or IPv6 address.
@@ -285,7 +313,36 @@ impl Builder {
         }
     }
 
-    /// Set the payload pattern.
+    /// Set the maximum packet size.
+    ///
+    /// This is the largest packet size which may be used and determines the size of the buffers
+    /// allocated for sending and receiving packets.  Raising it above the default allows probing
+    /// with jumbo frames.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use std::net::IpAddr;
+    /// use trippy_core::Builder;
+    ///
+    /// let addr = IpAddr::from([1, 1, 1, 1]);
+    /// let tracer = Builder::new(addr)
+    ///     .max_packet_size(9000)
+    ///     .packet_size(9000)
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn max_packet_size(self, max_packet_size: u16) -> Self {
+        Self {
+            max_packet_size: PacketSize(max_packet_size),
+            ..self
+        }
+    }
+
+    /// Set the payload pattern to a single repeated byte.
     ///
     /// # Examples
     ///
@@ -302,7 +359,34 @@ impl Builder {
     #[must_use]
     pub fn payload_pattern(self, payload_pattern: u8) -> Self {
         Self {
-            payload_pattern: PayloadPattern(payload_pattern),
+            payload_pattern: PayloadPattern::byte(payload_pattern),
+            ..self
+        }
+    }
+
+    /// Set the payload pattern to a sequence of bytes.
+    ///
+    /// The sequence is repeated to fill the payload and truncated if the payload is not a multiple
+    /// of its length.  The sequence may not be empty.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use std::net::IpAddr;
+    /// use trippy_core::Builder;
+    ///
+    /// let addr = IpAddr::from([1, 1, 1, 1]);
+    /// let tracer = Builder::new(addr)
+    ///     .payload_pattern_bytes([0xde, 0xad, 0xbe, 0xef])
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn payload_pattern_bytes(self, payload_pattern: impl Into<Vec<u8>>) -> Self {
+        Self {
+            payload_pattern: PayloadPattern(payload_pattern.into()),
             ..self
         }
     }
@@ -329,6 +413,35 @@ impl Builder {
         }
     }
 
+    /// Set the `Don't Fragment` bit on IPv4 probes.
+    ///
+    /// Probes which exceed the MTU of a link are then dropped, and an `ICMP` `Fragmentation
+    /// Needed` error returned, rather than being fragmented.  This applies to the `ICMP`, `UDP`
+    /// and `TCP` protocols and has no effect for IPv6, which never fragments in transit.
+    ///
+    /// Note that the `Don't Fragment` bit is always set when the IPv4 header is built by the
+    /// tracer, such as for `ICMP` probes and privileged `UDP` probes.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use std::net::IpAddr;
+    /// use trippy_core::Builder;
+    ///
+    /// let addr = IpAddr::from([1, 1, 1, 1]);
+    /// let tracer = Builder::new(addr).dont_fragment(true).build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn dont_fragment(self, dont_fragment: bool) -> Self {
+        Self {
+            dont_fragment,
+            ..self
+        }
+    }
+
     /// Set the ICMP extensions mode.
     ///
     /// # Examples
@@ -356,6 +469,32 @@ impl Builder {
         }
     }
 
+    /// Set the IPv6 extension header to insert into every probe.
+    ///
+    /// This may only be used for IPv6 targets and is not supported for the TCP protocol.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use std::net::IpAddr;
+    /// use trippy_core::{Builder, Ipv6ExtensionHeader};
+    ///
+    /// let addr = IpAddr::from([0x2606, 0x4700, 0x4700, 0, 0, 0, 0, 0x1111]);
+    /// let tracer = Builder::new(addr)
+    ///     .ipv6_extension_header(Ipv6ExtensionHeader::HopByHop)
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn ipv6_extension_header(self, ipv6_extension_header: Ipv6ExtensionHeader) -> Self {
+        Self {
+            ipv6_extension_header,
+            ..self
+        }
+    }
+
     /// Set the read timeout.
     ///
     /// # Examples
@@ -406,6 +545,35 @@ impl Builder {
         }
     }
 
+    /// Set the TCP maximum segment size (MSS).
+    ///
+    /// This sets the MSS option of the `SYN` segment of `TCP` probes, the OS default is used if
+    /// not set.  This is only valid for the `TCP` protocol.
+    ///
+    /// Note that the `window scale` and `SACK permitted` options of the `SYN` segment are added by
+    /// the OS, as configured for the host, and cannot be set per probe.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use std::net::IpAddr;
+    /// use trippy_core::{Builder, Port, PortDirection, Protocol};
+    ///
+    /// let addr = IpAddr::from([1, 1, 1, 1]);
+    /// let tracer = Builder::new(addr)
+    ///     .protocol(Protocol::Tcp)
+    ///     .port_direction(PortDirection::FixedDest(Port(443)))
+    ///     .tcp_mss(Some(1200))
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn tcp_mss(self, tcp_mss: Option<u16>) -> Self {
+        Self { tcp_mss, ..self }
+    }
+
     /// Set the maximum number of rounds.
     ///
     /// If set to `None` then the tracer will run indefinitely, otherwise it
@@ -545,6 +713,40 @@ impl Builder {
         }
     }
 
+    /// Set the maximum sequence number.
+    ///
+    /// For the UDP and TCP protocols the sequence number is stored in the src or dest port and so
+    /// the `initial_sequence` and `max_sequence` bound the range of ports which are used.  The
+    /// sequence number wraps back to the `initial_sequence` at the start of a round if there are
+    /// insufficient sequence numbers remaining for a complete round.
+    ///
+    /// Responses are only matched to probes issued in the current round, and so a delayed
+    /// response to a probe sent before the sequence wrapped is never attributed to a later probe
+    /// with the same sequence number.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use std::net::IpAddr;
+    /// use trippy_core::Builder;
+    ///
+    /// let addr = IpAddr::from([1, 1, 1, 1]);
+    /// let tracer = Builder::new(addr)
+    ///     .initial_sequence(35000)
+    ///     .max_sequence(36999)
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn max_sequence(self, max_sequence: u16) -> Self {
+        Self {
+            max_sequence: Sequence(max_sequence),
+            ..self
+        }
+    }
+
     /// Set the port direction.
     ///
     /// # Examples
@@ -569,6 +771,99 @@ impl Builder {
         }
     }
 
+    /// Set the IPv6 flow label strategy.
+    ///
+    /// This allows the flow label to be varied between rounds such that the different
+    /// [Equal-cost Multi-Path](https://en.wikipedia.org/wiki/Equal-cost_multi-path_routing) paths
+    /// to the target can be discovered, or to be fixed such that a single path is pinned.
+    ///
+    /// This may only be used for IPv6 targets.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use std::net::IpAddr;
+    /// use trippy_core::{Builder, FlowLabelStrategy};
+    ///
+    /// let addr = IpAddr::from([0x2606, 0x4700, 0x4700, 0, 0, 0, 0, 0x1111]);
+    /// let tracer = Builder::new(addr)
+    ///     .flow_label_strategy(FlowLabelStrategy::new_range(1, 16))
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn flow_label_strategy(self, flow_label_strategy: FlowLabelStrategy) -> Self {
+        Self {
+            flow_label_strategy,
+            ..self
+        }
+    }
+
+    /// Set the number of flows to probe per round.
+    ///
+    /// Each time-to-live is probed once per flow in every round, with each flow using a distinct
+    /// varying port (`paris` and `dublin` UDP multipath strategies) or IPv6 flow label (`range`
+    /// flow label strategy) such that the equal-cost paths to the target may be discovered within
+    /// a single round.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use std::net::IpAddr;
+    /// use trippy_core::{Builder, MultipathStrategy, PortDirection, Protocol};
+    ///
+    /// let addr = IpAddr::from([1, 1, 1, 1]);
+    /// let tracer = Builder::new(addr)
+    ///     .protocol(Protocol::Udp)
+    ///     .multipath_strategy(MultipathStrategy::Paris)
+    ///     .port_direction(PortDirection::new_fixed_src(5000))
+    ///     .flows_per_round(4)
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn flows_per_round(self, flows_per_round: u8) -> Self {
+        Self {
+            flows_per_round,
+            ..self
+        }
+    }
+
+    /// Set the NAT64 translation prefixes.
+    ///
+    /// Hops with an address within any of these prefixes are considered to be behind a NAT64
+    /// translator and probe responses which quote a translated form of the target address are
+    /// matched against the original IPv4 address embedded within it.
+    ///
+    /// The default is the well-known prefix `64:ff9b::/96` only.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use std::net::{IpAddr, Ipv6Addr};
+    /// use trippy_core::{Builder, Nat64Prefix};
+    ///
+    /// let addr = IpAddr::from([0x64, 0xff9b, 0, 0, 0, 0, 0xc633, 0x6401]);
+    /// let prefix = Nat64Prefix::new(Ipv6Addr::from([0x2001, 0xdb8, 0x64, 0, 0, 0, 0, 0]));
+    /// let tracer = Builder::new(addr)
+    ///     .nat64_prefixes([Nat64Prefix::WELL_KNOWN, prefix])
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn nat64_prefixes(self, nat64_prefixes: impl IntoIterator<Item = Nat64Prefix>) -> Self {
+        Self {
+            nat64_prefixes: nat64_prefixes.into_iter().collect(),
+            ..self
+        }
+    }
+
     /// Set the minimum round duration.
     ///
     /// # Examples
@@ -682,6 +977,121 @@ impl Builder {
         }
     }
 
+    /// Fall back to unprivileged mode if privileged `ICMP` sockets cannot be created.
+    ///
+    /// If enabled, an `ICMP` trace in privileged mode which fails to create a raw socket due to
+    /// insufficient permissions is retried in unprivileged mode, using datagram `ICMP` sockets.
+    ///
+    /// This is only supported on `macOS`, as other platforms do not allow the `IP_HDRINCL` socket
+    /// option to be set on datagram `ICMP` sockets, and `build` will fail if it is enabled on any
+    /// other platform.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use std::net::IpAddr;
+    /// use trippy_core::Builder;
+    ///
+    /// let addr = IpAddr::from([1, 1, 1, 1]);
+    /// let tracer = Builder::new(addr).unprivileged_fallback(true).build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn unprivileged_fallback(self, unprivileged_fallback: bool) -> Self {
+        Self {
+            unprivileged_fallback,
+            ..self
+        }
+    }
+
+    /// Receive the ICMP errors of `UDP` probes from the socket error queue.
+    ///
+    /// If enabled, `UDP` probes are sent from ordinary datagram sockets with `IP_RECVERR` set and
+    /// the ICMP errors they cause are read from the error queue of each socket, such that no raw
+    /// or `ICMP` socket is needed.  This may only be used for the `UDP` protocol in unprivileged
+    /// mode and is only supported on Linux.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use std::net::IpAddr;
+    /// use trippy_core::{Builder, PortDirection, PrivilegeMode, Protocol};
+    ///
+    /// let addr = IpAddr::from([1, 1, 1, 1]);
+    /// let tracer = Builder::new(addr)
+    ///     .protocol(Protocol::Udp)
+    ///     .port_direction(PortDirection::new_fixed_dest(33434))
+    ///     .privilege_mode(PrivilegeMode::Unprivileged)
+    ///     .udp_recv_err(true)
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn udp_recv_err(self, udp_recv_err: bool) -> Self {
+        Self {
+            udp_recv_err,
+            ..self
+        }
+    }
+
+    /// Validate the checksum of received `ICMP` packets.
+    ///
+    /// Packets which fail validation are discarded and counted, see
+    /// [`Tracer::invalid_checksums`](crate::Tracer::invalid_checksums).  This is enabled by
+    /// default and may be disabled to debug responses from devices which generate bad checksums.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use std::net::IpAddr;
+    /// use trippy_core::Builder;
+    ///
+    /// let addr = IpAddr::from([1, 1, 1, 1]);
+    /// let tracer = Builder::new(addr).validate_icmp_checksum(false).build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn validate_icmp_checksum(self, validate_icmp_checksum: bool) -> Self {
+        Self {
+            validate_icmp_checksum,
+            ..self
+        }
+    }
+
+    /// Write the probes sent and the responses received to a `pcap` capture file.
+    ///
+    /// The file is created, or truncated, when the tracer is run and packets are written with a
+    /// synthesised IP header (`DLT_RAW`) where the OS does not supply one.  The `SYN` packets of
+    /// `TCP` probes are not captured.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use std::net::IpAddr;
+    /// use trippy_core::Builder;
+    ///
+    /// let addr = IpAddr::from([1, 1, 1, 1]);
+    /// let tracer = Builder::new(addr)
+    ///     .capture_file(Some("trace.pcap"))
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn capture_file<P: Into<PathBuf>>(self, capture_file: Option<P>) -> Self {
+        Self {
+            capture_file: capture_file.map(Into::into),
+            ..self
+        }
+    }
+
     /// Build the `Tracer`.
     ///
     /// # Examples
@@ -700,16 +1110,17 @@ impl Builder {
     /// # Errors
     ///
     /// This function will return `Error::BadConfig` if the configuration is invalid.
+    #[allow(clippy::too_many_lines)]
     pub fn build(self) -> Result<Tracer> {
         match (self.protocol, self.port_direction) {
-            (Protocol::Udp, PortDirection::None) => {
-                return Err(Error::BadConfig(
-                    "port_direction may not be None for udp protocol".to_string(),
-                ));
+            (protocol @ (Protocol::Udp | Protocol::Tcp), PortDirection::None) => {
+                return Err(Error::BadConfig(format!(
+                    "port_direction may not be None for {protocol} protocol"
+                )));
             }
-            (Protocol::Tcp, PortDirection::None) => {
+            (Protocol::Tcp, PortDirection::FixedBoth(_, _)) => {
                 return Err(Error::BadConfig(
-                    "port_direction may not be None for tcp protocol".to_string(),
+                    "port_direction may not be FixedBoth for tcp protocol".to_string(),
                 ));
             }
             _ => (),
@@ -732,6 +1143,53 @@ impl Builder {
                 self.initial_sequence.0
             )));
         }
+        if u32::from(self.max_sequence.0) + 1
+            < u32::from(self.initial_sequence.0) + u32::from(MAX_SEQUENCE_PER_ROUND) * 2
+        {
+            return Err(Error::BadConfig(format!(
+                "max_sequence {} < initial_sequence {} + {}",
+                self.max_sequence.0,
+                self.initial_sequence.0,
+                MAX_SEQUENCE_PER_ROUND * 2 - 1
+            )));
+        }
+        if !self.ipv6_extension_header.is_none() {
+            if self.target_addr.is_ipv4() {
+                return Err(Error::BadConfig(
+                    "ipv6_extension_header may only be used for IPv6 targets".to_string(),
+                ));
+            }
+            if self.protocol == Protocol::Tcp {
+                return Err(Error::BadConfig(
+                    "ipv6_extension_header may not be used for tcp protocol".to_string(),
+                ));
+            }
+        }
+        if self.udp_recv_err
+            && (self.protocol != Protocol::Udp
+                || self.privilege_mode != PrivilegeMode::Unprivileged)
+        {
+            return Err(Error::BadConfig(
+                "udp_recv_err may only be used for udp protocol in unprivileged mode".to_string(),
+            ));
+        }
+        if self.unprivileged_fallback && !cfg!(target_os = "macos") {
+            return Err(Error::BadConfig(
+                "unprivileged_fallback is not supported on this platform".to_string(),
+            ));
+        }
+        self.validate_flow_label_strategy()?;
+        self.validate_flows_per_round()?;
+        if self.tcp_mss.is_some() && self.protocol != Protocol::Tcp {
+            return Err(Error::BadConfig(
+                "tcp_mss may only be used for tcp protocol".to_string(),
+            ));
+        }
+        if self.payload_pattern.0.is_empty() {
+            return Err(Error::BadConfig(
+                "payload_pattern may not be empty".to_string(),
+            ));
+        }
         Ok(Tracer::new(
             self.interface,
             self.source_addr,
@@ -739,11 +1197,15 @@ impl Builder {
             self.privilege_mode,
             self.protocol,
             self.packet_size,
+            self.max_packet_size,
             self.payload_pattern,
             self.tos,
+            self.dont_fragment,
             self.icmp_extension_parse_mode,
+            self.ipv6_extension_header,
             self.read_timeout,
             self.tcp_connect_timeout,
+            self.tcp_mss,
             self.trace_identifier,
             self.max_rounds,
             self.first_ttl,
@@ -751,15 +1213,91 @@ impl Builder {
             self.grace_duration,
             self.max_inflight,
             self.initial_sequence,
+            self.max_sequence,
             self.multipath_strategy,
             self.port_direction,
+            self.flow_label_strategy,
+            self.flows_per_round,
+            self.nat64_prefixes,
             self.min_round_duration,
             self.max_round_duration,
             self.max_samples,
             self.max_flows,
             self.drop_privileges,
+            self.unprivileged_fallback,
+            self.udp_recv_err,
+            self.validate_icmp_checksum,
+            self.capture_file,
         ))
     }
+
+    /// Validate the number of flows per round.
+    fn validate_flows_per_round(&self) -> Result<()> {
+        if self.flows_per_round == 0 {
+            return Err(Error::BadConfig("flows_per_round may not be 0".to_string()));
+        }
+        if self.flows_per_round > 1 {
+            let varies_port = self.protocol == Protocol::Udp
+                && self.multipath_strategy != MultipathStrategy::Classic;
+            let varies_flow_label =
+                matches!(self.flow_label_strategy, FlowLabelStrategy::Range(..));
+            if !varies_port && !varies_flow_label {
+                return Err(Error::BadConfig(
+                    "flows_per_round may only be used with the paris or dublin multipath strategy or a flow label range".to_string(),
+                ));
+            }
+        }
+        let probes_per_round = u16::from(self.max_ttl.0) * u16::from(self.flows_per_round);
+        if probes_per_round > MAX_SEQUENCE_PER_ROUND {
+            return Err(Error::BadConfig(format!(
+                "max_ttl {} * flows_per_round {} > {MAX_SEQUENCE_PER_ROUND}",
+                self.max_ttl.0, self.flows_per_round
+            )));
+        }
+        Ok(())
+    }
+
+    /// Validate the IPv6 flow label strategy.
+    fn validate_flow_label_strategy(&self) -> Result<()> {
+        if self.flow_label_strategy != FlowLabelStrategy::None && self.target_addr.is_ipv4() {
+            return Err(Error::BadConfig(
+                "flow_label_strategy may only be used for IPv6 targets".to_string(),
+            ));
+        }
+        if let FlowLabelStrategy::Fixed(label) = self.flow_label_strategy {
+            if label.0 == 0 {
+                return Err(Error::BadConfig(
+                    "flow_label_strategy fixed label may not be 0".to_string(),
+                ));
+            }
+            if label.0 > MAX_FLOW_LABEL {
+                return Err(Error::BadConfig(format!(
+                    "flow_label_strategy fixed label {} > {MAX_FLOW_LABEL}",
+                    label.0
+                )));
+            }
+        }
+        if let FlowLabelStrategy::Range(start, end) = self.flow_label_strategy {
+            if start.0 == 0 {
+                return Err(Error::BadConfig(
+                    "flow_label_strategy range may not start at 0".to_string(),
+                ));
+            }
+            if end.0 > MAX_FLOW_LABEL {
+                return Err(Error::BadConfig(format!(
+                    "flow_label_strategy range end {} > {MAX_FLOW_LABEL}",
+                    end.0
+                )));
+            }
+            if start > end {
+                return Err(Error::BadConfig(format!(
+                    "flow_label_strategy range start {} > end {}",
+                    start.0, end.0
+                )));
+            }
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -767,8 +1305,9 @@ mod tests {
     use super::*;
     use crate::{config, Port};
     use config::defaults;
-    use std::net::Ipv4Addr;
+    use std::net::{Ipv4Addr, Ipv6Addr};
     use std::num::NonZeroUsize;
+    use std::path::Path;
 
     const SOURCE_ADDR: IpAddr = IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0));
     const TARGET_ADDR: IpAddr = IpAddr::V4(Ipv4Addr::new(2, 2, 2, 2));
@@ -784,6 +1323,11 @@ mod tests {
         assert_eq!(defaults::DEFAULT_STRATEGY_PROTOCOL, tracer.protocol());
         assert_eq!(TraceId::default(), tracer.trace_identifier());
         assert_eq!(defaults::DEFAULT_PRIVILEGE_MODE, tracer.privilege_mode());
+        assert_eq!(
+            defaults::DEFAULT_VALIDATE_ICMP_CHECKSUM,
+            tracer.validate_icmp_checksum()
+        );
+        assert_eq!(0, tracer.invalid_checksums());
         assert_eq!(
             defaults::DEFAULT_STRATEGY_MULTIPATH,
             tracer.multipath_strategy()
@@ -793,14 +1337,26 @@ mod tests {
             tracer.packet_size().0
         );
         assert_eq!(
-            defaults::DEFAULT_STRATEGY_PAYLOAD_PATTERN,
-            tracer.payload_pattern().0
+            defaults::DEFAULT_MAX_PACKET_SIZE,
+            tracer.max_packet_size().0
+        );
+        assert_eq!(
+            &PayloadPattern::byte(defaults::DEFAULT_STRATEGY_PAYLOAD_PATTERN),
+            tracer.payload_pattern()
         );
         assert_eq!(defaults::DEFAULT_STRATEGY_TOS, tracer.tos().0);
+        assert_eq!(
+            defaults::DEFAULT_STRATEGY_DONT_FRAGMENT,
+            tracer.dont_fragment()
+        );
         assert_eq!(
             defaults::DEFAULT_ICMP_EXTENSION_PARSE_MODE,
             tracer.icmp_extension_parse_mode()
         );
+        assert_eq!(
+            defaults::DEFAULT_IPV6_EXTENSION_HEADER,
+            tracer.ipv6_extension_header()
+        );
         assert_eq!(
             defaults::DEFAULT_STRATEGY_READ_TIMEOUT,
             tracer.read_timeout()
@@ -824,7 +1380,24 @@ mod tests {
             defaults::DEFAULT_STRATEGY_INITIAL_SEQUENCE,
             tracer.initial_sequence().0
         );
+        assert_eq!(
+            defaults::DEFAULT_STRATEGY_MAX_SEQUENCE,
+            tracer.max_sequence().0
+        );
         assert_eq!(PortDirection::None, tracer.port_direction());
+        assert_eq!(None, tracer.capture_file());
+        assert_eq!(
+            defaults::DEFAULT_STRATEGY_FLOW_LABEL,
+            tracer.flow_label_strategy()
+        );
+        assert_eq!(
+            defaults::DEFAULT_STRATEGY_FLOWS_PER_ROUND,
+            tracer.flows_per_round()
+        );
+        assert_eq!(
+            defaults::DEFAULT_STRATEGY_NAT64_PREFIXES,
+            tracer.nat64_prefixes()
+        );
         assert_eq!(
             defaults::DEFAULT_STRATEGY_MIN_ROUND_DURATION,
             tracer.min_round_duration()
@@ -845,10 +1418,15 @@ mod tests {
             .protocol(Protocol::Udp)
             .trace_identifier(101)
             .privilege_mode(PrivilegeMode::Unprivileged)
+            .udp_recv_err(true)
+            .validate_icmp_checksum(false)
+            .capture_file(Some("trace.pcap"))
             .multipath_strategy(MultipathStrategy::Paris)
             .packet_size(128)
+            .max_packet_size(9000)
             .payload_pattern(0xff)
             .tos(0x1a)
+            .dont_fragment(true)
             .icmp_extension_parse_mode(IcmpExtensionParseMode::Enabled)
             .read_timeout(Duration::from_millis(50))
             .tcp_connect_timeout(Duration::from_millis(100))
@@ -858,6 +1436,7 @@ mod tests {
             .grace_duration(Duration::from_millis(100))
             .max_inflight(22)
             .initial_sequence(35000)
+            .max_sequence(40000)
             .port_direction(PortDirection::FixedSrc(Port(8080)))
             .min_round_duration(Duration::from_millis(500))
             .max_round_duration(Duration::from_millis(1500))
@@ -873,10 +1452,15 @@ mod tests {
         assert_eq!(Protocol::Udp, tracer.protocol());
         assert_eq!(TraceId(101), tracer.trace_identifier());
         assert_eq!(PrivilegeMode::Unprivileged, tracer.privilege_mode());
+        assert!(tracer.udp_recv_err());
+        assert!(!tracer.validate_icmp_checksum());
+        assert_eq!(Some(Path::new("trace.pcap")), tracer.capture_file());
         assert_eq!(MultipathStrategy::Paris, tracer.multipath_strategy());
         assert_eq!(PacketSize(128), tracer.packet_size());
-        assert_eq!(PayloadPattern(0xff), tracer.payload_pattern());
+        assert_eq!(PacketSize(9000), tracer.max_packet_size());
+        assert_eq!(&PayloadPattern::byte(0xff), tracer.payload_pattern());
         assert_eq!(TypeOfService(0x1a), tracer.tos());
+        assert!(tracer.dont_fragment());
         assert_eq!(
             IcmpExtensionParseMode::Enabled,
             tracer.icmp_extension_parse_mode()
@@ -892,6 +1476,7 @@ mod tests {
         assert_eq!(Duration::from_millis(100), tracer.grace_duration());
         assert_eq!(MaxInflight(22), tracer.max_inflight());
         assert_eq!(Sequence(35000), tracer.initial_sequence());
+        assert_eq!(Sequence(40000), tracer.max_sequence());
         assert_eq!(PortDirection::FixedSrc(Port(8080)), tracer.port_direction());
         assert_eq!(Duration::from_millis(500), tracer.min_round_duration());
         assert_eq!(Duration::from_millis(1500), tracer.max_round_duration());
@@ -906,6 +1491,18 @@ mod tests {
         assert_eq!(None, tracer.max_rounds());
     }
 
+    #[test]
+    fn test_invalid_tcp_fixed_both() {
+        let err = Builder::new(IpAddr::from([1, 2, 3, 4]))
+            .protocol(Protocol::Tcp)
+            .port_direction(PortDirection::FixedBoth(Port(5000), Port(80)))
+            .build()
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::BadConfig(s) if s == "port_direction may not be FixedBoth for tcp protocol")
+        );
+    }
+
     #[test]
     fn test_invalid_initial_sequence() {
         let err = Builder::new(IpAddr::from([1, 2, 3, 4]))
@@ -914,4 +1511,256 @@ mod tests {
             .unwrap_err();
         assert!(matches!(err, Error::BadConfig(s) if s == "initial_sequence 65535 > 64511"));
     }
+
+    #[test]
+    fn test_invalid_max_sequence() {
+        let err = Builder::new(IpAddr::from([1, 2, 3, 4]))
+            .initial_sequence(35000)
+            .max_sequence(36022)
+            .build()
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::BadConfig(s) if s == "max_sequence 36022 < initial_sequence 35000 + 1023")
+        );
+        assert!(Builder::new(IpAddr::from([1, 2, 3, 4]))
+            .initial_sequence(35000)
+            .max_sequence(36023)
+            .build()
+            .is_ok());
+    }
+
+    #[test]
+    fn test_flow_label_strategy() {
+        let tracer = Builder::new(IpAddr::from(Ipv6Addr::LOCALHOST))
+            .flow_label_strategy(FlowLabelStrategy::new_range(1, 16))
+            .build()
+            .unwrap();
+        assert_eq!(
+            FlowLabelStrategy::new_range(1, 16),
+            tracer.flow_label_strategy()
+        );
+    }
+
+    #[test]
+    fn test_ipv6_extension_header() {
+        let tracer = Builder::new(IpAddr::from(Ipv6Addr::LOCALHOST))
+            .protocol(Protocol::Udp)
+            .port_direction(PortDirection::FixedSrc(Port(5000)))
+            .ipv6_extension_header(Ipv6ExtensionHeader::DestinationOptions)
+            .build()
+            .unwrap();
+        assert_eq!(
+            Ipv6ExtensionHeader::DestinationOptions,
+            tracer.ipv6_extension_header()
+        );
+    }
+
+    #[test]
+    fn test_invalid_ipv6_extension_header() {
+        let err = Builder::new(IpAddr::from([1, 2, 3, 4]))
+            .ipv6_extension_header(Ipv6ExtensionHeader::HopByHop)
+            .build()
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::BadConfig(s) if s == "ipv6_extension_header may only be used for IPv6 targets")
+        );
+        let err = Builder::new(IpAddr::from(Ipv6Addr::LOCALHOST))
+            .protocol(Protocol::Tcp)
+            .port_direction(PortDirection::FixedDest(Port(80)))
+            .ipv6_extension_header(Ipv6ExtensionHeader::HopByHop)
+            .build()
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::BadConfig(s) if s == "ipv6_extension_header may not be used for tcp protocol")
+        );
+    }
+
+    #[test]
+    fn test_invalid_udp_recv_err() {
+        let err = Builder::new(IpAddr::from([1, 2, 3, 4]))
+            .privilege_mode(PrivilegeMode::Unprivileged)
+            .udp_recv_err(true)
+            .build()
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::BadConfig(s) if s == "udp_recv_err may only be used for udp protocol in unprivileged mode")
+        );
+        let err = Builder::new(IpAddr::from([1, 2, 3, 4]))
+            .protocol(Protocol::Udp)
+            .port_direction(PortDirection::FixedDest(Port(33434)))
+            .udp_recv_err(true)
+            .build()
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::BadConfig(s) if s == "udp_recv_err may only be used for udp protocol in unprivileged mode")
+        );
+    }
+
+    #[test]
+    fn test_nat64_prefixes() {
+        let prefix = Nat64Prefix::new(Ipv6Addr::from([0x2001, 0xdb8, 0x64, 0, 0, 0, 0, 0]));
+        let tracer = Builder::new(IpAddr::from(Ipv6Addr::LOCALHOST))
+            .nat64_prefixes([prefix])
+            .build()
+            .unwrap();
+        assert_eq!(&[prefix], tracer.nat64_prefixes());
+    }
+
+    #[test]
+    fn test_invalid_flow_label_strategy_ipv4() {
+        let err = Builder::new(IpAddr::from([1, 2, 3, 4]))
+            .flow_label_strategy(FlowLabelStrategy::new_range(1, 16))
+            .build()
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::BadConfig(s) if s == "flow_label_strategy may only be used for IPv6 targets")
+        );
+    }
+
+    #[test]
+    fn test_invalid_flow_label_strategy_range() {
+        let err = Builder::new(IpAddr::from(Ipv6Addr::LOCALHOST))
+            .flow_label_strategy(FlowLabelStrategy::new_range(16, 1))
+            .build()
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::BadConfig(s) if s == "flow_label_strategy range start 16 > end 1")
+        );
+        let err = Builder::new(IpAddr::from(Ipv6Addr::LOCALHOST))
+            .flow_label_strategy(FlowLabelStrategy::new_range(1, 0x0010_0000))
+            .build()
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::BadConfig(s) if s == "flow_label_strategy range end 1048576 > 1048575")
+        );
+        let err = Builder::new(IpAddr::from(Ipv6Addr::LOCALHOST))
+            .flow_label_strategy(FlowLabelStrategy::new_range(0, 16))
+            .build()
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::BadConfig(s) if s == "flow_label_strategy range may not start at 0")
+        );
+    }
+
+    #[test]
+    fn test_invalid_flow_label_strategy_fixed() {
+        let err = Builder::new(IpAddr::from([1, 2, 3, 4]))
+            .flow_label_strategy(FlowLabelStrategy::new_fixed(1))
+            .build()
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::BadConfig(s) if s == "flow_label_strategy may only be used for IPv6 targets")
+        );
+        let err = Builder::new(IpAddr::from(Ipv6Addr::LOCALHOST))
+            .flow_label_strategy(FlowLabelStrategy::new_fixed(0))
+            .build()
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::BadConfig(s) if s == "flow_label_strategy fixed label may not be 0")
+        );
+        let err = Builder::new(IpAddr::from(Ipv6Addr::LOCALHOST))
+            .flow_label_strategy(FlowLabelStrategy::new_fixed(0x0010_0000))
+            .build()
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::BadConfig(s) if s == "flow_label_strategy fixed label 1048576 > 1048575")
+        );
+    }
+
+    #[test]
+    fn test_flows_per_round() {
+        let tracer = Builder::new(IpAddr::from(Ipv4Addr::LOCALHOST))
+            .protocol(Protocol::Udp)
+            .multipath_strategy(MultipathStrategy::Paris)
+            .port_direction(PortDirection::new_fixed_src(5000))
+            .flows_per_round(4)
+            .build()
+            .unwrap();
+        assert_eq!(4, tracer.flows_per_round());
+        let tracer = Builder::new(IpAddr::from(Ipv6Addr::LOCALHOST))
+            .flow_label_strategy(FlowLabelStrategy::new_range(1, 16))
+            .flows_per_round(4)
+            .build()
+            .unwrap();
+        assert_eq!(4, tracer.flows_per_round());
+    }
+
+    #[test]
+    fn test_invalid_flows_per_round() {
+        let err = Builder::new(IpAddr::from(Ipv4Addr::LOCALHOST))
+            .flows_per_round(0)
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, Error::BadConfig(s) if s == "flows_per_round may not be 0"));
+        let err = Builder::new(IpAddr::from(Ipv4Addr::LOCALHOST))
+            .flows_per_round(2)
+            .build()
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::BadConfig(s) if s == "flows_per_round may only be used with the paris or dublin multipath strategy or a flow label range")
+        );
+        let err = Builder::new(IpAddr::from(Ipv6Addr::LOCALHOST))
+            .flow_label_strategy(FlowLabelStrategy::new_range(1, 16))
+            .max_ttl(64)
+            .flows_per_round(9)
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, Error::BadConfig(s) if s == "max_ttl 64 * flows_per_round 9 > 512"));
+    }
+
+    #[test]
+    fn test_payload_pattern_bytes() {
+        let tracer = Builder::new(TARGET_ADDR)
+            .payload_pattern_bytes([0xde, 0xad, 0xbe, 0xef])
+            .build()
+            .unwrap();
+        assert_eq!(
+            &PayloadPattern(vec![0xde, 0xad, 0xbe, 0xef]),
+            tracer.payload_pattern()
+        );
+        let err = Builder::new(TARGET_ADDR)
+            .payload_pattern_bytes([])
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, Error::BadConfig(s) if s == "payload_pattern may not be empty"));
+    }
+
+    #[test]
+    fn test_tcp_mss() {
+        let tracer = Builder::new(TARGET_ADDR).build().unwrap();
+        assert_eq!(None, tracer.tcp_mss());
+        let tracer = Builder::new(TARGET_ADDR)
+            .protocol(Protocol::Tcp)
+            .port_direction(PortDirection::FixedDest(Port(80)))
+            .tcp_mss(Some(1200))
+            .build()
+            .unwrap();
+        assert_eq!(Some(1200), tracer.tcp_mss());
+        let err = Builder::new(TARGET_ADDR)
+            .protocol(Protocol::Udp)
+            .port_direction(PortDirection::FixedDest(Port(33434)))
+            .tcp_mss(Some(1200))
+            .build()
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::BadConfig(s) if s == "tcp_mss may only be used for tcp protocol")
+        );
+    }
+
+    #[test]
+    fn test_unprivileged_fallback() {
+        let tracer = Builder::new(TARGET_ADDR).build().unwrap();
+        assert!(!tracer.unprivileged_fallback());
+        let res = Builder::new(TARGET_ADDR)
+            .unprivileged_fallback(true)
+            .build();
+        if cfg!(target_os = "macos") {
+            assert!(res.unwrap().unprivileged_fallback());
+        } else {
+            assert!(matches!(
+                res,
+                Err(Error::BadConfig(s)) if s == "unprivileged_fallback is not supported on this platform"
+            ));
+        }
+    }
 }
@@ -1,7 +1,7 @@
-use crate::types::Port;
+use crate::types::{FlowLabel, Port};
 use crate::{
-    MaxInflight, MaxRounds, PacketSize, PayloadPattern, Sequence, TimeToLive, TraceId,
-    TypeOfService,
+    MaxInflight, MaxRounds, Nat64Prefix, PacketSize, PayloadPattern, RoundId, Sequence, TimeToLive,
+    TraceId, TypeOfService,
 };
 use std::fmt::{Display, Formatter};
 use std::net::{IpAddr, Ipv4Addr};
@@ -9,23 +9,44 @@ use std::time::Duration;
 
 /// Default values for configuration.
 pub mod defaults {
-    use crate::config::IcmpExtensionParseMode;
-    use crate::{MultipathStrategy, PrivilegeMode, Protocol};
+    use crate::config::{IcmpExtensionParseMode, Ipv6ExtensionHeader};
+    use crate::{FlowLabelStrategy, MultipathStrategy, Nat64Prefix, PrivilegeMode, Protocol};
     use std::time::Duration;
 
     /// The default value for `unprivileged`.
     pub const DEFAULT_PRIVILEGE_MODE: PrivilegeMode = PrivilegeMode::Privileged;
 
+    /// The default value for `unprivileged-fallback`.
+    pub const DEFAULT_UNPRIVILEGED_FALLBACK: bool = false;
+
+    /// The default value for `udp-recv-err`.
+    pub const DEFAULT_UDP_RECV_ERR: bool = false;
+
+    /// The default value for `validate-icmp-checksum`.
+    pub const DEFAULT_VALIDATE_ICMP_CHECKSUM: bool = true;
+
     /// The default value for `protocol`.
     pub const DEFAULT_STRATEGY_PROTOCOL: Protocol = Protocol::Icmp;
 
     /// The default value for `multipath-strategy`.
     pub const DEFAULT_STRATEGY_MULTIPATH: MultipathStrategy = MultipathStrategy::Classic;
 
+    /// The default value for `flow-label-strategy`.
+    pub const DEFAULT_STRATEGY_FLOW_LABEL: FlowLabelStrategy = FlowLabelStrategy::None;
+
+    /// The default value for `flows-per-round`.
+    pub const DEFAULT_STRATEGY_FLOWS_PER_ROUND: u8 = 1;
+
+    /// The default value for `nat64-prefixes`.
+    pub const DEFAULT_STRATEGY_NAT64_PREFIXES: &[Nat64Prefix] = &[Nat64Prefix::WELL_KNOWN];
+
     /// The default value for `icmp-extensions`.
     pub const DEFAULT_ICMP_EXTENSION_PARSE_MODE: IcmpExtensionParseMode =
         IcmpExtensionParseMode::Disabled;
 
+    /// The default value for `ipv6-extension-header`.
+    pub const DEFAULT_IPV6_EXTENSION_HEADER: Ipv6ExtensionHeader = Ipv6ExtensionHeader::None;
+
     /// The default value for `max-inflight`.
     pub const DEFAULT_STRATEGY_MAX_INFLIGHT: u8 = 24;
 
@@ -38,6 +59,9 @@ pub mod defaults {
     /// The default value for `packet-size`.
     pub const DEFAULT_STRATEGY_PACKET_SIZE: u16 = 84;
 
+    /// The default value for `max-packet-size`.
+    pub const DEFAULT_MAX_PACKET_SIZE: u16 = 1024;
+
     /// The default value for `payload-pattern`.
     pub const DEFAULT_STRATEGY_PAYLOAD_PATTERN: u8 = 0;
 
@@ -50,9 +74,15 @@ pub mod defaults {
     /// The default value for `initial-sequence`.
     pub const DEFAULT_STRATEGY_INITIAL_SEQUENCE: u16 = 33000;
 
+    /// The default value for `max-sequence`.
+    pub const DEFAULT_STRATEGY_MAX_SEQUENCE: u16 = u16::MAX;
+
     /// The default value for `tos`.
     pub const DEFAULT_STRATEGY_TOS: u8 = 0;
 
+    /// The default value for `dont-fragment`.
+    pub const DEFAULT_STRATEGY_DONT_FRAGMENT: bool = false;
+
     /// The default value for `read-timeout`.
     pub const DEFAULT_STRATEGY_READ_TIMEOUT: Duration = Duration::from_millis(10);
 
@@ -125,6 +155,47 @@ impl Display for IcmpExtensionParseMode {
     }
 }
 
+/// The IPv6 extension header to insert into every IPv6 probe.
+///
+/// Some routers and middleboxes drop, or take a slow path for, packets which carry extension
+/// headers and so tracing with an extension header may reveal a different path.  The extension
+/// header is empty, containing only padding, and is not supported for the TCP protocol.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Ipv6ExtensionHeader {
+    /// Do not insert an extension header.
+    None,
+    /// Insert a Hop-by-Hop Options extension header.
+    HopByHop,
+    /// Insert a Destination Options extension header.
+    DestinationOptions,
+}
+
+impl Ipv6ExtensionHeader {
+    /// The size of the extension header in bytes.
+    #[must_use]
+    pub const fn size(self) -> usize {
+        match self {
+            Self::None => 0,
+            Self::HopByHop | Self::DestinationOptions => 8,
+        }
+    }
+
+    #[must_use]
+    pub const fn is_none(self) -> bool {
+        matches!(self, Self::None)
+    }
+}
+
+impl Display for Ipv6ExtensionHeader {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::HopByHop => write!(f, "hop-by-hop"),
+            Self::DestinationOptions => write!(f, "destination-options"),
+        }
+    }
+}
+
 /// The tracing protocol.
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum Protocol {
@@ -151,8 +222,9 @@ impl Display for Protocol {
 pub enum MultipathStrategy {
     /// The src or dest port is used to store the sequence number.
     ///
-    /// This does _not_ allow fixing both the src and dest port and so `PortDirection::Both` and
-    /// `SequenceField::Port` are mutually exclusive.
+    /// If both the src and dest ports are fixed (i.e. `PortDirection::FixedBoth`) then the UDP
+    /// `checksum` field is used to store the sequence number instead, as per
+    /// `MultipathStrategy::Paris`.
     Classic,
     /// The UDP `checksum` field is used to store the sequence number.
     ///
@@ -209,7 +281,7 @@ pub enum PortDirection {
     /// such that probes can be identified.  Typically, this is only used for UDP, whereby the
     /// checksum is manipulated by adjusting the payload and therefore used as the identifier.
     ///
-    /// Note that this case is not currently implemented.
+    /// Note that this case is not currently implemented for TCP.
     FixedBoth(Port, Port),
 }
 
@@ -245,6 +317,62 @@ impl PortDirection {
     }
 }
 
+/// The IPv6 flow label strategy.
+///
+/// Routers which perform [Equal-cost Multi-Path](https://en.wikipedia.org/wiki/Equal-cost_multi-path_routing)
+/// routing for IPv6 commonly include the flow label in the hash used to select a path.  Varying
+/// the flow label whilst keeping all other fields of the flow fixed therefore allows the
+/// different paths to a target to be discovered.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum FlowLabelStrategy {
+    /// Do not set the flow label (IPv6 only).
+    None,
+    /// Use a fixed flow label for every probe in every round (IPv6 only).
+    ///
+    /// All probes share the same flow label such that only a single path is traced.
+    Fixed(FlowLabel),
+    /// Iterate over an inclusive range of flow labels, one per round (IPv6 only).
+    ///
+    /// Every probe in a given round shares the same flow label such that each round traces a
+    /// single path.  The flow label used for round `n` is `start + (n % (end - start + 1))`.
+    Range(FlowLabel, FlowLabel),
+}
+
+impl FlowLabelStrategy {
+    #[must_use]
+    pub const fn new_fixed(label: u32) -> Self {
+        Self::Fixed(FlowLabel(label))
+    }
+
+    #[must_use]
+    pub const fn new_range(start: u32, end: u32) -> Self {
+        Self::Range(FlowLabel(start), FlowLabel(end))
+    }
+
+    /// The flow label to use for a given round.
+    #[must_use]
+    pub const fn flow_label(&self, round: RoundId) -> FlowLabel {
+        match *self {
+            Self::None => FlowLabel(0),
+            Self::Fixed(label) => label,
+            Self::Range(start, end) => {
+                let count = (end.0 - start.0) as usize + 1;
+                FlowLabel(start.0 + (round.0 % count) as u32)
+            }
+        }
+    }
+}
+
+impl Display for FlowLabelStrategy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::Fixed(label) => write!(f, "fixed({})", label.0),
+            Self::Range(start, end) => write!(f, "range({}..={})", start.0, end.0),
+        }
+    }
+}
+
 /// Tracer state configuration.
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub struct StateConfig {
@@ -270,41 +398,60 @@ impl Default for StateConfig {
 }
 
 /// Tracer network channel configuration.
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub struct ChannelConfig {
     pub privilege_mode: PrivilegeMode,
+    pub unprivileged_fallback: bool,
+    pub udp_recv_err: bool,
+    pub validate_icmp_checksum: bool,
     pub protocol: Protocol,
     pub source_addr: IpAddr,
     pub target_addr: IpAddr,
+    pub interface_index: Option<u32>,
+    pub port_direction: PortDirection,
     pub packet_size: PacketSize,
+    pub max_packet_size: PacketSize,
     pub payload_pattern: PayloadPattern,
     pub initial_sequence: Sequence,
     pub tos: TypeOfService,
+    pub dont_fragment: bool,
     pub icmp_extension_parse_mode: IcmpExtensionParseMode,
+    pub ipv6_extension_header: Ipv6ExtensionHeader,
     pub read_timeout: Duration,
     pub tcp_connect_timeout: Duration,
+    pub tcp_mss: Option<u16>,
 }
 
 impl Default for ChannelConfig {
     fn default() -> Self {
         Self {
             privilege_mode: defaults::DEFAULT_PRIVILEGE_MODE,
+            unprivileged_fallback: defaults::DEFAULT_UNPRIVILEGED_FALLBACK,
+            udp_recv_err: defaults::DEFAULT_UDP_RECV_ERR,
+            validate_icmp_checksum: defaults::DEFAULT_VALIDATE_ICMP_CHECKSUM,
             protocol: defaults::DEFAULT_STRATEGY_PROTOCOL,
             source_addr: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
             target_addr: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            interface_index: None,
+            port_direction: PortDirection::None,
             packet_size: PacketSize(defaults::DEFAULT_STRATEGY_PACKET_SIZE),
-            payload_pattern: PayloadPattern(defaults::DEFAULT_STRATEGY_PAYLOAD_PATTERN),
+            max_packet_size: PacketSize(defaults::DEFAULT_MAX_PACKET_SIZE),
+            payload_pattern: PayloadPattern::byte(defaults::DEFAULT_STRATEGY_PAYLOAD_PATTERN),
             initial_sequence: Sequence(defaults::DEFAULT_STRATEGY_INITIAL_SEQUENCE),
             tos: TypeOfService(defaults::DEFAULT_STRATEGY_TOS),
+            dont_fragment: defaults::DEFAULT_STRATEGY_DONT_FRAGMENT,
             icmp_extension_parse_mode: defaults::DEFAULT_ICMP_EXTENSION_PARSE_MODE,
+            ipv6_extension_header: defaults::DEFAULT_IPV6_EXTENSION_HEADER,
             read_timeout: defaults::DEFAULT_STRATEGY_READ_TIMEOUT,
             tcp_connect_timeout: defaults::DEFAULT_STRATEGY_TCP_CONNECT_TIMEOUT,
+            tcp_mss: None,
         }
     }
 }
 
 /// Tracing strategy configuration.
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub struct StrategyConfig {
     pub target_addr: IpAddr,
     pub protocol: Protocol,
@@ -315,8 +462,12 @@ pub struct StrategyConfig {
     pub grace_duration: Duration,
     pub max_inflight: MaxInflight,
     pub initial_sequence: Sequence,
+    pub max_sequence: Sequence,
     pub multipath_strategy: MultipathStrategy,
     pub port_direction: PortDirection,
+    pub flow_label_strategy: FlowLabelStrategy,
+    pub flows_per_round: u8,
+    pub nat64_prefixes: Vec<Nat64Prefix>,
     pub min_round_duration: Duration,
     pub max_round_duration: Duration,
 }
@@ -333,8 +484,12 @@ impl Default for StrategyConfig {
             grace_duration: defaults::DEFAULT_STRATEGY_GRACE_DURATION,
             max_inflight: MaxInflight(defaults::DEFAULT_STRATEGY_MAX_INFLIGHT),
             initial_sequence: Sequence(defaults::DEFAULT_STRATEGY_INITIAL_SEQUENCE),
+            max_sequence: Sequence(defaults::DEFAULT_STRATEGY_MAX_SEQUENCE),
             multipath_strategy: defaults::DEFAULT_STRATEGY_MULTIPATH,
             port_direction: PortDirection::None,
+            flow_label_strategy: defaults::DEFAULT_STRATEGY_FLOW_LABEL,
+            flows_per_round: defaults::DEFAULT_STRATEGY_FLOWS_PER_ROUND,
+            nat64_prefixes: defaults::DEFAULT_STRATEGY_NAT64_PREFIXES.to_vec(),
             min_round_duration: defaults::DEFAULT_STRATEGY_MIN_ROUND_DURATION,
             max_round_duration: defaults::DEFAULT_STRATEGY_MAX_ROUND_DURATION,
         }
@@ -17,3 +17,8 @@ pub const MAX_SEQUENCE_PER_ROUND: u16 = 512;
 /// require two rounds to ensure that delayed probe responses from the immediate prior round can be
 /// detected and excluded.
 pub const MAX_INITIAL_SEQUENCE: u16 = u16::MAX - (MAX_SEQUENCE_PER_ROUND * 2);
+
+/// The maximum IPv6 flow label value allowed.
+///
+/// The IPv6 flow label is a 20-bit field.
+pub const MAX_FLOW_LABEL: u32 = 0x000F_FFFF;
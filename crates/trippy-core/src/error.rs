@@ -12,14 +12,30 @@ pub type Result<T> = std::result::Result<T, Error>;
 pub enum Error {
     #[error("invalid packet size: {0}")]
     InvalidPacketSize(usize),
+    #[error("invalid packet size: {0} exceeds the maximum packet size of {1}")]
+    PacketSizeExceedsMax(usize, usize),
     #[error("invalid packet: {0}")]
     PacketError(#[from] trippy_packet::error::Error),
     #[error("unknown interface: {0}")]
     UnknownInterface(String),
+    #[error("no {1} address for interface: {0}")]
+    NoInterfaceAddr(String, &'static str),
+    #[error("permission denied binding to interface with index {0}: {1}")]
+    InterfaceBindDenied(u32, IoError),
+    #[error("binding to interface with index {0} is not supported on this platform")]
+    InterfaceBindUnsupported(u32),
     #[error("invalid config: {0}")]
     BadConfig(String),
+    #[error("unprivileged ICMP sockets are unavailable: {0}")]
+    UnprivilegedIcmpUnavailable(String),
     #[error("IO error: {0}")]
     IoError(#[from] IoError),
+    #[error("host unreachable: {0}")]
+    HostUnreachable(IoError),
+    #[error("network unreachable: {0}")]
+    NetworkUnreachable(IoError),
+    #[error("permission denied (try running with elevated privileges): {0}")]
+    PermissionDenied(IoError),
     #[error("insufficient buffer capacity")]
     InsufficientCapacity,
     #[error("address {0} not available")]
@@ -28,6 +44,8 @@ pub enum Error {
     InvalidSourceAddr(IpAddr),
     #[error("missing address from socket call")]
     MissingAddr,
+    #[error("invalid ICMP checksum, expected={expected:#06x}, actual={actual:#06x}")]
+    InvalidIcmpChecksum { expected: u16, actual: u16 },
     #[error("connect callback error: {0}")]
     PrivilegeError(#[from] trippy_privilege::Error),
     #[error("tracer error: {0}")]
@@ -84,6 +102,16 @@ pub enum IoOperation {
     SetReusePort,
     SetHeaderIncluded,
     SetUnicastHopsV6,
+    SetTclassV6,
+    SetFlowInfoSendV6,
+    SetIpv6ExtensionHeader,
+    BindDevice,
+    SetDontFragment,
+    SetMss,
+    SetRecvErr,
+    RecvErr,
+    CreateCaptureFile,
+    WriteCaptureFile,
     Close,
     WSACreateEvent,
     WSARecvFrom,
@@ -115,6 +143,16 @@ impl Display for IoOperation {
             Self::SetReusePort => write!(f, "set reuse port"),
             Self::SetHeaderIncluded => write!(f, "set header included"),
             Self::SetUnicastHopsV6 => write!(f, "set unicast hops v6"),
+            Self::SetTclassV6 => write!(f, "set tclass v6"),
+            Self::SetFlowInfoSendV6 => write!(f, "set flowinfo send v6"),
+            Self::SetIpv6ExtensionHeader => write!(f, "set IPv6 extension header"),
+            Self::BindDevice => write!(f, "bind device"),
+            Self::SetDontFragment => write!(f, "set dont fragment"),
+            Self::SetMss => write!(f, "set mss"),
+            Self::SetRecvErr => write!(f, "set recv err"),
+            Self::RecvErr => write!(f, "recv err"),
+            Self::CreateCaptureFile => write!(f, "create capture file"),
+            Self::WriteCaptureFile => write!(f, "write capture file"),
             Self::Close => write!(f, "close"),
             Self::WSACreateEvent => write!(f, "WSA create event"),
             Self::WSARecvFrom => write!(f, "WSA recv from"),
@@ -60,6 +60,7 @@ mod config;
 mod constants;
 mod error;
 mod flows;
+mod nat64;
 mod net;
 mod probe;
 mod state;
@@ -72,19 +73,21 @@ use net::source::SourceAddr;
 
 pub use builder::Builder;
 pub use config::{
-    defaults, IcmpExtensionParseMode, MultipathStrategy, PortDirection, PrivilegeMode, Protocol,
+    defaults, FlowLabelStrategy, IcmpExtensionParseMode, Ipv6ExtensionHeader, MultipathStrategy,
+    PortDirection, PrivilegeMode, Protocol,
 };
-pub use constants::MAX_TTL;
+pub use constants::{MAX_FLOW_LABEL, MAX_SEQUENCE_PER_ROUND, MAX_TTL};
 pub use error::Error;
 pub use flows::{FlowEntry, FlowId};
+pub use nat64::Nat64Prefix;
 pub use probe::{
     Extension, Extensions, IcmpPacketType, MplsLabelStack, MplsLabelStackMember, Probe,
     ProbeComplete, ProbeStatus, UnknownExtension,
 };
-pub use state::{Hop, State};
+pub use state::{Hop, Responder, State};
 pub use strategy::{CompletionReason, Round, Strategy};
 pub use tracer::Tracer;
 pub use types::{
-    Flags, MaxInflight, MaxRounds, PacketSize, PayloadPattern, Port, RoundId, Sequence, TimeToLive,
-    TraceId, TypeOfService,
+    Flags, FlowIndex, FlowLabel, MaxInflight, MaxRounds, PacketSize, PayloadPattern, Port, RoundId,
+    Sequence, TimeToLive, TraceId, TypeOfService,
 };
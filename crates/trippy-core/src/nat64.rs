@@ -0,0 +1,149 @@
+use std::fmt::{Display, Formatter};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// A NAT64 translation prefix.
+///
+/// A NAT64 (or 464XLAT) translator embeds IPv4 addresses in the low 32 bits of an IPv6 address
+/// within a given prefix, as described in [rfc6052].  Only `/96` prefixes are supported.
+///
+/// [rfc6052]: https://datatracker.ietf.org/doc/html/rfc6052
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Nat64Prefix(Ipv6Addr);
+
+impl Nat64Prefix {
+    /// The well-known NAT64 prefix `64:ff9b::/96`.
+    pub const WELL_KNOWN: Self = Self(Ipv6Addr::new(0x64, 0xff9b, 0, 0, 0, 0, 0, 0));
+
+    /// Create a `/96` `Nat64Prefix`.
+    ///
+    /// The low 32 bits of `prefix` are ignored.
+    #[must_use]
+    pub const fn new(prefix: Ipv6Addr) -> Self {
+        let s = prefix.segments();
+        Self(Ipv6Addr::new(s[0], s[1], s[2], s[3], s[4], s[5], 0, 0))
+    }
+
+    /// The prefix address.
+    #[must_use]
+    pub const fn addr(&self) -> Ipv6Addr {
+        self.0
+    }
+
+    /// Is `addr` within this prefix?
+    #[must_use]
+    pub fn contains(&self, addr: Ipv6Addr) -> bool {
+        addr.segments()[..6] == self.0.segments()[..6]
+    }
+
+    /// The IPv4 address embedded in `addr`, if `addr` is within this prefix.
+    #[must_use]
+    pub fn embedded_ipv4(&self, addr: Ipv6Addr) -> Option<Ipv4Addr> {
+        if self.contains(addr) {
+            let [.., a, b, c, d] = addr.octets();
+            Some(Ipv4Addr::new(a, b, c, d))
+        } else {
+            None
+        }
+    }
+}
+
+impl Display for Nat64Prefix {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/96", self.0)
+    }
+}
+
+/// Map an `IpAddr` back to its original IPv4 form, if it has one.
+///
+/// An IPv4 address is returned as is, an IPv4-mapped IPv6 address (i.e. `::ffff:a.b.c.d`) is
+/// mapped to `a.b.c.d` and an IPv6 address within any of the given `prefixes` is mapped to the
+/// embedded IPv4 address.  Returns the NAT64 prefix, if any, which was used in the translation.
+pub fn original_ipv4(
+    addr: IpAddr,
+    prefixes: &[Nat64Prefix],
+) -> Option<(Ipv4Addr, Option<Nat64Prefix>)> {
+    match addr {
+        IpAddr::V4(addr) => Some((addr, None)),
+        IpAddr::V6(addr) => addr.to_ipv4_mapped().map(|ipv4| (ipv4, None)).or_else(|| {
+            prefixes
+                .iter()
+                .find_map(|prefix| prefix.embedded_ipv4(addr).map(|ipv4| (ipv4, Some(*prefix))))
+        }),
+    }
+}
+
+/// Find the NAT64 prefix, if any, which contains an `IpAddr`.
+pub fn find_prefix(addr: IpAddr, prefixes: &[Nat64Prefix]) -> Option<Nat64Prefix> {
+    match addr {
+        IpAddr::V4(_) => None,
+        IpAddr::V6(addr) => prefixes
+            .iter()
+            .find(|prefix| prefix.contains(addr))
+            .copied(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_well_known() {
+        let prefix = Nat64Prefix::WELL_KNOWN;
+        assert_eq!("64:ff9b::/96", prefix.to_string());
+        assert!(prefix.contains(Ipv6Addr::from_str("64:ff9b::c000:201").unwrap()));
+        assert!(!prefix.contains(Ipv6Addr::from_str("64:ff9b:1::c000:201").unwrap()));
+        assert_eq!(
+            Some(Ipv4Addr::new(192, 0, 2, 1)),
+            prefix.embedded_ipv4(Ipv6Addr::from_str("64:ff9b::192.0.2.1").unwrap())
+        );
+        assert_eq!(
+            None,
+            prefix.embedded_ipv4(Ipv6Addr::from_str("2001:db8::1").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_new_ignores_low_bits() {
+        let prefix = Nat64Prefix::new(Ipv6Addr::from_str("2001:db8:64::1:2").unwrap());
+        assert_eq!(Ipv6Addr::from_str("2001:db8:64::").unwrap(), prefix.addr());
+    }
+
+    #[test]
+    fn test_original_ipv4() {
+        let custom = Nat64Prefix::new(Ipv6Addr::from_str("2001:db8:64::").unwrap());
+        let prefixes = [Nat64Prefix::WELL_KNOWN, custom];
+        let ipv4 = Ipv4Addr::new(198, 51, 100, 1);
+        assert_eq!(
+            Some((ipv4, None)),
+            original_ipv4(IpAddr::V4(ipv4), &prefixes)
+        );
+        assert_eq!(
+            Some((ipv4, None)),
+            original_ipv4(IpAddr::V6(ipv4.to_ipv6_mapped()), &prefixes)
+        );
+        assert_eq!(
+            Some((ipv4, Some(Nat64Prefix::WELL_KNOWN))),
+            original_ipv4(
+                IpAddr::from_str("64:ff9b::198.51.100.1").unwrap(),
+                &prefixes
+            )
+        );
+        assert_eq!(
+            Some((ipv4, Some(custom))),
+            original_ipv4(
+                IpAddr::from_str("2001:db8:64::198.51.100.1").unwrap(),
+                &prefixes
+            )
+        );
+        assert_eq!(
+            None,
+            original_ipv4(IpAddr::from_str("2001:db8::1").unwrap(), &prefixes)
+        );
+        assert_eq!(
+            None,
+            original_ipv4(IpAddr::from_str("64:ff9b::198.51.100.1").unwrap(), &[])
+        );
+    }
+}
@@ -19,6 +19,12 @@ mod platform;
 /// A network socket.
 mod socket;
 
+/// The probe payload header.
+pub mod payload;
+
+/// A capture of the packets sent and received.
+pub mod capture;
+
 /// A channel for sending and receiving probes.
 pub mod channel;
 
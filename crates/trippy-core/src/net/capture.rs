@@ -0,0 +1,152 @@
+use crate::error::{IoError, IoOperation, Result};
+use std::fs::File;
+use std::io::Write;
+use std::net::IpAddr;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The `pcap` magic number for microsecond resolution timestamps.
+const PCAP_MAGIC: u32 = 0xa1b2_c3d4;
+
+/// The major version of the `pcap` file format.
+const PCAP_VERSION_MAJOR: u16 = 2;
+
+/// The minor version of the `pcap` file format.
+const PCAP_VERSION_MINOR: u16 = 4;
+
+/// The maximum number of bytes captured per packet.
+const PCAP_SNAPLEN: u32 = 65535;
+
+/// The `pcap` link type for IPv4 and IPv6 packets without a link-layer header (`DLT_RAW`).
+const LINKTYPE_RAW: u32 = 101;
+
+/// The size of the `pcap` file header.
+const PCAP_HEADER_SIZE: usize = 24;
+
+/// The size of the `pcap` record header.
+const PCAP_RECORD_HEADER_SIZE: usize = 16;
+
+/// A capture of the probes sent and the responses received, written as a `pcap` file.
+///
+/// Every record is written to the file as it is captured, rather than being buffered, such that a
+/// capture remains readable up to the last packet if the tracer exits abnormally.
+#[derive(Debug)]
+pub struct Capture {
+    file: File,
+    local_addr: IpAddr,
+}
+
+impl Capture {
+    /// Create a capture file at `path` for packets sent from, and received by, `local_addr`.
+    ///
+    /// Any existing file at `path` is truncated.
+    pub fn create(path: &Path, local_addr: IpAddr) -> Result<Self> {
+        let mut file = File::create(path)
+            .map_err(|err| IoError::Other(err, IoOperation::CreateCaptureFile))?;
+        file.write_all(&pcap_header())
+            .map_err(|err| IoError::Other(err, IoOperation::WriteCaptureFile))?;
+        Ok(Self { file, local_addr })
+    }
+
+    /// The local address of the capture.
+    ///
+    /// This is used as the destination of a synthesised IP header for a packet which is received
+    /// without one.
+    pub const fn local_addr(&self) -> IpAddr {
+        self.local_addr
+    }
+
+    /// Write an IP packet to the capture, timestamped now.
+    pub fn write(&mut self, packet: &[u8]) -> Result<()> {
+        let record = pcap_record(SystemTime::now(), packet);
+        self.file
+            .write_all(&record)
+            .map_err(|err| IoError::Other(err, IoOperation::WriteCaptureFile))?;
+        Ok(())
+    }
+}
+
+/// The `pcap` file header, in little endian byte order.
+fn pcap_header() -> [u8; PCAP_HEADER_SIZE] {
+    let mut header = [0_u8; PCAP_HEADER_SIZE];
+    header[0..4].copy_from_slice(&PCAP_MAGIC.to_le_bytes());
+    header[4..6].copy_from_slice(&PCAP_VERSION_MAJOR.to_le_bytes());
+    header[6..8].copy_from_slice(&PCAP_VERSION_MINOR.to_le_bytes());
+    header[16..20].copy_from_slice(&PCAP_SNAPLEN.to_le_bytes());
+    header[20..24].copy_from_slice(&LINKTYPE_RAW.to_le_bytes());
+    header
+}
+
+/// A `pcap` record for `packet` captured at `timestamp`, in little endian byte order.
+fn pcap_record(timestamp: SystemTime, packet: &[u8]) -> Vec<u8> {
+    let since_epoch = timestamp.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = u32::try_from(since_epoch.as_secs()).unwrap_or(u32::MAX);
+    let len = u32::try_from(packet.len()).unwrap_or(u32::MAX);
+    let mut record = Vec::with_capacity(PCAP_RECORD_HEADER_SIZE + packet.len());
+    record.extend_from_slice(&secs.to_le_bytes());
+    record.extend_from_slice(&since_epoch.subsec_micros().to_le_bytes());
+    record.extend_from_slice(&len.to_le_bytes());
+    record.extend_from_slice(&len.to_le_bytes());
+    record.extend_from_slice(packet);
+    record
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_pcap_header() {
+        let expected = hex_literal::hex!(
+            "
+            d4 c3 b2 a1 02 00 04 00 00 00 00 00 00 00 00 00
+            ff ff 00 00 65 00 00 00
+            "
+        );
+        assert_eq!(expected, pcap_header());
+    }
+
+    #[test]
+    fn test_pcap_record() {
+        let timestamp = UNIX_EPOCH + Duration::new(1_700_000_000, 123_456_789);
+        let expected = hex_literal::hex!(
+            "
+            00 f1 53 65 40 e2 01 00 03 00 00 00 03 00 00 00
+            45 00 1c
+            "
+        );
+        assert_eq!(
+            expected.as_slice(),
+            pcap_record(timestamp, &[0x45, 0x00, 0x1c])
+        );
+    }
+
+    #[test]
+    fn test_capture_file() -> anyhow::Result<()> {
+        let path = std::env::temp_dir().join(format!("trippy-capture-{}.pcap", std::process::id()));
+        let local_addr = IpAddr::from([192, 0, 2, 1]);
+        let mut capture = Capture::create(&path, local_addr)?;
+        assert_eq!(local_addr, capture.local_addr());
+        capture.write(&[0x45, 0x00, 0x1c])?;
+        capture.write(&[0x60])?;
+        drop(capture);
+        let contents = std::fs::read(&path)?;
+        std::fs::remove_file(&path)?;
+        assert_eq!(
+            PCAP_HEADER_SIZE + PCAP_RECORD_HEADER_SIZE * 2 + 4,
+            contents.len()
+        );
+        assert_eq!(pcap_header().as_slice(), &contents[..PCAP_HEADER_SIZE]);
+        assert_eq!([0x45, 0x00, 0x1c], contents[40..43]);
+        assert_eq!([0x01, 0x00, 0x00, 0x00], contents[51..55]);
+        assert_eq!(0x60, contents[59]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_pcap_record_before_epoch() {
+        let record = pcap_record(UNIX_EPOCH - Duration::from_secs(1), &[]);
+        assert_eq!([0_u8; PCAP_RECORD_HEADER_SIZE].as_slice(), record);
+    }
+}
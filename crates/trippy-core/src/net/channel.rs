@@ -1,77 +1,191 @@
-use crate::config::{ChannelConfig, IcmpExtensionParseMode};
-use crate::error::{Error, Result};
+use crate::config::{defaults, ChannelConfig, IcmpExtensionParseMode, Ipv6ExtensionHeader};
+use crate::error::{Error, IoError, IoOperation, Result};
+use crate::net::capture::Capture;
+use crate::net::common::{bind_device, process_result, validate_packet_size};
 use crate::net::socket::Socket;
 use crate::net::{ipv4, ipv6, platform, Network};
 use crate::probe::{Probe, Response};
 use crate::types::{PacketSize, PayloadPattern, TypeOfService};
-use crate::{Port, PrivilegeMode, Protocol, Sequence};
+use crate::{Port, PortDirection, PrivilegeMode, Protocol, Sequence};
 use arrayvec::ArrayVec;
-use std::net::IpAddr;
+use std::io::ErrorKind;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::time::{Duration, SystemTime};
 use tracing::instrument;
 
-/// The maximum size of the IP packet we allow.
-pub const MAX_PACKET_SIZE: usize = 1024;
-
 /// The maximum number of TCP probes we allow.
 const MAX_TCP_PROBES: usize = 256;
 
+/// The maximum number of in-flight UDP probes sockets we allow when using the socket error queue.
+const MAX_UDP_PROBES: usize = 256;
+
 /// A channel for sending and receiving `Probe` packets.
+#[allow(clippy::struct_excessive_bools)]
 pub struct Channel<S: Socket> {
     privilege_mode: PrivilegeMode,
     protocol: Protocol,
     src_addr: IpAddr,
+    src_scope_id: u32,
+    interface_index: Option<u32>,
     ipv4_length_order: platform::Ipv4ByteOrder,
     dest_addr: IpAddr,
+    port_direction: PortDirection,
+    bound_send_socket: bool,
     packet_size: PacketSize,
+    max_packet_size: PacketSize,
     payload_pattern: PayloadPattern,
     initial_sequence: Sequence,
     tos: TypeOfService,
+    dont_fragment: bool,
     icmp_extension_mode: IcmpExtensionParseMode,
+    ipv6_extension_header: Ipv6ExtensionHeader,
     read_timeout: Duration,
     tcp_connect_timeout: Duration,
+    tcp_mss: Option<u16>,
+    udp_recv_err: bool,
+    validate_icmp_checksum: bool,
+    invalid_checksums: Arc<AtomicUsize>,
     send_socket: Option<S>,
-    recv_socket: S,
+    recv_socket: Option<S>,
+    recv_buf: Vec<u8>,
     tcp_probes: ArrayVec<TcpProbe<S>, MAX_TCP_PROBES>,
+    udp_probes: Vec<UdpProbe<S>>,
+    capture: Option<Capture>,
 }
 
 impl<S: Socket> Channel<S> {
     /// Create an `IcmpChannel`.
     ///
     /// This operation requires the `CAP_NET_RAW` capability on Linux.
+    ///
+    /// If `unprivileged_fallback` is set and a privileged `ICMP` channel cannot be created due to
+    /// insufficient permissions then an unprivileged channel is created instead.
+    ///
+    /// If `udp_recv_err` is set then no `ICMP` receive socket is created and the `ICMP` errors for
+    /// `UDP` probes are instead read from the error queue of the socket which sent them.
     #[instrument(skip_all)]
     pub fn connect(config: &ChannelConfig) -> Result<Self> {
         tracing::debug!(?config);
-        if usize::from(config.packet_size.0) > MAX_PACKET_SIZE {
-            return Err(Error::InvalidPacketSize(usize::from(config.packet_size.0)));
+        match Self::connect_with(config) {
+            Err(Error::IoError(err))
+                if config.unprivileged_fallback
+                    && config.protocol == Protocol::Icmp
+                    && config.privilege_mode == PrivilegeMode::Privileged
+                    && err.kind() == ErrorKind::PermissionDenied =>
+            {
+                tracing::warn!(%err, "falling back to unprivileged mode");
+                Self::connect_with(&ChannelConfig {
+                    privilege_mode: PrivilegeMode::Unprivileged,
+                    ..config.clone()
+                })
+            }
+            res => res,
         }
+    }
+
+    fn connect_with(config: &ChannelConfig) -> Result<Self> {
+        let min_packet_size = match config.target_addr {
+            IpAddr::V4(_) => ipv4::min_packet_size(config.protocol),
+            IpAddr::V6(_) => {
+                ipv6::min_packet_size(config.protocol) + config.ipv6_extension_header.size()
+            }
+        };
+        validate_packet_size(config.packet_size, min_packet_size, config.max_packet_size)?;
         let raw = config.privilege_mode == PrivilegeMode::Privileged;
         platform::startup()?;
         let ipv4_length_order = platform::Ipv4ByteOrder::for_address(config.source_addr)?;
-        let send_socket = match config.protocol {
-            Protocol::Icmp => Some(make_icmp_send_socket(config.source_addr, raw)?),
-            Protocol::Udp => Some(make_udp_send_socket(config.source_addr, raw)?),
+        // a link-local source address is only usable when bound with the interface as its scope.
+        let src_scope_id = match (config.source_addr, config.interface_index) {
+            (IpAddr::V6(addr), Some(index)) if platform::is_unicast_link_local(addr) => index,
+            _ => 0,
+        };
+        let bound_send_socket =
+            config.protocol == Protocol::Udp && !raw && config.port_direction.src().is_some();
+        let mut send_socket: Option<S> = match config.protocol {
+            Protocol::Icmp => Some(make_icmp_send_socket(
+                config.source_addr,
+                config.tos,
+                config.ipv6_extension_header,
+                config.interface_index,
+                raw,
+            )?),
+            Protocol::Udp => Some(make_udp_send_socket(
+                config.source_addr,
+                src_scope_id,
+                config.port_direction,
+                config.tos,
+                config.dont_fragment,
+                config.ipv6_extension_header,
+                config.interface_index,
+                raw,
+            )?),
             Protocol::Tcp => None,
         };
-        let recv_socket = make_recv_socket(config.source_addr, raw)?;
+        let recv_socket = if config.udp_recv_err {
+            if let (Some(socket), true) = (send_socket.as_mut(), bound_send_socket) {
+                socket.set_recv_err()?;
+            }
+            None
+        } else {
+            Some(make_recv_socket(config.source_addr, raw)?)
+        };
         Ok(Self {
             privilege_mode: config.privilege_mode,
             protocol: config.protocol,
             src_addr: config.source_addr,
+            src_scope_id,
+            interface_index: config.interface_index,
             ipv4_length_order,
             dest_addr: config.target_addr,
+            port_direction: config.port_direction,
+            bound_send_socket,
             packet_size: config.packet_size,
-            payload_pattern: config.payload_pattern,
+            max_packet_size: config.max_packet_size,
+            payload_pattern: config.payload_pattern.clone(),
             initial_sequence: config.initial_sequence,
             tos: config.tos,
+            dont_fragment: config.dont_fragment,
             icmp_extension_mode: config.icmp_extension_parse_mode,
+            ipv6_extension_header: config.ipv6_extension_header,
             read_timeout: config.read_timeout,
             tcp_connect_timeout: config.tcp_connect_timeout,
+            tcp_mss: config.tcp_mss,
+            udp_recv_err: config.udp_recv_err,
+            validate_icmp_checksum: config.validate_icmp_checksum,
+            invalid_checksums: Arc::new(AtomicUsize::new(0)),
             send_socket,
             recv_socket,
+            recv_buf: vec![0_u8; recv_buf_size(config.max_packet_size)],
             tcp_probes: ArrayVec::new(),
+            udp_probes: Vec::new(),
+            capture: None,
         })
     }
+
+    /// Write the probes sent and the responses received by this channel to a `Capture`.
+    ///
+    /// The `SYN` packets of `TCP` probes are sent by the OS and are not captured, nor are responses
+    /// which are read from a socket rather than received as a packet, such as a `TCP` connection
+    /// being established or an `ICMP` error read from the error queue of a `UDP` socket.
+    #[must_use]
+    pub fn with_capture(self, capture: Capture) -> Self {
+        Self {
+            capture: Some(capture),
+            ..self
+        }
+    }
+
+    /// Count the received `ICMP` packets which are discarded due to an invalid checksum in
+    /// `invalid_checksums`.
+    #[must_use]
+    pub fn with_invalid_checksums(self, invalid_checksums: Arc<AtomicUsize>) -> Self {
+        Self {
+            invalid_checksums,
+            ..self
+        }
+    }
 }
 
 impl<S: Socket> Network for Channel<S> {
@@ -86,6 +200,7 @@ impl<S: Socket> Network for Channel<S> {
     #[instrument(skip_all)]
     fn recv_probe(&mut self) -> Result<Option<Response>> {
         let prob_response = match self.protocol {
+            Protocol::Udp if self.udp_recv_err => self.recv_udp_errors(),
             Protocol::Icmp | Protocol::Udp => self.recv_icmp_probe(),
             Protocol::Tcp => match self.recv_tcp_sockets()? {
                 None => self.recv_icmp_probe(),
@@ -111,8 +226,11 @@ impl<S: Socket> Channel<S> {
                     src_addr,
                     dest_addr,
                     self.packet_size,
-                    self.payload_pattern,
+                    self.max_packet_size,
+                    &self.payload_pattern.0,
+                    self.tos,
                     self.ipv4_length_order,
+                    self.capture.as_mut(),
                 )
             }
             (IpAddr::V6(src_addr), IpAddr::V6(dest_addr), Some(socket)) => {
@@ -122,7 +240,10 @@ impl<S: Socket> Channel<S> {
                     src_addr,
                     dest_addr,
                     self.packet_size,
-                    self.payload_pattern,
+                    self.max_packet_size,
+                    &self.payload_pattern.0,
+                    self.ipv6_extension_header,
+                    self.capture.as_mut(),
                 )
             }
             _ => unreachable!(),
@@ -130,9 +251,39 @@ impl<S: Socket> Channel<S> {
     }
 
     /// Dispatch a UDP probe.
+    ///
+    /// When reading from the socket error queue and the source port varies per probe, each probe
+    /// is sent from its own socket which is retained until it receives an error or is evicted.
     #[instrument(skip_all)]
     fn dispatch_udp_probe(&mut self, probe: Probe) -> Result<()> {
-        match (self.src_addr, self.dest_addr, self.send_socket.as_mut()) {
+        let src_port = probe.src_port;
+        let mut probe_socket: Option<S> = None;
+        if self.udp_recv_err {
+            if self.bound_send_socket {
+                // clear any pending error so that it does not fail the send.
+                if let Some(socket) = self.send_socket.as_mut() {
+                    socket.take_error()?;
+                }
+            } else {
+                let mut socket: S = make_udp_send_socket(
+                    self.src_addr,
+                    self.src_scope_id,
+                    PortDirection::FixedSrc(probe.src_port),
+                    self.tos,
+                    self.dont_fragment,
+                    self.ipv6_extension_header,
+                    self.interface_index,
+                    false,
+                )?;
+                socket.set_recv_err()?;
+                probe_socket = Some(socket);
+            }
+        }
+        let (socket, bound_send_socket) = match probe_socket.as_mut() {
+            Some(socket) => (Some(socket), true),
+            None => (self.send_socket.as_mut(), self.bound_send_socket),
+        };
+        match (self.src_addr, self.dest_addr, socket) {
             (IpAddr::V4(src_addr), IpAddr::V4(dest_addr), Some(socket)) => {
                 ipv4::dispatch_udp_probe(
                     socket,
@@ -140,9 +291,15 @@ impl<S: Socket> Channel<S> {
                     src_addr,
                     dest_addr,
                     self.privilege_mode,
+                    bound_send_socket,
                     self.packet_size,
-                    self.payload_pattern,
+                    self.max_packet_size,
+                    &self.payload_pattern.0,
+                    self.tos,
+                    self.dont_fragment,
                     self.ipv4_length_order,
+                    self.interface_index,
+                    self.capture.as_mut(),
                 )
             }
             (IpAddr::V6(src_addr), IpAddr::V6(dest_addr), Some(socket)) => {
@@ -150,27 +307,53 @@ impl<S: Socket> Channel<S> {
                     socket,
                     probe,
                     src_addr,
+                    self.src_scope_id,
                     dest_addr,
                     self.privilege_mode,
+                    bound_send_socket,
                     self.packet_size,
-                    self.payload_pattern,
+                    self.max_packet_size,
+                    &self.payload_pattern.0,
                     self.initial_sequence,
+                    self.tos,
+                    self.ipv6_extension_header,
+                    self.interface_index,
+                    self.capture.as_mut(),
                 )
             }
             _ => unreachable!(),
+        }?;
+        if let Some(socket) = probe_socket {
+            if self.udp_probes.len() == MAX_UDP_PROBES {
+                self.udp_probes.remove(0);
+            }
+            self.udp_probes.push(UdpProbe::new(socket, src_port));
         }
+        Ok(())
     }
 
     /// Dispatch a TCP probe.
     #[instrument(skip_all)]
     fn dispatch_tcp_probe(&mut self, probe: Probe) -> Result<()> {
         let socket = match (self.src_addr, self.dest_addr) {
-            (IpAddr::V4(src_addr), IpAddr::V4(dest_addr)) => {
-                ipv4::dispatch_tcp_probe(&probe, src_addr, dest_addr, self.tos)
-            }
-            (IpAddr::V6(src_addr), IpAddr::V6(dest_addr)) => {
-                ipv6::dispatch_tcp_probe(&probe, src_addr, dest_addr)
-            }
+            (IpAddr::V4(src_addr), IpAddr::V4(dest_addr)) => ipv4::dispatch_tcp_probe(
+                &probe,
+                src_addr,
+                dest_addr,
+                self.tos,
+                self.dont_fragment,
+                self.tcp_mss,
+                self.interface_index,
+            ),
+            (IpAddr::V6(src_addr), IpAddr::V6(dest_addr)) => ipv6::dispatch_tcp_probe(
+                &probe,
+                src_addr,
+                self.src_scope_id,
+                dest_addr,
+                self.tos,
+                self.tcp_mss,
+                self.interface_index,
+            ),
             _ => unreachable!(),
         }?;
         self.tcp_probes.push(TcpProbe::new(
@@ -185,24 +368,60 @@ impl<S: Socket> Channel<S> {
     /// Generate a `ProbeResponse` for the next available ICMP packet, if any
     #[instrument(skip(self))]
     fn recv_icmp_probe(&mut self) -> Result<Option<Response>> {
-        if self.recv_socket.is_readable(self.read_timeout)? {
-            match self.dest_addr {
-                IpAddr::V4(_) => ipv4::recv_icmp_probe(
-                    &mut self.recv_socket,
-                    self.protocol,
-                    self.icmp_extension_mode,
-                ),
-                IpAddr::V6(_) => ipv6::recv_icmp_probe(
-                    &mut self.recv_socket,
-                    self.protocol,
-                    self.icmp_extension_mode,
-                ),
+        let Some(recv_socket) = self.recv_socket.as_mut() else {
+            return Ok(None);
+        };
+        if recv_socket.is_readable(self.read_timeout)? {
+            let resp = recv_icmp(
+                recv_socket,
+                self.src_addr,
+                &mut self.recv_buf,
+                self.protocol,
+                self.icmp_extension_mode,
+                self.validate_icmp_checksum,
+                self.capture.as_mut(),
+            );
+            match resp {
+                Err(Error::InvalidIcmpChecksum { expected, actual }) => {
+                    tracing::debug!(expected, actual, "discarding packet with invalid checksum");
+                    self.invalid_checksums.fetch_add(1, Ordering::Relaxed);
+                    Ok(None)
+                }
+                resp => resp,
             }
         } else {
             Ok(None)
         }
     }
 
+    /// Generate a `ProbeResponse` for the next `ICMP` error queued on a `UDP` probe socket, if any.
+    ///
+    /// The error queue cannot be polled for readability and so if no error is queued on any socket
+    /// we wait for the read timeout before returning.
+    #[instrument(skip(self))]
+    fn recv_udp_errors(&mut self) -> Result<Option<Response>> {
+        if let (Some(socket), Some(src_port), true) = (
+            self.send_socket.as_mut(),
+            self.port_direction.src(),
+            self.bound_send_socket,
+        ) {
+            if let Some(resp) = recv_udp_socket_err(socket, src_port, self.dest_addr)? {
+                return Ok(Some(resp));
+            }
+        }
+        for index in 0..self.udp_probes.len() {
+            let probe = &mut self.udp_probes[index];
+            if let Some(resp) =
+                recv_udp_socket_err(&mut probe.socket, probe.src_port, self.dest_addr)?
+            {
+                self.udp_probes.remove(index);
+                return Ok(Some(resp));
+            }
+        }
+        std::thread::sleep(self.read_timeout);
+        Ok(None)
+    }
+
     /// Generate synthetic `ProbeResponse` if a TCP socket is connected or if the connection was
     /// refused.
     ///
@@ -263,29 +482,593 @@ impl<S: Socket> TcpProbe<S> {
     }
 }
 
+/// An entry in the UDP probes array.
+struct UdpProbe<S: Socket> {
+    socket: S,
+    src_port: Port,
+}
+
+impl<S: Socket> UdpProbe<S> {
+    pub const fn new(socket: S, src_port: Port) -> Self {
+        Self { socket, src_port }
+    }
+}
+
+/// The size of the buffer for receiving packets for a given `max_packet_size`.
+///
+/// This is never smaller than the default maximum packet size, such that `ICMP` errors which
+/// quote a probe are received in full even if the `max_packet_size` is lowered.
+fn recv_buf_size(max_packet_size: PacketSize) -> usize {
+    usize::from(max_packet_size.0.max(defaults::DEFAULT_MAX_PACKET_SIZE))
+}
+
+/// Read the next `ICMP` error queued on a `UDP` socket bound to `src_port`, if any.
+fn recv_udp_socket_err<S: Socket>(
+    socket: &mut S,
+    src_port: Port,
+    dest_addr: IpAddr,
+) -> Result<Option<Response>> {
+    match dest_addr {
+        IpAddr::V4(_) => ipv4::recv_udp_socket_err(socket, src_port),
+        IpAddr::V6(_) => ipv6::recv_udp_socket_err(socket, src_port),
+    }
+}
+
 /// Make a socket for sending raw `ICMP` packets.
 #[instrument]
-fn make_icmp_send_socket<S: Socket>(addr: IpAddr, raw: bool) -> Result<S> {
-    Ok(match addr {
+fn make_icmp_send_socket<S: Socket>(
+    addr: IpAddr,
+    tos: TypeOfService,
+    extension_header: Ipv6ExtensionHeader,
+    interface_index: Option<u32>,
+    raw: bool,
+) -> Result<S> {
+    let socket = match addr {
         IpAddr::V4(_) => S::new_icmp_send_socket_ipv4(raw),
-        IpAddr::V6(_) => S::new_icmp_send_socket_ipv6(raw),
-    }?)
+        IpAddr::V6(_) => S::new_icmp_send_socket_ipv6(raw).and_then(|mut socket| {
+            socket.set_tclass_v6(u32::from(tos.0))?;
+            socket.set_ipv6_extension_header(extension_header)?;
+            Ok(socket)
+        }),
+    };
+    let mut socket = socket.map_err(|err| unprivileged_icmp_error(err, raw))?;
+    bind_device(&mut socket, interface_index)?;
+    Ok(socket)
 }
 
 /// Make a socket for sending `UDP` packets.
+///
+/// For unprivileged tracing with a fixed source port the socket is bound to the source address
+/// and port once here and then reused for every probe, only the destination varies per probe.
+///
+/// If the source port varies per probe then the source port is used to identify the probe and so a
+/// new socket must be created and bound for each probe, as a socket cannot be rebound (`EINVAL`).
+///
+/// The `Don't Fragment` bit is set by the OS for a non-raw IPv4 socket if `dont_fragment` is set,
+/// for a raw socket it is set in the IPv4 header built for each probe.
+#[allow(clippy::too_many_arguments)]
 #[instrument]
-fn make_udp_send_socket<S: Socket>(addr: IpAddr, raw: bool) -> Result<S> {
-    Ok(match addr {
-        IpAddr::V4(_) => S::new_udp_send_socket_ipv4(raw),
-        IpAddr::V6(_) => S::new_udp_send_socket_ipv6(raw),
-    }?)
+fn make_udp_send_socket<S: Socket>(
+    addr: IpAddr,
+    scope_id: u32,
+    port_direction: PortDirection,
+    tos: TypeOfService,
+    dont_fragment: bool,
+    extension_header: Ipv6ExtensionHeader,
+    interface_index: Option<u32>,
+    raw: bool,
+) -> Result<S> {
+    let mut socket = match addr {
+        IpAddr::V4(_) if raw => S::new_udp_send_socket_ipv4(raw),
+        IpAddr::V4(_) => S::new_udp_send_socket_ipv4(raw).and_then(|mut socket| {
+            socket.set_tos(u32::from(tos.0))?;
+            if dont_fragment {
+                socket.set_dont_fragment()?;
+            }
+            Ok(socket)
+        }),
+        IpAddr::V6(_) => S::new_udp_send_socket_ipv6(raw).and_then(|mut socket| {
+            socket.set_tclass_v6(u32::from(tos.0))?;
+            socket.set_ipv6_extension_header(extension_header)?;
+            Ok(socket)
+        }),
+    }?;
+    if let (false, Some(src_port)) = (raw, port_direction.src()) {
+        let local_addr = match addr {
+            IpAddr::V4(_) => SocketAddr::new(addr, src_port.0),
+            IpAddr::V6(addr) => ipv6::make_local_addr(addr, src_port.0, scope_id),
+        };
+        process_result(local_addr, socket.bind(local_addr))?;
+    }
+    bind_device(&mut socket, interface_index)?;
+    Ok(socket)
+}
+
+/// Receive the next `ICMP` packet, if any, from a `recv_socket` made for `src_addr`.
+///
+/// The packet is parsed as `ICMP` or `ICMPv6`, and the probe response extracted, as per the family
+/// of `src_addr` and hence of the socket, rather than of the target, such that the responses for
+/// probes of either family can be received from a socket of the matching family.
+fn recv_icmp<S: Socket>(
+    recv_socket: &mut S,
+    src_addr: IpAddr,
+    recv_buf: &mut [u8],
+    protocol: Protocol,
+    icmp_extension_mode: IcmpExtensionParseMode,
+    validate_icmp_checksum: bool,
+    capture: Option<&mut Capture>,
+) -> Result<Option<Response>> {
+    match src_addr {
+        IpAddr::V4(_) => ipv4::recv_icmp_probe(
+            recv_socket,
+            recv_buf,
+            protocol,
+            icmp_extension_mode,
+            validate_icmp_checksum,
+            capture,
+        ),
+        IpAddr::V6(src_addr) => ipv6::recv_icmp_probe(
+            recv_socket,
+            recv_buf,
+            protocol,
+            icmp_extension_mode,
+            validate_icmp_checksum.then_some(src_addr),
+            capture,
+        ),
+    }
 }
 
 /// Make a socket for receiving raw `ICMP` packets.
+///
+/// A socket receives the packets of a single family only and so a trace of both an IPv4 and an
+/// IPv6 target requires a receive socket for each.  A dual-stack socket, i.e. an IPv6 socket with
+/// `IPV6_V6ONLY` disabled, cannot be used in place of the pair as IPv4 packets are delivered to it
+/// as IPv4-mapped IPv6 addresses only for `UDP` and `TCP`, whereas `ICMP` and `ICMPv6` are
+/// distinct protocols and so an `ICMPv6` socket never receives `ICMP` packets.
 #[instrument]
 fn make_recv_socket<S: Socket>(addr: IpAddr, raw: bool) -> Result<S> {
-    Ok(match addr {
+    let socket = match addr {
         IpAddr::V4(ipv4addr) => S::new_recv_socket_ipv4(ipv4addr, raw),
         IpAddr::V6(ipv6addr) => S::new_recv_socket_ipv6(ipv6addr, raw),
-    }?)
+    };
+    socket.map_err(|err| unprivileged_icmp_error(err, raw))
+}
+
+/// The hint given when a datagram `ICMP` socket is not permitted.
+///
+/// On Linux datagram `ICMP` sockets are only permitted for groups in `net.ipv4.ping_group_range`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const UNPRIVILEGED_ICMP_DENIED_HINT: &str =
+    "check the net.ipv4.ping_group_range sysctl includes this group";
+
+/// The hint given when a datagram `ICMP` socket is not permitted.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+const UNPRIVILEGED_ICMP_DENIED_HINT: &str = "datagram ICMP sockets are not permitted on this host";
+
+/// Describe why a datagram `ICMP` socket could not be created.
+///
+/// Tracing with datagram `ICMP` sockets is only supported on `macOS`, as other platforms do not
+/// allow the `IP_HDRINCL` socket option to be set on them, and on Windows they are not
+/// supported at all.
+///
+/// All other errors are returned unchanged.
+fn unprivileged_icmp_error(err: IoError, raw: bool) -> Error {
+    match err {
+        IoError::Other(_, IoOperation::SetHeaderIncluded) if !raw => {
+            Error::UnprivilegedIcmpUnavailable(format!(
+                "{err} (unprivileged ICMP tracing is only supported on macOS)"
+            ))
+        }
+        _ if raw => Error::IoError(err),
+        _ => match err.kind() {
+            ErrorKind::PermissionDenied => Error::UnprivilegedIcmpUnavailable(format!(
+                "{err} ({UNPRIVILEGED_ICMP_DENIED_HINT})"
+            )),
+            ErrorKind::Unsupported => Error::UnprivilegedIcmpUnavailable(format!(
+                "{err} (not supported on this platform)"
+            )),
+            _ => Error::IoError(err),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::socket::{MockSocket, QueuedError};
+    use crate::probe::ResponseSeq;
+    use std::io;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+    use std::str::FromStr;
+    use std::sync::Mutex;
+
+    static MTX: Mutex<()> = Mutex::new(());
+
+    const SOURCE_ADDR: &str = "fd7a:115c:a1e0:ab12:4843:cd96:6263:82a";
+    const TARGET_ADDR: &str = "2a00:1450:4009:815::200e";
+
+    fn icmp_config(unprivileged_fallback: bool) -> ChannelConfig {
+        ChannelConfig {
+            unprivileged_fallback,
+            source_addr: IpAddr::from_str(SOURCE_ADDR).unwrap(),
+            target_addr: IpAddr::from_str(TARGET_ADDR).unwrap(),
+            ..ChannelConfig::default()
+        }
+    }
+
+    fn permission_denied() -> IoError {
+        IoError::Other(
+            io::Error::from(io::ErrorKind::PermissionDenied),
+            IoOperation::NewSocket,
+        )
+    }
+
+    fn mock_send_socket() -> MockSocket {
+        let mut mocket = MockSocket::new();
+        mocket.expect_set_tclass_v6().returning(|_| Ok(()));
+        mocket
+            .expect_set_ipv6_extension_header()
+            .returning(|_| Ok(()));
+        mocket
+    }
+
+    #[test]
+    fn test_connect_unprivileged_fallback() -> anyhow::Result<()> {
+        let _m = MTX.lock();
+
+        let send_ctx = MockSocket::new_icmp_send_socket_ipv6_context();
+        send_ctx
+            .expect()
+            .withf(|raw| *raw)
+            .times(1)
+            .returning(|_| Err(permission_denied()));
+        send_ctx
+            .expect()
+            .withf(|raw| !*raw)
+            .times(1)
+            .returning(|_| Ok(mock_send_socket()));
+        let recv_ctx = MockSocket::new_recv_socket_ipv6_context();
+        recv_ctx
+            .expect()
+            .withf(|addr: &Ipv6Addr, raw| addr.to_string() == SOURCE_ADDR && !*raw)
+            .times(1)
+            .returning(|_, _| Ok(MockSocket::new()));
+
+        let channel = Channel::<MockSocket>::connect(&icmp_config(true))?;
+        assert_eq!(PrivilegeMode::Unprivileged, channel.privilege_mode);
+        Ok(())
+    }
+
+    #[test]
+    fn test_connect_unprivileged_fallback_ipv4() -> anyhow::Result<()> {
+        let _m = MTX.lock();
+
+        let send_ctx = MockSocket::new_icmp_send_socket_ipv4_context();
+        send_ctx
+            .expect()
+            .withf(|raw| *raw)
+            .times(1)
+            .returning(|_| Err(permission_denied()));
+        send_ctx
+            .expect()
+            .withf(|raw| !*raw)
+            .times(1)
+            .returning(|_| Ok(MockSocket::new()));
+        let recv_ctx = MockSocket::new_recv_socket_ipv4_context();
+        recv_ctx
+            .expect()
+            .withf(|addr: &Ipv4Addr, raw| *addr == Ipv4Addr::LOCALHOST && !*raw)
+            .times(1)
+            .returning(|_, _| Ok(MockSocket::new()));
+
+        let config = ChannelConfig {
+            source_addr: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            target_addr: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            ..icmp_config(true)
+        };
+        let channel = Channel::<MockSocket>::connect(&config)?;
+        assert_eq!(PrivilegeMode::Unprivileged, channel.privilege_mode);
+        Ok(())
+    }
+
+    #[test]
+    fn test_connect_no_unprivileged_fallback() {
+        let _m = MTX.lock();
+
+        let send_ctx = MockSocket::new_icmp_send_socket_ipv6_context();
+        send_ctx
+            .expect()
+            .withf(|raw| *raw)
+            .times(1)
+            .returning(|_| Err(permission_denied()));
+
+        let err = Channel::<MockSocket>::connect(&icmp_config(false)).err();
+        assert!(matches!(err, Some(Error::IoError(_))));
+    }
+
+    #[test]
+    fn test_connect_unprivileged_unsupported() {
+        let _m = MTX.lock();
+
+        let send_ctx = MockSocket::new_icmp_send_socket_ipv6_context();
+        send_ctx.expect().times(1).returning(|_| {
+            Err(IoError::Other(
+                io::Error::from(io::ErrorKind::Unsupported),
+                IoOperation::NewSocket,
+            ))
+        });
+
+        let config = ChannelConfig {
+            privilege_mode: PrivilegeMode::Unprivileged,
+            ..icmp_config(false)
+        };
+        let err = Channel::<MockSocket>::connect(&config).err();
+        assert!(matches!(err, Some(Error::UnprivilegedIcmpUnavailable(_))));
+    }
+
+    #[test]
+    fn test_connect_unprivileged_unavailable() {
+        let _m = MTX.lock();
+
+        let send_ctx = MockSocket::new_icmp_send_socket_ipv6_context();
+        send_ctx
+            .expect()
+            .times(2)
+            .returning(|_| Err(permission_denied()));
+
+        let err = Channel::<MockSocket>::connect(&icmp_config(true)).err();
+        assert!(matches!(
+            err,
+            Some(Error::UnprivilegedIcmpUnavailable(s)) if s.ends_with(&format!("({UNPRIVILEGED_ICMP_DENIED_HINT})"))
+        ));
+    }
+
+    #[test]
+    fn test_connect_unprivileged_header_included_unsupported() {
+        let _m = MTX.lock();
+
+        let send_ctx = MockSocket::new_icmp_send_socket_ipv4_context();
+        send_ctx.expect().times(1).returning(|_| {
+            Err(IoError::Other(
+                io::Error::other("protocol not available"),
+                IoOperation::SetHeaderIncluded,
+            ))
+        });
+
+        let config = ChannelConfig {
+            privilege_mode: PrivilegeMode::Unprivileged,
+            source_addr: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            target_addr: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            ..icmp_config(false)
+        };
+        let err = Channel::<MockSocket>::connect(&config).err();
+        assert!(matches!(
+            err,
+            Some(Error::UnprivilegedIcmpUnavailable(s)) if s.ends_with("(unprivileged ICMP tracing is only supported on macOS)")
+        ));
+    }
+
+    fn udp_recv_err_config(port_direction: PortDirection) -> ChannelConfig {
+        ChannelConfig {
+            protocol: Protocol::Udp,
+            privilege_mode: PrivilegeMode::Unprivileged,
+            port_direction,
+            udp_recv_err: true,
+            ..icmp_config(false)
+        }
+    }
+
+    #[test]
+    fn test_connect_udp_recv_err_bound() -> anyhow::Result<()> {
+        let _m = MTX.lock();
+
+        let send_ctx = MockSocket::new_udp_send_socket_ipv6_context();
+        send_ctx.expect().times(1).returning(|_| {
+            let mut mocket = mock_send_socket();
+            mocket.expect_bind().times(1).returning(|_| Ok(()));
+            mocket.expect_set_recv_err().times(1).returning(|| Ok(()));
+            Ok(mocket)
+        });
+
+        let channel = Channel::<MockSocket>::connect(&udp_recv_err_config(
+            PortDirection::FixedSrc(Port(5000)),
+        ))?;
+        assert!(channel.bound_send_socket);
+        assert!(channel.recv_socket.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_connect_udp_dont_fragment_ipv4() -> anyhow::Result<()> {
+        let _m = MTX.lock();
+
+        let send_ctx = MockSocket::new_udp_send_socket_ipv4_context();
+        send_ctx.expect().times(1).returning(|_| {
+            let mut mocket = MockSocket::new();
+            mocket.expect_set_tos().times(1).returning(|_| Ok(()));
+            mocket
+                .expect_set_dont_fragment()
+                .times(1)
+                .returning(|| Ok(()));
+            mocket.expect_bind().times(1).returning(|_| Ok(()));
+            Ok(mocket)
+        });
+        let recv_ctx = MockSocket::new_recv_socket_ipv4_context();
+        recv_ctx
+            .expect()
+            .times(1)
+            .returning(|_, _| Ok(MockSocket::new()));
+
+        let config = ChannelConfig {
+            protocol: Protocol::Udp,
+            privilege_mode: PrivilegeMode::Unprivileged,
+            port_direction: PortDirection::FixedSrc(Port(5000)),
+            source_addr: IpAddr::from([192, 168, 1, 1]),
+            target_addr: IpAddr::from([10, 0, 0, 1]),
+            dont_fragment: true,
+            ..ChannelConfig::default()
+        };
+        Channel::<MockSocket>::connect(&config)?;
+        Ok(())
+    }
+
+    // Test that the `Don't Fragment` option is a no-op for IPv6.
+    #[test]
+    fn test_connect_udp_dont_fragment_ipv6() -> anyhow::Result<()> {
+        let _m = MTX.lock();
+
+        let send_ctx = MockSocket::new_udp_send_socket_ipv6_context();
+        send_ctx.expect().times(1).returning(|_| {
+            let mut mocket = mock_send_socket();
+            mocket.expect_bind().times(1).returning(|_| Ok(()));
+            mocket.expect_set_dont_fragment().never();
+            Ok(mocket)
+        });
+        let recv_ctx = MockSocket::new_recv_socket_ipv6_context();
+        recv_ctx
+            .expect()
+            .times(1)
+            .returning(|_, _| Ok(MockSocket::new()));
+
+        let config = ChannelConfig {
+            dont_fragment: true,
+            udp_recv_err: false,
+            ..udp_recv_err_config(PortDirection::FixedSrc(Port(5000)))
+        };
+        Channel::<MockSocket>::connect(&config)?;
+        Ok(())
+    }
+
+    // Test that a received packet is parsed as per the family of the receive socket.
+    #[test]
+    fn test_recv_icmp_family() -> anyhow::Result<()> {
+        let mut mocket = MockSocket::new();
+        mocket.expect_read().times(1).returning(|buf: &mut [u8]| {
+            let packet = hex_literal::hex!(
+                "
+                45 00 00 1c 00 00 00 00 40 01 00 00 0a 00 00 01
+                c0 a8 01 01 00 00 00 00 04 d2 80 e8
+                "
+            );
+            buf[..packet.len()].copy_from_slice(&packet);
+            Ok(packet.len())
+        });
+        mocket
+            .expect_recv_from()
+            .times(1)
+            .returning(|buf: &mut [u8]| {
+                let packet = [0x81, 0x00, 0x00, 0x00, 0x04, 0xd2, 0x80, 0xe8];
+                buf[..packet.len()].copy_from_slice(&packet);
+                Ok((
+                    packet.len(),
+                    Some(SocketAddr::new(IpAddr::from_str(TARGET_ADDR).unwrap(), 0)),
+                ))
+            });
+        let mut buf = [0_u8; 1024];
+        for (src_addr, host) in [
+            (IpAddr::from([192, 168, 1, 1]), IpAddr::from([10, 0, 0, 1])),
+            (
+                IpAddr::from_str(SOURCE_ADDR)?,
+                IpAddr::from_str(TARGET_ADDR)?,
+            ),
+        ] {
+            let resp = recv_icmp(
+                &mut mocket,
+                src_addr,
+                &mut buf,
+                Protocol::Icmp,
+                IcmpExtensionParseMode::Disabled,
+                false,
+                None,
+            )?;
+            let Some(Response::EchoReply(data, _)) = resp else {
+                panic!("expected echo reply: {resp:?}")
+            };
+            assert_eq!(host, data.addr);
+            let ResponseSeq::Icmp(seq) = data.resp_seq else {
+                panic!("expected icmp response")
+            };
+            assert_eq!((1234, 33000), (seq.identifier, seq.sequence));
+        }
+        Ok(())
+    }
+
+    // Test that a received packet with an invalid checksum is discarded and counted.
+    #[test]
+    fn test_recv_icmp_probe_invalid_checksum() -> anyhow::Result<()> {
+        let _m = MTX.lock();
+
+        let send_ctx = MockSocket::new_icmp_send_socket_ipv6_context();
+        send_ctx
+            .expect()
+            .times(1)
+            .returning(|_| Ok(mock_send_socket()));
+        let recv_ctx = MockSocket::new_recv_socket_ipv6_context();
+        recv_ctx
+            .expect()
+            .times(1)
+            .returning(|_, _| Ok(MockSocket::new()));
+        let invalid_checksums = Arc::new(AtomicUsize::new(0));
+        let mut channel = Channel::<MockSocket>::connect(&icmp_config(false))?
+            .with_invalid_checksums(Arc::clone(&invalid_checksums));
+        let mut mocket = MockSocket::new();
+        mocket.expect_is_readable().times(1).returning(|_| Ok(true));
+        mocket
+            .expect_recv_from()
+            .times(1)
+            .returning(|buf: &mut [u8]| {
+                let packet = [0x03, 0x00, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00];
+                buf[..packet.len()].copy_from_slice(&packet);
+                Ok((
+                    packet.len(),
+                    Some(SocketAddr::new(IpAddr::from_str(TARGET_ADDR).unwrap(), 0)),
+                ))
+            });
+        channel.recv_socket = Some(mocket);
+
+        assert!(channel.recv_probe()?.is_none());
+        assert_eq!(1, invalid_checksums.load(Ordering::Relaxed));
+        Ok(())
+    }
+
+    #[test]
+    fn test_recv_udp_recv_err_probe_socket() -> anyhow::Result<()> {
+        let _m = MTX.lock();
+
+        let send_ctx = MockSocket::new_udp_send_socket_ipv6_context();
+        send_ctx
+            .expect()
+            .times(1)
+            .returning(|_| Ok(mock_send_socket()));
+        let mut channel = Channel::<MockSocket>::connect(&udp_recv_err_config(
+            PortDirection::FixedDest(Port(33434)),
+        ))?;
+        let mut mocket = MockSocket::new();
+        mocket.expect_recv_err().times(1).returning(|| {
+            Ok(Some(QueuedError {
+                origin: IpAddr::from_str("2604:a880:ffff:6:1::41c").unwrap(),
+                dest: Some(SocketAddr::new(
+                    IpAddr::from_str(TARGET_ADDR).unwrap(),
+                    33434,
+                )),
+                icmp_type: 3,
+                icmp_code: 0,
+                info: 0,
+            }))
+        });
+        channel.udp_probes.push(UdpProbe::new(mocket, Port(5001)));
+
+        let resp = channel.recv_probe()?;
+        let Some(Response::TimeExceeded(data, _, None)) = resp else {
+            panic!("expected TimeExceeded, got {resp:?}")
+        };
+        assert_eq!(IpAddr::from_str("2604:a880:ffff:6:1::41c")?, data.addr);
+        let ResponseSeq::Udp(seq) = data.resp_seq else {
+            panic!("expected UDP sequence")
+        };
+        assert_eq!(5001, seq.src_port);
+        assert_eq!(33434, seq.dest_port);
+        assert!(channel.udp_probes.is_empty());
+        Ok(())
+    }
 }
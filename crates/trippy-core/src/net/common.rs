@@ -1,7 +1,10 @@
-use crate::error::{Error, IoResult, Result};
-use crate::net::platform::in_progress_error;
+use crate::error::{Error, IoError, IoResult, Result};
+use crate::net::platform::{host_unreachable_error, in_progress_error, network_unreachable_error};
+use crate::net::socket::Socket;
+use crate::types::PacketSize;
 use std::io::ErrorKind;
 use std::net::SocketAddr;
+use trippy_packet::tcp::TcpPacket;
 
 /// Helper function to convert an `IoResult` to a `TraceResult` with special handling for
 /// `AddressNotAvailable`.
@@ -16,17 +19,111 @@ pub fn process_result(addr: SocketAddr, res: IoResult<()>) -> Result<()> {
                     ErrorKind::AddrInUse | ErrorKind::AddrNotAvailable => {
                         Err(Error::AddressNotAvailable(addr))
                     }
-                    _ => Err(Error::IoError(err)),
+                    _ => Err(process_io_error(err)),
                 }
             }
         }
     }
 }
 
+/// Helper function to convert an `IoError` to a `TraceResult` with special handling for common
+/// socket errors.
+///
+/// Host unreachable, network unreachable and permission denied errors are mapped to dedicated
+/// `Error` variants, all other errors are mapped to `Error::IoError`.
+pub fn process_io_error(err: IoError) -> Error {
+    if err.kind() == ErrorKind::PermissionDenied {
+        Error::PermissionDenied(err)
+    } else if err.raw_os_error() == host_unreachable_error().raw_os_error() {
+        Error::HostUnreachable(err)
+    } else if err.raw_os_error() == network_unreachable_error().raw_os_error() {
+        Error::NetworkUnreachable(err)
+    } else {
+        Error::IoError(err)
+    }
+}
+
+/// Helper function to restrict a socket to the interface with `interface_index`, if any.
+///
+/// Permission being denied, as it is on Linux kernels prior to 5.7 without `CAP_NET_RAW`, is mapped
+/// to `Error::InterfaceBindDenied`, a platform which cannot bind a socket to an interface is
+/// mapped to `Error::InterfaceBindUnsupported` and all other errors are mapped to `Error::IoError`.
+pub fn bind_device<S: Socket>(socket: &mut S, interface_index: Option<u32>) -> Result<()> {
+    match interface_index.map(|index| (index, socket.bind_device(index))) {
+        Some((index, Err(err))) if err.kind() == ErrorKind::PermissionDenied => {
+            Err(Error::InterfaceBindDenied(index, err))
+        }
+        Some((index, Err(err))) if err.kind() == ErrorKind::Unsupported => {
+            Err(Error::InterfaceBindUnsupported(index))
+        }
+        Some((_, Err(err))) => Err(Error::IoError(err)),
+        Some((_, Ok(()))) | None => Ok(()),
+    }
+}
+
+/// Helper function to validate that `packet_size` is between `min_packet_size` and
+/// `max_packet_size` inclusive, and return it.
+///
+/// A `packet_size` which exceeds the `max_packet_size` is an `Error::PacketSizeExceedsMax`, such
+/// that the error states the configured limit.
+pub fn validate_packet_size(
+    packet_size: PacketSize,
+    min_packet_size: usize,
+    max_packet_size: PacketSize,
+) -> Result<usize> {
+    let packet_size = usize::from(packet_size.0);
+    let max_packet_size = usize::from(max_packet_size.0);
+    if packet_size < min_packet_size {
+        Err(Error::InvalidPacketSize(packet_size))
+    } else if packet_size > max_packet_size {
+        Err(Error::PacketSizeExceedsMax(packet_size, max_packet_size))
+    } else {
+        Ok(packet_size)
+    }
+}
+
+/// Helper function to treat a malformed packet as a non-fatal parse failure.
+///
+/// A received packet which cannot be parsed, such as one which is truncated, is discarded and
+/// `Ok(None)` is returned, all other errors are returned unchanged.
+pub fn discard_malformed<T>(res: Result<Option<T>>) -> Result<Option<T>> {
+    match res {
+        Err(Error::PacketError(err)) => {
+            tracing::debug!(%err, "discarding malformed packet");
+            Ok(None)
+        }
+        res => res,
+    }
+}
+
+/// Get the src and dest ports from the original `TcpPacket` packet embedded in an ICMP payload.
+///
+/// Unlike the embedded `ICMP` and `UDP` packets, which have a minimum header size of 8 bytes, the
+/// `TCP` packet header is a minimum of 20 bytes.
+///
+/// The `ICMP` packets we are extracting these from, such as `TimeExceeded`, only guarantee that 8
+/// bytes of the original packet (plus the IP header) be returned and so we may not have a complete
+/// TCP packet.
+///
+/// We therefore have to detect this situation and ensure we provide buffer a large enough for a
+/// complete TCP packet header.
+pub fn extract_tcp_ports(nested_tcp: &[u8]) -> Result<(u16, u16)> {
+    if nested_tcp.len() < TcpPacket::minimum_packet_size() {
+        let mut buf = [0_u8; TcpPacket::minimum_packet_size()];
+        buf[..nested_tcp.len()].copy_from_slice(nested_tcp);
+        let tcp_packet = TcpPacket::new_view(&buf)?;
+        Ok((tcp_packet.get_source(), tcp_packet.get_destination()))
+    } else {
+        let tcp_packet = TcpPacket::new_view(nested_tcp)?;
+        Ok((tcp_packet.get_source(), tcp_packet.get_destination()))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::error::{IoError, IoOperation};
+    use crate::net::socket::MockSocket;
     use std::io;
     use std::net::{Ipv4Addr, SocketAddrV4};
 
@@ -70,10 +167,86 @@ mod tests {
         assert!(matches!(trace_err, Error::AddressNotAvailable(ADDR)));
     }
 
+    #[test]
+    fn test_host_unreachable_err() {
+        let res = Err(IoError::Connect(host_unreachable_error(), ADDR));
+        let trace_res = process_result(ADDR, res);
+        let trace_err = trace_res.unwrap_err();
+        assert!(matches!(trace_err, Error::HostUnreachable(_)));
+    }
+
+    #[test]
+    fn test_network_unreachable_err() {
+        let io_error = IoError::SendTo(network_unreachable_error(), ADDR);
+        let trace_err = process_io_error(io_error);
+        assert!(matches!(trace_err, Error::NetworkUnreachable(_)));
+    }
+
+    #[test]
+    fn test_permission_denied_err() {
+        let res = Err(IoError::Other(
+            io::Error::from(ErrorKind::PermissionDenied),
+            IoOperation::Read,
+        ));
+        let trace_res = process_result(ADDR, res);
+        let trace_err = trace_res.unwrap_err();
+        assert!(matches!(trace_err, Error::PermissionDenied(_)));
+    }
+
+    #[test]
+    fn test_bind_device_permission_denied_err() {
+        let mut mocket = MockSocket::new();
+        mocket.expect_bind_device().times(1).returning(|_| {
+            Err(IoError::Other(
+                io::Error::from(ErrorKind::PermissionDenied),
+                IoOperation::BindDevice,
+            ))
+        });
+        let trace_err = bind_device(&mut mocket, Some(7)).unwrap_err();
+        assert!(matches!(trace_err, Error::InterfaceBindDenied(7, _)));
+    }
+
+    #[test]
+    fn test_bind_device_unsupported_err() {
+        let mut mocket = MockSocket::new();
+        mocket.expect_bind_device().times(1).returning(|_| {
+            Err(IoError::Other(
+                io::Error::from(ErrorKind::Unsupported),
+                IoOperation::BindDevice,
+            ))
+        });
+        let trace_err = bind_device(&mut mocket, Some(7)).unwrap_err();
+        assert!(matches!(trace_err, Error::InterfaceBindUnsupported(7)));
+    }
+
+    #[test]
+    fn test_bind_device_none() {
+        let mut mocket = MockSocket::new();
+        mocket.expect_bind_device().never();
+        assert!(bind_device(&mut mocket, None).is_ok());
+    }
+
     #[test]
     fn test_in_progress_ok() {
         let res = Err(IoError::Other(in_progress_error(), IoOperation::Select));
         let trace_res = process_result(ADDR, res);
         assert!(trace_res.is_ok());
     }
+
+    #[test]
+    fn test_extract_tcp_ports() {
+        let buf = hex_literal::hex!(
+            "
+            80 fd 00 50 61 f2 4d 4a 00 00 00 00 b0 02 ff ff
+            14 05 00 00
+            "
+        );
+        assert_eq!((33021, 80), extract_tcp_ports(&buf).unwrap());
+    }
+
+    #[test]
+    fn test_extract_tcp_ports_truncated() {
+        let buf = hex_literal::hex!("80 fd 00 50 61 f2 4d 4a");
+        assert_eq!((33021, 80), extract_tcp_ports(&buf).unwrap());
+    }
 }
@@ -1,7 +1,9 @@
 use crate::error::Error;
 use crate::probe::{Extension, Extensions, MplsLabelStack, MplsLabelStackMember, UnknownExtension};
 use trippy_packet::icmp_extension::extension_header::ExtensionHeaderPacket;
-use trippy_packet::icmp_extension::extension_object::{ClassNum, ExtensionObjectPacket};
+use trippy_packet::icmp_extension::extension_object::{
+    ClassNum, ClassSubType, ExtensionObjectPacket,
+};
 use trippy_packet::icmp_extension::extension_structure::ExtensionsPacket;
 use trippy_packet::icmp_extension::mpls_label_stack::MplsLabelStackPacket;
 use trippy_packet::icmp_extension::mpls_label_stack_member::MplsLabelStackMemberPacket;
@@ -9,6 +11,9 @@ use trippy_packet::icmp_extension::mpls_label_stack_member::MplsLabelStackMember
 /// The supported ICMP extension version number.
 const ICMP_EXTENSION_VERSION: u8 = 2;
 
+/// The class sub-type of the MPLS Label Stack object (RFC 4950).
+const MPLS_LABEL_STACK_SUBTYPE: ClassSubType = ClassSubType(1);
+
 impl TryFrom<&[u8]> for Extensions {
     type Error = Error;
 
@@ -28,14 +33,15 @@ impl TryFrom<ExtensionsPacket<'_>> for Extensions {
         let extensions = value
             .objects()
             .flat_map(ExtensionObjectPacket::new_view)
-            .map(|obj| match obj.get_class_num() {
-                ClassNum::MultiProtocolLabelSwitchingLabelStack => {
+            .filter_map(|obj| match (obj.get_class_num(), obj.get_class_subtype()) {
+                (ClassNum::MultiProtocolLabelSwitchingLabelStack, MPLS_LABEL_STACK_SUBTYPE) => {
                     MplsLabelStackPacket::new_view(obj.payload())
+                        .ok()
                         .map(|mpls| Extension::Mpls(MplsLabelStack::from(mpls)))
                 }
-                _ => Ok(Extension::Unknown(UnknownExtension::from(obj))),
+                _ => Some(Extension::Unknown(UnknownExtension::from(obj))),
             })
-            .collect::<Result<_, _>>()?;
+            .collect();
         Ok(Self { extensions })
     }
 }
@@ -118,6 +124,42 @@ mod tests {
         }
     }
 
+    /// Convert a malformed MPLS extension, which has no label stack, followed by a single MPLS
+    /// extension which contains one label.
+    ///
+    /// The malformed extension is skipped.
+    #[test]
+    fn test_convert_malformed_mpls_extensions() {
+        let buf = hex_literal::hex!("20 00 00 00 00 04 01 01 00 08 01 01 05 dd 11 01");
+        let exts = Extensions::try_from(buf.as_slice()).unwrap();
+        assert_eq!(1, exts.extensions.len());
+        match &exts.extensions[0] {
+            Extension::Mpls(mpls) => {
+                assert_eq!(1, mpls.members.len());
+                assert_eq!(24017, mpls.members[0].label);
+                assert_eq!(1, mpls.members[0].ttl);
+                assert_eq!(0, mpls.members[0].exp);
+                assert_eq!(1, mpls.members[0].bos);
+            }
+            Extension::Unknown(_) => panic!("expected Extension::Mpls"),
+        }
+    }
+
+    /// Convert an MPLS extension with an unknown class sub-type.
+    #[test]
+    fn test_convert_mpls_unknown_subtype_extensions() {
+        let buf = hex_literal::hex!("20 00 96 53 00 08 01 02 05 dd 11 01");
+        let exts = Extensions::try_from(buf.as_slice()).unwrap();
+        assert_eq!(1, exts.extensions.len());
+        match &exts.extensions[0] {
+            Extension::Unknown(unknown) => {
+                assert_eq!(0x01, unknown.class_num);
+                assert_eq!(0x02, unknown.class_subtype);
+            }
+            Extension::Mpls(_) => panic!("expected Extension::Unknown"),
+        }
+    }
+
     /// Convert an extension with an unknown header version.
     #[test]
     fn test_convert_unknown_version() {
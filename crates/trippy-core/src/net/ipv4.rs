@@ -1,41 +1,36 @@
 use crate::config::IcmpExtensionParseMode;
 use crate::error::{Error, Result};
-use crate::net::channel::MAX_PACKET_SIZE;
-use crate::net::common::process_result;
+use crate::net::capture::Capture;
+use crate::net::common::{
+    bind_device, discard_malformed, extract_tcp_ports, process_io_error, process_result,
+    validate_packet_size,
+};
+use crate::net::payload::{make_payload, PayloadHeader};
 use crate::net::platform;
 use crate::net::socket::{Socket, SocketError};
 use crate::probe::{
     Extensions, IcmpPacketCode, Probe, Response, ResponseData, ResponseSeq, ResponseSeqIcmp,
     ResponseSeqTcp, ResponseSeqUdp,
 };
-use crate::types::{PacketSize, PayloadPattern, Sequence, TraceId, TypeOfService};
+use crate::types::{PacketSize, TypeOfService};
 use crate::{Flags, Port, PrivilegeMode, Protocol};
 use std::io::ErrorKind;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::time::SystemTime;
 use tracing::instrument;
-use trippy_packet::checksum::{icmp_ipv4_checksum, udp_ipv4_checksum};
+use trippy_packet::checksum::{icmp_ipv4_checksum, ipv4_header_checksum, udp_ipv4_checksum};
 use trippy_packet::icmpv4::destination_unreachable::DestinationUnreachablePacket;
 use trippy_packet::icmpv4::echo_reply::EchoReplyPacket;
 use trippy_packet::icmpv4::echo_request::EchoRequestPacket;
+use trippy_packet::icmpv4::parameter_problem::ParameterProblemPacket;
 use trippy_packet::icmpv4::time_exceeded::TimeExceededPacket;
 use trippy_packet::icmpv4::{IcmpCode, IcmpPacket, IcmpTimeExceededCode, IcmpType};
 use trippy_packet::ipv4::Ipv4Packet;
-use trippy_packet::tcp::TcpPacket;
 use trippy_packet::udp::UdpPacket;
 use trippy_packet::IpProtocol;
 
-/// The maximum size of UDP packet we allow.
-const MAX_UDP_PACKET_BUF: usize = MAX_PACKET_SIZE - Ipv4Packet::minimum_packet_size();
-
-/// The maximum size of UDP payload we allow.
-const MAX_UDP_PAYLOAD_BUF: usize = MAX_UDP_PACKET_BUF - UdpPacket::minimum_packet_size();
-
-/// The maximum size of ICMP packet we allow.
-const MAX_ICMP_PACKET_BUF: usize = MAX_PACKET_SIZE - Ipv4Packet::minimum_packet_size();
-
-/// The maximum size of ICMP payload we allow.
-const MAX_ICMP_PAYLOAD_BUF: usize = MAX_ICMP_PACKET_BUF - IcmpPacket::minimum_packet_size();
+/// The code of a `DestinationUnreachable` ICMP packet for which fragmentation was needed.
+const FRAGMENTATION_NEEDED: IcmpCode = IcmpCode(4);
 
 /// The minimum size of ICMP packets we allow.
 const MIN_PACKET_SIZE_ICMP: usize =
@@ -45,31 +40,42 @@ const MIN_PACKET_SIZE_ICMP: usize =
 const MIN_PACKET_SIZE_UDP: usize =
     Ipv4Packet::minimum_packet_size() + UdpPacket::minimum_packet_size();
 
+/// The minimum size of packets we allow for a given `Protocol`.
+///
+/// The packet size does not apply to TCP probes and so any size is allowed.
+pub const fn min_packet_size(protocol: Protocol) -> usize {
+    match protocol {
+        Protocol::Icmp => MIN_PACKET_SIZE_ICMP,
+        Protocol::Udp => MIN_PACKET_SIZE_UDP,
+        Protocol::Tcp => 0,
+    }
+}
+
 /// The value for the IPv4 `flags_and_fragment_offset` field to set the `Don't fragment` bit.
 ///
 /// 0100 0000 0000 0000
 const DONT_FRAGMENT: u16 = 0x4000;
 
-#[instrument(skip(icmp_send_socket, probe))]
+#[allow(clippy::too_many_arguments)]
+#[instrument(skip(icmp_send_socket, probe, capture))]
 pub fn dispatch_icmp_probe<S: Socket>(
     icmp_send_socket: &mut S,
     probe: Probe,
     src_addr: Ipv4Addr,
     dest_addr: Ipv4Addr,
     packet_size: PacketSize,
-    payload_pattern: PayloadPattern,
+    max_packet_size: PacketSize,
+    payload_pattern: &[u8],
+    tos: TypeOfService,
     ipv4_byte_order: platform::Ipv4ByteOrder,
+    capture: Option<&mut Capture>,
 ) -> Result<()> {
-    let mut ipv4_buf = [0_u8; MAX_PACKET_SIZE];
-    let mut icmp_buf = [0_u8; MAX_ICMP_PACKET_BUF];
-    let packet_size = usize::from(packet_size.0);
-    if !(MIN_PACKET_SIZE_ICMP..=MAX_PACKET_SIZE).contains(&packet_size) {
-        return Err(Error::InvalidPacketSize(packet_size));
-    }
+    let packet_size = validate_packet_size(packet_size, MIN_PACKET_SIZE_ICMP, max_packet_size)?;
+    let mut ipv4_buf = vec![0_u8; packet_size];
+    let mut icmp_buf = vec![0_u8; packet_size - Ipv4Packet::minimum_packet_size()];
     let echo_request = make_echo_request_icmp_packet(
         &mut icmp_buf,
-        probe.identifier,
-        probe.sequence,
+        &probe,
         icmp_payload_size(packet_size),
         payload_pattern,
     )?;
@@ -81,43 +87,75 @@ pub fn dispatch_icmp_probe<S: Socket>(
         dest_addr,
         probe.ttl.0,
         0,
+        tos,
         echo_request.packet(),
     )?;
     let remote_addr = SocketAddr::new(IpAddr::V4(dest_addr), 0);
-    icmp_send_socket.send_to(ipv4.packet(), remote_addr)?;
-    Ok(())
+    icmp_send_socket
+        .send_to(ipv4.packet(), remote_addr)
+        .map_err(process_io_error)?;
+    capture_sent(
+        capture,
+        IpProtocol::Icmp,
+        src_addr,
+        dest_addr,
+        probe.ttl.0,
+        0,
+        tos,
+        echo_request.packet(),
+    )
 }
 
 #[allow(clippy::too_many_arguments)]
-#[instrument(skip(raw_send_socket, probe))]
+#[instrument(skip(send_socket, probe, capture))]
 pub fn dispatch_udp_probe<S: Socket>(
-    raw_send_socket: &mut S,
+    send_socket: &mut S,
     probe: Probe,
     src_addr: Ipv4Addr,
     dest_addr: Ipv4Addr,
     privilege_mode: PrivilegeMode,
+    bound_send_socket: bool,
     packet_size: PacketSize,
-    payload_pattern: PayloadPattern,
+    max_packet_size: PacketSize,
+    payload_pattern: &[u8],
+    tos: TypeOfService,
+    dont_fragment: bool,
     ipv4_byte_order: platform::Ipv4ByteOrder,
+    interface_index: Option<u32>,
+    capture: Option<&mut Capture>,
 ) -> Result<()> {
-    let packet_size = usize::from(packet_size.0);
-    if !(MIN_PACKET_SIZE_UDP..=MAX_PACKET_SIZE).contains(&packet_size) {
-        return Err(Error::InvalidPacketSize(packet_size));
-    }
-    let payload_size = udp_payload_size(packet_size);
-    let payload = &[payload_pattern.0; MAX_UDP_PAYLOAD_BUF][0..payload_size];
+    let packet_size = validate_packet_size(packet_size, MIN_PACKET_SIZE_UDP, max_packet_size)?;
+    let payload = &make_payload(&probe, udp_payload_size(packet_size), payload_pattern);
     match privilege_mode {
         PrivilegeMode::Privileged => dispatch_udp_probe_raw(
-            raw_send_socket,
+            send_socket,
             probe,
             src_addr,
             dest_addr,
             payload,
+            tos,
             ipv4_byte_order,
+            capture,
+        ),
+        PrivilegeMode::Unprivileged if bound_send_socket => dispatch_udp_probe_non_raw_bound(
+            send_socket,
+            probe,
+            src_addr,
+            dest_addr,
+            payload,
+            tos,
+            capture,
+        ),
+        PrivilegeMode::Unprivileged => dispatch_udp_probe_non_raw::<S>(
+            probe,
+            src_addr,
+            dest_addr,
+            payload,
+            tos,
+            dont_fragment,
+            interface_index,
+            capture,
         ),
-        PrivilegeMode::Unprivileged => {
-            dispatch_udp_probe_non_raw::<S>(probe, src_addr, dest_addr, payload)
-        }
     }
 }
 
@@ -125,23 +163,26 @@ pub fn dispatch_udp_probe<S: Socket>(
 ///
 /// As `IP_HDRINCL` is set we must supply the IP and UDP headers which allows us to set custom
 /// values for certain fields such as the checksum as required by the Paris tracing strategy.
-#[instrument(skip(raw_send_socket, probe))]
+#[allow(clippy::too_many_arguments)]
+#[instrument(skip(raw_send_socket, probe, capture))]
 fn dispatch_udp_probe_raw<S: Socket>(
     raw_send_socket: &mut S,
     probe: Probe,
     src_addr: Ipv4Addr,
     dest_addr: Ipv4Addr,
     payload: &[u8],
+    tos: TypeOfService,
     ipv4_byte_order: platform::Ipv4ByteOrder,
+    capture: Option<&mut Capture>,
 ) -> Result<()> {
-    let mut ipv4_buf = [0_u8; MAX_PACKET_SIZE];
-    let mut udp_buf = [0_u8; MAX_UDP_PACKET_BUF];
     let payload_paris = probe.sequence.0.to_be_bytes();
     let payload = if probe.flags.contains(Flags::PARIS_CHECKSUM) {
         payload_paris.as_slice()
     } else {
         payload
     };
+    let mut udp_buf = vec![0_u8; UdpPacket::minimum_packet_size() + payload.len()];
+    let mut ipv4_buf = vec![0_u8; Ipv4Packet::minimum_packet_size() + udp_buf.len()];
     let mut udp = make_udp_packet(
         &mut udp_buf,
         src_addr,
@@ -164,27 +205,100 @@ fn dispatch_udp_probe_raw<S: Socket>(
         dest_addr,
         probe.ttl.0,
         probe.identifier.0,
+        tos,
         udp.packet(),
     )?;
     let remote_addr = SocketAddr::new(IpAddr::V4(dest_addr), probe.dest_port.0);
-    raw_send_socket.send_to(ipv4.packet(), remote_addr)?;
-    Ok(())
+    raw_send_socket
+        .send_to(ipv4.packet(), remote_addr)
+        .map_err(process_io_error)?;
+    capture_sent(
+        capture,
+        IpProtocol::Udp,
+        src_addr,
+        dest_addr,
+        probe.ttl.0,
+        probe.identifier.0,
+        tos,
+        udp.packet(),
+    )
 }
 
 /// Dispatch a UDP probe using a new UDP datagram socket.
-#[instrument(skip(probe))]
+///
+/// A new socket is needed for each probe when the source port varies per probe as a socket cannot
+/// be rebound.
+#[allow(clippy::too_many_arguments)]
+#[instrument(skip(probe, capture))]
 fn dispatch_udp_probe_non_raw<S: Socket>(
     probe: Probe,
     src_addr: Ipv4Addr,
     dest_addr: Ipv4Addr,
     payload: &[u8],
+    tos: TypeOfService,
+    dont_fragment: bool,
+    interface_index: Option<u32>,
+    capture: Option<&mut Capture>,
 ) -> Result<()> {
     let local_addr = SocketAddr::new(IpAddr::V4(src_addr), probe.src_port.0);
-    let remote_addr = SocketAddr::new(IpAddr::V4(dest_addr), probe.dest_port.0);
     let mut socket = S::new_udp_send_socket_ipv4(false)?;
+    socket.set_tos(u32::from(tos.0))?;
+    if dont_fragment {
+        socket.set_dont_fragment()?;
+    }
     process_result(local_addr, socket.bind(local_addr))?;
-    socket.set_ttl(u32::from(probe.ttl.0))?;
-    socket.send_to(payload, remote_addr)?;
+    bind_device(&mut socket, interface_index)?;
+    dispatch_udp_probe_non_raw_bound(
+        &mut socket,
+        probe,
+        src_addr,
+        dest_addr,
+        payload,
+        tos,
+        capture,
+    )
+}
+
+/// Dispatch a UDP probe using a UDP datagram socket which is already bound to the probe source
+/// address and port.
+///
+/// The `UDP` header is added by the OS and so is synthesised for the capture, if any.
+#[instrument(skip(udp_send_socket, probe, capture))]
+fn dispatch_udp_probe_non_raw_bound<S: Socket>(
+    udp_send_socket: &mut S,
+    probe: Probe,
+    src_addr: Ipv4Addr,
+    dest_addr: Ipv4Addr,
+    payload: &[u8],
+    tos: TypeOfService,
+    capture: Option<&mut Capture>,
+) -> Result<()> {
+    let remote_addr = SocketAddr::new(IpAddr::V4(dest_addr), probe.dest_port.0);
+    udp_send_socket.set_ttl(u32::from(probe.ttl.0))?;
+    udp_send_socket
+        .send_to(payload, remote_addr)
+        .map_err(process_io_error)?;
+    if capture.is_some() {
+        let mut udp_buf = vec![0_u8; UdpPacket::minimum_packet_size() + payload.len()];
+        let udp = make_udp_packet(
+            &mut udp_buf,
+            src_addr,
+            dest_addr,
+            probe.src_port.0,
+            probe.dest_port.0,
+            payload,
+        )?;
+        capture_sent(
+            capture,
+            IpProtocol::Udp,
+            src_addr,
+            dest_addr,
+            probe.ttl.0,
+            0,
+            tos,
+            udp.packet(),
+        )?;
+    }
     Ok(())
 }
 
@@ -194,36 +308,117 @@ pub fn dispatch_tcp_probe<S: Socket>(
     src_addr: Ipv4Addr,
     dest_addr: Ipv4Addr,
     tos: TypeOfService,
+    dont_fragment: bool,
+    tcp_mss: Option<u16>,
+    interface_index: Option<u32>,
 ) -> Result<S> {
     let mut socket = S::new_stream_socket_ipv4()?;
     let local_addr = SocketAddr::new(IpAddr::V4(src_addr), probe.src_port.0);
     process_result(local_addr, socket.bind(local_addr))?;
+    bind_device(&mut socket, interface_index)?;
     socket.set_ttl(u32::from(probe.ttl.0))?;
     socket.set_tos(u32::from(tos.0))?;
+    if dont_fragment {
+        socket.set_dont_fragment()?;
+    }
+    if let Some(mss) = tcp_mss {
+        socket.set_mss(u32::from(mss))?;
+    }
     let remote_addr = SocketAddr::new(IpAddr::V4(dest_addr), probe.dest_port.0);
     process_result(remote_addr, socket.connect(remote_addr))?;
     Ok(socket)
 }
 
-#[instrument(skip(recv_socket))]
+#[instrument(skip(recv_socket, buf, capture))]
 pub fn recv_icmp_probe<S: Socket>(
     recv_socket: &mut S,
+    buf: &mut [u8],
     protocol: Protocol,
     icmp_extension_mode: IcmpExtensionParseMode,
+    validate_checksum: bool,
+    capture: Option<&mut Capture>,
 ) -> Result<Option<Response>> {
-    let mut buf = [0_u8; MAX_PACKET_SIZE];
-    match recv_socket.read(&mut buf) {
+    match recv_socket.read(buf) {
         Ok(bytes_read) => {
-            let ipv4 = Ipv4Packet::new_view(&buf[..bytes_read])?;
-            Ok(extract_probe_resp(protocol, icmp_extension_mode, &ipv4)?)
+            if let Some(capture) = capture {
+                capture.write(&buf[..bytes_read])?;
+            }
+            discard_malformed(
+                Ipv4Packet::new_view(&buf[..bytes_read])
+                    .map_err(Error::from)
+                    .and_then(|ipv4| {
+                        if validate_checksum {
+                            validate_icmp_checksum(&ipv4)?;
+                        }
+                        extract_probe_resp(protocol, icmp_extension_mode, &ipv4)
+                    }),
+            )
         }
         Err(err) => match err.kind() {
             ErrorKind::WouldBlock => Ok(None),
-            _ => Err(Error::IoError(err)),
+            _ => Err(process_io_error(err)),
         },
     }
 }
 
+/// Generate a `Response` for the next ICMP error queued on the `UDP` socket of a probe, if any.
+///
+/// The socket is bound to the probe `src_port` and the destination of the datagram which caused
+/// the error gives the destination address and port, from which the probe is identified.
+#[instrument(skip(udp_socket))]
+pub fn recv_udp_socket_err<S: Socket>(
+    udp_socket: &mut S,
+    src_port: Port,
+) -> Result<Option<Response>> {
+    let Some(err) = udp_socket.recv_err()? else {
+        return Ok(None);
+    };
+    let Some(dest) = err.dest else {
+        return Ok(None);
+    };
+    let resp_seq = ResponseSeq::Udp(ResponseSeqUdp::new(
+        0,
+        dest.ip(),
+        src_port.0,
+        dest.port(),
+        0,
+        0,
+        false,
+        None,
+    ));
+    let data = ResponseData::new(SystemTime::now(), err.origin, resp_seq);
+    let icmp_code = IcmpCode(err.icmp_code);
+    Ok(match IcmpType::from(err.icmp_type) {
+        IcmpType::TimeExceeded
+            if IcmpTimeExceededCode::from(icmp_code) == IcmpTimeExceededCode::TtlExpired =>
+        {
+            Some(Response::TimeExceeded(
+                data,
+                IcmpPacketCode(icmp_code.0),
+                None,
+            ))
+        }
+        IcmpType::DestinationUnreachable => {
+            // routers which predate RFC 1191 send a next-hop MTU of zero.
+            let next_hop_mtu = u16::try_from(err.info)
+                .ok()
+                .filter(|&mtu| icmp_code == FRAGMENTATION_NEEDED && mtu > 0);
+            Some(Response::DestinationUnreachable(
+                data,
+                IcmpPacketCode(icmp_code.0),
+                next_hop_mtu,
+                None,
+            ))
+        }
+        IcmpType::ParameterProblem => Some(Response::ParameterProblem(
+            data,
+            IcmpPacketCode(icmp_code.0),
+            err.info,
+        )),
+        _ => None,
+    })
+}
+
 #[instrument(skip(tcp_socket))]
 pub fn recv_tcp_socket<S: Socket>(
     tcp_socket: &mut S,
@@ -265,21 +460,21 @@ pub fn recv_tcp_socket<S: Socket>(
 }
 
 /// Create an ICMP `EchoRequest` packet.
-fn make_echo_request_icmp_packet(
-    icmp_buf: &mut [u8],
-    identifier: TraceId,
-    sequence: Sequence,
+///
+/// The payload starts with the `PayloadHeader` of the probe, if it is large enough.
+fn make_echo_request_icmp_packet<'a>(
+    icmp_buf: &'a mut [u8],
+    probe: &Probe,
     payload_size: usize,
-    payload_pattern: PayloadPattern,
-) -> Result<EchoRequestPacket<'_>> {
-    let payload_buf = [payload_pattern.0; MAX_ICMP_PAYLOAD_BUF];
+    payload_pattern: &[u8],
+) -> Result<EchoRequestPacket<'a>> {
     let packet_size = IcmpPacket::minimum_packet_size() + payload_size;
     let mut icmp = EchoRequestPacket::new(&mut icmp_buf[..packet_size])?;
     icmp.set_icmp_type(IcmpType::EchoRequest);
     icmp.set_icmp_code(IcmpCode(0));
-    icmp.set_identifier(identifier.0);
-    icmp.set_payload(&payload_buf[..payload_size]);
-    icmp.set_sequence(sequence.0);
+    icmp.set_identifier(probe.identifier.0);
+    icmp.set_payload(&make_payload(probe, payload_size, payload_pattern));
+    icmp.set_sequence(probe.sequence.0);
     icmp.set_checksum(icmp_ipv4_checksum(icmp.packet()));
     Ok(icmp)
 }
@@ -303,6 +498,41 @@ fn make_udp_packet<'a>(
     Ok(udp)
 }
 
+/// Write a sent packet to the capture, if any, synthesising the IPv4 header.
+///
+/// The header is written in network byte order and with the checksum set, as it is sent by the OS,
+/// regardless of the byte order required by the socket.
+#[allow(clippy::too_many_arguments)]
+fn capture_sent(
+    capture: Option<&mut Capture>,
+    protocol: IpProtocol,
+    src_addr: Ipv4Addr,
+    dest_addr: Ipv4Addr,
+    ttl: u8,
+    identification: u16,
+    tos: TypeOfService,
+    payload: &[u8],
+) -> Result<()> {
+    if let Some(capture) = capture {
+        let mut ipv4_buf = vec![0_u8; Ipv4Packet::minimum_packet_size() + payload.len()];
+        let mut ipv4 = make_ipv4_packet(
+            &mut ipv4_buf,
+            platform::Ipv4ByteOrder::Network,
+            protocol,
+            src_addr,
+            dest_addr,
+            ttl,
+            identification,
+            tos,
+            payload,
+        )?;
+        let checksum = ipv4_header_checksum(&ipv4.packet()[..Ipv4Packet::minimum_packet_size()]);
+        ipv4.set_checksum(checksum);
+        capture.write(ipv4.packet())?;
+    }
+    Ok(())
+}
+
 /// Create an `Ipv4Packet`.
 #[allow(clippy::too_many_arguments)]
 fn make_ipv4_packet<'a>(
@@ -313,6 +543,7 @@ fn make_ipv4_packet<'a>(
     dest_addr: Ipv4Addr,
     ttl: u8,
     identification: u16,
+    tos: TypeOfService,
     payload: &[u8],
 ) -> Result<Ipv4Packet<'a>> {
     let ipv4_total_length = (Ipv4Packet::minimum_packet_size() + payload.len()) as u16;
@@ -321,6 +552,8 @@ fn make_ipv4_packet<'a>(
     let mut ipv4 = Ipv4Packet::new(&mut ipv4_buf[..ipv4_total_length as usize])?;
     ipv4.set_version(4);
     ipv4.set_header_length(5);
+    ipv4.set_dscp(tos.0 >> 2);
+    ipv4.set_ecn(tos.0 & 0x3);
     ipv4.set_total_length(ipv4_total_length_header);
     ipv4.set_ttl(ttl);
     ipv4.set_protocol(protocol);
@@ -344,6 +577,18 @@ const fn udp_payload_size(packet_size: usize) -> usize {
     packet_size - udp_header_size - ip_header_size
 }
 
+/// Validate the checksum of the `ICMP` packet embedded in the payload.
+fn validate_icmp_checksum(ipv4: &Ipv4Packet<'_>) -> Result<()> {
+    let icmp_v4 = IcmpPacket::new_view(ipv4.payload())?;
+    let expected = icmp_ipv4_checksum(icmp_v4.packet());
+    let actual = icmp_v4.get_checksum();
+    if expected == actual {
+        Ok(())
+    } else {
+        Err(Error::InvalidIcmpChecksum { expected, actual })
+    }
+}
+
 #[instrument]
 fn extract_probe_resp(
     protocol: Protocol,
@@ -390,20 +635,37 @@ fn extract_probe_resp(
                 }
                 IcmpExtensionParseMode::Disabled => None,
             };
+            // routers which predate RFC 1191 send a next-hop MTU of zero.
+            let next_hop_mtu = Some(packet.get_next_hop_mtu())
+                .filter(|&mtu| icmp_code == FRAGMENTATION_NEEDED && mtu > 0);
             extract_probe_resp_seq(&nested_ipv4, protocol)?.map(|resp_seq| {
                 Response::DestinationUnreachable(
                     ResponseData::new(recv, src, resp_seq),
                     IcmpPacketCode(icmp_code.0),
+                    next_hop_mtu,
                     extension,
                 )
             })
         }
+        IcmpType::ParameterProblem => {
+            let packet = ParameterProblemPacket::new_view(icmp_v4.packet())?;
+            let nested_ipv4 = Ipv4Packet::new_view(packet.payload())?;
+            let pointer = u32::from(packet.get_pointer());
+            extract_probe_resp_seq(&nested_ipv4, protocol)?.map(|resp_seq| {
+                Response::ParameterProblem(
+                    ResponseData::new(recv, src, resp_seq),
+                    IcmpPacketCode(icmp_code.0),
+                    pointer,
+                )
+            })
+        }
         IcmpType::EchoReply => match protocol {
             Protocol::Icmp => {
                 let packet = EchoReplyPacket::new_view(icmp_v4.packet())?;
                 let id = packet.get_identifier();
                 let seq = packet.get_sequence();
-                let resp_seq = ResponseSeq::Icmp(ResponseSeqIcmp::new(id, seq));
+                let payload_header = PayloadHeader::parse(packet.payload());
+                let resp_seq = ResponseSeq::Icmp(ResponseSeqIcmp::new(id, seq, payload_header));
                 Some(Response::EchoReply(
                     ResponseData::new(recv, src, resp_seq),
                     IcmpPacketCode(icmp_code.0),
@@ -425,12 +687,15 @@ fn extract_probe_resp_seq(
             let echo_request = extract_echo_request(ipv4)?;
             let identifier = echo_request.get_identifier();
             let sequence = echo_request.get_sequence();
+            let payload_header = PayloadHeader::parse(echo_request.payload());
             Some(ResponseSeq::Icmp(ResponseSeqIcmp::new(
-                identifier, sequence,
+                identifier,
+                sequence,
+                payload_header,
             )))
         }
         (Protocol::Udp, IpProtocol::Udp) => {
-            let (src_port, dest_port, checksum, identifier, payload_length) =
+            let (src_port, dest_port, checksum, identifier, payload_length, payload_header) =
                 extract_udp_packet(ipv4)?;
             Some(ResponseSeq::Udp(ResponseSeqUdp::new(
                 identifier,
@@ -440,10 +705,11 @@ fn extract_probe_resp_seq(
                 checksum,
                 payload_length,
                 false,
+                payload_header,
             )))
         }
         (Protocol::Tcp, IpProtocol::Tcp) => {
-            let (src_port, dest_port) = extract_tcp_packet(ipv4)?;
+            let (src_port, dest_port) = extract_tcp_ports(ipv4.payload())?;
             Some(ResponseSeq::Tcp(ResponseSeqTcp::new(
                 IpAddr::V4(ipv4.get_destination()),
                 src_port,
@@ -460,53 +726,36 @@ fn extract_echo_request<'a>(ipv4: &'a Ipv4Packet<'a>) -> Result<EchoRequestPacke
 }
 
 /// Get the src and dest ports from the original `UdpPacket` packet embedded in the payload.
+///
+/// The `PayloadHeader` of the original `UdpPacket` is `None` if the payload was truncated.
 #[instrument]
-fn extract_udp_packet(ipv4: &Ipv4Packet<'_>) -> Result<(u16, u16, u16, u16, u16)> {
+fn extract_udp_packet(
+    ipv4: &Ipv4Packet<'_>,
+) -> Result<(u16, u16, u16, u16, u16, Option<PayloadHeader>)> {
     let nested = UdpPacket::new_view(ipv4.payload())?;
     Ok((
         nested.get_source(),
         nested.get_destination(),
         nested.get_checksum(),
         ipv4.get_identification(),
-        nested.get_length() - UdpPacket::minimum_packet_size() as u16,
+        nested
+            .get_length()
+            .saturating_sub(UdpPacket::minimum_packet_size() as u16),
+        PayloadHeader::parse(nested.payload()),
     ))
 }
 
-/// Get the src and dest ports from the original `TcpPacket` packet embedded in the payload.
-///
-/// Unlike the embedded `ICMP` and `UDP` packets, which have a minimum header size of 8 bytes, the
-/// `TCP` packet header is a minimum of 20 bytes.
-///
-/// The `ICMP` packets we are extracting these from, such as `TimeExceeded`, only guarantee that 8
-/// bytes of the original packet (plus the IP header) be returned and so we may not have a complete
-/// TCP packet.
-///
-/// We therefore have to detect this situation and ensure we provide buffer a large enough for a
-/// complete TCP packet header.
-#[instrument]
-fn extract_tcp_packet(ipv4: &Ipv4Packet<'_>) -> Result<(u16, u16)> {
-    let nested_tcp = ipv4.payload();
-    if nested_tcp.len() < TcpPacket::minimum_packet_size() {
-        let mut buf = [0_u8; TcpPacket::minimum_packet_size()];
-        buf[..nested_tcp.len()].copy_from_slice(nested_tcp);
-        let tcp_packet = TcpPacket::new_view(&buf)?;
-        Ok((tcp_packet.get_source(), tcp_packet.get_destination()))
-    } else {
-        let tcp_packet = TcpPacket::new_view(nested_tcp)?;
-        Ok((tcp_packet.get_source(), tcp_packet.get_destination()))
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::error::IoResult;
     use crate::mocket_read;
     use crate::net::socket::MockSocket;
-    use crate::{Flags, Port, RoundId, TimeToLive};
+    use crate::{Flags, FlowIndex, FlowLabel, Port, RoundId, Sequence, TimeToLive, TraceId};
     use mockall::predicate;
     use std::str::FromStr;
     use std::sync::Mutex;
+    use std::time::{Duration, UNIX_EPOCH};
 
     static MTX: Mutex<()> = Mutex::new(());
 
@@ -517,7 +766,7 @@ mod tests {
         let src_addr = Ipv4Addr::from_str("1.2.3.4")?;
         let dest_addr = Ipv4Addr::from_str("5.6.7.8")?;
         let packet_size = PacketSize(28);
-        let payload_pattern = PayloadPattern(0x00);
+        let payload_pattern: &[u8] = &[0x00];
         let ipv4_byte_order = platform::Ipv4ByteOrder::Network;
         let expected_send_to_buf = hex_literal::hex!(
             "
@@ -543,8 +792,54 @@ mod tests {
             src_addr,
             dest_addr,
             packet_size,
+            PacketSize(1024),
+            payload_pattern,
+            TypeOfService(0),
+            ipv4_byte_order,
+            None,
+        )?;
+        Ok(())
+    }
+
+    // Test dispatching a IPv4/ICMP probe with the DSCP set to `EF` and the ECN set to `ECT(0)`.
+    #[test]
+    fn test_dispatch_icmp_probe_with_tos() -> anyhow::Result<()> {
+        let probe = make_icmp_probe();
+        let src_addr = Ipv4Addr::from_str("1.2.3.4")?;
+        let dest_addr = Ipv4Addr::from_str("5.6.7.8")?;
+        let packet_size = PacketSize(28);
+        let payload_pattern: &[u8] = &[0x00];
+        let tos = TypeOfService(0xba);
+        let ipv4_byte_order = platform::Ipv4ByteOrder::Network;
+        let expected_send_to_buf = hex_literal::hex!(
+            "
+            45 ba 00 1c 00 00 40 00 0a 01 00 00 01 02 03 04
+            05 06 07 08 08 00 72 45 04 d2 80 e8
+            "
+        );
+        let expected_send_to_addr = SocketAddr::new(IpAddr::V4(dest_addr), 0);
+
+        let mut mocket = MockSocket::new();
+        mocket
+            .expect_send_to()
+            .with(
+                predicate::eq(expected_send_to_buf),
+                predicate::eq(expected_send_to_addr),
+            )
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        dispatch_icmp_probe(
+            &mut mocket,
+            probe,
+            src_addr,
+            dest_addr,
+            packet_size,
+            PacketSize(1024),
             payload_pattern,
+            tos,
             ipv4_byte_order,
+            None,
         )?;
         Ok(())
     }
@@ -555,13 +850,13 @@ mod tests {
         let src_addr = Ipv4Addr::from_str("1.2.3.4")?;
         let dest_addr = Ipv4Addr::from_str("5.6.7.8")?;
         let packet_size = PacketSize(48);
-        let payload_pattern = PayloadPattern(0xff);
+        let payload_pattern: &[u8] = &[0xff];
         let ipv4_byte_order = platform::Ipv4ByteOrder::Network;
         let expected_send_to_buf = hex_literal::hex!(
             "
             45 00 00 30 00 00 40 00 0a 01 00 00 01 02 03 04
-            05 06 07 08 08 00 72 45 04 d2 80 e8 ff ff ff ff
-            ff ff ff ff ff ff ff ff ff ff ff ff ff ff ff ff
+            05 06 07 08 08 00 88 ae 04 d2 80 e8 54 52 50 59
+            04 d2 80 e8 17 97 9c fe 3d 85 cd 15 ff ff ff ff
             "
         );
         let expected_send_to_addr = SocketAddr::new(IpAddr::V4(dest_addr), 0);
@@ -582,8 +877,11 @@ mod tests {
             src_addr,
             dest_addr,
             packet_size,
+            PacketSize(1024),
             payload_pattern,
+            TypeOfService(0),
             ipv4_byte_order,
+            None,
         )?;
         Ok(())
     }
@@ -594,7 +892,7 @@ mod tests {
         let src_addr = Ipv4Addr::from_str("1.2.3.4")?;
         let dest_addr = Ipv4Addr::from_str("5.6.7.8")?;
         let packet_size = PacketSize(27);
-        let payload_pattern = PayloadPattern(0x00);
+        let payload_pattern: &[u8] = &[0x00];
         let ipv4_byte_order = platform::Ipv4ByteOrder::Network;
         let mut mocket = MockSocket::new();
         let err = dispatch_icmp_probe(
@@ -603,8 +901,11 @@ mod tests {
             src_addr,
             dest_addr,
             packet_size,
+            PacketSize(1024),
             payload_pattern,
+            TypeOfService(0),
             ipv4_byte_order,
+            None,
         )
         .unwrap_err();
         assert!(matches!(err, Error::InvalidPacketSize(_)));
@@ -617,7 +918,7 @@ mod tests {
         let src_addr = Ipv4Addr::from_str("1.2.3.4")?;
         let dest_addr = Ipv4Addr::from_str("5.6.7.8")?;
         let packet_size = PacketSize(1025);
-        let payload_pattern = PayloadPattern(0x00);
+        let payload_pattern: &[u8] = &[0x00];
         let ipv4_byte_order = platform::Ipv4ByteOrder::Network;
         let mut mocket = MockSocket::new();
         let err = dispatch_icmp_probe(
@@ -626,11 +927,48 @@ mod tests {
             src_addr,
             dest_addr,
             packet_size,
+            PacketSize(1024),
             payload_pattern,
+            TypeOfService(0),
             ipv4_byte_order,
+            None,
         )
         .unwrap_err();
-        assert!(matches!(err, Error::InvalidPacketSize(_)));
+        assert!(matches!(err, Error::PacketSizeExceedsMax(1025, 1024)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_dispatch_icmp_probe_jumbo() -> anyhow::Result<()> {
+        let probe = make_icmp_probe();
+        let src_addr = Ipv4Addr::from_str("1.2.3.4")?;
+        let dest_addr = Ipv4Addr::from_str("5.6.7.8")?;
+        let packet_size = PacketSize(9000);
+        let payload_pattern: &[u8] = &[0xff];
+        let ipv4_byte_order = platform::Ipv4ByteOrder::Network;
+        let mut mocket = MockSocket::new();
+        mocket
+            .expect_send_to()
+            .withf(|buf: &[u8], _| {
+                buf.len() == 9000
+                    && buf[2..4] == [0x23, 0x28]
+                    && buf[28..32] == *b"TRPY"
+                    && buf[44..].iter().all(|&b| b == 0xff)
+            })
+            .times(1)
+            .returning(|_, _| Ok(()));
+        dispatch_icmp_probe(
+            &mut mocket,
+            probe,
+            src_addr,
+            dest_addr,
+            packet_size,
+            PacketSize(9000),
+            payload_pattern,
+            TypeOfService(0),
+            ipv4_byte_order,
+            None,
+        )?;
         Ok(())
     }
 
@@ -641,7 +979,7 @@ mod tests {
         let dest_addr = Ipv4Addr::from_str("5.6.7.8")?;
         let privilege_mode = PrivilegeMode::Privileged;
         let packet_size = PacketSize(28);
-        let payload_pattern = PayloadPattern(0x00);
+        let payload_pattern: &[u8] = &[0x00];
         let ipv4_byte_order = platform::Ipv4ByteOrder::Network;
         let expected_send_to_buf = hex_literal::hex!(
             "
@@ -667,9 +1005,15 @@ mod tests {
             src_addr,
             dest_addr,
             privilege_mode,
+            false,
             packet_size,
+            PacketSize(1024),
             payload_pattern,
+            TypeOfService(0),
+            false,
             ipv4_byte_order,
+            None,
+            None,
         )?;
         Ok(())
     }
@@ -681,7 +1025,7 @@ mod tests {
         let dest_addr = Ipv4Addr::from_str("5.6.7.8")?;
         let privilege_mode = PrivilegeMode::Privileged;
         let packet_size = PacketSize(38);
-        let payload_pattern = PayloadPattern(0xaa);
+        let payload_pattern: &[u8] = &[0xaa];
         let ipv4_byte_order = platform::Ipv4ByteOrder::Network;
         let expected_send_to_buf = hex_literal::hex!(
             "
@@ -708,9 +1052,15 @@ mod tests {
             src_addr,
             dest_addr,
             privilege_mode,
+            false,
             packet_size,
+            PacketSize(1024),
             payload_pattern,
+            TypeOfService(0),
+            false,
             ipv4_byte_order,
+            None,
+            None,
         )?;
         Ok(())
     }
@@ -727,7 +1077,7 @@ mod tests {
         // packet size and payload pattern are ignored for paris mode as a
         // fixed two byte payload is used to hold the sequence
         let packet_size = PacketSize(300);
-        let payload_pattern = PayloadPattern(0xaa);
+        let payload_pattern: &[u8] = &[0xaa];
         let ipv4_byte_order = platform::Ipv4ByteOrder::Network;
         let expected_send_to_buf = hex_literal::hex!(
             "
@@ -753,10 +1103,81 @@ mod tests {
             src_addr,
             dest_addr,
             privilege_mode,
+            false,
             packet_size,
+            PacketSize(1024),
             payload_pattern,
+            TypeOfService(0),
+            false,
             ipv4_byte_order,
+            None,
+            None,
+        )?;
+        Ok(())
+    }
+
+    // Test that the sequence stored in the UDP checksum of a Paris probe can be recovered from the
+    // (quoted) probe and that the compensating payload leaves the datagram with a valid checksum.
+    //
+    // Note that the checksum is a ones' complement sum and so 0x0000 and 0xffff are equivalent.
+    #[test_case::test_case(0; "zero")]
+    #[test_case::test_case(1; "one")]
+    #[test_case::test_case(33000; "default initial")]
+    #[test_case::test_case(0x7fff; "mid low")]
+    #[test_case::test_case(0x8000; "mid high")]
+    #[test_case::test_case(0xfffe; "max minus one")]
+    #[test_case::test_case(0xffff; "max")]
+    fn test_dispatch_udp_probe_paris_round_trip(sequence: u16) -> anyhow::Result<()> {
+        let probe = Probe {
+            sequence: Sequence(sequence),
+            flags: Flags::PARIS_CHECKSUM,
+            ..make_udp_probe(123, 456)
+        };
+        let src_addr = Ipv4Addr::from_str("1.2.3.4")?;
+        let dest_addr = Ipv4Addr::from_str("5.6.7.8")?;
+        let sent = std::sync::Arc::new(Mutex::new(Vec::new()));
+        let sent_clone = sent.clone();
+        let mut mocket = MockSocket::new();
+        mocket.expect_send_to().times(1).returning(move |buf, _| {
+            sent_clone.lock().unwrap().extend_from_slice(buf);
+            Ok(())
+        });
+        dispatch_udp_probe(
+            &mut mocket,
+            probe,
+            src_addr,
+            dest_addr,
+            PrivilegeMode::Privileged,
+            false,
+            PacketSize(300),
+            PacketSize(1024),
+            &[0xaa],
+            TypeOfService(0),
+            false,
+            platform::Ipv4ByteOrder::Network,
+            None,
+            None,
         )?;
+        let sent = std::mem::take(&mut *sent.lock().unwrap());
+        let ipv4 = Ipv4Packet::new_view(&sent)?;
+        let udp = UdpPacket::new_view(ipv4.payload())?;
+        let expected_checksum = udp_ipv4_checksum(udp.packet(), src_addr, dest_addr);
+        assert_eq!(
+            u32::from(expected_checksum) % 0xffff,
+            u32::from(udp.get_checksum()) % 0xffff
+        );
+        let Some(ResponseSeq::Udp(ResponseSeqUdp {
+            src_port,
+            dest_port,
+            checksum,
+            ..
+        })) = extract_probe_resp_seq(&ipv4, Protocol::Udp)?
+        else {
+            panic!("expected Udp")
+        };
+        assert_eq!(123, src_port);
+        assert_eq!(456, dest_port);
+        assert_eq!(sequence, checksum);
         Ok(())
     }
 
@@ -772,7 +1193,7 @@ mod tests {
         let dest_addr = Ipv4Addr::from_str("5.6.7.8")?;
         let privilege_mode = PrivilegeMode::Privileged;
         let packet_size = PacketSize(28);
-        let payload_pattern = PayloadPattern(0xaa);
+        let payload_pattern: &[u8] = &[0xaa];
         let ipv4_byte_order = platform::Ipv4ByteOrder::Network;
         let expected_send_to_buf = hex_literal::hex!(
             "
@@ -798,9 +1219,15 @@ mod tests {
             src_addr,
             dest_addr,
             privilege_mode,
+            false,
             packet_size,
+            PacketSize(1024),
             payload_pattern,
+            TypeOfService(0),
+            false,
             ipv4_byte_order,
+            None,
+            None,
         )?;
         Ok(())
     }
@@ -813,7 +1240,7 @@ mod tests {
         let dest_addr = Ipv4Addr::from_str("5.6.7.8")?;
         let privilege_mode = PrivilegeMode::Unprivileged;
         let packet_size = PacketSize(28);
-        let payload_pattern = PayloadPattern(0x00);
+        let payload_pattern: &[u8] = &[0x00];
         let ipv4_byte_order = platform::Ipv4ByteOrder::Network;
         let expected_send_to_buf = hex_literal::hex!("");
         let expected_send_to_addr = SocketAddr::new(IpAddr::V4(dest_addr), 456);
@@ -825,6 +1252,11 @@ mod tests {
         let ctx = MockSocket::new_udp_send_socket_ipv4_context();
         ctx.expect().with(predicate::eq(false)).returning(move |_| {
             let mut mocket = MockSocket::new();
+            mocket
+                .expect_set_tos()
+                .with(predicate::eq(0))
+                .times(1)
+                .returning(|_| Ok(()));
             mocket
                 .expect_bind()
                 .with(predicate::eq(expected_bind_addr))
@@ -855,9 +1287,15 @@ mod tests {
             src_addr,
             dest_addr,
             privilege_mode,
+            false,
             packet_size,
+            PacketSize(1024),
             payload_pattern,
+            TypeOfService(0),
+            false,
             ipv4_byte_order,
+            None,
+            None,
         )?;
         Ok(())
     }
@@ -870,7 +1308,7 @@ mod tests {
         let dest_addr = Ipv4Addr::from_str("5.6.7.8")?;
         let privilege_mode = PrivilegeMode::Unprivileged;
         let packet_size = PacketSize(36);
-        let payload_pattern = PayloadPattern(0x1f);
+        let payload_pattern: &[u8] = &[0x1f];
         let ipv4_byte_order = platform::Ipv4ByteOrder::Network;
         let expected_send_to_buf = hex_literal::hex!("1f 1f 1f 1f 1f 1f 1f 1f");
         let expected_send_to_addr = SocketAddr::new(IpAddr::V4(dest_addr), 456);
@@ -882,6 +1320,11 @@ mod tests {
         let ctx = MockSocket::new_udp_send_socket_ipv4_context();
         ctx.expect().with(predicate::eq(false)).returning(move |_| {
             let mut mocket = MockSocket::new();
+            mocket
+                .expect_set_tos()
+                .with(predicate::eq(0))
+                .times(1)
+                .returning(|_| Ok(()));
             mocket
                 .expect_bind()
                 .with(predicate::eq(expected_bind_addr))
@@ -912,46 +1355,75 @@ mod tests {
             src_addr,
             dest_addr,
             privilege_mode,
+            false,
             packet_size,
+            PacketSize(1024),
             payload_pattern,
+            TypeOfService(0),
+            false,
             ipv4_byte_order,
+            None,
+            None,
         )?;
         Ok(())
     }
 
     #[test]
-    fn test_dispatch_udp_probe_invalid_packet_size_low() -> anyhow::Result<()> {
+    fn test_dispatch_udp_probe_classic_unprivileged_bound() -> anyhow::Result<()> {
         let probe = make_udp_probe(123, 456);
         let src_addr = Ipv4Addr::from_str("1.2.3.4")?;
         let dest_addr = Ipv4Addr::from_str("5.6.7.8")?;
-        let privilege_mode = PrivilegeMode::Privileged;
-        let packet_size = PacketSize(27);
-        let payload_pattern = PayloadPattern(0x00);
+        let privilege_mode = PrivilegeMode::Unprivileged;
+        let packet_size = PacketSize(28);
+        let payload_pattern: &[u8] = &[0x00];
         let ipv4_byte_order = platform::Ipv4ByteOrder::Network;
+        let expected_send_to_buf = hex_literal::hex!("");
+        let expected_send_to_addr = SocketAddr::new(IpAddr::V4(dest_addr), 456);
+        let expected_set_ttl = 10;
+
         let mut mocket = MockSocket::new();
-        let err = dispatch_udp_probe(
+        mocket.expect_bind().times(0);
+        mocket
+            .expect_set_ttl()
+            .with(predicate::eq(expected_set_ttl))
+            .times(1)
+            .returning(|_| Ok(()));
+        mocket
+            .expect_send_to()
+            .with(
+                predicate::eq(expected_send_to_buf),
+                predicate::eq(expected_send_to_addr),
+            )
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        dispatch_udp_probe(
             &mut mocket,
             probe,
             src_addr,
             dest_addr,
             privilege_mode,
+            true,
             packet_size,
+            PacketSize(1024),
             payload_pattern,
+            TypeOfService(0),
+            false,
             ipv4_byte_order,
-        )
-        .unwrap_err();
-        assert!(matches!(err, Error::InvalidPacketSize(_)));
+            None,
+            None,
+        )?;
         Ok(())
     }
 
     #[test]
-    fn test_dispatch_udp_probe_invalid_packet_size_high() -> anyhow::Result<()> {
+    fn test_dispatch_udp_probe_invalid_packet_size_low() -> anyhow::Result<()> {
         let probe = make_udp_probe(123, 456);
         let src_addr = Ipv4Addr::from_str("1.2.3.4")?;
         let dest_addr = Ipv4Addr::from_str("5.6.7.8")?;
         let privilege_mode = PrivilegeMode::Privileged;
-        let packet_size = PacketSize(1025);
-        let payload_pattern = PayloadPattern(0x00);
+        let packet_size = PacketSize(27);
+        let payload_pattern: &[u8] = &[0x00];
         let ipv4_byte_order = platform::Ipv4ByteOrder::Network;
         let mut mocket = MockSocket::new();
         let err = dispatch_udp_probe(
@@ -960,15 +1432,59 @@ mod tests {
             src_addr,
             dest_addr,
             privilege_mode,
+            false,
             packet_size,
+            PacketSize(1024),
             payload_pattern,
+            TypeOfService(0),
+            false,
             ipv4_byte_order,
+            None,
+            None,
         )
         .unwrap_err();
         assert!(matches!(err, Error::InvalidPacketSize(_)));
         Ok(())
     }
 
+    #[test]
+    fn test_min_packet_size() {
+        assert_eq!(28, min_packet_size(Protocol::Icmp));
+        assert_eq!(28, min_packet_size(Protocol::Udp));
+        assert_eq!(0, min_packet_size(Protocol::Tcp));
+    }
+
+    #[test]
+    fn test_dispatch_udp_probe_invalid_packet_size_high() -> anyhow::Result<()> {
+        let probe = make_udp_probe(123, 456);
+        let src_addr = Ipv4Addr::from_str("1.2.3.4")?;
+        let dest_addr = Ipv4Addr::from_str("5.6.7.8")?;
+        let privilege_mode = PrivilegeMode::Privileged;
+        let packet_size = PacketSize(1025);
+        let payload_pattern: &[u8] = &[0x00];
+        let ipv4_byte_order = platform::Ipv4ByteOrder::Network;
+        let mut mocket = MockSocket::new();
+        let err = dispatch_udp_probe(
+            &mut mocket,
+            probe,
+            src_addr,
+            dest_addr,
+            privilege_mode,
+            false,
+            packet_size,
+            PacketSize(1024),
+            payload_pattern,
+            TypeOfService(0),
+            false,
+            ipv4_byte_order,
+            None,
+            None,
+        )
+        .unwrap_err();
+        assert!(matches!(err, Error::PacketSizeExceedsMax(1025, 1024)));
+        Ok(())
+    }
+
     #[test]
     fn test_dispatch_tcp_probe() -> anyhow::Result<()> {
         let _m = MTX.lock();
@@ -1011,7 +1527,123 @@ mod tests {
             Ok(mocket)
         });
 
-        dispatch_tcp_probe::<MockSocket>(&probe, src_addr, dest_addr, tos)?;
+        dispatch_tcp_probe::<MockSocket>(&probe, src_addr, dest_addr, tos, false, None, None)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_dispatch_tcp_probe_mss() -> anyhow::Result<()> {
+        let _m = MTX.lock();
+        let probe = make_udp_probe(123, 456);
+        let src_addr = Ipv4Addr::from_str("1.2.3.4")?;
+        let dest_addr = Ipv4Addr::from_str("5.6.7.8")?;
+        let tos = TypeOfService(0);
+        let expected_set_mss = 1200;
+        let expected_connect_addr = SocketAddr::new(IpAddr::V4(dest_addr), 456);
+
+        let ctx = MockSocket::new_stream_socket_ipv4_context();
+        ctx.expect().returning(move || {
+            let mut mocket = MockSocket::new();
+            mocket.expect_bind().times(1).returning(|_| Ok(()));
+            mocket.expect_set_ttl().times(1).returning(|_| Ok(()));
+            mocket.expect_set_tos().times(1).returning(|_| Ok(()));
+
+            mocket
+                .expect_set_mss()
+                .with(predicate::eq(expected_set_mss))
+                .times(1)
+                .returning(|_| Ok(()));
+
+            mocket
+                .expect_connect()
+                .with(predicate::eq(expected_connect_addr))
+                .times(1)
+                .returning(|_| Ok(()));
+
+            Ok(mocket)
+        });
+
+        dispatch_tcp_probe::<MockSocket>(
+            &probe,
+            src_addr,
+            dest_addr,
+            tos,
+            false,
+            Some(1200),
+            None,
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_dispatch_tcp_probe_bind_device() -> anyhow::Result<()> {
+        let _m = MTX.lock();
+        let probe = make_udp_probe(123, 456);
+        let src_addr = Ipv4Addr::from_str("1.2.3.4")?;
+        let dest_addr = Ipv4Addr::from_str("5.6.7.8")?;
+        let tos = TypeOfService(0);
+        let expected_bind_device = 7;
+
+        let ctx = MockSocket::new_stream_socket_ipv4_context();
+        ctx.expect().returning(move || {
+            let mut mocket = MockSocket::new();
+            mocket.expect_bind().times(1).returning(|_| Ok(()));
+            mocket
+                .expect_bind_device()
+                .with(predicate::eq(expected_bind_device))
+                .times(1)
+                .returning(|_| Ok(()));
+            mocket.expect_set_ttl().times(1).returning(|_| Ok(()));
+            mocket.expect_set_tos().times(1).returning(|_| Ok(()));
+            mocket.expect_connect().times(1).returning(|_| Ok(()));
+            Ok(mocket)
+        });
+
+        dispatch_tcp_probe::<MockSocket>(&probe, src_addr, dest_addr, tos, false, None, Some(7))?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_dispatch_udp_probe_unprivileged_bind_device() -> anyhow::Result<()> {
+        let _m = MTX.lock();
+        let probe = make_udp_probe(123, 456);
+        let src_addr = Ipv4Addr::from_str("1.2.3.4")?;
+        let dest_addr = Ipv4Addr::from_str("5.6.7.8")?;
+        let expected_bind_device = 7;
+
+        let mut mocket = MockSocket::new();
+
+        let ctx = MockSocket::new_udp_send_socket_ipv4_context();
+        ctx.expect().with(predicate::eq(false)).returning(move |_| {
+            let mut mocket = MockSocket::new();
+            mocket.expect_set_tos().times(1).returning(|_| Ok(()));
+            mocket.expect_bind().times(1).returning(|_| Ok(()));
+            mocket
+                .expect_bind_device()
+                .with(predicate::eq(expected_bind_device))
+                .times(1)
+                .returning(|_| Ok(()));
+            mocket.expect_set_ttl().times(1).returning(|_| Ok(()));
+            mocket.expect_send_to().times(1).returning(|_, _| Ok(()));
+            Ok(mocket)
+        });
+
+        dispatch_udp_probe(
+            &mut mocket,
+            probe,
+            src_addr,
+            dest_addr,
+            PrivilegeMode::Unprivileged,
+            false,
+            PacketSize(28),
+            PacketSize(1024),
+            &[0x00],
+            TypeOfService(0),
+            false,
+            platform::Ipv4ByteOrder::Network,
+            Some(7),
+            None,
+        )?;
         Ok(())
     }
 
@@ -1034,8 +1666,11 @@ mod tests {
             .returning(mocket_read!(expected_read_buf));
         let resp = recv_icmp_probe(
             &mut mocket,
+            &mut [0_u8; 1024],
             Protocol::Icmp,
             IcmpExtensionParseMode::Disabled,
+            true,
+            None,
         )?
         .unwrap();
 
@@ -1046,6 +1681,7 @@ mod tests {
                     ResponseSeq::Icmp(ResponseSeqIcmp {
                         identifier,
                         sequence,
+                        payload_header,
                     }),
                 ..
             },
@@ -1054,6 +1690,7 @@ mod tests {
         else {
             panic!("expected EchoReply")
         };
+        assert_eq!(None, payload_header);
         assert_eq!(
             IpAddr::V4(Ipv4Addr::from_str("142.251.222.206").unwrap()),
             addr
@@ -1084,8 +1721,11 @@ mod tests {
             .returning(mocket_read!(expected_read_buf));
         let resp = recv_icmp_probe(
             &mut mocket,
+            &mut [0_u8; 1024],
             Protocol::Icmp,
             IcmpExtensionParseMode::Disabled,
+            true,
+            None,
         )?
         .unwrap();
 
@@ -1096,6 +1736,7 @@ mod tests {
                     ResponseSeq::Icmp(ResponseSeqIcmp {
                         identifier,
                         sequence,
+                        payload_header,
                     }),
                 ..
             },
@@ -1105,6 +1746,7 @@ mod tests {
         else {
             panic!("expected TimeExceeded")
         };
+        assert_eq!(None, payload_header);
         assert_eq!(
             IpAddr::V4(Ipv4Addr::from_str("142.250.61.129").unwrap()),
             addr
@@ -1133,8 +1775,11 @@ mod tests {
             .returning(mocket_read!(expected_read_buf));
         let resp = recv_icmp_probe(
             &mut mocket,
+            &mut [0_u8; 1024],
             Protocol::Icmp,
             IcmpExtensionParseMode::Disabled,
+            true,
+            None,
         )?
         .unwrap();
 
@@ -1145,15 +1790,18 @@ mod tests {
                     ResponseSeq::Icmp(ResponseSeqIcmp {
                         identifier,
                         sequence,
+                        payload_header,
                     }),
                 ..
             },
             icmp_code,
+            _,
             extensions,
         ) = resp
         else {
             panic!("expected DestinationUnreachable")
         };
+        assert_eq!(None, payload_header);
         assert_eq!(IpAddr::V4(Ipv4Addr::from_str("20.0.0.254").unwrap()), addr);
         assert_eq!(31489, identifier);
         assert_eq!(33060, sequence);
@@ -1162,6 +1810,39 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_recv_icmp_probe_destination_unreachable_fragmentation_needed() -> anyhow::Result<()> {
+        let expected_read_buf = hex_literal::hex!(
+            "
+            45 20 00 38 00 00 40 00 70 01 33 ea 14 00 00 fe
+            c0 a8 01 15 03 04 f7 83 00 00 05 78 45 00 00 54
+            00 00 40 00 80 01 23 ee c0 a8 01 15 14 00 00 fe
+            08 00 fb d9 7b 01 81 24
+           "
+        );
+        let mut mocket = MockSocket::new();
+        mocket
+            .expect_read()
+            .times(1)
+            .returning(mocket_read!(expected_read_buf));
+        let resp = recv_icmp_probe(
+            &mut mocket,
+            &mut [0_u8; 1024],
+            Protocol::Icmp,
+            IcmpExtensionParseMode::Disabled,
+            true,
+            None,
+        )?
+        .unwrap();
+
+        let Response::DestinationUnreachable(_, icmp_code, next_hop_mtu, _) = resp else {
+            panic!("expected DestinationUnreachable")
+        };
+        assert_eq!(IcmpPacketCode(4), icmp_code);
+        assert_eq!(Some(1400), next_hop_mtu);
+        Ok(())
+    }
+
     #[test]
     fn test_recv_icmp_probe_time_exceeded_udp_no_extensions() -> anyhow::Result<()> {
         let expected_read_buf = hex_literal::hex!(
@@ -1180,8 +1861,15 @@ mod tests {
             .expect_read()
             .times(1)
             .returning(mocket_read!(expected_read_buf));
-        let resp =
-            recv_icmp_probe(&mut mocket, Protocol::Udp, IcmpExtensionParseMode::Disabled)?.unwrap();
+        let resp = recv_icmp_probe(
+            &mut mocket,
+            &mut [0_u8; 1024],
+            Protocol::Udp,
+            IcmpExtensionParseMode::Disabled,
+            true,
+            None,
+        )?
+        .unwrap();
 
         let Response::TimeExceeded(
             ResponseData {
@@ -1195,6 +1883,7 @@ mod tests {
                         checksum,
                         payload_len,
                         has_magic,
+                        payload_header,
                     }),
                 ..
             },
@@ -1204,6 +1893,7 @@ mod tests {
         else {
             panic!("expected TimeExceeded")
         };
+        assert_eq!(None, payload_header);
         assert_eq!(IpAddr::V4(Ipv4Addr::from_str("192.168.1.1").unwrap()), addr);
         assert_eq!(36969, identifier);
         assert_eq!(
@@ -1238,8 +1928,15 @@ mod tests {
             .expect_read()
             .times(1)
             .returning(mocket_read!(expected_read_buf));
-        let resp =
-            recv_icmp_probe(&mut mocket, Protocol::Udp, IcmpExtensionParseMode::Disabled)?.unwrap();
+        let resp = recv_icmp_probe(
+            &mut mocket,
+            &mut [0_u8; 1024],
+            Protocol::Udp,
+            IcmpExtensionParseMode::Disabled,
+            true,
+            None,
+        )?
+        .unwrap();
 
         let Response::DestinationUnreachable(
             ResponseData {
@@ -1253,15 +1950,18 @@ mod tests {
                         checksum,
                         payload_len,
                         has_magic,
+                        payload_header,
                     }),
                 ..
             },
             icmp_code,
+            _,
             extensions,
         ) = resp
         else {
             panic!("expected DestinationUnreachable")
         };
+        assert_eq!(None, payload_header);
         assert_eq!(IpAddr::V4(Ipv4Addr::from_str("9.9.9.9").unwrap()), addr);
         assert_eq!(41481, identifier);
         assert_eq!(
@@ -1295,8 +1995,15 @@ mod tests {
             .expect_read()
             .times(1)
             .returning(mocket_read!(expected_read_buf));
-        let resp =
-            recv_icmp_probe(&mut mocket, Protocol::Tcp, IcmpExtensionParseMode::Disabled)?.unwrap();
+        let resp = recv_icmp_probe(
+            &mut mocket,
+            &mut [0_u8; 1024],
+            Protocol::Tcp,
+            IcmpExtensionParseMode::Disabled,
+            true,
+            None,
+        )?
+        .unwrap();
 
         let Response::TimeExceeded(
             ResponseData {
@@ -1330,6 +2037,100 @@ mod tests {
         Ok(())
     }
 
+    // Test that a TCP probe is matched when only the first 8 bytes of the original TCP header are
+    // quoted.
+    #[test]
+    fn test_recv_icmp_probe_time_exceeded_tcp_truncated() -> anyhow::Result<()> {
+        let expected_read_buf = hex_literal::hex!(
+            "
+            45 20 00 38 a6 9d 00 00 3b 01 55 09 d1 55 f0 eb
+            c0 a8 01 15 0b 00 c4 75 00 00 00 00 45 80 00 40
+            00 00 40 00 01 06 5b f2 c0 a8 01 15 8e fa cc 8e
+            80 fd 00 50 61 f2 4d 4a
+           "
+        );
+        let mut mocket = MockSocket::new();
+        mocket
+            .expect_read()
+            .times(1)
+            .returning(mocket_read!(expected_read_buf));
+        let resp = recv_icmp_probe(
+            &mut mocket,
+            &mut [0_u8; 1024],
+            Protocol::Tcp,
+            IcmpExtensionParseMode::Disabled,
+            true,
+            None,
+        )?
+        .unwrap();
+
+        let Response::TimeExceeded(
+            ResponseData {
+                addr,
+                resp_seq:
+                    ResponseSeq::Tcp(ResponseSeqTcp {
+                        dest_addr,
+                        src_port,
+                        dest_port,
+                    }),
+                ..
+            },
+            ..,
+        ) = resp
+        else {
+            panic!("expected TimeExceeded")
+        };
+        assert_eq!(
+            IpAddr::V4(Ipv4Addr::from_str("209.85.240.235").unwrap()),
+            addr
+        );
+        assert_eq!(
+            IpAddr::V4(Ipv4Addr::from_str("142.250.204.142").unwrap()),
+            dest_addr
+        );
+        assert_eq!(33021, src_port);
+        assert_eq!(80, dest_port);
+        Ok(())
+    }
+
+    // Test that a packet with an invalid ICMP checksum is rejected, unless validation is disabled.
+    #[test_case::test_case(true; "validated")]
+    #[test_case::test_case(false; "not validated")]
+    fn test_recv_icmp_probe_invalid_checksum(validate_checksum: bool) {
+        let expected_read_buf = hex_literal::hex!(
+            "
+            45 20 00 38 a6 9d 00 00 3b 01 55 09 d1 55 f0 eb
+            c0 a8 01 15 0b 00 c4 76 00 00 00 00 45 80 00 40
+            00 00 40 00 01 06 5b f2 c0 a8 01 15 8e fa cc 8e
+            80 fd 00 50 61 f2 4d 4a
+           "
+        );
+        let mut mocket = MockSocket::new();
+        mocket
+            .expect_read()
+            .times(1)
+            .returning(mocket_read!(expected_read_buf));
+        let resp = recv_icmp_probe(
+            &mut mocket,
+            &mut [0_u8; 1024],
+            Protocol::Tcp,
+            IcmpExtensionParseMode::Disabled,
+            validate_checksum,
+            None,
+        );
+        if validate_checksum {
+            assert!(matches!(
+                resp,
+                Err(Error::InvalidIcmpChecksum {
+                    expected: 0xc475,
+                    actual: 0xc476
+                })
+            ));
+        } else {
+            assert!(matches!(resp, Ok(Some(Response::TimeExceeded(..)))));
+        }
+    }
+
     #[test]
     fn test_recv_icmp_probe_destination_unreachable_tcp_no_extensions() -> anyhow::Result<()> {
         let expected_read_buf = hex_literal::hex!(
@@ -1347,8 +2148,15 @@ mod tests {
             .expect_read()
             .times(1)
             .returning(mocket_read!(expected_read_buf));
-        let resp =
-            recv_icmp_probe(&mut mocket, Protocol::Tcp, IcmpExtensionParseMode::Disabled)?.unwrap();
+        let resp = recv_icmp_probe(
+            &mut mocket,
+            &mut [0_u8; 1024],
+            Protocol::Tcp,
+            IcmpExtensionParseMode::Disabled,
+            true,
+            None,
+        )?
+        .unwrap();
 
         let Response::DestinationUnreachable(
             ResponseData {
@@ -1362,6 +2170,7 @@ mod tests {
                 ..
             },
             icmp_code,
+            _,
             extensions,
         ) = resp
         else {
@@ -1397,15 +2206,130 @@ mod tests {
             .expect_read()
             .times(3)
             .returning(mocket_read!(expected_read_buf));
-        let resp = recv_icmp_probe(&mut mocket, Protocol::Icmp, IcmpExtensionParseMode::Enabled)?;
+        let resp = recv_icmp_probe(
+            &mut mocket,
+            &mut [0_u8; 1024],
+            Protocol::Icmp,
+            IcmpExtensionParseMode::Enabled,
+            true,
+            None,
+        )?;
         assert!(resp.is_some());
-        let resp = recv_icmp_probe(&mut mocket, Protocol::Udp, IcmpExtensionParseMode::Enabled)?;
+        let resp = recv_icmp_probe(
+            &mut mocket,
+            &mut [0_u8; 1024],
+            Protocol::Udp,
+            IcmpExtensionParseMode::Enabled,
+            true,
+            None,
+        )?;
         assert!(resp.is_none());
-        let resp = recv_icmp_probe(&mut mocket, Protocol::Tcp, IcmpExtensionParseMode::Enabled)?;
+        let resp = recv_icmp_probe(
+            &mut mocket,
+            &mut [0_u8; 1024],
+            Protocol::Tcp,
+            IcmpExtensionParseMode::Enabled,
+            true,
+            None,
+        )?;
         assert!(resp.is_none());
         Ok(())
     }
 
+    #[test]
+    fn test_recv_icmp_probe_parameter_problem_udp() -> anyhow::Result<()> {
+        let expected_read_buf = hex_literal::hex!(
+            "
+            45 c0 00 70 0e c8 00 00 40 01 e7 9e c0 a8 01 01
+            c0 a8 01 15 0c 00 fd 97 14 00 00 00 45 00 00 54
+            90 69 00 00 01 11 0b ea c0 a8 01 15 8e fa cc 8e
+            7c 55 81 06 00 40 e4 cb 00 00 00 00 00 00 00 00
+            00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00
+            00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00
+            00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00
+           "
+        );
+        let mut mocket = MockSocket::new();
+        mocket
+            .expect_read()
+            .times(1)
+            .returning(mocket_read!(expected_read_buf));
+        let resp = recv_icmp_probe(
+            &mut mocket,
+            &mut [0_u8; 1024],
+            Protocol::Udp,
+            IcmpExtensionParseMode::Disabled,
+            true,
+            None,
+        )?
+        .unwrap();
+
+        let Response::ParameterProblem(
+            ResponseData {
+                addr,
+                resp_seq:
+                    ResponseSeq::Udp(ResponseSeqUdp {
+                        identifier,
+                        dest_addr,
+                        src_port,
+                        dest_port,
+                        ..
+                    }),
+                ..
+            },
+            icmp_code,
+            pointer,
+        ) = resp
+        else {
+            panic!("expected ParameterProblem")
+        };
+        assert_eq!(IpAddr::V4(Ipv4Addr::from_str("192.168.1.1").unwrap()), addr);
+        assert_eq!(36969, identifier);
+        assert_eq!(
+            IpAddr::V4(Ipv4Addr::from_str("142.250.204.142").unwrap()),
+            dest_addr
+        );
+        assert_eq!(31829, src_port);
+        assert_eq!(33030, dest_port);
+        assert_eq!(IcmpPacketCode(0), icmp_code);
+        assert_eq!(20, pointer);
+        Ok(())
+    }
+
+    // A `ParameterProblem` which quotes a datagram of a different protocol to the probes is not
+    // attributed to a probe.
+    #[test]
+    fn test_recv_icmp_probe_parameter_problem_unrelated_ignored() -> anyhow::Result<()> {
+        let expected_read_buf = hex_literal::hex!(
+            "
+            45 c0 00 70 0e c8 00 00 40 01 e7 9e c0 a8 01 01
+            c0 a8 01 15 0c 00 fd 97 14 00 00 00 45 00 00 54
+            90 69 00 00 01 11 0b ea c0 a8 01 15 8e fa cc 8e
+            7c 55 81 06 00 40 e4 cb 00 00 00 00 00 00 00 00
+            00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00
+            00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00
+            00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00
+           "
+        );
+        let mut mocket = MockSocket::new();
+        mocket
+            .expect_read()
+            .times(2)
+            .returning(mocket_read!(expected_read_buf));
+        for protocol in [Protocol::Icmp, Protocol::Tcp] {
+            let resp = recv_icmp_probe(
+                &mut mocket,
+                &mut [0_u8; 1024],
+                protocol,
+                IcmpExtensionParseMode::Disabled,
+                true,
+                None,
+            )?;
+            assert!(resp.is_none());
+        }
+        Ok(())
+    }
+
     #[test]
     fn test_recv_icmp_probe_wrong_udp_original_datagram_type_ignored() -> anyhow::Result<()> {
         let expected_read_buf = hex_literal::hex!(
@@ -1424,11 +2348,32 @@ mod tests {
             .expect_read()
             .times(3)
             .returning(mocket_read!(expected_read_buf));
-        let resp = recv_icmp_probe(&mut mocket, Protocol::Udp, IcmpExtensionParseMode::Enabled)?;
+        let resp = recv_icmp_probe(
+            &mut mocket,
+            &mut [0_u8; 1024],
+            Protocol::Udp,
+            IcmpExtensionParseMode::Enabled,
+            true,
+            None,
+        )?;
         assert!(resp.is_some());
-        let resp = recv_icmp_probe(&mut mocket, Protocol::Icmp, IcmpExtensionParseMode::Enabled)?;
+        let resp = recv_icmp_probe(
+            &mut mocket,
+            &mut [0_u8; 1024],
+            Protocol::Icmp,
+            IcmpExtensionParseMode::Enabled,
+            true,
+            None,
+        )?;
         assert!(resp.is_none());
-        let resp = recv_icmp_probe(&mut mocket, Protocol::Tcp, IcmpExtensionParseMode::Enabled)?;
+        let resp = recv_icmp_probe(
+            &mut mocket,
+            &mut [0_u8; 1024],
+            Protocol::Tcp,
+            IcmpExtensionParseMode::Enabled,
+            true,
+            None,
+        )?;
         assert!(resp.is_none());
         Ok(())
     }
@@ -1450,11 +2395,92 @@ mod tests {
             .expect_read()
             .times(3)
             .returning(mocket_read!(expected_read_buf));
-        let resp = recv_icmp_probe(&mut mocket, Protocol::Tcp, IcmpExtensionParseMode::Enabled)?;
+        let resp = recv_icmp_probe(
+            &mut mocket,
+            &mut [0_u8; 1024],
+            Protocol::Tcp,
+            IcmpExtensionParseMode::Enabled,
+            true,
+            None,
+        )?;
         assert!(resp.is_some());
-        let resp = recv_icmp_probe(&mut mocket, Protocol::Icmp, IcmpExtensionParseMode::Enabled)?;
+        let resp = recv_icmp_probe(
+            &mut mocket,
+            &mut [0_u8; 1024],
+            Protocol::Icmp,
+            IcmpExtensionParseMode::Enabled,
+            true,
+            None,
+        )?;
         assert!(resp.is_none());
-        let resp = recv_icmp_probe(&mut mocket, Protocol::Udp, IcmpExtensionParseMode::Enabled)?;
+        let resp = recv_icmp_probe(
+            &mut mocket,
+            &mut [0_u8; 1024],
+            Protocol::Udp,
+            IcmpExtensionParseMode::Enabled,
+            true,
+            None,
+        )?;
+        assert!(resp.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_recv_icmp_probe_truncated_ignored() -> anyhow::Result<()> {
+        let expected_read_buf = hex_literal::hex!(
+            "
+            45 c0 00 70 0e c8 00 00 40 01 e7 9e c0 a8 01 01
+            c0 a8 01 15 0b 00 12 98 00 00 00 00 45 00 00 54
+            90 69 00 00 01 11 0b ea c0 a8 01 15 8e fa cc 8e
+            7c 55 81 06 00 40 e4 cb
+           "
+        );
+        for len in 0..=expected_read_buf.len() {
+            let mut mocket = MockSocket::new();
+            mocket
+                .expect_read()
+                .times(1)
+                .returning(move |buf: &mut [u8]| -> IoResult<usize> {
+                    buf[..len].copy_from_slice(&expected_read_buf[..len]);
+                    Ok(len)
+                });
+            let resp = recv_icmp_probe(
+                &mut mocket,
+                &mut [0_u8; 1024],
+                Protocol::Udp,
+                IcmpExtensionParseMode::Enabled,
+                false,
+                None,
+            )?;
+            assert_eq!(len == expected_read_buf.len(), resp.is_some());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_recv_icmp_probe_header_length_exceeds_buffer_ignored() -> anyhow::Result<()> {
+        let expected_read_buf = hex_literal::hex!(
+            "
+            4f c0 00 70 0e c8 00 00 40 01 e7 9e c0 a8 01 01
+            c0 a8 01 15 0b 00 12 98 00 00 00 00 45 00 00 54
+           "
+        );
+        let mut mocket = MockSocket::new();
+        mocket
+            .expect_read()
+            .times(1)
+            .returning(move |buf: &mut [u8]| -> IoResult<usize> {
+                buf[..expected_read_buf.len()].copy_from_slice(&expected_read_buf);
+                Ok(expected_read_buf.len())
+            });
+        let resp = recv_icmp_probe(
+            &mut mocket,
+            &mut [0_u8; 1024],
+            Protocol::Udp,
+            IcmpExtensionParseMode::Enabled,
+            true,
+            None,
+        )?;
         assert!(resp.is_none());
         Ok(())
     }
@@ -1584,7 +2610,14 @@ mod tests {
             .expect_read()
             .times(1)
             .returning(mocket_read!(expected_read_buf));
-        let resp = recv_icmp_probe(&mut mocket, Protocol::Udp, IcmpExtensionParseMode::Enabled)?;
+        let resp = recv_icmp_probe(
+            &mut mocket,
+            &mut [0_u8; 1024],
+            Protocol::Udp,
+            IcmpExtensionParseMode::Enabled,
+            false,
+            None,
+        )?;
         assert!(resp.is_none());
         Ok(())
     }
@@ -1597,8 +2630,10 @@ mod tests {
             Port(0),
             TimeToLive(10),
             RoundId(0),
-            SystemTime::now(),
+            UNIX_EPOCH + Duration::new(1_700_000_000, 123_456_789),
             Flags::empty(),
+            FlowLabel(0),
+            FlowIndex(0),
         )
     }
 
@@ -1610,8 +2645,10 @@ mod tests {
             Port(dest_port),
             TimeToLive(10),
             RoundId(0),
-            SystemTime::now(),
+            UNIX_EPOCH + Duration::new(1_700_000_000, 123_456_789),
             Flags::empty(),
+            FlowLabel(0),
+            FlowIndex(0),
         )
     }
 }
@@ -1,41 +1,34 @@
-use crate::config::IcmpExtensionParseMode;
+use crate::config::{IcmpExtensionParseMode, Ipv6ExtensionHeader};
 use crate::error::{Error, Result};
-use crate::net::channel::MAX_PACKET_SIZE;
-use crate::net::common::process_result;
+use crate::net::capture::Capture;
+use crate::net::common::{
+    bind_device, discard_malformed, extract_tcp_ports, process_io_error, process_result,
+    validate_packet_size,
+};
+use crate::net::payload::{make_pattern, make_payload, PayloadHeader};
 use crate::net::socket::{Socket, SocketError};
 use crate::probe::{
     Extensions, IcmpPacketCode, Probe, Response, ResponseData, ResponseSeq, ResponseSeqIcmp,
     ResponseSeqTcp, ResponseSeqUdp,
 };
-use crate::types::{PacketSize, PayloadPattern, Sequence, TraceId};
+use crate::types::{FlowLabel, PacketSize, Sequence, TypeOfService};
 use crate::{Flags, Port, PrivilegeMode, Protocol};
 use std::io::ErrorKind;
-use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+use std::net::{IpAddr, Ipv6Addr, SocketAddr, SocketAddrV6};
 use std::time::SystemTime;
 use tracing::instrument;
 use trippy_packet::checksum::{icmp_ipv6_checksum, udp_ipv6_checksum};
 use trippy_packet::icmpv6::destination_unreachable::DestinationUnreachablePacket;
 use trippy_packet::icmpv6::echo_reply::EchoReplyPacket;
 use trippy_packet::icmpv6::echo_request::EchoRequestPacket;
+use trippy_packet::icmpv6::packet_too_big::PacketTooBigPacket;
+use trippy_packet::icmpv6::parameter_problem::ParameterProblemPacket;
 use trippy_packet::icmpv6::time_exceeded::TimeExceededPacket;
 use trippy_packet::icmpv6::{IcmpCode, IcmpPacket, IcmpTimeExceededCode, IcmpType};
 use trippy_packet::ipv6::Ipv6Packet;
-use trippy_packet::tcp::TcpPacket;
 use trippy_packet::udp::UdpPacket;
 use trippy_packet::IpProtocol;
 
-/// The maximum size of UDP packet we allow.
-const MAX_UDP_PACKET_BUF: usize = MAX_PACKET_SIZE - Ipv6Packet::minimum_packet_size();
-
-/// The maximum size of UDP payload we allow.
-const MAX_UDP_PAYLOAD_BUF: usize = MAX_UDP_PACKET_BUF - UdpPacket::minimum_packet_size();
-
-/// The maximum size of UDP packet we allow.
-const MAX_ICMP_PACKET_BUF: usize = MAX_PACKET_SIZE - Ipv6Packet::minimum_packet_size();
-
-/// The maximum size of ICMP payload we allow.
-const MAX_ICMP_PAYLOAD_BUF: usize = MAX_ICMP_PACKET_BUF - IcmpPacket::minimum_packet_size();
-
 /// The minimum size of ICMP packets we allow.
 const MIN_PACKET_SIZE_ICMP: usize =
     Ipv6Packet::minimum_packet_size() + IcmpPacket::minimum_packet_size();
@@ -44,94 +37,141 @@ const MIN_PACKET_SIZE_ICMP: usize =
 const MIN_PACKET_SIZE_UDP: usize =
     Ipv6Packet::minimum_packet_size() + UdpPacket::minimum_packet_size();
 
+/// The minimum size of packets we allow for a given `Protocol`.
+///
+/// The packet size does not apply to TCP probes and so any size is allowed.
+pub const fn min_packet_size(protocol: Protocol) -> usize {
+    match protocol {
+        Protocol::Icmp => MIN_PACKET_SIZE_ICMP,
+        Protocol::Udp => MIN_PACKET_SIZE_UDP,
+        Protocol::Tcp => 0,
+    }
+}
+
 /// Magic prefix for IPv6/UDP/Dublin payloads.
 const MAGIC: &[u8] = b"trippy";
 
-#[instrument(skip(icmp_send_socket, probe))]
+#[allow(clippy::too_many_arguments)]
+#[instrument(skip(icmp_send_socket, probe, capture))]
 pub fn dispatch_icmp_probe<S: Socket>(
     icmp_send_socket: &mut S,
     probe: Probe,
     src_addr: Ipv6Addr,
     dest_addr: Ipv6Addr,
     packet_size: PacketSize,
-    payload_pattern: PayloadPattern,
+    max_packet_size: PacketSize,
+    payload_pattern: &[u8],
+    extension_header: Ipv6ExtensionHeader,
+    capture: Option<&mut Capture>,
 ) -> Result<()> {
-    let mut icmp_buf = [0_u8; MAX_ICMP_PACKET_BUF];
-    let packet_size = usize::from(packet_size.0);
-    if !(MIN_PACKET_SIZE_ICMP..=MAX_PACKET_SIZE).contains(&packet_size) {
-        return Err(Error::InvalidPacketSize(packet_size));
-    }
+    let min_packet_size = MIN_PACKET_SIZE_ICMP + extension_header.size();
+    let packet_size = validate_packet_size(packet_size, min_packet_size, max_packet_size)?;
+    let mut icmp_buf = vec![0_u8; packet_size - Ipv6Packet::minimum_packet_size()];
     let echo_request = make_echo_request_icmp_packet(
         &mut icmp_buf,
         src_addr,
         dest_addr,
-        probe.identifier,
-        probe.sequence,
-        icmp_payload_size(packet_size),
+        &probe,
+        icmp_payload_size(packet_size) - extension_header.size(),
         payload_pattern,
     )?;
     icmp_send_socket.set_unicast_hops_v6(probe.ttl.0)?;
-    let remote_addr = SocketAddr::new(IpAddr::V6(dest_addr), 0);
-    icmp_send_socket.send_to(echo_request.packet(), remote_addr)?;
-    Ok(())
+    let remote_addr = make_remote_addr(dest_addr, 0, probe.flow_label);
+    icmp_send_socket
+        .send_to(echo_request.packet(), remote_addr)
+        .map_err(process_io_error)?;
+    capture_packet(
+        capture,
+        IpProtocol::IcmpV6,
+        src_addr,
+        dest_addr,
+        probe.ttl.0,
+        probe.flow_label,
+        echo_request.packet(),
+    )
 }
 
 #[allow(clippy::too_many_arguments)]
-#[instrument(skip(raw_send_socket, probe))]
+#[instrument(skip(send_socket, probe, capture))]
 pub fn dispatch_udp_probe<S: Socket>(
-    raw_send_socket: &mut S,
+    send_socket: &mut S,
     probe: Probe,
     src_addr: Ipv6Addr,
+    src_scope_id: u32,
     dest_addr: Ipv6Addr,
     privilege_mode: PrivilegeMode,
+    bound_send_socket: bool,
     packet_size: PacketSize,
-    payload_pattern: PayloadPattern,
+    max_packet_size: PacketSize,
+    payload_pattern: &[u8],
     initial_sequence: Sequence,
+    tos: TypeOfService,
+    extension_header: Ipv6ExtensionHeader,
+    interface_index: Option<u32>,
+    capture: Option<&mut Capture>,
 ) -> Result<()> {
-    let packet_size = usize::from(packet_size.0);
-    if !(MIN_PACKET_SIZE_UDP..=MAX_PACKET_SIZE).contains(&packet_size) {
-        return Err(Error::InvalidPacketSize(packet_size));
-    }
-    let payload_size = udp_payload_size(packet_size);
-    let payload = &[payload_pattern.0; MAX_UDP_PAYLOAD_BUF][0..payload_size];
+    let min_packet_size = MIN_PACKET_SIZE_UDP + extension_header.size();
+    let packet_size = validate_packet_size(packet_size, min_packet_size, max_packet_size)?;
+    let payload_size = udp_payload_size(packet_size) - extension_header.size();
+    let payload = &make_payload(&probe, payload_size, payload_pattern);
     match privilege_mode {
         PrivilegeMode::Privileged => dispatch_udp_probe_raw(
-            raw_send_socket,
+            send_socket,
             probe,
             src_addr,
             dest_addr,
             payload,
             payload_pattern,
             initial_sequence,
+            capture,
+        ),
+        PrivilegeMode::Unprivileged if bound_send_socket => dispatch_udp_probe_non_raw_bound(
+            send_socket,
+            probe,
+            src_addr,
+            dest_addr,
+            payload,
+            capture,
+        ),
+        PrivilegeMode::Unprivileged => dispatch_udp_probe_non_raw::<S>(
+            probe,
+            src_addr,
+            src_scope_id,
+            dest_addr,
+            payload,
+            tos,
+            extension_header,
+            interface_index,
+            capture,
         ),
-        PrivilegeMode::Unprivileged => {
-            dispatch_udp_probe_non_raw::<S>(probe, src_addr, dest_addr, payload)
-        }
     }
 }
 
-#[instrument(skip(udp_send_socket, probe))]
+#[allow(clippy::too_many_arguments)]
+#[instrument(skip(udp_send_socket, probe, capture))]
 fn dispatch_udp_probe_raw<S: Socket>(
     udp_send_socket: &mut S,
     probe: Probe,
     src_addr: Ipv6Addr,
     dest_addr: Ipv6Addr,
     payload: &[u8],
-    payload_pattern: PayloadPattern,
+    payload_pattern: &[u8],
     initial_sequence: Sequence,
+    capture: Option<&mut Capture>,
 ) -> Result<()> {
-    let mut udp_buf = [0_u8; MAX_UDP_PACKET_BUF];
-    let mut dublin_payload = [payload_pattern.0; MAX_UDP_PAYLOAD_BUF];
+    let mut dublin_payload;
     let payload_paris = probe.sequence.0.to_be_bytes();
     let payload = if probe.flags.contains(Flags::PARIS_CHECKSUM) {
         payload_paris.as_slice()
     } else if probe.flags.contains(Flags::DUBLIN_IPV6_PAYLOAD_LENGTH) {
         let payload_len = probe.sequence.0 - initial_sequence.0;
+        dublin_payload = make_pattern(usize::from(payload_len) + MAGIC.len(), payload_pattern);
         dublin_payload[..MAGIC.len()].copy_from_slice(MAGIC);
-        &dublin_payload[..usize::from(payload_len) + MAGIC.len()]
+        dublin_payload.as_slice()
     } else {
         payload
     };
+    let mut udp_buf = vec![0_u8; UdpPacket::minimum_packet_size() + payload.len()];
     let mut udp = make_udp_packet(
         &mut udp_buf,
         src_addr,
@@ -150,24 +190,81 @@ fn dispatch_udp_probe_raw<S: Socket>(
     // Note that we set the port to be 0 in the remote `SocketAddr` as the target port is encoded in
     // the `UDP` packet.  If we (redundantly) set the target port here then the send will fail
     // with `EINVAL`.
-    let remote_addr = SocketAddr::new(IpAddr::V6(dest_addr), 0);
-    udp_send_socket.send_to(udp.packet(), remote_addr)?;
-    Ok(())
+    let remote_addr = make_remote_addr(dest_addr, 0, probe.flow_label);
+    udp_send_socket
+        .send_to(udp.packet(), remote_addr)
+        .map_err(process_io_error)?;
+    capture_packet(
+        capture,
+        IpProtocol::Udp,
+        src_addr,
+        dest_addr,
+        probe.ttl.0,
+        probe.flow_label,
+        udp.packet(),
+    )
 }
 
-#[instrument(skip(probe))]
+#[allow(clippy::too_many_arguments)]
+#[instrument(skip(probe, capture))]
 fn dispatch_udp_probe_non_raw<S: Socket>(
     probe: Probe,
     src_addr: Ipv6Addr,
+    src_scope_id: u32,
     dest_addr: Ipv6Addr,
     payload: &[u8],
+    tos: TypeOfService,
+    extension_header: Ipv6ExtensionHeader,
+    interface_index: Option<u32>,
+    capture: Option<&mut Capture>,
 ) -> Result<()> {
-    let local_addr = SocketAddr::new(IpAddr::V6(src_addr), probe.src_port.0);
-    let remote_addr = SocketAddr::new(IpAddr::V6(dest_addr), probe.dest_port.0);
+    let local_addr = make_local_addr(src_addr, probe.src_port.0, src_scope_id);
     let mut socket = S::new_udp_send_socket_ipv6(false)?;
+    socket.set_tclass_v6(u32::from(tos.0))?;
+    socket.set_ipv6_extension_header(extension_header)?;
     process_result(local_addr, socket.bind(local_addr))?;
-    socket.set_unicast_hops_v6(probe.ttl.0)?;
-    socket.send_to(payload, remote_addr)?;
+    bind_device(&mut socket, interface_index)?;
+    dispatch_udp_probe_non_raw_bound(&mut socket, probe, src_addr, dest_addr, payload, capture)
+}
+
+/// Dispatch a UDP probe using a UDP datagram socket which is already bound to the probe source
+/// address and port.
+///
+/// The `UDP` header is added by the OS and so is synthesised for the capture, if any.
+#[instrument(skip(udp_send_socket, probe, capture))]
+fn dispatch_udp_probe_non_raw_bound<S: Socket>(
+    udp_send_socket: &mut S,
+    probe: Probe,
+    src_addr: Ipv6Addr,
+    dest_addr: Ipv6Addr,
+    payload: &[u8],
+    capture: Option<&mut Capture>,
+) -> Result<()> {
+    let remote_addr = make_remote_addr(dest_addr, probe.dest_port.0, probe.flow_label);
+    udp_send_socket.set_unicast_hops_v6(probe.ttl.0)?;
+    udp_send_socket
+        .send_to(payload, remote_addr)
+        .map_err(process_io_error)?;
+    if capture.is_some() {
+        let mut udp_buf = vec![0_u8; UdpPacket::minimum_packet_size() + payload.len()];
+        let udp = make_udp_packet(
+            &mut udp_buf,
+            src_addr,
+            dest_addr,
+            probe.src_port.0,
+            probe.dest_port.0,
+            payload,
+        )?;
+        capture_packet(
+            capture,
+            IpProtocol::Udp,
+            src_addr,
+            dest_addr,
+            probe.ttl.0,
+            probe.flow_label,
+            udp.packet(),
+        )?;
+    }
     Ok(())
 }
 
@@ -175,45 +272,150 @@ fn dispatch_udp_probe_non_raw<S: Socket>(
 pub fn dispatch_tcp_probe<S: Socket>(
     probe: &Probe,
     src_addr: Ipv6Addr,
+    src_scope_id: u32,
     dest_addr: Ipv6Addr,
+    tos: TypeOfService,
+    tcp_mss: Option<u16>,
+    interface_index: Option<u32>,
 ) -> Result<S> {
     let mut socket = S::new_stream_socket_ipv6()?;
-    let local_addr = SocketAddr::new(IpAddr::V6(src_addr), probe.src_port.0);
+    let local_addr = make_local_addr(src_addr, probe.src_port.0, src_scope_id);
     process_result(local_addr, socket.bind(local_addr))?;
+    bind_device(&mut socket, interface_index)?;
     socket.set_unicast_hops_v6(probe.ttl.0)?;
-    let remote_addr = SocketAddr::new(IpAddr::V6(dest_addr), probe.dest_port.0);
+    socket.set_tclass_v6(u32::from(tos.0))?;
+    if let Some(mss) = tcp_mss {
+        socket.set_mss(u32::from(mss))?;
+    }
+    let remote_addr = make_remote_addr(dest_addr, probe.dest_port.0, probe.flow_label);
     process_result(remote_addr, socket.connect(remote_addr))?;
     Ok(socket)
 }
 
-#[instrument(skip(recv_socket))]
+/// Make the local `SocketAddr` to bind a probe socket to.
+///
+/// The scope id is only meaningful for a link-local source address and must otherwise be zero.
+pub const fn make_local_addr(src_addr: Ipv6Addr, port: u16, scope_id: u32) -> SocketAddr {
+    SocketAddr::V6(SocketAddrV6::new(src_addr, port, 0, scope_id))
+}
+
+/// Make the remote `SocketAddr` for a probe.
+///
+/// The flow label is carried in the `flowinfo` field of the `SocketAddrV6` which must be stored in
+/// network byte order.  Note that the flow label will only be honoured by the OS if the socket has
+/// been configured to allow this, see `new_icmp_send_socket_ipv6` & friends.
+const fn make_remote_addr(dest_addr: Ipv6Addr, port: u16, flow_label: FlowLabel) -> SocketAddr {
+    SocketAddr::V6(SocketAddrV6::new(dest_addr, port, flow_label.0.to_be(), 0))
+}
+
+/// Receive the next `ICMPv6` packet and generate a `Response`, if any.
+///
+/// If `checksum_dest_addr` is given then the checksum of the packet is validated using it as the
+/// destination address of the pseudo-header.
+#[instrument(skip(recv_socket, buf, capture))]
 pub fn recv_icmp_probe<S: Socket>(
     recv_socket: &mut S,
+    buf: &mut [u8],
     protocol: Protocol,
     icmp_extension_mode: IcmpExtensionParseMode,
+    checksum_dest_addr: Option<Ipv6Addr>,
+    capture: Option<&mut Capture>,
 ) -> Result<Option<Response>> {
-    let mut buf = [0_u8; MAX_PACKET_SIZE];
-    match recv_socket.recv_from(&mut buf) {
+    match recv_socket.recv_from(buf) {
         Ok((bytes_read, addr)) => {
-            let icmp_v6 = IcmpPacket::new_view(&buf[..bytes_read])?;
             let src_addr = match addr.as_ref().ok_or(Error::MissingAddr)? {
                 SocketAddr::V6(addr) => addr.ip(),
                 SocketAddr::V4(_) => panic!(),
             };
-            Ok(extract_probe_resp(
-                protocol,
-                icmp_extension_mode,
-                &icmp_v6,
-                *src_addr,
-            )?)
+            if let Some(capture) = capture {
+                // the hop limit of a received packet is not known and so is recorded as zero.
+                let IpAddr::V6(local_addr) = capture.local_addr() else {
+                    unreachable!()
+                };
+                capture_packet(
+                    Some(capture),
+                    IpProtocol::IcmpV6,
+                    *src_addr,
+                    local_addr,
+                    0,
+                    FlowLabel(0),
+                    &buf[..bytes_read],
+                )?;
+            }
+            discard_malformed(
+                IcmpPacket::new_view(&buf[..bytes_read])
+                    .map_err(Error::from)
+                    .and_then(|icmp_v6| {
+                        if let Some(dest_addr) = checksum_dest_addr {
+                            validate_icmp_checksum(&icmp_v6, *src_addr, dest_addr)?;
+                        }
+                        extract_probe_resp(protocol, icmp_extension_mode, &icmp_v6, *src_addr)
+                    }),
+            )
         }
         Err(err) => match err.kind() {
             ErrorKind::WouldBlock => Ok(None),
-            _ => Err(Error::IoError(err)),
+            _ => Err(process_io_error(err)),
         },
     }
 }
 
+/// Generate a `Response` for the next ICMP error queued on the `UDP` socket of a probe, if any.
+///
+/// The socket is bound to the probe `src_port` and the destination of the datagram which caused
+/// the error gives the destination address and port, from which the probe is identified.
+#[instrument(skip(udp_socket))]
+pub fn recv_udp_socket_err<S: Socket>(
+    udp_socket: &mut S,
+    src_port: Port,
+) -> Result<Option<Response>> {
+    let Some(err) = udp_socket.recv_err()? else {
+        return Ok(None);
+    };
+    let Some(dest) = err.dest else {
+        return Ok(None);
+    };
+    let resp_seq = ResponseSeq::Udp(ResponseSeqUdp::new(
+        0,
+        dest.ip(),
+        src_port.0,
+        dest.port(),
+        0,
+        0,
+        false,
+        None,
+    ));
+    let data = ResponseData::new(SystemTime::now(), err.origin, resp_seq);
+    let icmp_code = IcmpCode(err.icmp_code);
+    Ok(match IcmpType::from(err.icmp_type) {
+        IcmpType::TimeExceeded
+            if IcmpTimeExceededCode::from(icmp_code) == IcmpTimeExceededCode::TtlExpired =>
+        {
+            Some(Response::TimeExceeded(
+                data,
+                IcmpPacketCode(icmp_code.0),
+                None,
+            ))
+        }
+        IcmpType::DestinationUnreachable => Some(Response::DestinationUnreachable(
+            data,
+            IcmpPacketCode(icmp_code.0),
+            None,
+            None,
+        )),
+        IcmpType::PacketTooBig => Some(Response::PacketTooBig(
+            data,
+            u16::try_from(err.info).unwrap_or(u16::MAX),
+        )),
+        IcmpType::ParameterProblem => Some(Response::ParameterProblem(
+            data,
+            IcmpPacketCode(icmp_code.0),
+            err.info,
+        )),
+        _ => None,
+    })
+}
+
 #[instrument(skip(tcp_socket))]
 pub fn recv_tcp_socket<S: Socket>(
     tcp_socket: &mut S,
@@ -255,6 +457,34 @@ pub fn recv_tcp_socket<S: Socket>(
 }
 
 /// Create a `UdpPacket`
+/// Write a packet to the capture, if any, synthesising the IPv6 header.
+///
+/// The traffic class and any extension header are not recorded.
+fn capture_packet(
+    capture: Option<&mut Capture>,
+    next_header: IpProtocol,
+    src_addr: Ipv6Addr,
+    dest_addr: Ipv6Addr,
+    hop_limit: u8,
+    flow_label: FlowLabel,
+    payload: &[u8],
+) -> Result<()> {
+    if let Some(capture) = capture {
+        let mut ipv6_buf = vec![0_u8; Ipv6Packet::minimum_packet_size() + payload.len()];
+        let mut ipv6 = Ipv6Packet::new(&mut ipv6_buf)?;
+        ipv6.set_version(6);
+        ipv6.set_flow_label(flow_label.0);
+        ipv6.set_payload_length(payload.len() as u16);
+        ipv6.set_next_header(next_header);
+        ipv6.set_hop_limit(hop_limit);
+        ipv6.set_source_address(src_addr);
+        ipv6.set_destination_address(dest_addr);
+        ipv6.set_payload(payload);
+        capture.write(ipv6.packet())?;
+    }
+    Ok(())
+}
+
 fn make_udp_packet<'a>(
     udp_buf: &'a mut [u8],
     src_addr: Ipv6Addr,
@@ -274,23 +504,23 @@ fn make_udp_packet<'a>(
 }
 
 /// Create an ICMP `EchoRequest` packet.
-fn make_echo_request_icmp_packet(
-    icmp_buf: &mut [u8],
+///
+/// The payload starts with the `PayloadHeader` of the probe, if it is large enough.
+fn make_echo_request_icmp_packet<'a>(
+    icmp_buf: &'a mut [u8],
     src_addr: Ipv6Addr,
     dest_addr: Ipv6Addr,
-    identifier: TraceId,
-    sequence: Sequence,
+    probe: &Probe,
     payload_size: usize,
-    payload_pattern: PayloadPattern,
-) -> Result<EchoRequestPacket<'_>> {
-    let payload_buf = [payload_pattern.0; MAX_ICMP_PAYLOAD_BUF];
+    payload_pattern: &[u8],
+) -> Result<EchoRequestPacket<'a>> {
     let packet_size = IcmpPacket::minimum_packet_size() + payload_size;
     let mut icmp = EchoRequestPacket::new(&mut icmp_buf[..packet_size])?;
     icmp.set_icmp_type(IcmpType::EchoRequest);
     icmp.set_icmp_code(IcmpCode(0));
-    icmp.set_identifier(identifier.0);
-    icmp.set_payload(&payload_buf[..payload_size]);
-    icmp.set_sequence(sequence.0);
+    icmp.set_identifier(probe.identifier.0);
+    icmp.set_payload(&make_payload(probe, payload_size, payload_pattern));
+    icmp.set_sequence(probe.sequence.0);
     icmp.set_checksum(icmp_ipv6_checksum(icmp.packet(), src_addr, dest_addr));
     Ok(icmp)
 }
@@ -307,6 +537,21 @@ const fn udp_payload_size(packet_size: usize) -> usize {
     packet_size - udp_header_size - ip_header_size
 }
 
+/// Validate the checksum of an `ICMPv6` packet sent from `src_addr` to `dest_addr`.
+fn validate_icmp_checksum(
+    icmp_v6: &IcmpPacket<'_>,
+    src_addr: Ipv6Addr,
+    dest_addr: Ipv6Addr,
+) -> Result<()> {
+    let expected = icmp_ipv6_checksum(icmp_v6.packet(), src_addr, dest_addr);
+    let actual = icmp_v6.get_checksum();
+    if expected == actual {
+        Ok(())
+    } else {
+        Err(Error::InvalidIcmpChecksum { expected, actual })
+    }
+}
+
 fn extract_probe_resp(
     protocol: Protocol,
     icmp_extension_mode: IcmpExtensionParseMode,
@@ -356,16 +601,37 @@ fn extract_probe_resp(
                 Response::DestinationUnreachable(
                     ResponseData::new(recv, ip, resp_seq),
                     IcmpPacketCode(icmp_code.0),
+                    None,
                     extension,
                 )
             })
         }
+        IcmpType::PacketTooBig => {
+            let packet = PacketTooBigPacket::new_view(icmp_v6.packet())?;
+            let nested_ipv6 = Ipv6Packet::new_view(packet.payload())?;
+            let mtu = u16::try_from(packet.get_mtu()).unwrap_or(u16::MAX);
+            extract_probe_resp_seq(&nested_ipv6, protocol)?
+                .map(|resp_seq| Response::PacketTooBig(ResponseData::new(recv, ip, resp_seq), mtu))
+        }
+        IcmpType::ParameterProblem => {
+            let packet = ParameterProblemPacket::new_view(icmp_v6.packet())?;
+            let nested_ipv6 = Ipv6Packet::new_view(packet.payload())?;
+            let pointer = packet.get_pointer();
+            extract_probe_resp_seq(&nested_ipv6, protocol)?.map(|resp_seq| {
+                Response::ParameterProblem(
+                    ResponseData::new(recv, ip, resp_seq),
+                    IcmpPacketCode(icmp_code.0),
+                    pointer,
+                )
+            })
+        }
         IcmpType::EchoReply => match protocol {
             Protocol::Icmp => {
                 let packet = EchoReplyPacket::new_view(icmp_v6.packet())?;
                 let id = packet.get_identifier();
                 let seq = packet.get_sequence();
-                let resp_seq = ResponseSeq::Icmp(ResponseSeqIcmp::new(id, seq));
+                let payload_header = PayloadHeader::parse(packet.payload());
+                let resp_seq = ResponseSeq::Icmp(ResponseSeqIcmp::new(id, seq, payload_header));
                 Some(Response::EchoReply(
                     ResponseData::new(recv, ip, resp_seq),
                     IcmpPacketCode(icmp_code.0),
@@ -381,16 +647,20 @@ fn extract_probe_resp_seq(
     ipv6: &Ipv6Packet<'_>,
     protocol: Protocol,
 ) -> Result<Option<ResponseSeq>> {
-    Ok(match (protocol, ipv6.get_next_header()) {
+    let (next_header, payload) = ipv6.upper_layer_payload()?;
+    Ok(match (protocol, next_header) {
         (Protocol::Icmp, IpProtocol::IcmpV6) => {
-            let (identifier, sequence) = extract_echo_request(ipv6)?;
+            let (identifier, sequence, payload_header) = extract_echo_request(payload)?;
             Some(ResponseSeq::Icmp(ResponseSeqIcmp::new(
-                identifier, sequence,
+                identifier,
+                sequence,
+                payload_header,
             )))
         }
         (Protocol::Udp, IpProtocol::Udp) => {
-            let (src_port, dest_port, checksum, udp_payload_len) = extract_udp_packet(ipv6)?;
-            let has_magic = udp_payload_has_magic_prefix(ipv6)?;
+            let (src_port, dest_port, checksum, udp_payload_len, payload_header) =
+                extract_udp_packet(payload)?;
+            let has_magic = udp_payload_has_magic_prefix(payload)?;
             let payload_len = if has_magic {
                 udp_payload_len - MAGIC.len() as u16
             } else {
@@ -404,10 +674,11 @@ fn extract_probe_resp_seq(
                 checksum,
                 payload_len,
                 has_magic,
+                payload_header,
             )))
         }
         (Protocol::Tcp, IpProtocol::Tcp) => {
-            let (src_port, dest_port) = extract_tcp_packet(ipv6)?;
+            let (src_port, dest_port) = extract_tcp_ports(payload)?;
             Some(ResponseSeq::Tcp(ResponseSeqTcp::new(
                 IpAddr::V6(ipv6.get_destination_address()),
                 src_port,
@@ -418,50 +689,30 @@ fn extract_probe_resp_seq(
     })
 }
 
-fn extract_echo_request(ipv6: &Ipv6Packet<'_>) -> Result<(u16, u16)> {
-    let echo_request_packet = EchoRequestPacket::new_view(ipv6.payload())?;
+fn extract_echo_request(payload: &[u8]) -> Result<(u16, u16, Option<PayloadHeader>)> {
+    let echo_request_packet = EchoRequestPacket::new_view(payload)?;
     Ok((
         echo_request_packet.get_identifier(),
         echo_request_packet.get_sequence(),
+        PayloadHeader::parse(echo_request_packet.payload()),
     ))
 }
 
-fn extract_udp_packet(ipv6: &Ipv6Packet<'_>) -> Result<(u16, u16, u16, u16)> {
-    let udp_packet = UdpPacket::new_view(ipv6.payload())?;
+fn extract_udp_packet(payload: &[u8]) -> Result<(u16, u16, u16, u16, Option<PayloadHeader>)> {
+    let udp_packet = UdpPacket::new_view(payload)?;
     Ok((
         udp_packet.get_source(),
         udp_packet.get_destination(),
         udp_packet.get_checksum(),
-        udp_packet.get_length() - UdpPacket::minimum_packet_size() as u16,
+        udp_packet
+            .get_length()
+            .saturating_sub(UdpPacket::minimum_packet_size() as u16),
+        PayloadHeader::parse(udp_packet.payload()),
     ))
 }
 
-/// From [rfc4443] (section 2.4, point c):
-///
-///    "Every `ICMPv6` error message (type < 128) MUST include as much of
-///    the IPv6 offending (invoking) packet (the packet that caused the
-///    error) as possible without making the error message packet exceed
-///    the minimum IPv6 MTU"
-///
-/// From [rfc2460] (section 5):
-///
-///    "IPv6 requires that every link in the internet have an MTU of 1280
-///    octets or greater.  On any link that cannot convey a 1280-octet
-///    packet in one piece, link-specific fragmentation and reassembly must
-///    be provided at a layer below IPv6."
-///
-/// The maximum packet size we allow is 1024 and so we can safely assume that the originating IPv6
-/// packet being extracted will be at least as large as the minimum IPv6 packet size.
-///
-/// [rfc4443]: https://datatracker.ietf.org/doc/html/rfc4443#section-2.4
-/// [rfc2460]: https://datatracker.ietf.org/doc/html/rfc2460#section-5
-fn extract_tcp_packet(ipv6: &Ipv6Packet<'_>) -> Result<(u16, u16)> {
-    let tcp_packet = TcpPacket::new_view(ipv6.payload())?;
-    Ok((tcp_packet.get_source(), tcp_packet.get_destination()))
-}
-
-fn udp_payload_has_magic_prefix(ipv6: &Ipv6Packet<'_>) -> Result<bool> {
-    let udp_packet = UdpPacket::new_view(ipv6.payload())?;
+fn udp_payload_has_magic_prefix(payload: &[u8]) -> Result<bool> {
+    let udp_packet = UdpPacket::new_view(payload)?;
     Ok(udp_packet.payload().starts_with(MAGIC))
 }
 
@@ -471,10 +722,11 @@ mod tests {
     use crate::error::IoResult;
     use crate::mocket_recv_from;
     use crate::net::socket::MockSocket;
-    use crate::{Flags, Port, RoundId, TimeToLive};
+    use crate::{Flags, FlowIndex, FlowLabel, Port, RoundId, TimeToLive, TraceId};
     use mockall::predicate;
     use std::str::FromStr;
     use std::sync::Mutex;
+    use std::time::{Duration, UNIX_EPOCH};
 
     static MTX: Mutex<()> = Mutex::new(());
 
@@ -485,7 +737,7 @@ mod tests {
         let src_addr = Ipv6Addr::from_str("fd7a:115c:a1e0:ab12:4843:cd96:6263:82a")?;
         let dest_addr = Ipv6Addr::from_str("2a00:1450:4009:815::200e")?;
         let packet_size = PacketSize(48);
-        let payload_pattern = PayloadPattern(0x00);
+        let payload_pattern: &[u8] = &[0x00];
         let expected_send_to_buf = hex_literal::hex!("80 00 77 54 04 d2 80 e8");
         let expected_send_to_addr = SocketAddr::new(IpAddr::V6(dest_addr), 0);
 
@@ -510,7 +762,10 @@ mod tests {
             src_addr,
             dest_addr,
             packet_size,
+            PacketSize(1024),
             payload_pattern,
+            Ipv6ExtensionHeader::None,
+            None,
         )?;
         Ok(())
     }
@@ -521,11 +776,11 @@ mod tests {
         let src_addr = Ipv6Addr::from_str("fd7a:115c:a1e0:ab12:4843:cd96:6263:82a")?;
         let dest_addr = Ipv6Addr::from_str("2a00:1450:4009:815::200e")?;
         let packet_size = PacketSize(68);
-        let payload_pattern = PayloadPattern(0xff);
+        let payload_pattern: &[u8] = &[0xff];
         let expected_send_to_buf = hex_literal::hex!(
             "
-            80 00 77 40 04 d2 80 e8 ff ff ff ff ff ff ff ff
-            ff ff ff ff ff ff ff ff ff ff ff ff
+            80 00 8d a9 04 d2 80 e8 54 52 50 59 04 d2 80 e8
+            17 97 9c fe 3d 85 cd 15 ff ff ff ff
             "
         );
         let expected_send_to_addr = SocketAddr::new(IpAddr::V6(dest_addr), 0);
@@ -551,7 +806,10 @@ mod tests {
             src_addr,
             dest_addr,
             packet_size,
+            PacketSize(1024),
             payload_pattern,
+            Ipv6ExtensionHeader::None,
+            None,
         )?;
         Ok(())
     }
@@ -562,7 +820,7 @@ mod tests {
         let src_addr = Ipv6Addr::from_str("fd7a:115c:a1e0:ab12:4843:cd96:6263:82a")?;
         let dest_addr = Ipv6Addr::from_str("2a00:1450:4009:815::200e")?;
         let packet_size = PacketSize(47);
-        let payload_pattern = PayloadPattern(0x00);
+        let payload_pattern: &[u8] = &[0x00];
         let mut mocket = MockSocket::new();
         let err = dispatch_icmp_probe(
             &mut mocket,
@@ -570,7 +828,10 @@ mod tests {
             src_addr,
             dest_addr,
             packet_size,
+            PacketSize(1024),
             payload_pattern,
+            Ipv6ExtensionHeader::None,
+            None,
         )
         .unwrap_err();
         assert!(matches!(err, Error::InvalidPacketSize(_)));
@@ -583,7 +844,7 @@ mod tests {
         let src_addr = Ipv6Addr::from_str("fd7a:115c:a1e0:ab12:4843:cd96:6263:82a")?;
         let dest_addr = Ipv6Addr::from_str("2a00:1450:4009:815::200e")?;
         let packet_size = PacketSize(1025);
-        let payload_pattern = PayloadPattern(0x00);
+        let payload_pattern: &[u8] = &[0x00];
         let mut mocket = MockSocket::new();
         let err = dispatch_icmp_probe(
             &mut mocket,
@@ -591,10 +852,13 @@ mod tests {
             src_addr,
             dest_addr,
             packet_size,
+            PacketSize(1024),
             payload_pattern,
+            Ipv6ExtensionHeader::None,
+            None,
         )
         .unwrap_err();
-        assert!(matches!(err, Error::InvalidPacketSize(_)));
+        assert!(matches!(err, Error::PacketSizeExceedsMax(1025, 1024)));
         Ok(())
     }
 
@@ -605,7 +869,7 @@ mod tests {
         let dest_addr = Ipv6Addr::from_str("2a00:1450:4009:815::200e")?;
         let privilege_mode = PrivilegeMode::Privileged;
         let packet_size = PacketSize(48);
-        let payload_pattern = PayloadPattern(0x00);
+        let payload_pattern: &[u8] = &[0x00];
         let initial_sequence = Sequence(33000);
         let expected_send_to_buf = hex_literal::hex!("00 7b 01 c8 00 08 7a ed");
         let expected_send_to_addr = SocketAddr::new(IpAddr::V6(dest_addr), 0);
@@ -629,11 +893,18 @@ mod tests {
             &mut mocket,
             probe,
             src_addr,
+            0,
             dest_addr,
             privilege_mode,
+            false,
             packet_size,
+            PacketSize(1024),
             payload_pattern,
             initial_sequence,
+            TypeOfService(0),
+            Ipv6ExtensionHeader::None,
+            None,
+            None,
         )?;
         Ok(())
     }
@@ -645,7 +916,59 @@ mod tests {
         let dest_addr = Ipv6Addr::from_str("2a00:1450:4009:815::200e")?;
         let privilege_mode = PrivilegeMode::Privileged;
         let packet_size = PacketSize(56);
-        let payload_pattern = PayloadPattern(0xaa);
+        let payload_pattern: &[u8] = &[0xaa];
+        let initial_sequence = Sequence(33000);
+        let expected_send_to_buf = hex_literal::hex!(
+            "
+            00 7b 01 c8 00 10 d0 32 aa aa aa aa aa aa aa aa
+            "
+        );
+        let expected_send_to_addr = SocketAddr::new(IpAddr::V6(dest_addr), 0);
+
+        let mut mocket = MockSocket::new();
+        mocket
+            .expect_send_to()
+            .with(
+                predicate::eq(expected_send_to_buf),
+                predicate::eq(expected_send_to_addr),
+            )
+            .times(1)
+            .returning(|_, _| Ok(()));
+        mocket
+            .expect_set_unicast_hops_v6()
+            .times(1)
+            .with(predicate::eq(10))
+            .returning(|_| Ok(()));
+
+        dispatch_udp_probe(
+            &mut mocket,
+            probe,
+            src_addr,
+            0,
+            dest_addr,
+            privilege_mode,
+            false,
+            packet_size,
+            PacketSize(1024),
+            payload_pattern,
+            initial_sequence,
+            TypeOfService(0),
+            Ipv6ExtensionHeader::None,
+            None,
+            None,
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_dispatch_udp_probe_classic_privileged_with_extension_header() -> anyhow::Result<()> {
+        let probe = make_udp_probe(123, 456);
+        let src_addr = Ipv6Addr::from_str("fd7a:115c:a1e0:ab12:4843:cd96:6263:82a")?;
+        let dest_addr = Ipv6Addr::from_str("2a00:1450:4009:815::200e")?;
+        let privilege_mode = PrivilegeMode::Privileged;
+        // the 8 byte extension header is included in the packet size
+        let packet_size = PacketSize(64);
+        let payload_pattern: &[u8] = &[0xaa];
         let initial_sequence = Sequence(33000);
         let expected_send_to_buf = hex_literal::hex!(
             "
@@ -673,11 +996,18 @@ mod tests {
             &mut mocket,
             probe,
             src_addr,
+            0,
             dest_addr,
             privilege_mode,
+            false,
             packet_size,
+            PacketSize(1024),
             payload_pattern,
             initial_sequence,
+            TypeOfService(0),
+            Ipv6ExtensionHeader::HopByHop,
+            None,
+            None,
         )?;
         Ok(())
     }
@@ -694,7 +1024,7 @@ mod tests {
         // packet size and payload pattern are ignored for paris mode as a
         // fixed two byte payload is used to hold the sequence
         let packet_size = PacketSize(300);
-        let payload_pattern = PayloadPattern(0xaa);
+        let payload_pattern: &[u8] = &[0xaa];
         let initial_sequence = Sequence(33000);
         let expected_send_to_buf = hex_literal::hex!(
             "
@@ -722,11 +1052,18 @@ mod tests {
             &mut mocket,
             probe,
             src_addr,
+            0,
             dest_addr,
             privilege_mode,
+            false,
             packet_size,
+            PacketSize(1024),
             payload_pattern,
             initial_sequence,
+            TypeOfService(0),
+            Ipv6ExtensionHeader::None,
+            None,
+            None,
         )?;
         Ok(())
     }
@@ -747,7 +1084,7 @@ mod tests {
         let privilege_mode = PrivilegeMode::Privileged;
         // packet size and payload pattern are ignored for ipv6/udp/dublin mode.
         let packet_size = PacketSize(300);
-        let payload_pattern = PayloadPattern(0xaa);
+        let payload_pattern: &[u8] = &[0xaa];
         let initial_sequence = Sequence(33000);
         let expected_send_to_buf = hex_literal::hex!(
             "
@@ -777,11 +1114,18 @@ mod tests {
             &mut mocket,
             probe,
             src_addr,
+            0,
             dest_addr,
             privilege_mode,
+            false,
             packet_size,
+            PacketSize(1024),
             payload_pattern,
             initial_sequence,
+            TypeOfService(0),
+            Ipv6ExtensionHeader::None,
+            None,
+            None,
         )?;
         Ok(())
     }
@@ -794,7 +1138,7 @@ mod tests {
         let dest_addr = Ipv6Addr::from_str("2a00:1450:4009:815::200e")?;
         let privilege_mode = PrivilegeMode::Unprivileged;
         let packet_size = PacketSize(48);
-        let payload_pattern = PayloadPattern(0x00);
+        let payload_pattern: &[u8] = &[0x00];
         let initial_sequence = Sequence(33000);
         let expected_send_to_buf = hex_literal::hex!("");
         let expected_send_to_addr = SocketAddr::new(IpAddr::V6(dest_addr), 456);
@@ -806,6 +1150,16 @@ mod tests {
         let ctx = MockSocket::new_udp_send_socket_ipv6_context();
         ctx.expect().with(predicate::eq(false)).returning(move |_| {
             let mut mocket = MockSocket::new();
+            mocket
+                .expect_set_tclass_v6()
+                .with(predicate::eq(0))
+                .times(1)
+                .returning(|_| Ok(()));
+            mocket
+                .expect_set_ipv6_extension_header()
+                .with(predicate::eq(Ipv6ExtensionHeader::None))
+                .times(1)
+                .returning(|_| Ok(()));
             mocket
                 .expect_bind()
                 .with(predicate::eq(expected_bind_addr))
@@ -834,11 +1188,18 @@ mod tests {
             &mut mocket,
             probe,
             src_addr,
+            0,
             dest_addr,
             privilege_mode,
+            false,
             packet_size,
+            PacketSize(1024),
             payload_pattern,
             initial_sequence,
+            TypeOfService(0),
+            Ipv6ExtensionHeader::None,
+            None,
+            None,
         )?;
         Ok(())
     }
@@ -851,7 +1212,7 @@ mod tests {
         let dest_addr = Ipv6Addr::from_str("2a00:1450:4009:815::200e")?;
         let privilege_mode = PrivilegeMode::Unprivileged;
         let packet_size = PacketSize(56);
-        let payload_pattern = PayloadPattern(0x1f);
+        let payload_pattern: &[u8] = &[0x1f];
         let initial_sequence = Sequence(33000);
         let expected_send_to_buf = hex_literal::hex!("1f 1f 1f 1f 1f 1f 1f 1f");
         let expected_send_to_addr = SocketAddr::new(IpAddr::V6(dest_addr), 456);
@@ -863,6 +1224,16 @@ mod tests {
         let ctx = MockSocket::new_udp_send_socket_ipv6_context();
         ctx.expect().with(predicate::eq(false)).returning(move |_| {
             let mut mocket = MockSocket::new();
+            mocket
+                .expect_set_tclass_v6()
+                .with(predicate::eq(0))
+                .times(1)
+                .returning(|_| Ok(()));
+            mocket
+                .expect_set_ipv6_extension_header()
+                .with(predicate::eq(Ipv6ExtensionHeader::None))
+                .times(1)
+                .returning(|_| Ok(()));
             mocket
                 .expect_bind()
                 .with(predicate::eq(expected_bind_addr))
@@ -891,11 +1262,67 @@ mod tests {
             &mut mocket,
             probe,
             src_addr,
+            0,
+            dest_addr,
+            privilege_mode,
+            false,
+            packet_size,
+            PacketSize(1024),
+            payload_pattern,
+            initial_sequence,
+            TypeOfService(0),
+            Ipv6ExtensionHeader::None,
+            None,
+            None,
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_dispatch_udp_probe_classic_unprivileged_bound() -> anyhow::Result<()> {
+        let probe = make_udp_probe(123, 456);
+        let src_addr = Ipv6Addr::from_str("fd7a:115c:a1e0:ab12:4843:cd96:6263:82a")?;
+        let dest_addr = Ipv6Addr::from_str("2a00:1450:4009:815::200e")?;
+        let privilege_mode = PrivilegeMode::Unprivileged;
+        let packet_size = PacketSize(48);
+        let payload_pattern: &[u8] = &[0x00];
+        let initial_sequence = Sequence(33000);
+        let expected_send_to_buf = hex_literal::hex!("");
+        let expected_send_to_addr = SocketAddr::new(IpAddr::V6(dest_addr), 456);
+        let expected_set_unicast_hops_v6 = 10;
+
+        let mut mocket = MockSocket::new();
+        mocket.expect_bind().times(0);
+        mocket
+            .expect_set_unicast_hops_v6()
+            .with(predicate::eq(expected_set_unicast_hops_v6))
+            .times(1)
+            .returning(|_| Ok(()));
+        mocket
+            .expect_send_to()
+            .with(
+                predicate::eq(expected_send_to_buf),
+                predicate::eq(expected_send_to_addr),
+            )
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        dispatch_udp_probe(
+            &mut mocket,
+            probe,
+            src_addr,
+            0,
             dest_addr,
             privilege_mode,
+            true,
             packet_size,
+            PacketSize(1024),
             payload_pattern,
             initial_sequence,
+            TypeOfService(0),
+            Ipv6ExtensionHeader::None,
+            None,
+            None,
         )?;
         Ok(())
     }
@@ -907,24 +1334,70 @@ mod tests {
         let dest_addr = Ipv6Addr::from_str("2a00:1450:4009:815::200e")?;
         let privilege_mode = PrivilegeMode::Privileged;
         let packet_size = PacketSize(47);
-        let payload_pattern = PayloadPattern(0x00);
+        let payload_pattern: &[u8] = &[0x00];
         let initial_sequence = Sequence(33000);
         let mut mocket = MockSocket::new();
         let err = dispatch_udp_probe(
             &mut mocket,
             probe,
             src_addr,
+            0,
             dest_addr,
             privilege_mode,
+            false,
             packet_size,
+            PacketSize(1024),
             payload_pattern,
             initial_sequence,
+            TypeOfService(0),
+            Ipv6ExtensionHeader::None,
+            None,
+            None,
         )
         .unwrap_err();
         assert!(matches!(err, Error::InvalidPacketSize(_)));
         Ok(())
     }
 
+    #[test]
+    fn test_dispatch_udp_probe_invalid_packet_size_tiny() -> anyhow::Result<()> {
+        let probe = make_udp_probe(123, 456);
+        let src_addr = Ipv6Addr::from_str("fd7a:115c:a1e0:ab12:4843:cd96:6263:82a")?;
+        let dest_addr = Ipv6Addr::from_str("2a00:1450:4009:815::200e")?;
+        let privilege_mode = PrivilegeMode::Unprivileged;
+        let packet_size = PacketSize(28);
+        let payload_pattern: &[u8] = &[0x00];
+        let initial_sequence = Sequence(33000);
+        let mut mocket = MockSocket::new();
+        let err = dispatch_udp_probe(
+            &mut mocket,
+            probe,
+            src_addr,
+            0,
+            dest_addr,
+            privilege_mode,
+            true,
+            packet_size,
+            PacketSize(1024),
+            payload_pattern,
+            initial_sequence,
+            TypeOfService(0),
+            Ipv6ExtensionHeader::None,
+            None,
+            None,
+        )
+        .unwrap_err();
+        assert!(matches!(err, Error::InvalidPacketSize(28)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_min_packet_size() {
+        assert_eq!(48, min_packet_size(Protocol::Icmp));
+        assert_eq!(48, min_packet_size(Protocol::Udp));
+        assert_eq!(0, min_packet_size(Protocol::Tcp));
+    }
+
     #[test]
     fn test_dispatch_udp_probe_invalid_packet_size_high() -> anyhow::Result<()> {
         let probe = make_udp_probe(123, 456);
@@ -932,21 +1405,28 @@ mod tests {
         let dest_addr = Ipv6Addr::from_str("2a00:1450:4009:815::200e")?;
         let privilege_mode = PrivilegeMode::Privileged;
         let packet_size = PacketSize(1025);
-        let payload_pattern = PayloadPattern(0x00);
+        let payload_pattern: &[u8] = &[0x00];
         let initial_sequence = Sequence(33000);
         let mut mocket = MockSocket::new();
         let err = dispatch_udp_probe(
             &mut mocket,
             probe,
             src_addr,
+            0,
             dest_addr,
             privilege_mode,
+            false,
             packet_size,
+            PacketSize(1024),
             payload_pattern,
             initial_sequence,
+            TypeOfService(0),
+            Ipv6ExtensionHeader::None,
+            None,
+            None,
         )
         .unwrap_err();
-        assert!(matches!(err, Error::InvalidPacketSize(_)));
+        assert!(matches!(err, Error::PacketSizeExceedsMax(1025, 1024)));
         Ok(())
     }
 
@@ -958,6 +1438,8 @@ mod tests {
         let dest_addr = Ipv6Addr::from_str("2a00:1450:4009:815::200e")?;
         let expected_bind_addr = SocketAddr::new(IpAddr::V6(src_addr), 123);
         let expected_set_unicast_hops_v6 = 10;
+        let tos = TypeOfService(0xb8);
+        let expected_set_tclass_v6 = 0xb8;
         let expected_connect_addr = SocketAddr::new(IpAddr::V6(dest_addr), 456);
 
         let ctx = MockSocket::new_stream_socket_ipv6_context();
@@ -975,6 +1457,12 @@ mod tests {
                 .with(predicate::eq(expected_set_unicast_hops_v6))
                 .returning(|_| Ok(()));
 
+            mocket
+                .expect_set_tclass_v6()
+                .times(1)
+                .with(predicate::eq(expected_set_tclass_v6))
+                .returning(|_| Ok(()));
+
             mocket
                 .expect_connect()
                 .with(predicate::eq(expected_connect_addr))
@@ -984,24 +1472,140 @@ mod tests {
             Ok(mocket)
         });
 
-        dispatch_tcp_probe::<MockSocket>(&probe, src_addr, dest_addr)?;
+        dispatch_tcp_probe::<MockSocket>(&probe, src_addr, 0, dest_addr, tos, None, None)?;
         Ok(())
     }
 
     #[test]
-    fn test_recv_icmp_probe_echo_reply() -> anyhow::Result<()> {
-        let recv_from_addr = IpAddr::V6(Ipv6Addr::from_str("2604:a880:ffff:6:1::41c").unwrap());
-        let expected_recv_from_buf = hex_literal::hex!(
-            "
-            81 00 52 c0 55 b9 81 26 00 00 00 00 00 00 00 00
-            00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00
-            00 00 00 00 00 00 00 00 00 00 00 00
-           "
-        );
-        let expected_recv_from_addr = SocketAddr::new(recv_from_addr, 0);
-        let mut mocket = MockSocket::new();
-        mocket
-            .expect_recv_from()
+    fn test_dispatch_tcp_probe_mss() -> anyhow::Result<()> {
+        let _m = MTX.lock();
+        let probe = make_udp_probe(123, 456);
+        let src_addr = Ipv6Addr::from_str("fd7a:115c:a1e0:ab12:4843:cd96:6263:82a")?;
+        let dest_addr = Ipv6Addr::from_str("2a00:1450:4009:815::200e")?;
+        let tos = TypeOfService(0);
+        let expected_set_mss = 1200;
+        let expected_connect_addr = SocketAddr::new(IpAddr::V6(dest_addr), 456);
+
+        let ctx = MockSocket::new_stream_socket_ipv6_context();
+        ctx.expect().returning(move || {
+            let mut mocket = MockSocket::new();
+            mocket.expect_bind().times(1).returning(|_| Ok(()));
+            mocket
+                .expect_set_unicast_hops_v6()
+                .times(1)
+                .returning(|_| Ok(()));
+            mocket.expect_set_tclass_v6().times(1).returning(|_| Ok(()));
+
+            mocket
+                .expect_set_mss()
+                .with(predicate::eq(expected_set_mss))
+                .times(1)
+                .returning(|_| Ok(()));
+
+            mocket
+                .expect_connect()
+                .with(predicate::eq(expected_connect_addr))
+                .times(1)
+                .returning(|_| Ok(()));
+
+            Ok(mocket)
+        });
+
+        dispatch_tcp_probe::<MockSocket>(&probe, src_addr, 0, dest_addr, tos, Some(1200), None)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_dispatch_tcp_probe_bind_device() -> anyhow::Result<()> {
+        let _m = MTX.lock();
+        let probe = make_udp_probe(123, 456);
+        let src_addr = Ipv6Addr::from_str("fd7a:115c:a1e0:ab12:4843:cd96:6263:82a")?;
+        let dest_addr = Ipv6Addr::from_str("2a00:1450:4009:815::200e")?;
+        let tos = TypeOfService(0);
+        let expected_bind_device = 7;
+
+        let ctx = MockSocket::new_stream_socket_ipv6_context();
+        ctx.expect().returning(move || {
+            let mut mocket = MockSocket::new();
+            mocket.expect_bind().times(1).returning(|_| Ok(()));
+            mocket
+                .expect_bind_device()
+                .with(predicate::eq(expected_bind_device))
+                .times(1)
+                .returning(|_| Ok(()));
+            mocket
+                .expect_set_unicast_hops_v6()
+                .times(1)
+                .returning(|_| Ok(()));
+            mocket.expect_set_tclass_v6().times(1).returning(|_| Ok(()));
+            mocket.expect_connect().times(1).returning(|_| Ok(()));
+            Ok(mocket)
+        });
+
+        dispatch_tcp_probe::<MockSocket>(&probe, src_addr, 0, dest_addr, tos, None, Some(7))?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_dispatch_tcp_probe_link_local() -> anyhow::Result<()> {
+        let _m = MTX.lock();
+        let probe = make_udp_probe(123, 456);
+        let src_addr = Ipv6Addr::from_str("fe80::1c2a:3bff:fe4d:5e6f")?;
+        let dest_addr = Ipv6Addr::from_str("2a00:1450:4009:815::200e")?;
+        let expected_bind_addr = SocketAddr::V6(SocketAddrV6::new(src_addr, 123, 0, 3));
+        let expected_set_unicast_hops_v6 = 10;
+        let tos = TypeOfService(0xb8);
+        let expected_set_tclass_v6 = 0xb8;
+        let expected_connect_addr = SocketAddr::new(IpAddr::V6(dest_addr), 456);
+
+        let ctx = MockSocket::new_stream_socket_ipv6_context();
+        ctx.expect().returning(move || {
+            let mut mocket = MockSocket::new();
+            mocket
+                .expect_bind()
+                .with(predicate::eq(expected_bind_addr))
+                .times(1)
+                .returning(|_| Ok(()));
+
+            mocket
+                .expect_set_unicast_hops_v6()
+                .times(1)
+                .with(predicate::eq(expected_set_unicast_hops_v6))
+                .returning(|_| Ok(()));
+
+            mocket
+                .expect_set_tclass_v6()
+                .times(1)
+                .with(predicate::eq(expected_set_tclass_v6))
+                .returning(|_| Ok(()));
+
+            mocket
+                .expect_connect()
+                .with(predicate::eq(expected_connect_addr))
+                .times(1)
+                .returning(|_| Ok(()));
+
+            Ok(mocket)
+        });
+
+        dispatch_tcp_probe::<MockSocket>(&probe, src_addr, 3, dest_addr, tos, None, None)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_recv_icmp_probe_echo_reply() -> anyhow::Result<()> {
+        let recv_from_addr = IpAddr::V6(Ipv6Addr::from_str("2604:a880:ffff:6:1::41c").unwrap());
+        let expected_recv_from_buf = hex_literal::hex!(
+            "
+            81 00 52 c0 55 b9 81 26 00 00 00 00 00 00 00 00
+            00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00
+            00 00 00 00 00 00 00 00 00 00 00 00
+           "
+        );
+        let expected_recv_from_addr = SocketAddr::new(recv_from_addr, 0);
+        let mut mocket = MockSocket::new();
+        mocket
+            .expect_recv_from()
             .times(1)
             .returning(mocket_recv_from!(
                 expected_recv_from_buf,
@@ -1009,8 +1613,11 @@ mod tests {
             ));
         let resp = recv_icmp_probe(
             &mut mocket,
+            &mut [0_u8; 1024],
             Protocol::Icmp,
             IcmpExtensionParseMode::Disabled,
+            None,
+            None,
         )?
         .unwrap();
 
@@ -1021,6 +1628,7 @@ mod tests {
                     ResponseSeq::Icmp(ResponseSeqIcmp {
                         identifier,
                         sequence,
+                        payload_header,
                     }),
                 ..
             },
@@ -1029,6 +1637,7 @@ mod tests {
         else {
             panic!("expected EchoReply")
         };
+        assert_eq!(None, payload_header);
         assert_eq!(recv_from_addr, addr);
         assert_eq!(21945, identifier);
         assert_eq!(33062, sequence);
@@ -1060,8 +1669,11 @@ mod tests {
             ));
         let resp = recv_icmp_probe(
             &mut mocket,
+            &mut [0_u8; 1024],
             Protocol::Icmp,
             IcmpExtensionParseMode::Disabled,
+            None,
+            None,
         )?
         .unwrap();
 
@@ -1072,6 +1684,7 @@ mod tests {
                     ResponseSeq::Icmp(ResponseSeqIcmp {
                         identifier,
                         sequence,
+                        payload_header,
                     }),
                 ..
             },
@@ -1081,6 +1694,7 @@ mod tests {
         else {
             panic!("expected TimeExceeded")
         };
+        assert_eq!(None, payload_header);
         assert_eq!(recv_from_addr, addr);
         assert_eq!(21945, identifier);
         assert_eq!(33056, sequence);
@@ -1089,6 +1703,113 @@ mod tests {
         Ok(())
     }
 
+    // Test that the ICMPv6 checksum is validated using the pseudo-header of the received packet
+    // and that a packet corrupted in transit is discarded.
+    #[test_case::test_case("fd7a:115c:a1e0:ab12:4843:cd96:6263:82a", None, true; "valid")]
+    #[test_case::test_case("fd7a:115c:a1e0:ab12:4843:cd96:6263:82b", None, false; "invalid")]
+    #[test_case::test_case("fd7a:115c:a1e0:ab12:4843:cd96:6263:82a", Some(53), false; "corrupted")]
+    fn test_recv_icmp_probe_checksum(
+        dest_addr: &str,
+        corrupt_offset: Option<usize>,
+        valid: bool,
+    ) -> anyhow::Result<()> {
+        let recv_from_addr = IpAddr::V6(Ipv6Addr::from_str("2604:a880:ffff:6:1::41c").unwrap());
+        let mut expected_recv_from_buf = hex_literal::hex!(
+            "
+            03 00 ab b9 00 00 00 00 60 0f 08 00 00 2c 3a 01
+            fd 7a 11 5c a1 e0 ab 12 48 43 cd 96 62 63 08 2a
+            2a 04 4e 42 00 00 00 00 00 00 00 00 00 00 00 81
+            80 00 53 c6 55 b9 81 20 00 00 00 00 00 00 00 00
+            00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00
+            00 00 00 00 00 00 00 00 00 00 00 00
+           "
+        );
+        if let Some(offset) = corrupt_offset {
+            expected_recv_from_buf[offset] ^= 0x01;
+        }
+        let expected_recv_from_addr = SocketAddr::new(recv_from_addr, 0);
+        let mut mocket = MockSocket::new();
+        mocket.expect_recv_from().times(1).returning(
+            move |buf: &mut [u8]| -> IoResult<(usize, Option<SocketAddr>)> {
+                buf[..expected_recv_from_buf.len()].copy_from_slice(&expected_recv_from_buf);
+                Ok((expected_recv_from_buf.len(), Some(expected_recv_from_addr)))
+            },
+        );
+        let resp = recv_icmp_probe(
+            &mut mocket,
+            &mut [0_u8; 1024],
+            Protocol::Icmp,
+            IcmpExtensionParseMode::Disabled,
+            Some(Ipv6Addr::from_str(dest_addr)?),
+            None,
+        );
+        if valid {
+            assert!(matches!(resp, Ok(Some(Response::TimeExceeded(..)))));
+        } else {
+            assert!(matches!(
+                resp,
+                Err(Error::InvalidIcmpChecksum { actual: 0xabb9, .. })
+            ));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_recv_icmp_probe_packet_too_big_icmp() -> anyhow::Result<()> {
+        let recv_from_addr = IpAddr::V6(Ipv6Addr::from_str("2604:a880:ffff:6:1::41c").unwrap());
+        let expected_recv_from_buf = hex_literal::hex!(
+            "
+            02 00 a8 ba 00 00 05 00 60 06 08 00 00 2c 3a 02
+            fd 7a 11 5c a1 e0 ab 12 48 43 cd 96 62 63 08 2a
+            14 04 68 00 40 03 0c 02 00 00 00 00 00 00 00 69
+            80 00 02 62 57 a5 80 ed 00 00 00 00 00 00 00 00
+            00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00
+            00 00 00 00 00 00 00 00 00 00 00 00
+           "
+        );
+        let expected_recv_from_addr = SocketAddr::new(recv_from_addr, 0);
+        let mut mocket = MockSocket::new();
+        mocket
+            .expect_recv_from()
+            .times(1)
+            .returning(mocket_recv_from!(
+                expected_recv_from_buf,
+                expected_recv_from_addr
+            ));
+        let resp = recv_icmp_probe(
+            &mut mocket,
+            &mut [0_u8; 1024],
+            Protocol::Icmp,
+            IcmpExtensionParseMode::Disabled,
+            None,
+            None,
+        )?
+        .unwrap();
+
+        let Response::PacketTooBig(
+            ResponseData {
+                addr,
+                resp_seq:
+                    ResponseSeq::Icmp(ResponseSeqIcmp {
+                        identifier,
+                        sequence,
+                        payload_header,
+                    }),
+                ..
+            },
+            mtu,
+        ) = resp
+        else {
+            panic!("expected PacketTooBig")
+        };
+        assert_eq!(None, payload_header);
+        assert_eq!(recv_from_addr, addr);
+        assert_eq!(22437, identifier);
+        assert_eq!(33005, sequence);
+        assert_eq!(1280, mtu);
+        Ok(())
+    }
+
     #[test]
     fn test_recv_icmp_probe_destination_unreachable_icmp_no_extensions() -> anyhow::Result<()> {
         let recv_from_addr = IpAddr::V6(Ipv6Addr::from_str("2604:a880:ffff:6:1::41c").unwrap());
@@ -1113,8 +1834,11 @@ mod tests {
             ));
         let resp = recv_icmp_probe(
             &mut mocket,
+            &mut [0_u8; 1024],
             Protocol::Icmp,
             IcmpExtensionParseMode::Disabled,
+            None,
+            None,
         )?
         .unwrap();
 
@@ -1125,15 +1849,18 @@ mod tests {
                     ResponseSeq::Icmp(ResponseSeqIcmp {
                         identifier,
                         sequence,
+                        payload_header,
                     }),
                 ..
             },
             icmp_code,
+            _,
             extensions,
         ) = resp
         else {
             panic!("expected DestinationUnreachable")
         };
+        assert_eq!(None, payload_header);
         assert_eq!(recv_from_addr, addr);
         assert_eq!(22437, identifier);
         assert_eq!(33005, sequence);
@@ -1164,8 +1891,15 @@ mod tests {
                 expected_recv_from_buf,
                 expected_recv_from_addr
             ));
-        let resp =
-            recv_icmp_probe(&mut mocket, Protocol::Udp, IcmpExtensionParseMode::Disabled)?.unwrap();
+        let resp = recv_icmp_probe(
+            &mut mocket,
+            &mut [0_u8; 1024],
+            Protocol::Udp,
+            IcmpExtensionParseMode::Disabled,
+            None,
+            None,
+        )?
+        .unwrap();
 
         let Response::TimeExceeded(
             ResponseData {
@@ -1179,6 +1913,7 @@ mod tests {
                         checksum,
                         payload_len,
                         has_magic,
+                        payload_header,
                     }),
                 ..
             },
@@ -1188,6 +1923,7 @@ mod tests {
         else {
             panic!("expected TimeExceeded")
         };
+        assert_eq!(None, payload_header);
         assert_eq!(recv_from_addr, addr);
         assert_eq!(0, identifier);
         assert_eq!(
@@ -1204,6 +1940,288 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_recv_icmp_probe_parameter_problem_udp() -> anyhow::Result<()> {
+        let recv_from_addr = IpAddr::V6(Ipv6Addr::from_str("2604:a880:ffff:6:1::41c").unwrap());
+        let expected_recv_from_buf = hex_literal::hex!(
+            "
+            04 00 7a 7f 00 00 00 28 60 04 04 00 00 2c 11 01
+            fd 7a 11 5c a1 e0 ab 12 48 43 cd 96 62 63 08 2a
+            2a 04 4e 42 00 00 00 00 00 00 00 00 00 00 00 81
+            58 a6 81 05 00 2c d0 f1 00 00 00 00 00 00 00 00
+            00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00
+            00 00 00 00 00 00 00 00 00 00 00 00
+           "
+        );
+        let expected_recv_from_addr = SocketAddr::new(recv_from_addr, 0);
+        let mut mocket = MockSocket::new();
+        mocket
+            .expect_recv_from()
+            .times(3)
+            .returning(mocket_recv_from!(
+                expected_recv_from_buf,
+                expected_recv_from_addr
+            ));
+        let resp = recv_icmp_probe(
+            &mut mocket,
+            &mut [0_u8; 1024],
+            Protocol::Udp,
+            IcmpExtensionParseMode::Disabled,
+            None,
+            None,
+        )?
+        .unwrap();
+
+        let Response::ParameterProblem(
+            ResponseData {
+                addr,
+                resp_seq:
+                    ResponseSeq::Udp(ResponseSeqUdp {
+                        dest_addr,
+                        src_port,
+                        dest_port,
+                        checksum,
+                        ..
+                    }),
+                ..
+            },
+            icmp_code,
+            pointer,
+        ) = resp
+        else {
+            panic!("expected ParameterProblem")
+        };
+        assert_eq!(recv_from_addr, addr);
+        assert_eq!(
+            IpAddr::V6(Ipv6Addr::from_str("2a04:4e42::81").unwrap()),
+            dest_addr
+        );
+        assert_eq!(22694, src_port);
+        assert_eq!(33029, dest_port);
+        assert_eq!(53489, checksum);
+        assert_eq!(IcmpPacketCode(0), icmp_code);
+        assert_eq!(40, pointer);
+
+        // the quoted datagram is not of the protocol of the probes.
+        for protocol in [Protocol::Icmp, Protocol::Tcp] {
+            let resp = recv_icmp_probe(
+                &mut mocket,
+                &mut [0_u8; 1024],
+                protocol,
+                IcmpExtensionParseMode::Disabled,
+                None,
+                None,
+            )?;
+            assert!(resp.is_none());
+        }
+        Ok(())
+    }
+
+    // Test that the sequence and send time embedded in the payload of a UDP probe with fixed
+    // source and destination ports are recovered from the probe quoted in a `TimeExceeded`.
+    #[test]
+    fn test_udp_probe_payload_header_round_trip() -> anyhow::Result<()> {
+        let probe = make_udp_probe(5000, 80);
+        let src_addr = Ipv6Addr::from_str("fd7a:115c:a1e0:ab12:4843:cd96:6263:82a")?;
+        let dest_addr = Ipv6Addr::from_str("2a00:1450:4009:815::200e")?;
+        let sent = std::sync::Arc::new(Mutex::new(Vec::new()));
+        let mut mocket = MockSocket::new();
+        mocket
+            .expect_set_unicast_hops_v6()
+            .times(1)
+            .returning(|_| Ok(()));
+        let sent_udp = sent.clone();
+        mocket.expect_send_to().times(1).returning(move |buf, _| {
+            sent_udp.lock().unwrap().extend_from_slice(buf);
+            Ok(())
+        });
+        dispatch_udp_probe(
+            &mut mocket,
+            probe.clone(),
+            src_addr,
+            0,
+            dest_addr,
+            PrivilegeMode::Privileged,
+            false,
+            PacketSize(84),
+            PacketSize(1024),
+            &[0x00],
+            Sequence(33000),
+            TypeOfService(0),
+            Ipv6ExtensionHeader::None,
+            None,
+            None,
+        )?;
+
+        let udp = sent.lock().unwrap().clone();
+        let udp_len = u16::try_from(udp.len())?;
+        let mut time_exceeded = vec![0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        time_exceeded.extend_from_slice(&[0x60, 0x00, 0x00, 0x00]);
+        time_exceeded.extend_from_slice(&udp_len.to_be_bytes());
+        time_exceeded.extend_from_slice(&[0x11, 0x01]);
+        time_exceeded.extend_from_slice(&src_addr.octets());
+        time_exceeded.extend_from_slice(&dest_addr.octets());
+        time_exceeded.extend_from_slice(&udp);
+        let recv_from_addr = SocketAddr::new(IpAddr::V6(Ipv6Addr::from_str("2001:db8::1")?), 0);
+        mocket
+            .expect_recv_from()
+            .times(1)
+            .returning(mocket_recv_from!(time_exceeded, recv_from_addr));
+        let resp = recv_icmp_probe(
+            &mut mocket,
+            &mut [0_u8; 1024],
+            Protocol::Udp,
+            IcmpExtensionParseMode::Disabled,
+            None,
+            None,
+        )?;
+
+        let Some(Response::TimeExceeded(
+            ResponseData {
+                resp_seq:
+                    ResponseSeq::Udp(ResponseSeqUdp {
+                        src_port,
+                        dest_port,
+                        payload_header,
+                        ..
+                    }),
+                ..
+            },
+            ..,
+        )) = resp
+        else {
+            panic!("expected TimeExceeded")
+        };
+        assert_eq!(5000, src_port);
+        assert_eq!(80, dest_port);
+        assert_eq!(Some(PayloadHeader::new(&probe)), payload_header);
+        let payload_header = payload_header.unwrap();
+        assert_eq!(33000, payload_header.sequence);
+        assert_eq!(probe.sent, payload_header.sent);
+        Ok(())
+    }
+
+    // The original datagram carries a Hop-by-Hop Options extension header (containing a single
+    // `PadN` option) which must be skipped to find the quoted UDP header.
+    #[test]
+    fn test_recv_icmp_probe_time_exceeded_udp_hop_by_hop() -> anyhow::Result<()> {
+        let recv_from_addr = IpAddr::V6(Ipv6Addr::from_str("2604:a880:ffff:6:1::41c").unwrap());
+        let expected_recv_from_buf = hex_literal::hex!(
+            "
+            03 00 7b a7 00 00 00 00 60 04 04 00 00 34 00 01
+            fd 7a 11 5c a1 e0 ab 12 48 43 cd 96 62 63 08 2a
+            2a 04 4e 42 00 00 00 00 00 00 00 00 00 00 00 81
+            11 00 01 04 00 00 00 00 58 a6 81 05 00 2c d0 f1
+            00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00
+            00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00
+            00 00 00 00
+           "
+        );
+        let expected_recv_from_addr = SocketAddr::new(recv_from_addr, 0);
+        let mut mocket = MockSocket::new();
+        mocket
+            .expect_recv_from()
+            .times(1)
+            .returning(mocket_recv_from!(
+                expected_recv_from_buf,
+                expected_recv_from_addr
+            ));
+        let resp = recv_icmp_probe(
+            &mut mocket,
+            &mut [0_u8; 1024],
+            Protocol::Udp,
+            IcmpExtensionParseMode::Disabled,
+            None,
+            None,
+        )?
+        .unwrap();
+
+        let Response::TimeExceeded(
+            ResponseData {
+                resp_seq:
+                    ResponseSeq::Udp(ResponseSeqUdp {
+                        src_port,
+                        dest_port,
+                        checksum,
+                        payload_len,
+                        ..
+                    }),
+                ..
+            },
+            ..,
+        ) = resp
+        else {
+            panic!("expected TimeExceeded")
+        };
+        assert_eq!(22694, src_port);
+        assert_eq!(33029, dest_port);
+        assert_eq!(53489, checksum);
+        assert_eq!(36, payload_len);
+        Ok(())
+    }
+
+    // A `TimeExceeded` from a hop behind a NAT64 translator which quotes the probe with the
+    // destination address translated using the well-known NAT64 prefix.
+    #[test]
+    fn test_recv_icmp_probe_time_exceeded_udp_nat64_translated() -> anyhow::Result<()> {
+        let recv_from_addr = IpAddr::V6(Ipv6Addr::from_str("64:ff9b::c000:201").unwrap());
+        let expected_recv_from_buf = hex_literal::hex!(
+            "
+            03 00 7b a7 00 00 00 00 60 04 04 00 00 2c 11 01
+            fd 7a 11 5c a1 e0 ab 12 48 43 cd 96 62 63 08 2a
+            00 64 ff 9b 00 00 00 00 00 00 00 00 c6 33 64 01
+            58 a6 81 05 00 2c d0 f1 00 00 00 00 00 00 00 00
+            00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00
+            00 00 00 00 00 00 00 00 00 00 00 00
+           "
+        );
+        let expected_recv_from_addr = SocketAddr::new(recv_from_addr, 0);
+        let mut mocket = MockSocket::new();
+        mocket
+            .expect_recv_from()
+            .times(1)
+            .returning(mocket_recv_from!(
+                expected_recv_from_buf,
+                expected_recv_from_addr
+            ));
+        let resp = recv_icmp_probe(
+            &mut mocket,
+            &mut [0_u8; 1024],
+            Protocol::Udp,
+            IcmpExtensionParseMode::Disabled,
+            None,
+            None,
+        )?
+        .unwrap();
+
+        let Response::TimeExceeded(
+            ResponseData {
+                addr,
+                resp_seq:
+                    ResponseSeq::Udp(ResponseSeqUdp {
+                        dest_addr,
+                        src_port,
+                        dest_port,
+                        ..
+                    }),
+                ..
+            },
+            _,
+            _,
+        ) = resp
+        else {
+            panic!("expected TimeExceeded")
+        };
+        assert_eq!(recv_from_addr, addr);
+        assert_eq!(
+            IpAddr::V6(Ipv6Addr::from_str("64:ff9b::198.51.100.1").unwrap()),
+            dest_addr
+        );
+        assert_eq!(22694, src_port);
+        assert_eq!(33029, dest_port);
+        Ok(())
+    }
+
     #[test]
     fn test_recv_icmp_probe_destination_unreachable_udp_no_extensions() -> anyhow::Result<()> {
         let recv_from_addr = IpAddr::V6(Ipv6Addr::from_str("2604:a880:ffff:6:1::41c").unwrap());
@@ -1226,8 +2244,15 @@ mod tests {
                 expected_recv_from_buf,
                 expected_recv_from_addr
             ));
-        let resp =
-            recv_icmp_probe(&mut mocket, Protocol::Udp, IcmpExtensionParseMode::Disabled)?.unwrap();
+        let resp = recv_icmp_probe(
+            &mut mocket,
+            &mut [0_u8; 1024],
+            Protocol::Udp,
+            IcmpExtensionParseMode::Disabled,
+            None,
+            None,
+        )?
+        .unwrap();
 
         let Response::DestinationUnreachable(
             ResponseData {
@@ -1241,15 +2266,18 @@ mod tests {
                         checksum,
                         payload_len,
                         has_magic,
+                        payload_header,
                     }),
                 ..
             },
             icmp_code,
+            _,
             extensions,
         ) = resp
         else {
             panic!("expected DestinationUnreachable")
         };
+        assert_eq!(None, payload_header);
         assert_eq!(recv_from_addr, addr);
         assert_eq!(0, identifier);
         assert_eq!(
@@ -1294,8 +2322,15 @@ mod tests {
                 expected_recv_from_buf,
                 expected_recv_from_addr
             ));
-        let resp =
-            recv_icmp_probe(&mut mocket, Protocol::Udp, IcmpExtensionParseMode::Disabled)?.unwrap();
+        let resp = recv_icmp_probe(
+            &mut mocket,
+            &mut [0_u8; 1024],
+            Protocol::Udp,
+            IcmpExtensionParseMode::Disabled,
+            None,
+            None,
+        )?
+        .unwrap();
 
         let Response::TimeExceeded(
             ResponseData {
@@ -1309,6 +2344,7 @@ mod tests {
                         checksum,
                         payload_len,
                         has_magic,
+                        payload_header,
                     }),
                 ..
             },
@@ -1318,6 +2354,7 @@ mod tests {
         else {
             panic!("expected TimeExceeded")
         };
+        assert_eq!(None, payload_header);
         assert_eq!(recv_from_addr, addr);
         assert_eq!(0, identifier);
         assert_eq!(
@@ -1356,8 +2393,15 @@ mod tests {
                 expected_recv_from_buf,
                 expected_recv_from_addr
             ));
-        let resp =
-            recv_icmp_probe(&mut mocket, Protocol::Tcp, IcmpExtensionParseMode::Disabled)?.unwrap();
+        let resp = recv_icmp_probe(
+            &mut mocket,
+            &mut [0_u8; 1024],
+            Protocol::Tcp,
+            IcmpExtensionParseMode::Disabled,
+            None,
+            None,
+        )?
+        .unwrap();
 
         let Response::TimeExceeded(
             ResponseData {
@@ -1410,8 +2454,15 @@ mod tests {
                 expected_recv_from_buf,
                 expected_recv_from_addr
             ));
-        let resp =
-            recv_icmp_probe(&mut mocket, Protocol::Tcp, IcmpExtensionParseMode::Disabled)?.unwrap();
+        let resp = recv_icmp_probe(
+            &mut mocket,
+            &mut [0_u8; 1024],
+            Protocol::Tcp,
+            IcmpExtensionParseMode::Disabled,
+            None,
+            None,
+        )?
+        .unwrap();
 
         let Response::DestinationUnreachable(
             ResponseData {
@@ -1425,6 +2476,7 @@ mod tests {
                 ..
             },
             icmp_code,
+            _,
             extensions,
         ) = resp
         else {
@@ -1442,6 +2494,70 @@ mod tests {
         Ok(())
     }
 
+    // Test that an ICMPv6 administratively prohibited or port unreachable quoting a TCP SYN is
+    // matched to the probe, as is the case for IPv4.
+    #[test_case::test_case(1, 0xb1e8; "admin prohibited")]
+    #[test_case::test_case(4, 0xb1e5; "port unreachable")]
+    fn test_recv_icmp_probe_destination_unreachable_tcp_code(
+        code: u8,
+        checksum: u16,
+    ) -> anyhow::Result<()> {
+        let recv_from_addr = IpAddr::V6(Ipv6Addr::from_str("2604:a880:ffff:6:1::41c").unwrap());
+        let mut expected_recv_from_buf = hex_literal::hex!(
+            "
+            01 00 b1 e9 00 00 00 00 60 04 07 00 00 2c 06 01
+            fd 7a 11 5c a1 e0 ab 12 48 43 cd 96 62 63 08 2a
+            2a 00 14 50 40 09 08 21 00 00 00 00 00 00 20 0e
+            81 24 00 7b 35 d2 32 c6 00 00 00 00 b0 c2 ff ff
+            71 b2 00 00 02 04 04 c4 01 03 03 06 01 01 08 0a
+            fa 0b 5e 7c 00 00 00 00 04 02 00 00
+           "
+        );
+        expected_recv_from_buf[1] = code;
+        expected_recv_from_buf[2..4].copy_from_slice(&checksum.to_be_bytes());
+        let expected_recv_from_addr = SocketAddr::new(recv_from_addr, 0);
+        let mut mocket = MockSocket::new();
+        mocket
+            .expect_recv_from()
+            .times(1)
+            .returning(mocket_recv_from!(
+                expected_recv_from_buf,
+                expected_recv_from_addr
+            ));
+        let resp = recv_icmp_probe(
+            &mut mocket,
+            &mut [0_u8; 1024],
+            Protocol::Tcp,
+            IcmpExtensionParseMode::Disabled,
+            None,
+            None,
+        )?
+        .unwrap();
+
+        let Response::DestinationUnreachable(
+            ResponseData {
+                addr,
+                resp_seq:
+                    ResponseSeq::Tcp(ResponseSeqTcp {
+                        src_port,
+                        dest_port,
+                        ..
+                    }),
+                ..
+            },
+            icmp_code,
+            ..,
+        ) = resp
+        else {
+            panic!("expected DestinationUnreachable")
+        };
+        assert_eq!(recv_from_addr, addr);
+        assert_eq!(33060, src_port);
+        assert_eq!(123, dest_port);
+        assert_eq!(IcmpPacketCode(code), icmp_code);
+        Ok(())
+    }
+
     #[test]
     fn test_recv_icmp_probe_wrong_icmp_original_datagram_type_ignored() -> anyhow::Result<()> {
         let recv_from_addr = IpAddr::V6(Ipv6Addr::from_str("2604:a880:ffff:6:1::41c").unwrap());
@@ -1464,11 +2580,32 @@ mod tests {
                 expected_recv_from_buf,
                 expected_recv_from_addr
             ));
-        let resp = recv_icmp_probe(&mut mocket, Protocol::Icmp, IcmpExtensionParseMode::Enabled)?;
+        let resp = recv_icmp_probe(
+            &mut mocket,
+            &mut [0_u8; 1024],
+            Protocol::Icmp,
+            IcmpExtensionParseMode::Enabled,
+            None,
+            None,
+        )?;
         assert!(resp.is_some());
-        let resp = recv_icmp_probe(&mut mocket, Protocol::Udp, IcmpExtensionParseMode::Enabled)?;
+        let resp = recv_icmp_probe(
+            &mut mocket,
+            &mut [0_u8; 1024],
+            Protocol::Udp,
+            IcmpExtensionParseMode::Enabled,
+            None,
+            None,
+        )?;
         assert!(resp.is_none());
-        let resp = recv_icmp_probe(&mut mocket, Protocol::Tcp, IcmpExtensionParseMode::Enabled)?;
+        let resp = recv_icmp_probe(
+            &mut mocket,
+            &mut [0_u8; 1024],
+            Protocol::Tcp,
+            IcmpExtensionParseMode::Enabled,
+            None,
+            None,
+        )?;
         assert!(resp.is_none());
         Ok(())
     }
@@ -1495,11 +2632,32 @@ mod tests {
                 expected_recv_from_buf,
                 expected_recv_from_addr
             ));
-        let resp = recv_icmp_probe(&mut mocket, Protocol::Udp, IcmpExtensionParseMode::Enabled)?;
+        let resp = recv_icmp_probe(
+            &mut mocket,
+            &mut [0_u8; 1024],
+            Protocol::Udp,
+            IcmpExtensionParseMode::Enabled,
+            None,
+            None,
+        )?;
         assert!(resp.is_some());
-        let resp = recv_icmp_probe(&mut mocket, Protocol::Icmp, IcmpExtensionParseMode::Enabled)?;
+        let resp = recv_icmp_probe(
+            &mut mocket,
+            &mut [0_u8; 1024],
+            Protocol::Icmp,
+            IcmpExtensionParseMode::Enabled,
+            None,
+            None,
+        )?;
         assert!(resp.is_none());
-        let resp = recv_icmp_probe(&mut mocket, Protocol::Tcp, IcmpExtensionParseMode::Enabled)?;
+        let resp = recv_icmp_probe(
+            &mut mocket,
+            &mut [0_u8; 1024],
+            Protocol::Tcp,
+            IcmpExtensionParseMode::Enabled,
+            None,
+            None,
+        )?;
         assert!(resp.is_none());
         Ok(())
     }
@@ -1526,15 +2684,69 @@ mod tests {
                 expected_recv_from_buf,
                 expected_recv_from_addr
             ));
-        let resp = recv_icmp_probe(&mut mocket, Protocol::Tcp, IcmpExtensionParseMode::Enabled)?;
+        let resp = recv_icmp_probe(
+            &mut mocket,
+            &mut [0_u8; 1024],
+            Protocol::Tcp,
+            IcmpExtensionParseMode::Enabled,
+            None,
+            None,
+        )?;
         assert!(resp.is_some());
-        let resp = recv_icmp_probe(&mut mocket, Protocol::Icmp, IcmpExtensionParseMode::Enabled)?;
+        let resp = recv_icmp_probe(
+            &mut mocket,
+            &mut [0_u8; 1024],
+            Protocol::Icmp,
+            IcmpExtensionParseMode::Enabled,
+            None,
+            None,
+        )?;
         assert!(resp.is_none());
-        let resp = recv_icmp_probe(&mut mocket, Protocol::Udp, IcmpExtensionParseMode::Enabled)?;
+        let resp = recv_icmp_probe(
+            &mut mocket,
+            &mut [0_u8; 1024],
+            Protocol::Udp,
+            IcmpExtensionParseMode::Enabled,
+            None,
+            None,
+        )?;
         assert!(resp.is_none());
         Ok(())
     }
 
+    #[test]
+    fn test_recv_icmp_probe_truncated_ignored() -> anyhow::Result<()> {
+        let recv_from_addr = IpAddr::V6(Ipv6Addr::from_str("2604:a880:ffff:6:1::41c").unwrap());
+        let expected_recv_from_buf = hex_literal::hex!(
+            "
+            03 00 7b a7 00 00 00 00 60 04 04 00 00 2c 11 01
+            fd 7a 11 5c a1 e0 ab 12 48 43 cd 96 62 63 08 2a
+            2a 04 4e 42 00 00 00 00 00 00 00 00 00 00 00 81
+            58 a6 81 05 00 2c d0 f1
+           "
+        );
+        let expected_recv_from_addr = SocketAddr::new(recv_from_addr, 0);
+        for len in 0..=expected_recv_from_buf.len() {
+            let mut mocket = MockSocket::new();
+            mocket.expect_recv_from().times(1).returning(
+                move |buf: &mut [u8]| -> IoResult<(usize, Option<SocketAddr>)> {
+                    buf[..len].copy_from_slice(&expected_recv_from_buf[..len]);
+                    Ok((len, Some(expected_recv_from_addr)))
+                },
+            );
+            let resp = recv_icmp_probe(
+                &mut mocket,
+                &mut [0_u8; 1024],
+                Protocol::Udp,
+                IcmpExtensionParseMode::Enabled,
+                None,
+                None,
+            )?;
+            assert_eq!(len == expected_recv_from_buf.len(), resp.is_some());
+        }
+        Ok(())
+    }
+
     #[test]
     fn test_recv_tcp_socket_tcp_reply() -> anyhow::Result<()> {
         let dest_addr = IpAddr::V6(Ipv6Addr::from_str("2604:a880:ffff:6:1::41c").unwrap());
@@ -1670,7 +2882,14 @@ mod tests {
                 expected_recv_from_buf,
                 expected_recv_from_addr
             ));
-        let resp = recv_icmp_probe(&mut mocket, Protocol::Udp, IcmpExtensionParseMode::Enabled)?;
+        let resp = recv_icmp_probe(
+            &mut mocket,
+            &mut [0_u8; 1024],
+            Protocol::Udp,
+            IcmpExtensionParseMode::Enabled,
+            None,
+            None,
+        )?;
         assert!(resp.is_none());
         Ok(())
     }
@@ -1683,8 +2902,10 @@ mod tests {
             Port(0),
             TimeToLive(10),
             RoundId(0),
-            SystemTime::now(),
+            UNIX_EPOCH + Duration::new(1_700_000_000, 123_456_789),
             Flags::empty(),
+            FlowLabel(0),
+            FlowIndex(0),
         )
     }
 
@@ -1696,8 +2917,10 @@ mod tests {
             Port(dest_port),
             TimeToLive(10),
             RoundId(0),
-            SystemTime::now(),
+            UNIX_EPOCH + Duration::new(1_700_000_000, 123_456_789),
             Flags::empty(),
+            FlowLabel(0),
+            FlowIndex(0),
         )
     }
 }
@@ -0,0 +1,182 @@
+use crate::probe::Probe;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The magic bytes at the start of a probe payload header.
+const MAGIC: [u8; 4] = *b"TRPY";
+
+/// The size of a probe payload header.
+pub const HEADER_SIZE: usize = 16;
+
+/// The header embedded at the start of the payload of ICMP and UDP probes.
+///
+/// The header is written in network byte order as the `MAGIC` bytes, the trace identifier, the
+/// sequence and the time the probe was sent as nanoseconds since the UNIX epoch.
+///
+/// The header is only written if the payload is large enough to hold it, the remainder of the
+/// payload is filled with the payload pattern.
+///
+/// For IPv6/UDP probes with fixed source and destination ports, where neither port can carry the
+/// sequence, the sequence of the header identifies the probe when it is quoted in an `ICMPv6`
+/// error, which includes as much of the original datagram as will fit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PayloadHeader {
+    /// The trace identifier of the probe.
+    pub identifier: u16,
+    /// The sequence of the probe.
+    pub sequence: u16,
+    /// Timestamp when the probe was sent.
+    pub sent: SystemTime,
+}
+
+impl PayloadHeader {
+    /// The header of a `Probe`.
+    pub const fn new(probe: &Probe) -> Self {
+        Self {
+            identifier: probe.identifier.0,
+            sequence: probe.sequence.0,
+            sent: probe.sent,
+        }
+    }
+
+    /// Parse the header from the start of a probe `payload`.
+    ///
+    /// Returns `None` if the payload does not start with the `MAGIC` bytes, such as when the
+    /// payload was too small for the header to have been written or was truncated when quoted.
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        let header = payload.get(..HEADER_SIZE)?;
+        if header[..4] != MAGIC {
+            return None;
+        }
+        let identifier = u16::from_be_bytes([header[4], header[5]]);
+        let sequence = u16::from_be_bytes([header[6], header[7]]);
+        let nanos = u64::from_be_bytes(core::array::from_fn(|i| header[8 + i]));
+        Some(Self {
+            identifier,
+            sequence,
+            sent: UNIX_EPOCH + Duration::from_nanos(nanos),
+        })
+    }
+
+    /// Write the header to the start of `payload`, if it is large enough.
+    pub fn write(&self, payload: &mut [u8]) {
+        if let Some(header) = payload.get_mut(..HEADER_SIZE) {
+            let nanos = self
+                .sent
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since_epoch| {
+                    u64::try_from(since_epoch.as_nanos()).unwrap_or(u64::MAX)
+                });
+            header[..4].copy_from_slice(&MAGIC);
+            header[4..6].copy_from_slice(&self.identifier.to_be_bytes());
+            header[6..8].copy_from_slice(&self.sequence.to_be_bytes());
+            header[8..].copy_from_slice(&nanos.to_be_bytes());
+        }
+    }
+}
+
+/// Make a probe payload of `payload_size` bytes with a `PayloadHeader`.
+pub fn make_payload(probe: &Probe, payload_size: usize, payload_pattern: &[u8]) -> Vec<u8> {
+    let mut payload = make_pattern(payload_size, payload_pattern);
+    PayloadHeader::new(probe).write(&mut payload);
+    payload
+}
+
+/// Make `size` bytes of the repeated `payload_pattern`.
+///
+/// The last repetition is truncated if `size` is not a multiple of the length of the pattern and
+/// the bytes are zero if the pattern is empty.
+pub fn make_pattern(size: usize, payload_pattern: &[u8]) -> Vec<u8> {
+    let mut bytes = payload_pattern
+        .iter()
+        .copied()
+        .cycle()
+        .take(size)
+        .collect::<Vec<_>>();
+    bytes.resize(size, 0);
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Flags, FlowIndex, FlowLabel, Port, RoundId, Sequence, TimeToLive, TraceId};
+
+    fn make_probe() -> Probe {
+        Probe::new(
+            Sequence(33434),
+            TraceId(1234),
+            Port(0),
+            Port(0),
+            TimeToLive(10),
+            RoundId(0),
+            UNIX_EPOCH + Duration::new(1_700_000_000, 123_456_789),
+            Flags::empty(),
+            FlowLabel(0),
+            FlowIndex(0),
+        )
+    }
+
+    #[test]
+    fn test_make_payload() {
+        let expected = hex_literal::hex!(
+            "
+            54 52 50 59 04 d2 82 9a 17 97 9c fe 3d 85 cd 15
+            ff ff
+            "
+        );
+        let payload = make_payload(&make_probe(), 18, &[0xff]);
+        assert_eq!(expected.as_slice(), payload);
+    }
+
+    #[test]
+    fn test_make_payload_too_small() {
+        let payload = make_payload(&make_probe(), 15, &[0xff]);
+        assert_eq!([0xff; 15].as_slice(), payload);
+    }
+
+    #[test]
+    fn test_make_payload_pattern() {
+        let expected = hex_literal::hex!(
+            "
+            54 52 50 59 04 d2 82 9a 17 97 9c fe 3d 85 cd 15
+            02 03 01 02 03
+            "
+        );
+        let payload = make_payload(&make_probe(), 21, &[0x01, 0x02, 0x03]);
+        assert_eq!(expected.as_slice(), payload);
+    }
+
+    #[test]
+    fn test_make_pattern() {
+        assert_eq!(
+            [0xde, 0xad, 0xbe, 0xef, 0xde],
+            *make_pattern(5, &[0xde, 0xad, 0xbe, 0xef])
+        );
+        assert_eq!([0xde, 0xad], *make_pattern(2, &[0xde, 0xad, 0xbe, 0xef]));
+        assert_eq!([0xaa; 3], *make_pattern(3, &[0xaa]));
+        assert_eq!([0x00; 3], *make_pattern(3, &[]));
+        assert!(make_pattern(0, &[0xaa]).is_empty());
+    }
+
+    #[test]
+    fn test_parse() {
+        let probe = make_probe();
+        let payload = make_payload(&probe, 32, &[0x00]);
+        assert_eq!(
+            Some(PayloadHeader::new(&probe)),
+            PayloadHeader::parse(&payload)
+        );
+    }
+
+    #[test]
+    fn test_parse_truncated() {
+        let payload = make_payload(&make_probe(), 32, &[0x00]);
+        assert_eq!(None, PayloadHeader::parse(&payload[..HEADER_SIZE - 1]));
+        assert_eq!(None, PayloadHeader::parse(&[]));
+    }
+
+    #[test]
+    fn test_parse_no_magic() {
+        assert_eq!(None, PayloadHeader::parse(&[0xff; 32]));
+    }
+}
@@ -1,7 +1,7 @@
 pub mod byte_order;
 
 pub use byte_order::Ipv4ByteOrder;
-use std::net::IpAddr;
+use std::net::{IpAddr, Ipv6Addr};
 
 #[cfg(unix)]
 mod unix;
@@ -24,10 +24,64 @@ pub trait Platform {
 
     /// Lookup an `IpAddr` for an interface.
     ///
-    /// If the interface has more than one address then an arbitrary address
-    /// is selected and returned.
+    /// The interface may be given by name or by index.  Only addresses of the
+    /// same family as `addr` are considered and, if the interface has more than
+    /// one such address, the address is chosen by `select_interface_addr`.
     fn lookup_interface_addr(addr: IpAddr, name: &str) -> Result<IpAddr>;
 
+    /// Lookup the index of an interface given by name or by index.
+    fn lookup_interface_index(name: &str) -> Result<u32>;
+
     /// Discover a local `IpAddr` which can route to the target address.
     fn discover_local_addr(target_addr: IpAddr, port: u16) -> Result<IpAddr>;
 }
+
+/// Select an address from the addresses assigned to an interface.
+///
+/// Global addresses are preferred over IPv6 unique local addresses (`fc00::/7`)
+/// which are in turn preferred over link-local addresses (`169.254.0.0/16` and
+/// `fe80::/10`).  Where several addresses share the same scope the lowest
+/// address is chosen, so the selection does not depend on the order in which
+/// the OS reports them.
+pub fn select_interface_addr(addrs: impl IntoIterator<Item = IpAddr>) -> Option<IpAddr> {
+    addrs
+        .into_iter()
+        .min_by_key(|&addr| (scope_rank(addr), addr))
+}
+
+/// Is `addr` an IPv6 link-local unicast address (`fe80::/10`)?
+///
+/// Such an address is only unique within a single link and so must be bound along with the scope
+/// id of the interface it belongs to.
+pub const fn is_unicast_link_local(addr: Ipv6Addr) -> bool {
+    addr.segments()[0] & 0xffc0 == 0xfe80
+}
+
+/// The preference rank of an address scope, lower is preferred.
+const fn scope_rank(addr: IpAddr) -> u8 {
+    match addr {
+        IpAddr::V4(addr) if addr.is_link_local() => 2,
+        IpAddr::V6(addr) if is_unicast_link_local(addr) => 2,
+        IpAddr::V6(addr) if addr.segments()[0] & 0xfe00 == 0xfc00 => 1,
+        IpAddr::V4(_) | IpAddr::V6(_) => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(&[], None; "no addresses")]
+    #[test_case(&["10.0.0.2"], Some("10.0.0.2"); "single ipv4")]
+    #[test_case(&["10.0.0.2", "10.0.0.1"], Some("10.0.0.1"); "lowest ipv4")]
+    #[test_case(&["169.254.1.1", "192.168.1.1"], Some("192.168.1.1"); "ipv4 link local last")]
+    #[test_case(&["fe80::1", "fd00::1", "2001:db8::1"], Some("2001:db8::1"); "ipv6 global first")]
+    #[test_case(&["fe80::1", "fd00::1"], Some("fd00::1"); "ipv6 unique local before link local")]
+    #[test_case(&["2001:db8::2", "2001:db8::1"], Some("2001:db8::1"); "lowest ipv6")]
+    fn test_select_interface_addr(addrs: &[&str], expected: Option<&str>) {
+        let addrs = addrs.iter().map(|addr| addr.parse::<IpAddr>().unwrap());
+        let expected = expected.map(|addr| addr.parse::<IpAddr>().unwrap());
+        assert_eq!(expected, select_interface_addr(addrs));
+    }
+}
@@ -11,6 +11,9 @@ impl Platform for PlatformImpl {
     fn lookup_interface_addr(addr: IpAddr, name: &str) -> Result<IpAddr> {
         address::lookup_interface_addr(addr, name)
     }
+    fn lookup_interface_index(name: &str) -> Result<u32> {
+        address::lookup_interface_index(name)
+    }
     fn discover_local_addr(target_addr: IpAddr, port: u16) -> Result<IpAddr> {
         address::discover_local_addr(target_addr, port)
     }
@@ -18,9 +21,10 @@ impl Platform for PlatformImpl {
 
 mod address {
     use crate::error::{Error, Result};
-    use crate::net::platform::Ipv4ByteOrder;
+    use crate::net::platform::{select_interface_addr, Ipv4ByteOrder};
     use crate::net::socket::Socket;
     use crate::net::SocketImpl;
+    use nix::net::if_::{if_indextoname, if_nametoindex};
     use nix::sys::socket::{AddressFamily, SockaddrLike};
     use std::net::{IpAddr, SocketAddr};
     use tracing::instrument;
@@ -98,41 +102,61 @@ mod address {
         Ok(())
     }
 
-    pub fn lookup_interface_addr(addr: IpAddr, name: &str) -> Result<IpAddr> {
-        match addr {
-            IpAddr::V4(_) => lookup_interface_addr_ipv4(name),
-            IpAddr::V6(_) => lookup_interface_addr_ipv6(name),
+    /// Lookup the index of an interface given either its name or its index.
+    ///
+    /// Interface names are matched first, the value is only treated as an index if no interface
+    /// has the given name.
+    #[instrument(ret)]
+    pub fn lookup_interface_index(name: &str) -> Result<u32> {
+        if let Ok(index) = if_nametoindex(name) {
+            return Ok(index);
         }
+        name.parse::<u32>()
+            .ok()
+            .filter(|&index| if_indextoname(index).is_ok())
+            .ok_or_else(|| Error::UnknownInterface(name.to_string()))
     }
 
     #[instrument(ret)]
-    fn lookup_interface_addr_ipv4(name: &str) -> Result<IpAddr> {
-        nix::ifaddrs::getifaddrs()
+    pub fn lookup_interface_addr(addr: IpAddr, name: &str) -> Result<IpAddr> {
+        let index = lookup_interface_index(name)?;
+        let interface_name = if_indextoname(index)
+            .ok()
+            .and_then(|name| name.into_string().ok())
+            .ok_or_else(|| Error::UnknownInterface(name.to_string()))?;
+        let family_name = if addr.is_ipv4() { "IPv4" } else { "IPv6" };
+        let ifaddrs = nix::ifaddrs::getifaddrs()
             .map_err(|_| Error::UnknownInterface(name.to_string()))?
-            .find_map(|ia| {
-                ia.address.and_then(|addr| match addr.family() {
-                    Some(AddressFamily::Inet) if ia.interface_name == name => addr
+            .filter_map(|ia| {
+                let addr = ia.address.and_then(|addr| match addr.family() {
+                    Some(AddressFamily::Inet) => addr
                         .as_sockaddr_in()
                         .map(|sock_addr| IpAddr::V4(sock_addr.ip())),
+                    Some(AddressFamily::Inet6) => addr
+                        .as_sockaddr_in6()
+                        .map(|sock_addr| IpAddr::V6(sock_addr.ip())),
                     _ => None,
-                })
+                })?;
+                Some((ia.interface_name, addr))
             })
-            .ok_or_else(|| Error::UnknownInterface(name.to_string()))
+            .collect::<Vec<_>>();
+        select_interface_addr(interface_addrs(&ifaddrs, &interface_name, addr))
+            .ok_or_else(|| Error::NoInterfaceAddr(name.to_string(), family_name))
     }
 
-    #[instrument(ret)]
-    fn lookup_interface_addr_ipv6(name: &str) -> Result<IpAddr> {
-        nix::ifaddrs::getifaddrs()
-            .map_err(|_| Error::UnknownInterface(name.to_string()))?
-            .find_map(|ia| {
-                ia.address.and_then(|addr| match addr.family() {
-                    Some(AddressFamily::Inet6) if ia.interface_name == name => addr
-                        .as_sockaddr_in6()
-                        .map(|sock_addr| IpAddr::V6(sock_addr.ip())),
-                    _ => None,
-                })
+    /// The addresses of an interface which are of the same family as `addr`, from a list of
+    /// `(interface name, address)` pairs as returned by `getifaddrs`.
+    fn interface_addrs<'a>(
+        ifaddrs: &'a [(String, IpAddr)],
+        interface_name: &'a str,
+        addr: IpAddr,
+    ) -> impl Iterator<Item = IpAddr> + 'a {
+        ifaddrs
+            .iter()
+            .filter(move |(name, if_addr)| {
+                name == interface_name && if_addr.is_ipv4() == addr.is_ipv4()
             })
-            .ok_or_else(|| Error::UnknownInterface(name.to_string()))
+            .map(|(_, if_addr)| *if_addr)
     }
 
     // Note that no packets are transmitted by this method.
@@ -145,12 +169,83 @@ mod address {
         socket.connect(SocketAddr::new(target_addr, port))?;
         Ok(socket.local_addr()?.ok_or(Error::MissingAddr)?.ip())
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use std::str::FromStr;
+
+        fn ifaddrs() -> Vec<(String, IpAddr)> {
+            [
+                ("lo", "127.0.0.1"),
+                ("lo", "::1"),
+                ("wlan0", "fe80::1c2a:3bff:fe4d:5e6f"),
+                ("wlan0", "192.168.1.20"),
+                ("wlan0", "fd12:3456:789a::20"),
+                ("wlan0", "2001:db8:1::20"),
+                ("wlan0", "2001:db8:1::10"),
+                ("wlan0", "169.254.10.20"),
+                ("eth0", "fe80::aa:bbff:fecc:ddee"),
+                ("eth0", "fd12:3456:789a::30"),
+                ("usb0", "fe80::1"),
+                ("usb0", "fe80::2"),
+            ]
+            .into_iter()
+            .map(|(name, addr)| (name.to_string(), IpAddr::from_str(addr).unwrap()))
+            .collect()
+        }
+
+        fn select(interface_name: &str, family: &str) -> Option<IpAddr> {
+            let ifaddrs = ifaddrs();
+            let family = IpAddr::from_str(family).unwrap();
+            select_interface_addr(interface_addrs(&ifaddrs, interface_name, family))
+        }
+
+        #[test]
+        fn test_select_ipv6_global_before_unique_local_and_link_local() {
+            assert_eq!(
+                Some(IpAddr::from_str("2001:db8:1::10").unwrap()),
+                select("wlan0", "::")
+            );
+        }
+
+        #[test]
+        fn test_select_ipv6_unique_local_before_link_local() {
+            assert_eq!(
+                Some(IpAddr::from_str("fd12:3456:789a::30").unwrap()),
+                select("eth0", "::")
+            );
+        }
+
+        #[test]
+        fn test_select_ipv6_link_local_fallback() {
+            assert_eq!(
+                Some(IpAddr::from_str("fe80::1").unwrap()),
+                select("usb0", "::")
+            );
+        }
+
+        #[test]
+        fn test_select_ipv4() {
+            assert_eq!(
+                Some(IpAddr::from_str("192.168.1.20").unwrap()),
+                select("wlan0", "0.0.0.0")
+            );
+        }
+
+        #[test]
+        fn test_select_no_addr_of_family() {
+            assert_eq!(None, select("usb0", "0.0.0.0"));
+            assert_eq!(None, select("unknown0", "::"));
+        }
+    }
 }
 
 mod socket {
+    use crate::config::Ipv6ExtensionHeader;
     use crate::error::{IoError, IoOperation};
     use crate::error::{IoResult, Result};
-    use crate::net::socket::{Socket, SocketError};
+    use crate::net::socket::{QueuedError, Socket, SocketError};
     use itertools::Itertools;
     use nix::{
         sys::select::FdSet,
@@ -176,6 +271,14 @@ mod socket {
         io::Error::from(Error::EINPROGRESS)
     }
 
+    pub fn host_unreachable_error() -> io::Error {
+        io::Error::from(Error::EHOSTUNREACH)
+    }
+
+    pub fn network_unreachable_error() -> io::Error {
+        io::Error::from(Error::ENETUNREACH)
+    }
+
     /// A network socket.
     pub struct SocketImpl {
         inner: socket2::Socket,
@@ -223,6 +326,12 @@ mod socket {
                 .map_err(|err| IoError::Other(err, IoOperation::SetNonBlocking))
         }
 
+        fn set_flowinfo_send_v6(&self) -> IoResult<()> {
+            self.inner
+                .set_flowinfo_send_v6()
+                .map_err(|err| IoError::Other(err, IoOperation::SetFlowInfoSendV6))
+        }
+
         pub(super) fn local_addr(&self) -> IoResult<Option<SocketAddr>> {
             Ok(self
                 .inner
@@ -252,10 +361,12 @@ mod socket {
             if raw {
                 let socket = Self::new_raw_ipv6(Protocol::ICMPV6)?;
                 socket.set_nonblocking(true)?;
+                socket.set_flowinfo_send_v6()?;
                 Ok(socket)
             } else {
                 let socket = Self::new_dgram_ipv6(Protocol::ICMPV6)?;
                 socket.set_nonblocking(true)?;
+                socket.set_flowinfo_send_v6()?;
                 Ok(socket)
             }
         }
@@ -277,10 +388,12 @@ mod socket {
             if raw {
                 let socket = Self::new_raw_ipv6(Protocol::UDP)?;
                 socket.set_nonblocking(true)?;
+                socket.set_flowinfo_send_v6()?;
                 Ok(socket)
             } else {
                 let socket = Self::new_dgram_ipv6(Protocol::UDP)?;
                 socket.set_nonblocking(true)?;
+                socket.set_flowinfo_send_v6()?;
                 Ok(socket)
             }
         }
@@ -321,6 +434,7 @@ mod socket {
             let mut socket = Self::new(Domain::IPV6, Type::STREAM, Protocol::TCP)?;
             socket.set_nonblocking(true)?;
             socket.set_reuse_port(true)?;
+            socket.set_flowinfo_send_v6()?;
             Ok(socket)
         }
         #[instrument]
@@ -350,6 +464,18 @@ mod socket {
                 .map_err(|err| IoError::Other(err, IoOperation::SetTtl))
         }
         #[instrument(skip(self))]
+        fn set_dont_fragment(&mut self) -> IoResult<()> {
+            self.inner
+                .set_dont_fragment()
+                .map_err(|err| IoError::Other(err, IoOperation::SetDontFragment))
+        }
+        #[instrument(skip(self))]
+        fn set_mss(&mut self, mss: u32) -> IoResult<()> {
+            self.inner
+                .set_mss(mss)
+                .map_err(|err| IoError::Other(err, IoOperation::SetMss))
+        }
+        #[instrument(skip(self))]
         fn set_reuse_port(&mut self, reuse: bool) -> IoResult<()> {
             self.inner
                 .set_reuse_port(reuse)
@@ -368,6 +494,24 @@ mod socket {
                 .map_err(|err| IoError::Other(err, IoOperation::SetUnicastHopsV6))
         }
         #[instrument(skip(self))]
+        fn set_tclass_v6(&mut self, tclass: u32) -> IoResult<()> {
+            self.inner
+                .set_tclass_v6(tclass)
+                .map_err(|err| IoError::Other(err, IoOperation::SetTclassV6))
+        }
+        #[instrument(skip(self))]
+        fn set_ipv6_extension_header(&mut self, header: Ipv6ExtensionHeader) -> IoResult<()> {
+            self.inner
+                .set_ipv6_extension_header(header)
+                .map_err(|err| IoError::Other(err, IoOperation::SetIpv6ExtensionHeader))
+        }
+        #[instrument(skip(self))]
+        fn bind_device(&mut self, index: u32) -> IoResult<()> {
+            self.inner
+                .bind_device_index(index)
+                .map_err(|err| IoError::Other(err, IoOperation::BindDevice))
+        }
+        #[instrument(skip(self))]
         fn connect(&mut self, address: SocketAddr) -> IoResult<()> {
             tracing::debug!(?address);
             self.inner
@@ -482,6 +626,18 @@ mod socket {
         fn icmp_error_info(&mut self) -> IoResult<IpAddr> {
             Ok(IpAddr::V4(Ipv4Addr::UNSPECIFIED))
         }
+        #[instrument(skip(self))]
+        fn set_recv_err(&mut self) -> IoResult<()> {
+            self.inner
+                .set_recv_err()
+                .map_err(|err| IoError::Other(err, IoOperation::SetRecvErr))
+        }
+        #[instrument(skip(self), ret)]
+        fn recv_err(&mut self) -> IoResult<Option<QueuedError>> {
+            self.inner
+                .recv_err()
+                .map_err(|err| IoError::Other(err, IoOperation::RecvErr))
+        }
         #[allow(clippy::unused_self, clippy::unnecessary_wraps)]
         #[instrument(skip(self))]
         fn close(&mut self) -> IoResult<()> {
@@ -514,6 +670,275 @@ mod socket {
                 .map(|(size, addr)| (size, addr.as_socket()))
         }
     }
+
+    /// An extension trait to allow the IPv6 flow label to be set from the `flowinfo` field of the
+    /// destination `SocketAddrV6` when sending.
+    ///
+    /// On Linux the flow label provided by the caller is ignored unless `IPV6_FLOWINFO_SEND` has
+    /// been enabled on the socket.  On other platforms this is a no-op.
+    trait FlowInfoSend {
+        fn set_flowinfo_send_v6(&self) -> io::Result<()>;
+    }
+
+    impl FlowInfoSend for socket2::Socket {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        #[allow(unsafe_code)]
+        fn set_flowinfo_send_v6(&self) -> io::Result<()> {
+            use std::os::fd::AsRawFd;
+            let enable: nix::libc::c_int = 1;
+            // Safety: `enable` is a valid `c_int` which outlives the call and the length passed
+            // matches its size.
+            let res = unsafe {
+                nix::libc::setsockopt(
+                    self.as_raw_fd(),
+                    nix::libc::IPPROTO_IPV6,
+                    nix::libc::IPV6_FLOWINFO_SEND,
+                    std::ptr::addr_of!(enable).cast(),
+                    std::mem::size_of::<nix::libc::c_int>() as nix::libc::socklen_t,
+                )
+            };
+            if res == -1 {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(())
+            }
+        }
+
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        #[allow(clippy::unnecessary_wraps)]
+        fn set_flowinfo_send_v6(&self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// An extension trait to restrict a socket to a single interface.
+    ///
+    /// This uses `SO_BINDTODEVICE` on Linux and `IP_BOUND_IF` / `IPV6_BOUND_IF` on macOS and is
+    /// unsupported on other platforms.
+    trait BindDeviceIndex {
+        fn bind_device_index(&self, index: u32) -> io::Result<()>;
+    }
+
+    impl BindDeviceIndex for socket2::Socket {
+        // Kernels prior to 5.7 require `CAP_NET_RAW` for `SO_BINDTODEVICE`.
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        fn bind_device_index(&self, index: u32) -> io::Result<()> {
+            let name = nix::net::if_::if_indextoname(index)?;
+            self.bind_device(Some(name.as_bytes()))
+        }
+
+        #[cfg(any(target_os = "macos", target_os = "ios"))]
+        fn bind_device_index(&self, index: u32) -> io::Result<()> {
+            let index = std::num::NonZeroU32::new(index);
+            if self.local_addr()?.is_ipv6() {
+                self.bind_device_by_index_v6(index)
+            } else {
+                self.bind_device_by_index_v4(index)
+            }
+        }
+
+        #[cfg(not(any(
+            target_os = "linux",
+            target_os = "android",
+            target_os = "macos",
+            target_os = "ios"
+        )))]
+        fn bind_device_index(&self, _index: u32) -> io::Result<()> {
+            Err(io::Error::from(io::ErrorKind::Unsupported))
+        }
+    }
+
+    /// An extension trait to set the `Don't Fragment` bit on IPv4 datagrams sent on the socket.
+    ///
+    /// This uses `IP_MTU_DISCOVER` with `IP_PMTUDISC_DO` on Linux and `IP_DONTFRAG` on macOS and
+    /// FreeBSD and is unsupported on other platforms.
+    trait DontFragment {
+        fn set_dont_fragment(&self) -> io::Result<()>;
+    }
+
+    impl DontFragment for socket2::Socket {
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "android",
+            target_os = "macos",
+            target_os = "ios",
+            target_os = "freebsd"
+        ))]
+        #[allow(unsafe_code)]
+        fn set_dont_fragment(&self) -> io::Result<()> {
+            use std::os::fd::AsRawFd;
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            let (name, value) = (nix::libc::IP_MTU_DISCOVER, nix::libc::IP_PMTUDISC_DO);
+            #[cfg(not(any(target_os = "linux", target_os = "android")))]
+            let (name, value) = (nix::libc::IP_DONTFRAG, 1);
+            let value: nix::libc::c_int = value;
+            // Safety: `value` is a valid `c_int` which outlives the call and the length passed
+            // matches its size.
+            let res = unsafe {
+                nix::libc::setsockopt(
+                    self.as_raw_fd(),
+                    nix::libc::IPPROTO_IP,
+                    name,
+                    std::ptr::addr_of!(value).cast(),
+                    std::mem::size_of::<nix::libc::c_int>() as nix::libc::socklen_t,
+                )
+            };
+            if res == -1 {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(())
+            }
+        }
+
+        #[cfg(not(any(
+            target_os = "linux",
+            target_os = "android",
+            target_os = "macos",
+            target_os = "ios",
+            target_os = "freebsd"
+        )))]
+        fn set_dont_fragment(&self) -> io::Result<()> {
+            Err(io::Error::from(io::ErrorKind::Unsupported))
+        }
+    }
+
+    /// An extension trait to read the ICMP errors of datagrams sent on the socket from its error
+    /// queue.
+    ///
+    /// This uses `IP_RECVERR` / `IPV6_RECVERR` and `MSG_ERRQUEUE` and is only supported on Linux.
+    trait RecvErr {
+        fn set_recv_err(&self) -> io::Result<()>;
+        fn recv_err(&self) -> io::Result<Option<QueuedError>>;
+    }
+
+    impl RecvErr for socket2::Socket {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        fn set_recv_err(&self) -> io::Result<()> {
+            use nix::sys::socket::{setsockopt, sockopt};
+            if self.domain()? == Domain::IPV6 {
+                setsockopt(self, sockopt::Ipv6RecvErr, &true)?;
+            } else {
+                setsockopt(self, sockopt::Ipv4RecvErr, &true)?;
+            }
+            Ok(())
+        }
+
+        // Only the control message is needed, the returned copy of the datagram which caused
+        // the error is truncated to fit the buffer.
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        fn recv_err(&self) -> io::Result<Option<QueuedError>> {
+            use nix::libc::{
+                sock_extended_err, sockaddr_in6, SO_EE_ORIGIN_ICMP, SO_EE_ORIGIN_ICMP6,
+            };
+            use nix::sys::socket::{recvmsg, ControlMessageOwned, MsgFlags, SockaddrStorage};
+            use std::os::fd::AsRawFd;
+            let mut buf = [0_u8; 64];
+            let mut iov = [io::IoSliceMut::new(&mut buf)];
+            let mut cmsg = nix::cmsg_space!(sock_extended_err, sockaddr_in6);
+            let msg = match recvmsg::<SockaddrStorage>(
+                self.as_raw_fd(),
+                &mut iov,
+                Some(&mut cmsg),
+                MsgFlags::MSG_ERRQUEUE | MsgFlags::MSG_DONTWAIT,
+            ) {
+                Ok(msg) => msg,
+                Err(Error::EAGAIN) => return Ok(None),
+                Err(err) => return Err(io::Error::from(err)),
+            };
+            let dest = msg.address.and_then(|addr| {
+                addr.as_sockaddr_in()
+                    .map(|addr| SocketAddr::V4((*addr).into()))
+                    .or_else(|| {
+                        addr.as_sockaddr_in6()
+                            .map(|addr| SocketAddr::V6((*addr).into()))
+                    })
+            });
+            let queued = msg.cmsgs()?.find_map(|cmsg| match cmsg {
+                ControlMessageOwned::Ipv4RecvErr(err, Some(origin))
+                    if err.ee_origin == SO_EE_ORIGIN_ICMP =>
+                {
+                    let origin = Ipv4Addr::from(u32::from_be(origin.sin_addr.s_addr));
+                    Some((err, IpAddr::V4(origin)))
+                }
+                ControlMessageOwned::Ipv6RecvErr(err, Some(origin))
+                    if err.ee_origin == SO_EE_ORIGIN_ICMP6 =>
+                {
+                    Some((err, IpAddr::V6(Ipv6Addr::from(origin.sin6_addr.s6_addr))))
+                }
+                _ => None,
+            });
+            Ok(queued.map(|(err, origin)| QueuedError {
+                origin,
+                dest,
+                icmp_type: err.ee_type,
+                icmp_code: err.ee_code,
+                info: err.ee_info,
+            }))
+        }
+
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        fn set_recv_err(&self) -> io::Result<()> {
+            Err(io::Error::from(io::ErrorKind::Unsupported))
+        }
+
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        #[allow(clippy::unnecessary_wraps)]
+        fn recv_err(&self) -> io::Result<Option<QueuedError>> {
+            Ok(None)
+        }
+    }
+
+    /// An extension trait to allow an IPv6 extension header to be inserted into every packet sent
+    /// on the socket.
+    ///
+    /// The header is set as a "sticky" option (RFC 3542) and consists of only a single `PadN`
+    /// option which pads it to the minimum length of 8 octets.  This is only supported on Linux.
+    trait Ipv6ExtensionHeaderSend {
+        fn set_ipv6_extension_header(&self, header: Ipv6ExtensionHeader) -> io::Result<()>;
+    }
+
+    impl Ipv6ExtensionHeaderSend for socket2::Socket {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        #[allow(unsafe_code)]
+        fn set_ipv6_extension_header(&self, header: Ipv6ExtensionHeader) -> io::Result<()> {
+            use std::os::fd::AsRawFd;
+            let name = match header {
+                Ipv6ExtensionHeader::None => return Ok(()),
+                Ipv6ExtensionHeader::HopByHop => nix::libc::IPV6_HOPOPTS,
+                Ipv6ExtensionHeader::DestinationOptions => nix::libc::IPV6_DSTOPTS,
+            };
+            // The next header and length fields are filled in by the kernel, the remaining six
+            // octets are a single `PadN` option with four octets of padding.
+            let value: [u8; 8] = [0, 0, 1, 4, 0, 0, 0, 0];
+            // Safety: `value` is a valid buffer which outlives the call and the length passed
+            // matches its size.
+            let res = unsafe {
+                nix::libc::setsockopt(
+                    self.as_raw_fd(),
+                    nix::libc::IPPROTO_IPV6,
+                    name,
+                    value.as_ptr().cast(),
+                    value.len() as nix::libc::socklen_t,
+                )
+            };
+            if res == -1 {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(())
+            }
+        }
+
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        fn set_ipv6_extension_header(&self, header: Ipv6ExtensionHeader) -> io::Result<()> {
+            if header.is_none() {
+                Ok(())
+            } else {
+                Err(io::Error::from(io::ErrorKind::Unsupported))
+            }
+        }
+    }
 }
 
-pub use socket::{in_progress_error, startup, SocketImpl};
+pub use socket::{
+    host_unreachable_error, in_progress_error, network_unreachable_error, startup, SocketImpl,
+};
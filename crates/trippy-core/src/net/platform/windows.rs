@@ -1,9 +1,9 @@
 use super::byte_order::Ipv4ByteOrder;
+use crate::config::Ipv6ExtensionHeader;
 use crate::error::{Error, IoError, IoOperation, IoResult, Result};
-use crate::net::channel::MAX_PACKET_SIZE;
 use crate::net::platform::windows::adapter::Adapters;
-use crate::net::platform::Platform;
-use crate::net::socket::{Socket, SocketError};
+use crate::net::platform::{select_interface_addr, Platform};
+use crate::net::socket::{QueuedError, Socket, SocketError};
 use itertools::Itertools;
 use socket2::{Domain, Protocol, SockAddr, Type};
 use std::ffi::c_void;
@@ -17,14 +17,20 @@ use tracing::instrument;
 use windows_sys::Win32::Foundation::{WAIT_FAILED, WAIT_TIMEOUT};
 use windows_sys::Win32::Networking::WinSock::{
     AF_INET, AF_INET6, FD_CONNECT, FD_WRITE, ICMP_ERROR_INFO, IN6_ADDR, IN6_ADDR_0, IN_ADDR,
-    IN_ADDR_0, IPPROTO_RAW, IPPROTO_TCP, SIO_ROUTING_INTERFACE_QUERY, SOCKADDR_IN, SOCKADDR_IN6,
-    SOCKADDR_IN6_0, SOCKADDR_STORAGE, SOCKET_ERROR, SOL_SOCKET, SO_ERROR, SO_PORT_SCALABILITY,
-    SO_REUSE_UNICASTPORT, TCP_FAIL_CONNECT_ON_ICMP_ERROR, TCP_ICMP_ERROR_INFO, WSABUF, WSADATA,
-    WSAEADDRNOTAVAIL, WSAECONNREFUSED, WSAEHOSTUNREACH, WSAEINPROGRESS, WSA_IO_INCOMPLETE,
-    WSA_IO_PENDING,
+    IN_ADDR_0, IPPROTO_IP, IPPROTO_RAW, IPPROTO_TCP, IP_DONTFRAGMENT, SIO_ROUTING_INTERFACE_QUERY,
+    SOCKADDR_IN, SOCKADDR_IN6, SOCKADDR_IN6_0, SOCKADDR_STORAGE, SOCKET_ERROR, SOL_SOCKET,
+    SO_ERROR, SO_PORT_SCALABILITY, SO_REUSE_UNICASTPORT, TCP_FAIL_CONNECT_ON_ICMP_ERROR,
+    TCP_ICMP_ERROR_INFO, TCP_MAXSEG, WSABUF, WSADATA, WSAEADDRNOTAVAIL, WSAECONNREFUSED,
+    WSAEHOSTUNREACH, WSAEINPROGRESS, WSAENETUNREACH, WSA_IO_INCOMPLETE, WSA_IO_PENDING,
 };
 use windows_sys::Win32::System::IO::OVERLAPPED;
 
+/// The size of the buffer for receiving packets.
+///
+/// The maximum packet size of the channel is not known when a socket is created and so this is
+/// the maximum size of an IPv4 packet.
+const MAX_RECV_BUF_SIZE: usize = 65535;
+
 /// Execute a `Win32::Networking::WinSock` syscall.
 ///
 /// The result of the syscall will be passed to the supplied boolean closure to determine if it
@@ -72,11 +78,15 @@ impl Platform for PlatformImpl {
 
     fn lookup_interface_addr(addr: IpAddr, name: &str) -> Result<IpAddr> {
         match addr {
-            IpAddr::V4(_) => lookup_interface_addr(&Adapters::ipv4()?, name),
-            IpAddr::V6(_) => lookup_interface_addr(&Adapters::ipv6()?, name),
+            IpAddr::V4(_) => lookup_interface_addr(&Adapters::ipv4()?, name, "IPv4"),
+            IpAddr::V6(_) => lookup_interface_addr(&Adapters::ipv6()?, name, "IPv6"),
         }
     }
 
+    fn lookup_interface_index(name: &str) -> Result<u32> {
+        lookup_interface_index(&Adapters::unspecified()?, name)
+    }
+
     fn discover_local_addr(target_addr: IpAddr, _port: u16) -> Result<IpAddr> {
         routing_interface_query(target_addr)
     }
@@ -91,6 +101,25 @@ pub fn in_progress_error() -> StdIoError {
     StdIoError::from_raw_os_error(WSAEINPROGRESS)
 }
 
+pub fn host_unreachable_error() -> StdIoError {
+    StdIoError::from_raw_os_error(WSAEHOSTUNREACH)
+}
+
+pub fn network_unreachable_error() -> StdIoError {
+    StdIoError::from_raw_os_error(WSAENETUNREACH)
+}
+
+/// Non-raw sockets are not supported on Windows.
+fn non_raw_unsupported() -> IoError {
+    IoError::Other(
+        StdIoError::new(
+            ErrorKind::Unsupported,
+            "non-raw socket is not supported on Windows",
+        ),
+        IoOperation::NewSocket,
+    )
+}
+
 /// `WinSock` version 2.2
 const WINSOCK_VERSION: u16 = 0x202;
 
@@ -119,7 +148,7 @@ impl SocketImpl {
             .map_err(|err| IoError::Other(err, IoOperation::NewSocket))?;
         let from = Box::new(Self::new_sockaddr_storage());
         let ol = Box::new(Self::new_overlapped());
-        let buf = vec![0u8; MAX_PACKET_SIZE];
+        let buf = vec![0u8; MAX_RECV_BUF_SIZE];
         Ok(Self {
             inner,
             ol,
@@ -218,7 +247,7 @@ impl SocketImpl {
         }
         let mut fromlen = std::mem::size_of::<SOCKADDR_STORAGE>() as i32;
         let wbuf = WSABUF {
-            len: MAX_PACKET_SIZE as u32,
+            len: MAX_RECV_BUF_SIZE as u32,
             buf: self.buf.as_mut_ptr(),
         };
         syscall!(
@@ -304,7 +333,7 @@ impl Socket for SocketImpl {
             sock.set_header_included(true)?;
             Ok(sock)
         } else {
-            unimplemented!("non-raw socket is not supported on Windows")
+            Err(non_raw_unsupported())
         }
     }
 
@@ -315,7 +344,7 @@ impl Socket for SocketImpl {
             sock.set_non_blocking(true)?;
             Ok(sock)
         } else {
-            unimplemented!("non-raw socket is not supported on Windows")
+            Err(non_raw_unsupported())
         }
     }
 
@@ -327,7 +356,7 @@ impl Socket for SocketImpl {
             sock.set_header_included(true)?;
             Ok(sock)
         } else {
-            unimplemented!("non-raw socket is not supported on Windows")
+            Err(non_raw_unsupported())
         }
     }
 
@@ -338,7 +367,7 @@ impl Socket for SocketImpl {
             sock.set_non_blocking(true)?;
             Ok(sock)
         } else {
-            unimplemented!("non-raw socket is not supported on Windows")
+            Err(non_raw_unsupported())
         }
     }
 
@@ -352,7 +381,7 @@ impl Socket for SocketImpl {
             sock.set_header_included(true)?;
             Ok(sock)
         } else {
-            unimplemented!("non-raw socket is not supported on Windows")
+            Err(non_raw_unsupported())
         }
     }
 
@@ -365,7 +394,7 @@ impl Socket for SocketImpl {
             sock.set_non_blocking(true)?;
             Ok(sock)
         } else {
-            unimplemented!("non-raw socket is not supported on Windows")
+            Err(non_raw_unsupported())
         }
     }
 
@@ -425,6 +454,18 @@ impl Socket for SocketImpl {
             .map_err(|err| IoError::Other(err, IoOperation::SetTtl))
     }
 
+    #[instrument(skip(self))]
+    fn set_dont_fragment(&mut self) -> IoResult<()> {
+        self.setsockopt_bool(IPPROTO_IP, IP_DONTFRAGMENT as _, true)
+            .map_err(|err| IoError::Other(err, IoOperation::SetDontFragment))
+    }
+
+    #[instrument(skip(self))]
+    fn set_mss(&mut self, mss: u32) -> IoResult<()> {
+        self.setsockopt_u32(IPPROTO_TCP, TCP_MAXSEG as _, mss)
+            .map_err(|err| IoError::Other(err, IoOperation::SetMss))
+    }
+
     #[instrument(skip(self))]
     fn set_reuse_port(&mut self, is_reuse_port: bool) -> IoResult<()> {
         self.setsockopt_bool(SOL_SOCKET as _, SO_REUSE_UNICASTPORT as _, is_reuse_port)
@@ -448,6 +489,38 @@ impl Socket for SocketImpl {
             .map_err(|err| IoError::Other(err, IoOperation::SetUnicastHopsV6))
     }
 
+    #[instrument(skip(self))]
+    fn set_tclass_v6(&mut self, tclass: u32) -> IoResult<()> {
+        if tclass == 0 {
+            Ok(())
+        } else {
+            Err(IoError::Other(
+                StdIoError::from(ErrorKind::Unsupported),
+                IoOperation::SetTclassV6,
+            ))
+        }
+    }
+
+    #[instrument(skip(self))]
+    fn set_ipv6_extension_header(&mut self, header: Ipv6ExtensionHeader) -> IoResult<()> {
+        if header.is_none() {
+            Ok(())
+        } else {
+            Err(IoError::Other(
+                StdIoError::from(ErrorKind::Unsupported),
+                IoOperation::SetIpv6ExtensionHeader,
+            ))
+        }
+    }
+
+    // Binding to an interface is not supported on Windows.
+    fn bind_device(&mut self, _index: u32) -> IoResult<()> {
+        Err(IoError::Other(
+            StdIoError::from(ErrorKind::Unsupported),
+            IoOperation::BindDevice,
+        ))
+    }
+
     #[instrument(skip(self))]
     fn connect(&mut self, addr: SocketAddr) -> IoResult<()> {
         self.set_fail_connect_on_icmp_error(true)?;
@@ -582,6 +655,19 @@ impl Socket for SocketImpl {
         }
     }
 
+    // The socket error queue (`IP_RECVERR`) is specific to Linux.
+    fn set_recv_err(&mut self) -> IoResult<()> {
+        Err(IoError::Other(
+            StdIoError::from(ErrorKind::Unsupported),
+            IoOperation::SetRecvErr,
+        ))
+    }
+
+    #[allow(clippy::unnecessary_wraps)]
+    fn recv_err(&mut self) -> IoResult<Option<QueuedError>> {
+        Ok(None)
+    }
+
     // Interestingly, Socket2 sockets don't seem to call closesocket on drop??
     #[instrument(skip(self))]
     fn close(&mut self) -> IoResult<()> {
@@ -724,16 +810,26 @@ fn socketaddr_to_sockaddr(socketaddr: SocketAddr) -> (SOCKADDR_STORAGE, i32) {
 }
 
 #[instrument(skip(adapters), ret)]
-fn lookup_interface_addr(adapters: &Adapters, name: &str) -> Result<IpAddr> {
+fn lookup_interface_addr(adapters: &Adapters, name: &str, family: &'static str) -> Result<IpAddr> {
+    let index = lookup_interface_index(&Adapters::unspecified()?, name)?;
+    let addrs = adapters
+        .iter()
+        .filter(|adapter| adapter.index == index)
+        .flat_map(|adapter| adapter.addrs);
+    select_interface_addr(addrs).ok_or_else(|| Error::NoInterfaceAddr(name.to_string(), family))
+}
+
+/// Lookup an adapter index by friendly name or, if no adapter has that name, by index.
+#[instrument(skip(adapters), ret)]
+fn lookup_interface_index(adapters: &Adapters, name: &str) -> Result<u32> {
     adapters
         .iter()
-        .find_map(|addr| {
-            if addr.name.eq_ignore_ascii_case(name) {
-                Some(addr.addr)
-            } else {
-                None
-            }
+        .find(|adapter| adapter.name.eq_ignore_ascii_case(name))
+        .or_else(|| {
+            let index = name.parse::<u32>().ok()?;
+            adapters.iter().find(|adapter| adapter.index == index)
         })
+        .map(|adapter| adapter.index)
         .ok_or_else(|| Error::UnknownInterface(name.to_string()))
 }
 
@@ -750,7 +846,7 @@ mod adapter {
     use windows_sys::Win32::NetworkManagement::IpHelper::{
         GET_ADAPTERS_ADDRESSES_FLAGS, IP_ADAPTER_ADDRESSES_LH,
     };
-    use windows_sys::Win32::Networking::WinSock::{ADDRESS_FAMILY, AF_INET, AF_INET6};
+    use windows_sys::Win32::Networking::WinSock::{ADDRESS_FAMILY, AF_INET, AF_INET6, AF_UNSPEC};
 
     /// Retrieve adapter address information.
     pub struct Adapters {
@@ -768,6 +864,11 @@ mod adapter {
             Self::retrieve_addresses(AF_INET6)
         }
 
+        /// Retrieve adapter details for all address families.
+        pub fn unspecified() -> Result<Self> {
+            Self::retrieve_addresses(AF_UNSPEC)
+        }
+
         /// Return a iterator of `AdapterAddress` in this `Adapters`.
         pub fn iter(&self) -> AdaptersIter<'_> {
             AdaptersIter::new(self)
@@ -814,13 +915,15 @@ mod adapter {
         }
     }
 
-    /// A named adapter address.
+    /// A named adapter and its addresses.
     #[derive(Debug)]
     pub struct AdapterAddress {
         /// The adapter friendly name.
         pub name: String,
-        /// The adapter `IpAddress`.
-        pub addr: IpAddr,
+        /// The adapter interface index.
+        pub index: u32,
+        /// The adapter unicast `IpAddr`s.
+        pub addrs: Vec<IpAddr>,
     }
 
     /// An iterator for `Adapters` which yields `AdapterAddress`
@@ -854,16 +957,25 @@ mod adapter {
                     let friendly_name = WideCString::from_ptr_str((*self.next).FriendlyName)
                         .to_string()
                         .ok()?;
-                    let addr = {
-                        let first_unicast = (*self.next).FirstUnicastAddress;
-                        let socket_address = (*first_unicast).Address;
-                        let sockaddr = socket_address.lpSockaddr;
-                        sockaddrptr_to_ipaddr(sockaddr.cast()).ok()?
+                    // `IfIndex` is zero if IPv4 is not enabled on the adapter.
+                    let index = match (*self.next).Anonymous1.Anonymous.IfIndex {
+                        0 => (*self.next).Ipv6IfIndex,
+                        index => index,
                     };
+                    let mut addrs = vec![];
+                    let mut unicast = (*self.next).FirstUnicastAddress;
+                    while !unicast.is_null() {
+                        let sockaddr = (*unicast).Address.lpSockaddr;
+                        if let Ok(addr) = sockaddrptr_to_ipaddr(sockaddr.cast()) {
+                            addrs.push(addr);
+                        }
+                        unicast = (*unicast).Next;
+                    }
                     self.next = (*self.next).Next;
                     Some(AdapterAddress {
                         name: friendly_name,
-                        addr,
+                        index,
+                        addrs,
                     })
                 }
             }
@@ -1,3 +1,4 @@
+use crate::config::Ipv6ExtensionHeader;
 use crate::error::IoResult as Result;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::time::Duration;
@@ -30,9 +31,18 @@ where
     fn bind(&mut self, address: SocketAddr) -> Result<()>;
     fn set_tos(&mut self, tos: u32) -> Result<()>;
     fn set_ttl(&mut self, ttl: u32) -> Result<()>;
+    /// Set the `Don't Fragment` bit on IPv4 datagrams sent on this socket.
+    fn set_dont_fragment(&mut self) -> Result<()>;
+    /// Set the maximum segment size of a TCP socket.
+    fn set_mss(&mut self, mss: u32) -> Result<()>;
     fn set_reuse_port(&mut self, reuse: bool) -> Result<()>;
     fn set_header_included(&mut self, included: bool) -> Result<()>;
     fn set_unicast_hops_v6(&mut self, hops: u8) -> Result<()>;
+    fn set_tclass_v6(&mut self, tclass: u32) -> Result<()>;
+    /// Insert the given IPv6 extension header into every packet sent on this socket.
+    fn set_ipv6_extension_header(&mut self, header: Ipv6ExtensionHeader) -> Result<()>;
+    /// Restrict the socket to the interface with the given index, where supported.
+    fn bind_device(&mut self, index: u32) -> Result<()>;
     fn connect(&mut self, address: SocketAddr) -> Result<()>;
     fn send_to(&mut self, buf: &[u8], addr: SocketAddr) -> Result<()>;
     /// Returns true if the socket becomes readable before the timeout, false otherwise.
@@ -45,9 +55,28 @@ where
     fn peer_addr(&mut self) -> Result<Option<SocketAddr>>;
     fn take_error(&mut self) -> Result<Option<SocketError>>;
     fn icmp_error_info(&mut self) -> Result<IpAddr>;
+    /// Queue the ICMP errors of datagrams sent on this socket (`IP_RECVERR`), where supported.
+    fn set_recv_err(&mut self) -> Result<()>;
+    /// Read the next ICMP error from the error queue of this socket, if any.
+    fn recv_err(&mut self) -> Result<Option<QueuedError>>;
     fn close(&mut self) -> Result<()>;
 }
 
+/// An ICMP error read from the error queue of a socket by `Socket::recv_err`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueuedError {
+    /// The address of the host which sent the ICMP error.
+    pub origin: IpAddr,
+    /// The destination of the datagram which caused the ICMP error, if known.
+    pub dest: Option<SocketAddr>,
+    /// The ICMP type.
+    pub icmp_type: u8,
+    /// The ICMP code.
+    pub icmp_code: u8,
+    /// The ICMP info, such as the next-hop MTU of a fragmentation needed error.
+    pub info: u32,
+}
+
 /// A socket error returned by `Socket::take_error`.
 #[derive(Debug)]
 pub enum SocketError {
@@ -26,6 +26,11 @@ impl SourceAddr {
         }
     }
 
+    /// Lookup the index of the source interface, given by name or by index.
+    pub fn interface_index<P: Platform>(interface: &str) -> Result<u32> {
+        P::lookup_interface_index(interface)
+    }
+
     /// Validate that we can bind to the source `IpAddr`.
     pub fn validate<S: Socket>(source_addr: IpAddr) -> Result<IpAddr> {
         let mut socket = match source_addr {
@@ -146,6 +151,20 @@ mod tests {
         assert_eq!(expected_src, src_addr);
     }
 
+    #[test]
+    fn test_interface_index() {
+        let _m = MTX.lock();
+
+        let ctx = MockPlatform::lookup_interface_index_context();
+        ctx.expect()
+            .with(predicate::eq("7"))
+            .times(1)
+            .returning(|_| Ok(7));
+
+        let index = SourceAddr::interface_index::<MockPlatform>("7").unwrap();
+        assert_eq!(7, index);
+    }
+
     #[test]
     fn test_validate_ipv4() {
         let _m = MTX.lock();
@@ -1,4 +1,6 @@
-use crate::types::{Flags, Port, RoundId, Sequence, TimeToLive, TraceId};
+use crate::nat64::Nat64Prefix;
+use crate::net::payload::PayloadHeader;
+use crate::types::{Flags, FlowIndex, FlowLabel, Port, RoundId, Sequence, TimeToLive, TraceId};
 use std::net::IpAddr;
 use std::time::SystemTime;
 
@@ -55,6 +57,10 @@ pub struct Probe {
     pub sent: SystemTime,
     /// Probe flags.
     pub flags: Flags,
+    /// The IPv6 flow label of the probe.
+    pub flow_label: FlowLabel,
+    /// The index of the flow of the probe within the round.
+    pub flow_index: FlowIndex,
 }
 
 impl Probe {
@@ -70,6 +76,8 @@ impl Probe {
         round: RoundId,
         sent: SystemTime,
         flags: Flags,
+        flow_label: FlowLabel,
+        flow_index: FlowIndex,
     ) -> Self {
         Self {
             sequence,
@@ -80,6 +88,8 @@ impl Probe {
             round,
             sent,
             flags,
+            flow_label,
+            flow_index,
         }
     }
 
@@ -90,7 +100,9 @@ impl Probe {
         host: IpAddr,
         received: SystemTime,
         icmp_packet_type: IcmpPacketType,
+        next_hop_mtu: Option<u16>,
         extensions: Option<Extensions>,
+        nat64_prefix: Option<Nat64Prefix>,
     ) -> ProbeComplete {
         ProbeComplete {
             sequence: self.sequence,
@@ -100,10 +112,14 @@ impl Probe {
             ttl: self.ttl,
             round: self.round,
             sent: self.sent,
+            flow_label: self.flow_label,
+            flow_index: self.flow_index,
             host,
             received,
             icmp_packet_type,
+            next_hop_mtu,
             extensions,
+            nat64_prefix,
         }
     }
 }
@@ -117,6 +133,8 @@ impl Probe {
 /// - `EchoReply` - an ICMP packet indicating the probe has reached the target host.
 /// - `DestinationUnreachable` - an ICMP packet indicating the probe could not
 /// reach the target host.
+/// - `PacketTooBig` - an `ICMPv6` packet indicating the probe exceeded the path MTU.
+/// - `ParameterProblem` - an ICMP packet indicating a host rejected a header of the probe.
 /// - `NotApplicable` - a non-ICMP response (i.e. for some `UDP` & `TCP`
 /// probes).
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -135,14 +153,25 @@ pub struct ProbeComplete {
     pub round: RoundId,
     /// Timestamp when the probe was sent.
     pub sent: SystemTime,
+    /// The IPv6 flow label of the probe.
+    pub flow_label: FlowLabel,
+    /// The index of the flow of the probe within the round.
+    pub flow_index: FlowIndex,
     /// The host which responded to the probe.
     pub host: IpAddr,
     /// Timestamp when the response to the probe was received.
     pub received: SystemTime,
     /// The type of ICMP response packet received for the probe.
     pub icmp_packet_type: IcmpPacketType,
+    /// The next-hop MTU of an IPv4 `DestinationUnreachable` fragmentation needed response, or of
+    /// an `ICMPv6` `PacketTooBig` response, if any.
+    pub next_hop_mtu: Option<u16>,
     /// The ICMP response extensions.
     pub extensions: Option<Extensions>,
+    /// The NAT64 prefix of the host which responded to the probe, if any.
+    ///
+    /// This is set if the host is behind a NAT64 translator.
+    pub nat64_prefix: Option<Nat64Prefix>,
 }
 
 /// The type of ICMP packet received.
@@ -154,19 +183,60 @@ pub enum IcmpPacketType {
     EchoReply(IcmpPacketCode),
     /// Unreachable packet.
     Unreachable(IcmpPacketCode),
+    /// `ICMPv6` `PacketTooBig` packet.
+    PacketTooBig,
+    /// `ParameterProblem` packet, with the pointer to the octet of the probe at which the error
+    /// was detected.
+    ParameterProblem(IcmpPacketCode, u32),
     /// Non-ICMP response (i.e. for some `UDP` & `TCP` probes).
     NotApplicable,
+    /// `TCP` connection refused by the target (i.e. the target port is closed).
+    Refused,
 }
 
 /// The code of `TimeExceeded`, `EchoReply` and `Unreachable` ICMP packets.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct IcmpPacketCode(pub u8);
 
+impl IcmpPacketCode {
+    /// A short label describing this code of an `Unreachable` ICMP packet received from `addr`.
+    ///
+    /// The codes of ICMP for IPv4 (RFC 792 & RFC 1812) and IPv6 (RFC 4443) differ and so the
+    /// meaning depends on the address family of `addr`.  Returns `None` for an unknown code.
+    #[must_use]
+    pub const fn unreachable_label(self, addr: IpAddr) -> Option<&'static str> {
+        match (addr, self.0) {
+            (IpAddr::V4(_), 0) => Some("net"),
+            (IpAddr::V4(_), 1) => Some("host"),
+            (IpAddr::V4(_), 2) => Some("proto"),
+            (IpAddr::V4(_), 3) | (IpAddr::V6(_), 4) => Some("port"),
+            (IpAddr::V4(_), 4) => Some("frag"),
+            (IpAddr::V4(_), 9 | 10 | 13) | (IpAddr::V6(_), 1) => Some("admin"),
+            (IpAddr::V6(_), 0) => Some("route"),
+            (IpAddr::V6(_), 2) => Some("scope"),
+            (IpAddr::V6(_), 3) => Some("addr"),
+            (IpAddr::V6(_), 5) => Some("policy"),
+            (IpAddr::V6(_), 6) => Some("reject"),
+            _ => None,
+        }
+    }
+}
+
 /// The response to a probe.
 #[derive(Debug, Clone)]
 pub enum Response {
     TimeExceeded(ResponseData, IcmpPacketCode, Option<Extensions>),
-    DestinationUnreachable(ResponseData, IcmpPacketCode, Option<Extensions>),
+    /// The `Option<u16>` is the next-hop MTU of an IPv4 fragmentation needed response, if any.
+    DestinationUnreachable(
+        ResponseData,
+        IcmpPacketCode,
+        Option<u16>,
+        Option<Extensions>,
+    ),
+    /// The `u16` is the MTU of the next hop, saturated at `u16::MAX`.
+    PacketTooBig(ResponseData, u16),
+    /// The `u32` is the pointer to the octet of the probe at which the error was detected.
+    ParameterProblem(ResponseData, IcmpPacketCode, u32),
     EchoReply(ResponseData, IcmpPacketCode),
     TcpReply(ResponseData),
     TcpRefused(ResponseData),
@@ -249,13 +319,20 @@ pub struct ResponseSeqIcmp {
     pub identifier: u16,
     /// The ICMP sequence number.
     pub sequence: u16,
+    /// The header of the (possibly quoted) probe payload, if any.
+    pub payload_header: Option<PayloadHeader>,
 }
 
 impl ResponseSeqIcmp {
-    pub const fn new(identifier: u16, sequence: u16) -> Self {
+    pub const fn new(
+        identifier: u16,
+        sequence: u16,
+        payload_header: Option<PayloadHeader>,
+    ) -> Self {
         Self {
             identifier,
             sequence,
+            payload_header,
         }
     }
 }
@@ -281,7 +358,8 @@ pub struct ResponseSeqUdp {
     pub dest_port: u16,
     /// The UDP checksum.
     ///
-    /// This will contain the sequence number for IPv4 and IPv6 Paris.
+    /// This will contain the sequence number for IPv4 and IPv6 Paris and for IPv4 Classic with
+    /// fixed source and destination ports.
     pub checksum: u16,
     /// The length of the UDP payload.
     ///
@@ -294,9 +372,16 @@ pub struct ResponseSeqUdp {
     /// This will be true for IPv6 Dublin for probe responses which
     /// originated from the tracer and is used to validate the probe response.
     pub has_magic: bool,
+    /// The header of the quoted probe payload, if any.
+    ///
+    /// This will contain the sequence number for IPv6 Classic with fixed source and destination
+    /// ports.  This will be `None` for IPv4 and IPv6 Paris and IPv6 Dublin, which do not have a
+    /// payload header, and if the quoted probe payload was truncated.
+    pub payload_header: Option<PayloadHeader>,
 }
 
 impl ResponseSeqUdp {
+    #[allow(clippy::too_many_arguments)]
     pub const fn new(
         identifier: u16,
         dest_addr: IpAddr,
@@ -305,6 +390,7 @@ impl ResponseSeqUdp {
         checksum: u16,
         payload_len: u16,
         has_magic: bool,
+        payload_header: Option<PayloadHeader>,
     ) -> Self {
         Self {
             identifier,
@@ -314,6 +400,7 @@ impl ResponseSeqUdp {
             checksum,
             payload_len,
             has_magic,
+            payload_header,
         }
     }
 }
@@ -365,3 +452,27 @@ impl ProbeStatus {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+    use test_case::test_case;
+
+    const IPV4: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    const IPV6: IpAddr = IpAddr::V6(Ipv6Addr::LOCALHOST);
+
+    #[test_case(IPV4, 1, Some("host"))]
+    #[test_case(IPV4, 3, Some("port"))]
+    #[test_case(IPV4, 4, Some("frag"))]
+    #[test_case(IPV4, 13, Some("admin"))]
+    #[test_case(IPV4, 99, None)]
+    #[test_case(IPV6, 0, Some("route"))]
+    #[test_case(IPV6, 1, Some("admin"))]
+    #[test_case(IPV6, 3, Some("addr"))]
+    #[test_case(IPV6, 4, Some("port"))]
+    #[test_case(IPV6, 13, None)]
+    fn test_unreachable_label(addr: IpAddr, code: u8, expected: Option<&str>) {
+        assert_eq!(expected, IcmpPacketCode(code).unreachable_label(addr));
+    }
+}
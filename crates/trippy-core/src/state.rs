@@ -1,9 +1,12 @@
 use crate::config::StateConfig;
 use crate::constants::MAX_TTL;
 use crate::flows::{Flow, FlowId, FlowRegistry};
-use crate::{Extensions, IcmpPacketType, ProbeStatus, Round, RoundId, TimeToLive};
+use crate::{
+    Extensions, FlowIndex, FlowLabel, IcmpPacketType, Nat64Prefix, ProbeStatus, Round, RoundId,
+    TimeToLive,
+};
 use indexmap::IndexMap;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::iter::once;
 use std::net::IpAddr;
 use std::time::Duration;
@@ -52,6 +55,19 @@ impl State {
         self.state[&flow_id].hops()
     }
 
+    /// Information about each distinct responder across all hops for a given flow.
+    ///
+    /// Responders are returned in the order they were first observed.
+    pub fn responders(&self, flow_id: FlowId) -> impl Iterator<Item = &Responder> {
+        self.state[&flow_id].responders()
+    }
+
+    /// Information about a given responder address for a given flow, if it has responded.
+    #[must_use]
+    pub fn responder(&self, addr: IpAddr, flow_id: FlowId) -> Option<&Responder> {
+        self.state[&flow_id].responder(addr)
+    }
+
     /// Is a given `Hop` the target hop for a given flow?
     ///
     /// A `Hop` is considered to be the target if it has the highest `ttl` value observed.
@@ -75,6 +91,14 @@ impl State {
         self.state[&flow_id].target_hop()
     }
 
+    /// The IPv6 flow labels which revealed a given flow.
+    ///
+    /// This will be empty unless a `FlowLabelStrategy` other than `None` was configured.
+    #[must_use]
+    pub fn flow_labels(&self, flow_id: FlowId) -> &[FlowLabel] {
+        self.state[&flow_id].flow_labels()
+    }
+
     /// The current round of tracing for a given flow.
     #[must_use]
     pub fn round(&self, flow_id: FlowId) -> Option<usize> {
@@ -122,23 +146,46 @@ impl State {
     }
 
     /// Update the tracing state from a `TracerRound`.
+    ///
+    /// The probes of every flow of the round are recorded against the default flow and the
+    /// probes of each flow of the round are recorded against the flow they were registered as.
     pub fn update_from_round(&mut self, round: &Round<'_>) {
-        let flow = Flow::from_hops(
-            round
-                .probes
-                .iter()
-                .filter_map(|probe| match probe {
-                    ProbeStatus::Awaited(_) => Some(None),
-                    ProbeStatus::Complete(completed) => Some(Some(completed.host)),
-                    _ => None,
-                })
-                .take(usize::from(round.largest_ttl.0)),
-        );
         self.update_trace_flow(Self::default_flow_id(), round);
-        if self.registry.flows().len() < self.state_config.max_flows {
+        let mut round_flows = BTreeMap::<FlowIndex, Vec<ProbeStatus>>::new();
+        for probe in round.probes {
+            let flow_index = match probe {
+                ProbeStatus::Awaited(awaited) => awaited.flow_index,
+                ProbeStatus::Complete(completed) => completed.flow_index,
+                ProbeStatus::NotSent | ProbeStatus::Skipped => continue,
+            };
+            round_flows
+                .entry(flow_index)
+                .or_default()
+                .push(probe.clone());
+        }
+        let mut flow_probes = BTreeMap::<FlowId, Vec<ProbeStatus>>::new();
+        for (flow_index, probes) in round_flows {
+            if self.registry.flows().len() >= self.state_config.max_flows {
+                break;
+            }
+            let flow = Flow::from_hops(
+                probes
+                    .iter()
+                    .map(|probe| match probe {
+                        ProbeStatus::Complete(completed) => Some(completed.host),
+                        _ => None,
+                    })
+                    .take(usize::from(round.largest_ttl.0)),
+            );
             let flow_id = self.registry.register(flow);
-            self.round_flow_id = flow_id;
-            self.update_trace_flow(flow_id, round);
+            if flow_index == FlowIndex(0) {
+                self.round_flow_id = flow_id;
+            }
+            flow_probes.entry(flow_id).or_default().extend(probes);
+        }
+        for (flow_id, probes) in flow_probes {
+            let flow_round = Round::new(&probes, round.largest_ttl, round.reason);
+            self.update_trace_flow(flow_id, &flow_round);
         }
     }
 
@@ -186,10 +233,14 @@ pub struct Hop {
     last_sequence: u16,
     /// The icmp packet type for the last probe for this hop.
     last_icmp_packet_type: Option<IcmpPacketType>,
+    /// The next-hop MTU of the last probe for this hop, if any.
+    last_next_hop_mtu: Option<u16>,
     /// The history of round trip times across the last N rounds.
     samples: Vec<Duration>,
     /// The ICMP extensions for this hop.
     extensions: Option<Extensions>,
+    /// The NAT64 prefix of the last host to respond for this hop, if any.
+    nat64_prefix: Option<Nat64Prefix>,
     mean: f64,
     m2: f64,
 }
@@ -325,6 +376,14 @@ impl Hop {
         self.last_icmp_packet_type
     }
 
+    /// The next-hop MTU for the last probe for this hop, if any.
+    ///
+    /// This is only set for an IPv4 `Unreachable` fragmentation needed ICMP packet.
+    #[must_use]
+    pub const fn last_next_hop_mtu(&self) -> Option<u16> {
+        self.last_next_hop_mtu
+    }
+
     /// The last N samples.
     #[must_use]
     pub fn samples(&self) -> &[Duration] {
@@ -335,6 +394,18 @@ impl Hop {
     pub const fn extensions(&self) -> Option<&Extensions> {
         self.extensions.as_ref()
     }
+
+    /// Is this hop behind a NAT64 translator?
+    #[must_use]
+    pub const fn is_behind_nat64(&self) -> bool {
+        self.nat64_prefix.is_some()
+    }
+
+    /// The NAT64 translation prefix observed for this hop, if any.
+    #[must_use]
+    pub const fn nat64_prefix(&self) -> Option<Nat64Prefix> {
+        self.nat64_prefix
+    }
 }
 
 impl Default for Hop {
@@ -356,12 +427,121 @@ impl Default for Hop {
             last_dest_port: 0_u16,
             last_sequence: 0_u16,
             last_icmp_packet_type: None,
+            last_next_hop_mtu: None,
             mean: 0f64,
             m2: 0f64,
             samples: Vec::default(),
             extensions: None,
+            nat64_prefix: None,
+        }
+    }
+}
+
+/// Information about a single responder address aggregated across all hops within a `Trace`.
+///
+/// A responder may appear at more than one time-to-live, for example due to address reuse or a
+/// changing path, and so the statistics here are aggregated across every hop at which it was
+/// observed.
+#[derive(Debug, Clone)]
+pub struct Responder {
+    /// The address of this responder.
+    addr: IpAddr,
+    /// The total probes responses received from this responder.
+    total_recv: usize,
+    /// The total round trip time for this responder across all rounds.
+    total_time: Duration,
+    /// The best round trip time for this responder across all rounds.
+    best: Option<Duration>,
+    /// The worst round trip time for this responder across all rounds.
+    worst: Option<Duration>,
+    /// The time-to-live values at which this responder was observed and associated counts.
+    ttls: BTreeMap<u8, usize>,
+    mean: f64,
+    m2: f64,
+}
+
+impl Responder {
+    fn new(addr: IpAddr) -> Self {
+        Self {
+            addr,
+            total_recv: 0,
+            total_time: Duration::default(),
+            best: None,
+            worst: None,
+            ttls: BTreeMap::new(),
+            mean: 0f64,
+            m2: 0f64,
+        }
+    }
+
+    /// The address of this responder.
+    #[must_use]
+    pub const fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    /// The total number of probes responses received from this responder.
+    #[must_use]
+    pub const fn total_recv(&self) -> usize {
+        self.total_recv
+    }
+
+    /// The time-to-live values at which this responder was observed and associated counts.
+    ///
+    /// Returned in ascending time-to-live order.
+    pub fn ttls(&self) -> impl Iterator<Item = (u8, usize)> + '_ {
+        self.ttls.iter().map(|(ttl, count)| (*ttl, *count))
+    }
+
+    /// The number of distinct time-to-live values at which this responder was observed.
+    #[must_use]
+    pub fn ttl_count(&self) -> usize {
+        self.ttls.len()
+    }
+
+    /// The duration of the best probe observed.
+    #[must_use]
+    pub fn best_ms(&self) -> Option<f64> {
+        self.best.map(|best| best.as_secs_f64() * 1000_f64)
+    }
+
+    /// The duration of the worst probe observed.
+    #[must_use]
+    pub fn worst_ms(&self) -> Option<f64> {
+        self.worst.map(|worst| worst.as_secs_f64() * 1000_f64)
+    }
+
+    /// The average duration of all probes.
+    #[must_use]
+    pub fn avg_ms(&self) -> f64 {
+        if self.total_recv > 0 {
+            (self.total_time.as_secs_f64() * 1000_f64) / self.total_recv as f64
+        } else {
+            0_f64
+        }
+    }
+
+    /// The standard deviation of all probes.
+    #[must_use]
+    pub fn stddev_ms(&self) -> f64 {
+        if self.total_recv > 1 {
+            (self.m2 / (self.total_recv - 1) as f64).sqrt()
+        } else {
+            0_f64
         }
     }
+
+    fn update(&mut self, ttl: u8, dur: Duration) {
+        let dur_ms = dur.as_secs_f64() * 1000_f64;
+        self.total_recv += 1;
+        self.total_time += dur;
+        self.best = self.best.map_or(Some(dur), |d| Some(d.min(dur)));
+        self.worst = self.worst.map_or(Some(dur), |d| Some(d.max(dur)));
+        let delta = dur_ms - self.mean;
+        self.mean += delta / self.total_recv as f64;
+        self.m2 += delta * (dur_ms - self.mean);
+        *self.ttls.entry(ttl).or_default() += 1;
+    }
 }
 
 /// Data for a single trace flow.
@@ -381,6 +561,10 @@ struct FlowState {
     round_count: usize,
     /// The hops in this trace.
     hops: Vec<Hop>,
+    /// The distinct IPv6 flow labels observed for this flow.
+    flow_labels: Vec<FlowLabel>,
+    /// The distinct responders across all hops in this trace.
+    responders: IndexMap<IpAddr, Responder>,
 }
 
 impl FlowState {
@@ -393,6 +577,8 @@ impl FlowState {
             round: None,
             round_count: 0,
             hops: (0..MAX_TTL).map(|_| Hop::default()).collect(),
+            flow_labels: Vec::new(),
+            responders: IndexMap::new(),
         }
     }
 
@@ -430,6 +616,18 @@ impl FlowState {
         self.round_count
     }
 
+    fn flow_labels(&self) -> &[FlowLabel] {
+        &self.flow_labels
+    }
+
+    fn responders(&self) -> impl Iterator<Item = &Responder> {
+        self.responders.values()
+    }
+
+    fn responder(&self, addr: IpAddr) -> Option<&Responder> {
+        self.responders.get(&addr)
+    }
+
     fn update_from_round(&mut self, round: &Round<'_>) {
         self.round_count += 1;
         self.highest_ttl = std::cmp::max(self.highest_ttl, round.largest_ttl.0);
@@ -437,6 +635,23 @@ impl FlowState {
         for probe in round.probes {
             self.update_from_probe(probe);
         }
+        self.update_flow_labels(round);
+    }
+
+    /// Record the flow labels used for the round, if any.
+    ///
+    /// All probes of a flow within a round share the same flow label.
+    fn update_flow_labels(&mut self, round: &Round<'_>) {
+        let flow_labels = round.probes.iter().filter_map(|probe| match probe {
+            ProbeStatus::Awaited(awaited) => Some(awaited.flow_label),
+            ProbeStatus::Complete(completed) => Some(completed.flow_label),
+            ProbeStatus::NotSent | ProbeStatus::Skipped => None,
+        });
+        for flow_label in flow_labels.filter(|label| label.0 != 0) {
+            if !self.flow_labels.contains(&flow_label) {
+                self.flow_labels.push(flow_label);
+            }
+        }
     }
 
     fn update_from_probe(&mut self, probe: &ProbeStatus) {
@@ -478,10 +693,16 @@ impl FlowState {
                 let host = complete.host;
                 *hop.addrs.entry(host).or_default() += 1;
                 hop.extensions.clone_from(&complete.extensions);
+                hop.nat64_prefix = complete.nat64_prefix;
                 hop.last_src_port = complete.src_port.0;
                 hop.last_dest_port = complete.dest_port.0;
                 hop.last_sequence = complete.sequence.0;
                 hop.last_icmp_packet_type = Some(complete.icmp_packet_type);
+                hop.last_next_hop_mtu = complete.next_hop_mtu;
+                self.responders
+                    .entry(host)
+                    .or_insert_with(|| Responder::new(host))
+                    .update(complete.ttl.0, dur);
             }
             ProbeStatus::Awaited(awaited) => {
                 self.update_lowest_ttl(awaited.ttl);
@@ -521,8 +742,8 @@ impl FlowState {
 mod tests {
     use super::*;
     use crate::{
-        CompletionReason, Flags, IcmpPacketType, Port, Probe, ProbeComplete, ProbeStatus, Sequence,
-        TimeToLive, TraceId,
+        CompletionReason, Flags, FlowIndex, FlowLabel, IcmpPacketType, Nat64Prefix, Port, Probe,
+        ProbeComplete, ProbeStatus, Sequence, TimeToLive, TraceId,
     };
     use anyhow::anyhow;
     use serde::Deserialize;
@@ -585,6 +806,8 @@ mod tests {
                         round,
                         sent,
                         flags,
+                        FlowLabel(0),
+                        FlowIndex(0),
                     ))),
                     "c" => {
                         let host = IpAddr::from_str(values[3])?;
@@ -601,12 +824,16 @@ mod tests {
                                 round,
                                 sent,
                                 flags,
+                                FlowLabel(0),
+                                FlowIndex(0),
                             )
                             .complete(
                                 host,
                                 received,
                                 icmp_packet_type,
                                 None,
+                                None,
+                                None,
                             ),
                         ))
                     }
@@ -729,6 +956,191 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_hop_behind_nat64() {
+        let mut trace = State::new(StateConfig::default());
+        let hosts = [
+            (IpAddr::from_str("2001:db8::1").unwrap(), None),
+            (
+                IpAddr::from_str("64:ff9b::192.0.2.1").unwrap(),
+                Some(Nat64Prefix::WELL_KNOWN),
+            ),
+        ];
+        let probes = hosts
+            .into_iter()
+            .enumerate()
+            .map(|(i, (host, nat64_prefix))| {
+                let sent = SystemTime::now();
+                ProbeStatus::Complete(
+                    Probe::new(
+                        Sequence(33000 + i as u16),
+                        TraceId(0),
+                        Port(0),
+                        Port(0),
+                        TimeToLive(i as u8 + 1),
+                        RoundId(0),
+                        sent,
+                        Flags::empty(),
+                        FlowLabel(0),
+                        FlowIndex(0),
+                    )
+                    .complete(
+                        host,
+                        sent,
+                        IcmpPacketType::NotApplicable,
+                        None,
+                        None,
+                        nat64_prefix,
+                    ),
+                )
+            })
+            .collect::<Vec<_>>();
+        let round = Round::new(&probes, TimeToLive(2), CompletionReason::TargetFound);
+        trace.update_from_round(&round);
+        let hops = trace.hops(State::default_flow_id());
+        assert!(!hops[0].is_behind_nat64());
+        assert_eq!(None, hops[0].nat64_prefix());
+        assert!(hops[1].is_behind_nat64());
+        assert_eq!(Some(Nat64Prefix::WELL_KNOWN), hops[1].nat64_prefix());
+    }
+
+    #[test]
+    fn test_flows_per_round() {
+        let mut trace = State::new(StateConfig::default());
+        let hosts = [
+            (1, 0, IpAddr::from([10, 0, 0, 1])),
+            (1, 1, IpAddr::from([10, 0, 0, 1])),
+            (2, 0, IpAddr::from([10, 0, 0, 2])),
+            (2, 1, IpAddr::from([10, 0, 0, 3])),
+        ];
+        let probes = hosts
+            .into_iter()
+            .enumerate()
+            .map(|(i, (ttl, flow_index, host))| {
+                let sent = SystemTime::now();
+                ProbeStatus::Complete(
+                    Probe::new(
+                        Sequence(33000 + i as u16),
+                        TraceId(0),
+                        Port(0),
+                        Port(0),
+                        TimeToLive(ttl),
+                        RoundId(0),
+                        sent,
+                        Flags::empty(),
+                        FlowLabel(0),
+                        FlowIndex(flow_index),
+                    )
+                    .complete(
+                        host,
+                        sent,
+                        IcmpPacketType::NotApplicable,
+                        None,
+                        None,
+                        None,
+                    ),
+                )
+            })
+            .collect::<Vec<_>>();
+        let round = Round::new(&probes, TimeToLive(2), CompletionReason::TargetFound);
+        trace.update_from_round(&round);
+        assert_eq!(2, trace.flows().len());
+        assert_eq!(FlowId(1), trace.round_flow_id());
+        let hops = trace.hops(State::default_flow_id());
+        assert_eq!(2, hops[0].total_recv());
+        assert_eq!(2, hops[1].addr_count());
+        for (flow_id, addr) in [(FlowId(1), [10, 0, 0, 2]), (FlowId(2), [10, 0, 0, 3])] {
+            let hops = trace.hops(flow_id);
+            assert_eq!(1, hops[0].total_recv());
+            assert_eq!(
+                vec![&IpAddr::from(addr)],
+                hops[1].addrs().collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn test_responders() {
+        let rounds = [
+            vec![
+                "1 C 10 10.1.0.1 33000 0 0",
+                "2 C 20 10.1.0.2 33001 0 0",
+                "3 C 30 10.1.0.3 33002 0 0",
+            ],
+            vec![
+                "1 C 12 10.1.0.1 33003 0 0",
+                "2 C 40 10.1.0.3 33004 0 0",
+                "3 A 0 0.0.0.0 33005 0 0",
+            ],
+            vec![
+                "1 C 14 10.1.0.1 33006 0 0",
+                "2 C 22 10.1.0.2 33007 0 0",
+                "3 C 50 10.1.0.3 33008 0 0",
+            ],
+        ];
+        let mut trace = State::new(StateConfig::default());
+        for (i, round) in rounds.into_iter().enumerate() {
+            let probes = round
+                .into_iter()
+                .map(|p| ProbeData::try_from(p.to_string()).unwrap())
+                .map(|p| ProbeRound(p, RoundId(i)))
+                .map(Into::into)
+                .collect::<Vec<_>>();
+            let tracer_round = Round::new(&probes, TimeToLive(3), CompletionReason::TargetFound);
+            trace.update_from_round(&tracer_round);
+        }
+        let responders = trace
+            .responders(State::default_flow_id())
+            .collect::<Vec<_>>();
+        assert_eq!(3, responders.len());
+        let addrs = responders.iter().map(|r| r.addr()).collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                IpAddr::from_str("10.1.0.1").unwrap(),
+                IpAddr::from_str("10.1.0.2").unwrap(),
+                IpAddr::from_str("10.1.0.3").unwrap(),
+            ],
+            addrs
+        );
+        let first = responders[0];
+        assert_eq!(3, first.total_recv());
+        assert_eq!(vec![(1, 3)], first.ttls().collect::<Vec<_>>());
+        assert_eq_optional(first.best_ms(), Some(10_f64));
+        assert_eq_optional(first.worst_ms(), Some(14_f64));
+        assert_eq!(12_f64, first.avg_ms());
+        assert_eq!(2_f64, first.stddev_ms());
+        let reused = trace
+            .responder(
+                IpAddr::from_str("10.1.0.3").unwrap(),
+                State::default_flow_id(),
+            )
+            .unwrap();
+        assert_eq!(3, reused.total_recv());
+        assert_eq!(2, reused.ttl_count());
+        assert_eq!(vec![(2, 1), (3, 2)], reused.ttls().collect::<Vec<_>>());
+        assert_eq_optional(reused.best_ms(), Some(30_f64));
+        assert_eq_optional(reused.worst_ms(), Some(50_f64));
+        assert_eq!(40_f64, reused.avg_ms());
+        assert_eq!(10_f64, reused.stddev_ms());
+        let total_hop_recv = trace
+            .hops(State::default_flow_id())
+            .iter()
+            .map(Hop::total_recv)
+            .sum::<usize>();
+        let total_responder_recv = trace
+            .responders(State::default_flow_id())
+            .map(Responder::total_recv)
+            .sum::<usize>();
+        assert_eq!(total_hop_recv, total_responder_recv);
+        assert!(trace
+            .responder(
+                IpAddr::from_str("10.1.0.4").unwrap(),
+                State::default_flow_id()
+            )
+            .is_none());
+    }
+
     #[allow(clippy::float_cmp)]
     fn assert_eq_optional(actual: Option<f64>, expected: Option<f64>) {
         match (actual, expected) {
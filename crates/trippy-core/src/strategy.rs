@@ -1,6 +1,7 @@
 use self::state::TracerState;
 use crate::config::StrategyConfig;
 use crate::error::{Error, Result};
+use crate::nat64;
 use crate::net::Network;
 use crate::probe::{
     ProbeStatus, Response, ResponseData, ResponseSeq, ResponseSeqIcmp, ResponseSeqTcp,
@@ -59,7 +60,7 @@ impl<F: Fn(&Round<'_>)> Strategy<F> {
     pub fn new(config: &StrategyConfig, publish: F) -> Self {
         tracing::debug!(?config);
         Self {
-            config: *config,
+            config: config.clone(),
             publish,
         }
     }
@@ -67,7 +68,7 @@ impl<F: Fn(&Round<'_>)> Strategy<F> {
     /// Run a continuous trace and publish results.
     #[instrument(skip(self, network))]
     pub fn run<N: Network>(self, mut network: N) -> Result<()> {
-        let mut state = TracerState::new(self.config);
+        let mut state = TracerState::new(self.config.clone());
         while !state.finished(self.config.max_rounds) {
             self.send_request(&mut network, &mut state)?;
             self.recv_response(&mut network, &mut state)?;
@@ -151,31 +152,81 @@ impl<F: Fn(&Round<'_>)> Strategy<F> {
         match next {
             Some(Response::TimeExceeded(data, icmp_code, extensions)) => {
                 let (trace_id, sequence, received, host) = self.extract(&data);
-                let is_target = host == self.config.target_addr;
-                if self.check_trace_id(trace_id) && st.in_round(sequence) && self.validate(&data) {
+                let is_target = self.is_target_addr(host);
+                if self.check_trace_id(trace_id)
+                    && st.in_round(sequence)
+                    && self.validate(&data)
+                    && self.check_identification(st, sequence, &data)
+                    && Self::check_payload_header(st, sequence, &data)
+                {
                     st.complete_probe_time_exceeded(
                         sequence, host, received, is_target, icmp_code, extensions,
                     );
                 }
             }
-            Some(Response::DestinationUnreachable(data, icmp_code, extensions)) => {
+            Some(Response::DestinationUnreachable(data, icmp_code, next_hop_mtu, extensions)) => {
                 let (trace_id, sequence, received, host) = self.extract(&data);
-                if self.check_trace_id(trace_id) && st.in_round(sequence) && self.validate(&data) {
-                    st.complete_probe_unreachable(sequence, host, received, icmp_code, extensions);
+                if self.check_trace_id(trace_id)
+                    && st.in_round(sequence)
+                    && self.validate(&data)
+                    && self.check_identification(st, sequence, &data)
+                    && Self::check_payload_header(st, sequence, &data)
+                {
+                    st.complete_probe_unreachable(
+                        sequence,
+                        host,
+                        received,
+                        icmp_code,
+                        next_hop_mtu,
+                        extensions,
+                    );
+                }
+            }
+            Some(Response::PacketTooBig(data, mtu)) => {
+                let (trace_id, sequence, received, host) = self.extract(&data);
+                if self.check_trace_id(trace_id)
+                    && st.in_round(sequence)
+                    && self.validate(&data)
+                    && Self::check_payload_header(st, sequence, &data)
+                {
+                    st.complete_probe_packet_too_big(sequence, host, received, mtu);
+                }
+            }
+            Some(Response::ParameterProblem(data, icmp_code, pointer)) => {
+                let (trace_id, sequence, received, host) = self.extract(&data);
+                if self.check_trace_id(trace_id)
+                    && st.in_round(sequence)
+                    && self.validate(&data)
+                    && self.check_identification(st, sequence, &data)
+                    && Self::check_payload_header(st, sequence, &data)
+                {
+                    st.complete_probe_parameter_problem(
+                        sequence, host, received, icmp_code, pointer,
+                    );
                 }
             }
             Some(Response::EchoReply(data, icmp_code)) => {
                 let (trace_id, sequence, received, host) = self.extract(&data);
-                if self.check_trace_id(trace_id) && st.in_round(sequence) && self.validate(&data) {
+                if self.check_trace_id(trace_id)
+                    && st.in_round(sequence)
+                    && self.validate(&data)
+                    && Self::check_payload_header(st, sequence, &data)
+                {
                     st.complete_probe_echo_reply(sequence, host, received, icmp_code);
                 }
             }
-            Some(Response::TcpReply(data) | Response::TcpRefused(data)) => {
+            Some(Response::TcpReply(data)) => {
                 let (trace_id, sequence, received, host) = self.extract(&data);
                 if self.check_trace_id(trace_id) && st.in_round(sequence) && self.validate(&data) {
                     st.complete_probe_other(sequence, host, received);
                 }
             }
+            Some(Response::TcpRefused(data)) => {
+                let (trace_id, sequence, received, host) = self.extract(&data);
+                if self.check_trace_id(trace_id) && st.in_round(sequence) && self.validate(&data) {
+                    st.complete_probe_refused(sequence, host, received);
+                }
+            }
             None => {}
         }
         Ok(())
@@ -216,7 +267,7 @@ impl<F: Fn(&Round<'_>)> Strategy<F> {
             state
                 .max_received_ttl()
                 .map_or(TimeToLive(0), |max_received_ttl| {
-                    let max_sent_ttl = state.ttl() - TimeToLive(1);
+                    let (max_sent_ttl, _) = state.last_issued();
                     max_sent_ttl.min(max_received_ttl + TimeToLive(1))
                 })
         };
@@ -249,6 +300,9 @@ impl<F: Fn(&Round<'_>)> Strategy<F> {
     /// For UDP and TCP probe responses, check that the src/dest ports and
     /// dest address match the expected values.
     ///
+    /// For IPv6/UDP probe responses which carry the sequence in the probe
+    /// payload, check that the payload was quoted.
+    ///
     /// For ICMP probe responses no additional checks are required.
     fn validate(&self, resp: &ResponseData) -> bool {
         const fn validate_ports(
@@ -272,15 +326,23 @@ impl<F: Fn(&Round<'_>)> Strategy<F> {
                 src_port,
                 dest_port,
                 has_magic,
+                payload_header,
                 ..
             }) => {
                 let check_ports = validate_ports(self.config.port_direction, src_port, dest_port);
-                let check_dest_addr = self.config.target_addr == dest_addr;
-                let check_magic = match (self.config.multipath_strategy, self.config.target_addr) {
-                    (MultipathStrategy::Dublin, IpAddr::V6(_)) => has_magic,
+                let check_dest_addr = self.is_target_addr(dest_addr);
+                let check_payload = match (
+                    self.config.multipath_strategy,
+                    self.config.port_direction,
+                    self.config.target_addr,
+                ) {
+                    (MultipathStrategy::Dublin, _, IpAddr::V6(_)) => has_magic,
+                    (MultipathStrategy::Classic, PortDirection::FixedBoth(_, _), IpAddr::V6(_)) => {
+                        payload_header.is_some()
+                    }
                     _ => true,
                 };
-                check_dest_addr && check_ports && check_magic
+                check_dest_addr && check_ports && check_payload
             }
             ResponseSeq::Tcp(ResponseSeqTcp {
                 dest_addr,
@@ -288,12 +350,113 @@ impl<F: Fn(&Round<'_>)> Strategy<F> {
                 dest_port,
             }) => {
                 let check_ports = validate_ports(self.config.port_direction, src_port, dest_port);
-                let check_dest_addr = self.config.target_addr == dest_addr;
+                let check_dest_addr = self.is_target_addr(dest_addr);
                 check_dest_addr && check_ports
             }
         }
     }
 
+    /// Check the quoted IPv4 identification of an IPv4/Dublin probe response.
+    ///
+    /// For IPv4/Dublin the sequence is carried in the IPv4 identification field and so a NAT
+    /// device on the path which rewrites this field will cause the probe response to be matched
+    /// to the wrong probe.  Such responses are detected, and ignored, by checking that the ports
+    /// of the quoted UDP packet are those of the probe sent with the quoted identification.
+    ///
+    /// This must only be called for a `sequence` which is in the current round.
+    fn check_identification(
+        &self,
+        st: &TracerState,
+        sequence: Sequence,
+        resp: &ResponseData,
+    ) -> bool {
+        let (MultipathStrategy::Dublin, IpAddr::V4(_)) =
+            (self.config.multipath_strategy, self.config.target_addr)
+        else {
+            return true;
+        };
+        let ResponseSeq::Udp(ResponseSeqUdp {
+            src_port,
+            dest_port,
+            ..
+        }) = resp.resp_seq
+        else {
+            return true;
+        };
+        let (probe_src_port, probe_dest_port) = match st.probe_at(sequence) {
+            ProbeStatus::Awaited(probe) => (probe.src_port, probe.dest_port),
+            ProbeStatus::Complete(complete) => (complete.src_port, complete.dest_port),
+            ProbeStatus::NotSent | ProbeStatus::Skipped => return false,
+        };
+        let matched = probe_src_port.0 == src_port && probe_dest_port.0 == dest_port;
+        if !matched {
+            tracing::debug!(
+                ?sequence,
+                src_port,
+                dest_port,
+                "quoted identification rewritten by NAT"
+            );
+        }
+        matched
+    }
+
+    /// Check the payload header of an ICMP or UDP probe response, if any.
+    ///
+    /// The payload header holds the trace identifier, sequence and sent time of the probe, which
+    /// must match those of the probe sent with `sequence`, such that a response to a stale probe
+    /// with the same sequence from an earlier round is ignored.
+    ///
+    /// Responses without a payload header, such as when the payload was too small for the header
+    /// or was truncated by the router which quoted it, are not checked.
+    ///
+    /// This must only be called for a `sequence` which is in the current round.
+    fn check_payload_header(st: &TracerState, sequence: Sequence, resp: &ResponseData) -> bool {
+        let payload_header = match &resp.resp_seq {
+            ResponseSeq::Icmp(ResponseSeqIcmp { payload_header, .. })
+            | ResponseSeq::Udp(ResponseSeqUdp { payload_header, .. }) => payload_header,
+            ResponseSeq::Tcp(_) => &None,
+        };
+        let Some(payload_header) = payload_header else {
+            return true;
+        };
+        let (identifier, probe_sequence, sent) = match st.probe_at(sequence) {
+            ProbeStatus::Awaited(probe) => (probe.identifier, probe.sequence, probe.sent),
+            ProbeStatus::Complete(complete) => {
+                (complete.identifier, complete.sequence, complete.sent)
+            }
+            ProbeStatus::NotSent | ProbeStatus::Skipped => return false,
+        };
+        let matched = identifier.0 == payload_header.identifier
+            && probe_sequence.0 == payload_header.sequence
+            && sent == payload_header.sent;
+        if !matched {
+            tracing::debug!(?sequence, ?payload_header, "payload header mismatch");
+        }
+        matched
+    }
+
+    /// Is `addr` the target address?
+    ///
+    /// If the path to the target crosses a NAT64 translator then `addr` may be a translated form
+    /// of the target address, using a different NAT64 prefix or an IPv4-mapped address, in which
+    /// case the original IPv4 forms of both addresses are compared.
+    fn is_target_addr(&self, addr: IpAddr) -> bool {
+        if addr == self.config.target_addr {
+            return true;
+        }
+        let prefixes = &self.config.nat64_prefixes;
+        match (
+            nat64::original_ipv4(addr, prefixes),
+            nat64::original_ipv4(self.config.target_addr, prefixes),
+        ) {
+            (Some((addr_ipv4, prefix)), Some((target_ipv4, _))) if addr_ipv4 == target_ipv4 => {
+                tracing::debug!(%addr, ?prefix, "matched translated target address");
+                true
+            }
+            _ => false,
+        }
+    }
+
     /// Extract the `TraceId`, `Sequence`, `SystemTime` and `IpAddr` from the `ProbeResponseData` in
     /// a protocol specific way.
     #[instrument(skip(self))]
@@ -302,6 +465,7 @@ impl<F: Fn(&Round<'_>)> Strategy<F> {
             ResponseSeq::Icmp(ResponseSeqIcmp {
                 identifier,
                 sequence,
+                ..
             }) => (
                 TraceId(identifier),
                 Sequence(sequence),
@@ -314,6 +478,7 @@ impl<F: Fn(&Round<'_>)> Strategy<F> {
                 dest_port,
                 checksum,
                 payload_len,
+                payload_header,
                 ..
             }) => {
                 let sequence = match (
@@ -322,11 +487,15 @@ impl<F: Fn(&Round<'_>)> Strategy<F> {
                     self.config.target_addr,
                 ) {
                     (MultipathStrategy::Classic, PortDirection::FixedDest(_), _) => src_port,
+                    (MultipathStrategy::Classic, PortDirection::FixedBoth(_, _), IpAddr::V6(_)) => {
+                        payload_header.map_or(0, |header| header.sequence)
+                    }
+                    (MultipathStrategy::Classic, PortDirection::FixedBoth(_, _), IpAddr::V4(_))
+                    | (MultipathStrategy::Paris, _, _) => checksum,
                     (MultipathStrategy::Classic, _, _) => dest_port,
-                    (MultipathStrategy::Paris, _, _) => checksum,
                     (MultipathStrategy::Dublin, _, IpAddr::V4(_)) => identifier,
                     (MultipathStrategy::Dublin, _, IpAddr::V6(_)) => {
-                        self.config.initial_sequence.0 + payload_len
+                        self.config.initial_sequence.0.wrapping_add(payload_len)
                     }
                 };
                 (TraceId(0), Sequence(sequence), resp.recv, resp.addr)
@@ -349,11 +518,15 @@ impl<F: Fn(&Round<'_>)> Strategy<F> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::net::payload::PayloadHeader;
     use crate::net::MockNetwork;
-    use crate::probe::IcmpPacketCode;
-    use crate::{MaxRounds, Port};
-    use std::net::Ipv4Addr;
+    use crate::probe::{IcmpPacketCode, IcmpPacketType, ProbeStatus};
+    use crate::{Flags, MaxInflight, MaxRounds, Nat64Prefix, Port};
+    use std::net::{Ipv4Addr, Ipv6Addr};
     use std::num::NonZeroUsize;
+    use std::str::FromStr;
+    use std::sync::atomic::{AtomicU16, Ordering};
+    use std::sync::Arc;
 
     // The network can return both `DestinationUnreachable` and `TcpRefused`
     // for the same sequence number.  This can occur for the target hop for
@@ -384,6 +557,7 @@ mod tests {
                     ),
                     IcmpPacketCode(1),
                     None,
+                    None,
                 )))
             });
         network
@@ -413,6 +587,652 @@ mod tests {
         tracer.recv_response(&mut network, &mut state)?;
         Ok(())
     }
+
+    // A TCP connection refused by the target, because the target port is closed, reaches the
+    // target.
+    //
+    // This test simulates sending 1 TCP probe (seq=33000) and receiving a `TcpRefused` response.
+    #[test]
+    fn test_tcp_refused() -> anyhow::Result<()> {
+        let sequence = 33000;
+        let target_addr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+
+        let mut network = MockNetwork::new();
+        network.expect_send_probe().times(1).returning(|_| Ok(()));
+        network.expect_recv_probe().times(1).returning(move || {
+            Ok(Some(Response::TcpRefused(ResponseData::new(
+                SystemTime::now(),
+                target_addr,
+                ResponseSeq::Tcp(ResponseSeqTcp::new(target_addr, sequence, 80)),
+            ))))
+        });
+
+        let config = StrategyConfig {
+            target_addr,
+            max_rounds: Some(MaxRounds(NonZeroUsize::MIN)),
+            initial_sequence: Sequence(sequence),
+            port_direction: PortDirection::FixedDest(Port(80)),
+            protocol: Protocol::Tcp,
+            ..Default::default()
+        };
+        let tracer = Strategy::new(&config, |_| {});
+        let mut state = TracerState::new(config);
+        tracer.send_request(&mut network, &mut state)?;
+        tracer.recv_response(&mut network, &mut state)?;
+        let ProbeStatus::Complete(complete) = state.probe_at(Sequence(sequence)) else {
+            panic!("expected complete probe")
+        };
+        assert_eq!(IcmpPacketType::Refused, complete.icmp_packet_type);
+        assert_eq!(target_addr, complete.host);
+        assert!(state.target_found());
+        Ok(())
+    }
+
+    // A `ParameterProblem` response reports the pointer to the octet of the probe which a host
+    // rejected and, as the probe cannot be forwarded, ends the trace.
+    //
+    // This test simulates sending 1 UDP probe (seq=33000) and receiving a `ParameterProblem`
+    // which quotes a datagram with a different destination port, which is ignored, followed by a
+    // `ParameterProblem` which quotes the probe.
+    #[test]
+    fn test_udp_parameter_problem() -> anyhow::Result<()> {
+        let sequence = 33000;
+        let target_addr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let router_addr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 254));
+
+        let mut network = MockNetwork::new();
+        let mut seq = mockall::Sequence::new();
+        network.expect_send_probe().times(1).returning(|_| Ok(()));
+        for dest_port in [53, 33434] {
+            network
+                .expect_recv_probe()
+                .times(1)
+                .in_sequence(&mut seq)
+                .returning(move || {
+                    Ok(Some(Response::ParameterProblem(
+                        ResponseData::new(
+                            SystemTime::now(),
+                            router_addr,
+                            ResponseSeq::Udp(ResponseSeqUdp::new(
+                                0,
+                                target_addr,
+                                sequence,
+                                dest_port,
+                                0,
+                                0,
+                                false,
+                                None,
+                            )),
+                        ),
+                        IcmpPacketCode(0),
+                        20,
+                    )))
+                });
+        }
+
+        let config = StrategyConfig {
+            target_addr,
+            max_rounds: Some(MaxRounds(NonZeroUsize::MIN)),
+            initial_sequence: Sequence(sequence),
+            port_direction: PortDirection::FixedDest(Port(33434)),
+            protocol: Protocol::Udp,
+            ..Default::default()
+        };
+        let tracer = Strategy::new(&config, |_| {});
+        let mut state = TracerState::new(config);
+        tracer.send_request(&mut network, &mut state)?;
+        tracer.recv_response(&mut network, &mut state)?;
+        assert!(matches!(
+            state.probe_at(Sequence(sequence)),
+            ProbeStatus::Awaited(_)
+        ));
+        tracer.recv_response(&mut network, &mut state)?;
+        let ProbeStatus::Complete(complete) = state.probe_at(Sequence(sequence)) else {
+            panic!("expected complete probe")
+        };
+        assert_eq!(
+            IcmpPacketType::ParameterProblem(IcmpPacketCode(0), 20),
+            complete.icmp_packet_type
+        );
+        assert_eq!(router_addr, complete.host);
+        assert!(state.target_found());
+        Ok(())
+    }
+
+    // An `ICMPv6` `PacketTooBig` response reports the MTU of the next hop and, as the probe cannot
+    // be forwarded, ends the trace.
+    //
+    // This test simulates sending 1 ICMP probe (seq=33000) and receiving a `PacketTooBig`
+    // response from a router on the path.
+    #[test]
+    fn test_icmp_packet_too_big() -> anyhow::Result<()> {
+        let sequence = 33000;
+        let target_addr = IpAddr::V6(Ipv6Addr::from_str("2001:db8::1")?);
+        let router_addr = IpAddr::V6(Ipv6Addr::from_str("2001:db8::fe")?);
+
+        let mut network = MockNetwork::new();
+        network.expect_send_probe().times(1).returning(|_| Ok(()));
+        network.expect_recv_probe().times(1).returning(move || {
+            Ok(Some(Response::PacketTooBig(
+                ResponseData::new(
+                    SystemTime::now(),
+                    router_addr,
+                    ResponseSeq::Icmp(ResponseSeqIcmp::new(0, sequence, None)),
+                ),
+                1280,
+            )))
+        });
+
+        let config = StrategyConfig {
+            target_addr,
+            max_rounds: Some(MaxRounds(NonZeroUsize::MIN)),
+            initial_sequence: Sequence(sequence),
+            protocol: Protocol::Icmp,
+            ..Default::default()
+        };
+        let tracer = Strategy::new(&config, |_| {});
+        let mut state = TracerState::new(config);
+        tracer.send_request(&mut network, &mut state)?;
+        tracer.recv_response(&mut network, &mut state)?;
+        let ProbeStatus::Complete(complete) = state.probe_at(Sequence(sequence)) else {
+            panic!("expected complete probe")
+        };
+        assert_eq!(IcmpPacketType::PacketTooBig, complete.icmp_packet_type);
+        assert_eq!(Some(1280), complete.next_hop_mtu);
+        assert_eq!(router_addr, complete.host);
+        assert!(state.target_found());
+        Ok(())
+    }
+
+    // An `EchoReply` with a payload header which does not match the probe sent with the same
+    // sequence, such as a late response to a probe from an earlier round, is ignored.
+    //
+    // This test simulates sending 1 ICMP probe (seq=33000) and receiving an `EchoReply` with a
+    // stale payload header followed by an `EchoReply` with the payload header of the probe.
+    #[test]
+    fn test_icmp_payload_header_mismatch() -> anyhow::Result<()> {
+        let sequence = 33000;
+        let target_addr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let config = StrategyConfig {
+            target_addr,
+            max_rounds: Some(MaxRounds(NonZeroUsize::MIN)),
+            initial_sequence: Sequence(sequence),
+            protocol: Protocol::Icmp,
+            ..Default::default()
+        };
+        let tracer = Strategy::new(&config, |_| {});
+        let mut state = TracerState::new(config);
+        let mut network = MockNetwork::new();
+        network.expect_send_probe().times(1).returning(|_| Ok(()));
+        tracer.send_request(&mut network, &mut state)?;
+        let ProbeStatus::Awaited(probe) = state.probe_at(Sequence(sequence)) else {
+            panic!("expected awaited probe")
+        };
+        let payload_header = PayloadHeader::new(&probe);
+        let stale_payload_header = PayloadHeader {
+            sent: probe.sent - Duration::from_secs(1),
+            ..payload_header
+        };
+        for (payload_header, expect_complete) in
+            [(stale_payload_header, false), (payload_header, true)]
+        {
+            network.checkpoint();
+            network.expect_recv_probe().times(1).returning(move || {
+                Ok(Some(Response::EchoReply(
+                    ResponseData::new(
+                        SystemTime::now(),
+                        target_addr,
+                        ResponseSeq::Icmp(ResponseSeqIcmp::new(0, sequence, Some(payload_header))),
+                    ),
+                    IcmpPacketCode(0),
+                )))
+            });
+            tracer.recv_response(&mut network, &mut state)?;
+            assert_eq!(
+                expect_complete,
+                matches!(state.probe_at(Sequence(sequence)), ProbeStatus::Complete(_))
+            );
+        }
+        Ok(())
+    }
+
+    // A path to an IPv6 target which crosses a NAT64 translator.
+    //
+    // The target address is formed from a custom NAT64 prefix but the hops beyond the translator
+    // respond from addresses within the well-known NAT64 prefix and quote the target address
+    // translated using the well-known prefix.
+    //
+    // This test simulates sending 3 UDP probes (seq=33000..=33002) and receiving a response from
+    // a native IPv6 hop, a hop behind the translator and the target behind the translator.
+    #[test]
+    fn test_udp_path_crossing_nat64_translator() -> anyhow::Result<()> {
+        let custom_prefix = Nat64Prefix::new(Ipv6Addr::from_str("2001:db8:64::")?);
+        let target_addr = IpAddr::from_str("2001:db8:64::198.51.100.1")?;
+        let translated_target_addr = IpAddr::from_str("64:ff9b::198.51.100.1")?;
+        let hop1_addr = IpAddr::from_str("2001:db8::1")?;
+        let hop2_addr = IpAddr::from_str("64:ff9b::192.0.2.1")?;
+        let responses = [
+            (hop1_addr, target_addr, 33000),
+            (hop2_addr, translated_target_addr, 33001),
+            (translated_target_addr, translated_target_addr, 33002),
+        ];
+
+        let mut network = MockNetwork::new();
+        let mut seq = mockall::Sequence::new();
+        network.expect_send_probe().times(3).returning(|_| Ok(()));
+        for (addr, dest_addr, sequence) in responses {
+            network
+                .expect_recv_probe()
+                .times(1)
+                .in_sequence(&mut seq)
+                .returning(move || {
+                    let data = ResponseData::new(
+                        SystemTime::now(),
+                        addr,
+                        ResponseSeq::Udp(ResponseSeqUdp::new(
+                            0, dest_addr, 5000, sequence, 0, 0, false, None,
+                        )),
+                    );
+                    Ok(Some(if addr == dest_addr {
+                        Response::DestinationUnreachable(data, IcmpPacketCode(4), None, None)
+                    } else {
+                        Response::TimeExceeded(data, IcmpPacketCode(0), None)
+                    }))
+                });
+        }
+
+        let config = StrategyConfig {
+            target_addr,
+            max_rounds: Some(MaxRounds(NonZeroUsize::MIN)),
+            initial_sequence: Sequence(33000),
+            port_direction: PortDirection::FixedSrc(Port(5000)),
+            protocol: Protocol::Udp,
+            nat64_prefixes: vec![Nat64Prefix::WELL_KNOWN, custom_prefix],
+            ..Default::default()
+        };
+        let tracer = Strategy::new(&config, |_| {});
+        let mut state = TracerState::new(config);
+        for _ in 0..3 {
+            tracer.send_request(&mut network, &mut state)?;
+        }
+        for _ in 0..3 {
+            tracer.recv_response(&mut network, &mut state)?;
+        }
+        let probes = state
+            .probes()
+            .iter()
+            .map(|probe| match probe {
+                ProbeStatus::Complete(complete) => {
+                    (complete.sequence.0, complete.host, complete.nat64_prefix)
+                }
+                _ => panic!("expected complete probe"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (33000, hop1_addr, None),
+                (33001, hop2_addr, Some(Nat64Prefix::WELL_KNOWN)),
+                (33002, translated_target_addr, Some(Nat64Prefix::WELL_KNOWN)),
+            ],
+            probes
+        );
+        assert!(state.target_found());
+        Ok(())
+    }
+
+    // A long-running UDP trace with the classic strategy and the dest port fixed, for which the
+    // sequence, and hence the src port, wraps from the top of the sequence range back to the
+    // initial sequence.
+    //
+    // This test simulates sending 60 UDP probes per round for enough rounds to wrap the sequence
+    // several times, both for the full sequence range and for a narrow range bounded by the
+    // `max_sequence`, and checks that every probe is sent from a src port within the sequence
+    // range, and that a delayed response to a probe sent before the sequence wrapped is not
+    // attributed to a probe of the current round.
+    #[test_case::test_case(64000, 65535, 3; "full range")]
+    #[test_case::test_case(33000, 34023, 5; "max sequence range")]
+    fn test_udp_classic_fixed_dest_sequence_wrap(
+        initial_sequence: u16,
+        max_sequence: u16,
+        expected_wraps: usize,
+    ) -> anyhow::Result<()> {
+        let target_addr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let hop_addr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 254));
+        let response_sequence = Arc::new(AtomicU16::new(0));
+
+        let mut network = MockNetwork::new();
+        network
+            .expect_send_probe()
+            .withf(move |probe| {
+                probe.src_port.0 >= initial_sequence
+                    && probe.src_port.0 <= max_sequence
+                    && probe.src_port == Port(probe.sequence.0)
+                    && probe.dest_port == Port(33434)
+            })
+            .returning(|_| Ok(()));
+        let recv_sequence = response_sequence.clone();
+        network.expect_recv_probe().returning(move || {
+            let sequence = recv_sequence.load(Ordering::Relaxed);
+            Ok(Some(Response::TimeExceeded(
+                ResponseData::new(
+                    SystemTime::now(),
+                    hop_addr,
+                    ResponseSeq::Udp(ResponseSeqUdp::new(
+                        0,
+                        target_addr,
+                        sequence,
+                        33434,
+                        0,
+                        0,
+                        false,
+                        None,
+                    )),
+                ),
+                IcmpPacketCode(0),
+                None,
+            )))
+        });
+
+        let config = StrategyConfig {
+            target_addr,
+            initial_sequence: Sequence(initial_sequence),
+            max_sequence: Sequence(max_sequence),
+            max_ttl: TimeToLive(64),
+            max_inflight: MaxInflight(64),
+            port_direction: PortDirection::FixedDest(Port(33434)),
+            protocol: Protocol::Udp,
+            ..Default::default()
+        };
+        let tracer = Strategy::new(&config, |_| {});
+        let mut state = TracerState::new(config);
+        let mut wrapped = 0;
+        for _ in 0..40 {
+            for _ in 0..60 {
+                tracer.send_request(&mut network, &mut state)?;
+            }
+            let round_sequence = match &state.probes()[0] {
+                ProbeStatus::Awaited(probe) => probe.sequence.0,
+                _ => panic!("expected awaited probe"),
+            };
+            if round_sequence == initial_sequence {
+                wrapped += 1;
+            }
+
+            // A delayed response to a probe sent in an earlier round, which is within the buffer
+            // of the current round but has not been sent in the current round.
+            response_sequence.store(round_sequence + 60, Ordering::Relaxed);
+            tracer.recv_response(&mut network, &mut state)?;
+            assert!(state
+                .probes()
+                .iter()
+                .all(|probe| matches!(probe, ProbeStatus::Awaited(_))));
+
+            // A response to the first probe of the current round.
+            response_sequence.store(round_sequence, Ordering::Relaxed);
+            tracer.recv_response(&mut network, &mut state)?;
+            assert!(matches!(
+                state.probe_at(Sequence(round_sequence)),
+                ProbeStatus::Complete(_)
+            ));
+            state.advance_round(TimeToLive(1));
+        }
+        assert_eq!(expected_wraps, wrapped);
+        Ok(())
+    }
+
+    // A UDP trace with the classic strategy and both ports fixed.
+    //
+    // This test simulates sending 2 UDP probes (seq=33000..=33001) and checks that each probe is
+    // sent with both ports fixed and the sequence stored in the checksum, and that the responses
+    // are matched to the probes by the checksum of the quoted UDP packet.
+    #[test]
+    fn test_udp_classic_fixed_both() -> anyhow::Result<()> {
+        let target_addr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let hop_addr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 254));
+        let responses = [(hop_addr, 33000), (target_addr, 33001)];
+
+        let mut network = MockNetwork::new();
+        let mut seq = mockall::Sequence::new();
+        network
+            .expect_send_probe()
+            .times(2)
+            .withf(|probe| {
+                probe.src_port == Port(5000)
+                    && probe.dest_port == Port(80)
+                    && probe.flags == Flags::PARIS_CHECKSUM
+            })
+            .returning(|_| Ok(()));
+        for (addr, sequence) in responses {
+            network
+                .expect_recv_probe()
+                .times(1)
+                .in_sequence(&mut seq)
+                .returning(move || {
+                    let data = ResponseData::new(
+                        SystemTime::now(),
+                        addr,
+                        ResponseSeq::Udp(ResponseSeqUdp::new(
+                            0,
+                            target_addr,
+                            5000,
+                            80,
+                            sequence,
+                            0,
+                            false,
+                            None,
+                        )),
+                    );
+                    Ok(Some(if addr == target_addr {
+                        Response::DestinationUnreachable(data, IcmpPacketCode(3), None, None)
+                    } else {
+                        Response::TimeExceeded(data, IcmpPacketCode(0), None)
+                    }))
+                });
+        }
+
+        let config = StrategyConfig {
+            target_addr,
+            max_rounds: Some(MaxRounds(NonZeroUsize::MIN)),
+            initial_sequence: Sequence(33000),
+            port_direction: PortDirection::FixedBoth(Port(5000), Port(80)),
+            protocol: Protocol::Udp,
+            ..Default::default()
+        };
+        let tracer = Strategy::new(&config, |_| {});
+        let mut state = TracerState::new(config);
+        for _ in 0..2 {
+            tracer.send_request(&mut network, &mut state)?;
+        }
+        for _ in 0..2 {
+            tracer.recv_response(&mut network, &mut state)?;
+        }
+        let probes = state
+            .probes()
+            .iter()
+            .map(|probe| match probe {
+                ProbeStatus::Complete(complete) => (complete.sequence.0, complete.host),
+                _ => panic!("expected complete probe"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(vec![(33000, hop_addr), (33001, target_addr)], probes);
+        assert!(state.target_found());
+        Ok(())
+    }
+
+    // A UDP trace with the classic strategy and both ports fixed to an IPv6 target.
+    //
+    // This test simulates sending 2 UDP probes (seq=33000..=33001) and checks that each probe is
+    // sent with both ports fixed and without the checksum flag, and that the responses are matched
+    // to the probes by the `PayloadHeader` of the quoted UDP packet.  A response for which the
+    // payload was not quoted is ignored.
+    #[test]
+    fn test_udp_classic_fixed_both_ipv6() -> anyhow::Result<()> {
+        let target_addr = IpAddr::V6(Ipv6Addr::from_str("2001:db8::1")?);
+        let hop_addr = IpAddr::V6(Ipv6Addr::from_str("2001:db8::fe")?);
+        let sent = Arc::new(std::sync::Mutex::new(Vec::new()));
+
+        let mut network = MockNetwork::new();
+        let mut seq = mockall::Sequence::new();
+        let sent_probes = sent.clone();
+        network
+            .expect_send_probe()
+            .times(2)
+            .withf(|probe| {
+                probe.src_port == Port(5000)
+                    && probe.dest_port == Port(80)
+                    && probe.flags == Flags::empty()
+            })
+            .returning(move |probe| {
+                sent_probes.lock().unwrap().push(probe);
+                Ok(())
+            });
+        for (addr, index, quoted) in [
+            (hop_addr, 0, false),
+            (hop_addr, 0, true),
+            (target_addr, 1, true),
+        ] {
+            let sent_probes = sent.clone();
+            network
+                .expect_recv_probe()
+                .times(1)
+                .in_sequence(&mut seq)
+                .returning(move || {
+                    let probe = &sent_probes.lock().unwrap()[index];
+                    let payload_header = quoted.then(|| PayloadHeader::new(probe));
+                    let data = ResponseData::new(
+                        SystemTime::now(),
+                        addr,
+                        ResponseSeq::Udp(ResponseSeqUdp::new(
+                            0,
+                            target_addr,
+                            5000,
+                            80,
+                            0,
+                            0,
+                            false,
+                            payload_header,
+                        )),
+                    );
+                    Ok(Some(if addr == target_addr {
+                        Response::DestinationUnreachable(data, IcmpPacketCode(4), None, None)
+                    } else {
+                        Response::TimeExceeded(data, IcmpPacketCode(0), None)
+                    }))
+                });
+        }
+
+        let config = StrategyConfig {
+            target_addr,
+            max_rounds: Some(MaxRounds(NonZeroUsize::MIN)),
+            initial_sequence: Sequence(33000),
+            port_direction: PortDirection::FixedBoth(Port(5000), Port(80)),
+            protocol: Protocol::Udp,
+            ..Default::default()
+        };
+        let tracer = Strategy::new(&config, |_| {});
+        let mut state = TracerState::new(config);
+        for _ in 0..2 {
+            tracer.send_request(&mut network, &mut state)?;
+        }
+        tracer.recv_response(&mut network, &mut state)?;
+        assert!(matches!(
+            state.probe_at(Sequence(33000)),
+            ProbeStatus::Awaited(_)
+        ));
+        for _ in 0..2 {
+            tracer.recv_response(&mut network, &mut state)?;
+        }
+        let probes = state
+            .probes()
+            .iter()
+            .map(|probe| match probe {
+                ProbeStatus::Complete(complete) => (complete.sequence.0, complete.host),
+                _ => panic!("expected complete probe"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(vec![(33000, hop_addr), (33001, target_addr)], probes);
+        assert!(state.target_found());
+        Ok(())
+    }
+
+    // A UDP trace with the Dublin strategy to an IPv4 target behind a NAT device which rewrites
+    // the IPv4 identification field.
+    //
+    // This test simulates sending 2 UDP probes (seq=33000..=33001) and receiving a response for
+    // the first probe with the expected identification and a response for a probe from another
+    // flow (i.e. with a different destination port) with an identification which has been
+    // rewritten such that it maps to the second probe, which must be ignored.
+    #[test]
+    fn test_udp_dublin_ipv4_identification_rewritten() -> anyhow::Result<()> {
+        let target_addr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let hop_addr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 254));
+        let responses = [(33000, 33000), (33001, 32999)];
+
+        let mut network = MockNetwork::new();
+        let mut seq = mockall::Sequence::new();
+        network
+            .expect_send_probe()
+            .times(2)
+            .withf(|probe| {
+                probe.identifier.0 == probe.sequence.0
+                    && probe.src_port == Port(5000)
+                    && probe.dest_port == Port(33000)
+            })
+            .returning(|_| Ok(()));
+        for (identifier, dest_port) in responses {
+            network
+                .expect_recv_probe()
+                .times(1)
+                .in_sequence(&mut seq)
+                .returning(move || {
+                    Ok(Some(Response::TimeExceeded(
+                        ResponseData::new(
+                            SystemTime::now(),
+                            hop_addr,
+                            ResponseSeq::Udp(ResponseSeqUdp::new(
+                                identifier,
+                                target_addr,
+                                5000,
+                                dest_port,
+                                0,
+                                0,
+                                false,
+                                None,
+                            )),
+                        ),
+                        IcmpPacketCode(0),
+                        None,
+                    )))
+                });
+        }
+
+        let config = StrategyConfig {
+            target_addr,
+            max_rounds: Some(MaxRounds(NonZeroUsize::MIN)),
+            initial_sequence: Sequence(33000),
+            port_direction: PortDirection::FixedSrc(Port(5000)),
+            protocol: Protocol::Udp,
+            multipath_strategy: MultipathStrategy::Dublin,
+            ..Default::default()
+        };
+        let tracer = Strategy::new(&config, |_| {});
+        let mut state = TracerState::new(config);
+        for _ in 0..2 {
+            tracer.send_request(&mut network, &mut state)?;
+        }
+        for _ in 0..2 {
+            tracer.recv_response(&mut network, &mut state)?;
+        }
+        assert!(matches!(
+            state.probe_at(Sequence(33000)),
+            ProbeStatus::Complete(complete) if complete.host == hop_addr
+        ));
+        assert!(matches!(
+            state.probe_at(Sequence(33001)),
+            ProbeStatus::Awaited(_)
+        ));
+        Ok(())
+    }
 }
 
 /// Mutable state needed for the tracing algorithm.
@@ -421,9 +1241,10 @@ mod tests {
 /// the `TracerState` struct.
 mod state {
     use crate::constants::MAX_SEQUENCE_PER_ROUND;
+    use crate::nat64;
     use crate::probe::{Extensions, IcmpPacketCode, IcmpPacketType, Probe, ProbeStatus};
     use crate::strategy::StrategyConfig;
-    use crate::types::{MaxRounds, Port, RoundId, Sequence, TimeToLive, TraceId};
+    use crate::types::{FlowIndex, MaxRounds, Port, RoundId, Sequence, TimeToLive, TraceId};
     use crate::{Flags, MultipathStrategy, PortDirection, Protocol};
     use std::array::from_fn;
     use std::net::IpAddr;
@@ -468,6 +1289,8 @@ mod state {
         round_sequence: Sequence,
         /// The time-to-live for the _next_ `EchoRequest` packet to be sent.
         ttl: TimeToLive,
+        /// The flow for the _next_ `EchoRequest` packet to be sent.
+        flow_index: FlowIndex,
         /// The current round.
         round: RoundId,
         /// The timestamp of when the current round started.
@@ -488,11 +1311,12 @@ mod state {
     impl TracerState {
         pub fn new(config: StrategyConfig) -> Self {
             Self {
-                config,
                 buffer: from_fn(|_| ProbeStatus::default()),
                 sequence: config.initial_sequence,
                 round_sequence: config.initial_sequence,
                 ttl: config.first_ttl,
+                flow_index: FlowIndex(0),
+                config,
                 round: RoundId(0),
                 round_start: SystemTime::now(),
                 target_found: false,
@@ -504,19 +1328,41 @@ mod state {
 
         /// Get a slice of `ProbeState` for the current round.
         pub fn probes(&self) -> &[ProbeStatus] {
-            let round_size = self.sequence - self.round_sequence;
-            &self.buffer[..round_size.0 as usize]
+            &self.buffer[..self.round_index(self.sequence)]
         }
 
         /// Get the `ProbeState` for `sequence`
         pub fn probe_at(&self, sequence: Sequence) -> ProbeStatus {
-            self.buffer[usize::from(sequence - self.round_sequence)].clone()
+            self.buffer[self.round_index(sequence)].clone()
+        }
+
+        /// The index of `sequence` into the buffer of the current round.
+        ///
+        /// This is the offset of `sequence` from the `round_sequence`, modulo 2^16, such that a
+        /// `sequence` which precedes the `round_sequence` has an index beyond the buffer rather
+        /// than underflowing.
+        const fn round_index(&self, sequence: Sequence) -> usize {
+            sequence.0.wrapping_sub(self.round_sequence.0) as usize
         }
 
         pub const fn ttl(&self) -> TimeToLive {
             self.ttl
         }
 
+        /// The time-to-live and flow of the most recently issued `Probe`.
+        ///
+        /// Every flow is probed at a given time-to-live before the next time-to-live is probed.
+        pub const fn last_issued(&self) -> (TimeToLive, FlowIndex) {
+            if self.flow_index.0 == 0 {
+                (
+                    TimeToLive(self.ttl.0.saturating_sub(1)),
+                    FlowIndex(self.config.flows_per_round - 1),
+                )
+            } else {
+                (self.ttl, FlowIndex(self.flow_index.0 - 1))
+            }
+        }
+
         pub const fn round_start(&self) -> SystemTime {
             self.round_start
         }
@@ -538,14 +1384,17 @@ mod state {
         }
 
         /// Is `sequence` in the current round?
-        pub fn in_round(&self, sequence: Sequence) -> bool {
-            sequence >= self.round_sequence && sequence.0 - self.round_sequence.0 < BUFFER_SIZE
+        ///
+        /// Only a `sequence` which has been issued in the current round is in the round, such that
+        /// a delayed response to a probe from a prior round, including one sent before the
+        /// sequence was last wrapped, is never attributed to a probe of the current round.
+        pub const fn in_round(&self, sequence: Sequence) -> bool {
+            self.round_index(sequence) < self.round_index(self.sequence)
         }
 
         /// Do we have capacity in the current round for another sequence?
-        pub fn round_has_capacity(&self) -> bool {
-            let round_size = self.sequence - self.round_sequence;
-            round_size.0 < BUFFER_SIZE
+        pub const fn round_has_capacity(&self) -> bool {
+            self.round_index(self.sequence) < BUFFER_SIZE as usize
         }
 
         /// Are all rounds complete?
@@ -556,10 +1405,11 @@ mod state {
             }
         }
 
-        /// Create and return the next `Probe` at the current `sequence` and `ttl`.
+        /// Create and return the next `Probe` at the current `sequence`, `ttl` and `flow_index`.
         ///
-        /// We post-increment `ttl` here and so in practice we only allow `ttl` values in the range
-        /// `1..254` to allow us to use a `u8`.
+        /// We post-increment `ttl` here, once all flows have been probed at the current `ttl`, and
+        /// so in practice we only allow `ttl` values in the range `1..254` to allow us to use a
+        /// `u8`.
         #[instrument(skip(self))]
         pub fn next_probe(&mut self, sent: SystemTime) -> Probe {
             let (src_port, dest_port, identifier, flags) = self.probe_data();
@@ -572,11 +1422,19 @@ mod state {
                 self.round,
                 sent,
                 flags,
+                self.config
+                    .flow_label_strategy
+                    .flow_label(self.flow_round(self.flow_index)),
+                self.flow_index,
             );
-            let probe_index = usize::from(self.sequence - self.round_sequence);
+            let probe_index = self.round_index(self.sequence);
             self.buffer[probe_index] = ProbeStatus::Awaited(probe.clone());
-            debug_assert!(self.ttl < TimeToLive(u8::MAX));
-            self.ttl += TimeToLive(1);
+            self.flow_index.0 += 1;
+            if self.flow_index.0 == self.config.flows_per_round {
+                self.flow_index = FlowIndex(0);
+                debug_assert!(self.ttl < TimeToLive(u8::MAX));
+                self.ttl += TimeToLive(1);
+            }
             debug_assert!(self.sequence < Sequence(u16::MAX));
             self.sequence += Sequence(1);
             probe
@@ -585,7 +1443,7 @@ mod state {
         /// Re-issue the `Probe` with the next sequence number.
         ///
         /// This will mark the `ProbeState` at the previous `sequence` as skipped and re-create it
-        /// with the previous `ttl` and the current `sequence`.
+        /// with the previous `ttl` and flow and the current `sequence`.
         ///
         /// For example, if the sequence is `4` and the `ttl` is `5` prior to calling this method
         /// then afterward:
@@ -593,18 +1451,23 @@ mod state {
         /// - A new `ProbeState` will be created at sequence `4` with a `ttl` of `5`
         #[instrument(skip(self))]
         pub fn reissue_probe(&mut self, sent: SystemTime) -> Probe {
-            let probe_index = usize::from(self.sequence - self.round_sequence);
+            let probe_index = self.round_index(self.sequence);
             self.buffer[probe_index - 1] = ProbeStatus::Skipped;
             let (src_port, dest_port, identifier, flags) = self.probe_data();
+            let (ttl, flow_index) = self.last_issued();
             let probe = Probe::new(
                 self.sequence,
                 identifier,
                 src_port,
                 dest_port,
-                self.ttl - TimeToLive(1),
+                ttl,
                 self.round,
                 sent,
                 flags,
+                self.config
+                    .flow_label_strategy
+                    .flow_label(self.flow_round(flow_index)),
+                flow_index,
             );
             self.buffer[probe_index] = ProbeStatus::Awaited(probe.clone());
             debug_assert!(self.sequence < Sequence(u16::MAX));
@@ -634,6 +1497,30 @@ mod state {
             )
         }
 
+        /// A distinct `RoundId` for each flow of the current round.
+        ///
+        /// This is used to derive the varying port and flow label of a flow, such that every flow
+        /// of every round is distinct.  When there is a single flow per round this is the round.
+        const fn flow_round(&self, flow_index: FlowIndex) -> RoundId {
+            RoundId(self.round.0 * self.config.flows_per_round as usize + flow_index.0 as usize)
+        }
+
+        /// The varying src or dest port for all UDP probes of the current flow.
+        ///
+        /// This is used by the Paris and Dublin strategies, which must keep the ports fixed for
+        /// every probe in a flow, and is offset from the `initial_sequence` by the flow round
+        /// such that it wraps within the range of ports bounded by `initial_sequence` and
+        /// `max_sequence`.
+        fn round_port(&self) -> u16 {
+            let range = usize::from(
+                self.config
+                    .max_sequence
+                    .0
+                    .saturating_sub(self.config.initial_sequence.0),
+            ) + 1;
+            self.config.initial_sequence.0 + (self.flow_round(self.flow_index).0 % range) as u16
+        }
+
         /// Determine the `src_port`, `dest_port` and `identifier` for the current UDP probe.
         fn probe_udp_data(&self) -> (Port, Port, TraceId, Flags) {
             match self.config.multipath_strategy {
@@ -650,13 +1537,23 @@ mod state {
                         TraceId(0),
                         Flags::empty(),
                     ),
-                    PortDirection::FixedBoth(_, _) | PortDirection::None => {
-                        unimplemented!()
-                    }
+                    // The sequence is carried in the `PayloadHeader` for IPv6, as the whole of the
+                    // original datagram is quoted, and in the checksum for IPv4, where only the
+                    // UDP header may be quoted.
+                    PortDirection::FixedBoth(src_port, dest_port) => (
+                        Port(src_port.0),
+                        Port(dest_port.0),
+                        TraceId(0),
+                        if self.config.target_addr.is_ipv6() {
+                            Flags::empty()
+                        } else {
+                            Flags::PARIS_CHECKSUM
+                        },
+                    ),
+                    PortDirection::None => unimplemented!(),
                 },
                 MultipathStrategy::Paris => {
-                    let round_port = ((self.config.initial_sequence.0 as usize + self.round.0)
-                        % usize::from(u16::MAX)) as u16;
+                    let round_port = self.round_port();
                     match self.config.port_direction {
                         PortDirection::FixedSrc(src_port) => (
                             Port(src_port.0),
@@ -680,8 +1577,7 @@ mod state {
                     }
                 }
                 MultipathStrategy::Dublin => {
-                    let round_port = ((self.config.initial_sequence.0 as usize + self.round.0)
-                        % usize::from(u16::MAX)) as u16;
+                    let round_port = self.round_port();
                     match self.config.port_direction {
                         PortDirection::FixedSrc(src_port) => (
                             Port(src_port.0),
@@ -735,6 +1631,7 @@ mod state {
                 host,
                 received,
                 is_target,
+                None,
                 extensions,
             );
         }
@@ -748,6 +1645,7 @@ mod state {
             host: IpAddr,
             received: SystemTime,
             icmp_code: IcmpPacketCode,
+            next_hop_mtu: Option<u16>,
             extensions: Option<Extensions>,
         ) {
             self.complete_probe(
@@ -756,10 +1654,54 @@ mod state {
                 host,
                 received,
                 true,
+                next_hop_mtu,
                 extensions,
             );
         }
 
+        /// Mark the `ProbeState` at `sequence` completed as `PacketTooBig` and update the round
+        /// state.
+        #[instrument(skip(self))]
+        pub fn complete_probe_packet_too_big(
+            &mut self,
+            sequence: Sequence,
+            host: IpAddr,
+            received: SystemTime,
+            mtu: u16,
+        ) {
+            self.complete_probe(
+                sequence,
+                IcmpPacketType::PacketTooBig,
+                host,
+                received,
+                true,
+                Some(mtu),
+                None,
+            );
+        }
+
+        /// Mark the `ProbeState` at `sequence` completed as `ParameterProblem` and update the
+        /// round state.
+        #[instrument(skip(self))]
+        pub fn complete_probe_parameter_problem(
+            &mut self,
+            sequence: Sequence,
+            host: IpAddr,
+            received: SystemTime,
+            icmp_code: IcmpPacketCode,
+            pointer: u32,
+        ) {
+            self.complete_probe(
+                sequence,
+                IcmpPacketType::ParameterProblem(icmp_code, pointer),
+                host,
+                received,
+                true,
+                None,
+                None,
+            );
+        }
+
         /// Mark the `ProbeState` at `sequence` completed as `EchoReply` and update the round state.
         #[instrument(skip(self))]
         pub fn complete_probe_echo_reply(
@@ -776,6 +1718,7 @@ mod state {
                 received,
                 true,
                 None,
+                None,
             );
         }
 
@@ -795,6 +1738,27 @@ mod state {
                 received,
                 true,
                 None,
+                None,
+            );
+        }
+
+        /// Update the state of a `ProbeState` and the trace for a refused `TCP` connection.
+        ///
+        /// The target has been reached even though the target port is closed.
+        pub fn complete_probe_refused(
+            &mut self,
+            sequence: Sequence,
+            host: IpAddr,
+            received: SystemTime,
+        ) {
+            self.complete_probe(
+                sequence,
+                IcmpPacketType::Refused,
+                host,
+                received,
+                true,
+                None,
+                None,
             );
         }
 
@@ -812,6 +1776,7 @@ mod state {
         /// from the target host with differing time-to-live values and so must ensure we
         /// use the time-to-live with the lowest sequence number.
         #[instrument(skip(self))]
+        #[allow(clippy::too_many_arguments)]
         fn complete_probe(
             &mut self,
             sequence: Sequence,
@@ -819,6 +1784,7 @@ mod state {
             host: IpAddr,
             received: SystemTime,
             is_target: bool,
+            next_hop_mtu: Option<u16>,
             extensions: Option<Extensions>,
         ) {
             // Retrieve and update the `ProbeState` at `sequence`.
@@ -838,10 +1804,20 @@ mod state {
                     return;
                 }
             };
-            let completed = awaited.complete(host, received, icmp_packet_type, extensions);
+            let nat64_prefix = nat64::find_prefix(host, &self.config.nat64_prefixes);
+            if let Some(prefix) = nat64_prefix {
+                tracing::debug!(%host, %prefix, "probe response from behind NAT64");
+            }
+            let completed = awaited.complete(
+                host,
+                received,
+                icmp_packet_type,
+                next_hop_mtu,
+                extensions,
+                nat64_prefix,
+            );
             let ttl = completed.ttl;
-            self.buffer[usize::from(sequence - self.round_sequence)] =
-                ProbeStatus::Complete(completed);
+            self.buffer[self.round_index(sequence)] = ProbeStatus::Complete(completed);
 
             // If this `ProbeState` found the target then we set the `target_tll` if not already
             // set, being careful to account for `Probes` being received out-of-order.
@@ -890,6 +1866,7 @@ mod state {
             self.max_received_ttl = None;
             self.round += RoundId(1);
             self.ttl = first_ttl;
+            self.flow_index = FlowIndex(0);
         }
 
         /// The maximum sequence number allowed.
@@ -902,13 +1879,18 @@ mod state {
         /// _at least_ `BUFFER_SIZE` to ensure delayed responses from a prior
         /// round are not incorrectly associated with later rounds (see
         /// `in_round` function).
+        ///
+        /// The configured `max_sequence` must never be exceeded and so the
+        /// sequence is wrapped if there are fewer than `BUFFER_SIZE` sequence
+        /// numbers remaining for the next round.
         fn max_sequence(&self) -> Sequence {
-            match (self.config.multipath_strategy, self.config.target_addr) {
+            let max_sequence = match (self.config.multipath_strategy, self.config.target_addr) {
                 (MultipathStrategy::Dublin, IpAddr::V6(_)) => {
                     self.config.initial_sequence + Sequence(BUFFER_SIZE)
                 }
                 _ => MAX_SEQUENCE,
-            }
+            };
+            max_sequence.min(Sequence(self.config.max_sequence.0 - (BUFFER_SIZE - 1)))
         }
     }
 
@@ -916,7 +1898,8 @@ mod state {
     mod tests {
         use super::*;
         use crate::probe::IcmpPacketType;
-        use crate::types::MaxInflight;
+        use crate::types::{FlowLabel, MaxInflight};
+        use crate::FlowLabelStrategy;
         use rand::Rng;
         use std::net::{IpAddr, Ipv4Addr};
         use std::time::Duration;
@@ -1174,6 +2157,47 @@ mod state {
             }
         }
 
+        #[test]
+        fn test_sequence_wrap_max_sequence() {
+            let config = StrategyConfig {
+                max_sequence: Sequence(34023),
+                ..cfg(Sequence(33000))
+            };
+            let mut state = TracerState::new(config);
+            let mut wrapped = 0;
+            for _ in 0..100 {
+                for _ in 0..30 {
+                    let sequence = state.next_probe(SystemTime::now()).sequence;
+                    assert!((33000..=34023).contains(&sequence.0));
+                }
+                state.advance_round(TimeToLive(1));
+                if state.sequence == Sequence(33000) {
+                    wrapped += 1;
+                }
+            }
+            // The sequence wraps after every 18 rounds of 30 sequences, i.e. once fewer than 512
+            // sequences remain before the max_sequence.
+            assert_eq!(5, wrapped);
+        }
+
+        #[test]
+        fn test_round_port_wrap_max_sequence() {
+            let config = StrategyConfig {
+                protocol: Protocol::Udp,
+                multipath_strategy: MultipathStrategy::Paris,
+                port_direction: PortDirection::FixedDest(Port(33434)),
+                max_sequence: Sequence(41023),
+                ..cfg(Sequence(40000))
+            };
+            let mut state = TracerState::new(config);
+            for round in 0..2100 {
+                let probe = state.next_probe(SystemTime::now());
+                assert_eq!(Port(40000 + round % 1024), probe.src_port);
+                assert_eq!(Port(33434), probe.dest_port);
+                state.advance_round(TimeToLive(1));
+            }
+        }
+
         #[test]
         fn test_sequence_wrap_with_skip() {
             let total_rounds = 2000;
@@ -1193,14 +2217,21 @@ mod state {
 
         #[test]
         fn test_in_round() {
-            let state = TracerState::new(cfg(Sequence(33000)));
+            let mut state = TracerState::new(cfg(Sequence(33000)));
+            assert!(!state.in_round(Sequence(33000)));
+            for _ in 0..200 {
+                _ = state.next_probe(SystemTime::now());
+                _ = state.reissue_probe(SystemTime::now());
+            }
             assert!(state.in_round(Sequence(33000)));
-            assert!(state.in_round(Sequence(33511)));
+            assert!(state.in_round(Sequence(33399)));
+            assert!(!state.in_round(Sequence(33400)));
+            assert!(!state.in_round(Sequence(33511)));
             assert!(!state.in_round(Sequence(33512)));
+            assert!(!state.in_round(Sequence(32999)));
         }
 
         #[test]
-        #[should_panic(expected = "assertion failed: !state.in_round(Sequence(64491))")]
         fn test_in_delayed_probe_not_in_round() {
             let mut state = TracerState::new(cfg(Sequence(64000)));
             for _ in 0..55 {
@@ -1210,6 +2241,139 @@ mod state {
             assert!(!state.in_round(Sequence(64491)));
         }
 
+        #[test]
+        fn test_delayed_probe_across_wrap_not_in_round() {
+            let mut state = TracerState::new(cfg(Sequence(64000)));
+            for _ in 0..200 {
+                _ = state.next_probe(SystemTime::now());
+                _ = state.reissue_probe(SystemTime::now());
+            }
+            for _ in 0..4 {
+                state.advance_round(TimeToLive(1));
+                for _ in 0..200 {
+                    _ = state.next_probe(SystemTime::now());
+                }
+            }
+            state.advance_round(TimeToLive(1));
+            assert_eq!(Sequence(64000), state.round_sequence);
+            _ = state.next_probe(SystemTime::now());
+            assert!(state.in_round(Sequence(64000)));
+            assert!(!state.in_round(Sequence(64001)));
+            assert!(!state.in_round(Sequence(64999)));
+            assert!(!state.in_round(Sequence(u16::MAX)));
+            assert!(!state.in_round(Sequence(0)));
+            assert_eq!(1, state.probes().len());
+        }
+
+        #[test]
+        fn test_flow_label_range() {
+            let config = StrategyConfig {
+                flow_label_strategy: FlowLabelStrategy::new_range(100, 102),
+                ..cfg(Sequence(33000))
+            };
+            let mut state = TracerState::new(config);
+            let mut flow_labels = vec![];
+            for _ in 0..5 {
+                let probe1 = state.next_probe(SystemTime::now());
+                let probe2 = state.reissue_probe(SystemTime::now());
+                assert_eq!(probe1.flow_label, probe2.flow_label);
+                flow_labels.push(probe1.flow_label);
+                state.advance_round(TimeToLive(1));
+            }
+            assert_eq!(
+                vec![
+                    FlowLabel(100),
+                    FlowLabel(101),
+                    FlowLabel(102),
+                    FlowLabel(100),
+                    FlowLabel(101)
+                ],
+                flow_labels
+            );
+        }
+
+        #[test]
+        fn test_flows_per_round() {
+            let config = StrategyConfig {
+                protocol: Protocol::Udp,
+                multipath_strategy: MultipathStrategy::Paris,
+                port_direction: PortDirection::new_fixed_src(5000),
+                flows_per_round: 3,
+                ..cfg(Sequence(33000))
+            };
+            let mut state = TracerState::new(config);
+            let probes = (0..6)
+                .map(|_| state.next_probe(SystemTime::now()))
+                .map(|probe| (probe.ttl.0, probe.flow_index.0, probe.dest_port.0))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                vec![
+                    (1, 0, 33000),
+                    (1, 1, 33001),
+                    (1, 2, 33002),
+                    (2, 0, 33000),
+                    (2, 1, 33001),
+                    (2, 2, 33002)
+                ],
+                probes
+            );
+            assert_eq!(TimeToLive(3), state.ttl());
+            assert_eq!((TimeToLive(2), FlowIndex(2)), state.last_issued());
+            let probe = state.next_probe(SystemTime::now());
+            assert_eq!((TimeToLive(3), FlowIndex(0)), state.last_issued());
+            let reissued = state.reissue_probe(SystemTime::now());
+            assert_eq!(probe.ttl, reissued.ttl);
+            assert_eq!(probe.flow_index, reissued.flow_index);
+            state.advance_round(TimeToLive(1));
+            let probe = state.next_probe(SystemTime::now());
+            assert_eq!(TimeToLive(1), probe.ttl);
+            assert_eq!(FlowIndex(0), probe.flow_index);
+            assert_eq!(Port(33003), probe.dest_port);
+        }
+
+        #[test]
+        fn test_flows_per_round_flow_label() {
+            let config = StrategyConfig {
+                flow_label_strategy: FlowLabelStrategy::new_range(100, 102),
+                flows_per_round: 2,
+                ..cfg(Sequence(33000))
+            };
+            let mut state = TracerState::new(config);
+            let mut flow_labels = vec![];
+            for _ in 0..2 {
+                for _ in 0..4 {
+                    flow_labels.push(state.next_probe(SystemTime::now()).flow_label.0);
+                }
+                state.advance_round(TimeToLive(1));
+            }
+            assert_eq!(vec![100, 101, 100, 101, 102, 100, 102, 100], flow_labels);
+        }
+
+        #[test]
+        fn test_flow_label_fixed() {
+            let config = StrategyConfig {
+                flow_label_strategy: FlowLabelStrategy::new_fixed(100),
+                ..cfg(Sequence(33000))
+            };
+            let mut state = TracerState::new(config);
+            for _ in 0..3 {
+                let probe1 = state.next_probe(SystemTime::now());
+                let probe2 = state.reissue_probe(SystemTime::now());
+                assert_eq!(FlowLabel(100), probe1.flow_label);
+                assert_eq!(FlowLabel(100), probe2.flow_label);
+                state.advance_round(TimeToLive(1));
+            }
+        }
+
+        #[test]
+        fn test_flow_label_none() {
+            let mut state = TracerState::new(cfg(Sequence(33000)));
+            for _ in 0..3 {
+                assert_eq!(FlowLabel(0), state.next_probe(SystemTime::now()).flow_label);
+                state.advance_round(TimeToLive(1));
+            }
+        }
+
         fn cfg(initial_sequence: Sequence) -> StrategyConfig {
             StrategyConfig {
                 target_addr: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
@@ -1221,8 +2385,12 @@ mod state {
                 grace_duration: Duration::default(),
                 max_inflight: MaxInflight::default(),
                 initial_sequence,
+                max_sequence: Sequence(u16::MAX),
                 multipath_strategy: MultipathStrategy::Classic,
                 port_direction: PortDirection::None,
+                flow_label_strategy: FlowLabelStrategy::None,
+                flows_per_round: 1,
+                nat64_prefixes: vec![],
                 min_round_duration: Duration::default(),
                 max_round_duration: Duration::default(),
             }
@@ -1,11 +1,12 @@
 use crate::error::Result;
 use crate::{
-    Error, IcmpExtensionParseMode, MaxInflight, MaxRounds, MultipathStrategy, PacketSize,
-    PayloadPattern, PortDirection, PrivilegeMode, Protocol, Round, Sequence, State, TimeToLive,
-    TraceId, TypeOfService,
+    Error, FlowLabelStrategy, IcmpExtensionParseMode, Ipv6ExtensionHeader, MaxInflight, MaxRounds,
+    MultipathStrategy, Nat64Prefix, PacketSize, PayloadPattern, PortDirection, PrivilegeMode,
+    Protocol, Round, Sequence, State, TimeToLive, TraceId, TypeOfService,
 };
 use std::fmt::Debug;
 use std::net::IpAddr;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::thread;
 use std::thread::JoinHandle;
@@ -25,7 +26,7 @@ impl Tracer {
     /// Create a `Tracer`.
     ///
     /// Use the [`crate::Builder`] type to create a [`Tracer`].
-    #[allow(clippy::too_many_arguments)]
+    #[allow(clippy::too_many_arguments, clippy::fn_params_excessive_bools)]
     #[must_use]
     pub(crate) fn new(
         interface: Option<String>,
@@ -34,11 +35,15 @@ impl Tracer {
         privilege_mode: PrivilegeMode,
         protocol: Protocol,
         packet_size: PacketSize,
+        max_packet_size: PacketSize,
         payload_pattern: PayloadPattern,
         tos: TypeOfService,
+        dont_fragment: bool,
         icmp_extension_parse_mode: IcmpExtensionParseMode,
+        ipv6_extension_header: Ipv6ExtensionHeader,
         read_timeout: Duration,
         tcp_connect_timeout: Duration,
+        tcp_mss: Option<u16>,
         trace_identifier: TraceId,
         max_rounds: Option<MaxRounds>,
         first_ttl: TimeToLive,
@@ -46,13 +51,21 @@ impl Tracer {
         grace_duration: Duration,
         max_inflight: MaxInflight,
         initial_sequence: Sequence,
+        max_sequence: Sequence,
         multipath_strategy: MultipathStrategy,
         port_direction: PortDirection,
+        flow_label_strategy: FlowLabelStrategy,
+        flows_per_round: u8,
+        nat64_prefixes: Vec<Nat64Prefix>,
         min_round_duration: Duration,
         max_round_duration: Duration,
         max_samples: usize,
         max_flows: usize,
         drop_privileges: bool,
+        unprivileged_fallback: bool,
+        udp_recv_err: bool,
+        validate_icmp_checksum: bool,
+        capture_file: Option<PathBuf>,
     ) -> Self {
         Self {
             inner: Arc::new(inner::TracerInner::new(
@@ -62,11 +75,15 @@ impl Tracer {
                 privilege_mode,
                 protocol,
                 packet_size,
+                max_packet_size,
                 payload_pattern,
                 tos,
+                dont_fragment,
                 icmp_extension_parse_mode,
+                ipv6_extension_header,
                 read_timeout,
                 tcp_connect_timeout,
+                tcp_mss,
                 trace_identifier,
                 max_rounds,
                 first_ttl,
@@ -74,13 +91,21 @@ impl Tracer {
                 grace_duration,
                 max_inflight,
                 initial_sequence,
+                max_sequence,
                 multipath_strategy,
                 port_direction,
+                flow_label_strategy,
+                flows_per_round,
+                nat64_prefixes,
                 min_round_duration,
                 max_round_duration,
                 max_samples,
                 max_flows,
                 drop_privileges,
+                unprivileged_fallback,
+                udp_recv_err,
+                validate_icmp_checksum,
+                capture_file,
             )),
         }
     }
@@ -295,6 +320,36 @@ impl Tracer {
         self.inner.privilege_mode()
     }
 
+    /// Whether to fall back to unprivileged mode if privileged `ICMP` sockets are unavailable.
+    #[must_use]
+    pub fn unprivileged_fallback(&self) -> bool {
+        self.inner.unprivileged_fallback()
+    }
+
+    /// Whether the ICMP errors of `UDP` probes are received from the socket error queue.
+    #[must_use]
+    pub fn udp_recv_err(&self) -> bool {
+        self.inner.udp_recv_err()
+    }
+
+    /// Whether the checksum of received `ICMP` packets is validated.
+    #[must_use]
+    pub fn validate_icmp_checksum(&self) -> bool {
+        self.inner.validate_icmp_checksum()
+    }
+
+    /// The number of received `ICMP` packets discarded due to an invalid checksum.
+    #[must_use]
+    pub fn invalid_checksums(&self) -> usize {
+        self.inner.invalid_checksums()
+    }
+
+    /// The `pcap` file to which the packets of the tracer are written, if any.
+    #[must_use]
+    pub fn capture_file(&self) -> Option<&Path> {
+        self.inner.capture_file()
+    }
+
     /// The protocol of the tracer.
     #[must_use]
     pub fn protocol(&self) -> Protocol {
@@ -325,9 +380,15 @@ impl Tracer {
         self.inner.packet_size()
     }
 
+    /// The maximum packet size of the tracer.
+    #[must_use]
+    pub fn max_packet_size(&self) -> PacketSize {
+        self.inner.max_packet_size()
+    }
+
     /// The payload pattern of the tracer.
     #[must_use]
-    pub fn payload_pattern(&self) -> PayloadPattern {
+    pub fn payload_pattern(&self) -> &PayloadPattern {
         self.inner.payload_pattern()
     }
 
@@ -337,18 +398,36 @@ impl Tracer {
         self.inner.initial_sequence()
     }
 
+    /// The maximum sequence number of the tracer.
+    #[must_use]
+    pub fn max_sequence(&self) -> Sequence {
+        self.inner.max_sequence()
+    }
+
     /// The type of service of the tracer.
     #[must_use]
     pub fn tos(&self) -> TypeOfService {
         self.inner.tos()
     }
 
+    /// Whether the `Don't Fragment` bit is set on IPv4 probes.
+    #[must_use]
+    pub fn dont_fragment(&self) -> bool {
+        self.inner.dont_fragment()
+    }
+
     /// The ICMP extension parse mode of the tracer.
     #[must_use]
     pub fn icmp_extension_parse_mode(&self) -> IcmpExtensionParseMode {
         self.inner.icmp_extension_parse_mode()
     }
 
+    /// The IPv6 extension header of the tracer.
+    #[must_use]
+    pub fn ipv6_extension_header(&self) -> Ipv6ExtensionHeader {
+        self.inner.ipv6_extension_header()
+    }
+
     /// The read timeout of the tracer.
     #[must_use]
     pub fn read_timeout(&self) -> Duration {
@@ -361,6 +440,12 @@ impl Tracer {
         self.inner.tcp_connect_timeout()
     }
 
+    /// The TCP maximum segment size of the tracer.
+    #[must_use]
+    pub fn tcp_mss(&self) -> Option<u16> {
+        self.inner.tcp_mss()
+    }
+
     /// The trace identifier of the tracer.
     #[must_use]
     pub fn trace_identifier(&self) -> TraceId {
@@ -409,6 +494,24 @@ impl Tracer {
         self.inner.port_direction()
     }
 
+    /// The IPv6 flow label strategy of the tracer.
+    #[must_use]
+    pub fn flow_label_strategy(&self) -> FlowLabelStrategy {
+        self.inner.flow_label_strategy()
+    }
+
+    /// The number of flows probed per round by the tracer.
+    #[must_use]
+    pub fn flows_per_round(&self) -> u8 {
+        self.inner.flows_per_round()
+    }
+
+    /// The NAT64 translation prefixes of the tracer.
+    #[must_use]
+    pub fn nat64_prefixes(&self) -> &[Nat64Prefix] {
+        self.inner.nat64_prefixes()
+    }
+
     /// The minimum round duration of the tracer.
     #[must_use]
     pub fn min_round_duration(&self) -> Duration {
@@ -425,21 +528,26 @@ impl Tracer {
 mod inner {
     use crate::config::{ChannelConfig, StateConfig, StrategyConfig};
     use crate::error::Result;
+    use crate::net::capture::Capture;
     use crate::net::{PlatformImpl, SocketImpl};
     use crate::{
-        Channel, Error, IcmpExtensionParseMode, MaxInflight, MaxRounds, MultipathStrategy,
-        PacketSize, PayloadPattern, PortDirection, PrivilegeMode, Protocol, Round, Sequence,
-        SourceAddr, State, Strategy, TimeToLive, TraceId, TypeOfService,
+        Channel, Error, FlowLabelStrategy, IcmpExtensionParseMode, Ipv6ExtensionHeader,
+        MaxInflight, MaxRounds, MultipathStrategy, Nat64Prefix, PacketSize, PayloadPattern,
+        PortDirection, PrivilegeMode, Protocol, Round, Sequence, SourceAddr, State, Strategy,
+        TimeToLive, TraceId, TypeOfService,
     };
     use parking_lot::RwLock;
     use std::fmt::Debug;
     use std::net::IpAddr;
-    use std::sync::OnceLock;
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, OnceLock};
     use std::time::Duration;
     use tracing::instrument;
     use trippy_privilege::Privilege;
 
     #[derive(Debug)]
+    #[allow(clippy::struct_excessive_bools)]
     pub(super) struct TracerInner {
         source_addr: Option<IpAddr>,
         interface: Option<String>,
@@ -447,11 +555,15 @@ mod inner {
         privilege_mode: PrivilegeMode,
         protocol: Protocol,
         packet_size: PacketSize,
+        max_packet_size: PacketSize,
         payload_pattern: PayloadPattern,
         tos: TypeOfService,
+        dont_fragment: bool,
         icmp_extension_parse_mode: IcmpExtensionParseMode,
+        ipv6_extension_header: Ipv6ExtensionHeader,
         read_timeout: Duration,
         tcp_connect_timeout: Duration,
+        tcp_mss: Option<u16>,
         trace_identifier: TraceId,
         max_rounds: Option<MaxRounds>,
         first_ttl: TimeToLive,
@@ -459,19 +571,28 @@ mod inner {
         grace_duration: Duration,
         max_inflight: MaxInflight,
         initial_sequence: Sequence,
+        max_sequence: Sequence,
         multipath_strategy: MultipathStrategy,
         port_direction: PortDirection,
+        flow_label_strategy: FlowLabelStrategy,
+        flows_per_round: u8,
+        nat64_prefixes: Vec<Nat64Prefix>,
         min_round_duration: Duration,
         max_round_duration: Duration,
         max_samples: usize,
         max_flows: usize,
         drop_privileges: bool,
+        unprivileged_fallback: bool,
+        udp_recv_err: bool,
+        validate_icmp_checksum: bool,
+        capture_file: Option<PathBuf>,
+        invalid_checksums: Arc<AtomicUsize>,
         state: RwLock<State>,
         src: OnceLock<IpAddr>,
     }
 
     impl TracerInner {
-        #[allow(clippy::too_many_arguments)]
+        #[allow(clippy::too_many_arguments, clippy::fn_params_excessive_bools)]
         pub(super) fn new(
             interface: Option<String>,
             source_addr: Option<IpAddr>,
@@ -479,11 +600,15 @@ mod inner {
             privilege_mode: PrivilegeMode,
             protocol: Protocol,
             packet_size: PacketSize,
+            max_packet_size: PacketSize,
             payload_pattern: PayloadPattern,
             tos: TypeOfService,
+            dont_fragment: bool,
             icmp_extension_parse_mode: IcmpExtensionParseMode,
+            ipv6_extension_header: Ipv6ExtensionHeader,
             read_timeout: Duration,
             tcp_connect_timeout: Duration,
+            tcp_mss: Option<u16>,
             trace_identifier: TraceId,
             max_rounds: Option<MaxRounds>,
             first_ttl: TimeToLive,
@@ -491,13 +616,21 @@ mod inner {
             grace_duration: Duration,
             max_inflight: MaxInflight,
             initial_sequence: Sequence,
+            max_sequence: Sequence,
             multipath_strategy: MultipathStrategy,
             port_direction: PortDirection,
+            flow_label_strategy: FlowLabelStrategy,
+            flows_per_round: u8,
+            nat64_prefixes: Vec<Nat64Prefix>,
             min_round_duration: Duration,
             max_round_duration: Duration,
             max_samples: usize,
             max_flows: usize,
             drop_privileges: bool,
+            unprivileged_fallback: bool,
+            udp_recv_err: bool,
+            validate_icmp_checksum: bool,
+            capture_file: Option<PathBuf>,
         ) -> Self {
             Self {
                 source_addr,
@@ -506,11 +639,15 @@ mod inner {
                 privilege_mode,
                 protocol,
                 packet_size,
+                max_packet_size,
                 payload_pattern,
                 tos,
+                dont_fragment,
                 icmp_extension_parse_mode,
+                ipv6_extension_header,
                 read_timeout,
                 tcp_connect_timeout,
+                tcp_mss,
                 trace_identifier,
                 max_rounds,
                 first_ttl,
@@ -518,13 +655,22 @@ mod inner {
                 grace_duration,
                 max_inflight,
                 initial_sequence,
+                max_sequence,
                 multipath_strategy,
                 port_direction,
+                flow_label_strategy,
+                flows_per_round,
+                nat64_prefixes,
                 min_round_duration,
                 max_round_duration,
                 max_samples,
                 max_flows,
                 drop_privileges,
+                unprivileged_fallback,
+                udp_recv_err,
+                validate_icmp_checksum,
+                capture_file,
+                invalid_checksums: Arc::new(AtomicUsize::new(0)),
                 state: RwLock::new(State::new(Self::make_state_config(max_flows, max_samples))),
                 src: OnceLock::new(),
             }
@@ -563,6 +709,26 @@ mod inner {
             self.privilege_mode
         }
 
+        pub(super) const fn unprivileged_fallback(&self) -> bool {
+            self.unprivileged_fallback
+        }
+
+        pub(super) const fn udp_recv_err(&self) -> bool {
+            self.udp_recv_err
+        }
+
+        pub(super) const fn validate_icmp_checksum(&self) -> bool {
+            self.validate_icmp_checksum
+        }
+
+        pub(super) fn invalid_checksums(&self) -> usize {
+            self.invalid_checksums.load(Ordering::Relaxed)
+        }
+
+        pub(super) fn capture_file(&self) -> Option<&Path> {
+            self.capture_file.as_deref()
+        }
+
         pub(super) const fn protocol(&self) -> Protocol {
             self.protocol
         }
@@ -583,22 +749,38 @@ mod inner {
             self.packet_size
         }
 
-        pub(super) const fn payload_pattern(&self) -> PayloadPattern {
-            self.payload_pattern
+        pub(super) const fn max_packet_size(&self) -> PacketSize {
+            self.max_packet_size
+        }
+
+        pub(super) const fn payload_pattern(&self) -> &PayloadPattern {
+            &self.payload_pattern
         }
 
         pub(super) const fn initial_sequence(&self) -> Sequence {
             self.initial_sequence
         }
 
+        pub(super) const fn max_sequence(&self) -> Sequence {
+            self.max_sequence
+        }
+
         pub(super) const fn tos(&self) -> TypeOfService {
             self.tos
         }
 
+        pub(super) const fn dont_fragment(&self) -> bool {
+            self.dont_fragment
+        }
+
         pub(super) const fn icmp_extension_parse_mode(&self) -> IcmpExtensionParseMode {
             self.icmp_extension_parse_mode
         }
 
+        pub(super) const fn ipv6_extension_header(&self) -> Ipv6ExtensionHeader {
+            self.ipv6_extension_header
+        }
+
         pub(super) const fn read_timeout(&self) -> Duration {
             self.read_timeout
         }
@@ -607,6 +789,10 @@ mod inner {
             self.tcp_connect_timeout
         }
 
+        pub(super) const fn tcp_mss(&self) -> Option<u16> {
+            self.tcp_mss
+        }
+
         pub(super) const fn trace_identifier(&self) -> TraceId {
             self.trace_identifier
         }
@@ -639,6 +825,18 @@ mod inner {
             self.port_direction
         }
 
+        pub(super) const fn flow_label_strategy(&self) -> FlowLabelStrategy {
+            self.flow_label_strategy
+        }
+
+        pub(super) const fn flows_per_round(&self) -> u8 {
+            self.flows_per_round
+        }
+
+        pub(super) fn nat64_prefixes(&self) -> &[Nat64Prefix] {
+            &self.nat64_prefixes
+        }
+
         pub(super) const fn min_round_duration(&self) -> Duration {
             self.min_round_duration
         }
@@ -662,8 +860,17 @@ mod inner {
             self.src
                 .set(source_addr)
                 .map_err(|_| Error::Other(String::from("failed to set source_addr")))?;
-            let channel_config = self.make_channel_config(source_addr);
-            let channel = Channel::<SocketImpl>::connect(&channel_config)?;
+            let interface_index = self
+                .interface
+                .as_deref()
+                .map(SourceAddr::interface_index::<PlatformImpl>)
+                .transpose()?;
+            let channel_config = self.make_channel_config(source_addr, interface_index);
+            let mut channel = Channel::<SocketImpl>::connect(&channel_config)?
+                .with_invalid_checksums(Arc::clone(&self.invalid_checksums));
+            if let Some(path) = self.capture_file.as_deref() {
+                channel = channel.with_capture(Capture::create(path, source_addr)?);
+            }
             if self.drop_privileges {
                 Privilege::drop_privileges()?;
             }
@@ -692,23 +899,36 @@ mod inner {
             }
         }
 
-        const fn make_channel_config(&self, source_addr: IpAddr) -> ChannelConfig {
+        fn make_channel_config(
+            &self,
+            source_addr: IpAddr,
+            interface_index: Option<u32>,
+        ) -> ChannelConfig {
             ChannelConfig {
                 privilege_mode: self.privilege_mode,
+                unprivileged_fallback: self.unprivileged_fallback,
+                udp_recv_err: self.udp_recv_err,
+                validate_icmp_checksum: self.validate_icmp_checksum,
                 protocol: self.protocol,
                 source_addr,
                 target_addr: self.target_addr,
+                interface_index,
+                port_direction: self.port_direction,
                 packet_size: self.packet_size,
-                payload_pattern: self.payload_pattern,
+                max_packet_size: self.max_packet_size,
+                payload_pattern: self.payload_pattern.clone(),
                 initial_sequence: self.initial_sequence,
                 tos: self.tos,
+                dont_fragment: self.dont_fragment,
                 icmp_extension_parse_mode: self.icmp_extension_parse_mode,
+                ipv6_extension_header: self.ipv6_extension_header,
                 read_timeout: self.read_timeout,
                 tcp_connect_timeout: self.tcp_connect_timeout,
+                tcp_mss: self.tcp_mss,
             }
         }
 
-        const fn make_strategy_config(&self) -> StrategyConfig {
+        fn make_strategy_config(&self) -> StrategyConfig {
             StrategyConfig {
                 target_addr: self.target_addr,
                 protocol: self.protocol,
@@ -719,8 +939,12 @@ mod inner {
                 grace_duration: self.grace_duration,
                 max_inflight: self.max_inflight,
                 initial_sequence: self.initial_sequence,
+                max_sequence: self.max_sequence,
                 multipath_strategy: self.multipath_strategy,
                 port_direction: self.port_direction,
+                flow_label_strategy: self.flow_label_strategy,
+                flows_per_round: self.flows_per_round,
+                nat64_prefixes: self.nat64_prefixes.clone(),
                 min_round_duration: self.min_round_duration,
                 max_round_duration: self.max_round_duration,
             }
@@ -31,8 +31,25 @@ pub struct MaxInflight(pub u8);
 pub struct PacketSize(pub u16);
 
 /// `PayloadPattern` newtype.
-#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Ord, PartialOrd)]
-pub struct PayloadPattern(pub u8);
+///
+/// The bytes of the pattern are repeated to fill the payload, the last repetition is truncated if
+/// the payload is not a multiple of the length of the pattern.
+#[derive(Debug, Clone, PartialEq, Eq, Ord, PartialOrd)]
+pub struct PayloadPattern(pub Vec<u8>);
+
+impl PayloadPattern {
+    /// A pattern of a single repeated byte.
+    #[must_use]
+    pub fn byte(byte: u8) -> Self {
+        Self(vec![byte])
+    }
+}
+
+impl Default for PayloadPattern {
+    fn default() -> Self {
+        Self::byte(0)
+    }
+}
 
 /// `TypeOfService` (aka `DSCP` & `ECN`) newtype.
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Ord, PartialOrd)]
@@ -42,6 +59,16 @@ pub struct TypeOfService(pub u8);
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Ord, PartialOrd)]
 pub struct Port(pub u16);
 
+/// IPv6 `FlowLabel` newtype.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Ord, PartialOrd, Hash)]
+pub struct FlowLabel(pub u32);
+
+/// `FlowIndex` newtype.
+///
+/// The index of the flow of a probe within a round.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Ord, PartialOrd, Hash)]
+pub struct FlowIndex(pub u8);
+
 bitflags! {
     /// Probe flags.
     #[derive(Debug, Clone, PartialEq, Eq)]
@@ -0,0 +1,291 @@
+use crate::lazy_resolver::AsLookupSource;
+use crate::resolver::AsInfo;
+use serde_json::{json, Map, Value};
+use std::fs;
+use std::io;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The version of the cache file format.
+///
+/// A cache file of any other version is rejected.
+const VERSION: u64 = 1;
+
+/// The completed lookups persisted in a cache file.
+///
+/// A cache file is a JSON document which holds the `version` of the format and the `hostnames`
+/// and `asinfo` lookups, for example:
+///
+/// ```json
+/// {
+///   "version": 1,
+///   "hostnames": [
+///     {"addr": "192.0.2.1", "value": ["host.example"], "updated": 1700000000000, "expires": null}
+///   ],
+///   "asinfo": [
+///     {"addr": "192.0.2.1", "value": null, "updated": 1700000000000, "expires": null}
+///   ]
+/// }
+/// ```
+///
+/// Timestamps are given as milliseconds since the Unix epoch.
+#[derive(Debug, Clone, Default)]
+pub struct CacheFile {
+    /// The hostnames lookups, which were not found if there are no hostnames.
+    pub hostnames: Vec<Record<Vec<String>>>,
+    /// The `AsInfo` lookups, which were not found if there is no `AsInfo`.
+    pub asinfo: Vec<Record<Option<AsInfo>>>,
+}
+
+/// A lookup of an `IpAddr` which completed at `updated` and which expires at `expires`, if ever.
+#[derive(Debug, Clone)]
+pub struct Record<T> {
+    pub addr: IpAddr,
+    pub value: T,
+    pub updated: SystemTime,
+    pub expires: Option<SystemTime>,
+}
+
+impl CacheFile {
+    /// Read the cache file at `path`.
+    ///
+    /// A missing cache file is treated as an empty one, whereas a cache file which is corrupt or
+    /// of another version is an error.
+    pub fn read(path: &Path) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Self::parse(&contents).map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid cache file {}: {err}", path.display()),
+                )
+            }),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(io::Error::new(
+                err.kind(),
+                format!("failed to read cache file {}: {err}", path.display()),
+            )),
+        }
+    }
+
+    /// Write the cache file to `path`.
+    ///
+    /// The cache file is written to a temporary file alongside `path` which then replaces it,
+    /// such that a cache file is never left partially written.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let mut tmp = PathBuf::from(path).into_os_string();
+        tmp.push(".tmp");
+        fs::write(&tmp, self.to_json().to_string())?;
+        fs::rename(&tmp, path)
+    }
+
+    /// Parse the contents of a cache file.
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let json: Value = serde_json::from_str(contents).map_err(|err| err.to_string())?;
+        match json["version"].as_u64() {
+            Some(VERSION) => {}
+            Some(version) => return Err(format!("unsupported version {version}")),
+            None => return Err(String::from("missing version")),
+        }
+        Ok(Self {
+            hostnames: parse_records(&json["hostnames"], |value| {
+                value
+                    .as_array()?
+                    .iter()
+                    .map(|hostname| hostname.as_str().map(String::from))
+                    .collect()
+            })?,
+            asinfo: parse_records(&json["asinfo"], |value| match value {
+                Value::Null => Some(None),
+                value => parse_asinfo(value).map(Some),
+            })?,
+        })
+    }
+
+    fn to_json(&self) -> Value {
+        let hostnames = self
+            .hostnames
+            .iter()
+            .map(|record| record.to_json(&json!(record.value)))
+            .collect::<Vec<_>>();
+        let asinfo = self
+            .asinfo
+            .iter()
+            .map(|record| {
+                record.to_json(&record.value.as_ref().map_or(Value::Null, asinfo_to_json))
+            })
+            .collect::<Vec<_>>();
+        json!({
+            "version": VERSION,
+            "hostnames": hostnames,
+            "asinfo": asinfo,
+        })
+    }
+}
+
+impl<T> Record<T> {
+    fn to_json(&self, value: &Value) -> Value {
+        json!({
+            "addr": self.addr.to_string(),
+            "value": value,
+            "updated": to_millis(self.updated),
+            "expires": self.expires.map(to_millis),
+        })
+    }
+}
+
+/// Parse an array of records, the value of each of which is parsed by `parse_value`.
+fn parse_records<T>(
+    json: &Value,
+    parse_value: impl Fn(&Value) -> Option<T>,
+) -> Result<Vec<Record<T>>, String> {
+    json.as_array()
+        .ok_or_else(|| String::from("missing records"))?
+        .iter()
+        .map(|record| {
+            let addr = record["addr"]
+                .as_str()
+                .and_then(|addr| addr.parse().ok())
+                .ok_or_else(|| String::from("invalid addr"))?;
+            let value =
+                parse_value(&record["value"]).ok_or_else(|| format!("invalid value of {addr}"))?;
+            let updated = record["updated"]
+                .as_u64()
+                .map(from_millis)
+                .ok_or_else(|| format!("invalid updated of {addr}"))?;
+            let expires = match &record["expires"] {
+                Value::Null => None,
+                expires => Some(
+                    expires
+                        .as_u64()
+                        .map(from_millis)
+                        .ok_or_else(|| format!("invalid expires of {addr}"))?,
+                ),
+            };
+            Ok(Record {
+                addr,
+                value,
+                updated,
+                expires,
+            })
+        })
+        .collect()
+}
+
+fn parse_asinfo(json: &Value) -> Option<AsInfo> {
+    let field = |name: &str| json[name].as_str().map(String::from);
+    Some(AsInfo {
+        asn: field("asn")?,
+        prefix: field("prefix")?,
+        cc: field("cc")?,
+        registry: field("registry")?,
+        allocated: field("allocated")?,
+        name: field("name")?,
+        source: match json["source"].as_str() {
+            Some("dns") => Some(AsLookupSource::Dns),
+            Some("ripestat") => Some(AsLookupSource::RipeStat),
+            _ => None,
+        },
+    })
+}
+
+fn asinfo_to_json(asinfo: &AsInfo) -> Value {
+    let mut json = Map::new();
+    for (name, value) in [
+        ("asn", &asinfo.asn),
+        ("prefix", &asinfo.prefix),
+        ("cc", &asinfo.cc),
+        ("registry", &asinfo.registry),
+        ("allocated", &asinfo.allocated),
+        ("name", &asinfo.name),
+    ] {
+        json.insert(String::from(name), Value::from(value.as_str()));
+    }
+    let source = match asinfo.source {
+        Some(AsLookupSource::Dns) => Some("dns"),
+        Some(AsLookupSource::RipeStat) => Some("ripestat"),
+        None => None,
+    };
+    if let Some(source) = source {
+        json.insert(String::from("source"), Value::from(source));
+    }
+    Value::Object(json)
+}
+
+fn to_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map_or(0, |since| {
+        u64::try_from(since.as_millis()).unwrap_or(u64::MAX)
+    })
+}
+
+fn from_millis(millis: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(millis)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn test_round_trip() {
+        let addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+        let updated = from_millis(1_700_000_000_000);
+        let expires = Some(from_millis(1_700_000_300_000));
+        let cache_file = CacheFile {
+            hostnames: vec![Record {
+                addr,
+                value: vec![String::from("host.example")],
+                updated,
+                expires,
+            }],
+            asinfo: vec![Record {
+                addr,
+                value: Some(AsInfo {
+                    asn: String::from("64496"),
+                    prefix: String::from("192.0.2.0/24"),
+                    name: String::from("TEST-AS"),
+                    source: Some(AsLookupSource::RipeStat),
+                    ..AsInfo::default()
+                }),
+                updated,
+                expires: None,
+            }],
+        };
+        let parsed = CacheFile::parse(&cache_file.to_json().to_string()).unwrap();
+        assert_eq!(1, parsed.hostnames.len());
+        assert_eq!(addr, parsed.hostnames[0].addr);
+        assert_eq!(
+            vec![String::from("host.example")],
+            parsed.hostnames[0].value
+        );
+        assert_eq!(updated, parsed.hostnames[0].updated);
+        assert_eq!(expires, parsed.hostnames[0].expires);
+        let asinfo = parsed.asinfo[0].value.as_ref().unwrap();
+        assert_eq!("64496", asinfo.asn);
+        assert_eq!("192.0.2.0/24", asinfo.prefix);
+        assert_eq!("TEST-AS", asinfo.name);
+        assert_eq!(Some(AsLookupSource::RipeStat), asinfo.source);
+        assert!(parsed.asinfo[0].expires.is_none());
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(CacheFile::parse("not json").is_err());
+        assert!(CacheFile::parse(r#"{"hostnames": [], "asinfo": []}"#).is_err());
+        assert!(CacheFile::parse(r#"{"version": 2, "hostnames": [], "asinfo": []}"#).is_err());
+        assert!(
+            CacheFile::parse(r#"{"version": 1, "hostnames": [{"addr": "x"}], "asinfo": []}"#)
+                .is_err()
+        );
+        assert!(CacheFile::parse(r#"{"version": 1, "hostnames": [], "asinfo": []}"#).is_ok());
+    }
+
+    #[test]
+    fn test_read_missing() {
+        let path = std::env::temp_dir().join("trippy-test-missing-cache-file");
+        let cache_file = CacheFile::read(&path).unwrap();
+        assert!(cache_file.hostnames.is_empty());
+        assert!(cache_file.asinfo.is_empty());
+    }
+}
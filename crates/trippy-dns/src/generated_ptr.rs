@@ -0,0 +1,238 @@
+use crate::lazy_resolver::Config;
+use crate::resolver::{DnsEntry, Resolved, Unresolved};
+use regex::Regex;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// Labels typical of the hostnames of dynamically assigned addresses.
+const DYNAMIC_LABELS: [&str; 12] = [
+    "dynamic",
+    "dyn",
+    "dhcp",
+    "pool",
+    "dialup",
+    "ppp",
+    "pppoe",
+    "dsl",
+    "adsl",
+    "vdsl",
+    "broadband",
+    "residential",
+];
+
+/// The auto-generated reverse DNS hostnames to suppress.
+///
+/// A hostname is generated if it matches any of the configured patterns or, if there are none,
+/// if it embeds the address it was resolved from, i.e. `1-2-3-4.isp.example` for `1.2.3.4`, or if
+/// it has a label typical of dynamically assigned addresses, i.e. `dynamic` or `pool`, and a first
+/// label which contains a digit.
+#[derive(Debug, Clone)]
+pub struct GeneratedPtr {
+    patterns: Option<Vec<Regex>>,
+}
+
+impl GeneratedPtr {
+    /// The `GeneratedPtr` of a `Config`, if `suppress_generated_ptr` is set.
+    pub fn from_config(config: &Config) -> Option<Self> {
+        config.suppress_generated_ptr.then(|| Self {
+            patterns: config.generated_ptr_patterns.clone(),
+        })
+    }
+
+    /// Discard the generated hostnames of a resolved `DnsEntry`.
+    ///
+    /// A resolved entry with no hostnames remaining is `DnsEntry::NotFound`.
+    pub fn suppress(&self, dns_entry: DnsEntry) -> DnsEntry {
+        match dns_entry {
+            DnsEntry::Resolved(Resolved::Normal(addr, hostnames)) => {
+                let hostnames = hostnames
+                    .into_iter()
+                    .filter(|hostname| !self.is_generated(addr, hostname))
+                    .collect::<Vec<_>>();
+                if hostnames.is_empty() {
+                    DnsEntry::NotFound(Unresolved::Normal(addr))
+                } else {
+                    DnsEntry::Resolved(Resolved::Normal(addr, hostnames))
+                }
+            }
+            dns_entry => dns_entry,
+        }
+    }
+
+    /// Is `hostname`, resolved from `addr`, generated?
+    pub fn is_generated(&self, addr: IpAddr, hostname: &str) -> bool {
+        if let Some(patterns) = &self.patterns {
+            patterns.iter().any(|pattern| pattern.is_match(hostname))
+        } else {
+            let hostname = hostname.to_ascii_lowercase();
+            let embeds_addr = match addr {
+                IpAddr::V4(addr) => embeds_ipv4(addr, &hostname),
+                IpAddr::V6(addr) => embeds_ipv6(addr, &hostname),
+            };
+            embeds_addr || has_dynamic_label(&hostname)
+        }
+    }
+}
+
+/// Does `hostname` embed the IPv4 `addr`?
+///
+/// The octets may be given in either order, separated by any non-digit, or as a single number of
+/// zero padded octets, or in hex.
+fn embeds_ipv4(addr: Ipv4Addr, hostname: &str) -> bool {
+    let forward = addr.octets().map(|octet| Some(u64::from(octet)));
+    let mut reverse = forward;
+    reverse.reverse();
+    let numbers = hostname
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|number| !number.is_empty())
+        .map(|number| number.parse::<u64>().ok())
+        .collect::<Vec<_>>();
+    let [a, b, c, d] = addr.octets();
+    numbers
+        .windows(4)
+        .any(|window| window == forward || window == reverse)
+        || hostname.contains(&format!("{a:03}{b:03}{c:03}{d:03}"))
+        || hostname.contains(&format!("{:08x}", u32::from(addr)))
+}
+
+/// Does `hostname` embed the IPv6 `addr`?
+///
+/// The groups may be given separated by any non-hex digit, or in their compressed form with `-`
+/// in place of `:`, or as a single number of 32 hex digits.
+fn embeds_ipv6(addr: Ipv6Addr, hostname: &str) -> bool {
+    let segments = addr.segments().map(Some);
+    let groups = hostname
+        .split(|c: char| !c.is_ascii_hexdigit())
+        .filter(|group| !group.is_empty())
+        .map(|group| u16::from_str_radix(group, 16).ok())
+        .collect::<Vec<_>>();
+    let compressed = addr.to_string().replace(':', "-");
+    let nibbles = format!("{:032x}", u128::from(addr));
+    groups.windows(8).any(|window| window == segments)
+        || (compressed.len() >= 8 && hostname.contains(&compressed))
+        || hostname.contains(&nibbles)
+}
+
+/// Does `hostname` have a label typical of a dynamically assigned address?
+fn has_dynamic_label(hostname: &str) -> bool {
+    let first_has_digit = hostname
+        .split('.')
+        .next()
+        .is_some_and(|label| label.chars().any(|c| c.is_ascii_digit()));
+    first_has_digit
+        && hostname
+            .split(['.', '-'])
+            .any(|label| DYNAMIC_LABELS.contains(&label))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn heuristics() -> GeneratedPtr {
+        GeneratedPtr { patterns: None }
+    }
+
+    #[test]
+    fn test_generated_ipv4() {
+        let addr = IpAddr::from([81, 2, 69, 142]);
+        for hostname in [
+            "81-2-69-142.dynamic.isp.example",
+            "host81-2-69-142.range81-2.btcentralplus.example",
+            "142.69.2.81.static.isp.example",
+            "c-81-2-69-142.hsd1.ca.comcast.example",
+            "ip081002069142.isp.example",
+            "0x5102458e.isp.example",
+            "5102458E.ISP.EXAMPLE",
+            "customer-5a.pool.isp.example",
+            "ppp-1.isp.example",
+        ] {
+            assert!(heuristics().is_generated(addr, hostname), "{hostname}");
+        }
+    }
+
+    #[test]
+    fn test_not_generated_ipv4() {
+        let addr = IpAddr::from([81, 2, 69, 142]);
+        for hostname in [
+            "ae-1-3502.edge2.london.level3.example",
+            "81-2-69-143.isp.example",
+            "2-69-142.isp.example",
+            "router.pool.isp.example",
+            "dns.google",
+            "lon-core-1.isp.example",
+        ] {
+            assert!(!heuristics().is_generated(addr, hostname), "{hostname}");
+        }
+    }
+
+    #[test]
+    fn test_generated_ipv6() {
+        let addr = IpAddr::from_str("2001:db8:1234:5678::1").unwrap();
+        for hostname in [
+            "2001-db8-1234-5678-0-0-0-1.isp.example",
+            "2001-0db8-1234-5678-0000-0000-0000-0001.isp.example",
+            "2001-db8-1234-5678--1.isp.example",
+            "20010db8123456780000000000000001.isp.example",
+        ] {
+            assert!(heuristics().is_generated(addr, hostname), "{hostname}");
+        }
+    }
+
+    #[test]
+    fn test_not_generated_ipv6() {
+        let addr = IpAddr::from_str("2001:db8:1234:5678::1").unwrap();
+        for hostname in [
+            "xe-0-0-1.core1.isp.example",
+            "2001-db8-1234-5678--2.isp.example",
+            "lo0.router.isp.example",
+        ] {
+            assert!(!heuristics().is_generated(addr, hostname), "{hostname}");
+        }
+        let addr = IpAddr::from_str("::1").unwrap();
+        assert!(!heuristics().is_generated(addr, "xn--1ca.example"));
+    }
+
+    #[test]
+    fn test_patterns() {
+        let generated = GeneratedPtr {
+            patterns: Some(vec![Regex::new(r"^[a-z]+\d*\.cust\.isp\.example$").unwrap()]),
+        };
+        let addr = IpAddr::from([81, 2, 69, 142]);
+        assert!(generated.is_generated(addr, "abc.cust.isp.example"));
+        assert!(!generated.is_generated(addr, "81-2-69-142.dynamic.isp.example"));
+    }
+
+    #[test]
+    fn test_suppress() {
+        let addr = IpAddr::from([81, 2, 69, 142]);
+        let entry = DnsEntry::Resolved(Resolved::Normal(
+            addr,
+            vec![
+                String::from("81-2-69-142.dynamic.isp.example"),
+                String::from("mail.example"),
+            ],
+        ));
+        assert!(matches!(
+            heuristics().suppress(entry),
+            DnsEntry::Resolved(Resolved::Normal(_, hostnames)) if hostnames == ["mail.example"]
+        ));
+        let entry = DnsEntry::Resolved(Resolved::Normal(
+            addr,
+            vec![String::from("81-2-69-142.dynamic.isp.example")],
+        ));
+        assert!(matches!(
+            heuristics().suppress(entry),
+            DnsEntry::NotFound(Unresolved::Normal(a)) if a == addr
+        ));
+        let entry = DnsEntry::Timeout(addr);
+        assert!(matches!(heuristics().suppress(entry), DnsEntry::Timeout(_)));
+    }
+
+    #[test]
+    fn test_from_config() {
+        assert!(GeneratedPtr::from_config(&Config::default()).is_none());
+        let config = Config::builder().suppress_generated_ptr(true).build();
+        assert!(GeneratedPtr::from_config(&config).is_some_and(|g| g.patterns.is_none()));
+    }
+}
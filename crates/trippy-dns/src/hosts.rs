@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::net::IpAddr;
+use std::path::Path;
+
+/// The platform hosts file.
+#[cfg(not(windows))]
+pub const DEFAULT_HOSTS_FILE: &str = "/etc/hosts";
+
+/// The platform hosts file.
+#[cfg(windows)]
+pub const DEFAULT_HOSTS_FILE: &str = r"C:\Windows\System32\drivers\etc\hosts";
+
+/// The static mappings of a hosts file.
+///
+/// Each line of a hosts file maps an `IpAddr` to a canonical hostname followed by any number of
+/// aliases, for example `192.0.2.1 host.example host`.  Hostnames are matched case-insensitively
+/// and without regard to any trailing dot.
+#[derive(Debug, Clone, Default)]
+pub struct Hosts {
+    /// The addresses of each (lowercase) hostname, in the order they appear.
+    by_name: HashMap<String, Vec<IpAddr>>,
+    /// The hostnames of each address, canonical hostname first, in the order they appear.
+    by_addr: HashMap<IpAddr, Vec<String>>,
+}
+
+impl Hosts {
+    /// Read the hosts file at `path`.
+    ///
+    /// If `required` is false then a missing hosts file is treated as an empty one.
+    pub fn read(path: &Path, required: bool) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Ok(Self::parse(&contents)),
+            Err(err) if !required && err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(io::Error::new(
+                err.kind(),
+                format!("failed to read hosts file {}: {err}", path.display()),
+            )),
+        }
+    }
+
+    /// Parse the contents of a hosts file.
+    ///
+    /// Comments and any line which does not start with a valid `IpAddr` are ignored.
+    pub fn parse(contents: &str) -> Self {
+        let mut hosts = Self::default();
+        for line in contents.lines() {
+            let line = line.split_once('#').map_or(line, |(line, _)| line);
+            let mut fields = line.split_whitespace();
+            let Some(addr) = fields.next().and_then(|addr| addr.parse::<IpAddr>().ok()) else {
+                continue;
+            };
+            for hostname in fields.map(|hostname| hostname.trim_end_matches('.')) {
+                let addrs = hosts
+                    .by_name
+                    .entry(hostname.to_ascii_lowercase())
+                    .or_default();
+                if !addrs.contains(&addr) {
+                    addrs.push(addr);
+                }
+                let hostnames = hosts.by_addr.entry(addr).or_default();
+                if !hostnames.iter().any(|h| h.eq_ignore_ascii_case(hostname)) {
+                    hostnames.push(hostname.to_string());
+                }
+            }
+        }
+        hosts
+    }
+
+    /// The addresses of a hostname, if it has any.
+    pub fn lookup(&self, hostname: &str) -> Option<&[IpAddr]> {
+        self.by_name
+            .get(&hostname.trim_end_matches('.').to_ascii_lowercase())
+            .map(Vec::as_slice)
+    }
+
+    /// The hostnames of an address, if it has any.
+    pub fn reverse_lookup(&self, addr: IpAddr) -> Option<&[String]> {
+        self.by_addr.get(&addr).map(Vec::as_slice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    const HOSTS: &str = "\
+# a comment
+127.0.0.1       localhost
+192.0.2.1       host.example   host    # trailing comment
+192.0.2.2       Other.Example.
+2001:db8::1     host.example
+192.0.2.1       alias.example
+fe80::1%lo0     link.example
+not-an-addr     bogus.example
+192.0.2.3
+";
+
+    #[test]
+    fn test_lookup() {
+        let hosts = Hosts::parse(HOSTS);
+        let v4 = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+        let v6 = IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1));
+        assert_eq!(Some(&[v4, v6][..]), hosts.lookup("host.example"));
+        assert_eq!(Some(&[v4, v6][..]), hosts.lookup("HOST.example."));
+        assert_eq!(Some(&[v4][..]), hosts.lookup("host"));
+        assert_eq!(
+            Some(&[IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2))][..]),
+            hosts.lookup("other.example")
+        );
+        assert_eq!(None, hosts.lookup("link.example"));
+        assert_eq!(None, hosts.lookup("bogus.example"));
+        assert_eq!(None, hosts.lookup("missing.example"));
+    }
+
+    #[test]
+    fn test_reverse_lookup() {
+        let hosts = Hosts::parse(HOSTS);
+        assert_eq!(
+            Some(
+                &[
+                    String::from("host.example"),
+                    String::from("host"),
+                    String::from("alias.example")
+                ][..]
+            ),
+            hosts.reverse_lookup(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)))
+        );
+        assert_eq!(
+            Some(&[String::from("Other.Example")][..]),
+            hosts.reverse_lookup(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2)))
+        );
+        assert_eq!(
+            None,
+            hosts.reverse_lookup(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 3)))
+        );
+    }
+
+    #[test]
+    fn test_read_missing() {
+        let path = Path::new("/nonexistent/trippy/hosts");
+        assert!(Hosts::read(path, false)
+            .unwrap()
+            .lookup("localhost")
+            .is_none());
+        assert_eq!(
+            io::ErrorKind::NotFound,
+            Hosts::read(path, true).unwrap_err().kind()
+        );
+    }
+}
@@ -1,27 +1,357 @@
-use crate::resolver::{DnsEntry, ResolvedIpAddrs, Resolver, Result};
-use std::fmt::{Display, Formatter};
-use std::net::IpAddr;
-use std::rc::Rc;
-use std::time::Duration;
+use crate::resolver::{AsInfoEntry, DnsEntry, DnssecStatus, ResolvedIpAddrs, Resolver, Result};
+use crate::socks::ProxyConfig;
+use crossbeam::channel::Receiver;
+use ipnetwork::IpNetwork;
+use rand::Rng;
+use regex::Regex;
+use std::fmt::{Debug, Display, Formatter};
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::runtime::Handle;
+
+/// The default maximum number of lazy lookups which may be in-flight concurrently.
+const DEFAULT_MAX_INFLIGHT: usize = 32;
+
+/// The default time for which resolved `AS` information is cached.
+const DEFAULT_ASINFO_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The base zone of the Team Cymru IP to ASN mapping DNS service.
+const DEFAULT_AS_LOOKUP_ZONE: &str = "asn.cymru.com";
 
 /// Configuration for the `DnsResolver`.
-#[derive(Debug, Copy, Clone)]
+///
+/// As a defence against cache poisoning, every query sent with a `ResolveMethod` other than
+/// `ResolveMethod::System` has a random transaction id and, when sent over UDP, is sent from a
+/// random source port from the IANA dynamic port range (`49152..=65535`), including when
+/// `bind_addr` is set.  Both are chosen afresh for each query by the underlying resolver.  The
+/// transaction id cannot be made deterministic, the source port can be fixed with `source_port`.
+/// The behaviour of `ResolveMethod::System` is determined by the OS.
+#[derive(Clone)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct Config {
     /// The method to use for DNS resolution.
     pub resolve_method: ResolveMethod,
     /// The IP address resolution family.
     pub addr_family: IpAddrFamily,
     /// The timeout for DNS resolution.
+    ///
+    /// This is used as the default for any of `connect_timeout`, `query_timeout` and
+    /// `total_timeout` which are not set.  None of these timeouts apply to `ResolveMethod::System`.
     pub timeout: Duration,
+    /// The timeout for establishing a TCP connection to a name server.
+    ///
+    /// Defaults to `timeout` if not set.  Note that a connection attempt is also bounded by the
+    /// `query_timeout`.
+    pub connect_timeout: Option<Duration>,
+    /// The timeout for a single query round trip to a name server.
+    ///
+    /// Defaults to `timeout` if not set.
+    pub query_timeout: Option<Duration>,
+    /// The timeout for a lookup as a whole, including all retries and fallbacks.
+    ///
+    /// This bounds the combined attempt of a query sent over UDP and, if the response was
+    /// truncated, retried over TCP.  Defaults to twice `timeout` if not set, allowing for a
+    /// single retry or fallback.
+    pub total_timeout: Option<Duration>,
+    /// The local address to bind DNS queries to.
+    ///
+    /// If set, all DNS queries will originate from this address and only name servers of the
+    /// same address family are queried.  This is not supported for `ResolveMethod::System`.
+    pub bind_addr: Option<IpAddr>,
+    /// The fixed local port to send DNS queries from.
+    ///
+    /// This is intended only for tests which need deterministic source ports and should not
+    /// otherwise be set, as a random source port is a defence against cache poisoning.  As
+    /// concurrent queries cannot share a port this requires `max_inflight` to be 1.  This only
+    /// applies to queries sent over UDP.  Defaults to a random port per query.  This is not
+    /// supported for `ResolveMethod::System`.
+    pub source_port: Option<u16>,
+    /// How to behave if no upstream DNS server is reachable at startup.
+    pub startup_policy: StartupPolicy,
+    /// The sources of `AsInfo` lookups, in order of preference.
+    ///
+    /// Each source is tried in turn until one supplies the `AsInfo` of an address with at least
+    /// one AS number.  Sources which are not supported for the `resolve_method` are skipped.
+    /// Defaults to `[AsLookupSource::Dns]` if not set.
+    pub as_lookup_sources: Option<Vec<AsLookupSource>>,
+    /// The base zone of the Team Cymru compatible service queried by `AsLookupSource::Dns`.
+    ///
+    /// The `origin` queries are made against the `origin` and `origin6` subdomains of this zone
+    /// and the `asn` queries against the zone itself, i.e. `1.2.0.192.origin.asn.cymru.com` and
+    /// `AS64496.asn.cymru.com` for the default zone.  This allows a private mirror of the service
+    /// to be used.  Defaults to `asn.cymru.com` if not set.
+    pub as_lookup_zone: Option<String>,
+    /// An observer of the lifecycle of lazy reverse DNS lookups.
+    pub observer: Option<Arc<dyn DnsObserver>>,
+    /// The maximum number of lazy lookups which may be in-flight concurrently.
+    ///
+    /// Any further lazy lookups wait in the queue, and are `DnsEntry::Pending`, until an in-flight
+    /// lookup completes.  The queries of a lookup are sent one at a time and so this also bounds
+    /// the number of outstanding queries to the name servers.  Defaults to 32, a value of 0 is
+    /// treated as 1.
+    pub max_inflight: usize,
+    /// The maximum number of lazy lookups which may be started per second.
+    ///
+    /// Lookups are spaced evenly, without any initial burst, and any further lazy lookups wait,
+    /// and are `DnsEntry::Pending`, until they may start.  The number of lookups which are waiting
+    /// is available via `CacheStats::throttled`.  Note that this limits lookups rather than
+    /// queries, and that an `AS` information lookup may send more than one query.  Lookups which
+    /// are served from the hosts file, or from a cached prefix, are not limited.  Defaults to no
+    /// limit, a value of 0 is treated as 1.
+    pub max_lookups_per_sec: Option<u32>,
+    /// The maximum number of entries in each of the hostname and `AS` information caches.
+    ///
+    /// Once a cache is full the completed entry which was set least recently is evicted to make
+    /// room for a new entry.  Pending entries are never evicted and so a cache may exceed this
+    /// limit whilst lookups are in-flight.  Defaults to no limit.
+    pub max_cache_entries: Option<usize>,
+    /// The hosts file to consult before querying any name server.
+    ///
+    /// Static mappings in the hosts file take precedence for both forward and reverse lookups
+    /// with `ResolveMethod::System` and `ResolveMethod::Resolv`, and are not used with the other
+    /// methods.  Defaults to the platform hosts file (i.e. `/etc/hosts`), which is ignored if it
+    /// does not exist, whereas a hosts file which is set must exist.  Not used unless
+    /// `use_hosts_file` is set.
+    pub hosts_file: Option<PathBuf>,
+    /// Whether to consult the hosts file before querying any name server.
+    ///
+    /// If set then a forward or reverse lookup which is mapped by the hosts file resolves
+    /// immediately, without any query being made.  The hosts file is read once when the
+    /// resolver is started, or reconfigured, and so later changes to it are not observed.  Note
+    /// that the platform resolver used by `ResolveMethod::System` may consult the platform hosts
+    /// file regardless.  Defaults to true.
+    pub use_hosts_file: bool,
+    /// The bounds to which the TTL of a reverse DNS response is clamped.
+    ///
+    /// A resolved hostname entry expires once the TTL of its response, clamped to these bounds,
+    /// has elapsed, after which the next lazy lookup serves the stale entry whilst it is
+    /// refreshed in the background.  If not set then entries never expire.  Entries resolved via
+    /// `ResolveMethod::System`, for which no TTL is available, and entries which were not
+    /// resolved never expire.  `AS` information expires as per `asinfo_ttl` instead.  Defaults to
+    /// `TtlBounds::default()`.
+    pub ttl_bounds: Option<TtlBounds>,
+    /// The time for which resolved `AS` information is cached.
+    ///
+    /// `AS` information is cached, and expires, independently of the hostnames of an `IpAddr`,
+    /// such that refreshing either does not refresh the other.  Once expired, the next lazy
+    /// lookup serves the stale `AS` information whilst it is refreshed in the background.  The
+    /// announced prefix of resolved `AS` information, which is reused for all addresses within
+    /// it, expires alike.  If not set then `AS` information never expires.  Defaults to 24 hours.
+    pub asinfo_ttl: Option<Duration>,
+    /// The policy for retrying lookups which failed.
+    ///
+    /// Defaults to `RetryPolicy::default()`, which has no jitter.
+    pub retry_policy: RetryPolicy,
+    /// The file in which to persist the cache between runs.
+    ///
+    /// If set, the completed entries of the cache which have not expired are loaded from this
+    /// file when the resolver is started and the completed entries of the cache are written to
+    /// it when the resolver is shut down.  Only entries which were resolved, or which did not
+    /// match any records, are persisted.  A cache file which is missing is ignored, as is one
+    /// which is corrupt or of another version, with a warning.  The cache file is not used if
+    /// `caching` is disabled.  Defaults to no cache file.
+    pub cache_file: Option<PathBuf>,
+    /// The UDP payload size to advertise via EDNS0.
+    ///
+    /// If set, an EDNS0 `OPT` record advertising this UDP payload size is added to all queries.
+    /// The size is clamped to between 512 and 1232 bytes, the largest size which avoids IP
+    /// fragmentation on almost all paths.  A response which is truncated regardless is retried
+    /// over TCP.  Defaults to not using EDNS0.  This is not supported for `ResolveMethod::System`.
+    pub edns_udp_payload_size: Option<u16>,
+    /// Whether to request and report the DNSSEC validation of reverse DNS lookups.
+    ///
+    /// If set, reverse DNS queries are sent with the DNSSEC OK (`DO`) bit set and the upstream
+    /// resolver, which must be a validating resolver, is trusted to validate the responses.  A
+    /// response which fails validation is reported as `DnsEntry::Bogus` and the outcome of the
+    /// validation of a cached entry is available via `DnsEntryMeta::dnssec`.  Defaults to false.
+    /// This is not supported for `ResolveMethod::System`.
+    pub validate_dnssec: bool,
+    /// Whether to cache the results of lazy reverse DNS lookups.
+    ///
+    /// If not set then lazy lookups are still performed in the background, and a lazy lookup of
+    /// an `IpAddr` which is pending is not enqueued again, but the result of a lookup is only
+    /// served once, after which it is discarded such that the next lazy lookup of that `IpAddr`
+    /// is performed again.  Defaults to true.
+    pub caching: bool,
+    /// Whether to trim the trailing dot of resolved hostnames.
+    ///
+    /// If set then resolved hostnames are given without a trailing dot, i.e. `host.example.com`,
+    /// otherwise they are given in their canonical, fully qualified, form with a trailing dot,
+    /// i.e. `host.example.com.`, regardless of how they were resolved.  Defaults to true.
+    pub trim_trailing_dot: bool,
+    /// Whether to decode internationalized domain names (IDN) in resolved hostnames.
+    ///
+    /// If set then resolved hostnames with punycode labels, i.e. `xn--bcher-kva.example`, are
+    /// decoded to Unicode, i.e. `bücher.example`.  A hostname which cannot be decoded is left as
+    /// is.  The raw hostnames of a cached entry are available via `DnsResolver::raw_hostnames`.
+    /// Defaults to false.
+    pub decode_idn: bool,
+    /// Whether to suppress auto-generated reverse DNS hostnames.
+    ///
+    /// If set then a resolved hostname which appears to have been generated from its address,
+    /// i.e. `1-2-3-4.dynamic.isp.example` for `1.2.3.4`, is discarded, and a reverse lookup with
+    /// no other hostnames is `DnsEntry::NotFound`.  Hostnames from the hosts file are never
+    /// discarded.  Defaults to false.
+    pub suppress_generated_ptr: bool,
+    /// The patterns which match auto-generated reverse DNS hostnames.
+    ///
+    /// If set then a hostname is considered generated if it matches any of these patterns,
+    /// otherwise it is considered generated if it embeds the octets, or groups, of its address,
+    /// or has a label typical of a dynamically assigned address, i.e. `dynamic` or `pool`, and a
+    /// first label which contains a digit.  Not used unless `suppress_generated_ptr` is set.
+    /// Defaults to not set.
+    pub generated_ptr_patterns: Option<Vec<Regex>>,
+    /// Whether to send all queries over TCP.
+    ///
+    /// If set then all queries are sent over TCP, without first being sent over UDP.  Otherwise
+    /// queries are sent over UDP and any response which is truncated is retried over TCP.
+    /// Defaults to false.  This is not supported for `ResolveMethod::System`.
+    pub force_tcp: bool,
+    /// The SOCKS5 proxy through which to connect to name servers.
+    ///
+    /// If set, all connections to name servers are made via the proxy.  Queries cannot be sent
+    /// over UDP via the proxy and so `force_tcp` must also be set.  Lookups of `AS` information
+    /// via `AsLookupSource::RipeStat` do not use the proxy.  Defaults to no proxy.  This is not
+    /// supported for `ResolveMethod::System`.
+    pub proxy: Option<ProxyConfig>,
+}
+
+impl Debug for Config {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Config")
+            .field("resolve_method", &self.resolve_method)
+            .field("addr_family", &self.addr_family)
+            .field("timeout", &self.timeout)
+            .field("connect_timeout", &self.connect_timeout)
+            .field("query_timeout", &self.query_timeout)
+            .field("total_timeout", &self.total_timeout)
+            .field("bind_addr", &self.bind_addr)
+            .field("source_port", &self.source_port)
+            .field("startup_policy", &self.startup_policy)
+            .field("as_lookup_sources", &self.as_lookup_sources)
+            .field("as_lookup_zone", &self.as_lookup_zone)
+            .field("observer", &self.observer.is_some())
+            .field("max_inflight", &self.max_inflight)
+            .field("max_lookups_per_sec", &self.max_lookups_per_sec)
+            .field("max_cache_entries", &self.max_cache_entries)
+            .field("hosts_file", &self.hosts_file)
+            .field("use_hosts_file", &self.use_hosts_file)
+            .field("ttl_bounds", &self.ttl_bounds)
+            .field("asinfo_ttl", &self.asinfo_ttl)
+            .field("retry_policy", &self.retry_policy)
+            .field("cache_file", &self.cache_file)
+            .field("edns_udp_payload_size", &self.edns_udp_payload_size)
+            .field("validate_dnssec", &self.validate_dnssec)
+            .field("caching", &self.caching)
+            .field("trim_trailing_dot", &self.trim_trailing_dot)
+            .field("decode_idn", &self.decode_idn)
+            .field("suppress_generated_ptr", &self.suppress_generated_ptr)
+            .field("generated_ptr_patterns", &self.generated_ptr_patterns)
+            .field("force_tcp", &self.force_tcp)
+            .field("proxy", &self.proxy)
+            .finish()
+    }
 }
 
 impl Default for Config {
     fn default() -> Self {
-        Self {
-            resolve_method: ResolveMethod::System,
-            addr_family: IpAddrFamily::Ipv4thenIpv6,
-            timeout: Duration::from_millis(5000),
-        }
+        Self::new(
+            ResolveMethod::System,
+            IpAddrFamily::Ipv4thenIpv6,
+            Duration::from_millis(5000),
+        )
+    }
+}
+
+/// The bounds to which the TTL of a DNS response is clamped.
+///
+/// The bounds guard against responses with a TTL of zero, which would otherwise be refreshed on
+/// every lazy lookup, and responses with a TTL of several weeks.  The default bounds are 30
+/// seconds and 1 day.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct TtlBounds {
+    /// The minimum TTL.
+    pub min: Duration,
+    /// The maximum TTL.
+    ///
+    /// A maximum which is less than the `min` is treated as the `min`.
+    pub max: Duration,
+}
+
+impl TtlBounds {
+    const DEFAULT: Self = Self {
+        min: Duration::from_secs(30),
+        max: Duration::from_secs(86400),
+    };
+
+    /// Clamp a `ttl` to these bounds.
+    #[must_use]
+    pub fn clamp(&self, ttl: Duration) -> Duration {
+        ttl.clamp(self.min, self.max.max(self.min))
+    }
+}
+
+impl Default for TtlBounds {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// The policy for retrying lookups which failed.
+///
+/// A stale entry whose refresh failed is retained and expires again after a delay, the minimum
+/// TTL for hostnames and the `AS` information TTL for `AS` information, such that it is retried by
+/// the next lazy lookup thereafter.  Many lookups which failed together, such as when a name
+/// server was unreachable, would otherwise all be retried at once.
+///
+/// The default policy has no jitter.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct RetryPolicy {
+    /// The maximum random delay added to the delay before each retry.
+    pub jitter: Duration,
+}
+
+impl RetryPolicy {
+    const DEFAULT: Self = Self {
+        jitter: Duration::ZERO,
+    };
+
+    /// The delay before a retry, the `delay` plus a random delay of up to `jitter`.
+    #[must_use]
+    pub fn delay(&self, delay: Duration) -> Duration {
+        delay + rand::thread_rng().gen_range(Duration::ZERO..=self.jitter)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// An observer of the lifecycle of the lazy reverse DNS lookups performed by a `DnsResolver`.
+///
+/// This can be used to collect metrics, such as the latency and failure rate of lookups.
+///
+/// The callbacks are invoked synchronously, `on_enqueue` from the thread performing the lazy
+/// lookup and `on_complete` from the background resolution thread, and so must not block;
+/// any long running work should be offloaded, for example by sending to a channel.
+///
+/// Lookups of `AsInfo` are not observed.
+pub trait DnsObserver: Send + Sync {
+    /// A reverse DNS lookup of `addr` has been enqueued for resolution in the background.
+    fn on_enqueue(&self, addr: IpAddr) {
+        let _ = addr;
+    }
+
+    /// A reverse DNS lookup of `addr` has completed with `entry` having taken `elapsed`.
+    ///
+    /// The `elapsed` duration is measured from the start of the resolution and so excludes the
+    /// time spent waiting in the queue.
+    fn on_complete(&self, addr: IpAddr, entry: &DnsEntry, elapsed: Duration) {
+        let _ = (addr, entry, elapsed);
     }
 }
 
@@ -38,16 +368,69 @@ pub enum ResolveMethod {
     Cloudflare,
 }
 
+/// The source of `AsInfo` lookups.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum AsLookupSource {
+    /// Lookup via the Team Cymru IP to ASN mapping DNS service.
+    ///
+    /// This is not supported for `ResolveMethod::System`.
+    #[default]
+    Dns,
+    /// Lookup via the `RIPEstat` prefix overview HTTP API.
+    ///
+    /// This is supported for all `ResolveMethod`, is bounded by the `timeout` and does not honor
+    /// the `bind_addr`.
+    RipeStat,
+}
+
+/// How the resolver behaves if no upstream DNS server is reachable at startup.
+///
+/// The default is `StartupPolicy::Lenient`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum StartupPolicy {
+    /// Fail to start if no upstream DNS server is reachable.
+    ///
+    /// This is not supported for `ResolveMethod::System`, for which no check is performed.
+    Strict,
+    /// Start regardless of whether any upstream DNS server is reachable.
+    ///
+    /// Lookups will fail (or be retried, for lazy lookups which time out) until connectivity
+    /// returns.
+    #[default]
+    Lenient,
+}
+
 /// How to resolve IP addresses.
+///
+/// For the fallback orderings with `ResolveMethod::System` the preferred family is queried first
+/// and the other family is only queried if the preferred family has no addresses.
+///
+/// For the fallback orderings with all other `ResolveMethod` both families are queried
+/// concurrently, in the style of "happy eyeballs" (RFC 8305).  The addresses of the preferred
+/// family are returned as soon as they arrive.  If the other family answers first then the
+/// preferred family is given a short resolution delay to answer, after which the addresses of the
+/// other family are returned.  The query which does not supply the result is not aborted but is
+/// left to complete in the background, such that its response is cached by the underlying
+/// resolver.  There is therefore a transient window, until the response of the preferred family
+/// is cached, during which a lookup may return the addresses of the other family even though the
+/// preferred family has addresses; subsequent lookups return the addresses of the preferred family.
+///
+/// Forward lookups are not cached by the `DnsResolver`.  For all `ResolveMethod` other than
+/// `ResolveMethod::System` the responses for each family are cached independently by the
+/// underlying resolver, including an empty response which is cached for as long as the negative
+/// caching TTL of the zone allows.  Whilst an empty response for the preferred family is cached,
+/// subsequent lookups return the addresses of the other family, and so an address of the preferred
+/// family which is published in the meantime is not returned until that cached response expires.
+/// The caching of `ResolveMethod::System` is determined by the OS.
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum IpAddrFamily {
     /// Lookup Ipv4 only.
     Ipv4Only,
     /// Lookup Ipv6 only.
     Ipv6Only,
-    /// Lookup Ipv6 with a fallback to Ipv4
+    /// Lookup Ipv6 with a fallback to Ipv4.
     Ipv6thenIpv4,
-    /// Lookup Ipv4 with a fallback to Ipv6
+    /// Lookup Ipv4 with a fallback to Ipv6.
     Ipv4thenIpv6,
 }
 
@@ -62,6 +445,204 @@ impl Display for IpAddrFamily {
     }
 }
 
+/// How the cache of responses is treated when a `DnsResolver` is reconfigured.
+///
+/// The default is `CacheRetention::Retain`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum CacheRetention {
+    /// Retain all resolved and not found entries, which remain valid regardless of how they were
+    /// resolved.
+    #[default]
+    Retain,
+    /// Clear all entries.
+    Clear,
+}
+
+/// Statistics about the entries in the cache of a `DnsResolver`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub struct CacheStats {
+    /// The number of pending entries.
+    pub pending: usize,
+    /// The number of resolved entries.
+    pub resolved: usize,
+    /// The number of entries which did not match any records.
+    pub not_found: usize,
+    /// The number of failed entries.
+    pub failed: usize,
+    /// The number of timed out entries.
+    pub timeout: usize,
+    /// The number of entries which failed DNSSEC validation.
+    pub bogus: usize,
+    /// The number of pending entries whose lookup is waiting for the rate limit.
+    ///
+    /// These entries are also counted as `pending`.  See `Config::max_lookups_per_sec`.
+    pub throttled: usize,
+}
+
+impl CacheStats {
+    /// The total number of entries.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.pending + self.resolved + self.not_found + self.failed + self.timeout + self.bogus
+    }
+
+    /// Is the cache empty?
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The `Health` of the resolver, as inferred from the completed entries.
+    #[must_use]
+    pub const fn health(&self) -> Health {
+        let answered = self.resolved + self.not_found + self.bogus;
+        let unanswered = self.failed + self.timeout;
+        match (answered, unanswered) {
+            (_, 0) => Health::Healthy,
+            (0, _) => Health::Unhealthy,
+            _ => Health::Degraded,
+        }
+    }
+}
+
+/// A `DnsEntry` returned by a lazy lookup together with whether it was served from the cache.
+#[derive(Debug, Clone)]
+pub struct LazyDnsEntry {
+    entry: DnsEntry,
+    cached: bool,
+}
+
+impl LazyDnsEntry {
+    /// The `DnsEntry`.
+    #[must_use]
+    pub const fn entry(&self) -> &DnsEntry {
+        &self.entry
+    }
+
+    /// Consume the `LazyDnsEntry` and return the `DnsEntry`.
+    #[must_use]
+    pub fn into_entry(self) -> DnsEntry {
+        self.entry
+    }
+
+    /// Was the `DnsEntry` served from the cache?
+    ///
+    /// This is `false` if the lazy lookup enqueued a lookup to be resolved in the background, or
+    /// attempted to, and `true` otherwise.
+    #[must_use]
+    pub const fn cached(&self) -> bool {
+        self.cached
+    }
+}
+
+/// The timing of the cached entry for the reverse DNS lookup of an `IpAddr`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct DnsEntryMeta {
+    pending: bool,
+    updated: SystemTime,
+    elapsed: Option<Duration>,
+    dnssec: Option<DnssecStatus>,
+    server: Option<SocketAddr>,
+}
+
+impl DnsEntryMeta {
+    /// Is the lookup pending?
+    #[must_use]
+    pub const fn is_pending(&self) -> bool {
+        self.pending
+    }
+
+    /// When the lookup was enqueued, if it is pending.
+    #[must_use]
+    pub const fn enqueued(&self) -> Option<SystemTime> {
+        if self.pending {
+            Some(self.updated)
+        } else {
+            None
+        }
+    }
+
+    /// When the lookup completed, if it is not pending.
+    ///
+    /// A lookup which could not be enqueued, and so timed out without being performed, is
+    /// considered to have completed at that time.
+    #[must_use]
+    pub const fn completed(&self) -> Option<SystemTime> {
+        if self.pending {
+            None
+        } else {
+            Some(self.updated)
+        }
+    }
+
+    /// How long the lookup took, if it has completed.
+    ///
+    /// This is measured from when the query is dispatched until it is answered, and so excludes
+    /// the time spent waiting in the queue.  It is zero for an `IpAddr` mapped by the hosts file,
+    /// which is answered without any query, and `None` for a lookup which is pending or which was
+    /// never performed, such as an entry loaded from the cache file.
+    #[must_use]
+    pub const fn elapsed(&self) -> Option<Duration> {
+        self.elapsed
+    }
+
+    /// How long the lookup has been pending, if it is pending, or how long ago it completed.
+    ///
+    /// This is zero if the system clock has since moved backwards.
+    #[must_use]
+    pub fn age(&self) -> Duration {
+        self.updated.elapsed().unwrap_or_default()
+    }
+
+    /// The outcome of the DNSSEC validation of the lookup, if it was validated.
+    ///
+    /// This is `None` unless `Config::validate_dnssec` is set and the lookup has completed with
+    /// a response from the upstream resolver.
+    #[must_use]
+    pub const fn dnssec(&self) -> Option<DnssecStatus> {
+        self.dnssec
+    }
+
+    /// The name server which answered the lookup, if known.
+    ///
+    /// This is only known for lookups performed with `ResolveMethod::Resolv`,
+    /// `ResolveMethod::Google` or `ResolveMethod::Cloudflare` which were
+    /// answered over UDP.  Where the answer was served from the internal cache
+    /// of the resolver this is the name server which last answered a query for
+    /// the address.
+    #[must_use]
+    pub const fn server(&self) -> Option<SocketAddr> {
+        self.server
+    }
+}
+
+impl From<LazyDnsEntry> for DnsEntry {
+    fn from(value: LazyDnsEntry) -> Self {
+        value.entry
+    }
+}
+
+/// The health of a `DnsResolver`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Health {
+    /// No lookups have failed or timed out.
+    Healthy,
+    /// Some, but not all, lookups have failed or timed out.
+    Degraded,
+    /// All lookups have failed or timed out.
+    Unhealthy,
+}
+
+impl Display for Health {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Healthy => write!(f, "healthy"),
+            Self::Degraded => write!(f, "degraded"),
+            Self::Unhealthy => write!(f, "unhealthy"),
+        }
+    }
+}
+
 impl Config {
     /// Create a `Config`.
     #[must_use]
@@ -74,34 +655,647 @@ impl Config {
             resolve_method,
             addr_family,
             timeout,
+            connect_timeout: None,
+            query_timeout: None,
+            total_timeout: None,
+            bind_addr: None,
+            source_port: None,
+            startup_policy: StartupPolicy::Lenient,
+            as_lookup_sources: None,
+            as_lookup_zone: None,
+            observer: None,
+            max_inflight: DEFAULT_MAX_INFLIGHT,
+            max_lookups_per_sec: None,
+            max_cache_entries: None,
+            hosts_file: None,
+            use_hosts_file: true,
+            ttl_bounds: Some(TtlBounds::DEFAULT),
+            asinfo_ttl: Some(DEFAULT_ASINFO_TTL),
+            retry_policy: RetryPolicy::DEFAULT,
+            cache_file: None,
+            edns_udp_payload_size: None,
+            validate_dnssec: false,
+            caching: true,
+            trim_trailing_dot: true,
+            decode_idn: false,
+            suppress_generated_ptr: false,
+            generated_ptr_patterns: None,
+            force_tcp: false,
+            proxy: None,
+        }
+    }
+
+    /// Create a `ConfigBuilder` initialized with the default `Config`.
+    #[must_use]
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder::default()
+    }
+
+    /// Set the `DnsObserver` of lazy reverse DNS lookups.
+    #[must_use]
+    pub fn with_observer(self, observer: Arc<dyn DnsObserver>) -> Self {
+        Self {
+            observer: Some(observer),
+            ..self
+        }
+    }
+}
+
+/// Build a `Config`.
+///
+/// Any option which is not set takes the value of the default `Config`.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use trippy_dns::{Config, IpAddrFamily, ResolveMethod};
+///
+/// let config = Config::builder()
+///     .resolve_method(ResolveMethod::Cloudflare)
+///     .addr_family(IpAddrFamily::Ipv6thenIpv4)
+///     .timeout(Duration::from_secs(3))
+///     .max_cache_entries(Some(1024))
+///     .build();
+/// assert_eq!(ResolveMethod::Cloudflare, config.resolve_method);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ConfigBuilder {
+    config: Config,
+}
+
+impl ConfigBuilder {
+    /// Set the method to use for DNS resolution.
+    #[must_use]
+    pub fn resolve_method(self, resolve_method: ResolveMethod) -> Self {
+        Self {
+            config: Config {
+                resolve_method,
+                ..self.config
+            },
+        }
+    }
+
+    /// Set the IP address resolution family.
+    #[must_use]
+    pub fn addr_family(self, addr_family: IpAddrFamily) -> Self {
+        Self {
+            config: Config {
+                addr_family,
+                ..self.config
+            },
+        }
+    }
+
+    /// Set the timeout for DNS resolution.
+    #[must_use]
+    pub fn timeout(self, timeout: Duration) -> Self {
+        Self {
+            config: Config {
+                timeout,
+                ..self.config
+            },
+        }
+    }
+
+    /// Set the timeout for establishing a TCP connection to a name server.
+    #[must_use]
+    pub fn connect_timeout(self, connect_timeout: Option<Duration>) -> Self {
+        Self {
+            config: Config {
+                connect_timeout,
+                ..self.config
+            },
+        }
+    }
+
+    /// Set the timeout for a single query round trip to a name server.
+    #[must_use]
+    pub fn query_timeout(self, query_timeout: Option<Duration>) -> Self {
+        Self {
+            config: Config {
+                query_timeout,
+                ..self.config
+            },
+        }
+    }
+
+    /// Set the timeout for a lookup as a whole.
+    #[must_use]
+    pub fn total_timeout(self, total_timeout: Option<Duration>) -> Self {
+        Self {
+            config: Config {
+                total_timeout,
+                ..self.config
+            },
+        }
+    }
+
+    /// Set the local address to bind DNS queries to.
+    #[must_use]
+    pub fn bind_addr(self, bind_addr: Option<IpAddr>) -> Self {
+        Self {
+            config: Config {
+                bind_addr,
+                ..self.config
+            },
+        }
+    }
+
+    /// Set the fixed local port to send DNS queries from.
+    #[must_use]
+    pub fn source_port(self, source_port: Option<u16>) -> Self {
+        Self {
+            config: Config {
+                source_port,
+                ..self.config
+            },
+        }
+    }
+
+    /// Set how to behave if no upstream DNS server is reachable at startup.
+    #[must_use]
+    pub fn startup_policy(self, startup_policy: StartupPolicy) -> Self {
+        Self {
+            config: Config {
+                startup_policy,
+                ..self.config
+            },
+        }
+    }
+
+    /// Set a single source of `AsInfo` lookups.
+    #[must_use]
+    pub fn as_lookup_source(self, as_lookup_source: AsLookupSource) -> Self {
+        self.as_lookup_sources(vec![as_lookup_source])
+    }
+
+    /// Set the sources of `AsInfo` lookups, in order of preference.
+    #[must_use]
+    pub fn as_lookup_sources(self, as_lookup_sources: Vec<AsLookupSource>) -> Self {
+        Self {
+            config: Config {
+                as_lookup_sources: Some(as_lookup_sources),
+                ..self.config
+            },
+        }
+    }
+
+    /// Set the base zone of the Team Cymru compatible service queried for `AsInfo`.
+    #[must_use]
+    pub fn as_lookup_zone(self, as_lookup_zone: Option<String>) -> Self {
+        Self {
+            config: Config {
+                as_lookup_zone,
+                ..self.config
+            },
+        }
+    }
+
+    /// Set the `DnsObserver` of lazy reverse DNS lookups.
+    #[must_use]
+    pub fn observer(self, observer: Arc<dyn DnsObserver>) -> Self {
+        Self {
+            config: self.config.with_observer(observer),
+        }
+    }
+
+    /// Set the maximum number of lazy lookups which may be in-flight concurrently.
+    #[must_use]
+    pub fn max_inflight(self, max_inflight: usize) -> Self {
+        Self {
+            config: Config {
+                max_inflight,
+                ..self.config
+            },
+        }
+    }
+
+    /// Set the maximum number of lazy lookups which may be started per second.
+    #[must_use]
+    pub fn max_lookups_per_sec(self, max_lookups_per_sec: Option<u32>) -> Self {
+        Self {
+            config: Config {
+                max_lookups_per_sec,
+                ..self.config
+            },
+        }
+    }
+
+    /// Set the maximum number of entries in each cache.
+    #[must_use]
+    pub fn max_cache_entries(self, max_cache_entries: Option<usize>) -> Self {
+        Self {
+            config: Config {
+                max_cache_entries,
+                ..self.config
+            },
+        }
+    }
+
+    /// Set the hosts file.
+    #[must_use]
+    pub fn hosts_file(self, hosts_file: Option<PathBuf>) -> Self {
+        Self {
+            config: Config {
+                hosts_file,
+                ..self.config
+            },
+        }
+    }
+
+    /// Set whether to consult the hosts file before querying any name server.
+    #[must_use]
+    pub fn use_hosts_file(self, use_hosts_file: bool) -> Self {
+        Self {
+            config: Config {
+                use_hosts_file,
+                ..self.config
+            },
+        }
+    }
+
+    /// Set the bounds to which the TTL of a reverse DNS response is clamped.
+    #[must_use]
+    pub fn ttl_bounds(self, ttl_bounds: Option<TtlBounds>) -> Self {
+        Self {
+            config: Config {
+                ttl_bounds,
+                ..self.config
+            },
+        }
+    }
+
+    /// Set the time for which resolved `AS` information is cached.
+    #[must_use]
+    pub fn asinfo_ttl(self, asinfo_ttl: Option<Duration>) -> Self {
+        Self {
+            config: Config {
+                asinfo_ttl,
+                ..self.config
+            },
+        }
+    }
+
+    /// Set the policy for retrying lookups which failed.
+    #[must_use]
+    pub fn retry_policy(self, retry_policy: RetryPolicy) -> Self {
+        Self {
+            config: Config {
+                retry_policy,
+                ..self.config
+            },
+        }
+    }
+
+    /// Set the file in which to persist the cache between runs.
+    #[must_use]
+    pub fn cache_file(self, cache_file: Option<PathBuf>) -> Self {
+        Self {
+            config: Config {
+                cache_file,
+                ..self.config
+            },
+        }
+    }
+
+    /// Set the UDP payload size to advertise via EDNS0.
+    #[must_use]
+    pub fn edns_udp_payload_size(self, edns_udp_payload_size: Option<u16>) -> Self {
+        Self {
+            config: Config {
+                edns_udp_payload_size,
+                ..self.config
+            },
+        }
+    }
+
+    /// Set whether to request and report the DNSSEC validation of reverse DNS lookups.
+    #[must_use]
+    pub fn validate_dnssec(self, validate_dnssec: bool) -> Self {
+        Self {
+            config: Config {
+                validate_dnssec,
+                ..self.config
+            },
+        }
+    }
+
+    /// Set whether to cache the results of lazy reverse DNS lookups.
+    #[must_use]
+    pub fn caching(self, caching: bool) -> Self {
+        Self {
+            config: Config {
+                caching,
+                ..self.config
+            },
+        }
+    }
+
+    /// Set whether to trim the trailing dot of resolved hostnames.
+    #[must_use]
+    pub fn trim_trailing_dot(self, trim_trailing_dot: bool) -> Self {
+        Self {
+            config: Config {
+                trim_trailing_dot,
+                ..self.config
+            },
+        }
+    }
+
+    /// Set whether to decode internationalized domain names (IDN) in resolved hostnames.
+    #[must_use]
+    pub fn decode_idn(self, decode_idn: bool) -> Self {
+        Self {
+            config: Config {
+                decode_idn,
+                ..self.config
+            },
+        }
+    }
+
+    /// Set whether to suppress auto-generated reverse DNS hostnames.
+    #[must_use]
+    pub fn suppress_generated_ptr(self, suppress_generated_ptr: bool) -> Self {
+        Self {
+            config: Config {
+                suppress_generated_ptr,
+                ..self.config
+            },
+        }
+    }
+
+    /// Set the patterns which match auto-generated reverse DNS hostnames.
+    #[must_use]
+    pub fn generated_ptr_patterns(self, generated_ptr_patterns: Option<Vec<Regex>>) -> Self {
+        Self {
+            config: Config {
+                generated_ptr_patterns,
+                ..self.config
+            },
+        }
+    }
+
+    /// Set whether to send all queries over TCP.
+    #[must_use]
+    pub fn force_tcp(self, force_tcp: bool) -> Self {
+        Self {
+            config: Config {
+                force_tcp,
+                ..self.config
+            },
+        }
+    }
+
+    /// Set the SOCKS5 proxy through which to connect to name servers.
+    #[must_use]
+    pub fn proxy(self, proxy: Option<ProxyConfig>) -> Self {
+        Self {
+            config: Config {
+                proxy,
+                ..self.config
+            },
+        }
+    }
+
+    /// Build the `Config`.
+    #[must_use]
+    pub fn build(self) -> Config {
+        self.config
+    }
+}
+
+/// A policy which governs the reverse DNS lookups of a single tracing session.
+///
+/// Several sessions may share a single `DnsResolver`, and so a single cache and worker, whilst
+/// each applying a different policy via a `DnsSession`.  The policy is applied whenever an entry
+/// is read, and so never affects the results seen by any other session:
+///
+/// - an address within any of the `suppressed` networks is never looked up and is always
+///   reported as `DnsEntry::NotFound` without `AS` information, even if it has already been
+///   resolved on behalf of another session
+/// - `AS` information is only looked up and reported if `asinfo` is set, even if it has already
+///   been resolved on behalf of another session
+/// - a lazy lookup which has been pending for longer than the `timeout`, if set, is reported as
+///   `DnsEntry::Timeout` whilst the lookup itself continues and so may still complete for other
+///   sessions
+///
+/// Resolved hostnames and `AS` information are otherwise shared between all sessions, such that
+/// an address is looked up at most once regardless of how many sessions request it.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct LookupPolicy {
+    /// The networks for which reverse DNS lookups are suppressed.
+    pub suppressed: Vec<IpNetwork>,
+    /// Whether `AS` information may be looked up and reported.
+    pub asinfo: bool,
+    /// The maximum duration a lazy lookup may be pending before it is reported as timed out.
+    ///
+    /// This can only shorten, and never extend, the timeouts of the `Config` of the resolver.
+    pub timeout: Option<Duration>,
+}
+
+impl LookupPolicy {
+    /// Is reverse DNS lookup of `addr` suppressed by this policy?
+    #[must_use]
+    pub fn is_suppressed(&self, addr: IpAddr) -> bool {
+        self.suppressed.iter().any(|network| network.contains(addr))
+    }
+}
+
+impl Default for LookupPolicy {
+    fn default() -> Self {
+        Self {
+            suppressed: vec![],
+            asinfo: true,
+            timeout: None,
         }
     }
 }
 
 /// A cheaply cloneable, non-blocking, caching, forward and reverse DNS resolver.
+///
+/// Dropping the last clone of a `DnsResolver` stops the background resolution threads and aborts
+/// any lookup which is in-flight, without waiting for the threads to finish.  Every thread exits
+/// once its in-flight lookup, if any, has been aborted, and so no thread outlives the resolver for
+/// longer than a lookup which cannot be aborted (see `DnsResolver::shutdown`).  Use
+/// `DnsResolver::shutdown` to also wait for the threads to finish.
 #[derive(Clone)]
 pub struct DnsResolver {
-    inner: Rc<inner::DnsResolver>,
+    inner: Arc<inner::DnsResolver>,
 }
 
 impl DnsResolver {
     /// Create and start a new `DnsResolver`.
+    ///
+    /// Lookups are driven on a dedicated tokio runtime which is created for, and owned by, the
+    /// resolver.
     pub fn start(config: Config) -> std::io::Result<Self> {
         Ok(Self {
-            inner: Rc::new(inner::DnsResolver::start(config)?),
+            inner: Arc::new(inner::DnsResolver::start(config, None)?),
         })
     }
 
-    /// Get the `Config`.
+    /// Create and start a new `DnsResolver` which drives lookups on an existing tokio runtime.
+    ///
+    /// This avoids creating a dedicated runtime for applications which already have one.  The
+    /// runtime must be multi-threaded, as a current-thread runtime is only driven whilst the
+    /// application itself blocks on it, and must outlive the resolver.
+    ///
+    /// The lazy lookups remain synchronous and non-blocking and may be performed from any
+    /// thread.  The blocking lookups, and the start-up check of `StartupPolicy::Strict`, block
+    /// on the runtime and so, as with `DnsResolver::start`, must not be performed from within an
+    /// asynchronous context.
+    pub fn start_with_runtime(config: Config, runtime: Handle) -> std::io::Result<Self> {
+        Ok(Self {
+            inner: Arc::new(inner::DnsResolver::start(config, Some(runtime))?),
+        })
+    }
+
+    /// Get the current `Config`.
+    ///
+    /// This is returned by value, rather than by reference, as it is replaced by `reconfigure`.
     #[must_use]
-    pub fn config(&self) -> &Config {
+    pub fn config(&self) -> Config {
         self.inner.config()
     }
 
+    /// Reconfigure the `DnsResolver` at runtime.
+    ///
+    /// The underlying resolver is rebuilt from the new `Config` and shared by all clones of this
+    /// `DnsResolver`.  Lookups which are in-flight are aborted and will be retried with the new
+    /// resolver on their next lazy lookup, completed entries are retained or cleared as per the
+    /// given `CacheRetention`.
+    ///
+    /// If the new `Config` is invalid then an error is returned and the `DnsResolver` is unchanged.
+    pub fn reconfigure(&self, config: Config, retention: CacheRetention) -> std::io::Result<()> {
+        self.inner.reconfigure(config, retention)
+    }
+
+    /// Load the entries of a cache file, as written by [`DnsResolver::save_cache`], into the cache.
+    ///
+    /// Entries which have expired are skipped and at most `Config::max_cache_entries` of the most
+    /// recently updated entries are loaded.  A cache file which does not exist is treated as an
+    /// empty one.
+    ///
+    /// This allows the results of the lookups of a previous run to be reused, rather than being
+    /// queried again.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cache file cannot be read or is not a valid cache file.
+    pub fn load_cache(&self, path: &Path) -> std::io::Result<()> {
+        self.inner.load_cache(path)
+    }
+
+    /// Save the completed entries of the cache, with their expiry, to a cache file.
+    ///
+    /// The file is written as JSON and replaced atomically.  Entries which are pending, failed or
+    /// timed out are not saved.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cache file cannot be written.
+    pub fn save_cache(&self, path: &Path) -> std::io::Result<()> {
+        self.inner.save_cache(path)
+    }
+
+    /// Get statistics about the entries in the cache of responses.
+    #[must_use]
+    pub fn cache_stats(&self) -> CacheStats {
+        self.inner.cache_stats()
+    }
+
+    /// Get the timing of the cached entry for the reverse DNS lookup of `IpAddr`, if any.
+    ///
+    /// This does not perform, or enqueue, a lookup.
+    #[must_use]
+    pub fn entry_metadata(&self, addr: impl Into<IpAddr>) -> Option<DnsEntryMeta> {
+        self.inner.entry_metadata(addr.into())
+    }
+
+    /// Get the raw hostnames of the cached entry for the reverse DNS lookup of `IpAddr`, if any
+    /// were decoded as per `Config::decode_idn`.
+    ///
+    /// This does not perform, or enqueue, a lookup.
+    #[must_use]
+    pub fn raw_hostnames(&self, addr: impl Into<IpAddr>) -> Option<Vec<String>> {
+        self.inner.raw_hostnames(addr.into())
+    }
+
     /// Flush the cache of responses.
     pub fn flush(&self) {
         self.inner.flush();
     }
+
+    /// Invalidate the cached entry for the reverse DNS lookup of `IpAddr`, if any.
+    ///
+    /// The completed entry, and its `AS` information, are removed from the cache such that the
+    /// next lazy lookup of `IpAddr` is enqueued again.  The announced prefix of the `AS`
+    /// information is also removed from the cache, such that it is looked up again.  An entry
+    /// which is pending, or being refreshed, is not affected.
+    ///
+    /// Returns whether a completed entry was removed.
+    pub fn invalidate(&self, addr: impl Into<IpAddr>) -> bool {
+        self.inner.invalidate(addr.into())
+    }
+
+    /// Perform a lazy reverse DNS lookup of `IpAddr` and return a `LazyDnsEntry`.
+    ///
+    /// As per [`Resolver::lazy_reverse_lookup`], additionally indicating whether the `DnsEntry` was
+    /// served from the cache.
+    #[must_use]
+    pub fn lazy_reverse_lookup_cached(&self, addr: impl Into<IpAddr>) -> LazyDnsEntry {
+        self.inner
+            .lazy_reverse_lookup(addr.into(), false, &LookupPolicy::default())
+    }
+
+    /// Perform a lazy reverse DNS lookup of `IpAddr` and return a `LazyDnsEntry` with `AS`
+    /// information.
+    ///
+    /// As per [`Resolver::lazy_reverse_lookup_with_asinfo`], additionally indicating whether the
+    /// `DnsEntry` was served from the cache.  The `DnsEntry` is only considered to have been
+    /// served from the cache if both the hostnames and the `AS` information were.
+    #[must_use]
+    pub fn lazy_reverse_lookup_with_asinfo_cached(&self, addr: impl Into<IpAddr>) -> LazyDnsEntry {
+        self.inner
+            .lazy_reverse_lookup(addr.into(), true, &LookupPolicy::default())
+    }
+
+    /// Perform a lazy reverse DNS lookup of `IpAddr` as governed by a `LookupPolicy`.
+    ///
+    /// `AS` information is included if, and only if, the policy allows it.  A lookup which is
+    /// suppressed by the policy is neither enqueued nor served from the cache.
+    #[must_use]
+    pub fn lazy_reverse_lookup_with_policy(
+        &self,
+        addr: impl Into<IpAddr>,
+        policy: &LookupPolicy,
+    ) -> LazyDnsEntry {
+        self.inner
+            .lazy_reverse_lookup(addr.into(), policy.asinfo, policy)
+    }
+
+    /// Create a `DnsSession` which shares this `DnsResolver` and applies a `LookupPolicy`.
+    #[must_use]
+    pub fn session(&self, policy: LookupPolicy) -> DnsSession {
+        DnsSession {
+            resolver: self.clone(),
+            policy,
+        }
+    }
+
+    /// Shutdown the `DnsResolver` and wait for the background resolution threads to finish.
+    ///
+    /// The background threads stop accepting work and any lookup which is in-flight is aborted.
+    /// Requests which are queued but not yet in-flight are discarded.
+    /// This applies to all clones of this `DnsResolver`, for which all subsequent lookups fail;
+    /// lazy lookups return `DnsEntry::Failed` and `reconfigure` returns an error.
+    ///
+    /// Lookups performed with `ResolveMethod::System` or `AsLookupSource::RipeStat` cannot be
+    /// aborted and so any such lookup which is in-flight is waited for.
+    pub fn shutdown(self) {
+        self.inner.shutdown();
+    }
 }
 
 impl Resolver for DnsResolver {
@@ -110,416 +1304,154 @@ impl Resolver for DnsResolver {
     }
     #[must_use]
     fn reverse_lookup(&self, addr: impl Into<IpAddr>) -> DnsEntry {
-        self.inner.reverse_lookup(addr.into(), false, false)
+        self.inner
+            .reverse_lookup(addr.into(), false, false, &LookupPolicy::default())
     }
     #[must_use]
     fn reverse_lookup_with_asinfo(&self, addr: impl Into<IpAddr>) -> DnsEntry {
-        self.inner.reverse_lookup(addr.into(), true, false)
+        self.inner
+            .reverse_lookup(addr.into(), true, false, &LookupPolicy::default())
     }
     #[must_use]
     fn lazy_reverse_lookup(&self, addr: impl Into<IpAddr>) -> DnsEntry {
-        self.inner.reverse_lookup(addr.into(), false, true)
+        self.inner
+            .reverse_lookup(addr.into(), false, true, &LookupPolicy::default())
     }
     #[must_use]
     fn lazy_reverse_lookup_with_asinfo(&self, addr: impl Into<IpAddr>) -> DnsEntry {
-        self.inner.reverse_lookup(addr.into(), true, true)
+        self.inner
+            .reverse_lookup(addr.into(), true, true, &LookupPolicy::default())
+    }
+    fn cancel(&self, addr: impl Into<IpAddr>) {
+        self.inner.cancel(addr.into());
+    }
+    fn reverse_lookup_with_deadline(
+        &self,
+        addr: impl Into<IpAddr>,
+        deadline: Duration,
+    ) -> DnsEntry {
+        self.inner
+            .reverse_lookup_with_deadline(addr.into(), deadline, &LookupPolicy::default())
+    }
+    fn lookup_asinfo(&self, addr: impl Into<IpAddr>) -> AsInfoEntry {
+        self.inner
+            .lookup_asinfo(addr.into(), &LookupPolicy::default())
+    }
+    fn lazy_lookup_asinfo(&self, addr: impl Into<IpAddr>) -> AsInfoEntry {
+        self.inner
+            .lazy_lookup_asinfo(addr.into(), &LookupPolicy::default())
+    }
+    fn inflight(&self) -> usize {
+        self.inner.inflight()
+    }
+    fn queue_depth(&self) -> usize {
+        self.inner.queue_depth()
+    }
+    fn cached_entries(&self) -> Vec<(IpAddr, DnsEntry)> {
+        self.inner.cached_entries(&LookupPolicy::default())
+    }
+    fn subscribe(&self) -> Receiver<(IpAddr, DnsEntry)> {
+        self.inner.subscribe(&LookupPolicy::default())
     }
 }
 
-/// Private impl of resolver.
-mod inner {
-    use super::{Config, IpAddrFamily, ResolveMethod};
-    use crate::resolver::{AsInfo, DnsEntry, Error, Resolved, ResolvedIpAddrs, Result, Unresolved};
-    use crossbeam::channel::{bounded, Receiver, Sender};
-    use hickory_resolver::config::{LookupIpStrategy, ResolverConfig, ResolverOpts};
-    use hickory_resolver::error::{ResolveError, ResolveErrorKind};
-    use hickory_resolver::proto::error::ProtoError;
-    use hickory_resolver::proto::rr::RecordType;
-    use hickory_resolver::{Name, Resolver};
-    use itertools::{Either, Itertools};
-    use parking_lot::RwLock;
-    use std::collections::HashMap;
-    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
-    use std::str::FromStr;
-    use std::sync::Arc;
-    use std::thread;
-    use std::time::Duration;
-
-    /// The maximum number of in-flight reverse DNS resolutions that may be
-    const RESOLVER_MAX_QUEUE_SIZE: usize = 100;
-
-    /// The duration wait to enqueue a `DnsEntry::Pending` to the resolver before returning
-    /// `DnsEntry::Timeout`.
-    const RESOLVER_QUEUE_TIMEOUT: Duration = Duration::from_millis(10);
-
-    /// Alias for a cache of reverse DNS lookup entries.
-    type Cache = Arc<RwLock<HashMap<IpAddr, DnsEntry>>>;
-
-    #[derive(Clone)]
-    enum DnsProvider {
-        TrustDns(Arc<Resolver>),
-        DnsLookup,
-    }
-
-    #[derive(Debug, Clone)]
-    pub struct DnsResolveRequest {
-        addr: IpAddr,
-        with_asinfo: bool,
-    }
-
-    /// Resolver implementation.
-    pub struct DnsResolver {
-        config: Config,
-        provider: DnsProvider,
-        tx: Sender<DnsResolveRequest>,
-        addr_cache: Cache,
-    }
-
-    impl DnsResolver {
-        pub fn start(config: Config) -> std::io::Result<Self> {
-            let (tx, rx) = bounded(RESOLVER_MAX_QUEUE_SIZE);
-            let addr_cache = Arc::new(RwLock::new(HashMap::new()));
-
-            let provider = if matches!(config.resolve_method, ResolveMethod::System) {
-                DnsProvider::DnsLookup
-            } else {
-                let mut options = ResolverOpts::default();
-                options.timeout = config.timeout;
-                options.ip_strategy = match config.addr_family {
-                    IpAddrFamily::Ipv4Only => LookupIpStrategy::Ipv4Only,
-                    IpAddrFamily::Ipv6Only => LookupIpStrategy::Ipv6Only,
-                    IpAddrFamily::Ipv6thenIpv4 => LookupIpStrategy::Ipv6thenIpv4,
-                    IpAddrFamily::Ipv4thenIpv6 => LookupIpStrategy::Ipv4thenIpv6,
-                };
-                let res = match config.resolve_method {
-                    ResolveMethod::Resolv => Resolver::from_system_conf(),
-                    ResolveMethod::Google => Resolver::new(ResolverConfig::google(), options),
-                    ResolveMethod::Cloudflare => {
-                        Resolver::new(ResolverConfig::cloudflare(), options)
-                    }
-                    ResolveMethod::System => unreachable!(),
-                }?;
-                let resolver = Arc::new(res);
-                DnsProvider::TrustDns(resolver)
-            };
-
-            // spawn a thread to process the resolve queue
-            {
-                let cache = addr_cache.clone();
-                let provider = provider.clone();
-                thread::spawn(move || resolver_queue_processor(rx, &provider, &cache));
-            }
-            Ok(Self {
-                config,
-                provider,
-                tx,
-                addr_cache,
-            })
-        }
-
-        pub const fn config(&self) -> &Config {
-            &self.config
-        }
-
-        pub fn lookup(&self, hostname: &str) -> Result<ResolvedIpAddrs> {
-            match &self.provider {
-                DnsProvider::TrustDns(resolver) => Ok(resolver
-                    .lookup_ip(hostname)
-                    .map_err(|err| Error::LookupFailed(Box::new(err)))?
-                    .iter()
-                    .collect::<Vec<_>>()),
-                DnsProvider::DnsLookup => {
-                    let (ipv4, ipv6): (Vec<_>, Vec<_>) = dns_lookup::lookup_host(hostname)
-                        .map_err(|err| Error::LookupFailed(Box::new(err)))?
-                        .into_iter()
-                        .partition_map(|ip| match ip {
-                            IpAddr::V4(_) => Either::Left(ip),
-                            IpAddr::V6(_) => Either::Right(ip),
-                        });
-                    Ok(match self.config.addr_family {
-                        IpAddrFamily::Ipv4Only => {
-                            if ipv4.is_empty() {
-                                vec![]
-                            } else {
-                                ipv4
-                            }
-                        }
-                        IpAddrFamily::Ipv6Only => {
-                            if ipv6.is_empty() {
-                                vec![]
-                            } else {
-                                ipv6
-                            }
-                        }
-                        IpAddrFamily::Ipv6thenIpv4 => {
-                            if ipv6.is_empty() {
-                                ipv4
-                            } else {
-                                ipv6
-                            }
-                        }
-                        IpAddrFamily::Ipv4thenIpv6 => {
-                            if ipv4.is_empty() {
-                                ipv6
-                            } else {
-                                ipv4
-                            }
-                        }
-                    })
-                }
-            }
-            .map(ResolvedIpAddrs)
-        }
-
-        pub fn reverse_lookup(&self, addr: IpAddr, with_asinfo: bool, lazy: bool) -> DnsEntry {
-            if lazy {
-                self.lazy_reverse_lookup(addr, with_asinfo)
-            } else {
-                reverse_lookup(&self.provider, addr, with_asinfo)
-            }
-        }
-
-        fn lazy_reverse_lookup(&self, addr: IpAddr, with_asinfo: bool) -> DnsEntry {
-            let mut enqueue = false;
-
-            // Check if we have already attempted to resolve this `IpAddr` and return the current
-            // `DnsEntry` if so, otherwise add it in a state of `DnsEntry::Pending`.
-            let mut dns_entry = self
-                .addr_cache
-                .write()
-                .entry(addr)
-                .or_insert_with(|| {
-                    enqueue = true;
-                    DnsEntry::Pending(addr)
-                })
-                .clone();
-
-            // If the entry exists but has timed out, then set it as DnsEntry::Pending and enqueue
-            // it again.
-            if let DnsEntry::Timeout(addr) = dns_entry {
-                *self
-                    .addr_cache
-                    .write()
-                    .get_mut(&addr)
-                    .expect("addr must be in cache") = DnsEntry::Pending(addr);
-                dns_entry = DnsEntry::Pending(addr);
-                enqueue = true;
-            }
-
-            // If this is a newly added `DnsEntry` then send it to the channel to be resolved in the
-            // background.  We do this after the above to ensure we aren't holding the
-            // lock on the cache, which is usd by the resolver and so would deadlock.
-            if enqueue {
-                if self
-                    .tx
-                    .send_timeout(
-                        DnsResolveRequest { addr, with_asinfo },
-                        RESOLVER_QUEUE_TIMEOUT,
-                    )
-                    .is_ok()
-                {
-                    dns_entry
-                } else {
-                    *self
-                        .addr_cache
-                        .write()
-                        .get_mut(&addr)
-                        .expect("addr must be in cache") = DnsEntry::Timeout(addr);
-                    DnsEntry::Timeout(addr)
-                }
-            } else {
-                dns_entry
-            }
-        }
-
-        pub fn flush(&self) {
-            self.addr_cache.write().clear();
-        }
-    }
-
-    /// Process each `IpAddr` from the resolver queue and perform the reverse DNS lookup.
-    ///
-    /// For each `IpAddr`, perform the reverse DNS lookup and update the cache with the result
-    /// (`Resolved`, `NotFound`, `Timeout` or `Failed`) for that addr.
-    fn resolver_queue_processor(
-        rx: Receiver<DnsResolveRequest>,
-        provider: &DnsProvider,
-        cache: &Cache,
-    ) {
-        for DnsResolveRequest { addr, with_asinfo } in rx {
-            let dns_entry = reverse_lookup(provider, addr, with_asinfo);
-            cache.write().insert(addr, dns_entry);
-        }
-    }
-
-    fn reverse_lookup(provider: &DnsProvider, addr: IpAddr, with_asinfo: bool) -> DnsEntry {
-        match &provider {
-            DnsProvider::DnsLookup => {
-                // we can't distinguish between a failed lookup or a genuine error and so we just
-                // assume all failures are `DnsEntry::NotFound`.
-                match dns_lookup::lookup_addr(&addr) {
-                    Ok(dns) => DnsEntry::Resolved(Resolved::Normal(addr, vec![dns])),
-                    Err(_) => DnsEntry::NotFound(Unresolved::Normal(addr)),
-                }
-            }
-            DnsProvider::TrustDns(resolver) => match resolver.reverse_lookup(addr) {
-                Ok(name) => {
-                    let hostnames = name
-                        .into_iter()
-                        .map(|mut s| {
-                            s.0.set_fqdn(false);
-                            s
-                        })
-                        .map(|s| s.to_string())
-                        .collect();
-                    if with_asinfo {
-                        let as_info = lookup_asinfo(resolver, addr).unwrap_or_default();
-                        DnsEntry::Resolved(Resolved::WithAsInfo(addr, hostnames, as_info))
-                    } else {
-                        DnsEntry::Resolved(Resolved::Normal(addr, hostnames))
-                    }
-                }
-                Err(err) => match err.kind() {
-                    ResolveErrorKind::NoRecordsFound { .. } => {
-                        if with_asinfo {
-                            let as_info = lookup_asinfo(resolver, addr).unwrap_or_default();
-                            DnsEntry::NotFound(Unresolved::WithAsInfo(addr, as_info))
-                        } else {
-                            DnsEntry::NotFound(Unresolved::Normal(addr))
-                        }
-                    }
-                    ResolveErrorKind::Timeout => DnsEntry::Timeout(addr),
-                    _ => DnsEntry::Failed(addr),
-                },
-            },
-        }
-    }
+/// A tracing session which shares a `DnsResolver` with other sessions and applies its own
+/// `LookupPolicy` to every reverse DNS lookup.
+///
+/// See `LookupPolicy` for the semantics of sharing a `DnsResolver` between sessions.  The
+/// cached entries and the completions sent to subscribers of a session are also subject to the
+/// policy, such that a suppressed address is never reported.  Forward DNS lookups are not
+/// subject to the policy.  A lookup cancelled by one session is cancelled
+/// for all sessions, any of which may enqueue it again.
+#[derive(Clone)]
+pub struct DnsSession {
+    resolver: DnsResolver,
+    policy: LookupPolicy,
+}
 
-    /// Lookup up `AsInfo` for an `IpAddr` address.
-    fn lookup_asinfo(resolver: &Arc<Resolver>, addr: IpAddr) -> Result<AsInfo> {
-        let origin_query_txt = match addr {
-            IpAddr::V4(addr) => query_asn_ipv4(resolver, addr)?,
-            IpAddr::V6(addr) => query_asn_ipv6(resolver, addr)?,
-        };
-        let asinfo = parse_origin_query_txt(&origin_query_txt)?;
-        let asn_query_txt = query_asn_name(resolver, &asinfo.asn)?;
-        let as_name = parse_asn_query_txt(&asn_query_txt)?;
-        Ok(AsInfo {
-            asn: asinfo.asn,
-            prefix: asinfo.prefix,
-            cc: asinfo.cc,
-            registry: asinfo.registry,
-            allocated: asinfo.allocated,
-            name: as_name,
-        })
+impl DnsSession {
+    /// The `LookupPolicy` of this session.
+    #[must_use]
+    pub const fn policy(&self) -> &LookupPolicy {
+        &self.policy
     }
 
-    /// Perform the `origin` query.
-    fn query_asn_ipv4(resolver: &Arc<Resolver>, addr: Ipv4Addr) -> Result<String> {
-        let query = format!(
-            "{}.origin.asn.cymru.com.",
-            addr.octets().iter().rev().join(".")
-        );
-        let name = Name::from_str(query.as_str()).map_err(proto_error)?;
-        let response = resolver
-            .lookup(name, RecordType::TXT)
-            .map_err(resolve_error)?;
-        let data = response
-            .iter()
-            .next()
-            .ok_or_else(|| Error::QueryAsnOriginFailed)?;
-        let bytes = data.as_txt().ok_or_else(|| Error::QueryAsnOriginFailed)?;
-        Ok(bytes.to_string())
-    }
-
-    /// Perform the `origin` query.
-    fn query_asn_ipv6(resolver: &Arc<Resolver>, addr: Ipv6Addr) -> Result<String> {
-        let query = format!(
-            "{:x}.origin6.asn.cymru.com.",
-            addr.octets()
-                .iter()
-                .rev()
-                .flat_map(|o| [o & 0x0F, (o & 0xF0) >> 4])
-                .format(".")
-        );
-        let name = Name::from_str(query.as_str()).map_err(proto_error)?;
-        let response = resolver
-            .lookup(name, RecordType::TXT)
-            .map_err(resolve_error)?;
-        let data = response
-            .iter()
-            .next()
-            .ok_or_else(|| Error::QueryAsnOriginFailed)?;
-        let bytes = data.as_txt().ok_or_else(|| Error::QueryAsnOriginFailed)?;
-        Ok(bytes.to_string())
-    }
-
-    /// Perform the `asn` query.
-    fn query_asn_name(resolver: &Arc<Resolver>, asn: &str) -> Result<String> {
-        let query = format!("AS{asn}.asn.cymru.com.");
-        let name = Name::from_str(query.as_str()).map_err(proto_error)?;
-        let response = resolver
-            .lookup(name, RecordType::TXT)
-            .map_err(resolve_error)?;
-        let data = response
-            .iter()
-            .next()
-            .ok_or_else(|| Error::QueryAsnFailed)?;
-        let bytes = data.as_txt().ok_or_else(|| Error::QueryAsnFailed)?;
-        Ok(bytes.to_string())
-    }
-
-    /// The `origin` DNS query returns a TXT record in the formal:
-    ///      `asn | prefix | cc | registry | allocated`
-    ///
-    /// For example:
-    ///      `12301 | 81.0.100.0/22 | HU | ripencc | 2001-12-06`
-    ///
-    /// From this we extract all fields.
-    fn parse_origin_query_txt(origin_query_txt: &str) -> Result<AsInfo> {
-        if origin_query_txt.chars().filter(|c| *c == '|').count() != 4 {
-            return Err(Error::ParseOriginQueryFailed(String::from(
-                origin_query_txt,
-            )));
-        }
-        let mut split = origin_query_txt.split('|');
-        let asn = split.next().unwrap_or_default().trim().to_string();
-        let prefix = split.next().unwrap_or_default().trim().to_string();
-        let cc = split.next().unwrap_or_default().trim().to_string();
-        let registry = split.next().unwrap_or_default().trim().to_string();
-        let allocated = split.next().unwrap_or_default().trim().to_string();
-        Ok(AsInfo {
-            asn,
-            prefix,
-            cc,
-            registry,
-            allocated,
-            name: String::default(),
-        })
+    /// The shared `DnsResolver` of this session.
+    #[must_use]
+    pub const fn resolver(&self) -> &DnsResolver {
+        &self.resolver
     }
 
-    /// The `asn` DNS query returns a TXT record in the formal:
-    ///      `asn | cc | registry | allocated | name`
+    /// Perform a lazy reverse DNS lookup of `IpAddr` and return a `LazyDnsEntry`.
     ///
-    /// For example:
-    ///      `12301 | HU | ripencc | 1999-02-25 | INVITECH, HU`
-    ///
-    /// From this we extract the 4th field (name, `INVITECH, HU` in this example)
-    fn parse_asn_query_txt(asn_query_txt: &str) -> Result<String> {
-        if asn_query_txt.chars().filter(|c| *c == '|').count() != 4 {
-            return Err(Error::ParseAsnQueryFailed(String::from(asn_query_txt)));
-        }
-        let mut split = asn_query_txt.split('|');
-        Ok(split.nth(4).unwrap_or_default().trim().to_string())
+    /// As per [`DnsResolver::lazy_reverse_lookup_with_policy`] for the policy of this session.
+    #[must_use]
+    pub fn lazy_reverse_lookup_cached(&self, addr: impl Into<IpAddr>) -> LazyDnsEntry {
+        self.resolver
+            .lazy_reverse_lookup_with_policy(addr, &self.policy)
     }
+}
 
-    /// Convert a `ResolveError` to an `Error::LookupFailed`.
-    fn resolve_error(err: ResolveError) -> Error {
-        Error::LookupFailed(Box::new(err))
+impl Resolver for DnsSession {
+    fn lookup(&self, hostname: impl AsRef<str>) -> Result<ResolvedIpAddrs> {
+        self.resolver.lookup(hostname)
     }
-
-    /// Convert a `ProtoError` to an `Error::LookupFailed`.
-    fn proto_error(err: ProtoError) -> Error {
-        Error::LookupFailed(Box::new(err))
+    fn reverse_lookup(&self, addr: impl Into<IpAddr>) -> DnsEntry {
+        self.resolver
+            .inner
+            .reverse_lookup(addr.into(), false, false, &self.policy)
+    }
+    fn reverse_lookup_with_asinfo(&self, addr: impl Into<IpAddr>) -> DnsEntry {
+        self.resolver
+            .inner
+            .reverse_lookup(addr.into(), true, false, &self.policy)
+    }
+    fn lazy_reverse_lookup(&self, addr: impl Into<IpAddr>) -> DnsEntry {
+        self.resolver
+            .inner
+            .reverse_lookup(addr.into(), false, true, &self.policy)
+    }
+    fn lazy_reverse_lookup_with_asinfo(&self, addr: impl Into<IpAddr>) -> DnsEntry {
+        self.resolver
+            .inner
+            .reverse_lookup(addr.into(), true, true, &self.policy)
+    }
+    fn cancel(&self, addr: impl Into<IpAddr>) {
+        self.resolver.cancel(addr);
+    }
+    fn reverse_lookup_with_deadline(
+        &self,
+        addr: impl Into<IpAddr>,
+        deadline: Duration,
+    ) -> DnsEntry {
+        self.resolver
+            .inner
+            .reverse_lookup_with_deadline(addr.into(), deadline, &self.policy)
+    }
+    fn lookup_asinfo(&self, addr: impl Into<IpAddr>) -> AsInfoEntry {
+        self.resolver.inner.lookup_asinfo(addr.into(), &self.policy)
+    }
+    fn lazy_lookup_asinfo(&self, addr: impl Into<IpAddr>) -> AsInfoEntry {
+        self.resolver
+            .inner
+            .lazy_lookup_asinfo(addr.into(), &self.policy)
+    }
+    fn inflight(&self) -> usize {
+        self.resolver.inflight()
+    }
+    fn queue_depth(&self) -> usize {
+        self.resolver.queue_depth()
+    }
+    fn cached_entries(&self) -> Vec<(IpAddr, DnsEntry)> {
+        self.resolver.inner.cached_entries(&self.policy)
+    }
+    fn subscribe(&self) -> Receiver<(IpAddr, DnsEntry)> {
+        self.resolver.inner.subscribe(&self.policy)
     }
 }
+
+/// Private impl of resolver.
+mod inner;
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,2390 @@
+mod cache;
+mod cymru;
+#[cfg(test)]
+mod tests;
+
+use super::{
+    AsLookupSource, CacheRetention, CacheStats, Config, DnsEntryMeta, DnsObserver, IpAddrFamily,
+    LazyDnsEntry, LookupPolicy, ResolveMethod, RetryPolicy, StartupPolicy, TtlBounds,
+    DEFAULT_AS_LOOKUP_ZONE,
+};
+use crate::cache_file::CacheFile;
+use crate::generated_ptr::GeneratedPtr;
+use crate::hosts::{Hosts, DEFAULT_HOSTS_FILE};
+use crate::prefix_map::PrefixMap;
+use crate::resolver::{
+    AsInfo, AsInfoEntry, DnsEntry, DnssecStatus, Error, Rcode, Resolved, ResolvedIpAddrs, Result,
+    TimeoutReason, Unresolved,
+};
+use crate::ripestat;
+use crate::socks::{self, ProxyConfig};
+use cache::{
+    cancel_awaited, complete_if, evict, insert_prefix, is_awaited, latest_records,
+    longest_prefix_match, remove_completed, to_record, with_timeout, Cache, CacheEntry, Cached,
+    PrefixCache,
+};
+use crossbeam::channel::{bounded, unbounded, Receiver, Sender};
+use cymru::lookup_asinfo;
+use dns_lookup::{AddrFamily, AddrInfoHints, SockType};
+use hickory_resolver::config::{
+    LookupIpStrategy, NameServerConfig, NameServerConfigGroup, Protocol, ResolverConfig,
+    ResolverOpts,
+};
+use hickory_resolver::error::{ResolveError, ResolveErrorKind};
+use hickory_resolver::lookup::{Lookup, ReverseLookup};
+use hickory_resolver::lookup_ip::LookupIp;
+use hickory_resolver::name_server::{
+    GenericConnector, NameServerPool, RuntimeProvider, TokioRuntimeProvider,
+};
+use hickory_resolver::proto::error::{ProtoError, ProtoErrorKind};
+use hickory_resolver::proto::iocompat::AsyncIoTokioAsStd;
+use hickory_resolver::proto::op::{Edns, Message, MessageType, OpCode, Query, ResponseCode};
+use hickory_resolver::proto::rr::{RData, RecordType};
+use hickory_resolver::proto::udp::DnsUdpSocket;
+use hickory_resolver::proto::xfer::{
+    DnsHandle, DnsRequest, DnsRequestOptions, DnsResponse, FirstAnswer,
+};
+use hickory_resolver::proto::TokioTime;
+use hickory_resolver::{AsyncResolver, Name};
+use itertools::{Either, Itertools};
+use parking_lot::{Mutex, RwLock};
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::path::Path;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::net::TcpSocket;
+use tokio::runtime::{Builder, Handle, Runtime, RuntimeFlavor};
+use tokio::sync::watch;
+
+/// The maximum number of in-flight reverse DNS resolutions that may be
+const RESOLVER_MAX_QUEUE_SIZE: usize = 100;
+
+/// The duration wait to enqueue a `DnsEntry::Pending` to the resolver before returning
+/// `DnsEntry::Timeout`.
+const RESOLVER_QUEUE_TIMEOUT: Duration = Duration::from_millis(10);
+
+/// The interval at which a pending entry is polled whilst waiting for it to complete.
+const RESOLVER_POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// The largest UDP payload size which may be advertised via EDNS0.
+///
+/// This is the size advertised by the resolver itself, see `EdnsUdpSocket`.
+const MAX_EDNS_UDP_PAYLOAD_SIZE: u16 = 1232;
+
+/// The provider of DNS lookups and the static mappings of the hosts file it consults first.
+#[derive(Clone)]
+enum DnsProvider {
+    TrustDns(Arc<BlockingResolver>, Arc<Hosts>),
+    DnsLookup(Arc<Hosts>),
+}
+
+impl DnsProvider {
+    fn hosts(&self) -> &Hosts {
+        match self {
+            Self::TrustDns(_, hosts) | Self::DnsLookup(hosts) => hosts,
+        }
+    }
+
+    /// Abort all lookups which are in-flight, and fail all future lookups, if supported.
+    fn shutdown(&self) {
+        match self {
+            Self::TrustDns(resolver, _) => resolver.shutdown(),
+            Self::DnsLookup(_) => {}
+        }
+    }
+
+    /// The name server which last answered a reverse lookup of `addr`, if known.
+    fn answered_by(&self, addr: IpAddr) -> Option<SocketAddr> {
+        match self {
+            Self::TrustDns(resolver, _) => resolver.answered_by(addr),
+            Self::DnsLookup(_) => None,
+        }
+    }
+}
+
+/// The provider of `AsInfo` lookups.
+#[derive(Clone)]
+enum AsInfoProvider {
+    /// Lookup via the Team Cymru DNS service, or a compatible service, with the base zone.
+    Cymru(Arc<BlockingResolver>, Arc<str>),
+    /// Lookup via the `RIPEstat` HTTP API.
+    RipeStat(ureq::Agent),
+    /// Lookup via each provider in turn until one supplies the `AsInfo`.
+    Fallback(Vec<Self>),
+    /// `AsInfo` lookups are not supported.
+    Unsupported,
+}
+
+/// A blocking resolver which enforces the connect, query and total timeouts.
+///
+/// Lookups are driven to completion on a runtime, as is done by the
+/// `hickory_resolver::Resolver`, such that the lookup as a whole can be bounded by the total
+/// timeout and aborted on shutdown.  The runtime is either a dedicated runtime with a single
+/// worker thread or a shared multi-threaded runtime, the worker threads of which drive the
+/// connections to the name servers, and so lookups may be performed concurrently from any
+/// number of threads.
+struct BlockingResolver {
+    runtime: BlockingRuntime,
+    async_resolver: TrippyAsyncResolver,
+    /// The resolvers of the preferred and other family, which are queried concurrently for
+    /// forward lookups, if the `LookupIpStrategy` is a fallback ordering.
+    dual_resolvers: Option<(TrippyAsyncResolver, TrippyAsyncResolver)>,
+    /// The name servers queried directly for reverse lookups which request DNSSEC validation,
+    /// if enabled.
+    dnssec_pool: Option<NameServerPool<GenericConnector<TrippyRuntimeProvider>>>,
+    /// The name server which last answered a `PTR` query, by query name.
+    answered_by: AnsweredBy,
+    total_timeout: Duration,
+    shutdown: watch::Sender<bool>,
+}
+
+impl BlockingResolver {
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        config: ResolverConfig,
+        options: ResolverOpts,
+        connect_timeout: Duration,
+        total_timeout: Duration,
+        edns_udp_payload_size: Option<u16>,
+        validate_dnssec: bool,
+        proxy: Option<ProxyConfig>,
+        runtime: Option<&Handle>,
+    ) -> io::Result<Self> {
+        let runtime = match runtime {
+            Some(handle) => BlockingRuntime::Shared(handle.clone()),
+            None => BlockingRuntime::Dedicated(
+                Builder::new_multi_thread()
+                    .worker_threads(1)
+                    .enable_all()
+                    .build()?,
+            ),
+        };
+        let bind_addrs = config
+            .name_servers()
+            .iter()
+            .filter_map(|name_server| Some((name_server.socket_addr, name_server.bind_addr?)))
+            .collect::<HashMap<_, _>>();
+        let answered_by = AnsweredBy::default();
+        let provider = TrippyRuntimeProvider {
+            inner: TokioRuntimeProvider::new(),
+            bind_addrs: Arc::new(bind_addrs),
+            connect_timeout,
+            edns_udp_payload_size,
+            answered_by: answered_by.clone(),
+            proxy: proxy.map(Arc::new),
+        };
+        let dnssec_pool = validate_dnssec.then(|| {
+            NameServerPool::from_config(
+                NameServerConfigGroup::from(config.name_servers().to_vec()),
+                options.clone(),
+                GenericConnector::new(provider.clone()),
+            )
+        });
+        let dual_resolvers = match options.ip_strategy {
+            LookupIpStrategy::Ipv4thenIpv6 => {
+                Some((LookupIpStrategy::Ipv4Only, LookupIpStrategy::Ipv6Only))
+            }
+            LookupIpStrategy::Ipv6thenIpv4 => {
+                Some((LookupIpStrategy::Ipv6Only, LookupIpStrategy::Ipv4Only))
+            }
+            _ => None,
+        }
+        .map(|(preferred, other)| {
+            let resolver = |ip_strategy| {
+                let mut options = options.clone();
+                options.ip_strategy = ip_strategy;
+                AsyncResolver::new(
+                    config.clone(),
+                    options,
+                    GenericConnector::new(provider.clone()),
+                )
+            };
+            (resolver(preferred), resolver(other))
+        });
+        let async_resolver = AsyncResolver::new(config, options, GenericConnector::new(provider));
+        Ok(Self {
+            runtime,
+            async_resolver,
+            dual_resolvers,
+            dnssec_pool,
+            answered_by,
+            total_timeout,
+            shutdown: watch::Sender::new(false),
+        })
+    }
+
+    /// The name server which last answered a reverse lookup of `addr` over UDP, if any.
+    fn answered_by(&self, addr: IpAddr) -> Option<SocketAddr> {
+        let name = Name::from(canonical_addr(addr));
+        self.answered_by.lock().get(&name).copied()
+    }
+
+    /// Abort all lookups which are in-flight and fail all future lookups with
+    /// `Error::Shutdown`.
+    fn shutdown(&self) {
+        self.shutdown.send_replace(true);
+    }
+
+    fn lookup(&self, name: Name, record_type: RecordType) -> Result<Lookup> {
+        self.block_on(self.async_resolver.lookup(name, record_type))
+    }
+
+    /// Forward lookup the addresses of `hostname`, following any `CNAME` chain.
+    ///
+    /// For the fallback orderings both families are queried concurrently, see `IpAddrFamily`.
+    ///
+    /// A lookup which finds no records is checked for a `CNAME` chain which is longer than
+    /// `MAX_CNAME_CHAIN` or which loops, in which case `Error::CnameLoop` is returned.
+    fn lookup_ip(&self, hostname: &str) -> Result<ResolvedIpAddrs> {
+        let res = match &self.dual_resolvers {
+            Some((preferred, other)) => {
+                self.block_on(lookup_ip_concurrent(preferred, other, hostname))
+            }
+            None => self
+                .block_on(self.async_resolver.lookup_ip(hostname))
+                .map(|lookup| resolved_ip_addrs(&lookup)),
+        };
+        match res {
+            Err(err) if is_no_records_found(&err) && self.is_cname_loop(hostname) => {
+                Err(Error::CnameLoop(hostname.to_string()))
+            }
+            res => res,
+        }
+    }
+
+    /// Does the `CNAME` chain of `hostname` loop or exceed `MAX_CNAME_CHAIN` links?
+    fn is_cname_loop(&self, hostname: &str) -> bool {
+        let Ok(mut name) = Name::from_str(hostname) else {
+            return false;
+        };
+        let mut seen = vec![name.clone()];
+        for _ in 0..=MAX_CNAME_CHAIN {
+            let Some(target) = self
+                .lookup(name, RecordType::CNAME)
+                .ok()
+                .and_then(|lookup| lookup.iter().find_map(|rdata| rdata.as_cname().cloned()))
+            else {
+                return false;
+            };
+            if seen.contains(&target.0) {
+                return true;
+            }
+            seen.push(target.0.clone());
+            name = target.0;
+        }
+        true
+    }
+
+    /// Reverse lookup the hostnames of `addr`.
+    ///
+    /// An IPv4-mapped or IPv4-compatible IPv6 `addr` is queried via the `in-addr.arpa` tree
+    /// of the IPv4 address it embeds, rather than the `ip6.arpa` tree.
+    fn reverse_lookup(&self, addr: IpAddr) -> Result<ReverseLookup> {
+        self.block_on(self.async_resolver.reverse_lookup(canonical_addr(addr)))
+    }
+
+    /// Does this resolver request DNSSEC validation of reverse lookups?
+    const fn validates_dnssec(&self) -> bool {
+        self.dnssec_pool.is_some()
+    }
+
+    /// Reverse lookup the hostnames of `addr`, requesting DNSSEC validation.
+    ///
+    /// The upstream resolver is trusted to perform the validation: a response with the `AD`
+    /// bit set is `DnssecStatus::Secure` and one without is `DnssecStatus::Insecure`.  A
+    /// validating resolver answers a response which fails validation with `SERVFAIL`, which
+    /// is indistinguishable from any other failure, and so such a query is retried with the
+    /// `CD` bit set, disabling validation; if the retry is answered then the response is
+    /// `DnssecStatus::Bogus`, otherwise the original failure is returned.
+    ///
+    /// Returns the validation outcome, the hostnames (which are empty if the response is
+    /// bogus or has no `PTR` records) and the `Instant` until which the response is valid.
+    fn reverse_lookup_dnssec(
+        &self,
+        addr: IpAddr,
+    ) -> Result<(DnssecStatus, Vec<String>, Option<Instant>)> {
+        let Some(pool) = &self.dnssec_pool else {
+            return Err(Error::LookupFailed(
+                "DNSSEC validation is not enabled".into(),
+            ));
+        };
+        let name = Name::from(canonical_addr(addr));
+        self.block_on(async {
+            match dnssec_query(pool, name.clone(), false).await {
+                Ok(response) => {
+                    let status = if response.authentic_data() {
+                        DnssecStatus::Secure
+                    } else {
+                        DnssecStatus::Insecure
+                    };
+                    let (hostnames, valid_until) = ptr_hostnames(&response);
+                    Ok((status, hostnames, valid_until))
+                }
+                Err(err) if is_response_code(&err, ResponseCode::ServFail) => {
+                    match dnssec_query(pool, name, true).await {
+                        Ok(_) => Ok((DnssecStatus::Bogus, vec![], None)),
+                        Err(retry_err)
+                            if matches!(
+                                retry_err.kind(),
+                                ResolveErrorKind::NoRecordsFound { .. }
+                            ) && !is_response_code(&retry_err, ResponseCode::ServFail) =>
+                        {
+                            Ok((DnssecStatus::Bogus, vec![], None))
+                        }
+                        Err(_) => Err(err),
+                    }
+                }
+                Err(err) => Err(err),
+            }
+        })
+    }
+
+    /// Run a lookup to completion, failing with `TimeoutReason::Total` if it does not
+    /// complete within the total timeout or with `Error::Shutdown` if it is aborted.
+    fn block_on<T>(
+        &self,
+        lookup: impl Future<Output = std::result::Result<T, ResolveError>>,
+    ) -> Result<T> {
+        let mut shutdown = self.shutdown.subscribe();
+        self.runtime.block_on(async {
+            tokio::select! {
+                res = tokio::time::timeout(self.total_timeout, lookup) => res
+                    .map_err(|_| Error::Timeout(TimeoutReason::Total))?
+                    .map_err(resolve_error),
+                _ = shutdown.wait_for(|shutdown| *shutdown) => Err(Error::Shutdown),
+            }
+        })
+    }
+}
+
+/// The maximum number of `CNAME` records followed from a hostname before the chain is
+/// considered to loop.
+const MAX_CNAME_CHAIN: usize = 8;
+
+/// The addresses and the `CNAME` chain of a forward lookup.
+fn resolved_ip_addrs(lookup: &LookupIp) -> ResolvedIpAddrs {
+    let cnames = lookup
+        .as_lookup()
+        .records()
+        .iter()
+        .filter_map(|record| Some((record.name(), record.data()?.as_cname()?)))
+        .collect::<Vec<_>>();
+    let mut chain = vec![];
+    let mut name = lookup.query().name();
+    while let Some((_, target)) = cnames
+        .iter()
+        .find(|(alias, _)| *alias == name)
+        .filter(|_| chain.len() < MAX_CNAME_CHAIN)
+    {
+        chain.push(target.0.to_utf8().trim_end_matches('.').to_string());
+        name = &target.0;
+    }
+    ResolvedIpAddrs(lookup.iter().collect(), chain)
+}
+
+/// How long to wait for the preferred family to answer once the other family has answered with
+/// addresses, as per the "Resolution Delay" of RFC 8305.
+const RESOLUTION_DELAY: Duration = Duration::from_millis(50);
+
+/// Forward lookup the addresses of `hostname` from the `preferred` and `other` resolvers
+/// concurrently.
+///
+/// The addresses of the preferred family are returned if it answers with any before the
+/// resolution delay after the other family answers with addresses, otherwise the addresses of
+/// the other family, if any, are returned.  The lookups are spawned on the runtime so that
+/// the one which does not supply the result runs to completion and populates the cache.
+async fn lookup_ip_concurrent(
+    preferred: &TrippyAsyncResolver,
+    other: &TrippyAsyncResolver,
+    hostname: &str,
+) -> std::result::Result<ResolvedIpAddrs, ResolveError> {
+    let preferred = spawn_lookup_ip(preferred, hostname);
+    let other = spawn_lookup_ip(other, hostname);
+    tokio::pin!(preferred, other);
+    tokio::select! {
+        biased;
+        res = &mut preferred => match res {
+            Ok(addrs) if !addrs.0.is_empty() => Ok(addrs),
+            res => first_with_addrs(res, other.await),
+        },
+        res = &mut other => match res {
+            Ok(addrs) if !addrs.0.is_empty() => {
+                match tokio::time::timeout(RESOLUTION_DELAY, preferred).await {
+                    Ok(Ok(preferred)) if !preferred.0.is_empty() => Ok(preferred),
+                    _ => Ok(addrs),
+                }
+            }
+            res => first_with_addrs(preferred.await, res),
+        },
+    }
+}
+
+/// Spawn a forward lookup of the addresses of `hostname` on the current runtime.
+fn spawn_lookup_ip(
+    resolver: &TrippyAsyncResolver,
+    hostname: &str,
+) -> impl Future<Output = std::result::Result<ResolvedIpAddrs, ResolveError>> {
+    let resolver = resolver.clone();
+    let hostname = hostname.to_string();
+    let lookup = tokio::spawn(async move {
+        resolver
+            .lookup_ip(hostname)
+            .await
+            .map(|lookup| resolved_ip_addrs(&lookup))
+    });
+    async move {
+        lookup
+            .await
+            .unwrap_or_else(|err| Err(ResolveError::from(err.to_string())))
+    }
+}
+
+/// The result of the `preferred` lookup if it has addresses or the `other` lookup has none,
+/// otherwise the result of the `other` lookup.
+fn first_with_addrs(
+    preferred: std::result::Result<ResolvedIpAddrs, ResolveError>,
+    other: std::result::Result<ResolvedIpAddrs, ResolveError>,
+) -> std::result::Result<ResolvedIpAddrs, ResolveError> {
+    match (preferred, other) {
+        (Ok(addrs), _) | (_, Ok(addrs)) if !addrs.0.is_empty() => Ok(addrs),
+        (preferred, _) => preferred,
+    }
+}
+
+/// Send a `PTR` query for `name` with the DNSSEC OK bit set and, if `checking_disabled`, the
+/// `CD` bit set.
+async fn dnssec_query(
+    pool: &NameServerPool<GenericConnector<TrippyRuntimeProvider>>,
+    name: Name,
+    checking_disabled: bool,
+) -> std::result::Result<DnsResponse, ResolveError> {
+    let mut message = Message::new();
+    message
+        .add_query(Query::query(name, RecordType::PTR))
+        .set_message_type(MessageType::Query)
+        .set_op_code(OpCode::Query)
+        .set_recursion_desired(true)
+        .set_authentic_data(true)
+        .set_checking_disabled(checking_disabled);
+    message
+        .extensions_mut()
+        .get_or_insert_with(Edns::new)
+        .set_max_payload(MAX_EDNS_UDP_PAYLOAD_SIZE)
+        .set_version(0)
+        .set_dnssec_ok(true);
+    let mut options = DnsRequestOptions::default();
+    options.use_edns = true;
+    pool.send(DnsRequest::new(message, options))
+        .first_answer()
+        .await
+}
+
+/// The hostnames of the `PTR` records of a response, and the `Instant` until which they are
+/// valid as per the smallest TTL of those records, if any.
+fn ptr_hostnames(response: &DnsResponse) -> (Vec<String>, Option<Instant>) {
+    let now = Instant::now();
+    let (hostnames, ttls): (Vec<_>, Vec<_>) = response
+        .answers()
+        .iter()
+        .filter_map(|record| match record.data() {
+            Some(RData::PTR(ptr)) => Some((ptr.0.to_ascii(), record.ttl())),
+            _ => None,
+        })
+        .unzip();
+    let valid_until = ttls
+        .into_iter()
+        .min()
+        .map(|ttl| now + Duration::from_secs(u64::from(ttl)));
+    (hostnames, valid_until)
+}
+
+/// Did a query fail with the given `ResponseCode`?
+fn is_response_code(err: &ResolveError, code: ResponseCode) -> bool {
+    matches!(
+        err.kind(),
+        ResolveErrorKind::NoRecordsFound { response_code, .. } if *response_code == code
+    )
+}
+
+/// The tokio runtime on which the lookups of a `BlockingResolver` are driven.
+enum BlockingRuntime {
+    /// A runtime created for, and owned by, the resolver.
+    Dedicated(Runtime),
+    /// A runtime shared with the application.
+    Shared(Handle),
+}
+
+impl BlockingRuntime {
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        match self {
+            Self::Dedicated(runtime) => runtime.block_on(future),
+            Self::Shared(handle) => handle.block_on(future),
+        }
+    }
+}
+
+/// An `AsyncResolver` which connects to name servers via the `TrippyRuntimeProvider`.
+type TrippyAsyncResolver = AsyncResolver<GenericConnector<TrippyRuntimeProvider>>;
+
+/// The name server which last answered a `PTR` query, by query name.
+type AnsweredBy = Arc<Mutex<HashMap<Name, SocketAddr>>>;
+
+/// A `RuntimeProvider` which bounds the time taken to establish a TCP connection to a name
+/// server, which sets the UDP payload size advertised by the EDNS0 `OPT` record, if any,
+/// of each query sent over UDP and which records the name server which answered each `PTR`
+/// query received over UDP.
+///
+/// The underlying resolver ignores the local address to which each name server is bound and
+/// so the `bind_addrs`, by name server address, are applied here.  The port of a bind address,
+/// if not zero, is used for UDP sockets only.  If a SOCKS5 `proxy` is set
+/// then TCP connections are made via the proxy, without binding, bounded by the same connect
+/// timeout, and binding a UDP socket fails.
+#[derive(Clone)]
+struct TrippyRuntimeProvider {
+    inner: TokioRuntimeProvider,
+    bind_addrs: Arc<HashMap<SocketAddr, SocketAddr>>,
+    connect_timeout: Duration,
+    edns_udp_payload_size: Option<u16>,
+    answered_by: AnsweredBy,
+    proxy: Option<Arc<ProxyConfig>>,
+}
+
+impl RuntimeProvider for TrippyRuntimeProvider {
+    type Handle = <TokioRuntimeProvider as RuntimeProvider>::Handle;
+    type Timer = <TokioRuntimeProvider as RuntimeProvider>::Timer;
+    type Udp = EdnsUdpSocket;
+    type Tcp = <TokioRuntimeProvider as RuntimeProvider>::Tcp;
+
+    fn create_handle(&self) -> Self::Handle {
+        self.inner.create_handle()
+    }
+
+    fn connect_tcp(
+        &self,
+        server_addr: SocketAddr,
+    ) -> Pin<Box<dyn Send + Future<Output = io::Result<Self::Tcp>>>> {
+        let connect = match self.proxy.clone() {
+            Some(proxy) => Box::pin(async move {
+                socks::connect(&proxy, server_addr)
+                    .await
+                    .map(AsyncIoTokioAsStd)
+            }),
+            None => match self.bind_addrs.get(&server_addr).copied() {
+                Some(bind_addr) => Box::pin(async move {
+                    let socket = match server_addr {
+                        SocketAddr::V4(_) => TcpSocket::new_v4()?,
+                        SocketAddr::V6(_) => TcpSocket::new_v6()?,
+                    };
+                    socket.bind(SocketAddr::new(bind_addr.ip(), 0))?;
+                    socket.connect(server_addr).await.map(AsyncIoTokioAsStd)
+                }),
+                None => self.inner.connect_tcp(server_addr),
+            },
+        };
+        let connect_timeout = self.connect_timeout;
+        Box::pin(async move {
+            tokio::time::timeout(connect_timeout, connect)
+                .await
+                .unwrap_or_else(|_| {
+                    Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        format!("connection to {server_addr} timed out"),
+                    ))
+                })
+        })
+    }
+
+    fn bind_udp(
+        &self,
+        local_addr: SocketAddr,
+        server_addr: SocketAddr,
+    ) -> Pin<Box<dyn Send + Future<Output = io::Result<Self::Udp>>>> {
+        if self.proxy.is_some() {
+            return Box::pin(async move {
+                Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!("UDP to {server_addr} is not supported via a SOCKS5 proxy"),
+                ))
+            });
+        }
+        let local_addr = match self.bind_addrs.get(&server_addr) {
+            Some(bind_addr) if bind_addr.port() == 0 => {
+                SocketAddr::new(bind_addr.ip(), local_addr.port())
+            }
+            Some(bind_addr) => *bind_addr,
+            None => local_addr,
+        };
+        let bind = self.inner.bind_udp(local_addr, server_addr);
+        let edns_udp_payload_size = self.edns_udp_payload_size;
+        let answered_by = self.answered_by.clone();
+        Box::pin(async move {
+            Ok(EdnsUdpSocket {
+                inner: bind.await?,
+                edns_udp_payload_size,
+                answered_by,
+            })
+        })
+    }
+}
+
+/// A UDP socket which rewrites the UDP payload size advertised by the EDNS0 `OPT` record, if
+/// any, of each query it sends and records the name server which answered each `PTR` query.
+///
+/// The resolver always advertises a UDP payload size of 1232 bytes and sizes the buffer into
+/// which the response is received to match, and so a smaller size may be advertised but not
+/// a larger one.
+struct EdnsUdpSocket {
+    inner: tokio::net::UdpSocket,
+    edns_udp_payload_size: Option<u16>,
+    answered_by: AnsweredBy,
+}
+
+impl DnsUdpSocket for EdnsUdpSocket {
+    type Time = TokioTime;
+
+    fn poll_recv_from(
+        &self,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<(usize, SocketAddr)>> {
+        let res = DnsUdpSocket::poll_recv_from(&self.inner, cx, buf);
+        if let Poll::Ready(Ok((len, server))) = &res {
+            if let Some(name) = ptr_response_name(&buf[..*len]) {
+                self.answered_by.lock().insert(name, *server);
+            }
+        }
+        res
+    }
+
+    fn poll_send_to(
+        &self,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+        target: SocketAddr,
+    ) -> Poll<io::Result<usize>> {
+        match self
+            .edns_udp_payload_size
+            .and_then(|size| with_edns_udp_payload_size(buf, size))
+        {
+            Some(query) => DnsUdpSocket::poll_send_to(&self.inner, cx, &query, target)
+                .map_ok(|sent| if sent == query.len() { buf.len() } else { sent }),
+            None => DnsUdpSocket::poll_send_to(&self.inner, cx, buf, target),
+        }
+    }
+}
+
+/// The query name of an encoded `PTR` response, if it can be decoded.
+fn ptr_response_name(response: &[u8]) -> Option<Name> {
+    let message = Message::from_vec(response).ok()?;
+    let query = message.queries().first()?;
+    (message.message_type() == MessageType::Response && query.query_type() == RecordType::PTR)
+        .then(|| query.name().clone())
+}
+
+/// Rewrite the UDP payload size advertised by the EDNS0 `OPT` record of an encoded query.
+///
+/// The size is clamped to between 512 and `MAX_EDNS_UDP_PAYLOAD_SIZE` bytes.  Returns `None`
+/// if the query cannot be decoded or has no `OPT` record.
+fn with_edns_udp_payload_size(query: &[u8], size: u16) -> Option<Vec<u8>> {
+    let mut message = Message::from_vec(query).ok()?;
+    message
+        .extensions_mut()
+        .as_mut()?
+        .set_max_payload(size.clamp(512, MAX_EDNS_UDP_PAYLOAD_SIZE));
+    message.to_vec().ok()
+}
+
+#[derive(Debug, Clone)]
+pub enum DnsResolveRequest {
+    /// Resolve the hostnames for an `IpAddr`.
+    Hostnames(IpAddr),
+    /// Resolve the `AsInfo` for an `IpAddr`.
+    AsInfo(IpAddr),
+}
+
+/// Resolver implementation.
+pub struct DnsResolver {
+    backend: RwLock<Backend>,
+    runtime: Option<Handle>,
+    generation: Arc<AtomicU64>,
+    addr_cache: Cache<DnsEntry>,
+    asinfo_cache: Cache<AsInfoEntry>,
+    prefix_cache: PrefixCache,
+    subscribers: Subscribers,
+}
+
+/// The subscribers to the completion of lazy reverse DNS lookups.
+///
+/// This is shared by every worker of a resolver such that subscriptions survive a
+/// reconfiguration.
+type Subscribers = Arc<Mutex<Vec<Subscriber>>>;
+
+/// The sender of a subscriber and the `LookupPolicy` which governs what is sent to it.
+type Subscriber = (Sender<(IpAddr, DnsEntry)>, LookupPolicy);
+
+/// The `Config` and the providers and worker of the current resolver.
+///
+/// This is replaced when the resolver is reconfigured.  The `worker` is `None` once the
+/// resolver has been shut down.
+struct Backend {
+    config: Config,
+    provider: DnsProvider,
+    asinfo_provider: AsInfoProvider,
+    worker: Option<Worker>,
+}
+
+/// The resolve queue and threads of a resolver worker.
+struct Worker {
+    tx: Sender<DnsResolveRequest>,
+    handles: Vec<JoinHandle<()>>,
+    metrics: Arc<WorkerMetrics>,
+}
+
+/// The number of requests which are queued, throttled and in-flight, in a resolver worker.
+#[derive(Debug, Default)]
+struct WorkerMetrics {
+    queued: AtomicUsize,
+    throttled: AtomicUsize,
+    inflight: AtomicUsize,
+}
+
+/// The rate limit of the lookups of a resolver worker, shared by all of its threads.
+///
+/// This is a token bucket with a capacity of a single token, such that lookups are spaced
+/// evenly, at most one per `interval`, without any initial burst.
+struct RateLimiter {
+    interval: Duration,
+    next: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    fn new(per_sec: u32) -> Self {
+        Self {
+            interval: Duration::from_secs(1) / per_sec.max(1),
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Wait until the next lookup may start.
+    fn acquire(&self) {
+        let now = Instant::now();
+        let slot = {
+            let mut next = self.next.lock();
+            let slot = (*next).max(now);
+            *next = slot + self.interval;
+            slot
+        };
+        thread::sleep(slot.saturating_duration_since(now));
+    }
+}
+
+impl DnsResolver {
+    /// Start a resolver which drives lookups on the given tokio `runtime`, or on a dedicated
+    /// runtime if `None`.
+    pub fn start(config: Config, runtime: Option<Handle>) -> std::io::Result<Self> {
+        if runtime
+            .as_ref()
+            .is_some_and(|handle| handle.runtime_flavor() == RuntimeFlavor::CurrentThread)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the DNS resolver requires a multi-threaded tokio runtime",
+            ));
+        }
+        let provider = make_provider(&config, runtime.as_ref())?;
+        let mut resolver = Self::with_provider(config, provider);
+        resolver.runtime = runtime;
+        Ok(resolver)
+    }
+
+    /// Create a resolver which performs lookups via the `provider`.
+    ///
+    /// The cache is loaded from the cache file of the `config`, if any.
+    fn with_provider(config: Config, provider: DnsProvider) -> Self {
+        let addr_cache = Arc::new(RwLock::new(HashMap::new()));
+        let asinfo_cache = Arc::new(RwLock::new(HashMap::new()));
+        let prefix_cache = Arc::new(RwLock::new(PrefixMap::new()));
+        let generation = Arc::new(AtomicU64::new(0));
+        let subscribers = Arc::new(Mutex::new(Vec::new()));
+        let asinfo_provider = make_asinfo_provider(&config, &provider);
+        let worker = spawn_worker(
+            &config,
+            &provider,
+            &asinfo_provider,
+            &addr_cache,
+            &asinfo_cache,
+            &prefix_cache,
+            &subscribers,
+            &generation,
+            0,
+        );
+        let cache_file = config.cache_file.clone().filter(|_| config.caching);
+        let max_entries = config.max_cache_entries;
+        let resolver = Self {
+            backend: RwLock::new(Backend {
+                config,
+                provider,
+                asinfo_provider,
+                worker: Some(worker),
+            }),
+            runtime: None,
+            generation,
+            addr_cache,
+            asinfo_cache,
+            prefix_cache,
+            subscribers,
+        };
+        if let Some(path) = cache_file {
+            if let Err(err) = resolver.read_cache_file(&path, max_entries) {
+                tracing::warn!(%err, "ignoring DNS cache file");
+            }
+        }
+        resolver
+    }
+
+    /// Load the entries of the cache file at `path` which have not expired into the cache.
+    ///
+    /// At most `max_cache_entries` of the most recently updated entries are loaded into each
+    /// cache.
+    pub fn load_cache(&self, path: &Path) -> io::Result<()> {
+        let max_entries = self.backend.read().config.max_cache_entries;
+        self.read_cache_file(path, max_entries)
+    }
+
+    /// Save the completed entries of the cache to the cache file at `path`.
+    pub fn save_cache(&self, path: &Path) -> io::Result<()> {
+        self.write_cache_file(path)
+    }
+
+    /// Load the entries of the cache file at `path` which have not expired into the cache.
+    ///
+    /// At most `max_entries` of the most recently updated entries are loaded into each cache.
+    /// The prefix of each `AsInfo` is added to the prefix cache.
+    fn read_cache_file(&self, path: &Path, max_entries: Option<usize>) -> io::Result<()> {
+        let cache_file = CacheFile::read(path)?;
+        let now = Instant::now();
+        let wall_now = SystemTime::now();
+        // the expiry of a record as an `Instant`, or `None` if the record has expired.
+        let expiry = |expires: Option<SystemTime>| match expires {
+            Some(expires) => expires
+                .duration_since(wall_now)
+                .ok()
+                .filter(|remaining| !remaining.is_zero())
+                .map(|remaining| Some(now + remaining)),
+            None => Some(None),
+        };
+        let mut addr_cache = self.addr_cache.write();
+        for record in latest_records(cache_file.hostnames, max_entries) {
+            let Some(expires) = expiry(record.expires) else {
+                continue;
+            };
+            let entry = if record.value.is_empty() {
+                DnsEntry::NotFound(Unresolved::Normal(record.addr))
+            } else {
+                DnsEntry::Resolved(Resolved::Normal(record.addr, record.value))
+            };
+            addr_cache.insert(
+                record.addr,
+                Cached {
+                    updated: record.updated,
+                    expires,
+                    ..Cached::new(entry)
+                },
+            );
+        }
+        drop(addr_cache);
+        let mut asinfo_cache = self.asinfo_cache.write();
+        for record in latest_records(cache_file.asinfo, max_entries) {
+            let Some(expires) = expiry(record.expires) else {
+                continue;
+            };
+            let entry = match record.value {
+                Some(asinfo) => {
+                    insert_prefix(&self.prefix_cache, record.addr, &asinfo, expires);
+                    AsInfoEntry::Resolved(asinfo)
+                }
+                None => AsInfoEntry::NotFound,
+            };
+            asinfo_cache.insert(
+                record.addr,
+                Cached {
+                    updated: record.updated,
+                    expires,
+                    ..Cached::new(entry)
+                },
+            );
+        }
+        drop(asinfo_cache);
+        Ok(())
+    }
+
+    /// Save the completed entries of the cache, which were resolved or which did not match
+    /// any records, to the cache file at `path`.
+    fn write_cache_file(&self, path: &Path) -> io::Result<()> {
+        let hostnames = self
+            .addr_cache
+            .read()
+            .iter()
+            .filter_map(|(addr, cached)| match &cached.entry {
+                DnsEntry::Resolved(Resolved::Normal(_, hostnames)) => {
+                    Some(to_record(*addr, cached, hostnames.clone()))
+                }
+                DnsEntry::NotFound(Unresolved::Normal(_)) => Some(to_record(*addr, cached, vec![])),
+                _ => None,
+            })
+            .collect();
+        let asinfo = self
+            .asinfo_cache
+            .read()
+            .iter()
+            .filter_map(|(addr, cached)| match &cached.entry {
+                AsInfoEntry::Resolved(asinfo) => {
+                    Some(to_record(*addr, cached, Some(asinfo.clone())))
+                }
+                AsInfoEntry::NotFound => Some(to_record(*addr, cached, None)),
+                _ => None,
+            })
+            .collect();
+        CacheFile { hostnames, asinfo }.write(path)
+    }
+
+    /// Replace the provider and worker with ones created from a new `Config`.
+    ///
+    /// Any lookups which are in-flight in the current worker are discarded, and any entries
+    /// for such lookups are removed from the cache, such that they are enqueued to the new
+    /// worker on their next lazy lookup.  Completed entries are retained or cleared as per the
+    /// `CacheRetention`.
+    ///
+    /// If a provider cannot be created from the new `Config` then the current provider and
+    /// worker remain in use.  The resolver cannot be reconfigured once it has been shut down.
+    pub fn reconfigure(&self, config: Config, retention: CacheRetention) -> io::Result<()> {
+        let provider = make_provider(&config, self.runtime.as_ref())?;
+        let asinfo_provider = make_asinfo_provider(&config, &provider);
+        let mut backend = self.backend.write();
+        if backend.worker.is_none() {
+            return Err(io::Error::other("the DNS resolver has been shut down"));
+        }
+        backend.provider.shutdown();
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        match retention {
+            CacheRetention::Retain => {
+                // a refresh in progress in the current worker is discarded, and so the
+                // entry is refreshed again on its next lazy lookup.
+                self.addr_cache.write().retain(|_, cached| {
+                    cached.refreshing = false;
+                    matches!(cached.entry, DnsEntry::Resolved(_) | DnsEntry::NotFound(_))
+                });
+                self.asinfo_cache.write().retain(|_, cached| {
+                    matches!(
+                        cached.entry,
+                        AsInfoEntry::Resolved(_) | AsInfoEntry::NotFound
+                    )
+                });
+            }
+            CacheRetention::Clear => {
+                self.addr_cache.write().clear();
+                self.asinfo_cache.write().clear();
+                self.prefix_cache.write().clear();
+            }
+        }
+        if !config.caching {
+            self.prefix_cache.write().clear();
+        }
+        let worker = spawn_worker(
+            &config,
+            &provider,
+            &asinfo_provider,
+            &self.addr_cache,
+            &self.asinfo_cache,
+            &self.prefix_cache,
+            &self.subscribers,
+            &self.generation,
+            generation,
+        );
+        *backend = Backend {
+            config,
+            provider,
+            asinfo_provider,
+            worker: Some(worker),
+        };
+        drop(backend);
+        Ok(())
+    }
+
+    /// Shutdown the resolver and wait for the worker threads to finish.
+    pub fn shutdown(&self) {
+        for handle in self.stop() {
+            // the worker threads do not panic and so the result is ignored.
+            let _ = handle.join();
+        }
+    }
+
+    /// Stop the worker, if not already stopped, and return the handles of its threads.
+    ///
+    /// The worker is superseded, such that it discards the result of any lookup in progress,
+    /// the in-flight lookup, if any, is aborted and the resolve queue is closed.  The cache is
+    /// then saved to the cache file, if any.
+    fn stop(&self) -> Vec<JoinHandle<()>> {
+        let mut backend = self.backend.write();
+        let Some(worker) = backend.worker.take() else {
+            return vec![];
+        };
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        backend.provider.shutdown();
+        let cache_file = backend
+            .config
+            .cache_file
+            .clone()
+            .filter(|_| backend.config.caching);
+        drop(backend);
+        if let Some(path) = cache_file {
+            if let Err(err) = self.write_cache_file(&path) {
+                tracing::warn!(%err, "failed to write DNS cache file");
+            }
+        }
+        let Worker { tx, handles, .. } = worker;
+        drop(tx);
+        handles
+    }
+
+    pub fn config(&self) -> Config {
+        self.backend.read().config.clone()
+    }
+
+    pub fn inflight(&self) -> usize {
+        self.worker_metric(|metrics| &metrics.inflight)
+    }
+
+    pub fn queue_depth(&self) -> usize {
+        self.worker_metric(|metrics| &metrics.queued)
+    }
+
+    /// Read a metric of the current worker, if any.
+    fn worker_metric(&self, metric: impl Fn(&WorkerMetrics) -> &AtomicUsize) -> usize {
+        self.backend
+            .read()
+            .worker
+            .as_ref()
+            .map_or(0, |worker| metric(&worker.metrics).load(Ordering::SeqCst))
+    }
+
+    /// Cancel the pending lookups, if any, of the hostnames and `AsInfo` of `addr`.
+    pub fn cancel(&self, addr: IpAddr) {
+        let addr = canonical_addr(addr);
+        cancel_awaited(&self.addr_cache, addr);
+        cancel_awaited(&self.asinfo_cache, addr);
+    }
+
+    pub fn entry_metadata(&self, addr: IpAddr) -> Option<DnsEntryMeta> {
+        let addr = canonical_addr(addr);
+        self.addr_cache
+            .read()
+            .get(&addr)
+            .map(|cached| DnsEntryMeta {
+                pending: cached.entry.is_pending(),
+                updated: cached.updated,
+                elapsed: cached.elapsed,
+                dnssec: cached.dnssec,
+                server: cached.server,
+            })
+    }
+
+    pub fn raw_hostnames(&self, addr: IpAddr) -> Option<Vec<String>> {
+        let addr = canonical_addr(addr);
+        self.addr_cache
+            .read()
+            .get(&addr)
+            .and_then(|cached| cached.raw_hostnames.clone())
+    }
+
+    pub fn cache_stats(&self) -> CacheStats {
+        let stats = CacheStats {
+            throttled: self.worker_metric(|metrics| &metrics.throttled),
+            ..CacheStats::default()
+        };
+        self.addr_cache
+            .read()
+            .values()
+            .fold(stats, |mut stats, cached| {
+                match cached.entry {
+                    DnsEntry::Pending(_) => stats.pending += 1,
+                    DnsEntry::Resolved(_) => stats.resolved += 1,
+                    DnsEntry::NotFound(_) => stats.not_found += 1,
+                    DnsEntry::Failed(..) => stats.failed += 1,
+                    DnsEntry::Timeout(_) => stats.timeout += 1,
+                    DnsEntry::Bogus(_) => stats.bogus += 1,
+                }
+                stats
+            })
+    }
+
+    /// Subscribe to the completion of lazy reverse DNS lookups of the addresses which are not
+    /// suppressed by a `LookupPolicy`.
+    pub fn subscribe(&self, policy: &LookupPolicy) -> Receiver<(IpAddr, DnsEntry)> {
+        let (tx, rx) = unbounded();
+        self.subscribers.lock().push((tx, policy.clone()));
+        rx
+    }
+
+    /// A snapshot of the cached reverse DNS lookup entries as governed by a `LookupPolicy`.
+    ///
+    /// The entries of suppressed addresses are omitted and `AS` information is only included
+    /// if the policy allows it.
+    pub fn cached_entries(&self, policy: &LookupPolicy) -> Vec<(IpAddr, DnsEntry)> {
+        let asinfos: HashMap<_, _> = if policy.asinfo {
+            self.asinfo_cache
+                .read()
+                .iter()
+                .filter(|(_, cached)| matches!(cached.entry, AsInfoEntry::Resolved(_)))
+                .map(|(addr, cached)| (*addr, cached.entry.clone()))
+                .collect()
+        } else {
+            HashMap::new()
+        };
+        self.addr_cache
+            .read()
+            .iter()
+            .filter(|(addr, _)| !policy.is_suppressed(**addr))
+            .map(|(addr, cached)| match asinfos.get(addr) {
+                Some(asinfo) => (
+                    *addr,
+                    with_asinfo_entry(cached.entry.clone(), asinfo.clone()),
+                ),
+                None => (*addr, cached.entry.clone()),
+            })
+            .sorted_by_key(|(addr, _)| *addr)
+            .collect()
+    }
+
+    pub fn lookup(&self, hostname: &str) -> Result<ResolvedIpAddrs> {
+        let Backend {
+            config,
+            provider,
+            worker,
+            ..
+        } = &*self.backend.read();
+        if worker.is_none() {
+            return Err(Error::Shutdown);
+        }
+        if let Some(addrs) = provider
+            .hosts()
+            .lookup(hostname)
+            .map(|addrs| select_addr_family(config.addr_family, addrs.to_vec()))
+            .filter(|addrs| !addrs.is_empty())
+        {
+            return Ok(ResolvedIpAddrs(addrs, vec![]));
+        }
+        match provider {
+            DnsProvider::TrustDns(resolver, _) => resolver.lookup_ip(hostname),
+            DnsProvider::DnsLookup(_) => lookup_host(hostname, config.addr_family)
+                .map(|addrs| ResolvedIpAddrs(addrs, vec![])),
+        }
+    }
+
+    pub fn reverse_lookup(
+        &self,
+        addr: IpAddr,
+        with_asinfo: bool,
+        lazy: bool,
+        policy: &LookupPolicy,
+    ) -> DnsEntry {
+        let addr = canonical_addr(addr);
+        if policy.is_suppressed(addr) {
+            DnsEntry::NotFound(Unresolved::Normal(addr))
+        } else if lazy {
+            self.lazy_reverse_lookup(addr, with_asinfo, policy)
+                .into_entry()
+        } else {
+            let backend = self.backend.read();
+            if backend.worker.is_none() {
+                return DnsEntry::Failed(addr, None);
+            }
+            let provider = backend.provider.clone();
+            let asinfo_provider = backend.asinfo_provider.clone();
+            let trim_trailing_dot = backend.config.trim_trailing_dot;
+            let decode_idn = backend.config.decode_idn;
+            let generated_ptr = GeneratedPtr::from_config(&backend.config);
+            drop(backend);
+            reverse_lookup(
+                &provider,
+                &asinfo_provider,
+                addr,
+                with_asinfo && policy.asinfo,
+                trim_trailing_dot,
+                decode_idn,
+                generated_ptr.as_ref(),
+            )
+        }
+    }
+
+    /// Perform a lazy reverse DNS lookup of `addr`, as governed by a `LookupPolicy`, and wait
+    /// at most `deadline` for it to complete.
+    ///
+    /// A lookup which is still pending once the `deadline` has elapsed is returned as
+    /// `DnsEntry::Timeout` and remains pending in the cache.
+    pub fn reverse_lookup_with_deadline(
+        &self,
+        addr: IpAddr,
+        deadline: Duration,
+        policy: &LookupPolicy,
+    ) -> DnsEntry {
+        let addr = canonical_addr(addr);
+        let start = Instant::now();
+        loop {
+            match self.lazy_reverse_lookup(addr, false, policy).into_entry() {
+                DnsEntry::Pending(_) => {
+                    let remaining = deadline.saturating_sub(start.elapsed());
+                    if remaining.is_zero() {
+                        return DnsEntry::Timeout(addr);
+                    }
+                    thread::sleep(remaining.min(RESOLVER_POLL_INTERVAL));
+                }
+                entry => return entry,
+            }
+        }
+    }
+
+    /// Perform a lazy reverse DNS lookup of `addr` as governed by a `LookupPolicy`.
+    ///
+    /// The policy is applied to the entries read from the shared cache and never to the cache
+    /// itself, see `LookupPolicy`.
+    pub fn lazy_reverse_lookup(
+        &self,
+        addr: IpAddr,
+        with_asinfo: bool,
+        policy: &LookupPolicy,
+    ) -> LazyDnsEntry {
+        let addr = canonical_addr(addr);
+        if policy.is_suppressed(addr) {
+            return LazyDnsEntry {
+                entry: DnsEntry::NotFound(Unresolved::Normal(addr)),
+                cached: false,
+            };
+        }
+        // The backend is held for the duration such that a lookup cannot be enqueued to a
+        // worker which has been superseded by a concurrent `reconfigure`.
+        let backend = self.backend.read();
+        let Some(worker) = &backend.worker else {
+            return LazyDnsEntry {
+                entry: DnsEntry::Failed(addr, None),
+                cached: false,
+            };
+        };
+        let max_entries = backend.config.max_cache_entries;
+        let caching = backend.config.caching;
+        let (dns_entry, dns_cached) = lazy_lookup(
+            worker,
+            &self.addr_cache,
+            max_entries,
+            addr,
+            DnsResolveRequest::Hostnames(addr),
+            backend.config.observer.as_deref(),
+        );
+        let asinfo_entry = match &backend.asinfo_provider {
+            AsInfoProvider::Cymru(..)
+            | AsInfoProvider::RipeStat(_)
+            | AsInfoProvider::Fallback(_)
+                if with_asinfo && policy.asinfo =>
+            {
+                Some(self.lazy_asinfo_entry(worker, &backend.config, addr))
+            }
+            _ => None,
+        };
+        drop(backend);
+        // without caching, the result is discarded once it is complete and has been read.
+        let complete = !dns_entry.entry.is_pending()
+            && asinfo_entry
+                .as_ref()
+                .map_or(true, |(entry, _)| !entry.entry.is_pending());
+        if !caching && complete {
+            remove_completed(&self.addr_cache, addr);
+            remove_completed(&self.asinfo_cache, addr);
+        }
+        let dns_entry = with_timeout(addr, dns_entry, policy.timeout);
+        match asinfo_entry {
+            Some((asinfo_entry, asinfo_cached)) => LazyDnsEntry {
+                entry: with_asinfo_entry(
+                    dns_entry,
+                    with_timeout(addr, asinfo_entry, policy.timeout),
+                ),
+                cached: dns_cached && asinfo_cached,
+            },
+            None => LazyDnsEntry {
+                entry: dns_entry,
+                cached: dns_cached,
+            },
+        }
+    }
+
+    /// Perform a lazy `AsInfo` lookup of `addr`, without a reverse DNS lookup of its
+    /// hostnames, as governed by a `LookupPolicy`.
+    pub fn lazy_lookup_asinfo(&self, addr: IpAddr, policy: &LookupPolicy) -> AsInfoEntry {
+        let addr = canonical_addr(addr);
+        if policy.is_suppressed(addr) || !policy.asinfo {
+            return AsInfoEntry::NotFound;
+        }
+        let backend = self.backend.read();
+        let Some(worker) = &backend.worker else {
+            return AsInfoEntry::Failed;
+        };
+        if matches!(backend.asinfo_provider, AsInfoProvider::Unsupported) {
+            return AsInfoEntry::NotFound;
+        }
+        let caching = backend.config.caching;
+        let (asinfo_entry, _) = self.lazy_asinfo_entry(worker, &backend.config, addr);
+        drop(backend);
+        if !caching && !asinfo_entry.entry.is_pending() {
+            remove_completed(&self.asinfo_cache, addr);
+        }
+        with_timeout(addr, asinfo_entry, policy.timeout)
+    }
+
+    /// Perform a blocking `AsInfo` lookup of `addr`, without a reverse DNS lookup of its
+    /// hostnames, as governed by a `LookupPolicy`.
+    pub fn lookup_asinfo(&self, addr: IpAddr, policy: &LookupPolicy) -> AsInfoEntry {
+        let addr = canonical_addr(addr);
+        if policy.is_suppressed(addr) || !policy.asinfo {
+            return AsInfoEntry::NotFound;
+        }
+        let backend = self.backend.read();
+        if backend.worker.is_none() {
+            return AsInfoEntry::Failed;
+        }
+        let asinfo_provider = backend.asinfo_provider.clone();
+        drop(backend);
+        lookup_asinfo_entry(&asinfo_provider, addr)
+    }
+
+    /// Perform a lazy lookup of the `AsInfo` of `addr` in the `AsInfo` cache.
+    ///
+    /// The `AsInfo` of an `addr` within a prefix held by the prefix cache is served from it,
+    /// unless caching is disabled, without a lookup.
+    ///
+    /// Returns the entry and whether it was served from a cache, see `lazy_lookup`.
+    fn lazy_asinfo_entry(
+        &self,
+        worker: &Worker,
+        config: &Config,
+        addr: IpAddr,
+    ) -> (Cached<AsInfoEntry>, bool) {
+        let by_prefix = longest_prefix_match(&self.prefix_cache, addr, config.caching);
+        by_prefix.map_or_else(
+            || {
+                lazy_lookup(
+                    worker,
+                    &self.asinfo_cache,
+                    config.max_cache_entries,
+                    addr,
+                    DnsResolveRequest::AsInfo(addr),
+                    None,
+                )
+            },
+            |(asinfo, _)| (Cached::new(AsInfoEntry::Resolved(asinfo)), true),
+        )
+    }
+
+    pub fn flush(&self) {
+        self.addr_cache.write().clear();
+        self.asinfo_cache.write().clear();
+        self.prefix_cache.write().clear();
+    }
+
+    pub fn invalidate(&self, addr: IpAddr) -> bool {
+        let addr = canonical_addr(addr);
+        let removed = remove_completed(&self.addr_cache, addr);
+        if remove_completed(&self.asinfo_cache, addr) {
+            self.prefix_cache.write().remove_longest_match(addr);
+        }
+        removed
+    }
+}
+
+impl Drop for DnsResolver {
+    fn drop(&mut self) {
+        // the worker threads are not waited for, they finish once the in-flight lookups, if
+        // any, have been aborted.
+        drop(self.stop());
+    }
+}
+
+/// Perform a lazy lookup of `addr` in a `cache`.
+///
+/// Check if we have already attempted to resolve this `IpAddr` and return the current entry if
+/// so, otherwise add it in a pending state, evicting an entry if the cache holds `max_entries`
+/// or more, and send the `request` to be resolved in the background.
+///
+/// If the entry exists but has timed out, then set it as pending and enqueue it again.  If the
+/// entry exists but has expired, and is not already being refreshed, then return the stale
+/// entry and enqueue it again to be refreshed.
+///
+/// If enqueuing times out then the entry is set as timed out, or left as is if it was to be
+/// refreshed, otherwise the `observer`, if any, is notified that the request was enqueued.
+///
+/// Returns the entry and whether it was served from the cache, i.e. not enqueued.
+fn lazy_lookup<T: CacheEntry>(
+    worker: &Worker,
+    cache: &Cache<T>,
+    max_entries: Option<usize>,
+    addr: IpAddr,
+    request: DnsResolveRequest,
+    observer: Option<&dyn DnsObserver>,
+) -> (Cached<T>, bool) {
+    let mut enqueue = false;
+    let mut refresh = false;
+    let mut guard = cache.write();
+    let entry = if let Some(cached) = guard.get_mut(&addr) {
+        if cached.entry.is_timeout() {
+            *cached = Cached::new(T::pending(addr));
+            enqueue = true;
+        } else if !cached.refreshing && cached.is_expired() {
+            cached.refreshing = true;
+            enqueue = true;
+            refresh = true;
+        }
+        cached.clone()
+    } else {
+        if let Some(max_entries) = max_entries {
+            evict(&mut guard, max_entries);
+        }
+        enqueue = true;
+        let entry = Cached::new(T::pending(addr));
+        guard.insert(addr, entry.clone());
+        entry
+    };
+    drop(guard);
+
+    // We enqueue after the above to ensure we aren't holding the lock on the cache, which is
+    // used by the resolver and so would deadlock.
+    if enqueue {
+        // the request is counted before it is sent such that the worker cannot receive it
+        // before it has been counted.
+        worker.metrics.queued.fetch_add(1, Ordering::SeqCst);
+        if worker
+            .tx
+            .send_timeout(request, RESOLVER_QUEUE_TIMEOUT)
+            .is_ok()
+        {
+            if let Some(observer) = observer {
+                observer.on_enqueue(addr);
+            }
+            (entry, false)
+        } else if refresh {
+            worker.metrics.queued.fetch_sub(1, Ordering::SeqCst);
+            if let Some(cached) = cache.write().get_mut(&addr) {
+                cached.refreshing = false;
+            }
+            (entry, false)
+        } else {
+            worker.metrics.queued.fetch_sub(1, Ordering::SeqCst);
+            let entry = Cached::new(T::timeout(addr));
+            if let Some(cached) = cache.write().get_mut(&addr) {
+                *cached = entry.clone();
+            }
+            (entry, false)
+        }
+    } else {
+        (entry, true)
+    }
+}
+
+/// Combine a `DnsEntry` for the hostnames of an `IpAddr` with an `AsInfoEntry` for the same.
+///
+/// If the hostnames could not be resolved, because the lookup was not found, failed or timed
+/// out, but the `AsInfo` was resolved then a `DnsEntry::NotFound` with the `AsInfo` is
+/// returned such that the partial result is not discarded.
+///
+/// If the `AsInfo` lookup failed, for instance because the `AsInfo` source is unreachable,
+/// then the `DnsEntry` is returned without `AsInfo`.
+fn with_asinfo_entry(dns_entry: DnsEntry, asinfo_entry: AsInfoEntry) -> DnsEntry {
+    match (dns_entry, asinfo_entry) {
+        (dns_entry, AsInfoEntry::Failed) => dns_entry,
+        (DnsEntry::Resolved(Resolved::Normal(addr, hostnames)), asinfo_entry) => {
+            DnsEntry::Resolved(Resolved::WithAsInfo(
+                addr,
+                hostnames,
+                asinfo_entry.into_asinfo(),
+            ))
+        }
+        (DnsEntry::NotFound(Unresolved::Normal(addr)), asinfo_entry) => {
+            DnsEntry::NotFound(Unresolved::WithAsInfo(addr, asinfo_entry.into_asinfo()))
+        }
+        (DnsEntry::Failed(addr, _) | DnsEntry::Timeout(addr), AsInfoEntry::Resolved(asinfo)) => {
+            DnsEntry::NotFound(Unresolved::WithAsInfo(addr, asinfo))
+        }
+        (dns_entry, _) => dns_entry,
+    }
+}
+
+/// Make the `DnsProvider` for a `Config`.
+///
+/// Lookups are driven on the given tokio `runtime`, or on a dedicated runtime if `None`.
+fn make_provider(config: &Config, runtime: Option<&Handle>) -> io::Result<DnsProvider> {
+    let hosts = Arc::new(make_hosts(config)?);
+    if matches!(config.resolve_method, ResolveMethod::System) {
+        if let Some(bind_addr) = config.bind_addr {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("DNS bind address {bind_addr} is not supported by the system resolver"),
+            ));
+        }
+        if config.validate_dnssec {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "DNSSEC validation is not supported by the system resolver",
+            ));
+        }
+        if config.force_tcp {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "forcing TCP is not supported by the system resolver",
+            ));
+        }
+        if config.proxy.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "a SOCKS5 proxy is not supported by the system resolver",
+            ));
+        }
+        if config.source_port.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "a fixed DNS source port is not supported by the system resolver",
+            ));
+        }
+        return Ok(DnsProvider::DnsLookup(hosts));
+    }
+    if config.source_port.is_some() && config.max_inflight > 1 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "a fixed DNS source port requires max_inflight to be 1",
+        ));
+    }
+    if config.proxy.is_some() && !config.force_tcp {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "a SOCKS5 proxy only supports TCP, forcing TCP must be enabled",
+        ));
+    }
+    let connect_timeout = config.connect_timeout.unwrap_or(config.timeout);
+    let total_timeout = config.total_timeout.unwrap_or(config.timeout * 2);
+    let mut options = ResolverOpts::default();
+    options.timeout = config.query_timeout.unwrap_or(config.timeout);
+    options.ip_strategy = lookup_ip_strategy(config.addr_family);
+    let (resolver_config, mut options) = match config.resolve_method {
+        ResolveMethod::Resolv => {
+            let (resolver_config, mut options) = hickory_resolver::system_conf::read_system_conf()?;
+            if let Some(query_timeout) = config.query_timeout {
+                options.timeout = query_timeout;
+            }
+            options.ip_strategy = lookup_ip_strategy(config.addr_family);
+            // the hosts file is consulted by the provider rather than the resolver, which
+            // only supports the platform hosts file and only for forward lookups.
+            options.use_hosts_file = false;
+            (resolver_config, options)
+        }
+        ResolveMethod::Google => (ResolverConfig::google(), options),
+        ResolveMethod::Cloudflare => (ResolverConfig::cloudflare(), options),
+        ResolveMethod::System => unreachable!(),
+    };
+    if config.edns_udp_payload_size.is_some() {
+        options.edns0 = true;
+    }
+    if config.source_port.is_some() {
+        // name servers are otherwise queried concurrently, from sockets which cannot share the
+        // fixed port.
+        options.num_concurrent_reqs = 1;
+    }
+    let resolver_config = with_bind_addr(
+        with_force_tcp(resolver_config, config.force_tcp),
+        config.bind_addr,
+        config.source_port,
+    );
+    if let Some(bind_addr) = config.bind_addr {
+        validate_bind_addr(bind_addr, &resolver_config)?;
+    }
+    let res = BlockingResolver::new(
+        resolver_config,
+        options,
+        connect_timeout,
+        total_timeout,
+        config.edns_udp_payload_size,
+        config.validate_dnssec,
+        config.proxy.clone(),
+        runtime,
+    )?;
+    if matches!(config.startup_policy, StartupPolicy::Strict) {
+        check_upstream_reachable(&res)?;
+    }
+    Ok(DnsProvider::TrustDns(Arc::new(res), hosts))
+}
+
+/// The `LookupIpStrategy` for an `IpAddrFamily`.
+const fn lookup_ip_strategy(addr_family: IpAddrFamily) -> LookupIpStrategy {
+    match addr_family {
+        IpAddrFamily::Ipv4Only => LookupIpStrategy::Ipv4Only,
+        IpAddrFamily::Ipv6Only => LookupIpStrategy::Ipv6Only,
+        IpAddrFamily::Ipv6thenIpv4 => LookupIpStrategy::Ipv6thenIpv4,
+        IpAddrFamily::Ipv4thenIpv6 => LookupIpStrategy::Ipv4thenIpv6,
+    }
+}
+
+/// Read the hosts file for a `Config`.
+///
+/// The hosts file is only consulted for `ResolveMethod::System` and `ResolveMethod::Resolv`,
+/// and only if `use_hosts_file` is set.
+fn make_hosts(config: &Config) -> io::Result<Hosts> {
+    if !config.use_hosts_file {
+        return Ok(Hosts::default());
+    }
+    match (config.resolve_method, &config.hosts_file) {
+        (ResolveMethod::System | ResolveMethod::Resolv, Some(path)) => Hosts::read(path, true),
+        (ResolveMethod::System | ResolveMethod::Resolv, None) => {
+            Hosts::read(Path::new(DEFAULT_HOSTS_FILE), false)
+        }
+        (ResolveMethod::Google | ResolveMethod::Cloudflare, _) => Ok(Hosts::default()),
+    }
+}
+
+/// Make the `AsInfoProvider` for a `Config`.
+///
+/// Any source which is not supported for the `DnsProvider` is skipped and if more than one
+/// source remains then each is tried in turn.
+fn make_asinfo_provider(config: &Config, provider: &DnsProvider) -> AsInfoProvider {
+    let zone = config
+        .as_lookup_zone
+        .as_deref()
+        .unwrap_or(DEFAULT_AS_LOOKUP_ZONE)
+        .trim_end_matches('.');
+    let mut providers = config
+        .as_lookup_sources
+        .as_deref()
+        .unwrap_or(&[AsLookupSource::Dns])
+        .iter()
+        .filter_map(|source| match (source, provider) {
+            (AsLookupSource::Dns, DnsProvider::TrustDns(resolver, _)) => {
+                Some(AsInfoProvider::Cymru(resolver.clone(), Arc::from(zone)))
+            }
+            (AsLookupSource::Dns, DnsProvider::DnsLookup(_)) => None,
+            (AsLookupSource::RipeStat, _) => Some(AsInfoProvider::RipeStat(ripestat::make_agent(
+                config.timeout,
+            ))),
+        })
+        .collect::<Vec<_>>();
+    match providers.len() {
+        0 => AsInfoProvider::Unsupported,
+        1 => providers.remove(0),
+        _ => AsInfoProvider::Fallback(providers),
+    }
+}
+
+/// Spawn threads to process the resolve queue and return the `Worker`.
+///
+/// One thread is spawned for each of the `max_inflight` lookups which may be in-flight
+/// concurrently, all of which take requests from the same queue.
+///
+/// The worker is tagged with a `worker_generation` and stops processing, and discards the
+/// result of any lookup in progress, once the shared `generation` moves on from it.  The
+/// worker also stops once the sending side of the queue is dropped.
+///
+/// The `WorkerMetrics` of the worker count the requests which are queued, throttled and
+/// in-flight.  The lookups of all threads are subject to the same `RateLimiter`, if any.
+#[allow(clippy::too_many_arguments)]
+fn spawn_worker(
+    config: &Config,
+    provider: &DnsProvider,
+    asinfo_provider: &AsInfoProvider,
+    addr_cache: &Cache<DnsEntry>,
+    asinfo_cache: &Cache<AsInfoEntry>,
+    prefix_cache: &PrefixCache,
+    subscribers: &Subscribers,
+    generation: &Arc<AtomicU64>,
+    worker_generation: u64,
+) -> Worker {
+    let (tx, rx) = bounded(RESOLVER_MAX_QUEUE_SIZE);
+    let metrics = Arc::new(WorkerMetrics::default());
+    let rate_limiter = config
+        .max_lookups_per_sec
+        .map(RateLimiter::new)
+        .map(Arc::new);
+    let handles = (0..config.max_inflight.max(1))
+        .map(|_| {
+            let rx = rx.clone();
+            let rate_limiter = rate_limiter.clone();
+            let provider = provider.clone();
+            let asinfo_provider = asinfo_provider.clone();
+            let observer = config.observer.clone();
+            let addr_cache = addr_cache.clone();
+            let asinfo_cache = asinfo_cache.clone();
+            let prefix_cache = prefix_cache.clone();
+            let subscribers = subscribers.clone();
+            let generation = generation.clone();
+            let metrics = metrics.clone();
+            let ttl_bounds = config.ttl_bounds;
+            let asinfo_ttl = config.asinfo_ttl;
+            let retry_policy = config.retry_policy;
+            let caching = config.caching;
+            let trim_trailing_dot = config.trim_trailing_dot;
+            let decode_idn = config.decode_idn;
+            let generated_ptr = GeneratedPtr::from_config(config);
+            thread::spawn(move || {
+                resolver_queue_processor(
+                    rx,
+                    &provider,
+                    &asinfo_provider,
+                    observer.as_deref(),
+                    &addr_cache,
+                    &asinfo_cache,
+                    &prefix_cache,
+                    &subscribers,
+                    &generation,
+                    worker_generation,
+                    ttl_bounds,
+                    asinfo_ttl,
+                    retry_policy,
+                    caching,
+                    trim_trailing_dot,
+                    decode_idn,
+                    generated_ptr.as_ref(),
+                    rate_limiter.as_deref(),
+                    &metrics,
+                );
+            })
+        })
+        .collect();
+    Worker {
+        tx,
+        handles,
+        metrics,
+    }
+}
+
+/// Validate that DNS queries can be bound to the local `bind_addr`.
+///
+/// This fails fast if no name server of the `resolver_config` has the address family of the
+/// address, or if the address is not assigned to a local interface, rather than allowing
+/// every subsequent lookup to silently time out.
+fn validate_bind_addr(bind_addr: IpAddr, resolver_config: &ResolverConfig) -> io::Result<()> {
+    if resolver_config.name_servers().is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("no DNS name server has the address family of the bind address {bind_addr}"),
+        ));
+    }
+    UdpSocket::bind(SocketAddr::new(bind_addr, 0)).map_err(|err| {
+        io::Error::new(
+            err.kind(),
+            format!("invalid DNS bind address {bind_addr}: {err}"),
+        )
+    })?;
+    Ok(())
+}
+
+/// Check that at least one upstream DNS server is reachable.
+///
+/// This is done by querying the `NS` records of the root zone; any response, including an
+/// empty one, indicates that an upstream DNS server is reachable.
+fn check_upstream_reachable(resolver: &BlockingResolver) -> io::Result<()> {
+    match resolver.lookup(Name::root(), RecordType::NS) {
+        Ok(_) => Ok(()),
+        Err(err) if is_no_records_found(&err) => Ok(()),
+        Err(err) => Err(io::Error::new(
+            io::ErrorKind::NotConnected,
+            format!("no upstream DNS server is reachable: {err}"),
+        )),
+    }
+}
+
+/// Bind all name servers of a `ResolverConfig` to the local `bind_addr` and `source_port`, if
+/// any.
+///
+/// Name servers of a different address family to the `bind_addr` cannot be queried from it and
+/// so are dropped.  If only a `source_port` is given then each name server is bound to the
+/// unspecified address of its own family.
+fn with_bind_addr(
+    config: ResolverConfig,
+    bind_addr: Option<IpAddr>,
+    source_port: Option<u16>,
+) -> ResolverConfig {
+    if bind_addr.is_none() && source_port.is_none() {
+        return config;
+    }
+    let name_servers = config
+        .name_servers()
+        .iter()
+        .filter(|name_server| match bind_addr {
+            Some(addr) => addr.is_ipv4() == name_server.socket_addr.is_ipv4(),
+            None => true,
+        })
+        .cloned()
+        .map(|mut name_server| {
+            let addr = bind_addr.unwrap_or(match name_server.socket_addr {
+                SocketAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+                SocketAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+            });
+            name_server.bind_addr = Some(SocketAddr::new(addr, source_port.unwrap_or(0)));
+            name_server
+        })
+        .collect::<Vec<_>>();
+    ResolverConfig::from_parts(
+        config.domain().cloned(),
+        config.search().to_vec(),
+        NameServerConfigGroup::from(name_servers),
+    )
+}
+
+/// The canonical form of `addr`, under which it is cached and queried.
+///
+/// An IPv4-mapped (`::ffff:a.b.c.d`) or IPv4-compatible (`::a.b.c.d`) IPv6 address is given
+/// in its IPv4 form, such that both forms share a single cache entry.  The IPv6 unspecified
+/// (`::`) and loopback (`::1`) addresses are not treated as IPv4-compatible.
+fn canonical_addr(addr: IpAddr) -> IpAddr {
+    match addr {
+        IpAddr::V6(ipv6) if !ipv6.is_unspecified() && !ipv6.is_loopback() => {
+            ipv6.to_ipv4().map_or(addr, IpAddr::V4)
+        }
+        IpAddr::V4(_) | IpAddr::V6(_) => addr,
+    }
+}
+
+/// Restrict all name servers of a `ResolverConfig` to TCP if `force_tcp` is set.
+///
+/// A name server which is only configured for UDP is instead queried over TCP at the same
+/// address.  Name servers which use any other protocol are kept as is.
+fn with_force_tcp(config: ResolverConfig, force_tcp: bool) -> ResolverConfig {
+    if !force_tcp {
+        return config;
+    }
+    let has_tcp = |name_server: &NameServerConfig| {
+        config.name_servers().iter().any(|other| {
+            other.socket_addr == name_server.socket_addr && other.protocol == Protocol::Tcp
+        })
+    };
+    let name_servers = config
+        .name_servers()
+        .iter()
+        .filter_map(|name_server| match name_server.protocol {
+            Protocol::Udp if has_tcp(name_server) => None,
+            Protocol::Udp => Some(NameServerConfig {
+                protocol: Protocol::Tcp,
+                ..name_server.clone()
+            }),
+            _ => Some(name_server.clone()),
+        })
+        .collect::<Vec<_>>();
+    ResolverConfig::from_parts(
+        config.domain().cloned(),
+        config.search().to_vec(),
+        NameServerConfigGroup::from(name_servers),
+    )
+}
+
+/// Process each request from the resolver queue and perform the lookup.
+///
+/// For each `DnsResolveRequest::Hostnames`, perform the reverse DNS lookup and update the cache
+/// with the result (`Resolved`, `NotFound`, `Timeout` or `Failed`) for that addr.  The trailing
+/// dot of each resolved hostname is trimmed, or added, as per `trim_trailing_dot`, and any
+/// internationalized domain names are decoded if `decode_idn` is set.  The
+/// `observer`, if any, is notified of the result before the cache is updated and the
+/// `subscribers`, if any, are sent the result once it has been.
+///
+/// For each `DnsResolveRequest::AsInfo`, perform the `AsInfo` lookup, unless the addr is
+/// within a prefix held by the `prefix_cache`, and update the `AsInfo` cache with the result
+/// for that addr.  The prefix of a resolved `AsInfo` is added to the `prefix_cache`.  A
+/// resolved `AsInfo`, and its prefix, expire as per `asinfo_ttl`.  The `prefix_cache` is not
+/// used if `caching` is disabled.
+///
+/// A request for which the pending entry has been removed from the cache, because the lookup
+/// was cancelled, is skipped and the result of a lookup which was cancelled whilst in-flight
+/// is discarded, without notifying the `observer` or the `subscribers`.
+///
+/// Processing stops once the worker has been superseded, see `spawn_worker`.
+#[allow(clippy::too_many_arguments, clippy::too_many_lines)]
+fn resolver_queue_processor(
+    rx: Receiver<DnsResolveRequest>,
+    provider: &DnsProvider,
+    asinfo_provider: &AsInfoProvider,
+    observer: Option<&dyn DnsObserver>,
+    addr_cache: &Cache<DnsEntry>,
+    asinfo_cache: &Cache<AsInfoEntry>,
+    prefix_cache: &PrefixCache,
+    subscribers: &Mutex<Vec<Subscriber>>,
+    generation: &AtomicU64,
+    worker_generation: u64,
+    ttl_bounds: Option<TtlBounds>,
+    asinfo_ttl: Option<Duration>,
+    retry_policy: RetryPolicy,
+    caching: bool,
+    trim_trailing_dot: bool,
+    decode_idn: bool,
+    generated_ptr: Option<&GeneratedPtr>,
+    rate_limiter: Option<&RateLimiter>,
+    metrics: &WorkerMetrics,
+) {
+    let is_current = || generation.load(Ordering::SeqCst) == worker_generation;
+    // wait for the rate limiter, if any, and then check the request is still awaited.
+    let throttle = |awaited: &dyn Fn() -> bool| match rate_limiter {
+        Some(rate_limiter) => {
+            metrics.throttled.fetch_add(1, Ordering::SeqCst);
+            rate_limiter.acquire();
+            metrics.throttled.fetch_sub(1, Ordering::SeqCst);
+            is_current() && awaited()
+        }
+        None => true,
+    };
+    for request in rx {
+        if !is_current() {
+            break;
+        }
+        metrics.queued.fetch_sub(1, Ordering::SeqCst);
+        match request {
+            DnsResolveRequest::Hostnames(addr) => {
+                if !is_awaited(addr_cache, addr) {
+                    continue;
+                }
+                let from_hosts = provider.hosts().reverse_lookup(addr).is_some();
+                if !from_hosts && !throttle(&|| is_awaited(addr_cache, addr)) {
+                    continue;
+                }
+                let start = Instant::now();
+                metrics.inflight.fetch_add(1, Ordering::SeqCst);
+                let (dns_entry, valid_until, dnssec) = reverse_lookup_hostnames(provider, addr);
+                metrics.inflight.fetch_sub(1, Ordering::SeqCst);
+                // an address mapped by the hosts file is answered without any query.
+                let elapsed = if from_hosts {
+                    Duration::ZERO
+                } else {
+                    start.elapsed()
+                };
+                let server = answered_by(provider, addr, &dns_entry);
+                let dns_entry = with_trailing_dot(dns_entry, trim_trailing_dot);
+                let dns_entry = without_generated_ptr(dns_entry, from_hosts, generated_ptr);
+                let (dns_entry, raw_hostnames) = with_decoded_idn(dns_entry, decode_idn);
+                let now = Instant::now();
+                let expires = valid_until.zip(ttl_bounds).map(|(valid_until, bounds)| {
+                    now + bounds.clamp(valid_until.saturating_duration_since(now))
+                });
+                let retry = ttl_bounds.map(|bounds| now + retry_policy.delay(bounds.min));
+                if let Some(observer) =
+                    observer.filter(|_| is_current() && is_awaited(addr_cache, addr))
+                {
+                    observer.on_complete(addr, &dns_entry, elapsed);
+                }
+                let subscribed = (!subscribers.lock().is_empty()).then(|| dns_entry.clone());
+                let completed = complete_if(
+                    addr_cache,
+                    addr,
+                    Cached {
+                        dnssec,
+                        server,
+                        raw_hostnames,
+                        ..Cached::completed(dns_entry, elapsed, expires)
+                    },
+                    retry,
+                    is_current,
+                );
+                if let Some(dns_entry) = subscribed.filter(|_| completed) {
+                    broadcast(subscribers, addr, &dns_entry);
+                }
+            }
+            DnsResolveRequest::AsInfo(addr) => {
+                if !is_awaited(asinfo_cache, addr) {
+                    continue;
+                }
+                let by_prefix = longest_prefix_match(prefix_cache, addr, caching);
+                if by_prefix.is_none() && !throttle(&|| is_awaited(asinfo_cache, addr)) {
+                    continue;
+                }
+                let start = Instant::now();
+                metrics.inflight.fetch_add(1, Ordering::SeqCst);
+                let (asinfo_entry, expires) = by_prefix.map_or_else(
+                    || {
+                        let asinfo_entry = lookup_asinfo_entry(asinfo_provider, addr);
+                        let expires = asinfo_ttl
+                            .filter(|_| matches!(asinfo_entry, AsInfoEntry::Resolved(_)))
+                            .map(|ttl| Instant::now() + ttl);
+                        (asinfo_entry, expires)
+                    },
+                    |(asinfo, expires)| (AsInfoEntry::Resolved(asinfo), expires),
+                );
+                metrics.inflight.fetch_sub(1, Ordering::SeqCst);
+                if let AsInfoEntry::Resolved(asinfo) = &asinfo_entry {
+                    if caching && is_current() {
+                        insert_prefix(prefix_cache, addr, asinfo, expires);
+                    }
+                }
+                let retry = asinfo_ttl.map(|ttl| Instant::now() + retry_policy.delay(ttl));
+                complete_if(
+                    asinfo_cache,
+                    addr,
+                    Cached::completed(asinfo_entry, start.elapsed(), expires),
+                    retry,
+                    is_current,
+                );
+            }
+        }
+    }
+}
+
+/// Send the completed `dns_entry` of `addr` to every subscriber whose `LookupPolicy` does not
+/// suppress it.
+///
+/// The sender of a subscriber whose `Receiver` has been dropped is removed.
+fn broadcast(subscribers: &Mutex<Vec<Subscriber>>, addr: IpAddr, dns_entry: &DnsEntry) {
+    subscribers.lock().retain(|(tx, policy)| {
+        policy.is_suppressed(addr) || tx.send((addr, dns_entry.clone())).is_ok()
+    });
+}
+
+fn reverse_lookup(
+    provider: &DnsProvider,
+    asinfo_provider: &AsInfoProvider,
+    addr: IpAddr,
+    with_asinfo: bool,
+    trim_trailing_dot: bool,
+    decode_idn: bool,
+    generated_ptr: Option<&GeneratedPtr>,
+) -> DnsEntry {
+    let from_hosts = provider.hosts().reverse_lookup(addr).is_some();
+    let (dns_entry, _, _) = reverse_lookup_hostnames(provider, addr);
+    let dns_entry = with_trailing_dot(dns_entry, trim_trailing_dot);
+    let dns_entry = without_generated_ptr(dns_entry, from_hosts, generated_ptr);
+    let (dns_entry, _) = with_decoded_idn(dns_entry, decode_idn);
+    match asinfo_provider {
+        AsInfoProvider::Cymru(..) | AsInfoProvider::RipeStat(_) | AsInfoProvider::Fallback(_)
+            if with_asinfo =>
+        {
+            with_asinfo_entry(dns_entry, lookup_asinfo_entry(asinfo_provider, addr))
+        }
+        _ => dns_entry,
+    }
+}
+
+/// Trim, or add, the trailing dot of each hostname of a resolved `DnsEntry`.
+fn with_trailing_dot(dns_entry: DnsEntry, trim_trailing_dot: bool) -> DnsEntry {
+    match dns_entry {
+        DnsEntry::Resolved(Resolved::Normal(addr, hostnames)) => {
+            let hostnames = hostnames
+                .iter()
+                .map(|hostname| hostname.trim_end_matches('.'))
+                .map(|hostname| {
+                    if trim_trailing_dot {
+                        hostname.to_string()
+                    } else {
+                        format!("{hostname}.")
+                    }
+                })
+                .collect();
+            DnsEntry::Resolved(Resolved::Normal(addr, hostnames))
+        }
+        dns_entry => dns_entry,
+    }
+}
+
+/// Discard the auto-generated hostnames of a resolved `DnsEntry`, if `generated_ptr` is set,
+/// unless they are `from_hosts`.
+fn without_generated_ptr(
+    dns_entry: DnsEntry,
+    from_hosts: bool,
+    generated_ptr: Option<&GeneratedPtr>,
+) -> DnsEntry {
+    match generated_ptr {
+        Some(generated_ptr) if !from_hosts => generated_ptr.suppress(dns_entry),
+        _ => dns_entry,
+    }
+}
+
+/// Decode the internationalized domain names (IDN) of each hostname of a resolved `DnsEntry`,
+/// if `decode_idn` is set.
+///
+/// Only hostnames with a punycode label are decoded, and a hostname which cannot be decoded is
+/// left as is.  Returns the `DnsEntry` and the raw hostnames, if any were decoded.
+fn with_decoded_idn(dns_entry: DnsEntry, decode_idn: bool) -> (DnsEntry, Option<Vec<String>>) {
+    match dns_entry {
+        DnsEntry::Resolved(Resolved::Normal(addr, hostnames)) if decode_idn => {
+            let decoded = hostnames
+                .iter()
+                .map(|hostname| decode_hostname(hostname))
+                .collect::<Vec<_>>();
+            let raw = (decoded != hostnames).then_some(hostnames);
+            (DnsEntry::Resolved(Resolved::Normal(addr, decoded)), raw)
+        }
+        dns_entry => (dns_entry, None),
+    }
+}
+
+/// Decode a hostname with a punycode label, i.e. `xn--bcher-kva.example`, to Unicode.
+fn decode_hostname(hostname: &str) -> String {
+    let has_punycode = hostname.split('.').any(|label| {
+        label
+            .get(..4)
+            .is_some_and(|p| p.eq_ignore_ascii_case("xn--"))
+    });
+    if has_punycode {
+        match idna::domain_to_unicode(hostname) {
+            (decoded, Ok(())) => decoded,
+            (_, Err(_)) => hostname.to_string(),
+        }
+    } else {
+        hostname.to_string()
+    }
+}
+
+/// Reverse lookup the hostnames of an `IpAddr`.
+///
+/// The hostnames are taken from the hosts file, if it has any for `addr`, without querying
+/// the provider.  The hostnames of a DNS response are given in their raw, ASCII, form.
+///
+/// Returns the `DnsEntry`, the `Instant` until which the response is valid as per its TTL,
+/// if it was resolved from a DNS response, and the outcome of the DNSSEC validation of the
+/// response, if it was validated.
+fn reverse_lookup_hostnames(
+    provider: &DnsProvider,
+    addr: IpAddr,
+) -> (DnsEntry, Option<Instant>, Option<DnssecStatus>) {
+    if let Some(hostnames) = provider.hosts().reverse_lookup(addr) {
+        return (
+            DnsEntry::Resolved(Resolved::Normal(addr, hostnames.to_vec())),
+            None,
+            None,
+        );
+    }
+    match &provider {
+        DnsProvider::DnsLookup(_) => {
+            // we can't distinguish between a failed lookup or a genuine error and so we just
+            // assume all failures are `DnsEntry::NotFound`.
+            match dns_lookup::lookup_addr(&addr) {
+                Ok(dns) => (
+                    DnsEntry::Resolved(Resolved::Normal(addr, vec![dns])),
+                    None,
+                    None,
+                ),
+                Err(_) => (DnsEntry::NotFound(Unresolved::Normal(addr)), None, None),
+            }
+        }
+        DnsProvider::TrustDns(resolver, _) if resolver.validates_dnssec() => {
+            match resolver.reverse_lookup_dnssec(addr) {
+                Ok((DnssecStatus::Bogus, _, _)) => {
+                    (DnsEntry::Bogus(addr), None, Some(DnssecStatus::Bogus))
+                }
+                Ok((status, hostnames, _)) if hostnames.is_empty() => (
+                    DnsEntry::NotFound(Unresolved::Normal(addr)),
+                    None,
+                    Some(status),
+                ),
+                Ok((status, hostnames, valid_until)) => (
+                    DnsEntry::Resolved(Resolved::Normal(addr, hostnames)),
+                    valid_until,
+                    Some(status),
+                ),
+                Err(err) => (unresolved_entry(addr, &err), None, None),
+            }
+        }
+        DnsProvider::TrustDns(resolver, _) => match resolver.reverse_lookup(addr) {
+            Ok(name) => {
+                let valid_until = name.valid_until();
+                let hostnames = name.into_iter().map(|s| s.0.to_ascii()).collect();
+                (
+                    DnsEntry::Resolved(Resolved::Normal(addr, hostnames)),
+                    Some(valid_until),
+                    None,
+                )
+            }
+            Err(err) => (unresolved_entry(addr, &err), None, None),
+        },
+    }
+}
+
+/// The name server which answered the reverse lookup of `addr`, if it was answered by one.
+fn answered_by(provider: &DnsProvider, addr: IpAddr, dns_entry: &DnsEntry) -> Option<SocketAddr> {
+    let answered = matches!(
+        dns_entry,
+        DnsEntry::Resolved(_) | DnsEntry::NotFound(_) | DnsEntry::Bogus(_)
+    );
+    if answered && provider.hosts().reverse_lookup(addr).is_none() {
+        provider.answered_by(addr)
+    } else {
+        None
+    }
+}
+
+/// The `DnsEntry` of a reverse lookup of `addr` which failed with `err`.
+///
+/// A lookup for which the name server answered that there is no such name (`NXDOMAIN`), or
+/// that there are no records (`NOERROR`), is `DnsEntry::NotFound` whereas a lookup for which
+/// the name server answered with any other response code, such as `SERVFAIL` or `REFUSED`,
+/// is `DnsEntry::Failed` with that code.
+fn unresolved_entry(addr: IpAddr, err: &Error) -> DnsEntry {
+    match err {
+        Error::Timeout(_) => DnsEntry::Timeout(addr),
+        err => match response_code(err) {
+            Some(ResponseCode::NXDomain | ResponseCode::NoError) => {
+                DnsEntry::NotFound(Unresolved::Normal(addr))
+            }
+            Some(ResponseCode::ServFail) => DnsEntry::Failed(addr, Some(Rcode::ServFail)),
+            Some(ResponseCode::Refused) => DnsEntry::Failed(addr, Some(Rcode::Refused)),
+            Some(code) => DnsEntry::Failed(addr, Some(Rcode::Other(u16::from(code)))),
+            None => DnsEntry::Failed(addr, None),
+        },
+    }
+}
+
+/// Forward lookup the addresses of a hostname with the OS resolver, as per the `IpAddrFamily`.
+///
+/// The preferred family is queried first and the other family, if any, is only queried if
+/// the preferred family has no addresses.
+fn lookup_host(hostname: &str, addr_family: IpAddrFamily) -> Result<Vec<IpAddr>> {
+    let (preferred, fallback) = match addr_family {
+        IpAddrFamily::Ipv4Only => (AddrFamily::Inet, None),
+        IpAddrFamily::Ipv6Only => (AddrFamily::Inet6, None),
+        IpAddrFamily::Ipv6thenIpv4 => (AddrFamily::Inet6, Some(AddrFamily::Inet)),
+        IpAddrFamily::Ipv4thenIpv6 => (AddrFamily::Inet, Some(AddrFamily::Inet6)),
+    };
+    match (lookup_host_family(hostname, preferred), fallback) {
+        (Ok(addrs), _) if !addrs.is_empty() => Ok(addrs),
+        (_, Some(fallback)) => lookup_host_family(hostname, fallback),
+        (res, None) => res,
+    }
+}
+
+/// Forward lookup the addresses of a single family of a hostname with the OS resolver.
+fn lookup_host_family(hostname: &str, family: AddrFamily) -> Result<Vec<IpAddr>> {
+    let hints = AddrInfoHints {
+        socktype: SockType::Stream.into(),
+        address: family.into(),
+        ..AddrInfoHints::default()
+    };
+    dns_lookup::getaddrinfo(Some(hostname), None, Some(hints))
+        .map_err(io::Error::from)
+        .and_then(|addrs| {
+            addrs
+                .map(|addr| addr.map(|addr| addr.sockaddr.ip()))
+                .collect::<io::Result<Vec<_>>>()
+        })
+        .map_err(|err| Error::LookupFailed(Box::new(err)))
+}
+
+/// Select the addresses of the preferred family, as per the `IpAddrFamily`.
+fn select_addr_family(addr_family: IpAddrFamily, addrs: Vec<IpAddr>) -> Vec<IpAddr> {
+    let (ipv4, ipv6): (Vec<_>, Vec<_>) = addrs.into_iter().partition_map(|ip| match ip {
+        IpAddr::V4(_) => Either::Left(ip),
+        IpAddr::V6(_) => Either::Right(ip),
+    });
+    match addr_family {
+        IpAddrFamily::Ipv6thenIpv4 if ipv6.is_empty() => ipv4,
+        IpAddrFamily::Ipv4thenIpv6 if ipv4.is_empty() => ipv6,
+        IpAddrFamily::Ipv4Only | IpAddrFamily::Ipv4thenIpv6 => ipv4,
+        IpAddrFamily::Ipv6Only | IpAddrFamily::Ipv6thenIpv4 => ipv6,
+    }
+}
+
+/// Lookup up the `AsInfoEntry` for an `IpAddr` address.
+fn lookup_asinfo_entry(asinfo_provider: &AsInfoProvider, addr: IpAddr) -> AsInfoEntry {
+    let (asinfo, source) = match asinfo_provider {
+        AsInfoProvider::Cymru(resolver, zone) => {
+            (lookup_asinfo(resolver, zone, addr), AsLookupSource::Dns)
+        }
+        AsInfoProvider::RipeStat(agent) => (
+            ripestat::lookup_asinfo(agent, addr),
+            AsLookupSource::RipeStat,
+        ),
+        AsInfoProvider::Fallback(providers) => return lookup_asinfo_fallback(providers, addr),
+        AsInfoProvider::Unsupported => return AsInfoEntry::NotFound,
+    };
+    match asinfo {
+        Ok(asinfo) => AsInfoEntry::Resolved(AsInfo {
+            source: Some(source),
+            ..asinfo
+        }),
+        Err(Error::Timeout(_)) => AsInfoEntry::Timeout,
+        Err(err) if is_no_records_found(&err) => AsInfoEntry::NotFound,
+        Err(Error::QueryAsnOriginFailed | Error::QueryAsnFailed) => AsInfoEntry::NotFound,
+        Err(
+            Error::LookupFailed(_)
+            | Error::ParseOriginQueryFailed(_)
+            | Error::ParseAsnQueryFailed(_)
+            | Error::QueryRipeStatFailed(_)
+            | Error::ParseRipeStatFailed(_)
+            | Error::CnameLoop(_)
+            | Error::Shutdown,
+        ) => AsInfoEntry::Failed,
+    }
+}
+
+/// Lookup the `AsInfo` of `addr` from each of the `providers` in turn.
+///
+/// The first `AsInfo` with at least one AS number is returned.  Otherwise the first `AsInfo`
+/// without any AS number is returned, if any, or else the outcome of the last provider.
+fn lookup_asinfo_fallback(providers: &[AsInfoProvider], addr: IpAddr) -> AsInfoEntry {
+    let mut fallback = AsInfoEntry::NotFound;
+    for provider in providers {
+        match lookup_asinfo_entry(provider, addr) {
+            AsInfoEntry::Resolved(asinfo) if asinfo.asns().next().is_some() => {
+                return AsInfoEntry::Resolved(asinfo);
+            }
+            _ if matches!(fallback, AsInfoEntry::Resolved(_)) => {}
+            entry => fallback = entry,
+        }
+    }
+    fallback
+}
+
+/// Convert a `ResolveError` to an `Error::Timeout` if the lookup timed out or to an
+/// `Error::LookupFailed` otherwise.
+///
+/// A query which is not answered within the query timeout fails with a `Timeout` whereas a TCP
+/// connection which is not established within the connect timeout fails with an `Io` error of
+/// kind `TimedOut`.
+fn resolve_error(err: ResolveError) -> Error {
+    let is_timed_out = |err: &io::Error| err.kind() == io::ErrorKind::TimedOut;
+    match err.kind() {
+        ResolveErrorKind::Timeout => Error::Timeout(TimeoutReason::Query),
+        ResolveErrorKind::Io(io_err) if is_timed_out(io_err) => {
+            Error::Timeout(TimeoutReason::Connect)
+        }
+        ResolveErrorKind::Proto(proto_err) => match proto_err.kind() {
+            ProtoErrorKind::Timeout => Error::Timeout(TimeoutReason::Query),
+            ProtoErrorKind::Io(io_err) if is_timed_out(io_err) => {
+                Error::Timeout(TimeoutReason::Connect)
+            }
+            _ => Error::LookupFailed(Box::new(err)),
+        },
+        _ => Error::LookupFailed(Box::new(err)),
+    }
+}
+
+/// Did a lookup fail because no records were found?
+fn is_no_records_found(err: &Error) -> bool {
+    match err {
+        Error::LookupFailed(err) => matches!(
+            err.downcast_ref::<ResolveError>().map(ResolveError::kind),
+            Some(ResolveErrorKind::NoRecordsFound { .. })
+        ),
+        _ => false,
+    }
+}
+
+/// The response code of the name server answer for a lookup which failed because no records
+/// were found, if any.
+fn response_code(err: &Error) -> Option<ResponseCode> {
+    match err {
+        Error::LookupFailed(err) => match err.downcast_ref::<ResolveError>()?.kind() {
+            ResolveErrorKind::NoRecordsFound { response_code, .. } => Some(*response_code),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Convert a `ProtoError` to an `Error::LookupFailed`.
+fn proto_error(err: ProtoError) -> Error {
+    Error::LookupFailed(Box::new(err))
+}
@@ -0,0 +1,283 @@
+use crate::cache_file::Record;
+use crate::prefix_map::PrefixMap;
+use crate::resolver::{AsInfo, AsInfoEntry, DnsEntry, DnssecStatus};
+use ipnetwork::IpNetwork;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Alias for a cache of lookup entries.
+pub(super) type Cache<T> = Arc<RwLock<HashMap<IpAddr, Cached<T>>>>;
+
+/// The `AsInfo` of each announced prefix for which an `AsInfo` lookup has resolved, and when
+/// it expires, if ever.
+///
+/// An `AsInfo` lookup of any `IpAddr` within such a prefix is served from here, without any
+/// query being made, from the most specific prefix which contains it unless it has expired.
+pub(super) type PrefixCache = Arc<RwLock<PrefixMap<(AsInfo, Option<Instant>)>>>;
+
+/// An entry in a lazy lookup cache.
+#[derive(Debug, Clone)]
+pub(super) struct Cached<T> {
+    /// The entry.
+    pub(super) entry: T,
+    /// When the entry was set, i.e. when the lookup was enqueued for a pending entry.
+    pub(super) since: Instant,
+    /// The wall clock time at which the entry was set.
+    pub(super) updated: SystemTime,
+    /// How long the lookup which completed the entry took, if any.
+    pub(super) elapsed: Option<Duration>,
+    /// When the entry expires, if ever.
+    pub(super) expires: Option<Instant>,
+    /// Whether the entry has expired and is being refreshed.
+    pub(super) refreshing: bool,
+    /// The outcome of the DNSSEC validation of the lookup which completed the entry, if any.
+    pub(super) dnssec: Option<DnssecStatus>,
+    /// The name server which answered the lookup which completed the entry, if known.
+    pub(super) server: Option<SocketAddr>,
+    /// The raw hostnames of the lookup which completed the entry, if any were decoded.
+    pub(super) raw_hostnames: Option<Vec<String>>,
+}
+
+impl<T> Cached<T> {
+    pub(super) fn new(entry: T) -> Self {
+        Self {
+            entry,
+            since: Instant::now(),
+            updated: SystemTime::now(),
+            elapsed: None,
+            expires: None,
+            refreshing: false,
+            dnssec: None,
+            server: None,
+            raw_hostnames: None,
+        }
+    }
+
+    /// An entry completed by a lookup which took `elapsed` and which expires at `expires`.
+    pub(super) fn completed(entry: T, elapsed: Duration, expires: Option<Instant>) -> Self {
+        Self {
+            elapsed: Some(elapsed),
+            expires,
+            ..Self::new(entry)
+        }
+    }
+
+    /// Has the entry expired?
+    pub(super) fn is_expired(&self) -> bool {
+        self.expires
+            .is_some_and(|expires| Instant::now() >= expires)
+    }
+}
+
+impl<T: CacheEntry> Cached<T> {
+    /// Is the lookup of this entry awaited, i.e. pending or being refreshed?
+    pub(super) fn is_awaited(&self) -> bool {
+        self.entry.is_pending() || self.refreshing
+    }
+}
+
+/// An entry in a lazy lookup cache.
+pub(super) trait CacheEntry: Clone {
+    /// The entry for a lookup which is pending.
+    fn pending(addr: IpAddr) -> Self;
+    /// The entry for a lookup which has timed out.
+    fn timeout(addr: IpAddr) -> Self;
+    /// Has the lookup timed out?
+    fn is_timeout(&self) -> bool;
+    /// Is the lookup pending?
+    fn is_pending(&self) -> bool;
+    /// Has the lookup failed or timed out?
+    fn is_failed(&self) -> bool;
+}
+
+impl CacheEntry for DnsEntry {
+    fn pending(addr: IpAddr) -> Self {
+        Self::Pending(addr)
+    }
+    fn timeout(addr: IpAddr) -> Self {
+        Self::Timeout(addr)
+    }
+    fn is_timeout(&self) -> bool {
+        matches!(self, Self::Timeout(_))
+    }
+    fn is_pending(&self) -> bool {
+        matches!(self, Self::Pending(_))
+    }
+    fn is_failed(&self) -> bool {
+        matches!(self, Self::Failed(..) | Self::Timeout(_))
+    }
+}
+
+impl CacheEntry for AsInfoEntry {
+    fn pending(_: IpAddr) -> Self {
+        Self::Pending
+    }
+    fn timeout(_: IpAddr) -> Self {
+        Self::Timeout
+    }
+    fn is_timeout(&self) -> bool {
+        matches!(self, Self::Timeout)
+    }
+    fn is_pending(&self) -> bool {
+        matches!(self, Self::Pending)
+    }
+    fn is_failed(&self) -> bool {
+        matches!(self, Self::Failed | Self::Timeout)
+    }
+}
+
+/// Evict the completed entry which was set least recently from a `cache` which holds
+/// `max_entries` or more entries.
+///
+/// Pending entries are never evicted.
+pub(super) fn evict<T: CacheEntry>(cache: &mut HashMap<IpAddr, Cached<T>>, max_entries: usize) {
+    if cache.len() >= max_entries {
+        let oldest = cache
+            .iter()
+            .filter(|(_, cached)| !cached.entry.is_pending())
+            .min_by_key(|(_, cached)| cached.since)
+            .map(|(addr, _)| *addr);
+        if let Some(addr) = oldest {
+            cache.remove(&addr);
+        }
+    }
+}
+
+/// The entry of a lazy lookup, reported as timed out if it has been pending for longer than
+/// the `timeout`, if any.
+pub(super) fn with_timeout<T: CacheEntry>(
+    addr: IpAddr,
+    cached: Cached<T>,
+    timeout: Option<Duration>,
+) -> T {
+    match timeout {
+        Some(timeout) if cached.entry.is_pending() && cached.since.elapsed() >= timeout => {
+            T::timeout(addr)
+        }
+        _ => cached.entry,
+    }
+}
+
+/// The `max_entries`, if any, most recently updated `records`.
+pub(super) fn latest_records<T>(
+    mut records: Vec<Record<T>>,
+    max_entries: Option<usize>,
+) -> Vec<Record<T>> {
+    records.sort_by_key(|record| std::cmp::Reverse(record.updated));
+    records.truncate(max_entries.unwrap_or(usize::MAX));
+    records
+}
+
+/// The `Record` of the `value` of a completed `cached` entry of `addr` to persist.
+pub(super) fn to_record<T, V>(addr: IpAddr, cached: &Cached<T>, value: V) -> Record<V> {
+    let now = Instant::now();
+    Record {
+        addr,
+        value,
+        updated: cached.updated,
+        expires: cached
+            .expires
+            .map(|expires| SystemTime::now() + expires.saturating_duration_since(now)),
+    }
+}
+
+/// Add the announced prefix of the `AsInfo` of `addr`, which expires at `expires`, to the
+/// `prefix_cache`.
+///
+/// The prefix is ignored if it cannot be parsed, if it does not contain `addr` or if it is a
+/// default route, which would otherwise match every `IpAddr` of the family.
+pub(super) fn insert_prefix(
+    prefix_cache: &PrefixCache,
+    addr: IpAddr,
+    asinfo: &AsInfo,
+    expires: Option<Instant>,
+) {
+    if let Ok(prefix) = IpNetwork::from_str(&asinfo.prefix) {
+        if prefix.prefix() > 0 && prefix.contains(addr) {
+            prefix_cache
+                .write()
+                .insert(prefix, (asinfo.clone(), expires));
+        }
+    }
+}
+
+/// The `AsInfo`, and when it expires, of the most specific prefix in the `prefix_cache` which
+/// contains `addr`, unless it has expired or `caching` is disabled.
+pub(super) fn longest_prefix_match(
+    prefix_cache: &PrefixCache,
+    addr: IpAddr,
+    caching: bool,
+) -> Option<(AsInfo, Option<Instant>)> {
+    let now = Instant::now();
+    prefix_cache
+        .read()
+        .longest_match(addr)
+        .filter(|(_, expires)| caching && expires.map_or(true, |expires| now < expires))
+        .cloned()
+}
+
+/// Remove the entry of `addr` from a `cache`, unless it is awaited.
+pub(super) fn remove_completed<T: CacheEntry>(cache: &Cache<T>, addr: IpAddr) -> bool {
+    let mut guard = cache.write();
+    guard.get(&addr).is_some_and(|cached| !cached.is_awaited()) && guard.remove(&addr).is_some()
+}
+
+/// Is the lookup of `addr` awaited in a cache, i.e. pending or being refreshed?
+pub(super) fn is_awaited<T: CacheEntry>(cache: &Cache<T>, addr: IpAddr) -> bool {
+    cache.read().get(&addr).is_some_and(Cached::is_awaited)
+}
+
+/// Cancel the lookup of `addr` in a cache, if awaited.
+///
+/// A pending entry is removed whereas a stale entry which is being refreshed is retained.
+pub(super) fn cancel_awaited<T: CacheEntry>(cache: &Cache<T>, addr: IpAddr) {
+    let mut cache = cache.write();
+    match cache.get_mut(&addr) {
+        Some(cached) if cached.entry.is_pending() => {
+            cache.remove(&addr);
+        }
+        Some(cached) => cached.refreshing = false,
+        None => {}
+    }
+}
+
+/// Replace the awaited entry of `addr` in a cache with the completed `entry` if `cond` holds
+/// whilst the cache is locked.
+///
+/// The `entry` is discarded if there is no awaited entry, i.e. the lookup was cancelled.  If
+/// the refresh of a stale entry failed then the stale entry is retained and expires again at
+/// `retry`.
+///
+/// Returns true if the cache was updated with the completed `entry`.
+pub(super) fn complete_if<T: CacheEntry>(
+    cache: &Cache<T>,
+    addr: IpAddr,
+    entry: Cached<T>,
+    retry: Option<Instant>,
+    cond: impl Fn() -> bool,
+) -> bool {
+    let mut cache = cache.write();
+    if !cond() {
+        return false;
+    }
+    match cache.get_mut(&addr) {
+        Some(cached) if cached.entry.is_pending() => {
+            *cached = entry;
+            true
+        }
+        Some(cached) if cached.refreshing && entry.entry.is_failed() => {
+            cached.refreshing = false;
+            cached.expires = retry;
+            false
+        }
+        Some(cached) if cached.refreshing => {
+            *cached = entry;
+            true
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,341 @@
+use super::{proto_error, BlockingResolver};
+use crate::resolver::{AsInfo, Error, Result};
+use hickory_resolver::proto::rr::{RData, RecordType};
+use hickory_resolver::Name;
+use ipnetwork::IpNetwork;
+use itertools::Itertools;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Lookup up `AsInfo` for an `IpAddr` address.
+///
+/// The `asn` query for the name of an origin AS may fail, or time out, independently of the
+/// `origin` query, in which case the `AsInfo` is returned without the name of that AS rather
+/// than being discarded.
+///
+/// The queries are made against the base `zone` of the service.
+pub(super) fn lookup_asinfo(
+    resolver: &Arc<BlockingResolver>,
+    zone: &str,
+    addr: IpAddr,
+) -> Result<AsInfo> {
+    let origin_query_txts = match addr {
+        IpAddr::V4(addr) => query_asn_ipv4(resolver, zone, addr)?,
+        IpAddr::V6(addr) => query_asn_ipv6(resolver, zone, addr)?,
+    };
+    let origin = parse_origin_query_txts(&origin_query_txts)?;
+    let mut asns = vec![];
+    for asn in origin.asns() {
+        match query_asn_name(resolver, zone, asn).and_then(|txt| parse_asn_query_txt(&txt)) {
+            Ok(asn) => asns.push(asn),
+            Err(err) => tracing::debug!(%err, asn, "failed to query AS name"),
+        }
+    }
+    Ok(merge_asinfo(origin, &asns))
+}
+
+/// Merge the `AsInfo` parsed from the `origin` query and the `asn` query of each origin AS.
+///
+/// The `cc`, `registry` and `allocated` fields of the `origin` query, which relate to the
+/// prefix, are preferred and the fields of the first `asn` query, which relate to the AS, are
+/// used for any which are empty.  The name of each AS is retained.
+fn merge_asinfo(origin: AsInfo, asns: &[AsInfo]) -> AsInfo {
+    let or = |field: String, fallback: Option<&String>| {
+        if field.is_empty() {
+            fallback.cloned().unwrap_or_default()
+        } else {
+            field
+        }
+    };
+    let first = asns.first();
+    AsInfo {
+        asn: origin.asn,
+        prefix: origin.prefix,
+        cc: or(origin.cc, first.map(|asn| &asn.cc)),
+        registry: or(origin.registry, first.map(|asn| &asn.registry)),
+        allocated: or(origin.allocated, first.map(|asn| &asn.allocated)),
+        name: asns
+            .iter()
+            .map(|asn| asn.name.as_str())
+            .filter(|name| !name.is_empty())
+            .join(" / "),
+        source: None,
+    }
+}
+
+/// Perform the `origin` query, returning every `TXT` record.
+fn query_asn_ipv4(
+    resolver: &Arc<BlockingResolver>,
+    zone: &str,
+    addr: Ipv4Addr,
+) -> Result<Vec<String>> {
+    let query = format!("{}.origin.{zone}.", addr.octets().iter().rev().join("."));
+    let name = Name::from_str(query.as_str()).map_err(proto_error)?;
+    let response = resolver.lookup(name, RecordType::TXT)?;
+    let txts = response
+        .iter()
+        .filter_map(RData::as_txt)
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    if txts.is_empty() {
+        return Err(Error::QueryAsnOriginFailed);
+    }
+    Ok(txts)
+}
+
+/// Perform the `origin` query, returning every `TXT` record.
+fn query_asn_ipv6(
+    resolver: &Arc<BlockingResolver>,
+    zone: &str,
+    addr: Ipv6Addr,
+) -> Result<Vec<String>> {
+    let query = format!(
+        "{:x}.origin6.{zone}.",
+        addr.octets()
+            .iter()
+            .rev()
+            .flat_map(|o| [o & 0x0F, (o & 0xF0) >> 4])
+            .format(".")
+    );
+    let name = Name::from_str(query.as_str()).map_err(proto_error)?;
+    let response = resolver.lookup(name, RecordType::TXT)?;
+    let txts = response
+        .iter()
+        .filter_map(RData::as_txt)
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    if txts.is_empty() {
+        return Err(Error::QueryAsnOriginFailed);
+    }
+    Ok(txts)
+}
+
+/// Perform the `asn` query.
+fn query_asn_name(resolver: &Arc<BlockingResolver>, zone: &str, asn: &str) -> Result<String> {
+    let query = format!("AS{asn}.{zone}.");
+    let name = Name::from_str(query.as_str()).map_err(proto_error)?;
+    let response = resolver.lookup(name, RecordType::TXT)?;
+    let data = response
+        .iter()
+        .next()
+        .ok_or_else(|| Error::QueryAsnFailed)?;
+    let bytes = data.as_txt().ok_or_else(|| Error::QueryAsnFailed)?;
+    Ok(bytes.to_string())
+}
+
+/// The `origin` DNS query returns a TXT record in the formal:
+///      `asn | prefix | cc | registry | allocated`
+///
+/// For example:
+///      `12301 | 81.0.100.0/22 | HU | ripencc | 2001-12-06`
+///
+/// From this we extract all fields.  Any field after the `asn` which is missing is left
+/// empty.
+///
+/// A prefix which is announced by more than one AS (MOAS) has a space separated list of AS
+/// numbers, i.e. `13335 13336`, which are separated by a `/` in the `AsInfo`.
+fn parse_origin_query_txt(origin_query_txt: &str) -> Result<AsInfo> {
+    let [asn, prefix, cc, registry, allocated] = split_cymru_txt(origin_query_txt)
+        .ok_or_else(|| Error::ParseOriginQueryFailed(String::from(origin_query_txt)))?;
+    Ok(AsInfo {
+        asn: asn.split_whitespace().join("/"),
+        prefix,
+        cc,
+        registry,
+        allocated,
+        name: String::default(),
+        source: None,
+    })
+}
+
+/// Parse the `TXT` records of the `origin` DNS query.
+///
+/// There is one record per announced prefix which contains the address.  The most specific
+/// prefix is used and, should there be more than one record for that prefix, the AS numbers
+/// of each are combined.
+fn parse_origin_query_txts(origin_query_txts: &[String]) -> Result<AsInfo> {
+    let origins = origin_query_txts
+        .iter()
+        .map(|txt| parse_origin_query_txt(txt))
+        .collect::<Result<Vec<_>>>()?;
+    let prefix_len =
+        |origin: &AsInfo| IpNetwork::from_str(&origin.prefix).map_or(0, |net| net.prefix());
+    let most_specific = origins
+        .iter()
+        .map(prefix_len)
+        .max()
+        .ok_or(Error::QueryAsnOriginFailed)?;
+    let mut origins = origins
+        .into_iter()
+        .filter(|origin| prefix_len(origin) == most_specific);
+    let mut origin = origins.next().ok_or(Error::QueryAsnOriginFailed)?;
+    let mut asns = origin.asns().map(String::from).collect::<Vec<_>>();
+    for other in origins {
+        for asn in other.asns() {
+            if !asns.iter().any(|existing| existing == asn) {
+                asns.push(asn.to_string());
+            }
+        }
+    }
+    origin.asn = asns.join("/");
+    Ok(origin)
+}
+
+/// The `asn` DNS query returns a TXT record in the formal:
+///      `asn | cc | registry | allocated | name`
+///
+/// For example:
+///      `12301 | HU | ripencc | 1999-02-25 | INVITECH, HU`
+///
+/// From this we extract all fields, other than the `prefix` which is left empty.  Any field
+/// after the `asn` which is missing is left empty.
+fn parse_asn_query_txt(asn_query_txt: &str) -> Result<AsInfo> {
+    let [asn, cc, registry, allocated, name] = split_cymru_txt(asn_query_txt)
+        .ok_or_else(|| Error::ParseAsnQueryFailed(String::from(asn_query_txt)))?;
+    Ok(AsInfo {
+        asn,
+        prefix: String::default(),
+        cc,
+        registry,
+        allocated,
+        name,
+        source: None,
+    })
+}
+
+/// Split a Team Cymru TXT record into its 5 trimmed fields.
+///
+/// Any missing trailing fields are empty and any `|` within the last field is retained.
+/// Returns `None` if the first field, the `asn`, is empty or is not numeric.
+fn split_cymru_txt(txt: &str) -> Option<[String; 5]> {
+    let mut fields = txt.splitn(5, '|').map(|field| field.trim().to_string());
+    let fields: [String; 5] = std::array::from_fn(|_| fields.next().unwrap_or_default());
+    let asn = &fields[0];
+    if asn.is_empty() || !asn.chars().all(|c| c.is_ascii_digit() || c == ' ') {
+        return None;
+    }
+    Some(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_origin_query_txt() -> anyhow::Result<()> {
+        let asinfo = parse_origin_query_txt("13335 | 1.1.1.0/24 | AU | apnic | 2011-08-11")?;
+        assert_eq!("13335", asinfo.asn);
+        assert_eq!("1.1.1.0/24", asinfo.prefix);
+        assert_eq!("AU", asinfo.cc);
+        assert_eq!("apnic", asinfo.registry);
+        assert_eq!("2011-08-11", asinfo.allocated);
+        assert!(asinfo.name.is_empty());
+        let asinfo = parse_origin_query_txt("  3333|193.0.0.0/21 |NL|  ripencc |   1993-09-01  ")?;
+        assert_eq!("3333", asinfo.asn);
+        assert_eq!("193.0.0.0/21", asinfo.prefix);
+        assert_eq!("NL", asinfo.cc);
+        assert_eq!("ripencc", asinfo.registry);
+        assert_eq!("1993-09-01", asinfo.allocated);
+        let asinfo = parse_origin_query_txt("13335 | 2606:4700::/32 | US | arin |")?;
+        assert_eq!("arin", asinfo.registry);
+        assert!(asinfo.allocated.is_empty());
+        let asinfo = parse_origin_query_txt("64496 | 192.0.2.0/24")?;
+        assert_eq!("192.0.2.0/24", asinfo.prefix);
+        assert!(asinfo.cc.is_empty());
+        assert!(asinfo.registry.is_empty());
+        assert!(asinfo.allocated.is_empty());
+        assert!(matches!(
+            parse_origin_query_txt(""),
+            Err(Error::ParseOriginQueryFailed(_))
+        ));
+        assert!(matches!(
+            parse_origin_query_txt("not | a | record"),
+            Err(Error::ParseOriginQueryFailed(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_asn_query_txt() -> anyhow::Result<()> {
+        let asinfo = parse_asn_query_txt("13335 | US | arin | 2010-07-14 | CLOUDFLARENET, US")?;
+        assert_eq!("13335", asinfo.asn);
+        assert!(asinfo.prefix.is_empty());
+        assert_eq!("US", asinfo.cc);
+        assert_eq!("arin", asinfo.registry);
+        assert_eq!("2010-07-14", asinfo.allocated);
+        assert_eq!("CLOUDFLARENET, US", asinfo.name);
+        let asinfo = parse_asn_query_txt("64496 | ZZ | | | EXAMPLE | RESERVED ")?;
+        assert!(asinfo.registry.is_empty());
+        assert!(asinfo.allocated.is_empty());
+        assert_eq!("EXAMPLE | RESERVED", asinfo.name);
+        let asinfo = parse_asn_query_txt("64496")?;
+        assert!(asinfo.name.is_empty());
+        assert!(matches!(
+            parse_asn_query_txt(" | US | arin | 2010-07-14 | CLOUDFLARENET, US"),
+            Err(Error::ParseAsnQueryFailed(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_origin_query_txts_moas() -> anyhow::Result<()> {
+        let txts = |txts: &[&str]| txts.iter().map(ToString::to_string).collect::<Vec<_>>();
+        let asinfo = parse_origin_query_txts(&txts(&[
+            "13335 13336 | 1.1.1.0/24 | AU | apnic | 2011-08-11",
+        ]))?;
+        assert_eq!("13335/13336", asinfo.asn);
+        assert_eq!(vec!["13335", "13336"], asinfo.asns().collect::<Vec<_>>());
+        assert_eq!("1.1.1.0/24", asinfo.prefix);
+        let asinfo = parse_origin_query_txts(&txts(&[
+            "13335 | 1.1.1.0/24 | AU | apnic | 2011-08-11",
+            "13336 13335 | 1.1.1.0/24 | AU | apnic | 2011-08-11",
+        ]))?;
+        assert_eq!("13335/13336", asinfo.asn);
+        let asinfo = parse_origin_query_txts(&txts(&[
+            "64496 | 192.0.0.0/16 | ZZ | test | 2000-01-01",
+            "64497 | 192.0.2.0/24 | ZZ | test | 2001-01-01",
+        ]))?;
+        assert_eq!("64497", asinfo.asn);
+        assert_eq!("192.0.2.0/24", asinfo.prefix);
+        assert_eq!("2001-01-01", asinfo.allocated);
+        assert!(matches!(
+            parse_origin_query_txts(&[]),
+            Err(Error::QueryAsnOriginFailed)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_asinfo_moas() -> anyhow::Result<()> {
+        let origin = parse_origin_query_txt("13335 13336 | 1.1.1.0/24")?;
+        let asns = [
+            parse_asn_query_txt("13335 | US | arin | 2010-07-14 | CLOUDFLARENET, US")?,
+            parse_asn_query_txt("13336 | US | arin | 2010-07-14 | CLOUDFLARENET-SFO, US")?,
+        ];
+        let asinfo = merge_asinfo(origin, &asns);
+        assert_eq!("13335/13336", asinfo.asn);
+        assert_eq!("CLOUDFLARENET, US / CLOUDFLARENET-SFO, US", asinfo.name);
+        assert_eq!("US", asinfo.cc);
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_asinfo() -> anyhow::Result<()> {
+        let asn = parse_asn_query_txt("13335 | US | arin | 2010-07-14 | CLOUDFLARENET, US")?;
+        let origin = parse_origin_query_txt("13335 | 1.1.1.0/24 | AU | apnic | 2011-08-11")?;
+        let asinfo = merge_asinfo(origin, std::slice::from_ref(&asn));
+        assert_eq!("1.1.1.0/24", asinfo.prefix);
+        assert_eq!("AU", asinfo.cc);
+        assert_eq!("apnic", asinfo.registry);
+        assert_eq!("2011-08-11", asinfo.allocated);
+        assert_eq!("CLOUDFLARENET, US", asinfo.name);
+        let origin = parse_origin_query_txt("13335 | 2606:4700::/32")?;
+        let asinfo = merge_asinfo(origin, &[asn]);
+        assert_eq!("2606:4700::/32", asinfo.prefix);
+        assert_eq!("US", asinfo.cc);
+        assert_eq!("arin", asinfo.registry);
+        assert_eq!("2010-07-14", asinfo.allocated);
+        Ok(())
+    }
+}
@@ -0,0 +1,2400 @@
+use super::*;
+use crossbeam::channel::SendTimeoutError;
+use hickory_resolver::proto::rr::rdata::{A, AAAA, CNAME, PTR, TXT};
+use hickory_resolver::proto::rr::Record;
+use ipnetwork::IpNetwork;
+use parking_lot::Mutex;
+use std::collections::HashSet;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::atomic::AtomicBool;
+
+#[test]
+fn test_shutdown_unresponsive_nameserver() -> anyhow::Result<()> {
+    let nameserver = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0))?;
+    nameserver.set_read_timeout(Some(Duration::from_secs(10)))?;
+    let config = Config::new(ResolveMethod::Resolv, IpAddrFamily::Ipv4Only, TIMEOUT);
+    let resolver = DnsResolver::with_provider(config, make_provider(&nameserver)?);
+    let addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    assert!(matches!(
+        resolver.reverse_lookup(addr, false, true, &LookupPolicy::default()),
+        DnsEntry::Pending(_)
+    ));
+    // the query is never answered and so remains in-flight until aborted.
+    nameserver.recv(&mut [0_u8; 512])?;
+    let start = Instant::now();
+    resolver.shutdown();
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert!(matches!(
+        resolver.reverse_lookup(addr, false, true, &LookupPolicy::default()),
+        DnsEntry::Failed(..)
+    ));
+    assert!(matches!(
+        resolver.reverse_lookup(addr, false, false, &LookupPolicy::default()),
+        DnsEntry::Failed(..)
+    ));
+    assert!(matches!(resolver.lookup("localhost"), Err(Error::Shutdown)));
+    Ok(())
+}
+
+#[test]
+fn test_randomized_query_ids_and_ports() -> anyhow::Result<()> {
+    const QUERIES: u8 = 8;
+    let nameserver = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0))?;
+    nameserver.set_read_timeout(Some(Duration::from_secs(10)))?;
+    let config = Config::new(ResolveMethod::Resolv, IpAddrFamily::Ipv4Only, TIMEOUT);
+    let resolver = DnsResolver::with_provider(config, make_provider(&nameserver)?);
+    let responder = {
+        let nameserver = nameserver.try_clone()?;
+        thread::spawn(move || {
+            let mut buf = [0_u8; 512];
+            (0..QUERIES)
+                .map(|_| {
+                    let (len, from) = nameserver.recv_from(&mut buf)?;
+                    let query = Message::from_vec(&buf[..len])?;
+                    let response = ScriptedNameserver::respond(&query).to_vec()?;
+                    nameserver.send_to(&response, from)?;
+                    Ok((query.id(), from.port()))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()
+        })
+    };
+    for i in 1..=QUERIES {
+        let addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, i));
+        let _ = resolver.reverse_lookup(addr, false, false, &LookupPolicy::default());
+    }
+    let (ids, ports): (Vec<_>, Vec<_>) = responder.join().unwrap()?.into_iter().unzip();
+    // consecutive queries use a different transaction id and are sent from a source port
+    // in the dynamic port range.
+    assert!(ids.windows(2).all(|ids| ids[0] != ids[1]), "{ids:?}");
+    assert!(
+        ports.iter().all(|port| (49152..=65535).contains(port)),
+        "{ports:?}"
+    );
+    resolver.shutdown();
+    Ok(())
+}
+
+#[test]
+fn test_fixed_source_port() -> anyhow::Result<()> {
+    const QUERIES: u8 = 4;
+    let nameserver = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0))?;
+    nameserver.set_read_timeout(Some(Duration::from_secs(10)))?;
+    let source_port = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0))?
+        .local_addr()?
+        .port();
+    let mut options = ResolverOpts::default();
+    options.timeout = TIMEOUT;
+    options.cache_size = 0;
+    options.num_concurrent_reqs = 1;
+    let resolver_config = with_bind_addr(
+        ResolverConfig::from_parts(
+            None,
+            vec![],
+            NameServerConfigGroup::from_ips_clear(
+                &[nameserver.local_addr()?.ip()],
+                nameserver.local_addr()?.port(),
+                true,
+            ),
+        ),
+        None,
+        Some(source_port),
+    );
+    let provider = BlockingResolver::new(
+        resolver_config,
+        options,
+        TIMEOUT,
+        TIMEOUT,
+        None,
+        false,
+        None,
+        None,
+    )?;
+    let mut config = Config::new(ResolveMethod::Resolv, IpAddrFamily::Ipv4Only, TIMEOUT);
+    config.source_port = Some(source_port);
+    config.max_inflight = 1;
+    let resolver = DnsResolver::with_provider(
+        config,
+        DnsProvider::TrustDns(Arc::new(provider), Arc::new(Hosts::default())),
+    );
+    let responder = {
+        let nameserver = nameserver.try_clone()?;
+        thread::spawn(move || {
+            let mut buf = [0_u8; 512];
+            (0..QUERIES)
+                .map(|_| {
+                    let (len, from) = nameserver.recv_from(&mut buf)?;
+                    let query = Message::from_vec(&buf[..len])?;
+                    let response = ScriptedNameserver::respond(&query).to_vec()?;
+                    nameserver.send_to(&response, from)?;
+                    Ok(from.port())
+                })
+                .collect::<anyhow::Result<Vec<_>>>()
+        })
+    };
+    for i in 1..=QUERIES {
+        let addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, i));
+        let _ = resolver.reverse_lookup(addr, false, false, &LookupPolicy::default());
+    }
+    let ports = responder.join().unwrap()?;
+    assert_eq!(vec![source_port; usize::from(QUERIES)], ports);
+    resolver.shutdown();
+    Ok(())
+}
+
+#[test]
+fn test_reverse_lookup_with_deadline() -> anyhow::Result<()> {
+    let nameserver = ScriptedNameserver::start()?;
+    let config = Config::new(ResolveMethod::Resolv, IpAddrFamily::Ipv4Only, TIMEOUT);
+    let resolver = DnsResolver::with_provider(config, make_provider(&nameserver.socket)?);
+    let policy = LookupPolicy::default();
+    let addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    assert!(matches!(
+        resolver.reverse_lookup_with_deadline(addr, Duration::from_secs(10), &policy),
+        DnsEntry::Resolved(Resolved::Normal(..))
+    ));
+    // the result is cached.
+    assert!(resolver.lazy_reverse_lookup(addr, false, &policy).cached());
+    resolver.shutdown();
+    Ok(())
+}
+
+#[test]
+fn test_reverse_lookup_with_deadline_unresponsive_nameserver() -> anyhow::Result<()> {
+    let nameserver = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0))?;
+    nameserver.set_read_timeout(Some(Duration::from_secs(10)))?;
+    let config = Config::new(ResolveMethod::Resolv, IpAddrFamily::Ipv4Only, TIMEOUT);
+    let resolver = DnsResolver::with_provider(config, make_provider(&nameserver)?);
+    let policy = LookupPolicy::default();
+    let addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    let deadline = Duration::from_millis(200);
+    let start = Instant::now();
+    assert!(matches!(
+        resolver.reverse_lookup_with_deadline(addr, deadline, &policy),
+        DnsEntry::Timeout(timeout_addr) if timeout_addr == addr
+    ));
+    assert!(start.elapsed() >= deadline);
+    assert!(start.elapsed() < TIMEOUT);
+    // the query was sent and the lookup remains in-flight.
+    nameserver.recv(&mut [0_u8; 512])?;
+    assert_eq!(1, resolver.cache_stats().pending);
+    assert_eq!(1, resolver.inflight());
+    resolver.shutdown();
+    Ok(())
+}
+
+#[test]
+fn test_drop_stops_worker() -> anyhow::Result<()> {
+    let nameserver = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0))?;
+    nameserver.set_read_timeout(Some(Duration::from_secs(10)))?;
+    for i in 1..=5 {
+        let config = Config::new(ResolveMethod::Resolv, IpAddrFamily::Ipv4Only, TIMEOUT);
+        let resolver = DnsResolver::with_provider(config, make_provider(&nameserver)?);
+        let addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, i));
+        let _ = resolver.lazy_reverse_lookup(addr, false, &LookupPolicy::default());
+        nameserver.recv(&mut [0_u8; 512])?;
+        let tx = resolver.backend.read().worker.as_ref().unwrap().tx.clone();
+        drop(resolver);
+        // every worker thread holds the receiving side of the queue and so the queue is
+        // only disconnected once all of them, including the one with the in-flight
+        // lookup, have exited.  Each request sent wakes an idle worker thread, which then
+        // exits as it has been superseded.
+        let start = Instant::now();
+        while !matches!(
+            tx.send_timeout(DnsResolveRequest::Hostnames(addr), RESOLVER_QUEUE_TIMEOUT),
+            Err(SendTimeoutError::Disconnected(_))
+        ) {
+            assert!(start.elapsed() < Duration::from_secs(5));
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_cancel() -> anyhow::Result<()> {
+    let nameserver = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0))?;
+    nameserver.set_read_timeout(Some(Duration::from_secs(10)))?;
+    let mut config = Config::new(ResolveMethod::Resolv, IpAddrFamily::Ipv4Only, TIMEOUT);
+    config.max_inflight = 1;
+    let resolver = DnsResolver::with_provider(config, make_provider(&nameserver)?);
+    let policy = LookupPolicy::default();
+    let in_flight = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    let queued = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2));
+    let _ = resolver.lazy_reverse_lookup(in_flight, false, &policy);
+    let _ = resolver.lazy_reverse_lookup(queued, false, &policy);
+    let mut buf = [0_u8; 512];
+    let (len, from) = nameserver.recv_from(&mut buf)?;
+    let query = Message::from_vec(&buf[..len])?;
+    resolver.cancel(in_flight);
+    resolver.cancel(queued);
+    assert_eq!(0, resolver.cache_stats().pending);
+    // the result of the in-flight lookup is discarded and the queued lookup is skipped.
+    nameserver.send_to(&ScriptedNameserver::respond(&query).to_vec()?, from)?;
+    nameserver.set_read_timeout(Some(Duration::from_millis(200)))?;
+    assert!(nameserver.recv(&mut buf).is_err());
+    assert!(resolver.entry_metadata(in_flight).is_none());
+    assert!(resolver.entry_metadata(queued).is_none());
+    // a subsequent lazy lookup is enqueued as normal.
+    assert!(matches!(
+        resolver.reverse_lookup(queued, false, true, &policy),
+        DnsEntry::Pending(_)
+    ));
+    let (len, _) = nameserver.recv_from(&mut buf)?;
+    let query = Message::from_vec(&buf[..len])?;
+    assert_eq!(
+        "2.2.0.192.in-addr.arpa.",
+        query.queries()[0].name().to_string()
+    );
+    resolver.shutdown();
+    Ok(())
+}
+
+#[test]
+fn test_invalidate() -> anyhow::Result<()> {
+    let nameserver = ScriptedNameserver::start()?;
+    let config = Config::new(ResolveMethod::Resolv, IpAddrFamily::Ipv4Only, TIMEOUT);
+    let resolver = DnsResolver::with_provider(config, make_provider(&nameserver.socket)?);
+    let policy = LookupPolicy::default();
+    let addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    let other = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2));
+    let ptr_queries = || {
+        nameserver
+            .queries()
+            .iter()
+            .filter(|(record_type, _)| *record_type == RecordType::PTR)
+            .count()
+    };
+    let _ = resolve_all(&resolver, &policy, &[addr]);
+    assert_eq!(1, ptr_queries());
+    assert!(resolver.invalidate(addr));
+    assert!(resolver.entry_metadata(addr).is_none());
+    assert!(!resolver.invalidate(addr));
+    assert!(!resolver.invalidate(other));
+    // the next lazy lookup is enqueued again, as is the lookup of its `AsInfo`.
+    let queries = nameserver.queries().len();
+    let entry = resolve_all(&resolver, &policy, &[addr]).remove(0);
+    assert!(
+        matches!(entry, DnsEntry::Resolved(Resolved::WithAsInfo(_, _, asinfo))
+        if asinfo.asn == "64496")
+    );
+    assert_eq!(2, ptr_queries());
+    assert!(nameserver.queries()[queries..]
+        .iter()
+        .any(|(record_type, _)| *record_type == RecordType::TXT));
+    resolver.shutdown();
+    Ok(())
+}
+
+#[test]
+fn test_invalidate_pending() -> anyhow::Result<()> {
+    let nameserver = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0))?;
+    nameserver.set_read_timeout(Some(Duration::from_secs(10)))?;
+    let config = Config::new(ResolveMethod::Resolv, IpAddrFamily::Ipv4Only, TIMEOUT);
+    let resolver = DnsResolver::with_provider(config, make_provider(&nameserver)?);
+    let policy = LookupPolicy::default();
+    let addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    let _ = resolver.lazy_reverse_lookup(addr, false, &policy);
+    let mut buf = [0_u8; 512];
+    let (len, from) = nameserver.recv_from(&mut buf)?;
+    // the in-flight lookup is not orphaned and its result is cached.
+    assert!(!resolver.invalidate(addr));
+    assert_eq!(1, resolver.cache_stats().pending);
+    let query = Message::from_vec(&buf[..len])?;
+    nameserver.send_to(&ScriptedNameserver::respond(&query).to_vec()?, from)?;
+    let policy = LookupPolicy {
+        asinfo: false,
+        ..LookupPolicy::default()
+    };
+    assert!(matches!(
+        resolve_all(&resolver, &policy, &[addr]).remove(0),
+        DnsEntry::Resolved(_)
+    ));
+    resolver.shutdown();
+    Ok(())
+}
+
+#[test]
+fn test_edns_tcp_fallback() -> anyhow::Result<()> {
+    let nameserver = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0))?;
+    nameserver.set_read_timeout(Some(Duration::from_secs(10)))?;
+    let listener = TcpListener::bind(nameserver.local_addr()?)?;
+    let mut options = ResolverOpts::default();
+    options.timeout = TIMEOUT;
+    options.cache_size = 0;
+    options.edns0 = true;
+    let resolver = BlockingResolver::new(
+        ResolverConfig::from_parts(
+            None,
+            vec![],
+            NameServerConfigGroup::from_ips_clear(
+                &[nameserver.local_addr()?.ip()],
+                nameserver.local_addr()?.port(),
+                true,
+            ),
+        ),
+        options,
+        TIMEOUT,
+        TIMEOUT,
+        Some(1000),
+        false,
+        None,
+        None,
+    )?;
+    let addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    let lookup = thread::scope(|s| -> anyhow::Result<_> {
+        let lookup = s.spawn(|| resolver.reverse_lookup(addr));
+        // the query sent over UDP advertises the configured payload size and is answered
+        // with a truncated response.
+        let mut buf = [0_u8; 512];
+        let (len, from) = nameserver.recv_from(&mut buf)?;
+        let query = Message::from_vec(&buf[..len])?;
+        assert_eq!(
+            Some(1000),
+            query.extensions().as_ref().map(Edns::max_payload)
+        );
+        nameserver.send_to(&truncated(&query).to_vec()?, from)?;
+        // the query is then retried over TCP, where it is answered in full.
+        respond_tcp(&listener)?;
+        Ok(lookup.join().unwrap())
+    })?;
+    let hostnames = lookup
+        .unwrap()
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    assert_eq!(vec![String::from("host.example.")], hostnames);
+    Ok(())
+}
+
+#[test]
+fn test_tcp_fallback() -> anyhow::Result<()> {
+    let nameserver = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0))?;
+    nameserver.set_read_timeout(Some(Duration::from_millis(10)))?;
+    let listener = TcpListener::bind(nameserver.local_addr()?)?;
+    let policy = LookupPolicy {
+        asinfo: false,
+        ..LookupPolicy::default()
+    };
+    let addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    let config = Config::new(ResolveMethod::Resolv, IpAddrFamily::Ipv4Only, TIMEOUT);
+    let resolver = DnsResolver::with_provider(config, make_provider(&nameserver)?);
+    let stop = AtomicBool::new(false);
+    let entry = thread::scope(|s| -> anyhow::Result<_> {
+        // every query sent over UDP, without EDNS0, is answered with a truncated response.
+        s.spawn(|| {
+            let mut buf = [0_u8; 512];
+            while !stop.load(Ordering::SeqCst) {
+                if let Ok((len, from)) = nameserver.recv_from(&mut buf) {
+                    let query = Message::from_vec(&buf[..len]).unwrap();
+                    assert!(query.extensions().is_none());
+                    let response = truncated(&query).to_vec().unwrap();
+                    nameserver.send_to(&response, from).unwrap();
+                }
+            }
+        });
+        // the query is then retried over TCP, where it is answered in full.
+        let tcp = s.spawn(|| respond_tcp(&listener));
+        let entry = resolve_all(&resolver, &policy, &[addr]).remove(0);
+        stop.store(true, Ordering::SeqCst);
+        tcp.join().unwrap()?;
+        Ok(entry)
+    })?;
+    let DnsEntry::Resolved(Resolved::Normal(_, hostnames)) = entry else {
+        panic!("{entry:?}");
+    };
+    assert_eq!(&["host.example"], &hostnames[..]);
+    resolver.shutdown();
+    Ok(())
+}
+
+#[test]
+fn test_force_tcp() -> anyhow::Result<()> {
+    let nameserver = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0))?;
+    nameserver.set_nonblocking(true)?;
+    let listener = TcpListener::bind(nameserver.local_addr()?)?;
+    let mut options = ResolverOpts::default();
+    options.timeout = TIMEOUT;
+    options.cache_size = 0;
+    // the name server is configured for UDP only and is queried over TCP regardless.
+    let resolver_config = with_force_tcp(
+        ResolverConfig::from_parts(
+            None,
+            vec![],
+            NameServerConfigGroup::from_ips_clear(
+                &[nameserver.local_addr()?.ip()],
+                nameserver.local_addr()?.port(),
+                false,
+            ),
+        ),
+        true,
+    );
+    assert!(resolver_config
+        .name_servers()
+        .iter()
+        .all(|name_server| name_server.protocol == Protocol::Tcp));
+    let resolver = BlockingResolver::new(
+        resolver_config,
+        options,
+        TIMEOUT,
+        TIMEOUT,
+        None,
+        false,
+        None,
+        None,
+    )?;
+    let addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    let lookup = thread::scope(|s| -> anyhow::Result<_> {
+        let lookup = s.spawn(|| resolver.reverse_lookup(addr));
+        respond_tcp(&listener)?;
+        Ok(lookup.join().unwrap())
+    })?;
+    let hostnames = lookup
+        .unwrap()
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    assert_eq!(vec![String::from("host.example.")], hostnames);
+    // no query was sent over UDP.
+    assert!(nameserver.recv_from(&mut [0_u8; 512]).is_err());
+    Ok(())
+}
+
+// all of `127.0.0.0/8` is assigned to the loopback interface on Linux only.
+#[cfg(target_os = "linux")]
+#[test]
+fn test_bind_addr() -> anyhow::Result<()> {
+    let bind_addr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2));
+    let nameserver = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0))?;
+    nameserver.set_read_timeout(Some(Duration::from_secs(10)))?;
+    let listener = TcpListener::bind(nameserver.local_addr()?)?;
+    let make_resolver = |force_tcp| -> anyhow::Result<BlockingResolver> {
+        let mut options = ResolverOpts::default();
+        options.timeout = TIMEOUT;
+        options.cache_size = 0;
+        let resolver_config = with_bind_addr(
+            with_force_tcp(
+                ResolverConfig::from_parts(
+                    None,
+                    vec![],
+                    NameServerConfigGroup::from_ips_clear(
+                        &[nameserver.local_addr()?.ip()],
+                        nameserver.local_addr()?.port(),
+                        true,
+                    ),
+                ),
+                force_tcp,
+            ),
+            Some(bind_addr),
+            None,
+        );
+        Ok(BlockingResolver::new(
+            resolver_config,
+            options,
+            TIMEOUT,
+            TIMEOUT,
+            None,
+            false,
+            None,
+            None,
+        )?)
+    };
+    let addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    let resolver = make_resolver(false)?;
+    let from = thread::scope(|s| -> anyhow::Result<_> {
+        let lookup = s.spawn(|| resolver.reverse_lookup(addr));
+        let mut buf = [0_u8; 512];
+        let (len, from) = nameserver.recv_from(&mut buf)?;
+        let response = ScriptedNameserver::respond(&Message::from_vec(&buf[..len])?);
+        nameserver.send_to(&response.to_vec()?, from)?;
+        lookup.join().unwrap()?;
+        Ok(from)
+    })?;
+    assert_eq!(bind_addr, from.ip());
+    let resolver = make_resolver(true)?;
+    let peer = thread::scope(|s| -> anyhow::Result<_> {
+        let lookup = s.spawn(|| resolver.reverse_lookup(addr));
+        let peer = respond_tcp(&listener)?;
+        lookup.join().unwrap()?;
+        Ok(peer)
+    })?;
+    assert_eq!(bind_addr, peer.ip());
+    Ok(())
+}
+
+#[test]
+fn test_bind_addr_same_family_only() {
+    for bind_addr in [
+        IpAddr::V4(Ipv4Addr::LOCALHOST),
+        IpAddr::V6(Ipv6Addr::LOCALHOST),
+    ] {
+        let resolver_config = with_bind_addr(ResolverConfig::google(), Some(bind_addr), None);
+        assert!(!resolver_config.name_servers().is_empty());
+        assert!(resolver_config.name_servers().iter().all(|name_server| {
+            name_server.socket_addr.is_ipv4() == bind_addr.is_ipv4()
+                && name_server.bind_addr == Some(SocketAddr::new(bind_addr, 0))
+        }));
+    }
+}
+
+#[test]
+fn test_bind_addr_no_name_servers() {
+    let bind_addr = IpAddr::V6(Ipv6Addr::LOCALHOST);
+    let resolver_config = with_bind_addr(
+        ResolverConfig::from_parts(
+            None,
+            vec![],
+            NameServerConfigGroup::from_ips_clear(&[IpAddr::V4(Ipv4Addr::LOCALHOST)], 53, true),
+        ),
+        Some(bind_addr),
+        None,
+    );
+    assert!(resolver_config.name_servers().is_empty());
+    let err = validate_bind_addr(bind_addr, &resolver_config).unwrap_err();
+    assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+    assert_eq!(
+        "no DNS name server has the address family of the bind address ::1",
+        err.to_string()
+    );
+}
+
+#[test]
+fn test_force_tcp_large_response() -> anyhow::Result<()> {
+    let nameserver = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0))?;
+    let listener = TcpListener::bind(nameserver.local_addr()?)?;
+    let mut options = ResolverOpts::default();
+    options.timeout = TIMEOUT;
+    options.cache_size = 0;
+    let resolver = BlockingResolver::new(
+        with_force_tcp(
+            ResolverConfig::from_parts(
+                None,
+                vec![],
+                NameServerConfigGroup::from_ips_clear(
+                    &[nameserver.local_addr()?.ip()],
+                    nameserver.local_addr()?.port(),
+                    false,
+                ),
+            ),
+            true,
+        ),
+        options,
+        TIMEOUT,
+        TIMEOUT,
+        None,
+        false,
+        None,
+        None,
+    )?;
+    let addrs = [
+        IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)),
+        IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2)),
+    ];
+    // both queries are answered, with responses which exceed the 512 byte limit of a UDP
+    // response, on a single TCP connection.
+    let lookups = thread::scope(|s| -> anyhow::Result<_> {
+        let lookups = s.spawn(|| {
+            addrs
+                .iter()
+                .map(|&addr| resolver.reverse_lookup(addr))
+                .collect::<Vec<_>>()
+        });
+        let (mut stream, _) = listener.accept()?;
+        stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+        for _ in addrs {
+            let mut len = [0_u8; 2];
+            stream.read_exact(&mut len)?;
+            let mut buf = vec![0_u8; usize::from(u16::from_be_bytes(len))];
+            stream.read_exact(&mut buf)?;
+            let response = respond_large_ptr(&Message::from_vec(&buf)?).to_vec()?;
+            assert!(response.len() > 512);
+            stream.write_all(&u16::try_from(response.len())?.to_be_bytes())?;
+            stream.write_all(&response)?;
+        }
+        Ok(lookups.join().unwrap())
+    })?;
+    for lookup in lookups {
+        assert_eq!(LARGE_PTR_COUNT, lookup?.iter().count());
+    }
+    Ok(())
+}
+
+/// The number of `PTR` records in a response from `respond_large_ptr`.
+const LARGE_PTR_COUNT: usize = 32;
+
+/// Answer `PTR` queries with `LARGE_PTR_COUNT` distinct hostnames.
+fn respond_large_ptr(query: &Message) -> Message {
+    let mut response = ScriptedNameserver::respond(query);
+    let answers = response
+        .take_answers()
+        .into_iter()
+        .flat_map(|answer| {
+            (0..LARGE_PTR_COUNT).map(move |i| {
+                let mut answer = answer.clone();
+                let hostname = format!("host-{i}.a-rather-long-reverse-zone.example.");
+                answer.set_data(Some(RData::PTR(PTR(Name::from_str(&hostname).unwrap()))));
+                answer
+            })
+        })
+        .collect();
+    response.insert_answers(answers);
+    response
+}
+
+/// A truncated, and otherwise empty, response to a `query`.
+fn truncated(query: &Message) -> Message {
+    let mut truncated = Message::new();
+    truncated
+        .set_id(query.id())
+        .set_message_type(MessageType::Response)
+        .set_truncated(true)
+        .add_queries(query.queries().to_vec());
+    truncated
+}
+
+/// Accept a single TCP connection from the `listener` and answer a single query on it
+/// as per `ScriptedNameserver::respond`, returning the address of the peer.
+fn respond_tcp(listener: &TcpListener) -> anyhow::Result<SocketAddr> {
+    let (mut stream, peer) = listener.accept()?;
+    let mut len = [0_u8; 2];
+    stream.read_exact(&mut len)?;
+    let mut buf = vec![0_u8; usize::from(u16::from_be_bytes(len))];
+    stream.read_exact(&mut buf)?;
+    let response = ScriptedNameserver::respond(&Message::from_vec(&buf)?).to_vec()?;
+    stream.write_all(&u16::try_from(response.len())?.to_be_bytes())?;
+    stream.write_all(&response)?;
+    Ok(peer)
+}
+
+#[test]
+fn test_validate_dnssec() -> anyhow::Result<()> {
+    let nameserver = ScriptedNameserver::start_with(respond_dnssec)?;
+    let mut options = ResolverOpts::default();
+    options.timeout = TIMEOUT;
+    options.cache_size = 0;
+    let resolver = BlockingResolver::new(
+        ResolverConfig::from_parts(
+            None,
+            vec![],
+            NameServerConfigGroup::from_ips_clear(
+                &[nameserver.socket.local_addr()?.ip()],
+                nameserver.socket.local_addr()?.port(),
+                true,
+            ),
+        ),
+        options,
+        TIMEOUT,
+        TIMEOUT,
+        None,
+        true,
+        None,
+        None,
+    )?;
+    let config = Config::new(ResolveMethod::Resolv, IpAddrFamily::Ipv4Only, TIMEOUT);
+    let resolver = DnsResolver::with_provider(
+        config,
+        DnsProvider::TrustDns(Arc::new(resolver), Arc::new(Hosts::default())),
+    );
+    let policy = LookupPolicy {
+        asinfo: false,
+        ..LookupPolicy::default()
+    };
+    let addrs = (1..=4)
+        .map(|i| IpAddr::V4(Ipv4Addr::new(192, 0, 2, i)))
+        .collect::<Vec<_>>();
+    let entries = resolve_all(&resolver, &policy, &addrs);
+    let dnssec = |addr| resolver.entry_metadata(addr).unwrap().dnssec();
+    for (entry, status) in entries[..2]
+        .iter()
+        .zip([DnssecStatus::Secure, DnssecStatus::Insecure])
+    {
+        let DnsEntry::Resolved(Resolved::Normal(addr, hostnames)) = entry else {
+            panic!("{entry:?}");
+        };
+        assert_eq!(&["host.example"], &hostnames[..]);
+        assert_eq!(Some(status), dnssec(*addr));
+    }
+    assert!(matches!(entries[2], DnsEntry::Bogus(addr) if addr == addrs[2]));
+    assert_eq!(Some(DnssecStatus::Bogus), dnssec(addrs[2]));
+    // a failure which persists with validation disabled is not bogus.
+    assert!(matches!(
+        entries[3],
+        DnsEntry::Failed(_, Some(Rcode::ServFail))
+    ));
+    assert_eq!(None, dnssec(addrs[3]));
+    assert_eq!(1, resolver.cache_stats().bogus);
+    resolver.shutdown();
+    Ok(())
+}
+
+#[test]
+fn test_reverse_lookup_multiple_ptr() -> anyhow::Result<()> {
+    let nameserver = ScriptedNameserver::start_with(respond_multiple_ptr)?;
+    for validate_dnssec in [false, true] {
+        let mut options = ResolverOpts::default();
+        options.timeout = TIMEOUT;
+        options.cache_size = 0;
+        let resolver = BlockingResolver::new(
+            ResolverConfig::from_parts(
+                None,
+                vec![],
+                NameServerConfigGroup::from_ips_clear(
+                    &[nameserver.socket.local_addr()?.ip()],
+                    nameserver.socket.local_addr()?.port(),
+                    true,
+                ),
+            ),
+            options,
+            TIMEOUT,
+            TIMEOUT,
+            None,
+            validate_dnssec,
+            None,
+            None,
+        )?;
+        let config = Config::new(ResolveMethod::Resolv, IpAddrFamily::Ipv4Only, TIMEOUT);
+        let resolver = DnsResolver::with_provider(
+            config,
+            DnsProvider::TrustDns(Arc::new(resolver), Arc::new(Hosts::default())),
+        );
+        let policy = LookupPolicy {
+            asinfo: false,
+            ..LookupPolicy::default()
+        };
+        let addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+        let entries = resolve_all(&resolver, &policy, &[addr]);
+        let DnsEntry::Resolved(Resolved::Normal(_, hostnames)) = &entries[0] else {
+            panic!("{:?}", entries[0]);
+        };
+        assert_eq!(&["b.example", "a.example"], &hostnames[..]);
+        resolver.shutdown();
+    }
+    Ok(())
+}
+
+/// Answer `PTR` queries with two records, in non-alphabetical order.
+fn respond_multiple_ptr(query: &Message) -> Message {
+    let mut response = ScriptedNameserver::respond(query);
+    let answers = response
+        .take_answers()
+        .into_iter()
+        .flat_map(|answer| {
+            ["b.example.", "a.example."].map(|hostname| {
+                let mut answer = answer.clone();
+                answer.set_data(Some(RData::PTR(PTR(Name::from_str(hostname).unwrap()))));
+                answer
+            })
+        })
+        .collect();
+    response.insert_answers(answers);
+    response
+}
+
+/// Answer `PTR` queries as a validating resolver would for a signed zone (`192.0.2.1`),
+/// an unsigned zone (`192.0.2.2`), a zone with invalid signatures (`192.0.2.3`) and a
+/// zone which cannot be resolved at all (any other address).
+///
+/// The `AD` bit is only set if the DNSSEC OK bit of the query is set.
+fn respond_dnssec(query: &Message) -> Message {
+    let mut response = ScriptedNameserver::respond(query);
+    let dnssec_ok = query.extensions().as_ref().is_some_and(Edns::dnssec_ok);
+    match query.queries()[0].name().to_string().as_str() {
+        "1.2.0.192.in-addr.arpa." => {
+            response.set_authentic_data(dnssec_ok);
+        }
+        "2.2.0.192.in-addr.arpa." => {}
+        "3.2.0.192.in-addr.arpa." if query.checking_disabled() => {}
+        _ => {
+            response.take_answers();
+            response.set_response_code(ResponseCode::ServFail);
+        }
+    }
+    response
+}
+
+#[test]
+fn test_with_edns_udp_payload_size() -> anyhow::Result<()> {
+    let mut query = Message::new();
+    query.set_id(1).set_message_type(MessageType::Query);
+    assert!(with_edns_udp_payload_size(&query.to_vec()?, 1000).is_none());
+    query.set_edns(Edns::new());
+    let payload_size = |size| -> anyhow::Result<Option<u16>> {
+        let rewritten = with_edns_udp_payload_size(&query.to_vec()?, size).unwrap();
+        Ok(Message::from_vec(&rewritten)?
+            .extensions()
+            .as_ref()
+            .map(Edns::max_payload))
+    };
+    assert_eq!(Some(1000), payload_size(1000)?);
+    assert_eq!(Some(512), payload_size(100)?);
+    assert_eq!(Some(1232), payload_size(4096)?);
+    Ok(())
+}
+
+#[test]
+fn test_max_inflight() -> anyhow::Result<()> {
+    let nameserver = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0))?;
+    nameserver.set_read_timeout(Some(Duration::from_millis(500)))?;
+    let mut config = Config::new(ResolveMethod::Resolv, IpAddrFamily::Ipv4Only, TIMEOUT);
+    config.max_inflight = 3;
+    let resolver = DnsResolver::with_provider(config, make_provider(&nameserver)?);
+    for i in 1..=10 {
+        let addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, i));
+        let _ = resolver.reverse_lookup(addr, false, true, &LookupPolicy::default());
+    }
+    // no query is ever answered and so only the first 3 are sent.
+    let mut queries = 0;
+    while nameserver.recv(&mut [0_u8; 512]).is_ok() {
+        queries += 1;
+    }
+    assert_eq!(3, queries);
+    assert_eq!(10, resolver.cache_stats().pending);
+    assert_eq!(3, resolver.inflight());
+    assert_eq!(7, resolver.queue_depth());
+    resolver.shutdown();
+    assert_eq!(0, resolver.inflight());
+    assert_eq!(0, resolver.queue_depth());
+    Ok(())
+}
+
+#[test]
+fn test_max_lookups_per_sec() -> anyhow::Result<()> {
+    let nameserver = ScriptedNameserver::start()?;
+    let mut config = Config::new(ResolveMethod::Resolv, IpAddrFamily::Ipv4Only, TIMEOUT);
+    config.max_inflight = 4;
+    config.max_lookups_per_sec = Some(10);
+    let resolver = DnsResolver::with_provider(config, make_provider(&nameserver.socket)?);
+    let policy = LookupPolicy {
+        asinfo: false,
+        ..LookupPolicy::default()
+    };
+    let addrs = (1..=5)
+        .map(|i| IpAddr::V4(Ipv4Addr::new(192, 0, 2, i)))
+        .collect::<Vec<_>>();
+    let start = Instant::now();
+    for addr in &addrs {
+        let _ = resolver.reverse_lookup(*addr, false, true, &policy);
+    }
+    // the first lookup starts at once and the others wait their turn whilst pending.
+    thread::sleep(Duration::from_millis(50));
+    let stats = resolver.cache_stats();
+    assert!(stats.throttled > 0, "{stats:?}");
+    assert!(stats.pending >= stats.throttled, "{stats:?}");
+    let entries = resolve_all(&resolver, &policy, &addrs);
+    assert!(entries
+        .iter()
+        .all(|entry| matches!(entry, DnsEntry::Resolved(_))));
+    assert!(start.elapsed() >= Duration::from_millis(400));
+    assert_eq!(0, resolver.cache_stats().throttled);
+    resolver.shutdown();
+    Ok(())
+}
+
+#[test]
+fn test_max_inflight_never_exceeded() -> anyhow::Result<()> {
+    let nameserver = ScriptedNameserver::start()?;
+    let mut config = Config::new(ResolveMethod::Resolv, IpAddrFamily::Ipv4Only, TIMEOUT);
+    config.max_inflight = 3;
+    let resolver = DnsResolver::with_provider(config, make_provider(&nameserver.socket)?);
+    let policy = LookupPolicy::default();
+    let addrs = (1..=50)
+        .map(|i| IpAddr::V4(Ipv4Addr::new(192, 0, 2, i)))
+        .collect::<Vec<_>>();
+    for addr in &addrs {
+        let _ = resolver.reverse_lookup(*addr, false, true, &policy);
+    }
+    let start = Instant::now();
+    let mut max_inflight = 0;
+    while resolver.cache_stats().pending > 0 {
+        max_inflight = max_inflight.max(resolver.inflight());
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+    assert!(max_inflight <= 3, "{max_inflight}");
+    assert_eq!(addrs.len(), resolver.cache_stats().resolved);
+    resolver.shutdown();
+    Ok(())
+}
+
+#[test]
+fn test_sessions_with_opposing_policies() -> anyhow::Result<()> {
+    let nameserver = ScriptedNameserver::start()?;
+    let config = Config::new(ResolveMethod::Resolv, IpAddrFamily::Ipv4Only, TIMEOUT);
+    let resolver = DnsResolver::with_provider(config, make_provider(&nameserver.socket)?);
+    let permissive = LookupPolicy::default();
+    let restrictive = LookupPolicy {
+        suppressed: vec![IpNetwork::from_str("192.0.2.0/24")?],
+        asinfo: false,
+        timeout: None,
+    };
+    let suppressed_addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    let shared_addr = IpAddr::V4(Ipv4Addr::new(198, 51, 100, 1));
+    let addrs = [suppressed_addr, shared_addr];
+    let permissive_rx = resolver.subscribe(&permissive);
+    let restrictive_rx = resolver.subscribe(&restrictive);
+    let (permissive_entries, restrictive_entries) = thread::scope(|s| {
+        let permissive = s.spawn(|| resolve_all(&resolver, &permissive, &addrs));
+        let restrictive = s.spawn(|| resolve_all(&resolver, &restrictive, &addrs));
+        (permissive.join().unwrap(), restrictive.join().unwrap())
+    });
+    for entry in permissive_entries {
+        assert!(
+            matches!(&entry, DnsEntry::Resolved(Resolved::WithAsInfo(_, hosts, asinfo))
+                if hosts == &["host.example"] && asinfo.asn == "64496"),
+            "{entry:?}"
+        );
+    }
+    assert!(matches!(
+        restrictive_entries[0],
+        DnsEntry::NotFound(Unresolved::Normal(addr)) if addr == suppressed_addr
+    ));
+    assert!(
+        matches!(&restrictive_entries[1], DnsEntry::Resolved(Resolved::Normal(_, hosts))
+            if hosts == &["host.example"]),
+        "{:?}",
+        restrictive_entries[1]
+    );
+    // the entries resolved for the permissive session are not exposed to the restrictive
+    // session, even though they are now cached.
+    assert!(matches!(
+        resolver.reverse_lookup(suppressed_addr, true, true, &restrictive),
+        DnsEntry::NotFound(Unresolved::Normal(_))
+    ));
+    assert!(matches!(
+        resolver.reverse_lookup(shared_addr, true, true, &restrictive),
+        DnsEntry::Resolved(Resolved::Normal(_, _))
+    ));
+    let restrictive_cached = resolver.cached_entries(&restrictive);
+    assert_eq!(1, restrictive_cached.len());
+    assert!(matches!(
+        &restrictive_cached[0],
+        (addr, DnsEntry::Resolved(Resolved::Normal(_, _))) if *addr == shared_addr
+    ));
+    assert!(resolver
+        .cached_entries(&permissive)
+        .iter()
+        .all(|(_, entry)| matches!(entry, DnsEntry::Resolved(Resolved::WithAsInfo(..)))));
+    // each completion is sent to every subscriber at once, and so once both have been sent to
+    // the permissive subscriber any sent to the restrictive subscriber has also been sent.
+    for _ in addrs {
+        permissive_rx.recv_timeout(Duration::from_secs(10))?;
+    }
+    assert_eq!(
+        vec![shared_addr],
+        restrictive_rx
+            .try_iter()
+            .map(|(addr, _)| addr)
+            .collect::<Vec<_>>()
+    );
+    // each address was looked up once, and the `AsInfo` only for the permissive session.
+    let queries = nameserver.queries();
+    let ptr_queries = |name: &str| {
+        queries
+            .iter()
+            .filter(|(record_type, query)| *record_type == RecordType::PTR && query == name)
+            .count()
+    };
+    assert_eq!(1, ptr_queries("1.2.0.192.in-addr.arpa."));
+    assert_eq!(1, ptr_queries("1.100.51.198.in-addr.arpa."));
+    let txt_queries = queries
+        .iter()
+        .filter(|(record_type, _)| *record_type == RecordType::TXT)
+        .count();
+    assert_eq!(4, txt_queries);
+    resolver.shutdown();
+    Ok(())
+}
+
+#[test]
+fn test_session_timeout() -> anyhow::Result<()> {
+    let nameserver = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0))?;
+    nameserver.set_read_timeout(Some(Duration::from_secs(10)))?;
+    let config = Config::new(ResolveMethod::Resolv, IpAddrFamily::Ipv4Only, TIMEOUT);
+    let resolver = DnsResolver::with_provider(config, make_provider(&nameserver)?);
+    let impatient = LookupPolicy {
+        timeout: Some(Duration::from_millis(100)),
+        ..LookupPolicy::default()
+    };
+    let addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    assert!(matches!(
+        resolver.reverse_lookup(addr, false, true, &impatient),
+        DnsEntry::Pending(_)
+    ));
+    nameserver.recv(&mut [0_u8; 512])?;
+    thread::sleep(Duration::from_millis(150));
+    assert!(matches!(
+        resolver.reverse_lookup(addr, false, true, &impatient),
+        DnsEntry::Timeout(_)
+    ));
+    // the lookup remains pending for other sessions and is not enqueued again.
+    assert!(matches!(
+        resolver.reverse_lookup(addr, false, true, &LookupPolicy::default()),
+        DnsEntry::Pending(_)
+    ));
+    assert_eq!(1, resolver.cache_stats().pending);
+    nameserver.set_read_timeout(Some(Duration::from_millis(200)))?;
+    assert!(nameserver.recv(&mut [0_u8; 512]).is_err());
+    resolver.shutdown();
+    Ok(())
+}
+
+#[test]
+fn test_max_cache_entries() -> anyhow::Result<()> {
+    let nameserver = ScriptedNameserver::start()?;
+    let config = Config::builder()
+        .resolve_method(ResolveMethod::Resolv)
+        .addr_family(IpAddrFamily::Ipv4Only)
+        .timeout(TIMEOUT)
+        .max_cache_entries(Some(2))
+        .build();
+    let resolver = DnsResolver::with_provider(config, make_provider(&nameserver.socket)?);
+    let policy = LookupPolicy::default();
+    let addrs = (1..=3)
+        .map(|i| IpAddr::V4(Ipv4Addr::new(192, 0, 2, i)))
+        .collect::<Vec<_>>();
+    for addr in &addrs {
+        resolve_all(&resolver, &policy, &[*addr]);
+        thread::sleep(Duration::from_millis(10));
+    }
+    // the entry of the first address was evicted to make room for the third.
+    assert_eq!(2, resolver.cache_stats().resolved);
+    assert!(resolver
+        .lazy_reverse_lookup(addrs[2], false, &policy)
+        .cached());
+    let lookup = resolver.lazy_reverse_lookup(addrs[0], false, &policy);
+    assert!(!lookup.cached());
+    assert!(matches!(lookup.entry(), DnsEntry::Pending(_)));
+    resolver.shutdown();
+    Ok(())
+}
+
+#[test]
+fn test_hosts_file() -> anyhow::Result<()> {
+    let nameserver = ScriptedNameserver::start()?;
+    let config = Config::new(ResolveMethod::Resolv, IpAddrFamily::Ipv4Only, TIMEOUT);
+    let hosts = Hosts::parse("192.0.2.1 pinned.example pinned\n2001:db8::1 v6.example");
+    let resolver = DnsResolver::with_provider(
+        config,
+        make_provider_with(&nameserver.socket, IpAddrFamily::Ipv4Only, hosts, None)?,
+    );
+    let policy = LookupPolicy {
+        asinfo: false,
+        ..LookupPolicy::default()
+    };
+    let pinned = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    let other = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2));
+    assert!(matches!(
+        resolver.reverse_lookup(pinned, false, false, &policy),
+        DnsEntry::Resolved(Resolved::Normal(_, hostnames))
+            if hostnames == ["pinned.example", "pinned"]
+    ));
+    let entries = resolve_all(&resolver, &policy, &[pinned, other]);
+    assert!(
+        matches!(&entries[0], DnsEntry::Resolved(Resolved::Normal(_, hostnames))
+        if hostnames == &["pinned.example", "pinned"])
+    );
+    assert!(
+        matches!(&entries[1], DnsEntry::Resolved(Resolved::Normal(_, hostnames))
+        if hostnames == &["host.example"])
+    );
+    assert_eq!(
+        Some(Duration::ZERO),
+        resolver
+            .entry_metadata(pinned)
+            .and_then(|meta| meta.elapsed())
+    );
+    assert_eq!(
+        vec![pinned],
+        resolver
+            .lookup("PINNED.example.")?
+            .into_iter()
+            .collect::<Vec<_>>()
+    );
+    // only the address which is not in the hosts file, and the hostname which has no
+    // address of the family in the hosts file, are queried.
+    let _ = resolver.lookup("v6.example");
+    let queries = nameserver.queries();
+    assert_eq!(
+        (RecordType::PTR, String::from("2.2.0.192.in-addr.arpa.")),
+        queries[0]
+    );
+    assert_eq!((RecordType::A, String::from("v6.example.")), queries[1]);
+    assert!(queries
+        .iter()
+        .all(|(_, name)| !name.contains("pinned") && !name.starts_with("1.2.0.192")));
+    resolver.shutdown();
+    Ok(())
+}
+
+#[test]
+fn test_fallback_addr_family() -> anyhow::Result<()> {
+    let v4 = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    let v6 = IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1));
+    for (addr_family, preferred, fallback) in [
+        (
+            IpAddrFamily::Ipv6thenIpv4,
+            (RecordType::AAAA, v6),
+            (RecordType::A, v4),
+        ),
+        (
+            IpAddrFamily::Ipv4thenIpv6,
+            (RecordType::A, v4),
+            (RecordType::AAAA, v6),
+        ),
+    ] {
+        let nameserver = ScriptedNameserver::start()?;
+        let config = Config::new(ResolveMethod::Resolv, addr_family, TIMEOUT);
+        let resolver = DnsResolver::with_provider(
+            config,
+            make_provider_with(&nameserver.socket, addr_family, Hosts::default(), None)?,
+        );
+        let lookup = |hostname| -> anyhow::Result<Vec<IpAddr>> {
+            Ok(resolver.lookup(hostname)?.into_iter().collect())
+        };
+        // both families are queried, and the preferred family is used if it has addresses.
+        assert_eq!(vec![preferred.1], lookup("dual.example")?);
+        assert_eq!(
+            HashSet::from([
+                (preferred.0, String::from("dual.example.")),
+                (fallback.0, String::from("dual.example."))
+            ]),
+            nameserver.wait_for_queries(2).into_iter().collect()
+        );
+        let hostname = if preferred.0 == RecordType::A {
+            "v6.example"
+        } else {
+            "v4.example"
+        };
+        assert_eq!(vec![fallback.1], lookup(hostname)?);
+        assert_eq!(
+            HashSet::from([
+                (preferred.0, format!("{hostname}.")),
+                (fallback.0, format!("{hostname}."))
+            ]),
+            nameserver.wait_for_queries(4)[2..]
+                .iter()
+                .cloned()
+                .collect()
+        );
+        resolver.shutdown();
+    }
+    Ok(())
+}
+
+#[test]
+fn test_lookup_cname_chain() -> anyhow::Result<()> {
+    let nameserver = ScriptedNameserver::start_scripted(respond_cname)?;
+    let config = Config::new(ResolveMethod::Resolv, IpAddrFamily::Ipv4Only, TIMEOUT);
+    let resolver = DnsResolver::with_provider(config, make_provider(&nameserver.socket)?);
+    let addrs = resolver.lookup("www.example")?;
+    assert_eq!(
+        vec![IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))],
+        addrs.iter().copied().collect::<Vec<_>>()
+    );
+    assert_eq!(&["edge.example", "host.example"], addrs.cnames());
+    let addrs = resolver.lookup("host.example")?;
+    assert!(addrs.cnames().is_empty());
+    resolver.shutdown();
+    Ok(())
+}
+
+#[test]
+fn test_lookup_cname_loop() -> anyhow::Result<()> {
+    let nameserver = ScriptedNameserver::start_scripted(respond_cname)?;
+    let config = Config::new(ResolveMethod::Resolv, IpAddrFamily::Ipv4Only, TIMEOUT);
+    let resolver = DnsResolver::with_provider(config, make_provider(&nameserver.socket)?);
+    let err = resolver.lookup("loop1.example").unwrap_err();
+    assert!(matches!(err, Error::CnameLoop(hostname) if hostname == "loop1.example"));
+    resolver.shutdown();
+    Ok(())
+}
+
+/// Answer with a `CNAME` chain `www` -> `edge` -> `host` and a `CNAME` loop between
+/// `loop1` and `loop2`.
+fn respond_cname(query: &Message) -> Option<Message> {
+    let cname = |alias: &str, target: &str| {
+        Record::from_rdata(
+            Name::from_str(alias).unwrap(),
+            1,
+            RData::CNAME(CNAME(Name::from_str(target).unwrap())),
+        )
+    };
+    let mut response = ScriptedNameserver::respond(&Message::new());
+    response
+        .set_id(query.id())
+        .set_recursion_desired(query.recursion_desired())
+        .add_queries(query.queries().to_vec());
+    let question = query.queries().first()?;
+    match (question.name().to_string().as_str(), question.query_type()) {
+        ("www.example.", RecordType::A) => {
+            response.add_answer(cname("www.example.", "edge.example."));
+            response.add_answer(cname("edge.example.", "host.example."));
+            response.add_answer(Record::from_rdata(
+                Name::from_str("host.example.").unwrap(),
+                1,
+                RData::A(A::new(192, 0, 2, 1)),
+            ));
+        }
+        ("host.example.", RecordType::A) => {
+            response.add_answer(Record::from_rdata(
+                question.name().clone(),
+                1,
+                RData::A(A::new(192, 0, 2, 1)),
+            ));
+        }
+        ("loop1.example.", _) => {
+            response.add_answer(cname("loop1.example.", "loop2.example."));
+        }
+        ("loop2.example.", _) => {
+            response.add_answer(cname("loop2.example.", "loop1.example."));
+        }
+        _ => {}
+    }
+    Some(response)
+}
+
+#[test]
+fn test_concurrent_addr_family_unanswered() -> anyhow::Result<()> {
+    let v4 = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    let v6 = IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1));
+    for (addr_family, unanswered, expected) in [
+        (IpAddrFamily::Ipv6thenIpv4, RecordType::AAAA, v4),
+        (IpAddrFamily::Ipv6thenIpv4, RecordType::A, v6),
+        (IpAddrFamily::Ipv4thenIpv6, RecordType::A, v6),
+        (IpAddrFamily::Ipv4thenIpv6, RecordType::AAAA, v4),
+    ] {
+        let nameserver = ScriptedNameserver::start_scripted(move |query| {
+            query
+                .queries()
+                .iter()
+                .all(|query| query.query_type() != unanswered)
+                .then(|| ScriptedNameserver::respond(query))
+        })?;
+        let config = Config::new(ResolveMethod::Resolv, addr_family, TIMEOUT);
+        let resolver = DnsResolver::with_provider(
+            config,
+            make_provider_with(&nameserver.socket, addr_family, Hosts::default(), None)?,
+        );
+        // the lookup does not wait for the unanswered family to time out.
+        let start = Instant::now();
+        let addrs = resolver.lookup("dual.example")?;
+        assert!(start.elapsed() < TIMEOUT / 2);
+        assert_eq!(vec![expected], addrs.into_iter().collect::<Vec<_>>());
+        resolver.shutdown();
+    }
+    Ok(())
+}
+
+#[test]
+fn test_shared_runtime() -> anyhow::Result<()> {
+    let runtime = Builder::new_multi_thread()
+        .worker_threads(1)
+        .enable_all()
+        .build()?;
+    let nameserver = ScriptedNameserver::start()?;
+    let config = Config::new(ResolveMethod::Resolv, IpAddrFamily::Ipv4Only, TIMEOUT);
+    let provider = make_provider_with(
+        &nameserver.socket,
+        IpAddrFamily::Ipv4Only,
+        Hosts::default(),
+        Some(runtime.handle()),
+    )?;
+    let resolver = DnsResolver::with_provider(config.clone(), provider);
+    let policy = LookupPolicy::default();
+    let addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    assert!(matches!(&resolve_all(&resolver, &policy, &[addr])[0],
+        DnsEntry::Resolved(Resolved::WithAsInfo(_, hostnames, _))
+            if hostnames == &["host.example"]));
+    drop(resolver);
+
+    // an in-flight lookup is aborted when the resolver is dropped whilst the shared
+    // runtime remains usable.
+    let unresponsive = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0))?;
+    unresponsive.set_read_timeout(Some(Duration::from_secs(10)))?;
+    let provider = make_provider_with(
+        &unresponsive,
+        IpAddrFamily::Ipv4Only,
+        Hosts::default(),
+        Some(runtime.handle()),
+    )?;
+    let resolver = DnsResolver::with_provider(config, provider);
+    let (tx, rx) = std::sync::mpsc::channel();
+    let DnsProvider::TrustDns(blocking, _) = resolver.backend.read().provider.clone() else {
+        unreachable!()
+    };
+    let lookup = thread::spawn(move || {
+        let res = blocking.reverse_lookup(addr);
+        tx.send(()).unwrap();
+        res
+    });
+    unresponsive.recv(&mut [0_u8; 512])?;
+    drop(resolver);
+    rx.recv_timeout(Duration::from_secs(5))?;
+    assert!(matches!(lookup.join().unwrap(), Err(Error::Shutdown)));
+    assert_eq!(1, runtime.block_on(async { 1 }));
+    Ok(())
+}
+
+#[test]
+fn test_unresolved_entry() {
+    let addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    let no_records = |response_code| {
+        Error::LookupFailed(Box::new(ResolveError::from(
+            ResolveErrorKind::NoRecordsFound {
+                query: Box::new(Query::new()),
+                soa: None,
+                negative_ttl: None,
+                response_code,
+                trusted: true,
+            },
+        )))
+    };
+    for response_code in [ResponseCode::NXDomain, ResponseCode::NoError] {
+        assert!(matches!(
+            unresolved_entry(addr, &no_records(response_code)),
+            DnsEntry::NotFound(Unresolved::Normal(a)) if a == addr
+        ));
+    }
+    for (response_code, rcode) in [
+        (ResponseCode::ServFail, Rcode::ServFail),
+        (ResponseCode::Refused, Rcode::Refused),
+        (ResponseCode::NotImp, Rcode::Other(4)),
+    ] {
+        assert!(matches!(
+            unresolved_entry(addr, &no_records(response_code)),
+            DnsEntry::Failed(a, Some(r)) if a == addr && r == rcode
+        ));
+    }
+    assert!(matches!(
+        unresolved_entry(addr, &Error::Timeout(TimeoutReason::Query)),
+        DnsEntry::Timeout(_)
+    ));
+    assert!(matches!(
+        unresolved_entry(addr, &Error::Shutdown),
+        DnsEntry::Failed(_, None)
+    ));
+    assert_eq!(
+        "Failed (SERVFAIL): 192.0.2.1",
+        DnsEntry::Failed(addr, Some(Rcode::ServFail)).to_string()
+    );
+}
+
+#[test]
+fn test_entry_metadata() -> anyhow::Result<()> {
+    let nameserver = ScriptedNameserver::start()?;
+    let config = Config::new(ResolveMethod::Resolv, IpAddrFamily::Ipv4Only, TIMEOUT);
+    let resolver = DnsResolver::with_provider(config, make_provider(&nameserver.socket)?);
+    let policy = LookupPolicy::default();
+    let addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    assert!(resolver.entry_metadata(addr).is_none());
+    let _ = resolver.lazy_reverse_lookup(addr, false, &policy);
+    let pending = resolver.entry_metadata(addr).unwrap();
+    let enqueued = pending.enqueued().unwrap();
+    resolve_all(&resolver, &policy, &[addr]);
+    let meta = resolver.entry_metadata(addr).unwrap();
+    assert!(!meta.is_pending());
+    assert!(meta.enqueued().is_none());
+    assert!(meta.completed().unwrap() >= enqueued);
+    assert!(meta.elapsed().unwrap() <= enqueued.elapsed()?);
+    assert!(meta.age() < Duration::from_secs(10));
+    assert_eq!(Some(nameserver.socket.local_addr()?), meta.server());
+    resolver.shutdown();
+    Ok(())
+}
+
+#[test]
+fn test_entry_metadata_elapsed() -> anyhow::Result<()> {
+    const DELAY: Duration = Duration::from_millis(200);
+    let nameserver = ScriptedNameserver::start_scripted(|query| {
+        thread::sleep(DELAY);
+        Some(ScriptedNameserver::respond(query))
+    })?;
+    let config = Config::new(ResolveMethod::Resolv, IpAddrFamily::Ipv4Only, TIMEOUT);
+    let resolver = DnsResolver::with_provider(config, make_provider(&nameserver.socket)?);
+    let policy = LookupPolicy::default();
+    let addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    let start = Instant::now();
+    resolve_all(&resolver, &policy, &[addr]);
+    let elapsed = resolver.entry_metadata(addr).unwrap().elapsed().unwrap();
+    assert!(elapsed >= DELAY);
+    assert!(elapsed <= start.elapsed());
+    resolver.shutdown();
+    Ok(())
+}
+
+#[test]
+fn test_entry_metadata_pending() -> anyhow::Result<()> {
+    let nameserver = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0))?;
+    nameserver.set_read_timeout(Some(Duration::from_secs(10)))?;
+    let config = Config::new(ResolveMethod::Resolv, IpAddrFamily::Ipv4Only, TIMEOUT);
+    let resolver = DnsResolver::with_provider(config, make_provider(&nameserver)?);
+    let addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    let _ = resolver.lazy_reverse_lookup(addr, false, &LookupPolicy::default());
+    nameserver.recv(&mut [0_u8; 512])?;
+    thread::sleep(Duration::from_millis(50));
+    // the lookup is never answered and so remains pending with a growing age.
+    let meta = resolver.entry_metadata(addr).unwrap();
+    assert!(meta.is_pending());
+    assert!(meta.completed().is_none());
+    assert!(meta.elapsed().is_none());
+    assert!(meta.server().is_none());
+    assert!(meta.age() >= Duration::from_millis(50));
+    resolver.shutdown();
+    Ok(())
+}
+
+#[test]
+fn test_cached_entries() -> anyhow::Result<()> {
+    let nameserver = ScriptedNameserver::start()?;
+    let config = Config::new(ResolveMethod::Resolv, IpAddrFamily::Ipv4Only, TIMEOUT);
+    let resolver = DnsResolver::with_provider(config, make_provider(&nameserver.socket)?);
+    let policy = LookupPolicy::default();
+    let addr1 = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2));
+    let addr2 = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    assert!(resolver.cached_entries(&policy).is_empty());
+    resolve_all(&resolver, &policy, &[addr1, addr2]);
+    let queries = nameserver.queries();
+    let stats = resolver.cache_stats();
+    let entries = resolver.cached_entries(&policy);
+    assert_eq!(
+        vec![addr2, addr1],
+        entries.iter().map(|(addr, _)| *addr).collect::<Vec<_>>()
+    );
+    assert!(entries.iter().all(|(addr, entry)| matches!(
+        entry,
+        DnsEntry::Resolved(
+            Resolved::Normal(resolved_addr, _) | Resolved::WithAsInfo(resolved_addr, _, _)
+        ) if resolved_addr == addr
+    )));
+    // enumerating the cache neither performs lookups nor modifies the cache.
+    assert_eq!(
+        format!("{entries:?}"),
+        format!("{:?}", resolver.cached_entries(&policy))
+    );
+    assert_eq!(queries, nameserver.queries());
+    assert_eq!(stats, resolver.cache_stats());
+    resolver.shutdown();
+    Ok(())
+}
+
+#[test]
+fn test_ttl_refresh() -> anyhow::Result<()> {
+    let nameserver = ScriptedNameserver::start()?;
+    let config = Config::builder()
+        .resolve_method(ResolveMethod::Resolv)
+        .addr_family(IpAddrFamily::Ipv4Only)
+        .timeout(TIMEOUT)
+        .ttl_bounds(Some(TtlBounds {
+            min: Duration::ZERO,
+            max: Duration::from_secs(60),
+        }))
+        .build();
+    let resolver = DnsResolver::with_provider(config, make_provider(&nameserver.socket)?);
+    let policy = LookupPolicy::default();
+    let addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    let ptr_queries = || {
+        nameserver
+            .queries()
+            .iter()
+            .filter(|(record_type, _)| *record_type == RecordType::PTR)
+            .count()
+    };
+    let is_resolved = |lookup: &LazyDnsEntry| {
+        matches!(lookup.entry(), DnsEntry::Resolved(Resolved::Normal(_, hostnames))
+            if hostnames == &["host.example"])
+    };
+    let start = Instant::now();
+    while !is_resolved(&resolver.lazy_reverse_lookup(addr, false, &policy)) {
+        assert!(start.elapsed() < Duration::from_secs(10));
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert!(resolver.lazy_reverse_lookup(addr, false, &policy).cached());
+    assert_eq!(1, ptr_queries());
+    // once the TTL has elapsed the stale entry is served whilst it is refreshed, once.
+    thread::sleep(Duration::from_millis(1100));
+    let lookup = resolver.lazy_reverse_lookup(addr, false, &policy);
+    assert!(is_resolved(&lookup));
+    assert!(!lookup.cached());
+    let lookup = resolver.lazy_reverse_lookup(addr, false, &policy);
+    assert!(is_resolved(&lookup));
+    assert!(lookup.cached());
+    let start = Instant::now();
+    while ptr_queries() < 2 {
+        assert!(start.elapsed() < Duration::from_secs(10));
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(2, ptr_queries());
+    resolver.shutdown();
+    Ok(())
+}
+
+#[test]
+fn test_asinfo_ttl() -> anyhow::Result<()> {
+    let nameserver = ScriptedNameserver::start()?;
+    let queries = |query_type| {
+        nameserver
+            .queries()
+            .iter()
+            .filter(|(record_type, _)| *record_type == query_type)
+            .count()
+    };
+    let wait_for = |query_type, count| {
+        let start = Instant::now();
+        while queries(query_type) < count {
+            assert!(start.elapsed() < Duration::from_secs(10));
+            thread::sleep(Duration::from_millis(10));
+        }
+    };
+    let policy = LookupPolicy::default();
+    let addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    let short_ttl_bounds = TtlBounds {
+        min: Duration::ZERO,
+        max: Duration::from_secs(60),
+    };
+    // the hostnames expire and are refreshed without refreshing the `AsInfo`.
+    let config = Config::builder()
+        .resolve_method(ResolveMethod::Resolv)
+        .addr_family(IpAddrFamily::Ipv4Only)
+        .timeout(TIMEOUT)
+        .ttl_bounds(Some(short_ttl_bounds))
+        .asinfo_ttl(Some(Duration::from_secs(60)))
+        .build();
+    let resolver = DnsResolver::with_provider(config, make_provider(&nameserver.socket)?);
+    resolve_all(&resolver, &policy, &[addr]);
+    assert_eq!((1, 2), (queries(RecordType::PTR), queries(RecordType::TXT)));
+    thread::sleep(Duration::from_millis(1100));
+    let _ = resolver.lazy_reverse_lookup(addr, true, &policy);
+    wait_for(RecordType::PTR, 2);
+    assert_eq!(2, queries(RecordType::TXT));
+    resolver.shutdown();
+    // the `AsInfo` expires and is refreshed without refreshing the hostnames.
+    let config = Config::builder()
+        .resolve_method(ResolveMethod::Resolv)
+        .addr_family(IpAddrFamily::Ipv4Only)
+        .timeout(TIMEOUT)
+        .ttl_bounds(None)
+        .asinfo_ttl(Some(Duration::from_millis(200)))
+        .build();
+    let resolver = DnsResolver::with_provider(config, make_provider(&nameserver.socket)?);
+    resolve_all(&resolver, &policy, &[addr]);
+    assert_eq!((3, 4), (queries(RecordType::PTR), queries(RecordType::TXT)));
+    thread::sleep(Duration::from_millis(300));
+    let lookup = resolver.lazy_reverse_lookup(addr, true, &policy);
+    // the stale `AsInfo` is served whilst it is refreshed.
+    assert!(
+        matches!(lookup.entry(), DnsEntry::Resolved(Resolved::WithAsInfo(_, _, asinfo))
+        if asinfo.asn == "64496")
+    );
+    wait_for(RecordType::TXT, 6);
+    assert_eq!(3, queries(RecordType::PTR));
+    resolver.shutdown();
+    Ok(())
+}
+
+#[test]
+fn test_cache_file() -> anyhow::Result<()> {
+    let nameserver = ScriptedNameserver::start()?;
+    let path = std::env::temp_dir().join(format!(
+        "trippy-test-cache-file-{}.json",
+        std::process::id()
+    ));
+    let config = Config::builder()
+        .resolve_method(ResolveMethod::Resolv)
+        .addr_family(IpAddrFamily::Ipv4Only)
+        .timeout(TIMEOUT)
+        .cache_file(Some(path.clone()))
+        .build();
+    let policy = LookupPolicy::default();
+    let addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    let resolver = DnsResolver::with_provider(config.clone(), make_provider(&nameserver.socket)?);
+    resolve_all(&resolver, &policy, &[addr]);
+    resolver.shutdown();
+    let queries = nameserver.queries().len();
+    let cache_file = CacheFile::read(&path)?;
+    assert_eq!(1, cache_file.hostnames.len());
+    assert_eq!(1, cache_file.asinfo.len());
+    // the entries loaded from the cache file are served without any query being made.
+    let resolver = DnsResolver::with_provider(config.clone(), make_provider(&nameserver.socket)?);
+    let lookup = resolver.lazy_reverse_lookup(addr, true, &policy);
+    assert!(lookup.cached());
+    assert!(
+        matches!(lookup.entry(), DnsEntry::Resolved(Resolved::WithAsInfo(_, hostnames, asinfo))
+        if hostnames == &["host.example"] && asinfo.asn == "64496")
+    );
+    assert_eq!(queries, nameserver.queries().len());
+    // a pending entry is not persisted.
+    let pending = IpAddr::V4(Ipv4Addr::new(198, 51, 100, 1));
+    resolver
+        .addr_cache
+        .write()
+        .insert(pending, Cached::new(DnsEntry::Pending(pending)));
+    resolver.shutdown();
+    let cache_file = CacheFile::read(&path)?;
+    assert!(cache_file
+        .hostnames
+        .iter()
+        .all(|record| record.addr == addr));
+    // a corrupt cache file is ignored.
+    std::fs::write(&path, "{")?;
+    let resolver = DnsResolver::with_provider(config, make_provider(&nameserver.socket)?);
+    assert!(resolver.entry_metadata(addr).is_none());
+    resolver.shutdown();
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[test]
+fn test_load_save_cache() -> anyhow::Result<()> {
+    let nameserver = ScriptedNameserver::start()?;
+    let path = std::env::temp_dir().join(format!(
+        "trippy-test-load-save-cache-{}.json",
+        std::process::id()
+    ));
+    let config = Config::new(ResolveMethod::Resolv, IpAddrFamily::Ipv4Only, TIMEOUT);
+    let live = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    let expired = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2));
+    let now = SystemTime::now();
+    let record = |addr, expires| crate::cache_file::Record {
+        addr,
+        value: vec![String::from("host.example")],
+        updated: now,
+        expires: Some(expires),
+    };
+    CacheFile {
+        hostnames: vec![
+            record(live, now + Duration::from_secs(3600)),
+            record(expired, now - Duration::from_secs(1)),
+        ],
+        asinfo: vec![],
+    }
+    .write(&path)?;
+    let resolver = DnsResolver::with_provider(config, make_provider(&nameserver.socket)?);
+    resolver.load_cache(&path)?;
+    assert!(matches!(
+        resolver.addr_cache.read().get(&live).map(|cached| &cached.entry),
+        Some(DnsEntry::Resolved(Resolved::Normal(_, hostnames))) if hostnames == &["host.example"]
+    ));
+    // an expired entry is not loaded.
+    assert!(resolver.entry_metadata(expired).is_none());
+    std::fs::remove_file(&path)?;
+    resolver.save_cache(&path)?;
+    let cache_file = CacheFile::read(&path)?;
+    assert_eq!(1, cache_file.hostnames.len());
+    assert_eq!(live, cache_file.hostnames[0].addr);
+    assert!(cache_file.hostnames[0].expires.is_some());
+    // unlike a configured cache file, a corrupt cache file is an error.
+    std::fs::write(&path, "{")?;
+    assert!(resolver.load_cache(&path).is_err());
+    resolver.shutdown();
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[test]
+fn test_asinfo_prefix_cache() -> anyhow::Result<()> {
+    let nameserver = ScriptedNameserver::start()?;
+    let config = Config::new(ResolveMethod::Resolv, IpAddrFamily::Ipv4Only, TIMEOUT);
+    let resolver = DnsResolver::with_provider(config, make_provider(&nameserver.socket)?);
+    let policy = LookupPolicy::default();
+    let origin_queries = || {
+        nameserver
+            .queries()
+            .iter()
+            .filter(|(record_type, name)| {
+                *record_type == RecordType::TXT && name.contains(".origin.")
+            })
+            .count()
+    };
+    let first = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    let covered = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 200));
+    let uncovered = IpAddr::V4(Ipv4Addr::new(198, 51, 100, 1));
+    resolve_all(&resolver, &policy, &[first]);
+    assert_eq!(1, origin_queries());
+    // the `AsInfo` of an address within the announced prefix is served without a query.
+    let entries = resolve_all(&resolver, &policy, &[covered]);
+    assert!(
+        matches!(&entries[0], DnsEntry::Resolved(Resolved::WithAsInfo(_, _, asinfo))
+            if asinfo.prefix == "192.0.2.0/24"),
+        "{:?}",
+        entries[0]
+    );
+    assert_eq!(1, origin_queries());
+    // the prefix returned for an address outside of it is not cached.
+    resolve_all(&resolver, &policy, &[uncovered]);
+    assert_eq!(2, origin_queries());
+    resolver.flush();
+    resolve_all(&resolver, &policy, &[covered]);
+    assert_eq!(3, origin_queries());
+    resolver.shutdown();
+    Ok(())
+}
+
+#[test]
+fn test_asinfo_prefix_cache_queued() -> anyhow::Result<()> {
+    let nameserver = ScriptedNameserver::start()?;
+    let mut config = Config::new(ResolveMethod::Resolv, IpAddrFamily::Ipv4Only, TIMEOUT);
+    config.max_inflight = 1;
+    let resolver = DnsResolver::with_provider(config, make_provider(&nameserver.socket)?);
+    let policy = LookupPolicy::default();
+    let addrs = [
+        IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)),
+        IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2)),
+        IpAddr::V4(Ipv4Addr::new(192, 0, 2, 3)),
+    ];
+    // all lookups are enqueued before any has resolved, and the worker serves the
+    // `AsInfo` of those which are queued behind the first from the prefix it learned.
+    for addr in addrs {
+        let _ = resolver.lazy_lookup_asinfo(addr, &policy);
+    }
+    let entries = resolve_all(&resolver, &policy, &addrs);
+    assert!(entries.iter().all(|entry| matches!(
+        entry,
+        DnsEntry::Resolved(Resolved::WithAsInfo(_, _, asinfo)) if asinfo.asn == "64496"
+    )));
+    let origin_queries = nameserver
+        .queries()
+        .iter()
+        .filter(|(record_type, name)| *record_type == RecordType::TXT && name.contains(".origin."))
+        .count();
+    assert_eq!(1, origin_queries);
+    resolver.shutdown();
+    Ok(())
+}
+
+/// Answer every query without any records.
+fn respond_empty(query: &Message) -> Message {
+    let mut response = ScriptedNameserver::respond(query);
+    response.take_answers();
+    response
+}
+
+/// Make an `AsInfoProvider` which queries the Team Cymru service of a `nameserver`.
+fn make_cymru_provider(nameserver: &ScriptedNameserver) -> anyhow::Result<AsInfoProvider> {
+    let DnsProvider::TrustDns(resolver, _) = make_provider(&nameserver.socket)? else {
+        unreachable!()
+    };
+    Ok(AsInfoProvider::Cymru(
+        resolver,
+        Arc::from(DEFAULT_AS_LOOKUP_ZONE),
+    ))
+}
+
+#[test]
+fn test_make_asinfo_provider() -> anyhow::Result<()> {
+    let nameserver = ScriptedNameserver::start()?;
+    let trust_dns = make_provider(&nameserver.socket)?;
+    let dns_lookup = DnsProvider::DnsLookup(Arc::new(Hosts::default()));
+    let sources = |as_lookup_sources| Config {
+        as_lookup_sources: Some(as_lookup_sources),
+        ..Config::default()
+    };
+    let both = sources(vec![AsLookupSource::Dns, AsLookupSource::RipeStat]);
+    assert!(matches!(
+        make_asinfo_provider(&both, &trust_dns),
+        AsInfoProvider::Fallback(providers) if matches!(
+            providers.as_slice(),
+            [AsInfoProvider::Cymru(..), AsInfoProvider::RipeStat(_)]
+        )
+    ));
+    // the Team Cymru service is not supported by the system resolver and so is skipped.
+    assert!(matches!(
+        make_asinfo_provider(&both, &dns_lookup),
+        AsInfoProvider::RipeStat(_)
+    ));
+    let dns = sources(vec![AsLookupSource::Dns]);
+    assert!(matches!(
+        make_asinfo_provider(&dns, &trust_dns),
+        AsInfoProvider::Cymru(_, zone) if &*zone == "asn.cymru.com"
+    ));
+    let zone = Config {
+        as_lookup_zone: Some(String::from("asn.mirror.example.")),
+        ..dns.clone()
+    };
+    assert!(matches!(
+        make_asinfo_provider(&zone, &trust_dns),
+        AsInfoProvider::Cymru(_, zone) if &*zone == "asn.mirror.example"
+    ));
+    assert!(matches!(
+        make_asinfo_provider(&dns, &dns_lookup),
+        AsInfoProvider::Unsupported
+    ));
+    assert!(matches!(
+        make_asinfo_provider(&sources(vec![]), &trust_dns),
+        AsInfoProvider::Unsupported
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_lookup_asinfo_fallback() -> anyhow::Result<()> {
+    let empty = ScriptedNameserver::start_with(respond_empty)?;
+    let nameserver = ScriptedNameserver::start()?;
+    let addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    // the first provider has no `AsInfo` and so the next is tried.
+    let provider = AsInfoProvider::Fallback(vec![
+        make_cymru_provider(&empty)?,
+        make_cymru_provider(&nameserver)?,
+    ]);
+    assert!(matches!(
+        lookup_asinfo_entry(&provider, addr),
+        AsInfoEntry::Resolved(asinfo)
+            if asinfo.asn == "64496" && asinfo.source == Some(AsLookupSource::Dns)
+    ));
+    assert_eq!(1, empty.queries().len());
+    // once the `AsInfo` is supplied the remaining providers are not tried.
+    let provider = AsInfoProvider::Fallback(vec![
+        make_cymru_provider(&nameserver)?,
+        make_cymru_provider(&empty)?,
+    ]);
+    assert!(matches!(
+        lookup_asinfo_entry(&provider, addr),
+        AsInfoEntry::Resolved(_)
+    ));
+    assert_eq!(1, empty.queries().len());
+    let provider = AsInfoProvider::Fallback(vec![
+        make_cymru_provider(&empty)?,
+        make_cymru_provider(&empty)?,
+    ]);
+    assert!(matches!(
+        lookup_asinfo_entry(&provider, addr),
+        AsInfoEntry::NotFound
+    ));
+    assert_eq!(3, empty.queries().len());
+    Ok(())
+}
+
+#[test]
+fn test_lookup_asinfo_zone() -> anyhow::Result<()> {
+    let nameserver = ScriptedNameserver::start()?;
+    let DnsProvider::TrustDns(resolver, _) = make_provider(&nameserver.socket)? else {
+        unreachable!()
+    };
+    let provider = AsInfoProvider::Cymru(resolver, Arc::from("asn.mirror.example"));
+    for addr in [
+        IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)),
+        IpAddr::V6(Ipv6Addr::from_str("2001:db8::1")?),
+    ] {
+        assert!(matches!(
+            lookup_asinfo_entry(&provider, addr),
+            AsInfoEntry::Resolved(asinfo) if asinfo.asn == "64496"
+        ));
+    }
+    let names = nameserver
+        .queries()
+        .into_iter()
+        .map(|(_, name)| name.to_ascii_lowercase())
+        .collect::<Vec<_>>();
+    assert!(names
+        .iter()
+        .any(|name| name == "1.2.0.192.origin.asn.mirror.example."));
+    assert!(names
+        .iter()
+        .any(|name| name.ends_with(".origin6.asn.mirror.example.")));
+    assert!(names
+        .iter()
+        .any(|name| name == "as64496.asn.mirror.example."));
+    assert!(names.iter().all(|name| !name.contains("cymru")));
+    Ok(())
+}
+
+#[test]
+fn test_lookup_asinfo_asn_name_timeout() -> anyhow::Result<()> {
+    // the `origin` query is answered but the `asn` query for the AS name is not.
+    let nameserver = ScriptedNameserver::start_scripted(|query| {
+        let is_asn_query = query.queries().iter().any(|query| {
+            query.query_type() == RecordType::TXT && !query.name().to_string().contains(".origin.")
+        });
+        (!is_asn_query).then(|| ScriptedNameserver::respond(query))
+    })?;
+    let timeout = Duration::from_millis(200);
+    let mut options = ResolverOpts::default();
+    options.timeout = timeout;
+    options.attempts = 1;
+    options.cache_size = 0;
+    let resolver = BlockingResolver::new(
+        ResolverConfig::from_parts(
+            None,
+            vec![],
+            NameServerConfigGroup::from_ips_clear(
+                &[nameserver.socket.local_addr()?.ip()],
+                nameserver.socket.local_addr()?.port(),
+                true,
+            ),
+        ),
+        options,
+        timeout,
+        timeout * 2,
+        None,
+        false,
+        None,
+        None,
+    )?;
+    let provider = AsInfoProvider::Cymru(Arc::new(resolver), Arc::from(DEFAULT_AS_LOOKUP_ZONE));
+    let addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    let asinfo_entry = lookup_asinfo_entry(&provider, addr);
+    assert!(
+        matches!(&asinfo_entry, AsInfoEntry::Resolved(asinfo)
+            if asinfo.asn == "64496" && asinfo.prefix == "192.0.2.0/24" && asinfo.name.is_empty()),
+        "{asinfo_entry:?}"
+    );
+    assert!(nameserver
+        .queries()
+        .iter()
+        .any(|(_, name)| name.eq_ignore_ascii_case("AS64496.asn.cymru.com.")));
+    Ok(())
+}
+
+#[test]
+fn test_lazy_lookup_asinfo() -> anyhow::Result<()> {
+    let nameserver = ScriptedNameserver::start()?;
+    let config = Config::new(ResolveMethod::Resolv, IpAddrFamily::Ipv4Only, TIMEOUT);
+    let resolver = DnsResolver::with_provider(config, make_provider(&nameserver.socket)?);
+    let policy = LookupPolicy::default();
+    let queries = |query_type| {
+        nameserver
+            .queries()
+            .iter()
+            .filter(|(record_type, _)| *record_type == query_type)
+            .count()
+    };
+    let addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    let start = Instant::now();
+    let asinfo_entry = loop {
+        match resolver.lazy_lookup_asinfo(addr, &policy) {
+            AsInfoEntry::Pending => {
+                assert!(start.elapsed() < Duration::from_secs(10));
+                thread::sleep(Duration::from_millis(10));
+            }
+            asinfo_entry => break asinfo_entry,
+        }
+    };
+    assert!(matches!(asinfo_entry, AsInfoEntry::Resolved(asinfo) if asinfo.asn == "64496"));
+    assert_eq!(0, queries(RecordType::PTR));
+    assert_eq!(2, queries(RecordType::TXT));
+    // the cached `AsInfo` is shared with a lazy reverse DNS lookup with `AsInfo`.
+    let entries = resolve_all(&resolver, &policy, &[addr]);
+    assert!(matches!(
+        entries[0],
+        DnsEntry::Resolved(Resolved::WithAsInfo(..))
+    ));
+    assert_eq!(1, queries(RecordType::PTR));
+    assert_eq!(2, queries(RecordType::TXT));
+    let other = IpAddr::V4(Ipv4Addr::new(198, 51, 100, 1));
+    assert!(matches!(
+        resolver.lookup_asinfo(other, &policy),
+        AsInfoEntry::Resolved(_)
+    ));
+    assert_eq!(1, queries(RecordType::PTR));
+    let restrictive = LookupPolicy {
+        asinfo: false,
+        ..LookupPolicy::default()
+    };
+    assert!(matches!(
+        resolver.lazy_lookup_asinfo(other, &restrictive),
+        AsInfoEntry::NotFound
+    ));
+    resolver.shutdown();
+    assert!(matches!(
+        resolver.lookup_asinfo(other, &policy),
+        AsInfoEntry::Failed
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_trim_trailing_dot() -> anyhow::Result<()> {
+    let nameserver = ScriptedNameserver::start()?;
+    let policy = LookupPolicy {
+        asinfo: false,
+        ..LookupPolicy::default()
+    };
+    let addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    for (trim_trailing_dot, expected) in [(true, "host.example"), (false, "host.example.")] {
+        let mut config = Config::new(ResolveMethod::Resolv, IpAddrFamily::Ipv4Only, TIMEOUT);
+        config.trim_trailing_dot = trim_trailing_dot;
+        let resolver = DnsResolver::with_provider(config, make_provider(&nameserver.socket)?);
+        for entry in [
+            resolve_all(&resolver, &policy, &[addr]).remove(0),
+            resolver.reverse_lookup(addr, false, false, &policy),
+        ] {
+            let DnsEntry::Resolved(Resolved::Normal(_, hostnames)) = entry else {
+                panic!("{entry:?}");
+            };
+            assert_eq!(&[expected], &hostnames[..]);
+        }
+        resolver.shutdown();
+    }
+    let hostnames = |trim_trailing_dot| {
+        let entry = DnsEntry::Resolved(Resolved::Normal(
+            addr,
+            vec![String::from("a.example."), String::from("b.example")],
+        ));
+        match with_trailing_dot(entry, trim_trailing_dot) {
+            DnsEntry::Resolved(Resolved::Normal(_, hostnames)) => hostnames,
+            entry => panic!("{entry:?}"),
+        }
+    };
+    assert_eq!(vec!["a.example", "b.example"], hostnames(true));
+    assert_eq!(vec!["a.example.", "b.example."], hostnames(false));
+    Ok(())
+}
+
+#[test]
+fn test_decode_idn() -> anyhow::Result<()> {
+    let nameserver = ScriptedNameserver::start_with(respond_idn)?;
+    let policy = LookupPolicy {
+        asinfo: false,
+        ..LookupPolicy::default()
+    };
+    let addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    for (decode_idn, expected) in [(true, "bücher.example"), (false, "xn--bcher-kva.example")] {
+        let mut config = Config::new(ResolveMethod::Resolv, IpAddrFamily::Ipv4Only, TIMEOUT);
+        config.decode_idn = decode_idn;
+        let resolver = DnsResolver::with_provider(config, make_provider(&nameserver.socket)?);
+        let entry = resolve_all(&resolver, &policy, &[addr]).remove(0);
+        let DnsEntry::Resolved(Resolved::Normal(_, hostnames)) = entry else {
+            panic!("{entry:?}");
+        };
+        assert_eq!(&[expected], &hostnames[..]);
+        let raw = decode_idn.then(|| vec![String::from("xn--bcher-kva.example")]);
+        assert_eq!(raw, resolver.raw_hostnames(addr));
+        resolver.shutdown();
+    }
+    Ok(())
+}
+
+#[test]
+fn test_ipv4_mapped_addr() -> anyhow::Result<()> {
+    let nameserver = ScriptedNameserver::start()?;
+    let config = Config::new(ResolveMethod::Resolv, IpAddrFamily::Ipv4Only, TIMEOUT);
+    let resolver = DnsResolver::with_provider(config, make_provider(&nameserver.socket)?);
+    let policy = LookupPolicy::default();
+    let addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    let mapped = IpAddr::V6(Ipv4Addr::new(192, 0, 2, 1).to_ipv6_mapped());
+    let compatible = IpAddr::V6(Ipv4Addr::new(192, 0, 2, 1).to_ipv6_compatible());
+    let entries = resolve_all(&resolver, &policy, &[mapped, addr, compatible]);
+    for entry in &entries {
+        assert_eq!(format!("{:?}", entries[0]), format!("{entry:?}"));
+    }
+    assert!(
+        matches!(&entries[0], DnsEntry::Resolved(Resolved::WithAsInfo(ip, hosts, _))
+        if *ip == addr && hosts == &["host.example"])
+    );
+    assert_eq!(1, resolver.cache_stats().resolved);
+    assert!(resolver.entry_metadata(mapped).is_some());
+    // all forms are queried, once, in the IPv4 form.
+    let queries = nameserver.queries();
+    assert_eq!(
+        1,
+        queries
+            .iter()
+            .filter(|query| query == &&(RecordType::PTR, String::from("1.2.0.192.in-addr.arpa.")))
+            .count()
+    );
+    assert!(queries.iter().all(|(_, name)| !name.contains("ip6")));
+    resolver.shutdown();
+    Ok(())
+}
+
+#[test]
+fn test_reverse_lookup_ipv4_mapped() -> anyhow::Result<()> {
+    let nameserver = ScriptedNameserver::start()?;
+    let provider = make_provider(&nameserver.socket)?;
+    let mapped = IpAddr::V6(Ipv4Addr::new(192, 0, 2, 1).to_ipv6_mapped());
+    let (entry, _, _) = reverse_lookup_hostnames(&provider, mapped);
+    assert!(
+        matches!(&entry, DnsEntry::Resolved(Resolved::Normal(_, hostnames))
+        if hostnames == &["host.example."]),
+        "{entry:?}"
+    );
+    assert_eq!(
+        vec![(RecordType::PTR, String::from("1.2.0.192.in-addr.arpa."))],
+        nameserver.queries()
+    );
+    Ok(())
+}
+
+#[test]
+fn test_canonical_addr() {
+    let ipv4 = IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7));
+    for addr in ["203.0.113.7", "::ffff:203.0.113.7", "::203.0.113.7"] {
+        assert_eq!(ipv4, canonical_addr(IpAddr::from_str(addr).unwrap()));
+    }
+    for addr in ["::", "::1", "2001:db8::1", "127.0.0.1"] {
+        let addr = IpAddr::from_str(addr).unwrap();
+        assert_eq!(addr, canonical_addr(addr));
+    }
+}
+
+#[test]
+fn test_decode_hostname() {
+    assert_eq!("bücher.example", decode_hostname("xn--bcher-kva.example"));
+    assert_eq!("bücher.example.", decode_hostname("XN--bcher-kva.example."));
+    assert_eq!("Host.Example", decode_hostname("Host.Example"));
+    assert_eq!("xn--ab-.example", decode_hostname("xn--ab-.example"));
+}
+
+/// Answer `PTR` queries with the punycode hostname `xn--bcher-kva.example`.
+fn respond_idn(query: &Message) -> Message {
+    let mut response = ScriptedNameserver::respond(query);
+    let mut answers = response.take_answers();
+    for answer in &mut answers {
+        let hostname = Name::from_str("xn--bcher-kva.example.").unwrap();
+        answer.set_data(Some(RData::PTR(PTR(hostname))));
+    }
+    response.insert_answers(answers);
+    response
+}
+
+#[test]
+fn test_caching_disabled() -> anyhow::Result<()> {
+    let nameserver = ScriptedNameserver::start()?;
+    let mut config = Config::new(ResolveMethod::Resolv, IpAddrFamily::Ipv4Only, TIMEOUT);
+    config.caching = false;
+    let resolver = DnsResolver::with_provider(config, make_provider(&nameserver.socket)?);
+    let policy = LookupPolicy::default();
+    let queries = |query_type| {
+        nameserver
+            .queries()
+            .iter()
+            .filter(|(record_type, _)| *record_type == query_type)
+            .count()
+    };
+    let addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    let covered = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 200));
+    // a lookup which is pending is not enqueued again.
+    assert!(matches!(
+        resolver.reverse_lookup(addr, true, true, &policy),
+        DnsEntry::Pending(_)
+    ));
+    let entries = resolve_all(&resolver, &policy, &[addr]);
+    assert!(matches!(
+        entries[0],
+        DnsEntry::Resolved(Resolved::WithAsInfo(..))
+    ));
+    assert_eq!(1, queries(RecordType::PTR));
+    // the result is discarded once read and so the lookup is performed again.
+    assert_eq!(0, resolver.cache_stats().len());
+    assert!(matches!(
+        resolver.reverse_lookup(addr, true, true, &policy),
+        DnsEntry::Pending(_)
+    ));
+    resolve_all(&resolver, &policy, &[addr]);
+    assert_eq!(2, queries(RecordType::PTR));
+    // the `AsInfo` of an address within a previously announced prefix is looked up.
+    resolve_all(&resolver, &policy, &[covered]);
+    assert_eq!(6, queries(RecordType::TXT));
+    resolver.shutdown();
+    Ok(())
+}
+
+#[test]
+fn test_make_hosts() -> anyhow::Result<()> {
+    let missing = std::env::temp_dir().join("trippy-test-missing-hosts");
+    let config = Config::builder()
+        .resolve_method(ResolveMethod::Resolv)
+        .hosts_file(Some(missing.clone()))
+        .build();
+    assert!(make_hosts(&config).is_err());
+    // the hosts file is not used, and so not read, by the public resolvers.
+    let config = Config::builder()
+        .resolve_method(ResolveMethod::Google)
+        .hosts_file(Some(missing.clone()))
+        .build();
+    assert!(make_hosts(&config)?.lookup("localhost").is_none());
+    // the hosts file is not read if it is not used.
+    let config = Config::builder()
+        .resolve_method(ResolveMethod::Resolv)
+        .hosts_file(Some(missing))
+        .use_hosts_file(false)
+        .build();
+    assert!(make_hosts(&config)?.lookup("localhost").is_none());
+    Ok(())
+}
+
+/// Perform lazy lookups of all `addrs` with a `policy` until none are pending.
+fn resolve_all(resolver: &DnsResolver, policy: &LookupPolicy, addrs: &[IpAddr]) -> Vec<DnsEntry> {
+    let start = Instant::now();
+    loop {
+        let entries = addrs
+            .iter()
+            .map(|addr| resolver.reverse_lookup(*addr, true, true, policy))
+            .collect::<Vec<_>>();
+        if !entries
+            .iter()
+            .any(|entry| matches!(entry, DnsEntry::Pending(_)))
+        {
+            return entries;
+        }
+        assert!(start.elapsed() < Duration::from_secs(10));
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// A name server which answers every `PTR` query with `host.example` and every `TXT`
+/// query as per the Team Cymru `AsInfo` service for AS64496.
+///
+/// The `A` and `AAAA` queries of hostnames starting `v4.` and `v6.` respectively, and of
+/// hostnames starting `dual.`, are answered with `192.0.2.1` and `2001:db8::1`.
+///
+/// All answers have a TTL of 1 second.  All queries received are recorded.
+struct ScriptedNameserver {
+    socket: UdpSocket,
+    queries: Arc<Mutex<Vec<(RecordType, String)>>>,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl ScriptedNameserver {
+    fn start() -> anyhow::Result<Self> {
+        Self::start_with(Self::respond)
+    }
+
+    /// Start a name server which answers every query as per `respond`.
+    fn start_with(respond: fn(&Message) -> Message) -> anyhow::Result<Self> {
+        Self::start_scripted(move |query| Some(respond(query)))
+    }
+
+    /// Start a name server which answers every query as per `respond`, leaving those for
+    /// which it returns `None` unanswered.
+    fn start_scripted(
+        respond: impl Fn(&Message) -> Option<Message> + Send + 'static,
+    ) -> anyhow::Result<Self> {
+        let socket = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0))?;
+        socket.set_read_timeout(Some(Duration::from_millis(10)))?;
+        let queries = Arc::new(Mutex::new(vec![]));
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let socket = socket.try_clone()?;
+            let queries = queries.clone();
+            let stop = stop.clone();
+            thread::spawn(move || {
+                let mut buf = [0_u8; 512];
+                while !stop.load(Ordering::SeqCst) {
+                    if let Ok((len, from)) = socket.recv_from(&mut buf) {
+                        let query = Message::from_vec(&buf[..len]).unwrap();
+                        queries.lock().extend(
+                            query
+                                .queries()
+                                .iter()
+                                .map(|query| (query.query_type(), query.name().to_string())),
+                        );
+                        if let Some(response) = respond(&query) {
+                            let response = response.to_vec().unwrap();
+                            socket.send_to(&response, from).unwrap();
+                        }
+                    }
+                }
+            })
+        };
+        Ok(Self {
+            socket,
+            queries,
+            stop,
+            handle: Some(handle),
+        })
+    }
+
+    fn queries(&self) -> Vec<(RecordType, String)> {
+        self.queries.lock().clone()
+    }
+
+    /// Wait until at least `count` queries have been received.
+    fn wait_for_queries(&self, count: usize) -> Vec<(RecordType, String)> {
+        let start = Instant::now();
+        while self.queries.lock().len() < count && start.elapsed() < TIMEOUT {
+            thread::sleep(Duration::from_millis(10));
+        }
+        self.queries()
+    }
+
+    fn respond(query: &Message) -> Message {
+        let mut response = Message::new();
+        response
+            .set_id(query.id())
+            .set_message_type(MessageType::Response)
+            .set_op_code(query.op_code())
+            .set_recursion_desired(query.recursion_desired())
+            .set_recursion_available(true)
+            .add_queries(query.queries().to_vec());
+        for query in query.queries() {
+            let name = query.name().to_string();
+            let rdata = match query.query_type() {
+                RecordType::PTR => RData::PTR(PTR(Name::from_str("host.example.").unwrap())),
+                RecordType::TXT if name.contains(".origin.") => {
+                    RData::TXT(TXT::new(vec![String::from(
+                        "64496 | 192.0.2.0/24 | ZZ | test | 2000-01-01",
+                    )]))
+                }
+                RecordType::TXT => RData::TXT(TXT::new(vec![String::from(
+                    "64496 | ZZ | test | 2000-01-01 | TEST-AS",
+                )])),
+                RecordType::A if name.starts_with("v4.") || name.starts_with("dual.") => {
+                    RData::A(A::new(192, 0, 2, 1))
+                }
+                RecordType::AAAA if name.starts_with("v6.") || name.starts_with("dual.") => {
+                    RData::AAAA(AAAA::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1))
+                }
+                _ => continue,
+            };
+            response.add_answer(Record::from_rdata(query.name().clone(), 1, rdata));
+        }
+        response
+    }
+}
+
+impl Drop for ScriptedNameserver {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// The timeout for lookups against a name server which never answers.
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Make a `DnsProvider` which queries a `nameserver`.
+///
+/// Responses are not cached by the provider, such that every lookup which is not served
+/// from the cache of the `DnsResolver` is sent to the `nameserver`.
+fn make_provider(nameserver: &UdpSocket) -> anyhow::Result<DnsProvider> {
+    make_provider_with(
+        nameserver,
+        IpAddrFamily::Ipv4thenIpv6,
+        Hosts::default(),
+        None,
+    )
+}
+
+/// Make a `DnsProvider` which consults `hosts` before querying a `nameserver` for
+/// addresses as per the `addr_family`, driven on the given tokio `runtime`, if any.
+fn make_provider_with(
+    nameserver: &UdpSocket,
+    addr_family: IpAddrFamily,
+    hosts: Hosts,
+    runtime: Option<&Handle>,
+) -> anyhow::Result<DnsProvider> {
+    let mut options = ResolverOpts::default();
+    options.timeout = TIMEOUT;
+    options.ip_strategy = lookup_ip_strategy(addr_family);
+    options.cache_size = 0;
+    let resolver = BlockingResolver::new(
+        ResolverConfig::from_parts(
+            None,
+            vec![],
+            NameServerConfigGroup::from_ips_clear(
+                &[nameserver.local_addr()?.ip()],
+                nameserver.local_addr()?.port(),
+                true,
+            ),
+        ),
+        options,
+        TIMEOUT,
+        TIMEOUT,
+        None,
+        false,
+        None,
+        runtime,
+    )?;
+    Ok(DnsProvider::TrustDns(Arc::new(resolver), Arc::new(hosts)))
+}
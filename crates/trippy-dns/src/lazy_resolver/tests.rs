@@ -0,0 +1,389 @@
+use super::*;
+use crate::resolver::Resolved;
+use parking_lot::Mutex;
+use std::net::Ipv4Addr;
+use std::thread::sleep;
+use std::time::Instant;
+
+#[derive(Default)]
+struct RecordingObserver {
+    enqueued: Mutex<Vec<IpAddr>>,
+    completed: Mutex<Vec<(IpAddr, Duration)>>,
+}
+
+impl DnsObserver for RecordingObserver {
+    fn on_enqueue(&self, addr: IpAddr) {
+        self.enqueued.lock().push(addr);
+    }
+
+    fn on_complete(&self, addr: IpAddr, _entry: &DnsEntry, elapsed: Duration) {
+        self.completed.lock().push((addr, elapsed));
+    }
+}
+
+#[test]
+fn test_observer() {
+    let observer = Arc::new(RecordingObserver::default());
+    let config = Config::default().with_observer(observer.clone());
+    let resolver = DnsResolver::start(config).unwrap();
+    let addr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    let start = Instant::now();
+    while matches!(resolver.lazy_reverse_lookup(addr), DnsEntry::Pending(_)) {
+        assert!(start.elapsed() < Duration::from_secs(10));
+        sleep(Duration::from_millis(10));
+    }
+    let total = start.elapsed();
+    assert_eq!(vec![addr], *observer.enqueued.lock());
+    let completed = observer.completed.lock();
+    assert_eq!(1, completed.len());
+    assert_eq!(addr, completed[0].0);
+    assert!(completed[0].1 <= total);
+    drop(completed);
+    // the completed lookup is served from the cache and so is not enqueued again.
+    let _ = resolver.lazy_reverse_lookup(addr);
+    assert_eq!(1, observer.enqueued.lock().len());
+}
+
+#[test]
+fn test_subscribe() {
+    let resolver = DnsResolver::start(Config::default()).unwrap();
+    let rx = resolver.subscribe();
+    let dropped = resolver.subscribe();
+    drop(dropped);
+    let addr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    assert!(matches!(
+        resolver.lazy_reverse_lookup(addr),
+        DnsEntry::Pending(_)
+    ));
+    let (completed, dns_entry) = rx.recv_timeout(Duration::from_secs(10)).unwrap();
+    assert_eq!(addr, completed);
+    assert!(!matches!(dns_entry, DnsEntry::Pending(_)));
+    assert_eq!(
+        dns_entry.to_string(),
+        resolver.lazy_reverse_lookup(addr).to_string()
+    );
+    // a lookup served from the cache is not sent.
+    assert!(rx.try_recv().is_err());
+}
+
+#[test]
+fn test_send_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<DnsResolver>();
+    assert_send_sync::<DnsSession>();
+}
+
+#[test]
+fn test_cached() {
+    let resolver = DnsResolver::start(Config::default()).unwrap();
+    let addr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    let entry = resolver.lazy_reverse_lookup_cached(addr);
+    assert!(matches!(entry.entry(), DnsEntry::Pending(_)));
+    assert!(!entry.cached());
+    let start = Instant::now();
+    loop {
+        let entry = resolver.lazy_reverse_lookup_cached(addr);
+        assert!(entry.cached());
+        if !matches!(entry.entry(), DnsEntry::Pending(_)) {
+            break;
+        }
+        assert!(start.elapsed() < Duration::from_secs(10));
+        sleep(Duration::from_millis(10));
+    }
+    resolver.flush();
+    assert!(!resolver.lazy_reverse_lookup_cached(addr).cached());
+}
+
+#[test]
+fn test_config_builder() {
+    let observer = Arc::new(RecordingObserver::default());
+    let bind_addr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    let proxy_addr = SocketAddr::new(bind_addr, 1080);
+    let config = Config::builder()
+        .resolve_method(ResolveMethod::Google)
+        .addr_family(IpAddrFamily::Ipv6Only)
+        .timeout(Duration::from_secs(1))
+        .connect_timeout(Some(Duration::from_secs(2)))
+        .query_timeout(Some(Duration::from_secs(3)))
+        .total_timeout(Some(Duration::from_secs(4)))
+        .bind_addr(Some(bind_addr))
+        .source_port(Some(5353))
+        .startup_policy(StartupPolicy::Strict)
+        .as_lookup_source(AsLookupSource::RipeStat)
+        .observer(observer)
+        .max_inflight(4)
+        .max_lookups_per_sec(Some(20))
+        .max_cache_entries(Some(100))
+        .hosts_file(Some(PathBuf::from("/tmp/hosts")))
+        .use_hosts_file(false)
+        .asinfo_ttl(Some(Duration::from_secs(5)))
+        .retry_policy(RetryPolicy {
+            jitter: Duration::from_secs(6),
+        })
+        .cache_file(Some(PathBuf::from("/tmp/cache")))
+        .edns_udp_payload_size(Some(1232))
+        .validate_dnssec(true)
+        .caching(false)
+        .trim_trailing_dot(false)
+        .decode_idn(true)
+        .force_tcp(true)
+        .proxy(Some(ProxyConfig {
+            addr: proxy_addr,
+            auth: None,
+        }))
+        .as_lookup_zone(Some(String::from("asn.mirror.example")))
+        .build();
+    assert_eq!(ResolveMethod::Google, config.resolve_method);
+    assert_eq!(IpAddrFamily::Ipv6Only, config.addr_family);
+    assert_eq!(Duration::from_secs(1), config.timeout);
+    assert_eq!(Some(Duration::from_secs(2)), config.connect_timeout);
+    assert_eq!(Some(Duration::from_secs(3)), config.query_timeout);
+    assert_eq!(Some(Duration::from_secs(4)), config.total_timeout);
+    assert_eq!(Some(bind_addr), config.bind_addr);
+    assert_eq!(Some(5353), config.source_port);
+    assert_eq!(StartupPolicy::Strict, config.startup_policy);
+    assert_eq!(
+        Some(vec![AsLookupSource::RipeStat]),
+        config.as_lookup_sources
+    );
+    assert!(config.observer.is_some());
+    assert_eq!(4, config.max_inflight);
+    assert_eq!(Some(20), config.max_lookups_per_sec);
+    assert_eq!(Some(100), config.max_cache_entries);
+    assert_eq!(Some(PathBuf::from("/tmp/hosts")), config.hosts_file);
+    assert!(!config.use_hosts_file);
+    assert_eq!(Some(Duration::from_secs(5)), config.asinfo_ttl);
+    assert_eq!(Duration::from_secs(6), config.retry_policy.jitter);
+    assert_eq!(Some(PathBuf::from("/tmp/cache")), config.cache_file);
+    assert_eq!(Some(1232), config.edns_udp_payload_size);
+    assert!(config.validate_dnssec);
+    assert!(!config.caching);
+    assert!(!config.trim_trailing_dot);
+    assert!(config.decode_idn);
+    assert!(config.force_tcp);
+    assert_eq!(Some(proxy_addr), config.proxy.map(|proxy| proxy.addr));
+    assert_eq!(Some("asn.mirror.example"), config.as_lookup_zone.as_deref());
+}
+
+#[test]
+fn test_ttl_bounds() {
+    let bounds = TtlBounds::default();
+    assert_eq!(Duration::from_secs(30), bounds.clamp(Duration::ZERO));
+    assert_eq!(
+        Duration::from_secs(300),
+        bounds.clamp(Duration::from_secs(300))
+    );
+    assert_eq!(
+        Duration::from_secs(86400),
+        bounds.clamp(Duration::from_secs(14 * 86400))
+    );
+    let inverted = TtlBounds {
+        min: Duration::from_secs(60),
+        max: Duration::from_secs(10),
+    };
+    assert_eq!(
+        Duration::from_secs(60),
+        inverted.clamp(Duration::from_secs(300))
+    );
+}
+
+#[test]
+fn test_retry_policy_delay() {
+    let delay = Duration::from_secs(30);
+    assert_eq!(delay, RetryPolicy::default().delay(delay));
+    let policy = RetryPolicy {
+        jitter: Duration::from_secs(5),
+    };
+    for _ in 0..100 {
+        let retry = policy.delay(delay);
+        assert!((delay..=delay + policy.jitter).contains(&retry));
+    }
+}
+
+#[test]
+fn test_validate_dnssec_system() {
+    let config = Config::builder().validate_dnssec(true).build();
+    assert!(matches!(
+        DnsResolver::start(config),
+        Err(err) if err.kind() == std::io::ErrorKind::InvalidInput
+    ));
+}
+
+#[test]
+fn test_proxy_system() {
+    let config = Config::builder()
+        .proxy(Some(ProxyConfig {
+            addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1080),
+            auth: None,
+        }))
+        .build();
+    assert!(matches!(
+        DnsResolver::start(config),
+        Err(err) if err.kind() == std::io::ErrorKind::InvalidInput
+    ));
+}
+
+#[test]
+fn test_proxy_without_force_tcp() {
+    let config = Config::builder()
+        .resolve_method(ResolveMethod::Cloudflare)
+        .proxy(Some(ProxyConfig {
+            addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1080),
+            auth: None,
+        }))
+        .build();
+    assert!(matches!(
+        DnsResolver::start(config),
+        Err(err) if err.kind() == std::io::ErrorKind::InvalidInput
+    ));
+}
+
+#[test]
+fn test_force_tcp_system() {
+    let config = Config::builder().force_tcp(true).build();
+    assert!(matches!(
+        DnsResolver::start(config),
+        Err(err) if err.kind() == std::io::ErrorKind::InvalidInput
+    ));
+}
+
+#[test]
+fn test_config_builder_defaults() {
+    let config = Config::builder().build();
+    let default = Config::default();
+    assert_eq!(format!("{default:?}"), format!("{config:?}"));
+}
+
+#[test]
+fn test_config_new_const() {
+    const CONFIG: Config = Config::new(
+        ResolveMethod::Cloudflare,
+        IpAddrFamily::Ipv4Only,
+        Duration::from_secs(1),
+    );
+    let config = Config {
+        resolve_method: ResolveMethod::Cloudflare,
+        addr_family: IpAddrFamily::Ipv4Only,
+        timeout: Duration::from_secs(1),
+        ..Config::default()
+    };
+    assert_eq!(format!("{config:?}"), format!("{CONFIG:?}"));
+}
+
+#[test]
+fn test_bind_addr_system_resolver() {
+    let config = Config::builder()
+        .resolve_method(ResolveMethod::System)
+        .bind_addr(Some(IpAddr::V4(Ipv4Addr::LOCALHOST)))
+        .build();
+    let err = DnsResolver::start(config).err().unwrap();
+    assert_eq!(std::io::ErrorKind::InvalidInput, err.kind());
+    assert_eq!(
+        "DNS bind address 127.0.0.1 is not supported by the system resolver",
+        err.to_string()
+    );
+}
+
+#[test]
+fn test_bind_addr_not_local() {
+    let bind_addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    let config = Config::builder()
+        .resolve_method(ResolveMethod::Cloudflare)
+        .addr_family(IpAddrFamily::Ipv4Only)
+        .bind_addr(Some(bind_addr))
+        .build();
+    let err = DnsResolver::start(config).err().unwrap();
+    assert_eq!(std::io::ErrorKind::AddrNotAvailable, err.kind());
+    assert!(err
+        .to_string()
+        .starts_with("invalid DNS bind address 192.0.2.1: "));
+}
+
+#[test]
+fn test_source_port_validation() {
+    let config = Config::builder()
+        .resolve_method(ResolveMethod::System)
+        .source_port(Some(5353))
+        .max_inflight(1)
+        .build();
+    let err = DnsResolver::start(config).err().unwrap();
+    assert_eq!(std::io::ErrorKind::InvalidInput, err.kind());
+    let config = Config::builder()
+        .resolve_method(ResolveMethod::Cloudflare)
+        .source_port(Some(5353))
+        .build();
+    let err = DnsResolver::start(config).err().unwrap();
+    assert_eq!(std::io::ErrorKind::InvalidInput, err.kind());
+    assert_eq!(
+        "a fixed DNS source port requires max_inflight to be 1",
+        err.to_string()
+    );
+}
+
+#[test]
+fn test_system_hosts_file() -> anyhow::Result<()> {
+    let path = std::env::temp_dir().join(format!("trippy-test-hosts-{}", std::process::id()));
+    std::fs::write(&path, "192.0.2.1 pinned.example\n")?;
+    let config = Config::builder()
+        .resolve_method(ResolveMethod::System)
+        .addr_family(IpAddrFamily::Ipv4Only)
+        .hosts_file(Some(path.clone()))
+        .build();
+    let resolver = DnsResolver::start(config);
+    std::fs::remove_file(&path)?;
+    let resolver = resolver?;
+    let addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    assert_eq!(
+        vec![addr],
+        resolver
+            .lookup("pinned.example")?
+            .into_iter()
+            .collect::<Vec<_>>()
+    );
+    assert!(matches!(
+        resolver.reverse_lookup(addr),
+        DnsEntry::Resolved(Resolved::Normal(_, hostnames)) if hostnames == ["pinned.example"]
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_start_with_runtime() -> anyhow::Result<()> {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .build()?;
+    let resolver = DnsResolver::start_with_runtime(Config::default(), runtime.handle().clone())?;
+    let addr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    let start = Instant::now();
+    while matches!(resolver.lazy_reverse_lookup(addr), DnsEntry::Pending(_)) {
+        assert!(start.elapsed() < Duration::from_secs(10));
+        sleep(Duration::from_millis(10));
+    }
+    resolver.shutdown();
+    let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+    let err = DnsResolver::start_with_runtime(Config::default(), runtime.handle().clone())
+        .err()
+        .unwrap();
+    assert_eq!(std::io::ErrorKind::InvalidInput, err.kind());
+    Ok(())
+}
+
+#[test]
+fn test_shutdown() {
+    let resolver = DnsResolver::start(Config::default()).unwrap();
+    let clone = resolver.clone();
+    resolver.shutdown();
+    let addr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    assert!(matches!(
+        clone.lazy_reverse_lookup(addr),
+        DnsEntry::Failed(..)
+    ));
+    assert!(matches!(
+        clone.lazy_reverse_lookup_with_asinfo(addr),
+        DnsEntry::Failed(..)
+    ));
+    assert!(clone
+        .reconfigure(Config::default(), CacheRetention::Retain)
+        .is_err());
+    clone.shutdown();
+}
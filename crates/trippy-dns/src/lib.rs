@@ -22,11 +22,11 @@
 //!     Config, DnsEntry, DnsResolver, IpAddrFamily, ResolveMethod, Resolved, Resolver, Unresolved,
 //! };
 //!
-//! let config = Config::new(
-//!     ResolveMethod::Cloudflare,
-//!     IpAddrFamily::Ipv4Only,
-//!     Duration::from_secs(5),
-//! );
+//! let config = Config::builder()
+//!     .resolve_method(ResolveMethod::Cloudflare)
+//!     .addr_family(IpAddrFamily::Ipv4Only)
+//!     .timeout(Duration::from_secs(5))
+//!     .build();
 //! let resolver = DnsResolver::start(config)?;
 //! let addr = IpAddr::from_str("1.1.1.1")?;
 //! loop {
@@ -58,8 +58,12 @@
 //!             println!("lookup of {ip} timed out");
 //!             return Ok(());
 //!         }
-//!         DnsEntry::Failed(ip) => {
-//!             println!("lookup of {ip} failed");
+//!         DnsEntry::Failed(ip, rcode) => {
+//!             println!("lookup of {ip} failed with {rcode:?}");
+//!             return Ok(());
+//!         }
+//!         DnsEntry::Bogus(ip) => {
+//!             println!("lookup of {ip} failed DNSSEC validation");
 //!             return Ok(());
 //!         }
 //!     }
@@ -69,8 +73,26 @@
 //! ```
 #![forbid(unsafe_code)]
 
+mod cache_file;
+mod generated_ptr;
+mod hosts;
 mod lazy_resolver;
+#[cfg(any(test, feature = "test-util"))]
+mod mock;
+mod prefix_map;
 mod resolver;
+mod ripestat;
+mod socks;
 
-pub use lazy_resolver::{Config, DnsResolver, IpAddrFamily, ResolveMethod};
-pub use resolver::{AsInfo, DnsEntry, Error, Resolved, Resolver, Result, Unresolved};
+pub use lazy_resolver::{
+    AsLookupSource, CacheRetention, CacheStats, Config, ConfigBuilder, DnsEntryMeta, DnsObserver,
+    DnsResolver, DnsSession, Health, IpAddrFamily, LazyDnsEntry, LookupPolicy, ResolveMethod,
+    RetryPolicy, StartupPolicy,
+};
+#[cfg(any(test, feature = "test-util"))]
+pub use mock::{MockRequest, MockResolver};
+pub use resolver::{
+    AsInfo, AsInfoEntry, DnsEntry, DnssecStatus, Error, Rcode, Resolved, Resolver, Result,
+    TimeoutReason, Unresolved,
+};
+pub use socks::{ProxyAuth, ProxyConfig};
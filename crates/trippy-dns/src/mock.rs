@@ -0,0 +1,280 @@
+use crate::resolver::{
+    AsInfoEntry, DnsEntry, Error, Resolved, ResolvedIpAddrs, Resolver, Result, Unresolved,
+};
+use crossbeam::channel::{unbounded, Receiver, Sender};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::io;
+use std::net::IpAddr;
+use std::time::Duration;
+
+/// A `Resolver` which serves preloaded entries without any network access, for testing.
+///
+/// The reverse DNS lookup of an `IpAddr` returns the `DnsEntry` preloaded for it, or
+/// `DnsEntry::NotFound` if there is none.  Lookups which do not request `AS` information return
+/// the preloaded entry without it, and blocking lookups return a preloaded `DnsEntry::Pending` as
+/// `DnsEntry::Timeout`.  Lazy lookups are never enqueued and so the preloaded entry is returned
+/// as is, and so a subscriber is never sent any completed lookup.  Every request made of the resolver is recorded, see [`MockResolver::requests`].
+///
+/// This is only available with the `test-util` feature.
+///
+/// # Example
+///
+/// ```
+/// # use std::net::{IpAddr, Ipv4Addr};
+/// use trippy_dns::{DnsEntry, MockRequest, MockResolver, Resolved, Resolver};
+///
+/// let addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+/// let resolver = MockResolver::new().with_entry(
+///     addr,
+///     DnsEntry::Resolved(Resolved::Normal(addr, vec![String::from("host.example")])),
+/// );
+/// assert_eq!("host.example", resolver.lazy_reverse_lookup(addr).to_string());
+/// assert_eq!(vec![MockRequest::LazyReverseLookup(addr)], resolver.requests());
+/// ```
+#[derive(Debug, Default)]
+pub struct MockResolver {
+    entries: HashMap<IpAddr, DnsEntry>,
+    addrs: HashMap<String, Vec<IpAddr>>,
+    requests: Mutex<Vec<MockRequest>>,
+    subscribers: Mutex<Vec<Sender<(IpAddr, DnsEntry)>>>,
+}
+
+/// A request made of a `MockResolver`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum MockRequest {
+    /// A forward DNS lookup of a hostname.
+    Lookup(String),
+    /// A blocking reverse DNS lookup, with or without `AS` information or a deadline.
+    ReverseLookup(IpAddr),
+    /// A lazy reverse DNS lookup, with or without `AS` information.
+    LazyReverseLookup(IpAddr),
+    /// A blocking `AS` information lookup.
+    LookupAsInfo(IpAddr),
+    /// A lazy `AS` information lookup.
+    LazyLookupAsInfo(IpAddr),
+    /// The cancellation of a lazy reverse DNS lookup.
+    Cancel(IpAddr),
+}
+
+impl MockResolver {
+    /// Create a `MockResolver` without any preloaded entries.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Preload the `DnsEntry` returned by reverse DNS lookups of `addr`.
+    #[must_use]
+    pub fn with_entry(mut self, addr: IpAddr, entry: DnsEntry) -> Self {
+        self.entries.insert(addr, entry);
+        self
+    }
+
+    /// Preload the addresses returned by forward DNS lookups of `hostname`.
+    #[must_use]
+    pub fn with_addrs(mut self, hostname: impl Into<String>, addrs: Vec<IpAddr>) -> Self {
+        self.addrs.insert(hostname.into(), addrs);
+        self
+    }
+
+    /// All requests made of the resolver, in the order they were made.
+    #[must_use]
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().clone()
+    }
+
+    fn record(&self, request: MockRequest) {
+        self.requests.lock().push(request);
+    }
+
+    /// The preloaded entry for `addr`, with `AS` information only if `with_asinfo`.
+    fn entry(&self, addr: IpAddr, with_asinfo: bool, lazy: bool) -> DnsEntry {
+        let entry = self
+            .entries
+            .get(&addr)
+            .cloned()
+            .unwrap_or(DnsEntry::NotFound(Unresolved::Normal(addr)));
+        match entry {
+            DnsEntry::Resolved(Resolved::WithAsInfo(addr, hostnames, _)) if !with_asinfo => {
+                DnsEntry::Resolved(Resolved::Normal(addr, hostnames))
+            }
+            DnsEntry::NotFound(Unresolved::WithAsInfo(addr, _)) if !with_asinfo => {
+                DnsEntry::NotFound(Unresolved::Normal(addr))
+            }
+            DnsEntry::Pending(addr) if !lazy => DnsEntry::Timeout(addr),
+            entry => entry,
+        }
+    }
+
+    /// The `AS` information of the preloaded entry for `addr`.
+    fn asinfo_entry(&self, addr: IpAddr, lazy: bool) -> AsInfoEntry {
+        match self.entry(addr, true, lazy) {
+            DnsEntry::Resolved(Resolved::WithAsInfo(_, _, asinfo))
+            | DnsEntry::NotFound(Unresolved::WithAsInfo(_, asinfo)) => {
+                AsInfoEntry::Resolved(asinfo)
+            }
+            DnsEntry::Pending(_) => AsInfoEntry::Pending,
+            DnsEntry::Timeout(_) => AsInfoEntry::Timeout,
+            DnsEntry::Failed(..) => AsInfoEntry::Failed,
+            _ => AsInfoEntry::NotFound,
+        }
+    }
+}
+
+impl Resolver for MockResolver {
+    fn lookup(&self, hostname: impl AsRef<str>) -> Result<ResolvedIpAddrs> {
+        let hostname = hostname.as_ref();
+        self.record(MockRequest::Lookup(hostname.to_string()));
+        self.addrs
+            .get(hostname)
+            .map(|addrs| ResolvedIpAddrs(addrs.clone(), vec![]))
+            .ok_or_else(|| {
+                Error::LookupFailed(Box::new(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no addresses for {hostname}"),
+                )))
+            })
+    }
+    fn reverse_lookup(&self, addr: impl Into<IpAddr>) -> DnsEntry {
+        let addr = addr.into();
+        self.record(MockRequest::ReverseLookup(addr));
+        self.entry(addr, false, false)
+    }
+    fn reverse_lookup_with_asinfo(&self, addr: impl Into<IpAddr>) -> DnsEntry {
+        let addr = addr.into();
+        self.record(MockRequest::ReverseLookup(addr));
+        self.entry(addr, true, false)
+    }
+    fn reverse_lookup_with_deadline(
+        &self,
+        addr: impl Into<IpAddr>,
+        _deadline: Duration,
+    ) -> DnsEntry {
+        let addr = addr.into();
+        self.record(MockRequest::ReverseLookup(addr));
+        self.entry(addr, false, false)
+    }
+    fn lazy_reverse_lookup(&self, addr: impl Into<IpAddr>) -> DnsEntry {
+        let addr = addr.into();
+        self.record(MockRequest::LazyReverseLookup(addr));
+        self.entry(addr, false, true)
+    }
+    fn lazy_reverse_lookup_with_asinfo(&self, addr: impl Into<IpAddr>) -> DnsEntry {
+        let addr = addr.into();
+        self.record(MockRequest::LazyReverseLookup(addr));
+        self.entry(addr, true, true)
+    }
+    fn cancel(&self, addr: impl Into<IpAddr>) {
+        self.record(MockRequest::Cancel(addr.into()));
+    }
+    fn lookup_asinfo(&self, addr: impl Into<IpAddr>) -> AsInfoEntry {
+        let addr = addr.into();
+        self.record(MockRequest::LookupAsInfo(addr));
+        self.asinfo_entry(addr, false)
+    }
+    fn lazy_lookup_asinfo(&self, addr: impl Into<IpAddr>) -> AsInfoEntry {
+        let addr = addr.into();
+        self.record(MockRequest::LazyLookupAsInfo(addr));
+        self.asinfo_entry(addr, true)
+    }
+    fn inflight(&self) -> usize {
+        0
+    }
+    fn queue_depth(&self) -> usize {
+        0
+    }
+    fn cached_entries(&self) -> Vec<(IpAddr, DnsEntry)> {
+        let mut entries = self
+            .entries
+            .iter()
+            .map(|(addr, entry)| (*addr, entry.clone()))
+            .collect::<Vec<_>>();
+        entries.sort_by_key(|(addr, _)| *addr);
+        entries
+    }
+    fn subscribe(&self) -> Receiver<(IpAddr, DnsEntry)> {
+        let (tx, rx) = unbounded();
+        self.subscribers.lock().push(tx);
+        rx
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resolver::AsInfo;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn test_mock_resolver() {
+        let addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+        let pending = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2));
+        let unknown = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 3));
+        let asinfo = AsInfo {
+            asn: String::from("64496"),
+            ..AsInfo::default()
+        };
+        let resolver = MockResolver::new()
+            .with_entry(
+                addr,
+                DnsEntry::Resolved(Resolved::WithAsInfo(
+                    addr,
+                    vec![String::from("host.example")],
+                    asinfo,
+                )),
+            )
+            .with_entry(pending, DnsEntry::Pending(pending))
+            .with_addrs("host.example", vec![addr]);
+        assert_eq!("host.example", resolver.reverse_lookup(addr).to_string());
+        assert_eq!(
+            "AS64496 host.example",
+            resolver.lazy_reverse_lookup_with_asinfo(addr).to_string()
+        );
+        assert!(matches!(
+            resolver.lookup_asinfo(addr),
+            AsInfoEntry::Resolved(asinfo) if asinfo.asn == "64496"
+        ));
+        assert!(matches!(
+            resolver.lazy_reverse_lookup(pending),
+            DnsEntry::Pending(_)
+        ));
+        assert!(matches!(
+            resolver.lazy_lookup_asinfo(pending),
+            AsInfoEntry::Pending
+        ));
+        assert!(matches!(
+            resolver.reverse_lookup(pending),
+            DnsEntry::Timeout(_)
+        ));
+        assert!(matches!(
+            resolver.reverse_lookup(unknown),
+            DnsEntry::NotFound(Unresolved::Normal(_))
+        ));
+        assert_eq!(
+            vec![addr],
+            resolver
+                .lookup("host.example")
+                .unwrap()
+                .into_iter()
+                .collect::<Vec<_>>()
+        );
+        assert!(resolver.lookup("other.example").is_err());
+        resolver.cancel(pending);
+        assert_eq!(
+            vec![
+                MockRequest::ReverseLookup(addr),
+                MockRequest::LazyReverseLookup(addr),
+                MockRequest::LookupAsInfo(addr),
+                MockRequest::LazyReverseLookup(pending),
+                MockRequest::LazyLookupAsInfo(pending),
+                MockRequest::ReverseLookup(pending),
+                MockRequest::ReverseLookup(unknown),
+                MockRequest::Lookup(String::from("host.example")),
+                MockRequest::Lookup(String::from("other.example")),
+                MockRequest::Cancel(pending),
+            ],
+            resolver.requests()
+        );
+    }
+}
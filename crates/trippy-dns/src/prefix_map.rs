@@ -0,0 +1,121 @@
+use ipnetwork::IpNetwork;
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
+
+/// A map of IPv4 and IPv6 prefixes to values which supports longest prefix match lookups.
+///
+/// Prefixes are held by prefix length such that a lookup probes each length present in the map,
+/// from the most to the least specific, and so the cost of a lookup is bounded by the number of
+/// distinct prefix lengths rather than the number of prefixes.
+#[derive(Debug, Clone)]
+pub struct PrefixMap<V> {
+    prefixes: BTreeMap<u8, HashMap<IpNetwork, V>>,
+}
+
+impl<V> PrefixMap<V> {
+    pub const fn new() -> Self {
+        Self {
+            prefixes: BTreeMap::new(),
+        }
+    }
+
+    /// Insert the `value` of a `prefix`, replacing the value of the same prefix, if any.
+    ///
+    /// Any host bits set in the `prefix` are ignored, i.e. `192.0.2.1/24` is `192.0.2.0/24`.
+    pub fn insert(&mut self, prefix: IpNetwork, value: V) {
+        if let Some(network) = network_of(prefix.ip(), prefix.prefix()) {
+            self.prefixes
+                .entry(prefix.prefix())
+                .or_default()
+                .insert(network, value);
+        }
+    }
+
+    /// The value of the most specific prefix which contains `addr`, if any.
+    pub fn longest_match(&self, addr: IpAddr) -> Option<&V> {
+        self.prefixes.iter().rev().find_map(|(len, networks)| {
+            network_of(addr, *len).and_then(|network| networks.get(&network))
+        })
+    }
+
+    /// Remove the most specific prefix which contains `addr`, if any, and return its value.
+    pub fn remove_longest_match(&mut self, addr: IpAddr) -> Option<V> {
+        self.prefixes.iter_mut().rev().find_map(|(len, networks)| {
+            network_of(addr, *len).and_then(|network| networks.remove(&network))
+        })
+    }
+
+    /// Remove all prefixes from the map.
+    pub fn clear(&mut self) {
+        self.prefixes.clear();
+    }
+}
+
+impl<V> Default for PrefixMap<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The network of length `len` which contains `addr`, or `None` if `len` is not valid for the
+/// family of `addr`.
+fn network_of(addr: IpAddr, len: u8) -> Option<IpNetwork> {
+    let network = IpNetwork::new(addr, len).ok()?.network();
+    IpNetwork::new(network, len).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn prefix(s: &str) -> IpNetwork {
+        IpNetwork::from_str(s).unwrap()
+    }
+
+    fn addr(s: &str) -> IpAddr {
+        IpAddr::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_longest_match() {
+        let mut map = PrefixMap::new();
+        map.insert(prefix("10.0.0.0/8"), "/8");
+        map.insert(prefix("10.1.0.0/16"), "/16");
+        map.insert(prefix("10.1.2.0/24"), "/24");
+        map.insert(prefix("2001:db8::/32"), "v6 /32");
+        map.insert(prefix("2001:db8:1::/48"), "v6 /48");
+        assert_eq!(Some(&"/24"), map.longest_match(addr("10.1.2.3")));
+        assert_eq!(Some(&"/16"), map.longest_match(addr("10.1.3.1")));
+        assert_eq!(Some(&"/8"), map.longest_match(addr("10.2.0.1")));
+        assert_eq!(None, map.longest_match(addr("11.0.0.1")));
+        assert_eq!(Some(&"v6 /48"), map.longest_match(addr("2001:db8:1::1")));
+        assert_eq!(Some(&"v6 /32"), map.longest_match(addr("2001:db8:2::1")));
+        assert_eq!(None, map.longest_match(addr("2001:db9::1")));
+        // an IPv4-mapped IPv6 address is not matched by an IPv4 prefix.
+        assert_eq!(None, map.longest_match(addr("::ffff:10.1.2.3")));
+        map.clear();
+        assert_eq!(None, map.longest_match(addr("10.1.2.3")));
+    }
+
+    #[test]
+    fn test_remove_longest_match() {
+        let mut map = PrefixMap::new();
+        map.insert(prefix("10.0.0.0/8"), "/8");
+        map.insert(prefix("10.1.0.0/16"), "/16");
+        assert_eq!(Some("/16"), map.remove_longest_match(addr("10.1.2.3")));
+        assert_eq!(Some(&"/8"), map.longest_match(addr("10.1.2.3")));
+        assert_eq!(None, map.remove_longest_match(addr("11.0.0.1")));
+        assert_eq!(Some("/8"), map.remove_longest_match(addr("10.1.2.3")));
+        assert_eq!(None, map.longest_match(addr("10.1.2.3")));
+    }
+
+    #[test]
+    fn test_insert_ignores_host_bits() {
+        let mut map = PrefixMap::new();
+        map.insert(prefix("192.0.2.1/24"), 1);
+        map.insert(prefix("192.0.2.0/24"), 2);
+        assert_eq!(1, map.prefixes[&24].len());
+        assert_eq!(Some(&2), map.longest_match(addr("192.0.2.200")));
+    }
+}
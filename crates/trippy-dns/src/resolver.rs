@@ -1,8 +1,14 @@
+use crate::lazy_resolver::AsLookupSource;
+use crossbeam::channel::Receiver;
 use std::fmt::{Display, Formatter};
 use std::net::IpAddr;
+use std::time::Duration;
 use thiserror::Error;
 
 /// A DNS resolver.
+///
+/// An IPv4-mapped or IPv4-compatible IPv6 `IpAddr` is looked up, cached and returned in its IPv4
+/// form, such that both forms of an address share a single entry.
 pub trait Resolver {
     /// Perform a blocking DNS hostname lookup and return the resolved IPv4 or IPv6 addresses.
     fn lookup(&self, hostname: impl AsRef<str>) -> Result<ResolvedIpAddrs>;
@@ -20,6 +26,19 @@ pub trait Resolver {
     #[must_use]
     fn reverse_lookup_with_asinfo(&self, addr: impl Into<IpAddr>) -> DnsEntry;
 
+    /// Perform a blocking reverse DNS lookup of `IpAddr`, waiting at most `deadline`, and return
+    /// a `DnsEntry`.
+    ///
+    /// The lookup is performed as per [`Resolver::lazy_reverse_lookup`] and so populates the
+    /// cache, and is served from the cache if already resolved.  If the lookup is still pending
+    /// once the `deadline` has elapsed then `DnsEntry::Timeout` is returned, and the lookup
+    /// continues in the background such that a subsequent lazy lookup may benefit from it.
+    ///
+    /// As this method is blocking it will never return a `DnsEntry::Pending`.
+    #[must_use]
+    fn reverse_lookup_with_deadline(&self, addr: impl Into<IpAddr>, deadline: Duration)
+        -> DnsEntry;
+
     /// Perform a lazy reverse DNS lookup of `IpAddr` and return a `DnsEntry`.
     ///
     /// If the `IpAddr` has already been resolved then `DnsEntry::Resolved` is returned immediately.
@@ -36,9 +55,60 @@ pub trait Resolver {
 
     /// Perform a lazy reverse DNS lookup of `IpAddr` and return a `DnsEntry` with `AS` information.
     ///
+    /// The hostnames and the `AS` information are resolved and cached independently, such that the
+    /// failure of one does not discard the other.  If the hostnames could not be resolved but the
+    /// `AS` information was then a `DnsEntry::NotFound(Unresolved::WithAsInfo(..))` is returned.
+    ///
     /// See [`Resolver::lazy_reverse_lookup`]
     #[must_use]
     fn lazy_reverse_lookup_with_asinfo(&self, addr: impl Into<IpAddr>) -> DnsEntry;
+
+    /// Cancel the pending lazy reverse DNS lookup of `IpAddr`, if any.
+    ///
+    /// A lookup which is still queued is never performed and the result of a lookup which is
+    /// in-flight is discarded when it completes.  A lookup which has already completed is not
+    /// affected.  A subsequent lazy reverse DNS lookup of `IpAddr` is enqueued as normal.
+    fn cancel(&self, addr: impl Into<IpAddr>);
+
+    /// Perform a blocking `AS` information lookup of `IpAddr` and return an `AsInfoEntry`.
+    ///
+    /// This does not perform a reverse DNS lookup of the hostnames of `IpAddr`.  As this method is
+    /// blocking it will never return an `AsInfoEntry::Pending`.
+    #[must_use]
+    fn lookup_asinfo(&self, addr: impl Into<IpAddr>) -> AsInfoEntry;
+
+    /// Perform a lazy `AS` information lookup of `IpAddr` and return an `AsInfoEntry`.
+    ///
+    /// Unlike [`Resolver::lazy_reverse_lookup_with_asinfo`], this does not perform a reverse DNS
+    /// lookup of the hostnames of `IpAddr`.  The `AS` information is cached, and enqueued, as per
+    /// a lazy reverse DNS lookup with `AS` information, from the same `AS` information source,
+    /// and so each shares the result of the other.
+    fn lazy_lookup_asinfo(&self, addr: impl Into<IpAddr>) -> AsInfoEntry;
+
+    /// The number of lazy reverse DNS lookups which are in-flight.
+    ///
+    /// This is at most the number of lookups which may be in-flight concurrently.
+    fn inflight(&self) -> usize;
+
+    /// The number of lazy reverse DNS lookups which are queued and not yet in-flight.
+    fn queue_depth(&self) -> usize;
+
+    /// A snapshot of the reverse DNS lookup entries currently cached, ordered by `IpAddr`.
+    ///
+    /// An entry includes its `AS` information if that has been resolved.  This does not perform,
+    /// or enqueue, any lookup and does not modify the cache.
+    fn cached_entries(&self) -> Vec<(IpAddr, DnsEntry)>;
+
+    /// Subscribe to the completion of lazy reverse DNS lookups.
+    ///
+    /// The returned `Receiver` is sent the `IpAddr` and `DnsEntry`, without `AS` information, of
+    /// each lazy reverse DNS lookup which completes after subscribing, such that a consumer can
+    /// update only the affected entry rather than polling every pending lookup.  A lookup which
+    /// is cancelled, or superseded by a reconfiguration, is not sent.
+    ///
+    /// The channel is unbounded and so the resolver never blocks sending to it; dropping the
+    /// `Receiver` unsubscribes.
+    fn subscribe(&self) -> Receiver<(IpAddr, DnsEntry)>;
 }
 
 /// A DNS resolver error result.
@@ -57,16 +127,78 @@ pub enum Error {
     ParseOriginQueryFailed(String),
     #[error("asn query txt parse failed: {0}")]
     ParseAsnQueryFailed(String),
+    #[error("DNS lookup timed out: {0}")]
+    Timeout(TimeoutReason),
+    #[error("RIPEstat query failed: {0}")]
+    QueryRipeStatFailed(Box<dyn std::error::Error + Send + Sync + 'static>),
+    #[error("RIPEstat response parse failed: {0}")]
+    ParseRipeStatFailed(String),
+    #[error("DNS resolver has been shut down")]
+    Shutdown,
+    #[error("CNAME chain of {0} is too long or contains a loop")]
+    CnameLoop(String),
+}
+
+/// The reason a DNS lookup timed out.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TimeoutReason {
+    /// Establishing a connection to a name server timed out.
+    Connect,
+    /// A query round trip to a name server timed out.
+    Query,
+    /// The lookup as a whole, including all retries and fallbacks, timed out.
+    Total,
+}
+
+impl Display for TimeoutReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Connect => write!(f, "connect"),
+            Self::Query => write!(f, "query"),
+            Self::Total => write!(f, "total"),
+        }
+    }
+}
+
+/// The response code of a name server which failed a reverse DNS resolution.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Rcode {
+    /// The name server was unable to process the query (`SERVFAIL`).
+    ServFail,
+    /// The name server refused to process the query (`REFUSED`).
+    Refused,
+    /// Any other error response code, such as `FORMERR` or `NOTIMP`.
+    Other(u16),
+}
+
+impl Display for Rcode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ServFail => write!(f, "SERVFAIL"),
+            Self::Refused => write!(f, "REFUSED"),
+            Self::Other(code) => write!(f, "RCODE{code}"),
+        }
+    }
 }
 
 /// The output of a successful DNS lookup.
 #[derive(Debug, Clone)]
-pub struct ResolvedIpAddrs(pub(super) Vec<IpAddr>);
+pub struct ResolvedIpAddrs(pub(super) Vec<IpAddr>, pub(super) Vec<String>);
 
 impl ResolvedIpAddrs {
     pub fn iter(&self) -> impl Iterator<Item = &'_ IpAddr> {
         self.0.iter()
     }
+
+    /// The `CNAME` chain followed from the hostname to the name which holds the addresses.
+    ///
+    /// Each entry is the target of a `CNAME` record, in the order they were followed, such that
+    /// the last entry is the canonical name of the host.  This is empty if the hostname is not an
+    /// alias or if it was resolved from the hosts file or with `ResolveMethod::System`.
+    #[must_use]
+    pub fn cnames(&self) -> &[String] {
+        &self.1
+    }
 }
 
 impl IntoIterator for ResolvedIpAddrs {
@@ -86,14 +218,36 @@ pub enum DnsEntry {
     /// The reverse DNS resolution of `IpAddr` has resolved.
     Resolved(Resolved),
     /// The `IpAddr` could not be resolved.
+    ///
+    /// The name server answered that there is no such name (`NXDOMAIN`) or that the name has no
+    /// `PTR` records.
     NotFound(Unresolved),
     /// The reverse DNS resolution of `IpAddr` failed.
-    Failed(IpAddr),
+    ///
+    /// The `Rcode` is that of the name server which answered with an error, such as `SERVFAIL`
+    /// or `REFUSED`, if any, and is `None` if the resolution failed without an answer.
+    Failed(IpAddr, Option<Rcode>),
     /// The reverse DNS resolution of `IpAddr` timed out.
     Timeout(IpAddr),
+    /// The reverse DNS resolution of `IpAddr` failed DNSSEC validation.
+    Bogus(IpAddr),
+}
+
+/// The outcome of the DNSSEC validation of a reverse DNS resolution.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum DnssecStatus {
+    /// The response was validated, i.e. the upstream resolver set the `AD` bit.
+    Secure,
+    /// The response was not validated, typically because the zone is not signed.
+    Insecure,
+    /// The response failed validation and was discarded by the upstream resolver.
+    Bogus,
 }
 
 /// Information about a resolved `IpAddr`.
+///
+/// The hostnames are those of all `PTR` records of the `IpAddr`, in the order they were returned
+/// by the name server.  Note that only a single hostname is available with `ResolveMethod::System`.
 #[derive(Debug, Clone)]
 pub enum Resolved {
     /// Resolved without `AsInfo`.
@@ -111,12 +265,43 @@ pub enum Unresolved {
     WithAsInfo(IpAddr, AsInfo),
 }
 
+/// The state of `AsInfo` resolution.
+///
+/// This is tracked independently of the reverse DNS resolution of the hostnames for a given
+/// `IpAddr` such that the failure of one does not discard the result of the other.
+#[derive(Debug, Clone)]
+pub enum AsInfoEntry {
+    /// The `AsInfo` resolution is pending.
+    Pending,
+    /// The `AsInfo` has resolved.
+    Resolved(AsInfo),
+    /// No `AsInfo` could be found.
+    NotFound,
+    /// The `AsInfo` resolution failed.
+    Failed,
+    /// The `AsInfo` resolution timed out.
+    Timeout,
+}
+
+impl AsInfoEntry {
+    /// The resolved `AsInfo`, or the default `AsInfo` if not resolved.
+    pub(crate) fn into_asinfo(self) -> AsInfo {
+        match self {
+            Self::Resolved(asinfo) => asinfo,
+            _ => AsInfo::default(),
+        }
+    }
+}
+
 /// Autonomous System (AS) information.
 #[derive(Debug, Clone, Default)]
 pub struct AsInfo {
     /// The Autonomous System Number.
     ///
     /// This is returned without the AS prefix i.e. `12301`.
+    ///
+    /// If the prefix is announced by more than one AS (MOAS) then each AS number is given,
+    /// separated by a `/`, i.e. `13335/13336`.  See [`AsInfo::asns`].
     pub asn: String,
     /// The AS prefix.
     ///
@@ -137,7 +322,19 @@ pub struct AsInfo {
     /// The Autonomous System Name.
     ///
     /// Given as a string i.e. `INVITECH, HU`.
+    ///
+    /// If the prefix is announced by more than one AS (MOAS) then the name of each AS is given,
+    /// separated by a ` / `.
     pub name: String,
+    /// The source which supplied the `AsInfo`, if known.
+    pub source: Option<AsLookupSource>,
+}
+
+impl AsInfo {
+    /// The Autonomous System Numbers of each AS which announces the prefix.
+    pub fn asns(&self) -> impl Iterator<Item = &str> {
+        self.asn.split('/').filter(|asn| !asn.is_empty())
+    }
 }
 
 impl Display for DnsEntry {
@@ -154,7 +351,9 @@ impl Display for DnsEntry {
             Self::NotFound(Unresolved::WithAsInfo(ip, asinfo)) => {
                 write!(f, "AS{} {}", asinfo.asn, ip)
             }
-            Self::Failed(ip) => write!(f, "Failed: {ip}"),
+            Self::Failed(ip, None) => write!(f, "Failed: {ip}"),
+            Self::Failed(ip, Some(rcode)) => write!(f, "Failed ({rcode}): {ip}"),
+            Self::Bogus(ip) => write!(f, "Bogus: {ip}"),
         }
     }
 }
@@ -0,0 +1,165 @@
+use crate::resolver::{AsInfo, Error, Result, TimeoutReason};
+use itertools::Itertools;
+use serde_json::Value;
+use std::io;
+use std::net::IpAddr;
+use std::time::Duration;
+use ureq::{Agent, AgentBuilder};
+
+/// The `RIPEstat` prefix overview data call.
+const PREFIX_OVERVIEW_URL: &str = "https://stat.ripe.net/data/prefix-overview/data.json";
+
+/// The name of the application, as requested by `RIPEstat` for all data calls.
+const SOURCE_APP: &str = "trippy";
+
+/// Make the HTTP `Agent` used for `RIPEstat` data calls.
+///
+/// The `timeout` bounds each data call as a whole.
+pub fn make_agent(timeout: Duration) -> Agent {
+    AgentBuilder::new()
+        .timeout(timeout)
+        .user_agent(concat!("trippy/", env!("CARGO_PKG_VERSION")))
+        .build()
+}
+
+/// Lookup the `AsInfo` for an `IpAddr` via the `RIPEstat` prefix overview data call.
+///
+/// Only the `asn`, `prefix` and `name` fields of the `AsInfo` are populated.  If the `IpAddr` is
+/// announced by more than one AS then the first is used.
+pub fn lookup_asinfo(agent: &Agent, addr: IpAddr) -> Result<AsInfo> {
+    let response = agent
+        .get(PREFIX_OVERVIEW_URL)
+        .query("resource", &addr.to_string())
+        .query("sourceapp", SOURCE_APP)
+        .call()
+        .map_err(query_error)?;
+    let body = response
+        .into_string()
+        .map_err(|err| Error::QueryRipeStatFailed(Box::new(err)))?;
+    parse_prefix_overview(&body)
+}
+
+/// Parse the `AsInfo` from the JSON body of a prefix overview data call.
+///
+/// Returns `Error::QueryAsnOriginFailed` if the resource is not announced by any AS.
+fn parse_prefix_overview(body: &str) -> Result<AsInfo> {
+    let json: Value =
+        serde_json::from_str(body).map_err(|err| Error::ParseRipeStatFailed(err.to_string()))?;
+    let data = &json["data"];
+    let prefix = data["resource"]
+        .as_str()
+        .ok_or_else(|| Error::ParseRipeStatFailed(String::from("missing resource")))?;
+    let asns = data["asns"]
+        .as_array()
+        .ok_or_else(|| Error::ParseRipeStatFailed(String::from("missing asns")))?;
+    if asns.is_empty() {
+        return Err(Error::QueryAsnOriginFailed);
+    }
+    let numbers = asns
+        .iter()
+        .map(|asn| {
+            asn["asn"]
+                .as_u64()
+                .ok_or_else(|| Error::ParseRipeStatFailed(String::from("invalid asn")))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let holders = asns
+        .iter()
+        .filter_map(|asn| asn["holder"].as_str())
+        .filter(|holder| !holder.is_empty());
+    Ok(AsInfo {
+        asn: numbers.iter().join("/"),
+        prefix: prefix.to_string(),
+        name: holders.format(" / ").to_string(),
+        ..AsInfo::default()
+    })
+}
+
+/// Map a `ureq::Error` to an `Error`.
+fn query_error(err: ureq::Error) -> Error {
+    let timed_out = std::iter::successors(
+        std::error::Error::source(&err),
+        |err: &&(dyn std::error::Error + 'static)| err.source(),
+    )
+    .filter_map(|err| err.downcast_ref::<io::Error>())
+    .any(|err| {
+        matches!(
+            err.kind(),
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+        )
+    });
+    if timed_out {
+        Error::Timeout(TimeoutReason::Total)
+    } else {
+        Error::QueryRipeStatFailed(Box::new(err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_prefix_overview() {
+        let body = r#"{
+            "status": "ok",
+            "data": {
+                "announced": true,
+                "asns": [
+                    {
+                        "asn": 3333,
+                        "holder": "RIPE-NCC-AS - Reseaux IP Europeens Network Coordination Centre (RIPE NCC)"
+                    }
+                ],
+                "resource": "193.0.0.0/21",
+                "type": "prefix"
+            }
+        }"#;
+        let asinfo = parse_prefix_overview(body).unwrap();
+        assert_eq!("3333", asinfo.asn);
+        assert_eq!("193.0.0.0/21", asinfo.prefix);
+        assert_eq!(
+            "RIPE-NCC-AS - Reseaux IP Europeens Network Coordination Centre (RIPE NCC)",
+            asinfo.name
+        );
+        assert!(asinfo.cc.is_empty());
+    }
+
+    #[test]
+    fn test_parse_prefix_overview_moas() {
+        let body = r#"{
+            "status": "ok",
+            "data": {
+                "announced": true,
+                "asns": [
+                    {"asn": 13335, "holder": "CLOUDFLARENET - Cloudflare, Inc."},
+                    {"asn": 13336, "holder": "CLOUDFLARENET-SFO - Cloudflare, Inc."}
+                ],
+                "resource": "1.1.1.0/24",
+                "type": "prefix"
+            }
+        }"#;
+        let asinfo = parse_prefix_overview(body).unwrap();
+        assert_eq!("13335/13336", asinfo.asn);
+        assert_eq!(vec!["13335", "13336"], asinfo.asns().collect::<Vec<_>>());
+        assert_eq!(
+            "CLOUDFLARENET - Cloudflare, Inc. / CLOUDFLARENET-SFO - Cloudflare, Inc.",
+            asinfo.name
+        );
+    }
+
+    #[test]
+    fn test_parse_prefix_overview_not_announced() {
+        let body = r#"{"status": "ok", "data": {"announced": false, "asns": [], "resource": "192.0.2.1"}}"#;
+        let err = parse_prefix_overview(body).unwrap_err();
+        assert!(matches!(err, Error::QueryAsnOriginFailed));
+    }
+
+    #[test]
+    fn test_parse_prefix_overview_invalid() {
+        let err = parse_prefix_overview("not json").unwrap_err();
+        assert!(matches!(err, Error::ParseRipeStatFailed(_)));
+        let err = parse_prefix_overview(r#"{"status": "error"}"#).unwrap_err();
+        assert!(matches!(err, Error::ParseRipeStatFailed(_)));
+    }
+}
@@ -0,0 +1,327 @@
+use std::fmt::{Debug, Formatter};
+use std::io;
+use std::net::SocketAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// The SOCKS protocol version.
+const VERSION: u8 = 0x05;
+
+/// The version of the username/password authentication sub-negotiation (RFC 1929).
+const AUTH_VERSION: u8 = 0x01;
+
+/// The authentication method for no authentication.
+const METHOD_NO_AUTH: u8 = 0x00;
+
+/// The authentication method for username/password authentication.
+const METHOD_USERNAME_PASSWORD: u8 = 0x02;
+
+/// The `CONNECT` command.
+const CMD_CONNECT: u8 = 0x01;
+
+/// The address type of an IPv4 address.
+const ATYP_IPV4: u8 = 0x01;
+
+/// The address type of a domain name.
+const ATYP_DOMAIN: u8 = 0x03;
+
+/// The address type of an IPv6 address.
+const ATYP_IPV6: u8 = 0x04;
+
+/// A SOCKS5 (RFC 1928) proxy through which to connect to name servers.
+///
+/// Only TCP connections are made via the proxy, the `UDP ASSOCIATE` command is not supported.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ProxyConfig {
+    /// The address of the proxy.
+    pub addr: SocketAddr,
+    /// The credentials with which to authenticate to the proxy, if it requires authentication.
+    pub auth: Option<ProxyAuth>,
+}
+
+/// The username and password with which to authenticate to a SOCKS5 proxy (RFC 1929).
+///
+/// Both the username and password must be between 1 and 255 bytes long.
+#[derive(Clone, Eq, PartialEq)]
+pub struct ProxyAuth {
+    pub username: String,
+    pub password: String,
+}
+
+impl Debug for ProxyAuth {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProxyAuth")
+            .field("username", &self.username)
+            .field("password", &"<redacted>")
+            .finish()
+    }
+}
+
+/// Connect to `target` via the SOCKS5 `proxy`.
+///
+/// Returns the stream to the proxy once the proxy has connected to the `target`, after which
+/// the stream is relayed to and from the `target` as is.
+pub async fn connect(proxy: &ProxyConfig, target: SocketAddr) -> io::Result<TcpStream> {
+    let mut stream = TcpStream::connect(proxy.addr).await?;
+    let method = match proxy.auth {
+        Some(_) => METHOD_USERNAME_PASSWORD,
+        None => METHOD_NO_AUTH,
+    };
+    stream.write_all(&[VERSION, 1, method]).await?;
+    let mut reply = [0_u8; 2];
+    stream.read_exact(&mut reply).await?;
+    match (reply, &proxy.auth) {
+        ([VERSION, METHOD_NO_AUTH], None) => {}
+        ([VERSION, METHOD_USERNAME_PASSWORD], Some(auth)) => {
+            authenticate(&mut stream, auth).await?;
+        }
+        ([VERSION, _], _) => {
+            return Err(proxy_error(
+                io::ErrorKind::PermissionDenied,
+                "no acceptable authentication method",
+            ))
+        }
+        _ => return Err(invalid_reply()),
+    }
+    stream.write_all(&connect_request(target)).await?;
+    let mut reply = [0_u8; 4];
+    stream.read_exact(&mut reply).await?;
+    match reply {
+        [VERSION, 0x00, _, _] => {}
+        [VERSION, code, _, _] => return Err(connect_error(code, target)),
+        _ => return Err(invalid_reply()),
+    }
+    // the address the proxy bound to connect to the target is not needed.
+    let bound_len = match reply[3] {
+        ATYP_IPV4 => 4,
+        ATYP_IPV6 => 16,
+        ATYP_DOMAIN => usize::from(stream.read_u8().await?),
+        _ => return Err(invalid_reply()),
+    };
+    stream.read_exact(&mut vec![0_u8; bound_len + 2]).await?;
+    Ok(stream)
+}
+
+/// Authenticate to the proxy with a username and password (RFC 1929).
+async fn authenticate(stream: &mut TcpStream, auth: &ProxyAuth) -> io::Result<()> {
+    let field = |value: &str| {
+        u8::try_from(value.len())
+            .ok()
+            .filter(|&len| len > 0)
+            .ok_or_else(|| {
+                proxy_error(
+                    io::ErrorKind::InvalidInput,
+                    "username and password must be between 1 and 255 bytes long",
+                )
+            })
+    };
+    let mut request = vec![AUTH_VERSION, field(&auth.username)?];
+    request.extend_from_slice(auth.username.as_bytes());
+    request.push(field(&auth.password)?);
+    request.extend_from_slice(auth.password.as_bytes());
+    stream.write_all(&request).await?;
+    let mut reply = [0_u8; 2];
+    stream.read_exact(&mut reply).await?;
+    match reply {
+        [AUTH_VERSION, 0x00] => Ok(()),
+        [AUTH_VERSION, _] => Err(proxy_error(
+            io::ErrorKind::PermissionDenied,
+            "authentication failed",
+        )),
+        _ => Err(invalid_reply()),
+    }
+}
+
+/// The `CONNECT` request for `target`.
+fn connect_request(target: SocketAddr) -> Vec<u8> {
+    let mut request = vec![VERSION, CMD_CONNECT, 0x00];
+    match target {
+        SocketAddr::V4(addr) => {
+            request.push(ATYP_IPV4);
+            request.extend_from_slice(&addr.ip().octets());
+        }
+        SocketAddr::V6(addr) => {
+            request.push(ATYP_IPV6);
+            request.extend_from_slice(&addr.ip().octets());
+        }
+    }
+    request.extend_from_slice(&target.port().to_be_bytes());
+    request
+}
+
+/// The error for a `CONNECT` request to `target` which the proxy failed with the reply `code`.
+fn connect_error(code: u8, target: SocketAddr) -> io::Error {
+    let (kind, reason) = match code {
+        0x02 => (io::ErrorKind::PermissionDenied, "connection not allowed"),
+        0x03 => (io::ErrorKind::Other, "network unreachable"),
+        0x04 => (io::ErrorKind::Other, "host unreachable"),
+        0x05 => (io::ErrorKind::ConnectionRefused, "connection refused"),
+        0x06 => (io::ErrorKind::TimedOut, "TTL expired"),
+        0x07 => (io::ErrorKind::Unsupported, "command not supported"),
+        0x08 => (io::ErrorKind::Unsupported, "address type not supported"),
+        _ => (io::ErrorKind::Other, "general failure"),
+    };
+    proxy_error(kind, &format!("connect to {target} failed: {reason}"))
+}
+
+fn invalid_reply() -> io::Error {
+    proxy_error(io::ErrorKind::InvalidData, "invalid reply")
+}
+
+fn proxy_error(kind: io::ErrorKind, msg: &str) -> io::Error {
+    io::Error::new(kind, format!("SOCKS5 proxy: {msg}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, TcpListener};
+    use std::thread;
+
+    /// Run a SOCKS5 proxy which accepts a single connection from the `listener`, reads the
+    /// `expected` requests and answers each with the corresponding reply.
+    fn run_proxy(listener: &TcpListener, exchanges: &[(&[u8], &[u8])]) -> anyhow::Result<()> {
+        let (mut stream, _) = listener.accept()?;
+        for (expected, reply) in exchanges {
+            let mut request = vec![0_u8; expected.len()];
+            stream.read_exact(&mut request)?;
+            assert_eq!(expected, &request.as_slice());
+            stream.write_all(reply)?;
+        }
+        Ok(())
+    }
+
+    fn connect_via(proxy: &ProxyConfig, target: SocketAddr) -> io::Result<TcpStream> {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_io()
+            .build()?
+            .block_on(connect(proxy, target))
+    }
+
+    fn start_proxy() -> anyhow::Result<(TcpListener, SocketAddr)> {
+        let listener = TcpListener::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0))?;
+        let addr = listener.local_addr()?;
+        Ok((listener, addr))
+    }
+
+    #[test]
+    fn test_connect_no_auth() -> anyhow::Result<()> {
+        let (listener, addr) = start_proxy()?;
+        let proxy = ProxyConfig { addr, auth: None };
+        let target = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 53)), 53);
+        thread::scope(|s| -> anyhow::Result<()> {
+            let conn = s.spawn(|| connect_via(&proxy, target));
+            run_proxy(
+                &listener,
+                &[
+                    (&[5, 1, 0], &[5, 0]),
+                    (
+                        &[5, 1, 0, 1, 192, 0, 2, 53, 0, 53],
+                        &[5, 0, 0, 1, 127, 0, 0, 1, 0x9c, 0x40],
+                    ),
+                ],
+            )?;
+            assert!(conn.join().unwrap().is_ok());
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_connect_auth_ipv6() -> anyhow::Result<()> {
+        let (listener, addr) = start_proxy()?;
+        let proxy = ProxyConfig {
+            addr,
+            auth: Some(ProxyAuth {
+                username: String::from("user"),
+                password: String::from("pass"),
+            }),
+        };
+        let target = SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), 853);
+        let mut connect = vec![5, 1, 0, 4];
+        connect.extend_from_slice(&Ipv6Addr::LOCALHOST.octets());
+        connect.extend_from_slice(&[3, 85]);
+        thread::scope(|s| -> anyhow::Result<()> {
+            let conn = s.spawn(|| connect_via(&proxy, target));
+            run_proxy(
+                &listener,
+                &[
+                    (&[5, 1, 2], &[5, 2]),
+                    (b"\x01\x04user\x04pass", &[1, 0]),
+                    (&connect, b"\x05\x00\x00\x03\x05proxy\x00\x35"),
+                ],
+            )?;
+            assert!(conn.join().unwrap().is_ok());
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_connect_auth_failed() -> anyhow::Result<()> {
+        let (listener, addr) = start_proxy()?;
+        let proxy = ProxyConfig {
+            addr,
+            auth: Some(ProxyAuth {
+                username: String::from("user"),
+                password: String::from("wrong"),
+            }),
+        };
+        let target = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 53);
+        thread::scope(|s| -> anyhow::Result<()> {
+            let conn = s.spawn(|| connect_via(&proxy, target));
+            run_proxy(
+                &listener,
+                &[(&[5, 1, 2], &[5, 2]), (b"\x01\x04user\x05wrong", &[1, 1])],
+            )?;
+            let err = conn.join().unwrap().unwrap_err();
+            assert_eq!(io::ErrorKind::PermissionDenied, err.kind());
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_connect_no_acceptable_method() -> anyhow::Result<()> {
+        let (listener, addr) = start_proxy()?;
+        let proxy = ProxyConfig { addr, auth: None };
+        let target = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 53);
+        thread::scope(|s| -> anyhow::Result<()> {
+            let conn = s.spawn(|| connect_via(&proxy, target));
+            run_proxy(&listener, &[(&[5, 1, 0], &[5, 0xff])])?;
+            let err = conn.join().unwrap().unwrap_err();
+            assert_eq!(io::ErrorKind::PermissionDenied, err.kind());
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_connect_refused() -> anyhow::Result<()> {
+        let (listener, addr) = start_proxy()?;
+        let proxy = ProxyConfig { addr, auth: None };
+        let target = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 53);
+        thread::scope(|s| -> anyhow::Result<()> {
+            let conn = s.spawn(|| connect_via(&proxy, target));
+            run_proxy(
+                &listener,
+                &[
+                    (&[5, 1, 0], &[5, 0]),
+                    (
+                        &[5, 1, 0, 1, 127, 0, 0, 1, 0, 53],
+                        &[5, 5, 0, 1, 0, 0, 0, 0, 0, 0],
+                    ),
+                ],
+            )?;
+            let err = conn.join().unwrap().unwrap_err();
+            assert_eq!(io::ErrorKind::ConnectionRefused, err.kind());
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_proxy_auth_debug_redacts_password() {
+        let auth = ProxyAuth {
+            username: String::from("user"),
+            password: String::from("secret"),
+        };
+        assert!(!format!("{auth:?}").contains("secret"));
+    }
+}
@@ -9,4 +9,7 @@ pub enum Error {
     /// Attempting to create a packet with a insufficient buffer size.
     #[error("insufficient buffer for {0} packet, minimum={1}, provided={2}")]
     InsufficientPacketBuffer(String, usize, usize),
+    /// A variable length field of a packet extends beyond the end of the buffer.
+    #[error("truncated packet, required={required}, actual={actual}")]
+    Truncated { required: usize, actual: usize },
 }
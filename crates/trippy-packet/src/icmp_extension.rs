@@ -139,6 +139,35 @@ pub mod extension_structure {
             assert!(object_iter.next().is_none());
         }
 
+        #[test]
+        fn test_new_view_boundary_lengths() {
+            const SIZE: usize = ExtensionsPacket::minimum_packet_size();
+            let buf = [0_u8; SIZE];
+            for len in 0..SIZE {
+                assert_eq!(
+                    Some(Error::InsufficientPacketBuffer(
+                        String::from("ExtensionsPacket"),
+                        SIZE,
+                        len
+                    ),),
+                    ExtensionsPacket::new_view(&buf[..len]).err()
+                );
+            }
+            let extensions = ExtensionsPacket::new_view(&buf).unwrap();
+            assert_eq!(SIZE, extensions.header().len());
+            assert!(extensions.objects().next().is_none());
+        }
+
+        #[test]
+        fn test_object_iterator_truncated() {
+            let buf = [
+                0x20, 0x00, 0x99, 0x3a, 0x00, 0x0c, 0x01, 0x01, 0x04, 0xbb, 0x41, 0x01,
+            ];
+            let extensions = ExtensionsPacket::new_view(&buf).unwrap();
+            let mut object_iter = extensions.objects();
+            assert!(object_iter.next().is_none());
+        }
+
         #[test]
         fn test_object_iterator_zero_length() {
             let buf = [
@@ -273,6 +302,25 @@ pub mod extension_header {
             assert_eq!([0xFF, 0xFF], extension.packet()[2..=3]);
         }
 
+        #[test]
+        fn test_new_view_boundary_lengths() {
+            const SIZE: usize = ExtensionHeaderPacket::minimum_packet_size();
+            let buf = [0_u8; SIZE];
+            for len in 0..SIZE {
+                let err = ExtensionHeaderPacket::new_view(&buf[..len]).unwrap_err();
+                assert_eq!(
+                    Error::InsufficientPacketBuffer(
+                        String::from("ExtensionHeaderPacket"),
+                        SIZE,
+                        len
+                    ),
+                    err
+                );
+            }
+            let header = ExtensionHeaderPacket::new_view(&buf).unwrap();
+            assert!(!format!("{header:?}").is_empty());
+        }
+
         #[test]
         fn test_extension_header_view() {
             let buf = [
@@ -364,11 +412,22 @@ pub mod extension_object {
             }
         }
 
+        /// Create a view of an `ExtensionObjectPacket`.
+        ///
+        /// Returns `Error::Truncated` if the object length exceeds the size of the buffer.
         pub fn new_view(packet: &'a [u8]) -> Result<Self> {
             if packet.len() >= Self::minimum_packet_size() {
-                Ok(Self {
+                let object = Self {
                     buf: Buffer::Immutable(packet),
-                })
+                };
+                let required = usize::from(object.get_length());
+                if required > packet.len() {
+                    return Err(Error::Truncated {
+                        required,
+                        actual: packet.len(),
+                    });
+                }
+                Ok(object)
             } else {
                 Err(Error::InsufficientPacketBuffer(
                     String::from("ExtensionObjectPacket"),
@@ -423,7 +482,10 @@ pub mod extension_object {
 
         #[must_use]
         pub fn payload(&self) -> &[u8] {
-            &self.buf.as_slice()[Self::minimum_packet_size()..usize::from(self.get_length())]
+            self.buf
+                .as_slice()
+                .get(Self::minimum_packet_size()..usize::from(self.get_length()))
+                .unwrap_or_default()
         }
     }
 
@@ -514,6 +576,49 @@ pub mod extension_object {
             assert_eq!(ClassSubType(1), object.get_class_subtype());
             assert_eq!([0x04, 0xbb, 0x41, 0x01], object.payload());
         }
+
+        #[test]
+        fn test_new_view_boundary_lengths() {
+            const SIZE: usize = ExtensionObjectPacket::minimum_packet_size();
+            let buf = [0_u8; SIZE];
+            for len in 0..SIZE {
+                let err = ExtensionObjectPacket::new_view(&buf[..len]).unwrap_err();
+                assert_eq!(
+                    Error::InsufficientPacketBuffer(
+                        String::from("ExtensionObjectPacket"),
+                        SIZE,
+                        len
+                    ),
+                    err
+                );
+            }
+            let object = ExtensionObjectPacket::new_view(&buf).unwrap();
+            assert!(!format!("{object:?}").is_empty());
+        }
+
+        #[test]
+        fn test_new_view_truncated() {
+            let buf = [0x00, 0x08, 0x01, 0x01, 0x04, 0xbb, 0x41, 0x01];
+            for len in ExtensionObjectPacket::minimum_packet_size()..buf.len() {
+                let err = ExtensionObjectPacket::new_view(&buf[..len]).unwrap_err();
+                assert_eq!(
+                    Error::Truncated {
+                        required: 8,
+                        actual: len
+                    },
+                    err
+                );
+            }
+        }
+
+        #[test]
+        fn test_payload_length_less_than_header() {
+            for length in 0..4 {
+                let buf = [0x00, length, 0x01, 0x01, 0x04, 0xbb, 0x41, 0x01];
+                let object = ExtensionObjectPacket::new_view(&buf).unwrap();
+                assert!(object.payload().is_empty());
+            }
+        }
     }
 }
 
@@ -629,6 +734,24 @@ pub mod mpls_label_stack {
             assert_eq!(1, member.get_ttl());
             assert!(member_iter.next().is_none());
         }
+
+        #[test]
+        fn test_new_view_boundary_lengths() {
+            const SIZE: usize = MplsLabelStackPacket::minimum_packet_size();
+            let buf = [0_u8; SIZE];
+            for len in 0..SIZE {
+                assert_eq!(
+                    Some(Error::InsufficientPacketBuffer(
+                        String::from("MplsLabelStackPacket"),
+                        SIZE,
+                        len
+                    ),),
+                    MplsLabelStackPacket::new_view(&buf[..len]).err()
+                );
+            }
+            let stack = MplsLabelStackPacket::new_view(&buf).unwrap();
+            assert_eq!(1, stack.members().count());
+        }
     }
 }
 
@@ -838,6 +961,25 @@ pub mod mpls_label_stack_member {
             assert_eq!(1, object.get_bos());
             assert_eq!(1, object.get_ttl());
         }
+
+        #[test]
+        fn test_new_view_boundary_lengths() {
+            const SIZE: usize = MplsLabelStackMemberPacket::minimum_packet_size();
+            let buf = [0_u8; SIZE];
+            for len in 0..SIZE {
+                let err = MplsLabelStackMemberPacket::new_view(&buf[..len]).unwrap_err();
+                assert_eq!(
+                    Error::InsufficientPacketBuffer(
+                        String::from("MplsLabelStackMemberPacket"),
+                        SIZE,
+                        len
+                    ),
+                    err
+                );
+            }
+            let member = MplsLabelStackMemberPacket::new_view(&buf).unwrap();
+            assert!(!format!("{member:?}").is_empty());
+        }
     }
 }
 
@@ -8,7 +8,9 @@ pub enum IcmpType {
     EchoRequest,
     EchoReply,
     DestinationUnreachable,
+    PacketTooBig,
     TimeExceeded,
+    ParameterProblem,
     Other(u8),
 }
 
@@ -19,7 +21,9 @@ impl IcmpType {
             Self::EchoRequest => 128,
             Self::EchoReply => 129,
             Self::DestinationUnreachable => 1,
+            Self::PacketTooBig => 2,
             Self::TimeExceeded => 3,
+            Self::ParameterProblem => 4,
             Self::Other(id) => *id,
         }
     }
@@ -31,7 +35,9 @@ impl From<u8> for IcmpType {
             128 => Self::EchoRequest,
             129 => Self::EchoReply,
             1 => Self::DestinationUnreachable,
+            2 => Self::PacketTooBig,
             3 => Self::TimeExceeded,
+            4 => Self::ParameterProblem,
             id => Self::Other(id),
         }
     }
@@ -174,9 +180,15 @@ mod tests {
         packet.set_icmp_type(IcmpType::DestinationUnreachable);
         assert_eq!(IcmpType::DestinationUnreachable, packet.get_icmp_type());
         assert_eq!([0x01], packet.packet()[0..1]);
+        packet.set_icmp_type(IcmpType::PacketTooBig);
+        assert_eq!(IcmpType::PacketTooBig, packet.get_icmp_type());
+        assert_eq!([0x02], packet.packet()[0..1]);
         packet.set_icmp_type(IcmpType::TimeExceeded);
         assert_eq!(IcmpType::TimeExceeded, packet.get_icmp_type());
         assert_eq!([0x03], packet.packet()[0..1]);
+        packet.set_icmp_type(IcmpType::ParameterProblem);
+        assert_eq!(IcmpType::ParameterProblem, packet.get_icmp_type());
+        assert_eq!([0x04], packet.packet()[0..1]);
         packet.set_icmp_type(IcmpType::Other(255));
         assert_eq!(IcmpType::Other(255), packet.get_icmp_type());
         assert_eq!([0xFF], packet.packet()[0..1]);
@@ -223,6 +235,21 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_new_view_boundary_lengths() {
+        const SIZE: usize = IcmpPacket::minimum_packet_size();
+        let buf = [0_u8; SIZE];
+        for len in 0..SIZE {
+            let err = IcmpPacket::new_view(&buf[..len]).unwrap_err();
+            assert_eq!(
+                Error::InsufficientPacketBuffer(String::from("IcmpPacket"), SIZE, len),
+                err
+            );
+        }
+        let packet = IcmpPacket::new_view(&buf).unwrap();
+        assert!(!format!("{packet:?}").is_empty());
+    }
+
     #[test]
     fn test_new_view_insufficient_buffer() {
         const SIZE: usize = IcmpPacket::minimum_packet_size();
@@ -474,6 +501,21 @@ pub mod echo_request {
             );
         }
 
+        #[test]
+        fn test_new_view_boundary_lengths() {
+            const SIZE: usize = EchoRequestPacket::minimum_packet_size();
+            let buf = [0_u8; SIZE];
+            for len in 0..SIZE {
+                let err = EchoRequestPacket::new_view(&buf[..len]).unwrap_err();
+                assert_eq!(
+                    Error::InsufficientPacketBuffer(String::from("EchoRequestPacket"), SIZE, len),
+                    err
+                );
+            }
+            let packet = EchoRequestPacket::new_view(&buf).unwrap();
+            assert!(!format!("{packet:?}").is_empty());
+        }
+
         #[test]
         fn test_new_view_insufficient_buffer() {
             const SIZE: usize = EchoRequestPacket::minimum_packet_size();
@@ -726,6 +768,21 @@ pub mod echo_reply {
             );
         }
 
+        #[test]
+        fn test_new_view_boundary_lengths() {
+            const SIZE: usize = EchoReplyPacket::minimum_packet_size();
+            let buf = [0_u8; SIZE];
+            for len in 0..SIZE {
+                let err = EchoReplyPacket::new_view(&buf[..len]).unwrap_err();
+                assert_eq!(
+                    Error::InsufficientPacketBuffer(String::from("EchoReplyPacket"), SIZE, len),
+                    err
+                );
+            }
+            let packet = EchoReplyPacket::new_view(&buf).unwrap();
+            assert!(!format!("{packet:?}").is_empty());
+        }
+
         #[test]
         fn test_new_view_insufficient_buffer() {
             const SIZE: usize = EchoReplyPacket::minimum_packet_size();
@@ -860,7 +917,7 @@ pub mod time_exceeded {
         }
 
         fn split_payload_extension(&self) -> (&[u8], Option<&[u8]>) {
-            let length = usize::from(self.get_length() * 8);
+            let length = usize::from(self.get_length()) * 8;
             let icmp_payload = &self.buf.as_slice()[Self::minimum_packet_size()..];
             split(length, icmp_payload)
         }
@@ -970,6 +1027,31 @@ pub mod time_exceeded {
             );
         }
 
+        #[test]
+        fn test_length_exceeds_buffer() {
+            let mut buf = [0_u8; TimeExceededPacket::minimum_packet_size() + 8];
+            buf[4] = u8::MAX;
+            let packet = TimeExceededPacket::new_view(&buf).unwrap();
+            assert_eq!(u8::MAX, packet.get_length());
+            assert_eq!(8, packet.payload().len());
+            assert!(packet.extension().is_none());
+        }
+
+        #[test]
+        fn test_new_view_boundary_lengths() {
+            const SIZE: usize = TimeExceededPacket::minimum_packet_size();
+            let buf = [0_u8; SIZE];
+            for len in 0..SIZE {
+                let err = TimeExceededPacket::new_view(&buf[..len]).unwrap_err();
+                assert_eq!(
+                    Error::InsufficientPacketBuffer(String::from("TimeExceededPacket"), SIZE, len),
+                    err
+                );
+            }
+            let packet = TimeExceededPacket::new_view(&buf).unwrap();
+            assert!(!format!("{packet:?}").is_empty());
+        }
+
         #[test]
         fn test_new_view_insufficient_buffer() {
             const SIZE: usize = TimeExceededPacket::minimum_packet_size();
@@ -1117,7 +1199,7 @@ pub mod destination_unreachable {
             // From rfc4884:
             //
             // "For ICMPv6 messages, the length attribute represents 64-bit words"
-            let length = usize::from(self.get_length() * 8);
+            let length = usize::from(self.get_length()) * 8;
             let icmp_payload = &self.buf.as_slice()[Self::minimum_packet_size()..];
             split(length, icmp_payload)
         }
@@ -1232,6 +1314,35 @@ pub mod destination_unreachable {
             );
         }
 
+        #[test]
+        fn test_length_exceeds_buffer() {
+            let mut buf = [0_u8; DestinationUnreachablePacket::minimum_packet_size() + 8];
+            buf[4] = u8::MAX;
+            let packet = DestinationUnreachablePacket::new_view(&buf).unwrap();
+            assert_eq!(u8::MAX, packet.get_length());
+            assert_eq!(8, packet.payload().len());
+            assert!(packet.extension().is_none());
+        }
+
+        #[test]
+        fn test_new_view_boundary_lengths() {
+            const SIZE: usize = DestinationUnreachablePacket::minimum_packet_size();
+            let buf = [0_u8; SIZE];
+            for len in 0..SIZE {
+                let err = DestinationUnreachablePacket::new_view(&buf[..len]).unwrap_err();
+                assert_eq!(
+                    Error::InsufficientPacketBuffer(
+                        String::from("DestinationUnreachablePacket"),
+                        SIZE,
+                        len
+                    ),
+                    err
+                );
+            }
+            let packet = DestinationUnreachablePacket::new_view(&buf).unwrap();
+            assert!(!format!("{packet:?}").is_empty());
+        }
+
         #[test]
         fn test_new_view_insufficient_buffer() {
             const SIZE: usize = DestinationUnreachablePacket::minimum_packet_size();
@@ -1248,3 +1359,444 @@ pub mod destination_unreachable {
         }
     }
 }
+
+pub mod packet_too_big {
+    use crate::buffer::Buffer;
+    use crate::error::{Error, Result};
+    use crate::fmt_payload;
+    use crate::icmpv6::{IcmpCode, IcmpType};
+    use std::fmt::{Debug, Formatter};
+
+    const TYPE_OFFSET: usize = 0;
+    const CODE_OFFSET: usize = 1;
+    const CHECKSUM_OFFSET: usize = 2;
+    const MTU_OFFSET: usize = 4;
+
+    /// Represents an ICMP `PacketTooBig` packet.
+    ///
+    /// The internal representation is held in network byte order (big-endian) and all accessor
+    /// methods take and return data in host byte order, converting as necessary for the given
+    /// architecture.
+    pub struct PacketTooBigPacket<'a> {
+        buf: Buffer<'a>,
+    }
+
+    impl<'a> PacketTooBigPacket<'a> {
+        pub fn new(packet: &'a mut [u8]) -> Result<Self> {
+            if packet.len() >= Self::minimum_packet_size() {
+                Ok(Self {
+                    buf: Buffer::Mutable(packet),
+                })
+            } else {
+                Err(Error::InsufficientPacketBuffer(
+                    String::from("PacketTooBigPacket"),
+                    Self::minimum_packet_size(),
+                    packet.len(),
+                ))
+            }
+        }
+
+        pub fn new_view(packet: &'a [u8]) -> Result<Self> {
+            if packet.len() >= Self::minimum_packet_size() {
+                Ok(Self {
+                    buf: Buffer::Immutable(packet),
+                })
+            } else {
+                Err(Error::InsufficientPacketBuffer(
+                    String::from("PacketTooBigPacket"),
+                    Self::minimum_packet_size(),
+                    packet.len(),
+                ))
+            }
+        }
+
+        #[must_use]
+        pub const fn minimum_packet_size() -> usize {
+            8
+        }
+
+        #[must_use]
+        pub fn get_icmp_type(&self) -> IcmpType {
+            IcmpType::from(self.buf.read(TYPE_OFFSET))
+        }
+
+        #[must_use]
+        pub fn get_icmp_code(&self) -> IcmpCode {
+            IcmpCode::from(self.buf.read(CODE_OFFSET))
+        }
+
+        #[must_use]
+        pub fn get_checksum(&self) -> u16 {
+            u16::from_be_bytes(self.buf.get_bytes(CHECKSUM_OFFSET))
+        }
+
+        #[must_use]
+        pub fn get_mtu(&self) -> u32 {
+            u32::from_be_bytes(self.buf.get_bytes(MTU_OFFSET))
+        }
+
+        pub fn set_icmp_type(&mut self, val: IcmpType) {
+            *self.buf.write(TYPE_OFFSET) = val.id();
+        }
+
+        pub fn set_icmp_code(&mut self, val: IcmpCode) {
+            *self.buf.write(CODE_OFFSET) = val.0;
+        }
+
+        pub fn set_checksum(&mut self, val: u16) {
+            self.buf.set_bytes(CHECKSUM_OFFSET, val.to_be_bytes());
+        }
+
+        pub fn set_mtu(&mut self, val: u32) {
+            self.buf.set_bytes(MTU_OFFSET, val.to_be_bytes());
+        }
+
+        pub fn set_payload(&mut self, vals: &[u8]) {
+            let current_offset = Self::minimum_packet_size();
+            self.buf.as_slice_mut()[current_offset..current_offset + vals.len()]
+                .copy_from_slice(vals);
+        }
+
+        #[must_use]
+        pub fn packet(&self) -> &[u8] {
+            self.buf.as_slice()
+        }
+
+        #[must_use]
+        pub fn payload(&self) -> &[u8] {
+            &self.buf.as_slice()[Self::minimum_packet_size()..]
+        }
+    }
+
+    impl Debug for PacketTooBigPacket<'_> {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("PacketTooBigPacket")
+                .field("icmp_type", &self.get_icmp_type())
+                .field("icmp_code", &self.get_icmp_code())
+                .field("checksum", &self.get_checksum())
+                .field("mtu", &self.get_mtu())
+                .field("payload", &fmt_payload(self.payload()))
+                .finish()
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_icmp_type() {
+            let mut buf = [0_u8; PacketTooBigPacket::minimum_packet_size()];
+            let mut packet = PacketTooBigPacket::new(&mut buf).unwrap();
+            packet.set_icmp_type(IcmpType::PacketTooBig);
+            assert_eq!(IcmpType::PacketTooBig, packet.get_icmp_type());
+            assert_eq!([0x02], packet.packet()[0..1]);
+            packet.set_icmp_type(IcmpType::Other(255));
+            assert_eq!(IcmpType::Other(255), packet.get_icmp_type());
+            assert_eq!([0xFF], packet.packet()[0..1]);
+        }
+
+        #[test]
+        fn test_icmp_code() {
+            let mut buf = [0_u8; PacketTooBigPacket::minimum_packet_size()];
+            let mut packet = PacketTooBigPacket::new(&mut buf).unwrap();
+            packet.set_icmp_code(IcmpCode(0));
+            assert_eq!(IcmpCode(0), packet.get_icmp_code());
+            assert_eq!([0x00], packet.packet()[1..2]);
+            packet.set_icmp_code(IcmpCode(255));
+            assert_eq!(IcmpCode(255), packet.get_icmp_code());
+            assert_eq!([0xFF], packet.packet()[1..2]);
+        }
+
+        #[test]
+        fn test_checksum() {
+            let mut buf = [0_u8; PacketTooBigPacket::minimum_packet_size()];
+            let mut packet = PacketTooBigPacket::new(&mut buf).unwrap();
+            packet.set_checksum(0);
+            assert_eq!(0, packet.get_checksum());
+            assert_eq!([0x00, 0x00], packet.packet()[2..=3]);
+            packet.set_checksum(1999);
+            assert_eq!(1999, packet.get_checksum());
+            assert_eq!([0x07, 0xCF], packet.packet()[2..=3]);
+            packet.set_checksum(u16::MAX);
+            assert_eq!(u16::MAX, packet.get_checksum());
+            assert_eq!([0xFF, 0xFF], packet.packet()[2..=3]);
+        }
+
+        #[test]
+        fn test_mtu() {
+            let mut buf = [0_u8; PacketTooBigPacket::minimum_packet_size()];
+            let mut packet = PacketTooBigPacket::new(&mut buf).unwrap();
+            packet.set_mtu(0);
+            assert_eq!(0, packet.get_mtu());
+            assert_eq!([0x00, 0x00, 0x00, 0x00], packet.packet()[4..8]);
+            packet.set_mtu(1280);
+            assert_eq!(1280, packet.get_mtu());
+            assert_eq!([0x00, 0x00, 0x05, 0x00], packet.packet()[4..8]);
+            packet.set_mtu(u32::MAX);
+            assert_eq!(u32::MAX, packet.get_mtu());
+            assert_eq!([0xFF, 0xFF, 0xFF, 0xFF], packet.packet()[4..8]);
+        }
+
+        #[test]
+        fn test_view() {
+            let buf = [
+                0x02, 0x00, 0x5b, 0x2e, 0x00, 0x00, 0x05, 0xdc, 0x60, 0x00, 0x00, 0x00,
+            ];
+            let packet = PacketTooBigPacket::new_view(&buf).unwrap();
+            assert_eq!(IcmpType::PacketTooBig, packet.get_icmp_type());
+            assert_eq!(IcmpCode(0), packet.get_icmp_code());
+            assert_eq!(23342, packet.get_checksum());
+            assert_eq!(1500, packet.get_mtu());
+            assert_eq!([0x60, 0x00, 0x00, 0x00], packet.payload());
+        }
+
+        #[test]
+        fn test_new_insufficient_buffer() {
+            const SIZE: usize = PacketTooBigPacket::minimum_packet_size();
+            let mut buf = [0_u8; SIZE - 1];
+            let err = PacketTooBigPacket::new(&mut buf).unwrap_err();
+            assert_eq!(
+                Error::InsufficientPacketBuffer(String::from("PacketTooBigPacket"), SIZE, SIZE - 1),
+                err
+            );
+        }
+
+        #[test]
+        fn test_new_view_insufficient_buffer() {
+            const SIZE: usize = PacketTooBigPacket::minimum_packet_size();
+            let buf = [0_u8; SIZE - 1];
+            let err = PacketTooBigPacket::new_view(&buf).unwrap_err();
+            assert_eq!(
+                Error::InsufficientPacketBuffer(String::from("PacketTooBigPacket"), SIZE, SIZE - 1),
+                err
+            );
+        }
+    }
+}
+
+pub mod parameter_problem {
+    use crate::buffer::Buffer;
+    use crate::error::{Error, Result};
+    use crate::fmt_payload;
+    use crate::icmpv6::{IcmpCode, IcmpType};
+    use std::fmt::{Debug, Formatter};
+
+    const TYPE_OFFSET: usize = 0;
+    const CODE_OFFSET: usize = 1;
+    const CHECKSUM_OFFSET: usize = 2;
+    const POINTER_OFFSET: usize = 4;
+
+    /// Represents an ICMP `ParameterProblem` packet.
+    ///
+    /// The pointer identifies the octet offset within the original datagram where the error was
+    /// detected.
+    ///
+    /// The internal representation is held in network byte order (big-endian) and all accessor
+    /// methods take and return data in host byte order, converting as necessary for the given
+    /// architecture.
+    pub struct ParameterProblemPacket<'a> {
+        buf: Buffer<'a>,
+    }
+
+    impl<'a> ParameterProblemPacket<'a> {
+        pub fn new(packet: &'a mut [u8]) -> Result<Self> {
+            if packet.len() >= Self::minimum_packet_size() {
+                Ok(Self {
+                    buf: Buffer::Mutable(packet),
+                })
+            } else {
+                Err(Error::InsufficientPacketBuffer(
+                    String::from("ParameterProblemPacket"),
+                    Self::minimum_packet_size(),
+                    packet.len(),
+                ))
+            }
+        }
+
+        pub fn new_view(packet: &'a [u8]) -> Result<Self> {
+            if packet.len() >= Self::minimum_packet_size() {
+                Ok(Self {
+                    buf: Buffer::Immutable(packet),
+                })
+            } else {
+                Err(Error::InsufficientPacketBuffer(
+                    String::from("ParameterProblemPacket"),
+                    Self::minimum_packet_size(),
+                    packet.len(),
+                ))
+            }
+        }
+
+        #[must_use]
+        pub const fn minimum_packet_size() -> usize {
+            8
+        }
+
+        #[must_use]
+        pub fn get_icmp_type(&self) -> IcmpType {
+            IcmpType::from(self.buf.read(TYPE_OFFSET))
+        }
+
+        #[must_use]
+        pub fn get_icmp_code(&self) -> IcmpCode {
+            IcmpCode::from(self.buf.read(CODE_OFFSET))
+        }
+
+        #[must_use]
+        pub fn get_checksum(&self) -> u16 {
+            u16::from_be_bytes(self.buf.get_bytes(CHECKSUM_OFFSET))
+        }
+
+        #[must_use]
+        pub fn get_pointer(&self) -> u32 {
+            u32::from_be_bytes(self.buf.get_bytes(POINTER_OFFSET))
+        }
+
+        pub fn set_icmp_type(&mut self, val: IcmpType) {
+            *self.buf.write(TYPE_OFFSET) = val.id();
+        }
+
+        pub fn set_icmp_code(&mut self, val: IcmpCode) {
+            *self.buf.write(CODE_OFFSET) = val.0;
+        }
+
+        pub fn set_checksum(&mut self, val: u16) {
+            self.buf.set_bytes(CHECKSUM_OFFSET, val.to_be_bytes());
+        }
+
+        pub fn set_pointer(&mut self, val: u32) {
+            self.buf.set_bytes(POINTER_OFFSET, val.to_be_bytes());
+        }
+
+        pub fn set_payload(&mut self, vals: &[u8]) {
+            let current_offset = Self::minimum_packet_size();
+            self.buf.as_slice_mut()[current_offset..current_offset + vals.len()]
+                .copy_from_slice(vals);
+        }
+
+        #[must_use]
+        pub fn packet(&self) -> &[u8] {
+            self.buf.as_slice()
+        }
+
+        #[must_use]
+        pub fn payload(&self) -> &[u8] {
+            &self.buf.as_slice()[Self::minimum_packet_size()..]
+        }
+    }
+
+    impl Debug for ParameterProblemPacket<'_> {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("ParameterProblemPacket")
+                .field("icmp_type", &self.get_icmp_type())
+                .field("icmp_code", &self.get_icmp_code())
+                .field("checksum", &self.get_checksum())
+                .field("pointer", &self.get_pointer())
+                .field("payload", &fmt_payload(self.payload()))
+                .finish()
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_icmp_type() {
+            let mut buf = [0_u8; ParameterProblemPacket::minimum_packet_size()];
+            let mut packet = ParameterProblemPacket::new(&mut buf).unwrap();
+            packet.set_icmp_type(IcmpType::ParameterProblem);
+            assert_eq!(IcmpType::ParameterProblem, packet.get_icmp_type());
+            assert_eq!([0x04], packet.packet()[0..1]);
+            packet.set_icmp_type(IcmpType::Other(255));
+            assert_eq!(IcmpType::Other(255), packet.get_icmp_type());
+            assert_eq!([0xFF], packet.packet()[0..1]);
+        }
+
+        #[test]
+        fn test_icmp_code() {
+            let mut buf = [0_u8; ParameterProblemPacket::minimum_packet_size()];
+            let mut packet = ParameterProblemPacket::new(&mut buf).unwrap();
+            packet.set_icmp_code(IcmpCode(0));
+            assert_eq!(IcmpCode(0), packet.get_icmp_code());
+            assert_eq!([0x00], packet.packet()[1..2]);
+            packet.set_icmp_code(IcmpCode(255));
+            assert_eq!(IcmpCode(255), packet.get_icmp_code());
+            assert_eq!([0xFF], packet.packet()[1..2]);
+        }
+
+        #[test]
+        fn test_checksum() {
+            let mut buf = [0_u8; ParameterProblemPacket::minimum_packet_size()];
+            let mut packet = ParameterProblemPacket::new(&mut buf).unwrap();
+            packet.set_checksum(0);
+            assert_eq!(0, packet.get_checksum());
+            assert_eq!([0x00, 0x00], packet.packet()[2..=3]);
+            packet.set_checksum(1999);
+            assert_eq!(1999, packet.get_checksum());
+            assert_eq!([0x07, 0xCF], packet.packet()[2..=3]);
+            packet.set_checksum(u16::MAX);
+            assert_eq!(u16::MAX, packet.get_checksum());
+            assert_eq!([0xFF, 0xFF], packet.packet()[2..=3]);
+        }
+
+        #[test]
+        fn test_pointer() {
+            let mut buf = [0_u8; ParameterProblemPacket::minimum_packet_size()];
+            let mut packet = ParameterProblemPacket::new(&mut buf).unwrap();
+            packet.set_pointer(0);
+            assert_eq!(0, packet.get_pointer());
+            assert_eq!([0x00, 0x00, 0x00, 0x00], packet.packet()[4..8]);
+            packet.set_pointer(40);
+            assert_eq!(40, packet.get_pointer());
+            assert_eq!([0x00, 0x00, 0x00, 0x28], packet.packet()[4..8]);
+            packet.set_pointer(u32::MAX);
+            assert_eq!(u32::MAX, packet.get_pointer());
+            assert_eq!([0xFF, 0xFF, 0xFF, 0xFF], packet.packet()[4..8]);
+        }
+
+        #[test]
+        fn test_view() {
+            let buf = [
+                0x04, 0x01, 0x5b, 0x2e, 0x00, 0x00, 0x00, 0x28, 0x60, 0x00, 0x00, 0x00,
+            ];
+            let packet = ParameterProblemPacket::new_view(&buf).unwrap();
+            assert_eq!(IcmpType::ParameterProblem, packet.get_icmp_type());
+            assert_eq!(IcmpCode(1), packet.get_icmp_code());
+            assert_eq!(23342, packet.get_checksum());
+            assert_eq!(40, packet.get_pointer());
+            assert_eq!([0x60, 0x00, 0x00, 0x00], packet.payload());
+        }
+
+        #[test]
+        fn test_new_insufficient_buffer() {
+            const SIZE: usize = ParameterProblemPacket::minimum_packet_size();
+            let mut buf = [0_u8; SIZE - 1];
+            let err = ParameterProblemPacket::new(&mut buf).unwrap_err();
+            assert_eq!(
+                Error::InsufficientPacketBuffer(
+                    String::from("ParameterProblemPacket"),
+                    SIZE,
+                    SIZE - 1
+                ),
+                err
+            );
+        }
+
+        #[test]
+        fn test_new_view_insufficient_buffer() {
+            const SIZE: usize = ParameterProblemPacket::minimum_packet_size();
+            let buf = [0_u8; SIZE - 1];
+            let err = ParameterProblemPacket::new_view(&buf).unwrap_err();
+            assert_eq!(
+                Error::InsufficientPacketBuffer(
+                    String::from("ParameterProblemPacket"),
+                    SIZE,
+                    SIZE - 1
+                ),
+                err
+            );
+        }
+    }
+}
@@ -40,11 +40,22 @@ impl<'a> Ipv4Packet<'a> {
         }
     }
 
+    /// Create a view of an `Ipv4Packet`.
+    ///
+    /// Returns `Error::Truncated` if the header length exceeds the size of the buffer.
     pub fn new_view(packet: &'a [u8]) -> Result<Self> {
         if packet.len() >= Self::minimum_packet_size() {
-            Ok(Self {
+            let ipv4 = Self {
                 buf: Buffer::Immutable(packet),
-            })
+            };
+            let required = Self::minimum_packet_size() + ipv4_options_length(&ipv4);
+            if required > packet.len() {
+                return Err(Error::Truncated {
+                    required,
+                    actual: packet.len(),
+                });
+            }
+            Ok(ipv4)
         } else {
             Err(Error::InsufficientPacketBuffer(
                 String::from("Ipv4Packet"),
@@ -202,7 +213,7 @@ impl<'a> Ipv4Packet<'a> {
     #[must_use]
     pub fn payload(&self) -> &[u8] {
         let start = Ipv4Packet::minimum_packet_size() + ipv4_options_length(self);
-        &self.buf.as_slice()[start..]
+        self.buf.as_slice().get(start..).unwrap_or_default()
     }
 }
 
@@ -470,6 +481,21 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_new_view_boundary_lengths() {
+        const SIZE: usize = Ipv4Packet::minimum_packet_size();
+        let buf = [0_u8; SIZE];
+        for len in 0..SIZE {
+            let err = Ipv4Packet::new_view(&buf[..len]).unwrap_err();
+            assert_eq!(
+                Error::InsufficientPacketBuffer(String::from("Ipv4Packet"), SIZE, len),
+                err
+            );
+        }
+        let packet = Ipv4Packet::new_view(&buf).unwrap();
+        assert!(!format!("{packet:?}").is_empty());
+    }
+
     #[test]
     fn test_new_view_insufficient_buffer() {
         const SIZE: usize = Ipv4Packet::minimum_packet_size();
@@ -480,4 +506,39 @@ mod tests {
             err
         );
     }
+
+    #[test]
+    fn test_new_view_truncated_options() {
+        let mut buf = [0_u8; 24];
+        buf[0] = 0x46;
+        let err = Ipv4Packet::new_view(&buf[..23]).unwrap_err();
+        assert_eq!(
+            Error::Truncated {
+                required: 24,
+                actual: 23
+            },
+            err
+        );
+        let packet = Ipv4Packet::new_view(&buf).unwrap();
+        assert_eq!(4, packet.get_options_raw().len());
+        assert!(packet.payload().is_empty());
+        buf[0] = 0x4f;
+        let err = Ipv4Packet::new_view(&buf).unwrap_err();
+        assert_eq!(
+            Error::Truncated {
+                required: 60,
+                actual: 24
+            },
+            err
+        );
+    }
+
+    #[test]
+    fn test_payload_header_length_exceeds_buffer() {
+        let mut buf = [0_u8; Ipv4Packet::minimum_packet_size()];
+        let mut packet = Ipv4Packet::new(&mut buf).unwrap();
+        packet.set_header_length(15);
+        assert!(packet.get_options_raw().is_empty());
+        assert!(packet.payload().is_empty());
+    }
 }
@@ -13,6 +13,21 @@ const HOP_LIMIT_OFFSET: usize = 7;
 const SOURCE_ADDRESS_OFFSET: usize = 8;
 const DESTINATION_ADDRESS_OFFSET: usize = 24;
 
+/// The next header value of a Hop-by-Hop Options extension header.
+const NEXT_HEADER_HOP_BY_HOP: u8 = 0;
+
+/// The next header value of a Routing extension header.
+const NEXT_HEADER_ROUTING: u8 = 43;
+
+/// The next header value of a Fragment extension header.
+const NEXT_HEADER_FRAGMENT: u8 = 44;
+
+/// The next header value of a Destination Options extension header.
+const NEXT_HEADER_DESTINATION_OPTIONS: u8 = 60;
+
+/// The size of a Fragment extension header, which has no length field.
+const FRAGMENT_HEADER_SIZE: usize = 8;
+
 /// Represents an IPv6 Packet.
 ///
 /// The internal representation is held in network byte order (big-endian) and all accessor methods
@@ -165,6 +180,38 @@ impl<'a> Ipv6Packet<'a> {
         }
         &self.buf.as_slice()[start..end]
     }
+
+    /// The upper-layer protocol and payload, following any extension headers.
+    ///
+    /// The chain of Hop-by-Hop Options, Routing, Fragment and Destination Options extension
+    /// headers is walked until an upper-layer header is found.
+    ///
+    /// Returns `Error::Truncated` if an extension header extends beyond the end of the payload.
+    pub fn upper_layer_payload(&self) -> Result<(IpProtocol, &[u8])> {
+        let mut next_header = self.get_next_header();
+        let mut payload = self.payload();
+        while let IpProtocol::Other(id) = next_header {
+            let required = match id {
+                NEXT_HEADER_HOP_BY_HOP | NEXT_HEADER_ROUTING | NEXT_HEADER_DESTINATION_OPTIONS => {
+                    match payload {
+                        [_, length, ..] => (usize::from(*length) + 1) * 8,
+                        _ => 2,
+                    }
+                }
+                NEXT_HEADER_FRAGMENT => FRAGMENT_HEADER_SIZE,
+                _ => break,
+            };
+            if required > payload.len() {
+                return Err(Error::Truncated {
+                    required,
+                    actual: payload.len(),
+                });
+            }
+            next_header = IpProtocol::from(payload[0]);
+            payload = &payload[required..];
+        }
+        Ok((next_header, payload))
+    }
 }
 
 impl Debug for Ipv6Packet<'_> {
@@ -371,6 +418,110 @@ mod tests {
         assert!(packet.payload().is_empty());
     }
 
+    #[test]
+    fn test_upper_layer_payload_no_extension_headers() {
+        let buf = hex_literal::hex!(
+            "
+            60 00 00 00 00 08 11 40 fe 80 00 00 00 00 00 00
+            00 00 00 00 00 00 00 01 fe 80 00 00 00 00 00 00
+            00 00 00 00 00 00 00 02 82 9a 82 9a 00 08 00 00
+            "
+        );
+        let packet = Ipv6Packet::new_view(&buf).unwrap();
+        let (next_header, payload) = packet.upper_layer_payload().unwrap();
+        assert_eq!(IpProtocol::Udp, next_header);
+        assert_eq!(&buf[40..], payload);
+    }
+
+    #[test]
+    fn test_upper_layer_payload_one_extension_header() {
+        let buf = hex_literal::hex!(
+            "
+            60 00 00 00 00 10 00 40 fe 80 00 00 00 00 00 00
+            00 00 00 00 00 00 00 01 fe 80 00 00 00 00 00 00
+            00 00 00 00 00 00 00 02 11 00 01 04 00 00 00 00
+            82 9a 82 9a 00 08 00 00
+            "
+        );
+        let packet = Ipv6Packet::new_view(&buf).unwrap();
+        let (next_header, payload) = packet.upper_layer_payload().unwrap();
+        assert_eq!(IpProtocol::Udp, next_header);
+        assert_eq!(&buf[48..], payload);
+    }
+
+    #[test]
+    fn test_upper_layer_payload_two_extension_headers() {
+        let buf = hex_literal::hex!(
+            "
+            60 00 00 00 00 20 2b 40 fe 80 00 00 00 00 00 00
+            00 00 00 00 00 00 00 01 fe 80 00 00 00 00 00 00
+            00 00 00 00 00 00 00 02 2c 01 04 00 00 00 00 00
+            00 00 00 00 00 00 00 00 3a 00 00 01 12 34 56 78
+            80 00 00 00 04 d2 80 e8
+            "
+        );
+        let packet = Ipv6Packet::new_view(&buf).unwrap();
+        let (next_header, payload) = packet.upper_layer_payload().unwrap();
+        assert_eq!(IpProtocol::IcmpV6, next_header);
+        assert_eq!(&buf[64..], payload);
+    }
+
+    #[test]
+    fn test_upper_layer_payload_unknown_next_header() {
+        let buf = hex_literal::hex!(
+            "
+            60 00 00 00 00 08 3b 40 fe 80 00 00 00 00 00 00
+            00 00 00 00 00 00 00 01 fe 80 00 00 00 00 00 00
+            00 00 00 00 00 00 00 02 00 00 00 00 00 00 00 00
+            "
+        );
+        let packet = Ipv6Packet::new_view(&buf).unwrap();
+        let (next_header, payload) = packet.upper_layer_payload().unwrap();
+        assert_eq!(IpProtocol::Other(59), next_header);
+        assert_eq!(&buf[40..], payload);
+    }
+
+    #[test]
+    fn test_upper_layer_payload_truncated() {
+        let buf = hex_literal::hex!(
+            "
+            60 00 00 00 00 10 00 40 fe 80 00 00 00 00 00 00
+            00 00 00 00 00 00 00 01 fe 80 00 00 00 00 00 00
+            00 00 00 00 00 00 00 02 3c 00 01 04 00 00 00 00
+            11 01 01 04 00 00 00 00
+            "
+        );
+        let packet = Ipv6Packet::new_view(&buf).unwrap();
+        let err = packet.upper_layer_payload().unwrap_err();
+        assert_eq!(
+            Error::Truncated {
+                required: 16,
+                actual: 8
+            },
+            err
+        );
+        let packet = Ipv6Packet::new_view(&buf[..49]).unwrap();
+        let err = packet.upper_layer_payload().unwrap_err();
+        assert_eq!(
+            Error::Truncated {
+                required: 2,
+                actual: 1
+            },
+            err
+        );
+        let mut buf = buf;
+        buf[40] = NEXT_HEADER_FRAGMENT;
+        let packet = Ipv6Packet::new_view(&buf[..52]).unwrap();
+        let err = packet.upper_layer_payload().unwrap_err();
+        assert_eq!(
+            Error::Truncated {
+                required: 8,
+                actual: 4
+            },
+            err
+        );
+    }
+
     #[test]
     fn test_new_insufficient_buffer() {
         const SIZE: usize = Ipv6Packet::minimum_packet_size();
@@ -382,6 +533,21 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_new_view_boundary_lengths() {
+        const SIZE: usize = Ipv6Packet::minimum_packet_size();
+        let buf = [0_u8; SIZE];
+        for len in 0..SIZE {
+            let err = Ipv6Packet::new_view(&buf[..len]).unwrap_err();
+            assert_eq!(
+                Error::InsufficientPacketBuffer(String::from("Ipv6Packet"), SIZE, len),
+                err
+            );
+        }
+        let packet = Ipv6Packet::new_view(&buf).unwrap();
+        assert!(!format!("{packet:?}").is_empty());
+    }
+
     #[test]
     fn test_new_view_insufficient_buffer() {
         const SIZE: usize = Ipv6Packet::minimum_packet_size();
@@ -37,6 +37,10 @@ impl<'a> TcpPacket<'a> {
         }
     }
 
+    /// Create a view of a `TcpPacket`.
+    ///
+    /// The data offset is not validated against the size of the buffer as the `TCP` header quoted
+    /// in an ICMP error is often truncated; the options and payload are bounded by the buffer.
     pub fn new_view(packet: &[u8]) -> Result<TcpPacket<'_>> {
         if packet.len() >= Self::minimum_packet_size() {
             Ok(TcpPacket {
@@ -456,6 +460,21 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_new_view_boundary_lengths() {
+        const SIZE: usize = TcpPacket::minimum_packet_size();
+        let buf = [0_u8; SIZE];
+        for len in 0..SIZE {
+            let err = TcpPacket::new_view(&buf[..len]).unwrap_err();
+            assert_eq!(
+                Error::InsufficientPacketBuffer(String::from("TcpPacket"), SIZE, len),
+                err
+            );
+        }
+        let packet = TcpPacket::new_view(&buf).unwrap();
+        assert!(!format!("{packet:?}").is_empty());
+    }
+
     #[test]
     fn test_new_view_insufficient_buffer() {
         const SIZE: usize = TcpPacket::minimum_packet_size();
@@ -466,4 +485,19 @@ mod tests {
             err
         );
     }
+
+    #[test]
+    fn test_view_data_offset_exceeds_buffer() {
+        let mut buf = [0_u8; TcpPacket::minimum_packet_size() + 4];
+        buf[12] = 0xf0;
+        for len in TcpPacket::minimum_packet_size()..=buf.len() {
+            let packet = TcpPacket::new_view(&buf[..len]).unwrap();
+            assert_eq!(15, packet.get_data_offset());
+            assert_eq!(
+                len - TcpPacket::minimum_packet_size(),
+                packet.get_options_raw().len()
+            );
+            assert!(packet.payload().is_empty());
+        }
+    }
 }
@@ -54,13 +54,17 @@ fn start_tracer(
 ) -> Result<TraceInfo, Error> {
     let (tracer, _) = Builder::new(target_addr)
         .interface(cfg.interface.clone())
+        .capture_file(cfg.capture_file.clone())
         .source_addr(cfg.source_addr)
         .privilege_mode(cfg.privilege_mode)
         .protocol(cfg.protocol)
         .packet_size(cfg.packet_size)
+        .max_packet_size(cfg.max_packet_size)
         .payload_pattern(cfg.payload_pattern)
         .tos(cfg.tos)
+        .dont_fragment(cfg.dont_fragment)
         .icmp_extension_parse_mode(cfg.icmp_extension_parse_mode)
+        .ipv6_extension_header(cfg.ipv6_extension_header)
         .read_timeout(cfg.read_timeout)
         .tcp_connect_timeout(cfg.min_round_duration)
         .trace_identifier(trace_identifier)
@@ -70,6 +74,7 @@ fn start_tracer(
         .grace_duration(cfg.grace_duration)
         .max_inflight(cfg.max_inflight)
         .initial_sequence(cfg.initial_sequence)
+        .max_sequence(cfg.max_sequence)
         .multipath_strategy(cfg.multipath_strategy)
         .port_direction(cfg.port_direction)
         .min_round_duration(cfg.min_round_duration)
@@ -93,7 +98,12 @@ fn run_frontend(
         Mode::Tui => frontend::run_frontend(traces, make_tui_config(args), resolver, geoip_lookup)?,
         Mode::Stream => report::stream::report(&traces[0], &resolver)?,
         Mode::Csv => report::csv::report(&traces[0], args.report_cycles, &resolver)?,
-        Mode::Json => report::json::report(&traces[0], args.report_cycles, &resolver)?,
+        Mode::Json => report::json::report(
+            &traces[0],
+            args.report_cycles,
+            args.report_responders,
+            &resolver,
+        )?,
         Mode::Pretty => report::table::report_pretty(&traces[0], args.report_cycles, &resolver)?,
         Mode::Markdown => report::table::report_md(&traces[0], args.report_cycles, &resolver)?,
         Mode::Dot => report::dot::report(&traces[0], args.report_cycles)?,
@@ -205,6 +215,7 @@ fn make_tui_config(args: &TrippyConfig) -> TuiConfig {
         &args.tui_custom_columns,
         args.geoip_mmdb_file.clone(),
         args.dns_resolve_all,
+        args.dns_retain_cache,
     )
 }
 
@@ -8,8 +8,8 @@ use std::collections::HashMap;
 use std::net::IpAddr;
 use std::time::Duration;
 use trippy_core::{
-    defaults, IcmpExtensionParseMode, MultipathStrategy, PortDirection, PrivilegeMode, Protocol,
-    MAX_TTL,
+    defaults, IcmpExtensionParseMode, Ipv6ExtensionHeader, MultipathStrategy, PortDirection,
+    PrivilegeMode, Protocol, MAX_SEQUENCE_PER_ROUND, MAX_TTL,
 };
 use trippy_dns::{IpAddrFamily, ResolveMethod};
 
@@ -122,6 +122,28 @@ impl From<MultipathStrategy> for MultipathStrategyConfig {
     }
 }
 
+/// The IPv6 extension header to insert into probes.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Ipv6ExtensionHeaderConfig {
+    /// Do not insert an extension header.
+    None,
+    /// Insert a Hop-by-Hop Options extension header.
+    HopByHop,
+    /// Insert a Destination Options extension header.
+    DestinationOptions,
+}
+
+impl From<Ipv6ExtensionHeader> for Ipv6ExtensionHeaderConfig {
+    fn from(value: Ipv6ExtensionHeader) -> Self {
+        match value {
+            Ipv6ExtensionHeader::None => Self::None,
+            Ipv6ExtensionHeader::HopByHop => Self::HopByHop,
+            Ipv6ExtensionHeader::DestinationOptions => Self::DestinationOptions,
+        }
+    }
+}
+
 /// How to render the addresses.
 #[derive(Debug, Copy, Clone, Eq, PartialEq, ValueEnum, Deserialize)]
 #[serde(rename_all = "kebab-case")]
@@ -289,10 +311,15 @@ pub struct TrippyConfig {
     pub grace_duration: Duration,
     pub max_inflight: u8,
     pub initial_sequence: u16,
+    pub max_sequence: u16,
     pub tos: u8,
+    pub dont_fragment: bool,
     pub icmp_extension_parse_mode: IcmpExtensionParseMode,
+    pub ipv6_extension_header: Ipv6ExtensionHeader,
     pub read_timeout: Duration,
+    pub capture_file: Option<String>,
     pub packet_size: u16,
+    pub max_packet_size: u16,
     pub payload_pattern: u8,
     pub source_addr: Option<IpAddr>,
     pub interface: Option<String>,
@@ -301,6 +328,7 @@ pub struct TrippyConfig {
     pub dns_timeout: Duration,
     pub dns_resolve_method: ResolveMethod,
     pub dns_lookup_as_info: bool,
+    pub dns_retain_cache: bool,
     pub max_samples: usize,
     pub max_flows: usize,
     pub tui_preserve_screen: bool,
@@ -318,6 +346,7 @@ pub struct TrippyConfig {
     pub privilege_mode: PrivilegeMode,
     pub dns_resolve_all: bool,
     pub report_cycles: usize,
+    pub report_responders: bool,
     pub geoip_mmdb_file: Option<String>,
     pub max_rounds: Option<usize>,
     pub verbose: bool,
@@ -407,6 +436,8 @@ impl TrippyConfig {
         );
         let target_port = cfg_layer_opt(args.target_port, cfg_file_strategy.target_port);
         let source_port = cfg_layer_opt(args.source_port, cfg_file_strategy.source_port);
+        let source_port_range =
+            cfg_layer_opt(args.source_port_range, cfg_file_strategy.source_port_range);
         let source_addr = cfg_layer_opt(args.source_address, cfg_file_strategy.source_address);
         let interface = cfg_layer_opt(args.interface, cfg_file_strategy.interface);
         let min_round_duration = cfg_layer(
@@ -424,6 +455,11 @@ impl TrippyConfig {
             cfg_file_strategy.initial_sequence,
             defaults::DEFAULT_STRATEGY_INITIAL_SEQUENCE,
         );
+        let max_sequence = cfg_layer(
+            args.max_sequence,
+            cfg_file_strategy.max_sequence,
+            defaults::DEFAULT_STRATEGY_MAX_SEQUENCE,
+        );
         let multipath_strategy_cfg = cfg_layer(
             args.multipath_strategy,
             cfg_file_strategy.multipath_strategy,
@@ -454,6 +490,11 @@ impl TrippyConfig {
             cfg_file_strategy.packet_size,
             defaults::DEFAULT_STRATEGY_PACKET_SIZE,
         );
+        let max_packet_size = cfg_layer(
+            args.max_packet_size,
+            cfg_file_strategy.max_packet_size,
+            defaults::DEFAULT_MAX_PACKET_SIZE,
+        );
         let payload_pattern = cfg_layer(
             args.payload_pattern,
             cfg_file_strategy.payload_pattern,
@@ -464,6 +505,11 @@ impl TrippyConfig {
             cfg_file_strategy.tos,
             defaults::DEFAULT_STRATEGY_TOS,
         );
+        let dont_fragment = cfg_layer_bool_flag(
+            args.dont_fragment,
+            cfg_file_strategy.dont_fragment,
+            defaults::DEFAULT_STRATEGY_DONT_FRAGMENT,
+        );
         let icmp_extensions = cfg_layer_bool_flag(
             args.icmp_extensions,
             cfg_file_strategy.icmp_extensions,
@@ -474,11 +520,23 @@ impl TrippyConfig {
         } else {
             IcmpExtensionParseMode::Disabled
         };
+        let ipv6_extension_header = match cfg_layer(
+            args.ipv6_extension_header,
+            cfg_file_strategy.ipv6_extension_header,
+            Ipv6ExtensionHeaderConfig::from(defaults::DEFAULT_IPV6_EXTENSION_HEADER),
+        ) {
+            Ipv6ExtensionHeaderConfig::None => Ipv6ExtensionHeader::None,
+            Ipv6ExtensionHeaderConfig::HopByHop => Ipv6ExtensionHeader::HopByHop,
+            Ipv6ExtensionHeaderConfig::DestinationOptions => {
+                Ipv6ExtensionHeader::DestinationOptions
+            }
+        };
         let read_timeout = cfg_layer(
             args.read_timeout,
             cfg_file_strategy.read_timeout,
             defaults::DEFAULT_STRATEGY_READ_TIMEOUT,
         );
+        let capture_file = cfg_layer_opt(args.capture_file, cfg_file_strategy.capture_file);
         let max_samples = cfg_layer(
             args.max_samples,
             cfg_file_strategy.max_samples,
@@ -546,11 +604,17 @@ impl TrippyConfig {
             cfg_file_dns.dns_timeout,
             constants::DEFAULT_DNS_TIMEOUT,
         );
+        let dns_retain_cache = cfg_file_dns
+            .dns_retain_cache
+            .unwrap_or(constants::DEFAULT_DNS_RETAIN_CACHE);
         let report_cycles = cfg_layer(
             args.report_cycles,
             cfg_file_report.report_cycles,
             constants::DEFAULT_REPORT_CYCLES,
         );
+        let report_responders = cfg_file_report
+            .report_responders
+            .unwrap_or(constants::DEFAULT_REPORT_RESPONDERS);
         let geoip_mmdb_file = cfg_layer_opt(args.geoip_mmdb_file, cfg_file_tui.geoip_mmdb_file);
         let protocol = match (args.udp, args.tcp, args.icmp, protocol) {
             (false, false, false, ProtocolConfig::Udp) | (true, _, _, _) => Protocol::Udp,
@@ -593,21 +657,25 @@ impl TrippyConfig {
             (Protocol::Tcp, None, None, _) => PortDirection::new_fixed_dest(80),
             (Protocol::Tcp, Some(src), None, _) => PortDirection::new_fixed_src(src),
             (_, None, Some(dest), _) => PortDirection::new_fixed_dest(dest),
-            (
-                Protocol::Udp,
-                Some(src),
-                Some(dest),
-                MultipathStrategyConfig::Dublin | MultipathStrategyConfig::Paris,
-            ) => {
+            (Protocol::Udp, Some(_), Some(_), MultipathStrategyConfig::Classic) if unprivileged => {
+                return Err(anyhow!(
+                    "source-port and target-port may not both be fixed for udp protocol with classic strategy in unprivileged mode"
+                ));
+            }
+            (Protocol::Udp, Some(src), Some(dest), _) => {
                 validate_source_port(src)?;
                 PortDirection::new_fixed_both(src, dest)
             }
             (_, Some(_), Some(_), _) => {
                 return Err(anyhow!(
-                    "only one of source-port and target-port may be fixed (except IPv4/udp protocol with dublin or paris strategy)"
+                    "only one of source-port and target-port may be fixed (except udp protocol)"
                 ));
             }
         };
+        let (initial_sequence, max_sequence) = match source_port_range {
+            Some(range) => parse_source_port_range(&range, port_direction)?,
+            None => (initial_sequence, max_sequence),
+        };
         let dns_resolve_method = match dns_resolve_method_config {
             DnsResolveMethodConfig::System => ResolveMethod::System,
             DnsResolveMethodConfig::Resolv => ResolveMethod::Resolv,
@@ -639,7 +707,8 @@ impl TrippyConfig {
         validate_read_timeout(read_timeout)?;
         validate_round_duration(min_round_duration, max_round_duration)?;
         validate_grace_duration(grace_duration)?;
-        validate_packet_size(addr_family, packet_size)?;
+        validate_packet_size(addr_family, packet_size, max_packet_size)?;
+        validate_ipv6_extension_header(protocol, addr_family, ipv6_extension_header)?;
         validate_tui_refresh_rate(tui_refresh_rate)?;
         validate_report_cycles(report_cycles)?;
         validate_dns(dns_resolve_method, dns_lookup_as_info)?;
@@ -667,18 +736,24 @@ impl TrippyConfig {
             grace_duration,
             max_inflight,
             initial_sequence,
+            max_sequence,
             multipath_strategy,
             read_timeout,
+            capture_file,
             packet_size,
+            max_packet_size,
             payload_pattern,
             tos,
+            dont_fragment,
             icmp_extension_parse_mode,
+            ipv6_extension_header,
             source_addr,
             interface,
             port_direction,
             dns_timeout,
             dns_resolve_method,
             dns_lookup_as_info,
+            dns_retain_cache,
             max_samples,
             max_flows,
             tui_preserve_screen,
@@ -696,6 +771,7 @@ impl TrippyConfig {
             privilege_mode,
             dns_resolve_all,
             report_cycles,
+            report_responders,
             geoip_mmdb_file,
             max_rounds,
             verbose,
@@ -719,10 +795,15 @@ impl Default for TrippyConfig {
             grace_duration: defaults::DEFAULT_STRATEGY_GRACE_DURATION,
             max_inflight: defaults::DEFAULT_STRATEGY_MAX_INFLIGHT,
             initial_sequence: defaults::DEFAULT_STRATEGY_INITIAL_SEQUENCE,
+            max_sequence: defaults::DEFAULT_STRATEGY_MAX_SEQUENCE,
             tos: defaults::DEFAULT_STRATEGY_TOS,
+            dont_fragment: defaults::DEFAULT_STRATEGY_DONT_FRAGMENT,
             icmp_extension_parse_mode: defaults::DEFAULT_ICMP_EXTENSION_PARSE_MODE,
+            ipv6_extension_header: defaults::DEFAULT_IPV6_EXTENSION_HEADER,
             read_timeout: defaults::DEFAULT_STRATEGY_READ_TIMEOUT,
+            capture_file: None,
             packet_size: defaults::DEFAULT_STRATEGY_PACKET_SIZE,
+            max_packet_size: defaults::DEFAULT_MAX_PACKET_SIZE,
             payload_pattern: defaults::DEFAULT_STRATEGY_PAYLOAD_PATTERN,
             source_addr: None,
             interface: None,
@@ -731,6 +812,7 @@ impl Default for TrippyConfig {
             dns_timeout: constants::DEFAULT_DNS_TIMEOUT,
             dns_resolve_method: dns_resolve_method(constants::DEFAULT_DNS_RESOLVE_METHOD),
             dns_lookup_as_info: constants::DEFAULT_DNS_LOOKUP_AS_INFO,
+            dns_retain_cache: constants::DEFAULT_DNS_RETAIN_CACHE,
             max_samples: defaults::DEFAULT_MAX_SAMPLES,
             max_flows: defaults::DEFAULT_MAX_FLOWS,
             tui_preserve_screen: constants::DEFAULT_TUI_PRESERVE_SCREEN,
@@ -747,6 +829,7 @@ impl Default for TrippyConfig {
             privilege_mode: defaults::DEFAULT_PRIVILEGE_MODE,
             dns_resolve_all: constants::DEFAULT_DNS_RESOLVE_ALL,
             report_cycles: constants::DEFAULT_REPORT_CYCLES,
+            report_responders: constants::DEFAULT_REPORT_RESPONDERS,
             geoip_mmdb_file: None,
             max_rounds: None,
             verbose: false,
@@ -1008,27 +1091,46 @@ fn validate_grace_duration(grace_duration: Duration) -> anyhow::Result<()> {
     }
 }
 
-/// Validate `packet_size`.
-fn validate_packet_size(address_family: IpAddrFamily, packet_size: u16) -> anyhow::Result<()> {
+/// Validate `packet_size` is at least the minimum for the `address_family` and at most
+/// `max_packet_size`.
+fn validate_packet_size(
+    address_family: IpAddrFamily,
+    packet_size: u16,
+    max_packet_size: u16,
+) -> anyhow::Result<()> {
     let min_size = match address_family {
         IpAddrFamily::Ipv4Only => constants::MIN_PACKET_SIZE_IPV4,
         IpAddrFamily::Ipv6Only | IpAddrFamily::Ipv6thenIpv4 | IpAddrFamily::Ipv4thenIpv6 => {
             constants::MIN_PACKET_SIZE_IPV6
         }
     };
-    if (min_size..=constants::MAX_PACKET_SIZE).contains(&packet_size) {
+    if (min_size..=max_packet_size).contains(&packet_size) {
         Ok(())
     } else {
         Err(anyhow!(
-            "packet-size ({}) must be between {} and {} inclusive for {}",
-            packet_size,
-            min_size,
-            constants::MAX_PACKET_SIZE,
-            address_family,
+            "packet-size ({packet_size}) must be between {min_size} and {max_packet_size} inclusive for {address_family}"
         ))
     }
 }
 
+/// Validate `ipv6_extension_header`.
+fn validate_ipv6_extension_header(
+    protocol: Protocol,
+    address_family: IpAddrFamily,
+    extension_header: Ipv6ExtensionHeader,
+) -> anyhow::Result<()> {
+    match (protocol, address_family, extension_header) {
+        (_, _, Ipv6ExtensionHeader::None) => Ok(()),
+        (Protocol::Tcp, _, _) => Err(anyhow!(
+            "ipv6-extension-header ({extension_header}) may not be used for tcp protocol"
+        )),
+        (_, IpAddrFamily::Ipv4Only, _) => Err(anyhow!(
+            "ipv6-extension-header ({extension_header}) may not be used for {address_family}"
+        )),
+        _ => Ok(()),
+    }
+}
+
 /// Validate `source_port`.
 fn validate_source_port(source_port: u16) -> anyhow::Result<()> {
     if source_port < 1024 {
@@ -1038,6 +1140,51 @@ fn validate_source_port(source_port: u16) -> anyhow::Result<()> {
     }
 }
 
+/// Parse and validate `source_port_range`.
+///
+/// The range is given as `MIN-MAX` and is returned as the initial and maximum sequence numbers, as
+/// the sequence number is stored in the source port when the target port is fixed.
+fn parse_source_port_range(
+    source_port_range: &str,
+    port_direction: PortDirection,
+) -> anyhow::Result<(u16, u16)> {
+    let (min, max) = source_port_range
+        .split_once('-')
+        .and_then(|(min, max)| {
+            Some((
+                min.trim().parse::<u16>().ok()?,
+                max.trim().parse::<u16>().ok()?,
+            ))
+        })
+        .ok_or_else(|| {
+            anyhow!("source-port-range ({source_port_range}) must be of the form MIN-MAX")
+        })?;
+    let min_ports = MAX_SEQUENCE_PER_ROUND * 2;
+    if min < 1024 {
+        return Err(anyhow!(
+            "source-port-range ({source_port_range}) must be >= 1024"
+        ));
+    }
+    if min > max || max - min < min_ports - 1 {
+        return Err(anyhow!(
+            "source-port-range ({source_port_range}) must contain at least {min_ports} ports"
+        ));
+    }
+    match port_direction {
+        PortDirection::FixedDest(dest) if (min..=max).contains(&dest.0) => Err(anyhow!(
+            "source-port-range ({source_port_range}) may not contain the target-port ({})",
+            dest.0
+        )),
+        PortDirection::FixedDest(_) => Ok((min, max)),
+        PortDirection::None => Err(anyhow!(
+            "source-port-range ({source_port_range}) may not be used for icmp protocol"
+        )),
+        PortDirection::FixedSrc(_) | PortDirection::FixedBoth(_, _) => Err(anyhow!(
+            "source-port-range ({source_port_range}) may only be used with a fixed target-port"
+        )),
+    }
+}
+
 /// Validate `tui_refresh_rate`.
 fn validate_tui_refresh_rate(tui_refresh_rate: Duration) -> anyhow::Result<()> {
     if tui_refresh_rate < constants::TUI_MIN_REFRESH_RATE_MS
@@ -1239,11 +1386,31 @@ mod tests {
     #[test_case("trip example.com --udp --multipath-strategy dublin --source-port 33000", Ok(cfg().protocol(Protocol::Udp).multipath_strategy(MultipathStrategy::Dublin).addr_family(IpAddrFamily::Ipv4Only).port_direction(PortDirection::FixedSrc(Port(33000))).build()); "udp protocol dublin strategy custom src port")]
     #[test_case("trip example.com --udp --multipath-strategy dublin --target-port 5000", Ok(cfg().protocol(Protocol::Udp).multipath_strategy(MultipathStrategy::Dublin).addr_family(IpAddrFamily::Ipv4Only).port_direction(PortDirection::FixedDest(Port(5000))).build()); "udp protocol dublin strategy custom target port")]
     #[test_case("trip example.com --udp --multipath-strategy dublin --source-port 33000 --target-port 5000", Ok(cfg().protocol(Protocol::Udp).multipath_strategy(MultipathStrategy::Dublin).addr_family(IpAddrFamily::Ipv4Only).port_direction(PortDirection::FixedBoth(Port(33000), Port(5000))).build()); "udp protocol dublin strategy custom both ports")]
-    #[test_case("trip example.com --udp --source-port 33000 --target-port 5000", Err(anyhow!("only one of source-port and target-port may be fixed (except IPv4/udp protocol with dublin or paris strategy)")); "udp protocol custom both ports with invalid strategy")]
+    #[test_case("trip example.com --udp --source-port 33000 --target-port 5000", Ok(cfg().protocol(Protocol::Udp).port_direction(PortDirection::FixedBoth(Port(33000), Port(5000))).build()); "udp protocol classic strategy custom both ports")]
+    #[test_case("trip example.com --udp --unprivileged --source-port 33000 --target-port 5000", Err(anyhow!("source-port and target-port may not both be fixed for udp protocol with classic strategy in unprivileged mode")); "udp protocol classic strategy custom both ports unprivileged")]
+    #[test_case("trip example.com --tcp --source-port 33000 --target-port 5000", Err(anyhow!("only one of source-port and target-port may be fixed (except udp protocol)")); "tcp protocol custom both ports")]
     fn test_ports(cmd: &str, expected: anyhow::Result<TrippyConfig>) {
         compare(parse_config(cmd), expected);
     }
 
+    #[test_case("trip example.com --udp --target-port 33434 --source-port-range 40000-41023", Ok(cfg().protocol(Protocol::Udp).port_direction(PortDirection::FixedDest(Port(33434))).initial_sequence(40000).max_sequence(41023).build()); "udp protocol source port range")]
+    #[test_case("trip example.com --tcp --source-port-range 40000-49999", Ok(cfg().protocol(Protocol::Tcp).port_direction(PortDirection::FixedDest(Port(80))).initial_sequence(40000).max_sequence(49999).build()); "tcp protocol source port range")]
+    #[test_case("trip example.com --udp --multipath-strategy paris --target-port 33434 --source-port-range 40000-41023", Ok(cfg().protocol(Protocol::Udp).multipath_strategy(MultipathStrategy::Paris).port_direction(PortDirection::FixedDest(Port(33434))).initial_sequence(40000).max_sequence(41023).build()); "udp protocol paris strategy source port range")]
+    #[test_case("trip example.com --tcp --source-port-range 40000", Err(anyhow!("source-port-range (40000) must be of the form MIN-MAX")); "source port range missing max")]
+    #[test_case("trip example.com --tcp --source-port-range 40000-foo", Err(anyhow!("source-port-range (40000-foo) must be of the form MIN-MAX")); "source port range invalid max")]
+    #[test_case("trip example.com --tcp --source-port-range 40000-70000", Err(anyhow!("source-port-range (40000-70000) must be of the form MIN-MAX")); "source port range max out of range")]
+    #[test_case("trip example.com --tcp --source-port-range 1000-9999", Err(anyhow!("source-port-range (1000-9999) must be >= 1024")); "source port range privileged")]
+    #[test_case("trip example.com --tcp --source-port-range 40000-40999", Err(anyhow!("source-port-range (40000-40999) must contain at least 1024 ports")); "source port range too small")]
+    #[test_case("trip example.com --tcp --source-port-range 49999-40000", Err(anyhow!("source-port-range (49999-40000) must contain at least 1024 ports")); "source port range reversed")]
+    #[test_case("trip example.com --tcp --target-port 45000 --source-port-range 40000-49999", Err(anyhow!("source-port-range (40000-49999) may not contain the target-port (45000)")); "source port range overlaps target port")]
+    #[test_case("trip example.com --source-port-range 40000-49999", Err(anyhow!("source-port-range (40000-49999) may not be used for icmp protocol")); "source port range icmp protocol")]
+    #[test_case("trip example.com --udp --source-port-range 40000-49999", Err(anyhow!("source-port-range (40000-49999) may only be used with a fixed target-port")); "source port range udp protocol without target port")]
+    #[test_case("trip example.com --udp --source-port 2222 --source-port-range 40000-49999", Err(anyhow!("error: the argument '--source-port <SOURCE_PORT>' cannot be used with '--source-port-range <SOURCE_PORT_RANGE>' Usage: trip --udp --source-port <SOURCE_PORT> <TARGETS>... For more information, try '--help'.")); "source port range with source port")]
+    #[test_case("trip example.com --tcp --initial-sequence 40000 --source-port-range 40000-49999", Err(anyhow!("error: the argument '--initial-sequence <INITIAL_SEQUENCE>' cannot be used with '--source-port-range <SOURCE_PORT_RANGE>' Usage: trip --tcp --initial-sequence <INITIAL_SEQUENCE> <TARGETS>... For more information, try '--help'.")); "source port range with initial sequence")]
+    fn test_source_port_range(cmd: &str, expected: anyhow::Result<TrippyConfig>) {
+        compare(parse_config(cmd), expected);
+    }
+
     #[test_case("trip example.com", Ok(cfg().addr_family(IpAddrFamily::Ipv4thenIpv6).build()); "default address family")]
     #[test_case("trip example.com --addr-family ipv4", Ok(cfg().addr_family(IpAddrFamily::Ipv4Only).build()); "ipv4 address family")]
     #[test_case("trip example.com --addr-family ipv6", Ok(cfg().addr_family(IpAddrFamily::Ipv6Only).build()); "ipv6 address family")]
@@ -1311,6 +1478,8 @@ mod tests {
     #[test_case("trip example.com", Ok(cfg().initial_sequence(33000).build()); "default initial sequence")]
     #[test_case("trip example.com --initial-sequence 5000", Ok(cfg().initial_sequence(5000).build()); "custom initial sequence")]
     #[test_case("trip example.com --initial-sequence foo", Err(anyhow!("error: invalid value 'foo' for '--initial-sequence <INITIAL_SEQUENCE>': invalid digit found in string For more information, try '--help'. ")); "invalid format initial sequence")]
+    #[test_case("trip example.com", Ok(cfg().max_sequence(65535).build()); "default max sequence")]
+    #[test_case("trip example.com --max-sequence 40000", Ok(cfg().max_sequence(40000).build()); "custom max sequence")]
     #[test_case("trip example.com --initial-sequence 100000", Err(anyhow!("error: invalid value '100000' for '--initial-sequence <INITIAL_SEQUENCE>': 100000 is not in 0..=65535 For more information, try '--help'.")); "invalid high initial sequence")]
     fn test_init_seq(cmd: &str, expected: anyhow::Result<TrippyConfig>) {
         compare(parse_config(cmd), expected);
@@ -1325,6 +1494,13 @@ mod tests {
         compare(parse_config(cmd), expected);
     }
 
+    #[test_case("trip example.com", Ok(cfg().dont_fragment(false).build()); "default dont fragment")]
+    #[test_case("trip example.com --dont-fragment", Ok(cfg().dont_fragment(true).build()); "enabled dont fragment")]
+    #[test_case("trip example.com --udp --dont-fragment", Ok(cfg().protocol(Protocol::Udp).port_direction(PortDirection::FixedSrc(Port(1024))).dont_fragment(true).build()); "enabled dont fragment udp")]
+    fn test_dont_fragment(cmd: &str, expected: anyhow::Result<TrippyConfig>) {
+        compare(parse_config(cmd), expected);
+    }
+
     #[test_case("trip example.com", Ok(cfg().icmp_extension_parse_mode(IcmpExtensionParseMode::Disabled).build()); "default icmp extensions")]
     #[test_case("trip example.com --icmp-extensions", Ok(cfg().icmp_extension_parse_mode(IcmpExtensionParseMode::Enabled).build()); "enabled icmp extensions")]
     #[test_case("trip example.com -e", Ok(cfg().icmp_extension_parse_mode(IcmpExtensionParseMode::Enabled).build()); "enabled icmp extensions short")]
@@ -1332,6 +1508,16 @@ mod tests {
         compare(parse_config(cmd), expected);
     }
 
+    #[test_case("trip example.com", Ok(cfg().ipv6_extension_header(Ipv6ExtensionHeader::None).build()); "default ipv6 extension header")]
+    #[test_case("trip example.com --ipv6-extension-header hop-by-hop", Ok(cfg().ipv6_extension_header(Ipv6ExtensionHeader::HopByHop).build()); "hop-by-hop ipv6 extension header")]
+    #[test_case("trip example.com --udp --ipv6-extension-header destination-options", Ok(cfg().protocol(Protocol::Udp).port_direction(PortDirection::FixedSrc(Port(1024))).ipv6_extension_header(Ipv6ExtensionHeader::DestinationOptions).build()); "destination options ipv6 extension header")]
+    #[test_case("trip example.com --tcp --ipv6-extension-header hop-by-hop", Err(anyhow!("ipv6-extension-header (hop-by-hop) may not be used for tcp protocol")); "tcp ipv6 extension header")]
+    #[test_case("trip example.com -4 --ipv6-extension-header hop-by-hop", Err(anyhow!("ipv6-extension-header (hop-by-hop) may not be used for Ipv4Only")); "ipv4 ipv6 extension header")]
+    #[test_case("trip example.com --ipv6-extension-header foo", Err(anyhow!("error: invalid value 'foo' for '--ipv6-extension-header <IPV6_EXTENSION_HEADER>' [possible values: none, hop-by-hop, destination-options] For more information, try '--help'.")); "invalid ipv6 extension header")]
+    fn test_ipv6_extension_header(cmd: &str, expected: anyhow::Result<TrippyConfig>) {
+        compare(parse_config(cmd), expected);
+    }
+
     #[test_case("trip example.com", Ok(cfg().read_timeout(Duration::from_millis(10)).build()); "default read timeout")]
     #[test_case("trip example.com --read-timeout 20ms", Ok(cfg().read_timeout(Duration::from_millis(20)).build()); "custom read timeout")]
     #[test_case("trip example.com --read-timeout 20", Err(anyhow!("error: invalid value '20' for '--read-timeout <READ_TIMEOUT>': time unit needed, for example 20sec or 20ms For more information, try '--help'.")); "invalid custom read timeout")]
@@ -1350,6 +1536,8 @@ mod tests {
     #[test_case("trip example.com --packet-size 1025 -F ipv4", Err(anyhow!("packet-size (1025) must be between 28 and 1024 inclusive for Ipv4Only")); "invalid high packet size for ipv4")]
     #[test_case("trip example.com --packet-size 47 -F ipv6", Err(anyhow!("packet-size (47) must be between 48 and 1024 inclusive for Ipv6Only")); "invalid low packet size for ipv6")]
     #[test_case("trip example.com --packet-size 1025 -F ipv6", Err(anyhow!("packet-size (1025) must be between 48 and 1024 inclusive for Ipv6Only")); "invalid high packet size for ipv6")]
+    #[test_case("trip example.com --packet-size 9000 --max-packet-size 9000", Ok(cfg().packet_size(9000).max_packet_size(9000).build()); "custom jumbo packet size")]
+    #[test_case("trip example.com --packet-size 9001 --max-packet-size 9000 -F ipv4", Err(anyhow!("packet-size (9001) must be between 28 and 9000 inclusive for Ipv4Only")); "invalid high packet size for custom max packet size")]
     #[test_case("trip example.com --packet-size 100000", Err(anyhow!("error: invalid value '100000' for '--packet-size <PACKET_SIZE>': 100000 is not in 0..=65535 For more information, try '--help'.")); "invalid out of range packet size")]
     fn test_packet_size(cmd: &str, expected: anyhow::Result<TrippyConfig>) {
         compare(parse_config(cmd), expected);
@@ -1379,6 +1567,12 @@ mod tests {
         compare(parse_config(cmd), expected);
     }
 
+    #[test_case("trip example.com", Ok(cfg().capture_file(None).build()); "default capture file")]
+    #[test_case("trip example.com --capture-file trace.pcap", Ok(cfg().capture_file(Some(String::from("trace.pcap"))).build()); "custom capture file")]
+    fn test_capture_file(cmd: &str, expected: anyhow::Result<TrippyConfig>) {
+        compare(parse_config(cmd), expected);
+    }
+
     #[test_case("trip example.com", Ok(cfg().dns_timeout(Duration::from_millis(5000)).build()); "default dns timeout")]
     #[test_case("trip example.com --dns-timeout 20ms", Ok(cfg().dns_timeout(Duration::from_millis(20)).build()); "custom dns timeout")]
     #[test_case("trip example.com --dns-timeout 20", Err(anyhow!("error: invalid value '20' for '--dns-timeout <DNS_TIMEOUT>': time unit needed, for example 20sec or 20ms For more information, try '--help'.")); "invalid custom dns timeout")]
@@ -1854,12 +2048,30 @@ mod tests {
             }
         }
 
+        pub fn max_sequence(self, max_sequence: u16) -> Self {
+            Self {
+                config: TrippyConfig {
+                    max_sequence,
+                    ..self.config
+                },
+            }
+        }
+
         pub fn tos(self, tos: u8) -> Self {
             Self {
                 config: TrippyConfig { tos, ..self.config },
             }
         }
 
+        pub fn dont_fragment(self, dont_fragment: bool) -> Self {
+            Self {
+                config: TrippyConfig {
+                    dont_fragment,
+                    ..self.config
+                },
+            }
+        }
+
         pub fn icmp_extension_parse_mode(
             self,
             icmp_extension_parse_mode: IcmpExtensionParseMode,
@@ -1872,6 +2084,15 @@ mod tests {
             }
         }
 
+        pub fn ipv6_extension_header(self, ipv6_extension_header: Ipv6ExtensionHeader) -> Self {
+            Self {
+                config: TrippyConfig {
+                    ipv6_extension_header,
+                    ..self.config
+                },
+            }
+        }
+
         pub fn read_timeout(self, read_timeout: Duration) -> Self {
             Self {
                 config: TrippyConfig {
@@ -1890,6 +2111,15 @@ mod tests {
             }
         }
 
+        pub fn max_packet_size(self, max_packet_size: u16) -> Self {
+            Self {
+                config: TrippyConfig {
+                    max_packet_size,
+                    ..self.config
+                },
+            }
+        }
+
         pub fn payload_pattern(self, payload_pattern: u8) -> Self {
             Self {
                 config: TrippyConfig {
@@ -1917,6 +2147,15 @@ mod tests {
             }
         }
 
+        pub fn capture_file(self, capture_file: Option<String>) -> Self {
+            Self {
+                config: TrippyConfig {
+                    capture_file,
+                    ..self.config
+                },
+            }
+        }
+
         pub fn port_direction(self, port_direction: PortDirection) -> Self {
             Self {
                 config: TrippyConfig {
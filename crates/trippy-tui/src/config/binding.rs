@@ -25,6 +25,7 @@ pub struct TuiBindings {
     pub toggle_freeze: TuiKeyBinding,
     pub toggle_chart: TuiKeyBinding,
     pub toggle_map: TuiKeyBinding,
+    pub toggle_responders: TuiKeyBinding,
     pub toggle_flows: TuiKeyBinding,
     pub toggle_privacy: TuiKeyBinding,
     pub expand_hosts: TuiKeyBinding,
@@ -62,6 +63,7 @@ impl Default for TuiBindings {
             ),
             toggle_chart: TuiKeyBinding::new(KeyCode::Char('c')),
             toggle_map: TuiKeyBinding::new(KeyCode::Char('m')),
+            toggle_responders: TuiKeyBinding::new(KeyCode::Char('r')),
             toggle_flows: TuiKeyBinding::new(KeyCode::Char('f')),
             toggle_privacy: TuiKeyBinding::new(KeyCode::Char('p')),
             expand_hosts: TuiKeyBinding::new(KeyCode::Char(']')),
@@ -110,6 +112,7 @@ impl TuiBindings {
             (self.toggle_freeze, TuiCommandItem::ToggleFreeze),
             (self.toggle_chart, TuiCommandItem::ToggleChart),
             (self.toggle_map, TuiCommandItem::ToggleMap),
+            (self.toggle_responders, TuiCommandItem::ToggleResponders),
             (self.toggle_flows, TuiCommandItem::ToggleFlows),
             (self.toggle_privacy, TuiCommandItem::TogglePrivacy),
             (self.expand_hosts, TuiCommandItem::ExpandHosts),
@@ -219,6 +222,10 @@ impl From<(HashMap<TuiCommandItem, TuiKeyBinding>, ConfigBindings)> for TuiBindi
                 .get(&TuiCommandItem::ToggleMap)
                 .or(cfg.toggle_map.as_ref())
                 .unwrap_or(&Self::default().toggle_map),
+            toggle_responders: *cmd_items
+                .get(&TuiCommandItem::ToggleResponders)
+                .or(cfg.toggle_responders.as_ref())
+                .unwrap_or(&Self::default().toggle_responders),
             expand_hosts: *cmd_items
                 .get(&TuiCommandItem::ExpandHosts)
                 .or(cfg.expand_hosts.as_ref())
@@ -520,6 +527,8 @@ pub enum TuiCommandItem {
     ToggleChart,
     /// Toggle the map.
     ToggleMap,
+    /// Toggle the responders table.
+    ToggleResponders,
     /// Toggle the flows panel.
     ToggleFlows,
     /// Toggle hop privacy mode.
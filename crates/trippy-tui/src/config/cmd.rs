@@ -2,8 +2,8 @@ use crate::config::binding::TuiCommandItem;
 use crate::config::theme::TuiThemeItem;
 use crate::config::{
     AddressFamilyConfig, AddressMode, AsMode, DnsResolveMethodConfig, GeoIpMode, IcmpExtensionMode,
-    LogFormat, LogSpanEvents, Mode, MultipathStrategyConfig, ProtocolConfig, TuiColor,
-    TuiKeyBinding,
+    Ipv6ExtensionHeaderConfig, LogFormat, LogSpanEvents, Mode, MultipathStrategyConfig,
+    ProtocolConfig, TuiColor, TuiKeyBinding,
 };
 use anyhow::anyhow;
 use clap::builder::Styles;
@@ -95,6 +95,15 @@ pub struct Args {
     #[arg(long, short = 'S')]
     pub source_port: Option<u16>,
 
+    /// The range of source ports to use, as MIN-MAX (TCP & UDP only) [default: none]
+    #[arg(
+        long,
+        conflicts_with = "source_port",
+        conflicts_with = "initial_sequence",
+        conflicts_with = "max_sequence"
+    )]
+    pub source_port_range: Option<String>,
+
     /// The source IP address [default: auto]
     #[arg(short = 'A', long, value_parser = parse_addr, conflicts_with = "interface")]
     pub source_address: Option<IpAddr>,
@@ -120,6 +129,10 @@ pub struct Args {
     #[arg(long)]
     pub initial_sequence: Option<u16>,
 
+    /// The maximum sequence number [default: 65535]
+    #[arg(long)]
+    pub max_sequence: Option<u16>,
+
     /// The Equal-cost Multi-Path routing strategy (UDP only) [default: classic]
     #[arg(value_enum, short = 'R', long)]
     pub multipath_strategy: Option<MultipathStrategyConfig>,
@@ -140,22 +153,38 @@ pub struct Args {
     #[arg(long)]
     pub packet_size: Option<u16>,
 
+    /// The maximum size of IP packet which may be sent [default: 1024]
+    #[arg(long)]
+    pub max_packet_size: Option<u16>,
+
     /// The repeating pattern in the payload of the ICMP packet [default: 0]
     #[arg(long)]
     pub payload_pattern: Option<u8>,
 
-    /// The TOS (i.e. DSCP+ECN) IP header value (TCP and UDP only) [default: 0]
+    /// The TOS (i.e. DSCP+ECN) IP header value [default: 0]
     #[arg(short = 'Q', long)]
     pub tos: Option<u8>,
 
+    /// Set the Don't Fragment bit on IPv4 probes
+    #[arg(long)]
+    pub dont_fragment: bool,
+
     /// Parse ICMP extensions
     #[arg(short = 'e', long)]
     pub icmp_extensions: bool,
 
+    /// The IPv6 extension header to insert into probes (ICMP and UDP only) [default: none]
+    #[arg(value_enum, long)]
+    pub ipv6_extension_header: Option<Ipv6ExtensionHeaderConfig>,
+
     /// The socket read timeout [default: 10ms]
     #[arg(long, value_parser = parse_duration)]
     pub read_timeout: Option<Duration>,
 
+    /// Write the probes sent and responses received to a pcap file [default: none]
+    #[arg(long)]
+    pub capture_file: Option<String>,
+
     /// How to perform DNS queries [default: system]
     #[arg(value_enum, short = 'r', long)]
     pub dns_resolve_method: Option<DnsResolveMethodConfig>,
@@ -58,9 +58,15 @@ pub const DEFAULT_DNS_LOOKUP_AS_INFO: bool = false;
 /// The default value for `dns-timeout`.
 pub const DEFAULT_DNS_TIMEOUT: Duration = Duration::from_millis(5000);
 
+/// The default value for `dns-retain-cache`.
+pub const DEFAULT_DNS_RETAIN_CACHE: bool = true;
+
 /// The default value for `report-cycles`.
 pub const DEFAULT_REPORT_CYCLES: usize = 10;
 
+/// The default value for `report-responders`.
+pub const DEFAULT_REPORT_RESPONDERS: bool = false;
+
 /// The minimum TUI refresh rate.
 pub const TUI_MIN_REFRESH_RATE_MS: Duration = Duration::from_millis(50);
 
@@ -84,6 +90,3 @@ pub const MIN_PACKET_SIZE_IPV4: u16 = 28;
 
 /// The minimum IPv6 packet size we allow.
 pub const MIN_PACKET_SIZE_IPV6: u16 = 48;
-
-/// The maximum packet size we allow.
-pub const MAX_PACKET_SIZE: u16 = 1024;
@@ -2,7 +2,8 @@ use crate::config::binding::TuiKeyBinding;
 use crate::config::theme::TuiColor;
 use crate::config::{
     AddressFamilyConfig, AddressMode, AsMode, DnsResolveMethodConfig, GeoIpMode, IcmpExtensionMode,
-    LogFormat, LogSpanEvents, Mode, MultipathStrategyConfig, ProtocolConfig,
+    Ipv6ExtensionHeaderConfig, LogFormat, LogSpanEvents, Mode, MultipathStrategyConfig,
+    ProtocolConfig,
 };
 use anyhow::Context;
 use encoding_rs_io::DecodeReaderBytes;
@@ -132,6 +133,7 @@ pub struct ConfigStrategy {
     pub addr_family: Option<AddressFamilyConfig>,
     pub target_port: Option<u16>,
     pub source_port: Option<u16>,
+    pub source_port_range: Option<String>,
     #[serde(default)]
     #[serde(deserialize_with = "addr_deser")]
     pub source_address: Option<IpAddr>,
@@ -143,6 +145,7 @@ pub struct ConfigStrategy {
     #[serde(deserialize_with = "humantime_deser")]
     pub max_round_duration: Option<Duration>,
     pub initial_sequence: Option<u16>,
+    pub max_sequence: Option<u16>,
     pub multipath_strategy: Option<MultipathStrategyConfig>,
     #[serde(default)]
     #[serde(deserialize_with = "humantime_deser")]
@@ -151,12 +154,16 @@ pub struct ConfigStrategy {
     pub first_ttl: Option<u8>,
     pub max_ttl: Option<u8>,
     pub packet_size: Option<u16>,
+    pub max_packet_size: Option<u16>,
     pub payload_pattern: Option<u8>,
     pub tos: Option<u8>,
+    pub dont_fragment: Option<bool>,
     pub icmp_extensions: Option<bool>,
+    pub ipv6_extension_header: Option<Ipv6ExtensionHeaderConfig>,
     #[serde(default)]
     #[serde(deserialize_with = "humantime_deser")]
     pub read_timeout: Option<Duration>,
+    pub capture_file: Option<String>,
     pub max_samples: Option<usize>,
     pub max_flows: Option<usize>,
 }
@@ -168,11 +175,13 @@ impl Default for ConfigStrategy {
             addr_family: Some(super::constants::DEFAULT_ADDR_FAMILY),
             target_port: None,
             source_port: None,
+            source_port_range: None,
             source_address: None,
             interface: None,
             min_round_duration: Some(defaults::DEFAULT_STRATEGY_MIN_ROUND_DURATION),
             max_round_duration: Some(defaults::DEFAULT_STRATEGY_MAX_ROUND_DURATION),
             initial_sequence: Some(defaults::DEFAULT_STRATEGY_INITIAL_SEQUENCE),
+            max_sequence: Some(defaults::DEFAULT_STRATEGY_MAX_SEQUENCE),
             multipath_strategy: Some(MultipathStrategyConfig::from(
                 defaults::DEFAULT_STRATEGY_MULTIPATH,
             )),
@@ -181,10 +190,16 @@ impl Default for ConfigStrategy {
             first_ttl: Some(defaults::DEFAULT_STRATEGY_FIRST_TTL),
             max_ttl: Some(defaults::DEFAULT_STRATEGY_MAX_TTL),
             packet_size: Some(defaults::DEFAULT_STRATEGY_PACKET_SIZE),
+            max_packet_size: Some(defaults::DEFAULT_MAX_PACKET_SIZE),
             payload_pattern: Some(defaults::DEFAULT_STRATEGY_PAYLOAD_PATTERN),
             tos: Some(defaults::DEFAULT_STRATEGY_TOS),
+            dont_fragment: Some(defaults::DEFAULT_STRATEGY_DONT_FRAGMENT),
             icmp_extensions: Some(defaults::DEFAULT_ICMP_EXTENSION_PARSE_MODE.is_enabled()),
+            ipv6_extension_header: Some(Ipv6ExtensionHeaderConfig::from(
+                defaults::DEFAULT_IPV6_EXTENSION_HEADER,
+            )),
             read_timeout: Some(defaults::DEFAULT_STRATEGY_READ_TIMEOUT),
+            capture_file: None,
             max_samples: Some(defaults::DEFAULT_MAX_SAMPLES),
             max_flows: Some(defaults::DEFAULT_MAX_FLOWS),
         }
@@ -201,6 +216,7 @@ pub struct ConfigDns {
     #[serde(default)]
     #[serde(deserialize_with = "humantime_deser")]
     pub dns_timeout: Option<Duration>,
+    pub dns_retain_cache: Option<bool>,
 }
 
 impl Default for ConfigDns {
@@ -210,6 +226,7 @@ impl Default for ConfigDns {
             dns_resolve_all: Some(super::constants::DEFAULT_DNS_RESOLVE_ALL),
             dns_lookup_as_info: Some(super::constants::DEFAULT_DNS_LOOKUP_AS_INFO),
             dns_timeout: Some(super::constants::DEFAULT_DNS_TIMEOUT),
+            dns_retain_cache: Some(super::constants::DEFAULT_DNS_RETAIN_CACHE),
         }
     }
 }
@@ -218,12 +235,14 @@ impl Default for ConfigDns {
 #[serde(rename_all = "kebab-case", deny_unknown_fields)]
 pub struct ConfigReport {
     pub report_cycles: Option<usize>,
+    pub report_responders: Option<bool>,
 }
 
 impl Default for ConfigReport {
     fn default() -> Self {
         Self {
             report_cycles: Some(super::constants::DEFAULT_REPORT_CYCLES),
+            report_responders: Some(super::constants::DEFAULT_REPORT_RESPONDERS),
         }
     }
 }
@@ -364,6 +383,7 @@ pub struct ConfigBindings {
     pub toggle_flows: Option<TuiKeyBinding>,
     pub toggle_privacy: Option<TuiKeyBinding>,
     pub toggle_map: Option<TuiKeyBinding>,
+    pub toggle_responders: Option<TuiKeyBinding>,
     pub expand_hosts: Option<TuiKeyBinding>,
     pub contract_hosts: Option<TuiKeyBinding>,
     pub expand_hosts_max: Option<TuiKeyBinding>,
@@ -399,6 +419,7 @@ impl Default for ConfigBindings {
             toggle_flows: Some(bindings.toggle_flows),
             toggle_privacy: Some(bindings.toggle_privacy),
             toggle_map: Some(bindings.toggle_map),
+            toggle_responders: Some(bindings.toggle_responders),
             expand_hosts: Some(bindings.expand_hosts),
             contract_hosts: Some(bindings.contract_hosts),
             expand_hosts_max: Some(bindings.expand_hosts_max),
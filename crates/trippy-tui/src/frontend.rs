@@ -99,6 +99,7 @@ fn run_app<B: Backend>(
                             app.previous_settings_item();
                         } else if bindings.toggle_chart.check(key) {
                             app.toggle_column_visibility();
+                            app.change_dns_setting();
                         } else if bindings.next_hop_address.check(key) {
                             app.move_column_down();
                         } else if bindings.previous_hop_address.check(key) {
@@ -141,6 +142,8 @@ fn run_app<B: Backend>(
                         app.toggle_chart();
                     } else if bindings.toggle_map.check(key) {
                         app.toggle_map();
+                    } else if bindings.toggle_responders.check(key) {
+                        app.toggle_responders();
                     } else if bindings.toggle_flows.check(key) {
                         app.toggle_flows();
                     } else if bindings.toggle_privacy.check(key) {
@@ -161,7 +164,7 @@ fn run_app<B: Backend>(
                         app.clear();
                         app.clear_trace_data();
                     } else if bindings.clear_dns_cache.check(key) {
-                        app.resolver.flush();
+                        app.flush_dns_cache();
                     } else if bindings.clear_selection.check(key) {
                         app.clear();
                     } else if bindings.toggle_as_info.check(key) {
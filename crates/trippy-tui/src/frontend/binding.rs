@@ -21,6 +21,7 @@ pub struct Bindings {
     pub toggle_freeze: KeyBinding,
     pub toggle_chart: KeyBinding,
     pub toggle_map: KeyBinding,
+    pub toggle_responders: KeyBinding,
     pub toggle_flows: KeyBinding,
     pub toggle_privacy: KeyBinding,
     pub expand_hosts: KeyBinding,
@@ -55,6 +56,7 @@ impl From<TuiBindings> for Bindings {
             toggle_freeze: KeyBinding::from(value.toggle_freeze),
             toggle_chart: KeyBinding::from(value.toggle_chart),
             toggle_map: KeyBinding::from(value.toggle_map),
+            toggle_responders: KeyBinding::from(value.toggle_responders),
             toggle_flows: KeyBinding::from(value.toggle_flows),
             toggle_privacy: KeyBinding::from(value.toggle_privacy),
             expand_hosts: KeyBinding::from(value.expand_hosts),
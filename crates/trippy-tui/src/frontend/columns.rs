@@ -289,7 +289,7 @@ impl ColumnType {
             Self::LastDestPort => ColumnWidth::Fixed(7),
             Self::LastSeq => ColumnWidth::Fixed(7),
             Self::LastIcmpPacketType => ColumnWidth::Fixed(7),
-            Self::LastIcmpPacketCode => ColumnWidth::Fixed(7),
+            Self::LastIcmpPacketCode => ColumnWidth::Fixed(9),
         }
     }
 }
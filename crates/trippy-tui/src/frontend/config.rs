@@ -34,10 +34,12 @@ pub struct TuiConfig {
     pub tui_columns: Columns,
     pub geoip_mmdb_file: Option<String>,
     pub dns_resolve_all: bool,
+    /// Retain the DNS cache when the resolve method is changed.
+    pub dns_retain_cache: bool,
 }
 
 impl TuiConfig {
-    #[allow(clippy::too_many_arguments)]
+    #[allow(clippy::too_many_arguments, clippy::fn_params_excessive_bools)]
     pub fn new(
         refresh_rate: Duration,
         privacy_max_ttl: u8,
@@ -54,6 +56,7 @@ impl TuiConfig {
 
         geoip_mmdb_file: Option<String>,
         dns_resolve_all: bool,
+        dns_retain_cache: bool,
     ) -> Self {
         Self {
             refresh_rate,
@@ -70,6 +73,7 @@ impl TuiConfig {
             tui_columns: Columns::from(tui_columns.clone()),
             geoip_mmdb_file,
             dns_resolve_all,
+            dns_retain_cache,
         }
     }
 }
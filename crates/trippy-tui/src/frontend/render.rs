@@ -8,6 +8,7 @@ pub mod header;
 pub mod help;
 pub mod histogram;
 pub mod history;
+pub mod responders;
 pub mod settings;
 pub mod splash;
 pub mod table;
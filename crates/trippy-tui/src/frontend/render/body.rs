@@ -1,4 +1,4 @@
-use crate::frontend::render::{bsod, chart, splash, table, world};
+use crate::frontend::render::{bsod, chart, responders, splash, table, world};
 use crate::frontend::tui_app::TuiApp;
 use ratatui::layout::Rect;
 use ratatui::Frame;
@@ -6,8 +6,8 @@ use trippy_core::State;
 
 /// Render the body.
 ///
-/// This is either an BSOD if there wa san error or the table of hop data or, if there is no data,
-/// the splash screen.
+/// This is either an BSOD if there wa san error or the table of hop data (or responder data) or, if
+/// there is no data, the splash screen.
 pub fn render(f: &mut Frame<'_>, rec: Rect, app: &mut TuiApp) {
     if let Some(err) = app.selected_tracer_data.error() {
         bsod::render(f, rec, err);
@@ -17,6 +17,8 @@ pub fn render(f: &mut Frame<'_>, rec: Rect, app: &mut TuiApp) {
         chart::render(f, app, rec);
     } else if app.show_map {
         world::render(f, app, rec);
+    } else if app.show_responders {
+        responders::render(f, app, rec);
     } else {
         table::render(f, app, rec);
     }
@@ -25,7 +25,7 @@ pub fn render(f: &mut Frame<'_>, app: &TuiApp) {
     f.render_widget(control, area);
 }
 
-const HELP_LINES: [&str; 22] = [
+const HELP_LINES: [&str; 23] = [
     "[up] & [down]    - select hop",
     "[left] & [right] - select trace or flow",
     ", & .            - select hop address",
@@ -34,6 +34,7 @@ const HELP_LINES: [&str; 22] = [
     "f                - toggle flows",
     "c                - toggle chart",
     "m                - toggle map",
+    "r                - toggle responders",
     "p                - toggle privacy",
     "Ctrl+f           - toggle freeze display",
     "Ctrl+r           - reset statistics",
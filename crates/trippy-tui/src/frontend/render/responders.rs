@@ -0,0 +1,93 @@
+use crate::frontend::render::table;
+use crate::frontend::tui_app::TuiApp;
+use itertools::Itertools;
+use ratatui::layout::{Constraint, Rect};
+use ratatui::style::Style;
+use ratatui::widgets::{Block, BorderType, Borders, Cell, Row, Table};
+use ratatui::Frame;
+use trippy_core::Responder;
+
+/// Render the table of data about the distinct responders.
+///
+/// For each distinct responder address across all hops, we show:
+///
+/// - The host of the responder (`Host`)
+/// - The time-to-live values this responder was observed at and associated counts (`TTLs`)
+/// - The number of replies received from this responder (`Recv`)
+/// - The average round-trip time for all replies from this responder (`Avg`)
+/// - The best round-trip time for all replies from this responder (`Best`)
+/// - The worst round-trip time for all replies from this responder (`Wrst`)
+/// - The standard deviation round-trip time for all replies from this responder (`StDev`)
+pub fn render(f: &mut Frame<'_>, app: &TuiApp, rect: Rect) {
+    let theme = app.tui_config.theme;
+    let header_cells = ["Host", "TTLs", "Recv", "Avg", "Best", "Wrst", "StDev"]
+        .into_iter()
+        .map(|h| Cell::from(h).style(Style::default().fg(theme.hops_table_header_text)));
+    let header = Row::new(header_cells)
+        .style(Style::default().bg(theme.hops_table_header_bg))
+        .height(1)
+        .bottom_margin(0);
+    let rows = app
+        .tracer_data()
+        .responders(app.selected_flow)
+        .map(|responder| render_responder_row(app, responder));
+    let widths = [
+        Constraint::Min(20),
+        Constraint::Length(16),
+        Constraint::Length(6),
+        Constraint::Length(7),
+        Constraint::Length(7),
+        Constraint::Length(7),
+        Constraint::Length(7),
+    ];
+    let table = Table::new(rows, widths)
+        .header(header)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .border_style(Style::default().fg(theme.border))
+                .title("Responders"),
+        )
+        .style(Style::default().bg(theme.bg).fg(theme.text))
+        .column_spacing(1);
+    f.render_widget(table, rect);
+}
+
+/// Render a single row in the table of responders.
+fn render_responder_row(app: &TuiApp, responder: &Responder) -> Row<'static> {
+    let is_private = responder
+        .ttls()
+        .any(|(ttl, _)| ttl <= app.tui_config.privacy_max_ttl);
+    let host = if app.hide_private_hops && is_private {
+        String::from("**Hidden**")
+    } else {
+        table::format_host(&responder.addr(), &app.resolver, &app.tui_config)
+    };
+    let ttls = responder
+        .ttls()
+        .map(|(ttl, count)| format!("{ttl} ({count})"))
+        .join(", ");
+    let stddev = if responder.total_recv() > 1 {
+        format!("{:.1}", responder.stddev_ms())
+    } else {
+        String::default()
+    };
+    let cells = [
+        Cell::from(host),
+        Cell::from(ttls),
+        Cell::from(format!("{}", responder.total_recv())),
+        Cell::from(format!("{:.1}", responder.avg_ms())),
+        Cell::from(format_ms(responder.best_ms())),
+        Cell::from(format_ms(responder.worst_ms())),
+        Cell::from(stddev),
+    ];
+    Row::new(cells)
+        .height(1)
+        .bottom_margin(0)
+        .style(Style::default().fg(app.tui_config.theme.hops_table_row_active_text))
+}
+
+fn format_ms(value: Option<f64>) -> String {
+    value.map(|v| format!("{v:.1}")).unwrap_or_default()
+}
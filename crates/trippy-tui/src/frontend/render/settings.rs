@@ -1,7 +1,7 @@
 use crate::config::{AddressMode, AsMode, GeoIpMode};
 use crate::frontend::render::util;
 use crate::frontend::theme;
-use crate::frontend::tui_app::TuiApp;
+use crate::frontend::tui_app::{Provenance, TuiApp};
 use humantime::format_duration;
 use ratatui::layout::{Alignment, Constraint, Direction, Layout, Rect};
 use ratatui::style::{Modifier, Style};
@@ -10,8 +10,8 @@ use ratatui::widgets::{
     Block, BorderType, Borders, Cell, Clear, Paragraph, Row, Table, Tabs, Wrap,
 };
 use ratatui::Frame;
-use trippy_core::PortDirection;
-use trippy_dns::ResolveMethod;
+use trippy_core::{PayloadPattern, PortDirection};
+use trippy_dns::{IpAddrFamily, ResolveMethod};
 
 /// Render settings dialog.
 pub fn render(f: &mut Frame<'_>, app: &mut TuiApp) {
@@ -144,7 +144,7 @@ fn format_all_settings(app: &TuiApp) -> Vec<(&'static str, String, Vec<SettingsI
         ),
         (
             "Dns",
-            String::from("Settings which control how DNS lookups are performed"),
+            format!("Settings which control how DNS lookups are performed (press {toggle_column} to change the resolve method, toggle AS lookups or flush the cache)"),
             dns_settings,
         ),
         ("GeoIp", String::from("Settings relating to GeoIp"), geoip_settings),
@@ -229,20 +229,39 @@ fn format_trace_settings(app: &TuiApp) -> Vec<SettingsItem> {
             "initial-sequence",
             format!("{}", cfg.data.initial_sequence().0),
         ),
+        SettingsItem::new("max-sequence", format!("{}", cfg.data.max_sequence().0)),
         SettingsItem::new(
             "read-timeout",
             format!("{}", format_duration(cfg.data.read_timeout())),
         ),
         SettingsItem::new("packet-size", format!("{}", cfg.data.packet_size().0)),
+        SettingsItem::new(
+            "max-packet-size",
+            format!("{}", cfg.data.max_packet_size().0),
+        ),
         SettingsItem::new(
             "payload-pattern",
-            format!("{}", cfg.data.payload_pattern().0),
+            format_payload_pattern(cfg.data.payload_pattern()),
         ),
         SettingsItem::new(
             "icmp-extensions",
             format!("{}", cfg.data.icmp_extension_parse_mode()),
         ),
+        SettingsItem::new(
+            "invalid-icmp-checksums",
+            format!("{}", cfg.data.invalid_checksums()),
+        ),
+        SettingsItem::new(
+            "ipv6-extension-header",
+            format!("{}", cfg.data.ipv6_extension_header()),
+        ),
         SettingsItem::new("interface", interface),
+        SettingsItem::new(
+            "capture-file",
+            cfg.data
+                .capture_file()
+                .map_or_else(|| "none".to_string(), |path| path.display().to_string()),
+        ),
         SettingsItem::new(
             "multipath-strategy",
             cfg.data.multipath_strategy().to_string(),
@@ -262,14 +281,20 @@ fn format_trace_settings(app: &TuiApp) -> Vec<SettingsItem> {
 
 /// Format DNS settings.
 fn format_dns_settings(app: &TuiApp) -> Vec<SettingsItem> {
+    let config = app.resolver.config();
+    let stats = app.resolver.cache_stats();
     vec![
         SettingsItem::new(
-            "dns-timeout",
-            format!("{}", format_duration(app.resolver.config().timeout)),
+            "dns-resolve-method",
+            format_provenance(
+                format_dns_method(config.resolve_method),
+                app.dns_resolve_method_provenance,
+            ),
         ),
+        SettingsItem::new("dns-addr-family", format_dns_family(config.addr_family)),
         SettingsItem::new(
-            "dns-resolve-method",
-            format_dns_method(app.resolver.config().resolve_method),
+            "dns-timeout",
+            format!("{}", format_duration(config.timeout)),
         ),
         SettingsItem::new(
             "dns-resolve-all",
@@ -277,8 +302,20 @@ fn format_dns_settings(app: &TuiApp) -> Vec<SettingsItem> {
         ),
         SettingsItem::new(
             "dns-lookup-as-info",
-            format!("{}", app.tui_config.lookup_as_info),
+            format_provenance(
+                format!("{}", app.tui_config.lookup_as_info),
+                app.dns_lookup_as_info_provenance,
+            ),
         ),
+        SettingsItem::new(
+            "dns-retain-cache",
+            format!("{}", app.tui_config.dns_retain_cache),
+        ),
+        SettingsItem::new(
+            "dns-cache",
+            format!("{} entries ({} pending)", stats.len(), stats.pending),
+        ),
+        SettingsItem::new("dns-health", format!("{}", stats.health())),
     ]
 }
 
@@ -316,6 +353,7 @@ fn format_binding_settings(app: &TuiApp) -> Vec<SettingsItem> {
         SettingsItem::new("toggle-freeze", format!("{}", binds.toggle_freeze)),
         SettingsItem::new("toggle-chart", format!("{}", binds.toggle_chart)),
         SettingsItem::new("toggle-map", format!("{}", binds.toggle_map)),
+        SettingsItem::new("toggle-responders", format!("{}", binds.toggle_responders)),
         SettingsItem::new("toggle-flows", format!("{}", binds.toggle_flows)),
         SettingsItem::new("toggle-privacy", format!("{}", binds.toggle_privacy)),
         SettingsItem::new("expand-hosts", format!("{}", binds.expand_hosts)),
@@ -459,15 +497,21 @@ fn format_columns_settings(app: &TuiApp) -> Vec<SettingsItem> {
         .collect()
 }
 
+pub const SETTINGS_TAB_DNS: usize = 2;
 pub const SETTINGS_TAB_COLUMNS: usize = 6;
 
+/// The index of the DNS settings which can be changed at runtime.
+pub const SETTINGS_DNS_RESOLVE_METHOD: usize = 0;
+pub const SETTINGS_DNS_LOOKUP_AS_INFO: usize = 4;
+pub const SETTINGS_DNS_CACHE: usize = 6;
+
 /// The name and number of items for each tabs in the setting dialog.
 pub const SETTINGS_TABS: [(&str, usize); 7] = [
     ("Tui", 8),
-    ("Trace", 17),
-    ("Dns", 4),
+    ("Trace", 22),
+    ("Dns", 8),
     ("GeoIp", 1),
-    ("Bindings", 29),
+    ("Bindings", 30),
     ("Theme", 31),
     ("Columns", 0),
 ];
@@ -505,6 +549,24 @@ fn format_dns_method(resolve_method: ResolveMethod) -> String {
     }
 }
 
+/// Format the `IpAddrFamily`.
+fn format_dns_family(addr_family: IpAddrFamily) -> String {
+    match addr_family {
+        IpAddrFamily::Ipv4Only => String::from("ipv4"),
+        IpAddrFamily::Ipv6Only => String::from("ipv6"),
+        IpAddrFamily::Ipv6thenIpv4 => String::from("ipv6-then-ipv4"),
+        IpAddrFamily::Ipv4thenIpv6 => String::from("ipv4-then-ipv6"),
+    }
+}
+
+/// Format a setting value along with where it was set, if changed at runtime.
+fn format_provenance(value: String, provenance: Provenance) -> String {
+    match provenance {
+        Provenance::Config => value,
+        Provenance::Runtime => format!("{value} (runtime)"),
+    }
+}
+
 /// Format the `AsMode`.
 fn format_as_mode(as_mode: AsMode) -> String {
     match as_mode {
@@ -535,3 +597,80 @@ fn format_geoip_mode(geoip_mode: GeoIpMode) -> String {
         GeoIpMode::Location => "location".to_string(),
     }
 }
+
+/// Format a payload pattern, as a decimal byte or as hex bytes.
+fn format_payload_pattern(payload_pattern: &PayloadPattern) -> String {
+    match payload_pattern.0.as_slice() {
+        [byte] => format!("{byte}"),
+        bytes => {
+            let hex = bytes
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect::<Vec<_>>();
+            format!("0x{}", hex.join(""))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontend::tui_app::tests::make_app;
+
+    fn items(settings: &[SettingsItem]) -> Vec<(&str, &str)> {
+        settings
+            .iter()
+            .map(|item| (item.item.as_str(), item.value.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn test_format_dns_settings() {
+        let app = make_app(ResolveMethod::Google, false, true);
+        let settings = format_dns_settings(&app);
+        assert_eq!(SETTINGS_TABS[SETTINGS_TAB_DNS].1, settings.len());
+        assert_eq!(
+            vec![
+                ("dns-resolve-method", "google"),
+                ("dns-addr-family", "ipv4-then-ipv6"),
+                ("dns-timeout", "5s"),
+                ("dns-resolve-all", "false"),
+                ("dns-lookup-as-info", "false"),
+                ("dns-retain-cache", "true"),
+                ("dns-cache", "0 entries (0 pending)"),
+                ("dns-health", "healthy"),
+            ],
+            items(&settings)
+        );
+    }
+
+    #[test]
+    fn test_format_dns_settings_runtime_provenance() {
+        let mut app = make_app(ResolveMethod::Google, false, true);
+        app.toggle_asinfo();
+        app.next_dns_resolve_method();
+        let settings = format_dns_settings(&app);
+        let items = items(&settings);
+        assert_eq!(
+            ("dns-resolve-method", "cloudflare (runtime)"),
+            items[SETTINGS_DNS_RESOLVE_METHOD]
+        );
+        assert_eq!(
+            ("dns-lookup-as-info", "true (runtime)"),
+            items[SETTINGS_DNS_LOOKUP_AS_INFO]
+        );
+        assert_eq!(
+            ("dns-cache", "0 entries (0 pending)"),
+            items[SETTINGS_DNS_CACHE]
+        );
+    }
+
+    #[test]
+    fn test_format_payload_pattern() {
+        assert_eq!("255", format_payload_pattern(&PayloadPattern::byte(0xff)));
+        assert_eq!(
+            "0xdead0f",
+            format_payload_pattern(&PayloadPattern(vec![0xde, 0xad, 0x0f]))
+        );
+    }
+}
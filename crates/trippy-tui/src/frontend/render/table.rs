@@ -162,7 +162,10 @@ fn new_cell(
         ColumnType::LastDestPort => render_port_cell(hop.last_dest_port()),
         ColumnType::LastSeq => render_usize_cell(usize::from(hop.last_sequence())),
         ColumnType::LastIcmpPacketType => render_icmp_packet_type_cell(hop.last_icmp_packet_type()),
-        ColumnType::LastIcmpPacketCode => render_icmp_packet_code_cell(hop.last_icmp_packet_type()),
+        ColumnType::LastIcmpPacketCode => render_icmp_packet_code_cell(
+            hop.last_icmp_packet_type(),
+            app.tracer_config().data.target_addr(),
+        ),
     }
 }
 
@@ -215,17 +218,30 @@ fn render_icmp_packet_type_cell(icmp_packet_type: Option<IcmpPacketType>) -> Cel
         Some(IcmpPacketType::TimeExceeded(_)) => Cell::from("TE"),
         Some(IcmpPacketType::EchoReply(_)) => Cell::from("ER"),
         Some(IcmpPacketType::Unreachable(_)) => Cell::from("DU"),
+        Some(IcmpPacketType::PacketTooBig) => Cell::from("PTB"),
+        Some(IcmpPacketType::ParameterProblem(..)) => Cell::from("PP"),
         Some(IcmpPacketType::NotApplicable) => Cell::from("NA"),
+        Some(IcmpPacketType::Refused) => Cell::from("RF"),
     }
 }
 
-fn render_icmp_packet_code_cell(icmp_packet_type: Option<IcmpPacketType>) -> Cell<'static> {
+/// Render the ICMP code, with a short label describing the code of an `Unreachable` packet if
+/// known, or the pointer of a `ParameterProblem` packet.
+fn render_icmp_packet_code_cell(
+    icmp_packet_type: Option<IcmpPacketType>,
+    target_addr: IpAddr,
+) -> Cell<'static> {
     match icmp_packet_type {
-        Some(
-            IcmpPacketType::Unreachable(code)
-            | IcmpPacketType::TimeExceeded(code)
-            | IcmpPacketType::EchoReply(code),
-        ) => Cell::from(format!("{}", code.0)),
+        Some(IcmpPacketType::Unreachable(code)) => match code.unreachable_label(target_addr) {
+            Some(label) => Cell::from(format!("{} {label}", code.0)),
+            None => Cell::from(format!("{}", code.0)),
+        },
+        Some(IcmpPacketType::ParameterProblem(code, pointer)) => {
+            Cell::from(format!("{} pointer={pointer}", code.0))
+        }
+        Some(IcmpPacketType::TimeExceeded(code) | IcmpPacketType::EchoReply(code)) => {
+            Cell::from(format!("{}", code.0))
+        }
         _ => Cell::from("n/a"),
     }
 }
@@ -290,28 +306,7 @@ fn format_address(
     geoip_lookup: &GeoIpLookup,
     config: &TuiConfig,
 ) -> String {
-    let addr_fmt = match config.address_mode {
-        AddressMode::IP => addr.to_string(),
-        AddressMode::Host => {
-            if config.lookup_as_info {
-                let entry = dns.lazy_reverse_lookup_with_asinfo(*addr);
-                format_dns_entry(entry, true, config.as_mode)
-            } else {
-                let entry = dns.lazy_reverse_lookup(*addr);
-                format_dns_entry(entry, false, config.as_mode)
-            }
-        }
-        AddressMode::Both => {
-            let hostname = if config.lookup_as_info {
-                let entry = dns.lazy_reverse_lookup_with_asinfo(*addr);
-                format_dns_entry(entry, true, config.as_mode)
-            } else {
-                let entry = dns.lazy_reverse_lookup(*addr);
-                format_dns_entry(entry, false, config.as_mode)
-            };
-            format!("{hostname} ({addr})")
-        }
-    };
+    let addr_fmt = format_host(addr, dns, config);
     let exp_fmt = format_extensions(config, hop);
     let geo_fmt = match config.geoip_mode {
         GeoIpMode::Off => None,
@@ -349,6 +344,32 @@ fn format_address(
     address
 }
 
+/// Perform a reverse DNS lookup for an address and format it for the configured address mode.
+pub fn format_host(addr: &IpAddr, dns: &DnsResolver, config: &TuiConfig) -> String {
+    match config.address_mode {
+        AddressMode::IP => addr.to_string(),
+        AddressMode::Host => {
+            if config.lookup_as_info {
+                let entry = dns.lazy_reverse_lookup_with_asinfo(*addr);
+                format_dns_entry(entry, true, config.as_mode)
+            } else {
+                let entry = dns.lazy_reverse_lookup(*addr);
+                format_dns_entry(entry, false, config.as_mode)
+            }
+        }
+        AddressMode::Both => {
+            let hostname = if config.lookup_as_info {
+                let entry = dns.lazy_reverse_lookup_with_asinfo(*addr);
+                format_dns_entry(entry, true, config.as_mode)
+            } else {
+                let entry = dns.lazy_reverse_lookup(*addr);
+                format_dns_entry(entry, false, config.as_mode)
+            };
+            format!("{hostname} ({addr})")
+        }
+    }
+}
+
 /// Format a `DnsEntry` with or without `AS` information (if available)
 fn format_dns_entry(dns_entry: DnsEntry, lookup_as_info: bool, as_mode: AsMode) -> String {
     match dns_entry {
@@ -368,8 +389,10 @@ fn format_dns_entry(dns_entry: DnsEntry, lookup_as_info: bool, as_mode: AsMode)
                 format!("{ip}")
             }
         }
-        DnsEntry::Failed(ip) => format!("Failed: {ip}"),
+        DnsEntry::Failed(ip, None) => format!("Failed: {ip}"),
+        DnsEntry::Failed(ip, Some(rcode)) => format!("Failed ({rcode}): {ip}"),
         DnsEntry::Timeout(ip) => format!("Timeout: {ip}"),
+        DnsEntry::Bogus(ip) => format!("Bogus: {ip}"),
     }
 }
 
@@ -498,7 +521,7 @@ fn render_hostname_with_details(
     } else {
         String::from("No response")
     };
-    (Cell::from(rendered), 7)
+    (Cell::from(rendered), 8)
 }
 
 /// Format hop details.
@@ -521,9 +544,10 @@ fn format_details(
         dns.lazy_reverse_lookup(*addr)
     };
     let ext = hop.extensions();
+    let mtu = hop.last_next_hop_mtu();
     match dns_entry {
         DnsEntry::Pending(addr) => {
-            fmt_details_line(addr, index, count, None, None, geoip, ext, config)
+            fmt_details_line(addr, index, count, None, None, geoip, ext, mtu, config)
         }
         DnsEntry::Resolved(Resolved::WithAsInfo(addr, hosts, asinfo)) => fmt_details_line(
             addr,
@@ -533,6 +557,7 @@ fn format_details(
             Some(asinfo),
             geoip,
             ext,
+            mtu,
             config,
         ),
         DnsEntry::NotFound(Unresolved::WithAsInfo(addr, asinfo)) => fmt_details_line(
@@ -543,20 +568,43 @@ fn format_details(
             Some(asinfo),
             geoip,
             ext,
+            mtu,
             config,
         ),
-        DnsEntry::Resolved(Resolved::Normal(addr, hosts)) => {
-            fmt_details_line(addr, index, count, Some(hosts), None, geoip, ext, config)
-        }
-        DnsEntry::NotFound(Unresolved::Normal(addr)) => {
-            fmt_details_line(addr, index, count, Some(vec![]), None, geoip, ext, config)
-        }
-        DnsEntry::Failed(ip) => {
+        DnsEntry::Resolved(Resolved::Normal(addr, hosts)) => fmt_details_line(
+            addr,
+            index,
+            count,
+            Some(hosts),
+            None,
+            geoip,
+            ext,
+            mtu,
+            config,
+        ),
+        DnsEntry::NotFound(Unresolved::Normal(addr)) => fmt_details_line(
+            addr,
+            index,
+            count,
+            Some(vec![]),
+            None,
+            geoip,
+            ext,
+            mtu,
+            config,
+        ),
+        DnsEntry::Failed(ip, None) => {
             format!("Failed: {ip}")
         }
+        DnsEntry::Failed(ip, Some(rcode)) => {
+            format!("Failed ({rcode}): {ip}")
+        }
         DnsEntry::Timeout(ip) => {
             format!("Timeout: {ip}")
         }
+        DnsEntry::Bogus(ip) => {
+            format!("Bogus: {ip}")
+        }
     }
 }
 
@@ -572,6 +620,7 @@ fn format_details(
 /// Geo: United States, North America
 /// Pos: 37.751, -97.822 (~1000km)
 /// Ext: [mpls(label=48268, ttl=1, exp=0, bos=1)]
+/// MTU: 1400
 /// ```
 #[allow(clippy::too_many_arguments)]
 fn fmt_details_line(
@@ -582,6 +631,7 @@ fn fmt_details_line(
     asinfo: Option<AsInfo>,
     geoip: Option<Rc<GeoIpCity>>,
     extensions: Option<&Extensions>,
+    mtu: Option<u16>,
     config: &TuiConfig,
 ) -> String {
     let as_formatted = match (config.lookup_as_info, asinfo) {
@@ -621,5 +671,10 @@ fn fmt_details_line(
     } else {
         "Ext: <none>".to_string()
     };
-    format!("{addr} [{index} of {count}]\n{hosts_rendered}\n{as_formatted}\n{geoip_formatted}\n{ext_formatted}")
+    let mtu_formatted = if let Some(mtu) = mtu {
+        format!("MTU: {mtu}")
+    } else {
+        "MTU: <none>".to_string()
+    };
+    format!("{addr} [{index} of {count}]\n{hosts_rendered}\n{as_formatted}\n{geoip_formatted}\n{ext_formatted}\n{mtu_formatted}")
 }
@@ -1,6 +1,9 @@
 use crate::app::TraceInfo;
 use crate::frontend::config::TuiConfig;
-use crate::frontend::render::settings::{SETTINGS_TABS, SETTINGS_TAB_COLUMNS};
+use crate::frontend::render::settings::{
+    SETTINGS_DNS_CACHE, SETTINGS_DNS_LOOKUP_AS_INFO, SETTINGS_DNS_RESOLVE_METHOD, SETTINGS_TABS,
+    SETTINGS_TAB_COLUMNS, SETTINGS_TAB_DNS,
+};
 use crate::geoip::GeoIpLookup;
 use itertools::Itertools;
 use ratatui::widgets::TableState;
@@ -8,7 +11,27 @@ use std::time::SystemTime;
 use trippy_core::FlowId;
 use trippy_core::Hop;
 use trippy_core::State;
-use trippy_dns::{DnsResolver, ResolveMethod};
+use trippy_dns::{CacheRetention, Config, DnsResolver, ResolveMethod};
+
+/// Where the effective value of a setting came from.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Provenance {
+    /// Set from the command line, the configuration file or the defaults.
+    Config,
+    /// Changed at runtime.
+    Runtime,
+}
+
+/// A runtime change to the DNS settings.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum DnsEvent {
+    /// The cache was flushed.
+    CacheFlushed,
+    /// `AS` lookups were enabled or disabled.
+    LookupAsInfo(bool),
+    /// The resolve method was changed.
+    ResolveMethod(ResolveMethod),
+}
 
 pub struct TuiApp {
     pub selected_tracer_data: State,
@@ -33,6 +56,12 @@ pub struct TuiApp {
     /// Ordered flow ids with counts.
     pub flow_counts: Vec<(FlowId, usize)>,
     pub resolver: DnsResolver,
+    /// Where the DNS resolve method was set.
+    pub dns_resolve_method_provenance: Provenance,
+    /// Where the DNS `AS` lookup setting was set.
+    pub dns_lookup_as_info_provenance: Provenance,
+    /// The runtime changes made to the DNS settings, oldest first.
+    pub dns_events: Vec<DnsEvent>,
     pub geoip_lookup: GeoIpLookup,
     pub show_help: bool,
     pub show_settings: bool,
@@ -42,6 +71,8 @@ pub struct TuiApp {
     pub hide_private_hops: bool,
     pub show_chart: bool,
     pub show_map: bool,
+    /// Whether the responder table should be shown instead of the hop table.
+    pub show_responders: bool,
     pub frozen_start: Option<SystemTime>,
     pub zoom_factor: usize,
 }
@@ -65,6 +96,9 @@ impl TuiApp {
             selected_flow: State::default_flow_id(),
             flow_counts: vec![],
             resolver,
+            dns_resolve_method_provenance: Provenance::Config,
+            dns_lookup_as_info_provenance: Provenance::Config,
+            dns_events: vec![],
             geoip_lookup,
             show_help: false,
             show_settings: false,
@@ -73,6 +107,7 @@ impl TuiApp {
             hide_private_hops: true,
             show_chart: false,
             show_map: false,
+            show_responders: false,
             frozen_start: None,
             zoom_factor: 1,
         }
@@ -298,6 +333,18 @@ impl TuiApp {
         }
     }
 
+    /// Change the selected setting in the DNS settings tab.
+    pub fn change_dns_setting(&mut self) {
+        if self.settings_tab_selected == SETTINGS_TAB_DNS {
+            match self.setting_table_state.selected() {
+                Some(SETTINGS_DNS_RESOLVE_METHOD) => self.next_dns_resolve_method(),
+                Some(SETTINGS_DNS_LOOKUP_AS_INFO) => self.toggle_asinfo(),
+                Some(SETTINGS_DNS_CACHE) => self.flush_dns_cache(),
+                _ => {}
+            }
+        }
+    }
+
     pub fn move_column_down(&mut self) {
         if self.settings_tab_selected == SETTINGS_TAB_COLUMNS {
             let count = self.tui_config.tui_columns.all_columns_count();
@@ -353,11 +400,19 @@ impl TuiApp {
     pub fn toggle_chart(&mut self) {
         self.show_chart = !self.show_chart;
         self.show_map = false;
+        self.show_responders = false;
     }
 
     pub fn toggle_map(&mut self) {
         self.show_map = !self.show_map;
         self.show_chart = false;
+        self.show_responders = false;
+    }
+
+    pub fn toggle_responders(&mut self) {
+        self.show_responders = !self.show_responders;
+        self.show_chart = false;
+        self.show_map = false;
     }
 
     pub fn toggle_flows(&mut self) {
@@ -382,12 +437,61 @@ impl TuiApp {
         match self.resolver.config().resolve_method {
             ResolveMethod::Resolv | ResolveMethod::Google | ResolveMethod::Cloudflare => {
                 self.tui_config.lookup_as_info = !self.tui_config.lookup_as_info;
+                self.dns_lookup_as_info_provenance = Provenance::Runtime;
                 self.resolver.flush();
+                self.dns_event(DnsEvent::LookupAsInfo(self.tui_config.lookup_as_info));
             }
             ResolveMethod::System => {}
         }
     }
 
+    pub fn flush_dns_cache(&mut self) {
+        self.resolver.flush();
+        self.dns_event(DnsEvent::CacheFlushed);
+    }
+
+    /// Switch the resolver to the next DNS resolve method.
+    ///
+    /// `AS` lookups are not supported by the `System` resolve method and so are disabled when
+    /// switching to it.  If the resolver cannot be rebuilt then the current method is retained.
+    pub fn next_dns_resolve_method(&mut self) {
+        let config = self.resolver.config();
+        let resolve_method = match config.resolve_method {
+            ResolveMethod::System => ResolveMethod::Resolv,
+            ResolveMethod::Resolv => ResolveMethod::Google,
+            ResolveMethod::Google => ResolveMethod::Cloudflare,
+            ResolveMethod::Cloudflare => ResolveMethod::System,
+        };
+        let retention = if self.tui_config.dns_retain_cache {
+            CacheRetention::Retain
+        } else {
+            CacheRetention::Clear
+        };
+        let config = Config {
+            resolve_method,
+            ..config
+        };
+        match self.resolver.reconfigure(config, retention) {
+            Ok(()) => {
+                self.dns_resolve_method_provenance = Provenance::Runtime;
+                self.dns_event(DnsEvent::ResolveMethod(resolve_method));
+                if resolve_method == ResolveMethod::System && self.tui_config.lookup_as_info {
+                    self.tui_config.lookup_as_info = false;
+                    self.dns_lookup_as_info_provenance = Provenance::Runtime;
+                    self.dns_event(DnsEvent::LookupAsInfo(false));
+                }
+            }
+            Err(err) => {
+                tracing::warn!(?resolve_method, ?err, "failed to change DNS resolve method");
+            }
+        }
+    }
+
+    fn dns_event(&mut self, event: DnsEvent) {
+        tracing::info!(?event, "DNS settings changed");
+        self.dns_events.push(event);
+    }
+
     pub fn expand_hosts(&mut self) {
         self.tui_config.max_addrs = match self.tui_config.max_addrs {
             None => Some(1),
@@ -435,3 +539,142 @@ impl TuiApp {
 }
 
 const MAX_ZOOM_FACTOR: usize = 16;
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::config::{
+        AddressMode, AsMode, GeoIpMode, IcmpExtensionMode, TuiBindings, TuiColumns, TuiTheme,
+    };
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::time::Duration;
+    use trippy_dns::{IpAddrFamily, Resolver};
+
+    /// Make a `TuiApp` with no traces.
+    pub fn make_app(
+        resolve_method: ResolveMethod,
+        lookup_as_info: bool,
+        dns_retain_cache: bool,
+    ) -> TuiApp {
+        let tui_config = TuiConfig::new(
+            Duration::from_millis(100),
+            0,
+            false,
+            AddressMode::Host,
+            lookup_as_info,
+            AsMode::Asn,
+            IcmpExtensionMode::Off,
+            GeoIpMode::Off,
+            None,
+            TuiTheme::default(),
+            &TuiBindings::default(),
+            &TuiColumns::default(),
+            None,
+            false,
+            dns_retain_cache,
+        );
+        let resolver = DnsResolver::start(Config::new(
+            resolve_method,
+            IpAddrFamily::Ipv4thenIpv6,
+            Duration::from_secs(5),
+        ))
+        .unwrap();
+        TuiApp::new(tui_config, resolver, GeoIpLookup::empty(), vec![])
+    }
+
+    const ADDR: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+
+    #[test]
+    fn test_next_dns_resolve_method_retain_cache() {
+        let mut app = make_app(ResolveMethod::Google, true, true);
+        let _ = app.resolver.lazy_reverse_lookup(ADDR);
+        app.next_dns_resolve_method();
+        assert_eq!(
+            ResolveMethod::Cloudflare,
+            app.resolver.config().resolve_method
+        );
+        assert_eq!(Provenance::Runtime, app.dns_resolve_method_provenance);
+        assert_eq!(Provenance::Config, app.dns_lookup_as_info_provenance);
+        assert_eq!(
+            vec![DnsEvent::ResolveMethod(ResolveMethod::Cloudflare)],
+            app.dns_events
+        );
+        // lookups which were in-flight, or which failed, with the old resolver are discarded.
+        let stats = app.resolver.cache_stats();
+        assert_eq!(0, stats.pending + stats.failed + stats.timeout);
+        assert!(app.tui_config.lookup_as_info);
+    }
+
+    #[test]
+    fn test_next_dns_resolve_method_clear_cache() {
+        let mut app = make_app(ResolveMethod::Google, false, false);
+        let _ = app.resolver.lazy_reverse_lookup(ADDR);
+        app.next_dns_resolve_method();
+        assert_eq!(
+            ResolveMethod::Cloudflare,
+            app.resolver.config().resolve_method
+        );
+        assert!(app.resolver.cache_stats().is_empty());
+    }
+
+    #[test]
+    fn test_next_dns_resolve_method_to_system_disables_asinfo() {
+        let mut app = make_app(ResolveMethod::Cloudflare, true, true);
+        app.next_dns_resolve_method();
+        assert_eq!(ResolveMethod::System, app.resolver.config().resolve_method);
+        assert!(!app.tui_config.lookup_as_info);
+        assert_eq!(Provenance::Runtime, app.dns_lookup_as_info_provenance);
+        assert_eq!(
+            vec![
+                DnsEvent::ResolveMethod(ResolveMethod::System),
+                DnsEvent::LookupAsInfo(false)
+            ],
+            app.dns_events
+        );
+    }
+
+    #[test]
+    fn test_toggle_asinfo_system_unchanged() {
+        let mut app = make_app(ResolveMethod::System, false, true);
+        app.toggle_asinfo();
+        assert!(!app.tui_config.lookup_as_info);
+        assert_eq!(Provenance::Config, app.dns_lookup_as_info_provenance);
+        assert!(app.dns_events.is_empty());
+    }
+
+    #[test]
+    fn test_change_dns_setting() {
+        let mut app = make_app(ResolveMethod::Google, false, true);
+        app.settings_tab_selected = SETTINGS_TAB_DNS;
+        app.setting_table_state.select(Some(1));
+        app.change_dns_setting();
+        assert!(app.dns_events.is_empty());
+        app.setting_table_state
+            .select(Some(SETTINGS_DNS_LOOKUP_AS_INFO));
+        app.change_dns_setting();
+        app.setting_table_state.select(Some(SETTINGS_DNS_CACHE));
+        app.change_dns_setting();
+        app.setting_table_state
+            .select(Some(SETTINGS_DNS_RESOLVE_METHOD));
+        app.change_dns_setting();
+        assert_eq!(
+            vec![
+                DnsEvent::LookupAsInfo(true),
+                DnsEvent::CacheFlushed,
+                DnsEvent::ResolveMethod(ResolveMethod::Cloudflare)
+            ],
+            app.dns_events
+        );
+    }
+
+    #[test]
+    fn test_change_dns_setting_other_tab() {
+        let mut app = make_app(ResolveMethod::Google, false, true);
+        app.settings_tab_selected = SETTINGS_TAB_COLUMNS;
+        app.setting_table_state
+            .select(Some(SETTINGS_DNS_RESOLVE_METHOD));
+        app.change_dns_setting();
+        assert_eq!(ResolveMethod::Google, app.resolver.config().resolve_method);
+        assert!(app.dns_events.is_empty());
+    }
+}
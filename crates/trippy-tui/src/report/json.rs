@@ -1,12 +1,16 @@
 use crate::app::TraceInfo;
-use crate::report::types::{Hop, Host, Info, Report};
+use crate::report::types::{Hop, Host, Info, Report, Responder};
 use trippy_core::State;
 use trippy_dns::Resolver;
 
 /// Generate a json report of trace data.
+///
+/// If `report_responders` is set then the report includes the statistics for each distinct
+/// responder across all hops.
 pub fn report<R: Resolver>(
     info: &TraceInfo,
     report_cycles: usize,
+    report_responders: bool,
     resolver: &R,
 ) -> anyhow::Result<()> {
     let trace = super::wait_for_round(&info.data, report_cycles)?;
@@ -15,6 +19,12 @@ pub fn report<R: Resolver>(
         .iter()
         .map(|hop| Hop::from((hop, resolver)))
         .collect();
+    let responders = report_responders.then(|| {
+        trace
+            .responders(State::default_flow_id())
+            .map(|responder| Responder::from((responder, resolver)))
+            .collect()
+    });
     let report = Report {
         info: Info {
             target: Host {
@@ -23,6 +33,7 @@ pub fn report<R: Resolver>(
             },
         },
         hops,
+        responders,
     };
     Ok(serde_json::to_writer_pretty(std::io::stdout(), &report)?)
 }
@@ -2,12 +2,15 @@ use itertools::Itertools;
 use serde::{Serialize, Serializer};
 use std::fmt::{Display, Formatter};
 use std::net::IpAddr;
-use trippy_dns::Resolver;
+use trippy_core::IcmpPacketType;
+use trippy_dns::{DnsEntry, Resolved, Resolver, Unresolved};
 
 #[derive(Serialize)]
 pub struct Report {
     pub info: Info,
     pub hops: Vec<Hop>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub responders: Option<Vec<Responder>>,
 }
 
 #[derive(Serialize)]
@@ -42,12 +45,37 @@ pub struct Hop {
     pub jmax: f64,
     #[serde(serialize_with = "fixed_width")]
     pub jinta: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unreachable: Option<Unreachable>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub packet_too_big: Option<PacketTooBig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parameter_problem: Option<ParameterProblem>,
 }
 
 impl<R: Resolver> From<(&trippy_core::Hop, &R)> for Hop {
     fn from((value, resolver): (&trippy_core::Hop, &R)) -> Self {
         let hosts = Hosts::from((value.addrs(), resolver));
         let extensions = value.extensions().map(Extensions::from).unwrap_or_default();
+        let unreachable = match (value.last_icmp_packet_type(), value.addrs().next()) {
+            (Some(IcmpPacketType::Unreachable(code)), Some(addr)) => Some(Unreachable {
+                code: code.0,
+                label: code.unreachable_label(*addr),
+                next_hop_mtu: value.last_next_hop_mtu(),
+            }),
+            _ => None,
+        };
+        let packet_too_big = match (value.last_icmp_packet_type(), value.last_next_hop_mtu()) {
+            (Some(IcmpPacketType::PacketTooBig), Some(mtu)) => Some(PacketTooBig { mtu }),
+            _ => None,
+        };
+        let parameter_problem = match value.last_icmp_packet_type() {
+            Some(IcmpPacketType::ParameterProblem(code, pointer)) => Some(ParameterProblem {
+                code: code.0,
+                pointer,
+            }),
+            _ => None,
+        };
         Self {
             ttl: value.ttl(),
             hosts,
@@ -64,10 +92,87 @@ impl<R: Resolver> From<(&trippy_core::Hop, &R)> for Hop {
             javg: value.javg_ms(),
             jmax: value.jmax_ms().unwrap_or_default(),
             jinta: value.jinta(),
+            unreachable,
+            packet_too_big,
+            parameter_problem,
         }
     }
 }
 
+/// The ICMP `Unreachable` packet received for the last probe for a hop.
+#[derive(Serialize)]
+pub struct Unreachable {
+    pub code: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_hop_mtu: Option<u16>,
+}
+
+/// The `ICMPv6` `PacketTooBig` packet received for the last probe for a hop.
+#[derive(Serialize)]
+pub struct PacketTooBig {
+    pub mtu: u16,
+}
+
+/// The ICMP `ParameterProblem` packet received for the last probe for a hop.
+#[derive(Serialize)]
+pub struct ParameterProblem {
+    pub code: u8,
+    pub pointer: u32,
+}
+
+#[derive(Serialize)]
+pub struct Responder {
+    pub ip: IpAddr,
+    pub hostname: String,
+    pub asn: Option<String>,
+    pub ttls: Vec<ResponderTtl>,
+    pub recv: usize,
+    #[serde(serialize_with = "fixed_width")]
+    pub avg: f64,
+    #[serde(serialize_with = "fixed_width")]
+    pub best: f64,
+    #[serde(serialize_with = "fixed_width")]
+    pub worst: f64,
+    #[serde(serialize_with = "fixed_width")]
+    pub stddev: f64,
+}
+
+impl<R: Resolver> From<(&trippy_core::Responder, &R)> for Responder {
+    fn from((value, resolver): (&trippy_core::Responder, &R)) -> Self {
+        let (hostname, asn) = match resolver.reverse_lookup_with_asinfo(value.addr()) {
+            DnsEntry::Resolved(Resolved::WithAsInfo(_, hosts, asinfo)) => {
+                (hosts.join(" "), Some(asinfo.asn))
+            }
+            DnsEntry::NotFound(Unresolved::WithAsInfo(ip, asinfo)) => {
+                (ip.to_string(), Some(asinfo.asn))
+            }
+            entry => (entry.to_string(), None),
+        };
+        Self {
+            ip: value.addr(),
+            hostname,
+            asn: asn.filter(|asn| !asn.is_empty()),
+            ttls: value
+                .ttls()
+                .map(|(ttl, count)| ResponderTtl { ttl, count })
+                .collect(),
+            recv: value.total_recv(),
+            avg: value.avg_ms(),
+            best: value.best_ms().unwrap_or_default(),
+            worst: value.worst_ms().unwrap_or_default(),
+            stddev: value.stddev_ms(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ResponderTtl {
+    pub ttl: u8,
+    pub count: usize,
+}
+
 #[derive(Serialize)]
 pub struct Hosts(pub Vec<Host>);
 